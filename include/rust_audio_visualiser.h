@@ -0,0 +1,30 @@
+/* C ABI of rust_audio_visualiser's analysis, built with the `ffi` feature.
+ * See src/ffi.rs for how to build the shared library. */
+#ifndef RUST_AUDIO_VISUALISER_H
+#define RUST_AUDIO_VISUALISER_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Analyzer Analyzer;
+
+/* NULL when any argument is zero or bar_count is over 512. */
+Analyzer *create_analyzer(uint32_t sample_rate, uint16_t channels, size_t bar_count);
+
+/* Feeds interleaved samples; returns how many analysis frames they completed. */
+size_t feed_samples(Analyzer *analyzer, const float *samples, size_t len);
+
+/* Copies up to len bar levels (0.0 to 1.0) into out; returns how many. */
+size_t get_bars(const Analyzer *analyzer, float *out, size_t len);
+
+void destroy_analyzer(Analyzer *analyzer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
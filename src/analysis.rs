@@ -0,0 +1,562 @@
+use rayon::prelude::*;
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::VecDeque,
+  fmt,
+  ops::RangeInclusive,
+  sync::{Arc, Mutex, OnceLock, mpsc::Receiver},
+  thread,
+  time::{Duration, Instant},
+};
+
+use crate::{
+  components::{
+    beat::BeatDetector, channels::ChannelMode, histogram::AmplitudeHistogram,
+    loudness::LoudnessMeter, phase, weighting::Weighting, window_fn::WindowFunction,
+  },
+  shm::ShmOutput,
+};
+
+pub const BUFFER_SIZE: usize = 2048;
+pub const FFT_SIZES: [usize; 6] = [512, 1024, 2048, 4096, 8192, 16384];
+pub const DEFAULT_SAMPLE_RATE: u32 = 44100;
+const MIN_DECIBEL: f32 = -90.0;
+const MAX_DECIBEL: f32 = -10.0;
+/// Where each end of the dB range can go, and the least room kept between
+/// them so the mapping never divides by zero.
+pub const MIN_DECIBEL_RANGE: RangeInclusive<f32> = -120.0..=-30.0;
+pub const MAX_DECIBEL_RANGE: RangeInclusive<f32> = -60.0..=0.0;
+pub const MIN_DECIBEL_GAP: f32 = 10.0;
+/// Frames held back at most; more means nobody is reading them.
+const MAX_QUEUED_FRAMES: usize = 256;
+/// FFTs this big or bigger are shared out over the worker pool when there's
+/// more than one to run, such as both sides of a split or several hops.
+const PARALLEL_FFT_SIZE: usize = 8192;
+/// Most threads the pool takes, leaving the rest of the machine to the UI
+/// and the audio.
+const MAX_WORKERS: usize = 4;
+
+/// Changes to the analysis settings.
+#[derive(Debug, Clone)]
+pub enum Message {
+  FftSizeSelected(usize),
+  WindowSelected(WindowFunction),
+  ChannelModeSelected(ChannelMode),
+  WeightingSelected(Weighting),
+  OverlapSelected(Overlap),
+}
+
+/// How much each analysis window overlaps the next. More overlap means
+/// more frames a second from the same FFT size, for more CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Overlap {
+  Half,
+  #[default]
+  ThreeQuarters,
+  SevenEighths,
+}
+
+impl Overlap {
+  pub const ALL: [Overlap; 3] = [Overlap::Half, Overlap::ThreeQuarters, Overlap::SevenEighths];
+
+  /// Samples from one window to the next.
+  pub fn hop(self, fft_size: usize) -> usize {
+    match self {
+      Overlap::Half => fft_size / 2,
+      Overlap::ThreeQuarters => fft_size / 4,
+      Overlap::SevenEighths => fft_size / 8,
+    }
+  }
+}
+
+impl fmt::Display for Overlap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Overlap::Half => "50%",
+      Overlap::ThreeQuarters => "75%",
+      Overlap::SevenEighths => "87.5%",
+    })
+  }
+}
+
+/// Settings the analysis thread picks up between chunks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AnalysisSettings {
+  pub fft_size: usize,
+  pub window: WindowFunction,
+  pub channel_mode: ChannelMode,
+  pub weighting: Weighting,
+  pub overlap: Overlap,
+  /// Channel count of the interleaved samples coming from the tap.
+  pub channels: u16,
+  pub sample_rate: u32,
+}
+
+impl AnalysisSettings {
+  pub(crate) fn apply(&mut self, message: Message) {
+    match message {
+      Message::FftSizeSelected(fft_size) => self.fft_size = fft_size,
+      Message::WindowSelected(window) => self.window = window,
+      Message::ChannelModeSelected(mode) => self.channel_mode = mode,
+      Message::WeightingSelected(weighting) => self.weighting = weighting,
+      Message::OverlapSelected(overlap) => self.overlap = overlap,
+    }
+  }
+}
+
+impl Default for AnalysisSettings {
+  fn default() -> Self {
+    Self {
+      fft_size: BUFFER_SIZE,
+      window: WindowFunction::default(),
+      channel_mode: ChannelMode::default(),
+      weighting: Weighting::default(),
+      overlap: Overlap::default(),
+      channels: 2,
+      sample_rate: DEFAULT_SAMPLE_RATE,
+    }
+  }
+}
+
+/// One analysis step: a magnitude spectrum per analysed stream (one, or left
+/// and right when the channels are split).
+#[derive(Debug, Clone)]
+pub struct AnalysisFrame {
+  pub spectra: Vec<Vec<f32>>,
+  /// When the analysis thread finished this frame.
+  pub produced_at: Instant,
+  /// Audio between the frame before this one and it, which is a hop, or
+  /// more when frames were skipped over to reach it.
+  pub elapsed: Duration,
+  /// Strength of a beat detected at (or since the last read of) this frame.
+  pub beat: Option<f32>,
+  /// Unwrapped, smoothed phase of the first stream, in radians per bin.
+  pub phase: Vec<f32>,
+  /// Group delay of the first stream, in seconds per bin.
+  pub group_delay: Vec<f32>,
+}
+
+impl AnalysisFrame {
+  /// All analysed streams averaged into one spectrum.
+  pub fn mixed(&self) -> Vec<f32> {
+    match self.spectra.as_slice() {
+      [single] => single.clone(),
+      spectra => {
+        let len = spectra.iter().map(Vec::len).min().unwrap_or(0);
+        (0..len).map(|i| spectra.iter().map(|s| s[i]).sum::<f32>() / spectra.len() as f32).collect()
+      }
+    }
+  }
+}
+
+/// Analysed frames waiting for the audio they came from to be heard.
+///
+/// The tap hands samples over as soon as the output pulls them, which is
+/// ahead of the speakers by however much the output buffers. Each frame is
+/// queued with when the middle of its window was pulled and only comes out
+/// once the latency has passed since, so the bars move with what's audible
+/// rather than with what's buffered.
+#[derive(Debug, Default)]
+pub struct FrameQueue {
+  frames: VecDeque<(Instant, AnalysisFrame)>,
+  /// From a sample leaving the tap to it reaching the speakers.
+  latency: Duration,
+}
+
+impl FrameQueue {
+  /// Queues `frame`, the middle of whose window was pulled at `pulled_at`.
+  pub fn push(&mut self, frame: AnalysisFrame, pulled_at: Instant) {
+    if self.frames.len() >= MAX_QUEUED_FRAMES {
+      self.frames.pop_front();
+    }
+    self.frames.push_back((pulled_at, frame));
+  }
+
+  /// The newest frame that's audible by `now`, dropping older ones but
+  /// keeping the strongest beat any of them had and the audio they covered.
+  pub fn take_due(&mut self, now: Instant) -> Option<AnalysisFrame> {
+    let mut due: Option<AnalysisFrame> = None;
+    while let Some((pulled_at, _)) = self.frames.front()
+      && *pulled_at + self.latency <= now
+    {
+      let (_, mut frame) = self.frames.pop_front()?;
+      if let Some(older) = &due {
+        frame.elapsed += older.elapsed;
+        frame.beat = match (frame.beat, older.beat) {
+          (Some(a), Some(b)) => Some(a.max(b)),
+          (a, b) => a.or(b),
+        };
+      }
+      due = Some(frame);
+    }
+    due
+  }
+
+  pub fn len(&self) -> usize {
+    self.frames.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.frames.is_empty()
+  }
+
+  /// Drops everything queued, as after a seek or a new track.
+  pub fn clear(&mut self) {
+    self.frames.clear();
+  }
+
+  pub fn set_latency(&mut self, latency: Duration) {
+    self.latency = latency;
+  }
+}
+
+/// Windowed FFT, phase analysis and beat detection for one frame at a time,
+/// shared by the live analysis thread and offline rendering.
+pub struct Analyser {
+  planner: FftPlanner<f32>,
+  fft: Arc<dyn Fft<f32>>,
+  window: Vec<f32>,
+  window_sum: f32,
+  /// Per-bin weighting gains, rebuilt with the FFT size or sample rate.
+  weights: Vec<f32>,
+  beat_detector: BeatDetector,
+  settings: AnalysisSettings,
+}
+
+impl Analyser {
+  pub fn new(settings: AnalysisSettings) -> Self {
+    // Plan the FFT up front to avoid reallocating on every chunk, and
+    // re-plan only when the settings change
+    let mut planner = FftPlanner::new();
+    let fft = planner.plan_fft_forward(settings.fft_size);
+    let window = settings.window.coefficients(settings.fft_size);
+    let window_sum = window.iter().sum();
+    let weights = settings.weighting.gains(settings.fft_size, settings.sample_rate);
+    Self {
+      planner,
+      fft,
+      window,
+      window_sum,
+      weights,
+      beat_detector: BeatDetector::default(),
+      settings,
+    }
+  }
+
+  pub fn settings(&self) -> AnalysisSettings {
+    self.settings
+  }
+
+  pub fn configure(&mut self, latest: AnalysisSettings) {
+    if latest == self.settings {
+      return;
+    }
+    if latest.fft_size != self.settings.fft_size {
+      self.fft = self.planner.plan_fft_forward(latest.fft_size);
+    }
+    self.window = latest.window.coefficients(latest.fft_size);
+    self.window_sum = self.window.iter().sum();
+    if latest.fft_size != self.settings.fft_size
+      || latest.sample_rate != self.settings.sample_rate
+      || latest.weighting != self.settings.weighting
+    {
+      self.weights = latest.weighting.gains(latest.fft_size, latest.sample_rate);
+    }
+    self.settings = latest;
+    // Flux against a spectrum of another size or routing is meaningless
+    self.beat_detector.reset();
+  }
+
+  /// Analyses the first `fft_size` samples of each stream. `hop_seconds` is
+  /// the time since the previous frame, for the beat detector.
+  pub fn frame(&mut self, streams: &[Vec<f32>], hop_seconds: f32) -> AnalysisFrame {
+    self.frames(&[streams], hop_seconds).remove(0)
+  }
+
+  /// Analyses a run of windows, `hop_seconds` apart, each with the first
+  /// `fft_size` samples of every stream. Large FFTs run on the worker pool
+  /// together; the phase and beats then follow the frames in order.
+  pub fn frames<S: AsRef<[Vec<f32>]>>(
+    &mut self,
+    windows: &[S],
+    hop_seconds: f32,
+  ) -> Vec<AnalysisFrame> {
+    let fft_size = self.settings.fft_size;
+    let transform = Transform {
+      fft: self.fft.as_ref(),
+      window: &self.window,
+      window_sum: self.window_sum,
+      weights: &self.weights,
+    };
+    let jobs: Vec<&[f32]> = windows
+      .iter()
+      .flat_map(|streams| streams.as_ref())
+      .map(|samples| &samples[..fft_size])
+      .collect();
+    let results: Vec<(Vec<f32>, Vec<f32>)> = match workers() {
+      Some(pool) if fft_size >= PARALLEL_FFT_SIZE && jobs.len() > 1 => {
+        pool.install(|| jobs.par_iter().map(|samples| transform.run(samples)).collect())
+      }
+      _ => jobs.iter().map(|samples| transform.run(samples)).collect(),
+    };
+
+    let bin_hz = self.settings.sample_rate as f32 / fft_size as f32;
+    let mut results = results.into_iter();
+    windows
+      .iter()
+      .map(|streams| {
+        let (spectra, phases): (Vec<Vec<f32>>, Vec<Vec<f32>>) =
+          results.by_ref().take(streams.as_ref().len()).unzip();
+        let (phase, group_delay) = phase::analyse(&phases[0], bin_hz);
+        let mut frame = AnalysisFrame {
+          spectra,
+          produced_at: Instant::now(),
+          elapsed: Duration::from_secs_f32(hop_seconds),
+          beat: None,
+          phase,
+          group_delay,
+        };
+        frame.beat = self.beat_detector.process(&frame.mixed(), hop_seconds);
+        frame
+      })
+      .collect()
+  }
+}
+
+/// What one stream's FFT needs, borrowed from the analyser so the worker
+/// threads can share it.
+struct Transform<'a> {
+  fft: &'a dyn Fft<f32>,
+  window: &'a [f32],
+  window_sum: f32,
+  weights: &'a [f32],
+}
+
+impl Transform<'_> {
+  /// Amplitudes, normalised by the window's coherent gain and weighted, and
+  /// phases of the first half of `samples`' spectrum.
+  fn run(&self, samples: &[f32]) -> (Vec<f32>, Vec<f32>) {
+    let mut buffer: Vec<Complex<f32>> =
+      samples.iter().zip(self.window).map(|(&x, &w)| Complex::new(x * w, 0.0)).collect();
+    self.fft.process(&mut buffer);
+    let half = &buffer[..samples.len() / 2];
+    (
+      half.iter().zip(self.weights).map(|(c, &w)| c.norm() / self.window_sum * w).collect(),
+      half.iter().map(|c| c.arg()).collect(),
+    )
+  }
+}
+
+/// The analysis threads' own small pool, built on first use; `None` if the
+/// threads couldn't be started, when everything runs in turn instead.
+fn workers() -> Option<&'static rayon::ThreadPool> {
+  static POOL: OnceLock<Option<rayon::ThreadPool>> = OnceLock::new();
+  POOL
+    .get_or_init(|| {
+      let threads = thread::available_parallelism()
+        .map_or(1, |threads| threads.get().saturating_sub(1))
+        .clamp(1, MAX_WORKERS);
+      rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|index| format!("analysis-{}", index))
+        .build()
+        .inspect_err(|e| eprintln!("Failed to start the analysis workers: {}", e))
+        .ok()
+    })
+    .as_ref()
+}
+
+/// Spawns the FFT thread. It reads interleaved chunks from `receiver` until the
+/// sending side hangs up, queues every frame in `audio_data`, stamped with
+/// when its audio arrived, shares it through `shm` straight away, and adds
+/// every sample to `histogram` and `loudness`.
+pub fn spawn(
+  receiver: Receiver<Vec<f32>>,
+  analysis_settings: Arc<Mutex<AnalysisSettings>>,
+  audio_data: Arc<Mutex<FrameQueue>>,
+  histogram: Arc<Mutex<AmplitudeHistogram>>,
+  loudness: Arc<Mutex<LoudnessMeter>>,
+  shm: Arc<Mutex<ShmOutput>>,
+) {
+  thread::spawn(move || {
+    let mut analyser = Analyser::new(*analysis_settings.lock().unwrap());
+    let mut streams = StreamBuffers::new(analyser.settings());
+
+    while let Ok(samples) = receiver.recv() {
+      // The tap sends each chunk the moment it fills, so this is close enough
+      // to when its last sample was pulled
+      let received_at = Instant::now();
+      histogram.lock().unwrap().add(&samples);
+
+      let latest = *analysis_settings.lock().unwrap();
+      loudness.lock().unwrap().add(&samples, latest.channels, latest.sample_rate);
+      analyse_chunk(
+        &mut analyser,
+        &mut streams,
+        &samples,
+        latest,
+        received_at,
+        |frame, pulled_at| {
+          if let Ok(mut shm) = shm.lock() {
+            shm.publish(&frame, latest.sample_rate);
+          }
+          if let Ok(mut queue) = audio_data.lock() {
+            queue.push(frame, pulled_at);
+          }
+        },
+      );
+    }
+  });
+}
+
+/// Analyses a chunk the tap handed over at `received_at`, under the `latest`
+/// settings, passing every frame it completes to `queue` along with when the
+/// middle of that frame's window was pulled.
+pub fn analyse_chunk(
+  analyser: &mut Analyser,
+  streams: &mut StreamBuffers,
+  samples: &[f32],
+  latest: AnalysisSettings,
+  received_at: Instant,
+  mut queue: impl FnMut(AnalysisFrame, Instant),
+) {
+  streams.configure(analyser.settings(), latest);
+  analyser.configure(latest);
+  streams.push(samples, latest);
+
+  while let Some((frame, after)) = streams.next_frame(analyser) {
+    // The middle of this window is followed by `after` samples before
+    // the end of the chunk
+    let pulled_at = received_at
+      .checked_sub(Duration::from_secs_f32(after as f32 / latest.sample_rate.max(1) as f32))
+      .unwrap_or(received_at);
+    queue(frame, pulled_at);
+  }
+}
+
+/// Interleaved samples split into the streams the analyser reads, keeping
+/// the overlap between one window and the next.
+#[derive(Debug, Default)]
+pub struct StreamBuffers {
+  /// Interleaved samples that don't make up a whole frame yet.
+  pending: Vec<f32>,
+  /// One de-interleaved buffer per analysed stream.
+  streams: Vec<Vec<f32>>,
+  /// Frames analysed together but not handed out yet, with the samples
+  /// buffered after each one's middle.
+  ready: VecDeque<(AnalysisFrame, usize)>,
+}
+
+impl StreamBuffers {
+  pub fn new(settings: AnalysisSettings) -> Self {
+    Self {
+      pending: Vec::new(),
+      streams: vec![Vec::with_capacity(settings.fft_size * 2); settings.channel_mode.streams()],
+      ready: VecDeque::new(),
+    }
+  }
+
+  /// Follows a change from `old` to `latest` settings.
+  pub fn configure(&mut self, old: AnalysisSettings, latest: AnalysisSettings) {
+    if latest != old {
+      self.ready.clear();
+    }
+    if latest.fft_size != old.fft_size {
+      // Keep only the most recent samples so the new size starts from current audio
+      for buffer in &mut self.streams {
+        let excess = buffer.len().saturating_sub(latest.fft_size);
+        buffer.drain(..excess);
+      }
+    }
+    if latest.channel_mode != old.channel_mode || latest.channels != old.channels {
+      // The buffered samples belong to the old channel routing
+      self.pending.clear();
+      self.streams = vec![Vec::new(); latest.channel_mode.streams()];
+    }
+  }
+
+  /// Takes in interleaved samples with `settings`' channel count.
+  pub fn push(&mut self, samples: &[f32], settings: AnalysisSettings) {
+    // De-interleave so each channel gets its own FFT instead of a smeared mix
+    let channels = settings.channels.max(1) as usize;
+    self.pending.extend_from_slice(samples);
+    let whole = self.pending.len() / channels * channels;
+    for frame in self.pending[..whole].chunks_exact(channels) {
+      settings.channel_mode.push_frame(frame, &mut self.streams);
+    }
+    self.pending.drain(..whole);
+  }
+
+  /// Analyses the next window once enough samples are in, then moves on by
+  /// a hop so frames overlap. Also gives how many samples per
+  /// stream were buffered after the middle of the window.
+  ///
+  /// Every window already in is analysed in one go, so large FFTs can run
+  /// side by side, and handed out one per call.
+  pub fn next_frame(&mut self, analyser: &mut Analyser) -> Option<(AnalysisFrame, usize)> {
+    if self.ready.is_empty() {
+      let settings = analyser.settings();
+      let fft_size = settings.fft_size;
+      let hop_size = settings.overlap.hop(fft_size);
+      let mut windows: Vec<Vec<Vec<f32>>> = Vec::new();
+      let mut afters = Vec::new();
+      while self.streams.first().is_some_and(|buffer| buffer.len() >= fft_size) {
+        windows.push(self.streams.iter().map(|buffer| buffer[..fft_size].to_vec()).collect());
+        afters.push(self.streams[0].len() - fft_size / 2);
+        // Remove only a hop, keeping the rest for the overlap
+        for buffer in &mut self.streams {
+          buffer.drain(..hop_size);
+        }
+      }
+      let hop_seconds = hop_size as f32 / settings.sample_rate as f32;
+      let frames = analyser.frames(&windows, hop_seconds);
+      self.ready.extend(frames.into_iter().zip(afters));
+    }
+    self.ready.pop_front()
+  }
+}
+
+/// The dBFS window mapped onto the visuals; anything quieter reads as silence.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DecibelRange {
+  pub min: f32,
+  pub max: f32,
+}
+
+impl DecibelRange {
+  /// Converts a normalised FFT amplitude to dBFS, clamped to the range.
+  pub fn decibels(self, raw: f32) -> f32 {
+    if raw > 0.0 { (20.0 * raw.log10()).clamp(self.min, self.max) } else { self.min }
+  }
+
+  /// Where `raw` falls in the range, 0.0..=1.0.
+  pub fn normalise(self, raw: f32) -> f32 {
+    map_range(self.decibels(raw), self.min, self.max, 0.0, 1.0)
+  }
+
+  /// The range with both ends where the sliders can put them and at least
+  /// `MIN_DECIBEL_GAP` apart, for ranges read from files. An end that isn't
+  /// a number at all falls back to the default's.
+  pub fn repaired(self) -> Self {
+    let end = |value: f32, default: f32, range: RangeInclusive<f32>| {
+      if value.is_finite() { value.clamp(*range.start(), *range.end()) } else { default }
+    };
+    let min = end(self.min, MIN_DECIBEL, MIN_DECIBEL_RANGE);
+    let max = end(self.max, MAX_DECIBEL, MAX_DECIBEL_RANGE).max(min + MIN_DECIBEL_GAP);
+    Self { min, max }
+  }
+}
+
+impl Default for DecibelRange {
+  fn default() -> Self {
+    Self { min: MIN_DECIBEL, max: MAX_DECIBEL }
+  }
+}
+
+pub fn map_range(value: f32, from_min: f32, from_max: f32, to_min: f32, to_max: f32) -> f32 {
+  let from_range = from_max - from_min;
+  let to_range = to_max - to_min;
+  let scaled = (value - from_min) / from_range;
+  to_min + scaled * to_range
+}
@@ -0,0 +1,114 @@
+//! A/B comparison of the file playing against another one.
+
+use iced::Task as Command;
+use std::path::{Path, PathBuf};
+
+use super::{AudioVisualizer, Message::Compare};
+use crate::compare::{CompareView, ComparedTrack, Comparison};
+use crate::decode;
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  /// Picks a second file to compare the one playing against.
+  Load,
+  /// The file that was playing and the one picked, decoded, or `None` if
+  /// either couldn't be.
+  Loaded(Option<[ComparedTrack; 2]>),
+  ViewSelected(CompareView),
+  OffsetChanged(f32),
+  /// Plays the other compared file from the same point in the music.
+  Swap,
+  Close,
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_comparison(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::Load => {
+        let Some(playing) = self.player.file_path().map(PathBuf::from) else {
+          return Command::none();
+        };
+        let Some(other) =
+          rfd::FileDialog::new().add_filter("Audio", &decode::EXTENSIONS).pick_file()
+        else {
+          return Command::none();
+        };
+        self.is_loading_comparison = true;
+        // Decoding both whole files takes a second or two
+        Command::perform(
+          async move {
+            tokio::task::spawn_blocking(move || {
+              let load = |path: &Path| {
+                ComparedTrack::load(path)
+                  .map_err(|e| eprintln!("Failed to load {} to compare: {}", path.display(), e))
+                  .ok()
+              };
+              Some([load(&playing)?, load(&other)?])
+            })
+            .await
+            .unwrap_or(None)
+          },
+          |tracks| Compare(Message::Loaded(tracks)),
+        )
+      }
+      Message::Loaded(tracks) => {
+        self.is_loading_comparison = false;
+        self.comparison = tracks.map(Comparison::new);
+        Command::none()
+      }
+      Message::ViewSelected(view) => {
+        if let Some(comparison) = &mut self.comparison {
+          comparison.view = view;
+        }
+        Command::none()
+      }
+      Message::OffsetChanged(offset) => {
+        if let Some(comparison) = &mut self.comparison {
+          comparison.offset = offset;
+        }
+        Command::none()
+      }
+      Message::Swap => {
+        let Some(comparison) = &self.comparison else {
+          return Command::none();
+        };
+        let playing = self.player.file_path();
+        let path = comparison.other(playing).path.clone();
+        let position = comparison.other_position(playing, self.player.position());
+        self.audio_data.lock().unwrap().clear();
+        match self.player.swap_track(path, position) {
+          Ok(Some(track)) => self.start_audio_analysis(track),
+          Ok(None) => {}
+          Err(e) => {
+            eprintln!("Failed to swap to the compared file: {}", e);
+            self.warning = Some(e.summary());
+          }
+        }
+        self.warn_seek_error();
+        self.canvas_cache.clear();
+        self.refresh_track()
+      }
+      Message::Close => {
+        self.comparison = None;
+        Command::none()
+      }
+    }
+  }
+
+  /// Brings the compared file's bars up to the position playing.
+  pub(super) fn sync_comparison(&mut self) {
+    let bar_count = self.bar_count();
+    let analysis_settings = *self.analysis_settings.lock().unwrap();
+    let decibels = self.decibels();
+    if let Some(comparison) = &mut self.comparison {
+      comparison.update(
+        self.player.file_path(),
+        self.player.position(),
+        &analysis_settings,
+        &self.visuals,
+        decibels,
+        bar_count,
+      );
+    }
+  }
+}
@@ -0,0 +1,89 @@
+//! What's shown besides the style itself: the settings and stats panels,
+//! meters, energy blocks and labels, the terrain's camera, and how calm or
+//! safe beat effects are.
+
+use iced::Task as Command;
+
+use super::AudioVisualizer;
+use crate::components::terrain::{Camera, CameraMove};
+use crate::playback::CaptureSource;
+use crate::quality::QualityProfile;
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  ToggleSettings,
+  /// Shows or hides the listening statistics.
+  ToggleStats,
+  /// Switches the analysis settings to a bundled profile.
+  QualityProfileSelected(QualityProfile),
+  /// Turns the strobe-safety limit on beat effects on or off.
+  StrobeSafetyToggled(bool),
+  /// Shows or hides the VU and loudness meters beside the visualiser.
+  MetersToggled(bool),
+  /// Shows or hides the bass, mid and treble energy blocks.
+  EnergyToggled(bool),
+  /// Labels the bars with their frequencies; hovering one always reads it out.
+  FrequencyLabelsToggled(bool),
+  /// Turns calm visuals during speech on or off for an input (`None` being files).
+  SpeechGateToggled(Option<CaptureSource>, bool),
+  /// The 3D terrain's camera was dragged or zoomed.
+  MoveCamera(CameraMove),
+  /// Puts the camera back and lets it orbit by itself again.
+  ResetCamera,
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_display(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::ToggleSettings => {
+        self.show_settings = !self.show_settings;
+        Command::none()
+      }
+      Message::ToggleStats => {
+        self.show_stats = !self.show_stats;
+        Command::none()
+      }
+      Message::QualityProfileSelected(profile) => {
+        profile.apply(&mut self.analysis_settings.lock().unwrap(), &mut self.visuals);
+        self.canvas_cache.clear();
+        Command::none()
+      }
+      Message::MetersToggled(show) => {
+        self.show_meters = show;
+        Command::none()
+      }
+      Message::EnergyToggled(show) => {
+        self.show_energy = show;
+        Command::none()
+      }
+      Message::FrequencyLabelsToggled(show) => {
+        self.show_frequency_labels = show;
+        Command::none()
+      }
+      Message::StrobeSafetyToggled(safe) => {
+        self.strobe_safety = safe;
+        if !safe {
+          self.warning = Some(
+            "Strobe safety is off, so beat effects can flash fast and bright enough to \
+             trigger seizures in people with photosensitive epilepsy."
+              .to_string(),
+          );
+        }
+        Command::none()
+      }
+      Message::SpeechGateToggled(source, enabled) => {
+        self.speech_gate.set(source, enabled);
+        self.canvas_cache.clear();
+        Command::none()
+      }
+      Message::MoveCamera(movement) => {
+        self.camera.apply(movement);
+        Command::none()
+      }
+      Message::ResetCamera => {
+        self.camera = Camera::default();
+        Command::none()
+      }
+    }
+  }
+}
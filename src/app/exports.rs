@@ -0,0 +1,284 @@
+//! Everything written out to files: videos, stems, markers, clips,
+//! snapshots, the listening statistics and inspector dumps.
+
+use iced::Task as Command;
+use std::{io::Write, ops::Range, path::PathBuf, time::Duration};
+
+use super::{AudioVisualizer, Message::Exports};
+use crate::encode::{AudioFormat, EncodeSettings};
+use crate::export::{self, Resolution};
+use crate::ui::inspector::Snapshot;
+use crate::{markers, stem};
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  /// Writes an inspector snapshot to a JSON file.
+  DumpState,
+  /// Writes the listening statistics to a CSV file.
+  ExportStats,
+  /// Renders the loaded file with the current visuals to a video file.
+  ExportVideo,
+  /// Whether a video export also saves its analysis as a stem beside it.
+  SaveStemToggled(bool),
+  /// Renders a saved analysis stem with the current visuals to a video file.
+  RenderStem,
+  VideoExported(Result<(), String>),
+  /// Writes the track's beats, sections and band hits as markers for a
+  /// video editor.
+  ExportMarkers,
+  MarkersExported(Result<usize, String>),
+  /// Marks the clip's start or end at the playback position.
+  SetClipStart,
+  SetClipEnd,
+  /// Marks the clip around the section playing now.
+  ClipToSection,
+  ClipFadeChanged(f32),
+  /// Writes the audio between the clip markers to a file.
+  ExportClip,
+  ClipExported(Result<(), String>),
+  EncodeSettingsChanged(EncodeSettings),
+  SnapshotResolutionSelected(Resolution),
+  /// Saves the frame on screen as a PNG, or its bars as an SVG.
+  SaveSnapshot,
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_exports(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::DumpState => {
+        let json = match Snapshot::capture(self).to_json() {
+          Ok(json) => json,
+          Err(e) => {
+            eprintln!("Failed to serialise state: {}", e);
+            self.warning = Some(format!("Couldn't dump the state: {}", e));
+            return Command::none();
+          }
+        };
+        if let Some(path) = rfd::FileDialog::new()
+          .add_filter("JSON", &["json"])
+          .set_file_name("visualiser-state.json")
+          .save_file()
+          && let Err(e) = std::fs::write(&path, json)
+        {
+          eprintln!("Failed to write state dump: {}", e);
+          self.warning = Some(format!("Couldn't write the state dump: {}", e));
+        }
+        Command::none()
+      }
+      Message::ExportStats => {
+        let Some(path) = rfd::FileDialog::new()
+          .add_filter("CSV", &["csv"])
+          .set_file_name("listening-stats.csv")
+          .save_file()
+        else {
+          return Command::none();
+        };
+        let written = std::fs::File::create(&path).and_then(|file| {
+          let mut writer = std::io::BufWriter::new(file);
+          self.stats.write_csv(&mut writer)?;
+          writer.flush()
+        });
+        if let Err(e) = written {
+          eprintln!("Failed to write listening stats: {}", e);
+          self.warning = Some(format!("Couldn't export the listening stats: {}", e));
+        }
+        Command::none()
+      }
+      Message::ExportVideo => {
+        let Some(input) = self.player.file_path().map(PathBuf::from) else {
+          return Command::none();
+        };
+        let Some(output) = rfd::FileDialog::new()
+          .add_filter("Video", &["mp4", "webm"])
+          .set_file_name("visualisation.mp4")
+          .save_file()
+        else {
+          return Command::none();
+        };
+
+        let stem = self.save_stem.then(|| output.with_extension(stem::STEM_EXTENSION));
+        let visuals = self.visuals.clone();
+        let analysis_settings = *self.analysis_settings.lock().unwrap();
+        self.is_exporting = true;
+        // Rendering takes a while, so keep it off the UI thread
+        Command::perform(
+          async move {
+            tokio::task::spawn_blocking(move || {
+              export::render_video(&input, &output, stem.as_deref(), visuals, analysis_settings)
+                .map_err(|e| e.to_string())
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()))
+          },
+          |result| Exports(Message::VideoExported(result)),
+        )
+      }
+      Message::SaveStemToggled(save) => {
+        self.save_stem = save;
+        Command::none()
+      }
+      Message::RenderStem => {
+        let Some(stem) =
+          rfd::FileDialog::new().add_filter("Analysis stem", &[stem::STEM_EXTENSION]).pick_file()
+        else {
+          return Command::none();
+        };
+        let Some(output) = rfd::FileDialog::new()
+          .add_filter("Video", &["mp4", "webm"])
+          .set_file_name("visualisation.mp4")
+          .save_file()
+        else {
+          return Command::none();
+        };
+
+        let visuals = self.visuals.clone();
+        self.is_exporting = true;
+        Command::perform(
+          async move {
+            tokio::task::spawn_blocking(move || {
+              export::render_stem_video(&stem, &output, visuals).map_err(|e| e.to_string())
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()))
+          },
+          |result| Exports(Message::VideoExported(result)),
+        )
+      }
+      Message::VideoExported(result) => {
+        self.is_exporting = false;
+        if let Err(e) = result {
+          eprintln!("Failed to export video: {}", e);
+          self.warning = Some(format!("Couldn't export the video: {}", e));
+        }
+        Command::none()
+      }
+      Message::ExportMarkers => {
+        let Some(input) = self.player.file_path().map(PathBuf::from) else {
+          return Command::none();
+        };
+        let Some(output) = rfd::FileDialog::new()
+          .add_filter("Final Cut Pro", &["fcpxml"])
+          .add_filter("Premiere (Final Cut 7 XML)", &["xml"])
+          .add_filter("EDL", &["edl"])
+          .add_filter("CSV", &["csv"])
+          .set_file_name("markers.fcpxml")
+          .save_file()
+        else {
+          return Command::none();
+        };
+
+        let outline = self.outline.clone();
+        self.is_exporting_markers = true;
+        Command::perform(
+          async move {
+            tokio::task::spawn_blocking(move || {
+              markers::export_markers(&input, &output, outline).map_err(|e| e.to_string())
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()))
+          },
+          |result| Exports(Message::MarkersExported(result)),
+        )
+      }
+      Message::MarkersExported(result) => {
+        self.is_exporting_markers = false;
+        if let Err(e) = result {
+          eprintln!("Failed to export markers: {}", e);
+          self.warning = Some(format!("Couldn't export the markers: {}", e));
+        }
+        Command::none()
+      }
+      Message::SetClipStart => {
+        self.clip_start = Some(self.player.position().as_secs_f32());
+        Command::none()
+      }
+      Message::SetClipEnd => {
+        self.clip_end = Some(self.player.position().as_secs_f32());
+        Command::none()
+      }
+      Message::ClipToSection => {
+        if let Some(outline) = &self.outline {
+          let position = self.player.position().as_secs_f32();
+          let playing = outline.sections.iter().rposition(|section| section.start <= position);
+          if let Some(index) = playing {
+            self.clip_start = Some(outline.sections[index].start);
+            self.clip_end = Some(
+              outline.sections.get(index + 1).map_or(outline.duration, |section| section.start),
+            );
+          }
+        }
+        Command::none()
+      }
+      Message::ClipFadeChanged(seconds) => {
+        self.clip_fade = seconds.clamp(0.0, export::MAX_CLIP_FADE_SECONDS);
+        Command::none()
+      }
+      Message::ExportClip => {
+        let (Some(input), Some(range)) = (self.player.file_path().map(PathBuf::from), self.clip())
+        else {
+          return Command::none();
+        };
+        let Some(output) = rfd::FileDialog::new()
+          .add_filter("Audio", &AudioFormat::EXTENSIONS)
+          .set_file_name("clip.wav")
+          .save_file()
+        else {
+          return Command::none();
+        };
+
+        let fade = Duration::from_secs_f32(self.clip_fade);
+        let settings = self.encode_settings;
+        self.is_exporting_clip = true;
+        Command::perform(
+          async move {
+            tokio::task::spawn_blocking(move || {
+              export::export_clip(&input, &output, range, fade, &settings)
+                .map_err(|e| e.to_string())
+            })
+            .await
+            .unwrap_or_else(|e| Err(e.to_string()))
+          },
+          |result| Exports(Message::ClipExported(result)),
+        )
+      }
+      Message::ClipExported(result) => {
+        self.is_exporting_clip = false;
+        if let Err(e) = result {
+          eprintln!("Failed to export clip: {}", e);
+          self.warning = Some(format!("Couldn't export the clip: {}", e));
+        }
+        Command::none()
+      }
+      Message::EncodeSettingsChanged(settings) => {
+        self.encode_settings = settings;
+        Command::none()
+      }
+      Message::SnapshotResolutionSelected(resolution) => {
+        self.snapshot_resolution = resolution;
+        Command::none()
+      }
+      Message::SaveSnapshot => {
+        let Some(output) = rfd::FileDialog::new()
+          .add_filter("PNG", &["png"])
+          .add_filter("SVG (bars only)", &["svg"])
+          .set_file_name("snapshot.png")
+          .save_file()
+        else {
+          return Command::none();
+        };
+        // A single frame is quick enough to draw right here
+        if let Err(e) = export::save_snapshot(self, self.snapshot_resolution, &output) {
+          eprintln!("Failed to save snapshot: {}", e);
+          self.warning = Some(format!("Couldn't save the snapshot: {}", e));
+        }
+        Command::none()
+      }
+    }
+  }
+
+  /// The span between the clip markers, once both are set and in order.
+  pub(crate) fn clip(&self) -> Option<Range<f32>> {
+    let (start, end) = (self.clip_start?, self.clip_end?);
+    (end > start).then_some(start..end)
+  }
+}
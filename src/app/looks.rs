@@ -0,0 +1,189 @@
+//! Saved looks: presets, the ones suggested for the track playing, and
+//! the plugins the Plugin style can draw.
+
+use iced::Task as Command;
+use std::path::Path;
+
+use super::{AudioVisualizer, Message::Looks};
+use crate::plugins::Plugin;
+use crate::presets::Preset;
+use crate::suggest::{self, SuggestMode, SuggestSettings, TrackCharacter};
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  PresetNameChanged(String),
+  /// Saves the current look under the typed name, replacing any preset
+  /// already called that.
+  SavePreset,
+  LoadPreset(usize),
+  DeletePreset(usize),
+  /// Turning suggestions on looks at the playing track straight away.
+  SuggestSettingsChanged(SuggestSettings),
+  /// What a track is like, or `None` where it couldn't be decoded.
+  CharacterAnalysed(String, Option<TrackCharacter>),
+  /// Picks the plugin the Plugin style draws, by name.
+  PluginSelected(String),
+  /// Loads the plugin directory again, for plugins added since startup.
+  ReloadPlugins,
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_looks(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::PresetNameChanged(name) => {
+        self.preset_name = name;
+        Command::none()
+      }
+      Message::SavePreset => {
+        let name = self.preset_name.trim().to_string();
+        if name.is_empty() {
+          return Command::none();
+        }
+        let preset = Preset::capture(name, &self.visuals, &self.analysis_settings.lock().unwrap());
+        self.presets.insert(preset);
+        self.preset_name.clear();
+        if let Err(e) = self.presets.save() {
+          eprintln!("Failed to save presets: {}", e);
+          self.warning = Some(format!("Couldn't save the presets: {}", e));
+        }
+        Command::none()
+      }
+      Message::LoadPreset(index) => {
+        self.apply_preset(index);
+        // Picked by hand for the track playing, so remember it for others like it
+        if self.suggest_settings.learn
+          && let Some(character) =
+            self.character_source.as_ref().and_then(|path| self.characters.get(path))
+          && let Some(preset) = self.presets.get(index)
+        {
+          self.preset_choices.record(*character, preset.name.clone());
+          if let Err(e) = self.preset_choices.save() {
+            eprintln!("Failed to save preset choices: {}", e);
+          }
+        }
+        Command::none()
+      }
+      Message::DeletePreset(index) => {
+        self.presets.remove(index);
+        // The rest have moved up one
+        self.suggested_preset = None;
+        if let Err(e) = self.presets.save() {
+          eprintln!("Failed to save presets: {}", e);
+          self.warning = Some(format!("Couldn't save the presets: {}", e));
+        }
+        Command::none()
+      }
+      Message::SuggestSettingsChanged(settings) => {
+        let was_off = self.suggest_settings.mode == SuggestMode::Off;
+        self.suggest_settings = settings;
+        if was_off && settings.mode != SuggestMode::Off {
+          self.character_source = None;
+        }
+        self.refresh_character()
+      }
+      Message::CharacterAnalysed(path, character) => {
+        let Some(character) = character else {
+          return Command::none();
+        };
+        self.characters.insert(path.clone(), character);
+        // A later track may have started while this one was analysed
+        if self.character_source.as_deref() == Some(path.as_str()) {
+          self.suggest_preset(character);
+        }
+        Command::none()
+      }
+      Message::PluginSelected(name) => {
+        self.select_plugin(&name);
+        self.canvas_cache.clear();
+        Command::none()
+      }
+      Message::ReloadPlugins => {
+        let name = self.active_plugin().map(|plugin| plugin.name().to_string());
+        // Unload the old ones first, so a rebuilt library is read afresh
+        self.plugins.clear();
+        self.plugins = Plugin::load_all();
+        self.select_plugin(name.as_deref().unwrap_or_default());
+        self.canvas_cache.clear();
+        Command::none()
+      }
+    }
+  }
+
+  /// Works out what the playing track is like, once per track while
+  /// suggestions are on, to suggest or pick a preset for it.
+  pub(super) fn refresh_character(&mut self) -> Command<super::Message> {
+    let source = match self.player.capture_source() {
+      None => self.player.file_path().map(str::to_string),
+      Some(_) => None,
+    };
+    if source == self.character_source {
+      return Command::none();
+    }
+    self.character_source = source.clone();
+    self.suggested_preset = None;
+    let Some(path) = source else {
+      return Command::none();
+    };
+    if self.suggest_settings.mode == SuggestMode::Off {
+      return Command::none();
+    }
+    if let Some(&character) = self.characters.get(&path) {
+      self.suggest_preset(character);
+      return Command::none();
+    }
+
+    // The auto-DJ may have the tempo already; the rest means decoding the track
+    let bpm = self.player.grids.get(&path).map(|grid| grid.bpm);
+    Command::perform(
+      {
+        let path = path.clone();
+        async move {
+          tokio::task::spawn_blocking(move || {
+            match TrackCharacter::analyse(Path::new(&path), bpm) {
+              Ok(character) => Some(character),
+              Err(e) => {
+                eprintln!("Failed to analyse {}: {}", path, e);
+                None
+              }
+            }
+          })
+          .await
+          .unwrap_or(None)
+        }
+      },
+      move |character| Looks(Message::CharacterAnalysed(path.clone(), character)),
+    )
+  }
+
+  /// Suggests the preset that suits a track like `character`, and switches
+  /// to it when suggestions are picked automatically.
+  fn suggest_preset(&mut self, character: TrackCharacter) {
+    self.suggested_preset = suggest::suggest(&character, &self.presets, &self.preset_choices);
+    if self.suggest_settings.mode == SuggestMode::AutoPick
+      && let Some(index) = self.suggested_preset
+    {
+      self.apply_preset(index);
+    }
+  }
+
+  fn apply_preset(&mut self, index: usize) {
+    let Some(preset) = self.presets.get(index) else {
+      return;
+    };
+    preset.apply(&mut self.visuals, &mut self.analysis_settings.lock().unwrap());
+    self.trim_spectrogram();
+    self.resize_bars();
+    self.canvas_cache.clear();
+  }
+
+  /// The plugin the Plugin style draws, if any loaded.
+  pub(crate) fn active_plugin(&self) -> Option<&Plugin> {
+    self.plugins.get(self.plugin)
+  }
+
+  /// Switches the Plugin style to the plugin called `name`, or the first
+  /// when there's none by that name.
+  pub(super) fn select_plugin(&mut self, name: &str) {
+    self.plugin = self.plugins.iter().position(|plugin| plugin.name() == name).unwrap_or(0);
+  }
+}
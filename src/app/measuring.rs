@@ -0,0 +1,152 @@
+//! Measuring the room and the signal chain with the mic: transfer
+//! functions, impulse responses and feedback, and recording it.
+
+use iced::Task as Command;
+
+use super::{AudioVisualizer, Message::Measuring};
+use crate::components::{feedback::FeedbackDetector, visualiser::VisualStyle};
+use crate::impulse::ImpulseResponse;
+use crate::measurement::Measurement;
+use crate::playback::{self, CaptureSource};
+use crate::recording::{self, MicRecording};
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  /// Starts or stops comparing the mic against the playing audio.
+  ToggleMeasurement,
+  /// Plays a sweep and records the mic to measure the room's impulse response.
+  MeasureImpulse,
+  ImpulseMeasured(Result<ImpulseResponse, String>),
+  /// Saves the measured impulse response as a WAV file.
+  ExportImpulse,
+  /// Switches to the mic and watches it for feedback, or stops.
+  ToggleFeedback,
+  /// Starts writing the live mic to a WAV file, or stops and asks where to save it.
+  ToggleRecording,
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_measuring(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::MeasureImpulse => {
+        self.is_measuring_impulse = true;
+        // The sweep takes several seconds, so record it off the UI thread
+        Command::perform(
+          async {
+            tokio::task::spawn_blocking(|| ImpulseResponse::measure().map_err(|e| e.to_string()))
+              .await
+              .unwrap_or_else(|e| Err(e.to_string()))
+          },
+          |result| Measuring(Message::ImpulseMeasured(result)),
+        )
+      }
+      Message::ImpulseMeasured(result) => {
+        self.is_measuring_impulse = false;
+        match result {
+          Ok(impulse) => self.impulse = Some(impulse),
+          Err(e) => {
+            eprintln!("Failed to measure impulse response: {}", e);
+            self.warning = Some(format!("Couldn't measure the impulse response: {}", e));
+          }
+        }
+        self.canvas_cache.clear();
+        Command::none()
+      }
+      Message::ExportImpulse => {
+        if let Some(impulse) = &self.impulse
+          && let Some(path) = rfd::FileDialog::new()
+            .add_filter("WAV", &["wav"])
+            .set_file_name("impulse-response.wav")
+            .save_file()
+          && let Err(e) = impulse.save_wav(&path)
+        {
+          eprintln!("Failed to write impulse response: {}", e);
+          self.warning = Some(format!("Couldn't write the impulse response: {}", e));
+        }
+        Command::none()
+      }
+      Message::ToggleMeasurement => {
+        if self.measurement.take().is_none() {
+          let fft_size = self.analysis_settings.lock().unwrap().fft_size;
+          match Measurement::start(
+            self.player.reference(),
+            self.channels,
+            self.sample_rate,
+            fft_size,
+          ) {
+            Ok(measurement) => {
+              self.measurement = Some(measurement);
+              self.visuals.style = VisualStyle::Transfer;
+            }
+            Err(e) => {
+              eprintln!("Failed to start measurement: {}", e);
+              self.warning = Some(format!("Couldn't start measuring: {}", e));
+            }
+          }
+        }
+        self.canvas_cache.clear();
+        Command::none()
+      }
+      Message::ToggleRecording => {
+        if self.mic_recording.is_some() {
+          self.stop_mic_recording();
+        } else if self.player.capture_source() == Some(CaptureSource::Microphone) {
+          match MicRecording::start(
+            self.player.recording(),
+            self.channels,
+            self.sample_rate,
+            self.encode_settings.wav_depth,
+          ) {
+            Ok(recording) => self.mic_recording = Some(recording),
+            Err(e) => {
+              eprintln!("Failed to start recording: {}", e);
+              self.warning = Some(format!("Couldn't start recording: {}", e));
+            }
+          }
+        }
+        Command::none()
+      }
+      Message::ToggleFeedback => {
+        let mic_is_live = self.player.capture_source() == Some(CaptureSource::Microphone);
+        let enable = self.feedback.take().is_none();
+        let command = if enable != mic_is_live {
+          self.update(super::Message::Playback(playback::Message::ToggleMicrophone))
+        } else {
+          Command::none()
+        };
+        // Nothing to watch if the mic wouldn't open
+        if enable && self.player.capture_source() == Some(CaptureSource::Microphone) {
+          self.feedback = Some(FeedbackDetector::default());
+        }
+        self.canvas_cache.clear();
+        command
+      }
+    }
+  }
+
+  /// Stops any mic recording and asks where to keep it; cancelling the
+  /// dialog throws it away.
+  pub(super) fn stop_mic_recording(&mut self) {
+    let Some(recording) = self.mic_recording.take() else {
+      return;
+    };
+    let recorded = match recording.finish() {
+      Ok(recorded) => recorded,
+      Err(e) => {
+        eprintln!("Failed to finish recording: {}", e);
+        self.warning = Some(format!("Couldn't finish the recording: {}", e));
+        return;
+      }
+    };
+    let output =
+      rfd::FileDialog::new().add_filter("WAV", &["wav"]).set_file_name("recording.wav").save_file();
+    let result = match output {
+      Some(output) => recording::save(&recorded, &output),
+      None => std::fs::remove_file(&recorded),
+    };
+    if let Err(e) = result {
+      eprintln!("Failed to save recording: {}", e);
+      self.warning = Some(format!("Couldn't save the recording: {}", e));
+    }
+  }
+}
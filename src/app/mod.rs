@@ -0,0 +1,1197 @@
+//! The visualiser window itself: its state, the messages it reacts to, and
+//! the areas of [`update`](AudioVisualizer::update) split out by topic.
+
+use iced::{Task as Command, widget::canvas, window};
+use std::{
+  cell::Cell,
+  collections::{HashMap, HashSet, VecDeque},
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+pub(crate) mod comparison;
+pub(crate) mod display;
+pub(crate) mod exports;
+pub(crate) mod looks;
+pub(crate) mod measuring;
+pub(crate) mod outputs;
+pub(crate) mod preferences;
+pub(crate) mod track;
+mod view;
+pub(crate) mod windows;
+
+use crate::analysis::{
+  self, AnalysisFrame, AnalysisSettings, DEFAULT_SAMPLE_RATE, DecibelRange, FrameQueue, map_range,
+};
+use crate::autodj::GridCache;
+use crate::compare::Comparison;
+use crate::components::{
+  auto_range::AutoRange,
+  bars,
+  bpm::TempoTracker,
+  budget::{Degradation, PerformanceBudget},
+  chroma::Chroma,
+  classifier::{CALM_ENVELOPE, Content, ContentClassifier, SpeechGate},
+  energy::BandEnergy,
+  feedback::FeedbackDetector,
+  histogram::AmplitudeHistogram,
+  loudness::LoudnessMeter,
+  particles::ParticleSystem,
+  postfx::TRAIL_FRAMES,
+  recorder::MacroRecorder,
+  sections,
+  strobe::StrobeLimiter,
+  terrain::Camera,
+  visualiser::VisualStyle,
+};
+use crate::config::Config;
+use crate::encode::EncodeSettings;
+use crate::export::Resolution;
+use crate::geometry::WindowGeometry;
+use crate::graphics::{GraphicsSettings, RendererBackend};
+use crate::identify::TrackInfo;
+use crate::impulse::ImpulseResponse;
+use crate::keymap::{self, Action, Keymap};
+use crate::lyrics::Lyrics;
+use crate::measurement::Measurement;
+use crate::midi::{MidiSender, MidiSettings};
+use crate::osc::{OscSender, OscSettings};
+use crate::outline::TrackOutline;
+use crate::playback::{self, CaptureSource, LoadedTrack, OutputDevice, PlaybackError, Player};
+use crate::plugins::Plugin;
+use crate::presets::{HOTKEY_PRESETS, PresetLibrary};
+use crate::recording::MicRecording;
+use crate::remote::{Remote, RemoteSettings};
+use crate::rumble::{RumbleSender, RumbleSettings};
+use crate::session::{Session, SessionSettings};
+use crate::shm::{ShmOutput, ShmSettings};
+use crate::stats::{Listen, ListeningStats};
+use crate::suggest::{PresetChoices, SuggestSettings, TrackCharacter};
+use crate::tags::{Metadata, TagReview};
+use crate::tray::{Tray, TraySettings};
+use crate::ui::{self, inspector::FrameLog, settings::VisualSettings};
+
+pub(crate) const DEFAULT_NUM_BARS: usize = 75;
+pub(crate) const DEFAULT_BAR_WIDTH: f32 = 8.0;
+pub(crate) const DEFAULT_BAR_GAP: f32 = 2.0;
+pub(crate) const MIN_BAR_WIDTH: f32 = 2.0;
+pub(crate) const MIN_BAR_HEIGHT: f32 = 10.0;
+pub(crate) const MAX_BAR_HEIGHT: f32 = 150.0;
+/// Raw samples kept for the waveform view (a little over 2 s of 48 kHz stereo).
+pub(crate) const WAVEFORM_CAPACITY: usize = 1 << 18;
+const SPECTROGRAM_ROWS: usize = 128;
+/// Multiplier per 60 fps tick that fades the beat pulse back out.
+const BEAT_PULSE_DECAY: f32 = 0.85;
+/// Tempo the ring spin setting is taken at when it's locked to the tempo.
+const LOCKED_SPIN_BPM: f32 = 120.0;
+/// A tick at the default 60 fps.
+pub(crate) const DEFAULT_UPDATE_INTERVAL: Duration = Duration::from_nanos(1_000_000_000 / 60);
+/// Longest an uncapped frame counts for, so a stall or a pause doesn't
+/// jump the visuals on in one go.
+const MAX_FRAME_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a peak marker holds at its maximum before it starts to fall.
+const PEAK_HOLD: Duration = Duration::from_millis(500);
+/// Pixels a released peak marker falls per second.
+const PEAK_FALL_SPEED: f32 = 90.0;
+const SEEK_STEP: Duration = Duration::from_secs(5);
+const VOLUME_STEP: f32 = 0.05;
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  Playback(playback::Message),
+  Analysis(analysis::Message),
+  Visual(ui::settings::Message),
+  /// OSC, shared memory, MIDI, rumble, the phone remote and the tray.
+  Outputs(outputs::Message),
+  /// Videos, stems, markers, clips, snapshots and other files written out.
+  Exports(exports::Message),
+  /// A/B comparison against a second file.
+  Compare(comparison::Message),
+  /// Transfer functions, impulse responses, feedback and recordings from the mic.
+  Measuring(measuring::Message),
+  /// Cover art, tags, lyrics, grids and the rest read per track.
+  Track(track::Message),
+  /// Presets, their suggestions and the plugins.
+  Looks(looks::Message),
+  /// The main and mini windows and presentation mode.
+  Windows(windows::Message),
+  /// The panels, meters and labels shown, the terrain's camera and how
+  /// calm or safe beat effects are.
+  Display(display::Message),
+  /// Saving and resetting the config, the last session, graphics and shortcuts.
+  Preferences(preferences::Message),
+  ToggleMacroRecording,
+  ToggleMacroReplay,
+  DismissWarning,
+  KeyPressed(iced::keyboard::Key),
+  Tick,
+  /// A window redrew, at the time given; the main window's tick while the
+  /// frame rate is uncapped.
+  Frame(window::Id, Instant),
+  AudioData(Vec<f32>),
+  /// A beat was detected; carries its strength (0.0..=1.0).
+  Beat(f32),
+}
+
+impl Message {
+  /// Control actions the macro recorder captures; transport and data messages aren't.
+  fn is_recordable(&self) -> bool {
+    matches!(
+      self,
+      Message::Analysis(_)
+        | Message::Visual(_)
+        | Message::Playback(playback::Message::VolumeChanged(_) | playback::Message::ToggleMute)
+    )
+  }
+}
+
+pub(crate) struct AudioVisualizer {
+  pub(crate) player: Player,
+  pub(crate) is_decaying: bool,
+  pub(crate) audio_data: Arc<Mutex<FrameQueue>>,
+  pub(crate) tick: u64,
+  /// Time ticks take frames due by instead of the system's, for tests
+  /// that drive the app on a fake clock.
+  pub(crate) clock: Option<Instant>,
+  /// Seconds of ticks so far, which jitter moves with.
+  pub(crate) animation_time: f32,
+  /// When the window last redrew, to time uncapped ticks by.
+  pub(crate) last_frame: Option<Instant>,
+  pub(crate) frequency_data: Vec<f32>,
+  /// Recent maximum of each bar, drawn as a marker above it.
+  pub(crate) peak_data: Vec<f32>,
+  /// Hold each peak marker has left before it falls.
+  pub(crate) peak_hold: Vec<Duration>,
+  /// The bars' last few frames, oldest first, while they leave trails.
+  pub(crate) trail: VecDeque<Vec<f32>>,
+  pub(crate) canvas_cache: canvas::Cache,
+  /// The waveform overview, drawn again only for a new track or colours.
+  pub(crate) overview_cache: canvas::Cache,
+  pub(crate) analysis_settings: Arc<Mutex<AnalysisSettings>>,
+  pub(crate) visuals: VisualSettings,
+  pub(crate) sample_rate: u32,
+  pub(crate) channels: u16,
+  pub(crate) waveform: Arc<Mutex<VecDeque<f32>>>,
+  pub(crate) spectrogram: VecDeque<Vec<f32>>,
+  /// Simulation behind the particle style, stepped every tick while it shows.
+  pub(crate) particles: ParticleSystem,
+  /// Energy per pitch class, for the chromagram.
+  pub(crate) chroma: Chroma,
+  /// The signal's recent level, for the auto range.
+  pub(crate) auto_range: AutoRange,
+  /// Live tempo of what's playing, and a metronome kept on its beats.
+  pub(crate) tempo: TempoTracker,
+  /// Seconds the rings have spun through: ticks, sped up or slowed to the
+  /// tempo while it's locked.
+  pub(crate) spin_time: f32,
+  pub(crate) histogram: Arc<Mutex<AmplitudeHistogram>>,
+  /// VU levels and loudness, fed by the analysis thread.
+  pub(crate) loudness: Arc<Mutex<LoudnessMeter>>,
+  pub(crate) show_meters: bool,
+  /// Bass, mid and treble energy of the latest frame, smoothed.
+  pub(crate) energy: BandEnergy,
+  pub(crate) show_energy: bool,
+  /// Labels each bar with its centre frequency.
+  pub(crate) show_frequency_labels: bool,
+  /// Phase and group delay of the latest frame, per FFT bin.
+  pub(crate) phase: Vec<f32>,
+  pub(crate) group_delay: Vec<f32>,
+  pub(crate) recorder: MacroRecorder,
+  /// The live mic being written to a file.
+  pub(crate) mic_recording: Option<MicRecording>,
+  /// 1.0 right after a beat, decaying to 0.0.
+  pub(crate) beat_pulse: f32,
+  /// Present when the debug inspector was enabled with `--inspector`.
+  pub(crate) inspector: Option<FrameLog>,
+  pub(crate) show_settings: bool,
+  /// Transfer-function measurement against the mic, while one runs.
+  pub(crate) measurement: Option<Measurement>,
+  pub(crate) is_exporting: bool,
+  pub(crate) save_stem: bool,
+  pub(crate) is_exporting_markers: bool,
+  /// Clip markers, in seconds into the track playing.
+  pub(crate) clip_start: Option<f32>,
+  pub(crate) clip_end: Option<f32>,
+  /// Fade at either end of an exported clip, in seconds.
+  pub(crate) clip_fade: f32,
+  pub(crate) is_exporting_clip: bool,
+  /// Two files being A/B compared, one of them playing.
+  pub(crate) comparison: Option<Comparison>,
+  pub(crate) is_loading_comparison: bool,
+  /// Quality of exported audio, per format.
+  pub(crate) encode_settings: EncodeSettings,
+  pub(crate) osc_settings: OscSettings,
+  /// The OSC host and port as typed, only applied on Enter.
+  pub(crate) osc_host: String,
+  pub(crate) osc_port: String,
+  /// Streams the bars and beats to lighting software while OSC is on.
+  pub(crate) osc: Option<OscSender>,
+  pub(crate) shm_settings: ShmSettings,
+  /// Shares each analysis frame with other local processes while it's on,
+  /// written from the analysis thread.
+  pub(crate) shm: Arc<Mutex<ShmOutput>>,
+  pub(crate) midi_settings: MidiSettings,
+  /// Plays beats and band energy out to MIDI while it's on.
+  pub(crate) midi: Option<MidiSender>,
+  /// Outputs found when MIDI was last set up, to pick from.
+  pub(crate) midi_ports: Vec<String>,
+  pub(crate) rumble_settings: RumbleSettings,
+  /// Shakes game controllers with the bass and beats while it's on.
+  pub(crate) rumble: Option<RumbleSender>,
+  /// Controllers found when rumble was last set up, to pick from.
+  pub(crate) rumble_devices: Vec<String>,
+  pub(crate) remote_settings: RemoteSettings,
+  /// The remote's port as typed, only applied on Enter once it's a port.
+  pub(crate) remote_port: String,
+  /// Serves the phone remote while it's on.
+  pub(crate) remote: Option<Remote>,
+  pub(crate) tray_settings: TraySettings,
+  /// The tray icon while it's on.
+  pub(crate) tray: Option<Tray>,
+  /// Visualisers from the plugin directory, by name.
+  pub(crate) plugins: Vec<Plugin>,
+  /// Which of them the Plugin style draws.
+  pub(crate) plugin: usize,
+  /// Saved looks, the first nine on the number keys.
+  pub(crate) presets: PresetLibrary,
+  /// Name typed in for the next saved preset.
+  pub(crate) preset_name: String,
+  /// Size snapshots are rendered at, independent of the window.
+  pub(crate) snapshot_resolution: Resolution,
+  /// Last measured room response, drawn over the bars.
+  pub(crate) impulse: Option<ImpulseResponse>,
+  pub(crate) is_measuring_impulse: bool,
+  /// Watches the live mic for feedback while that mode is on.
+  pub(crate) feedback: Option<FeedbackDetector>,
+  pub(crate) classifier: ContentClassifier,
+  /// Inputs whose visuals calm down while speech is detected.
+  pub(crate) speech_gate: SpeechGate,
+  pub(crate) acoustid_key: String,
+  /// Last identified track, with the file it was identified from (`None`
+  /// for system audio).
+  pub(crate) identified: Option<(Option<String>, TrackInfo)>,
+  pub(crate) is_identifying: bool,
+  /// Corrected tags offered for an identified file whose own were poor.
+  pub(crate) tag_review: Option<TagReview>,
+  /// File the current cover-art palette and backdrop were (or are being) taken from.
+  pub(crate) cover_source: Option<String>,
+  /// File the header's metadata was (or is being) read from.
+  pub(crate) metadata_source: Option<String>,
+  pub(crate) metadata: Option<Metadata>,
+  /// File the lyrics were (or are being) loaded for.
+  pub(crate) lyrics_source: Option<String>,
+  pub(crate) lyrics: Option<Lyrics>,
+  /// File the timeline's outline is analysed for.
+  pub(crate) outline_source: Option<String>,
+  pub(crate) outline: Option<TrackOutline>,
+  pub(crate) is_analysing_grids: bool,
+  /// Tracks that failed analysis, so they aren't tried again this run.
+  pub(crate) ungridded: HashSet<String>,
+  pub(crate) is_measuring_loudness: bool,
+  /// Tracks that couldn't be measured, so they play as they are.
+  pub(crate) unmeasurable: HashSet<String>,
+  pub(crate) suggest_settings: SuggestSettings,
+  /// Presets picked by hand, and what the track playing was like.
+  pub(crate) preset_choices: PresetChoices,
+  /// What each track analysed this run is like.
+  pub(crate) characters: HashMap<String, TrackCharacter>,
+  /// File the preset suggestion is (or is being) worked out for.
+  pub(crate) character_source: Option<String>,
+  pub(crate) suggested_preset: Option<usize>,
+  pub(crate) presenting: bool,
+  /// Whether presentation mode also goes fullscreen, on the monitor the
+  /// window is on.
+  pub(crate) present_fullscreen: bool,
+  /// Whether beat effects are kept from flashing at photosensitivity-unsafe rates.
+  pub(crate) strobe_safety: bool,
+  pub(crate) pulse_limiter: StrobeLimiter,
+  /// Draw cost per style, and how far each is degraded to stay in budget.
+  pub(crate) budget: PerformanceBudget,
+  /// How long the window's scene last took to draw, taken in by the next tick.
+  pub(crate) draw_cost: Cell<Option<Duration>>,
+  pub(crate) background_limiter: StrobeLimiter,
+  /// Whether presentation mode put the window into fullscreen, to undo it.
+  pub(crate) is_fullscreen: bool,
+  /// Last mouse movement while presenting; the controls show until it times out.
+  pub(crate) cursor_moved_at: Option<Instant>,
+  /// `None` for the off-screen copies exports render with.
+  pub(crate) main_window: Option<window::Id>,
+  /// The always-on-top visualiser window, while it's open.
+  pub(crate) mini_window: Option<window::Id>,
+  /// Where the main window is, saved when it closes.
+  pub(crate) window_geometry: WindowGeometry,
+  /// Whether opening the mini window minimises the main one until it closes.
+  pub(crate) mini_hides_main: bool,
+  pub(crate) session_settings: SessionSettings,
+  /// Plays, time and loudness per track, across runs.
+  pub(crate) stats: ListeningStats,
+  pub(crate) show_stats: bool,
+  /// File being listened to now, and how much of it has been heard.
+  pub(crate) listening: Option<(String, Listen)>,
+  /// As saved; the CLI can override them for one run.
+  pub(crate) graphics_settings: GraphicsSettings,
+  /// Whether this run was asked to draw in software, so falling back to it
+  /// isn't worth a warning.
+  pub(crate) software_requested: bool,
+  /// Shown across the top until dismissed.
+  pub(crate) warning: Option<String>,
+  /// Where the 3D terrain is seen from.
+  pub(crate) camera: Camera,
+  pub(crate) keymap: Keymap,
+  /// Shortcut waiting for its new key, after its button in the settings was pressed.
+  pub(crate) rebinding: Option<Action>,
+}
+
+impl AudioVisualizer {
+  fn new(inspector: bool, graphics: GraphicsSettings) -> (Self, Command<Message>) {
+    let mut visualizer = Self {
+      inspector: inspector.then(FrameLog::default),
+      software_requested: graphics.backend == RendererBackend::Software,
+      ..Self::default()
+    };
+    let config = Config::load();
+    visualizer.plugins = Plugin::load_all();
+    visualizer.apply_config(&config);
+    // Only the live app talks to the outside; offline renders never do
+    let outputs = visualizer.set_outputs(&config);
+    visualizer.player.grids = GridCache::load();
+    visualizer.presets = PresetLibrary::load();
+    visualizer.preset_choices = PresetChoices::load();
+    visualizer.stats = ListeningStats::load();
+    visualizer.window_geometry = config.window;
+    visualizer.is_fullscreen = config.window.fullscreen;
+    let (main_window, open) = window::open(config.window.settings());
+    visualizer.main_window = Some(main_window);
+    // Asked once the window is up, since that's when the renderer is picked
+    let information = iced::system::fetch_information().map(|information| {
+      Message::Preferences(preferences::Message::GraphicsInformation(information))
+    });
+    let restore = config
+      .session
+      .restore
+      .then(|| Command::done(Message::Preferences(preferences::Message::RestoreSession)));
+    (
+      visualizer,
+      Command::batch(
+        [
+          open.map(|id| Message::Windows(windows::Message::MainWindowOpened(id))),
+          information,
+          outputs,
+        ]
+        .into_iter()
+        .chain(restore),
+      ),
+    )
+  }
+
+  fn title(&self, _window: window::Id) -> String {
+    String::from("Rust Audio Visualizer")
+  }
+
+  pub(crate) fn apply_config(&mut self, config: &Config) {
+    self.visuals.apply_config(config, &mut self.analysis_settings.lock().unwrap());
+    self.keymap = config.keymap.clone();
+    self.speech_gate = config.speech_gate;
+    self.acoustid_key = config.acoustid_key.clone();
+    self.encode_settings = config.encode;
+    self.present_fullscreen = config.present_fullscreen;
+    self.strobe_safety = config.strobe_safety;
+    self.mini_hides_main = config.mini_hides_main;
+    self.session_settings = config.session;
+    self.graphics_settings = config.graphics;
+    self.player.auto_dj = config.auto_dj;
+    self.player.set_normalise(config.normalise);
+    self.player.set_equalizer(config.equalizer);
+    self.suggest_settings = config.suggest;
+    self.select_plugin(&config.plugin);
+    self.player.output_latency = Duration::from_secs_f32(
+      config.output_latency_ms.clamp(0.0, playback::MAX_OUTPUT_LATENCY_MS) / 1000.0,
+    );
+    // Offline rendering never loads a track, and keeps its frames undelayed
+    if self.player.is_loaded {
+      self.sync_latency();
+    }
+    self.player.output_device = if config.output_device.is_empty() {
+      OutputDevice::Default
+    } else {
+      OutputDevice::Named(config.output_device.clone())
+    };
+    self.player.crossfade =
+      Duration::from_secs_f32(config.crossfade_seconds.clamp(0.0, playback::MAX_CROSSFADE_SECONDS));
+    self.resize_bars();
+    self.canvas_cache.clear();
+  }
+
+  /// Refits the bars, their peaks and trails to the bar count, keeping
+  /// their heights so a change mid-track doesn't drop them to the floor.
+  pub(crate) fn resize_bars(&mut self) {
+    let count = self.bar_count();
+    if self.frequency_data.len() != count {
+      self.frequency_data = bars::rescale(&self.frequency_data, count, MIN_BAR_HEIGHT);
+      self.peak_data = bars::rescale(&self.peak_data, count, MIN_BAR_HEIGHT);
+      self.peak_hold = bars::rescale(&self.peak_hold, count, Duration::ZERO);
+      for frame in &mut self.trail {
+        *frame = bars::rescale(frame, count, MIN_BAR_HEIGHT);
+      }
+    }
+  }
+
+  /// Keeps this frame of the bars for the trails, if they're on.
+  fn push_trail(&mut self) {
+    if self.visuals.effects.trails <= 0.0 {
+      self.trail.clear();
+      return;
+    }
+    if self.trail.len() == TRAIL_FRAMES {
+      self.trail.pop_front();
+    }
+    self.trail.push_back(self.frequency_data.clone());
+  }
+
+  /// The dB range mapped onto the visuals: the signal's own while auto
+  /// range is on, otherwise the setting.
+  pub(crate) fn decibels(&self) -> DecibelRange {
+    if self.visuals.auto_range {
+      self.auto_range.range(self.visuals.decibels)
+    } else {
+      self.visuals.decibels
+    }
+  }
+
+  /// Bars to draw: the setting, capped while the style is over its budget.
+  fn bar_count(&self) -> usize {
+    let count = self.visuals.bar_count;
+    self.degradation().max_bars().map_or(count, |max| count.min(max))
+  }
+
+  /// How far the current style's effects are cut back to keep up.
+  pub(crate) fn degradation(&self) -> Degradation {
+    self.budget.degradation(self.visuals.style)
+  }
+
+  /// Pushes each peak marker up to its bar, or lets it fall once its hold runs
+  /// out. Returns whether any marker is still above the floor.
+  fn update_peaks(&mut self) -> bool {
+    let elapsed = self.visuals.update_interval;
+    let mut any_above_min = false;
+    let bars = self.frequency_data.iter();
+    for ((peak, hold), &height) in self.peak_data.iter_mut().zip(&mut self.peak_hold).zip(bars) {
+      if height >= *peak {
+        *peak = height;
+        *hold = PEAK_HOLD;
+      } else if !hold.is_zero() {
+        *hold = hold.saturating_sub(elapsed);
+      } else {
+        *peak = (*peak - PEAK_FALL_SPEED * elapsed.as_secs_f32()).max(height);
+      }
+      any_above_min |= *peak > MIN_BAR_HEIGHT + 0.1;
+    }
+    any_above_min
+  }
+
+  /// The message a shortcut stands for, if it does anything right now.
+  pub(crate) fn shortcut(&self, action: Action) -> Option<Message> {
+    let track_sections = self.outline.as_ref().map_or(&[][..], |outline| &outline.sections);
+    let position = self.player.position();
+    let transport = match action {
+      Action::Presentation => return Some(Message::Windows(windows::Message::TogglePresentation)),
+      Action::Snapshot => return Some(Message::Exports(exports::Message::SaveSnapshot)),
+      Action::PlayPause if self.player.is_playing => playback::Message::Pause,
+      Action::PlayPause => playback::Message::Play,
+      Action::Stop => playback::Message::Stop,
+      Action::OpenFile => playback::Message::LoadFile,
+      Action::SeekBackward => playback::Message::Seek(position.saturating_sub(SEEK_STEP)),
+      Action::SeekForward => playback::Message::Seek(position + SEEK_STEP),
+      Action::VolumeUp => {
+        playback::Message::VolumeChanged((self.player.volume + VOLUME_STEP).min(1.0))
+      }
+      Action::VolumeDown => {
+        playback::Message::VolumeChanged((self.player.volume - VOLUME_STEP).max(0.0))
+      }
+      Action::NextSection => {
+        let start = sections::next_start(track_sections, position.as_secs_f32())?;
+        playback::Message::Seek(Duration::from_secs_f32(start))
+      }
+      Action::PreviousSection => {
+        let start = sections::previous_start(track_sections, position.as_secs_f32())?;
+        playback::Message::Seek(Duration::from_secs_f32(start))
+      }
+    };
+    Some(Message::Playback(transport))
+  }
+
+  /// The bars, each 0.0..=1.0.
+  pub(crate) fn bar_levels(&self) -> Vec<f32> {
+    self
+      .frequency_data
+      .iter()
+      .map(|&height| map_range(height, MIN_BAR_HEIGHT, MAX_BAR_HEIGHT, 0.0, 1.0).clamp(0.0, 1.0))
+      .collect()
+  }
+
+  /// How far out the bars pulse: with the beats, or with a band's energy
+  /// when the pulse is bound to one.
+  pub(crate) fn pulse(&self) -> f32 {
+    self.pulse_limiter.level()
+  }
+
+  /// The time, from the fake clock when there is one.
+  fn now(&self) -> Instant {
+    self.clock.unwrap_or_else(Instant::now)
+  }
+
+  /// Seconds of ticks so far, which jitter moves with.
+  pub(crate) fn animation_time(&self) -> f32 {
+    self.animation_time
+  }
+
+  /// Seconds the rings spin with, in step with the tempo while it's locked.
+  pub(crate) fn spin_time(&self) -> f32 {
+    self.spin_time
+  }
+
+  /// How fast spin runs: the detected tempo over the 120 BPM the spin
+  /// setting is taken at, while locked to it.
+  fn spin_rate(&self) -> f32 {
+    match self.tempo.bpm() {
+      Some(bpm) if self.visuals.tempo_lock => bpm / LOCKED_SPIN_BPM,
+      _ => 1.0,
+    }
+  }
+
+  /// Moves the beat-reactive effects on by a tick, through the strobe limit
+  /// while it's on.
+  fn limit_strobes(&mut self) {
+    let elapsed = self.visuals.update_interval;
+    let pulse = match self.visuals.pulse_binding.region() {
+      Some(region) => self.energy.level(region),
+      None if self.visuals.tempo_lock => self.tempo.pulse().unwrap_or(self.beat_pulse),
+      None => self.beat_pulse,
+    };
+    self.pulse_limiter.update(pulse, elapsed, self.strobe_safety);
+    let background =
+      self.visuals.background_binding.region().map_or(0.0, |region| self.energy.level(region));
+    self.background_limiter.update(background, elapsed, self.strobe_safety);
+  }
+
+  /// Whether speech is playing on an input that calms the visuals for it.
+  pub(crate) fn is_calm(&self) -> bool {
+    self.speech_gate.enabled(self.player.capture_source())
+      && self.classifier.content() == Content::Speech
+  }
+
+  /// Counts `elapsed` towards the file playing, starting a new listen
+  /// when it's changed.
+  fn note_listening(&mut self, elapsed: Duration) {
+    let path = match self.player.capture_source() {
+      None => self.player.file_path().map(str::to_string),
+      Some(_) => None,
+    };
+    if self.listening.as_ref().map(|(listening, _)| listening) != path.as_ref() {
+      self.end_listen();
+      self.listening = path.map(|path| (path, Listen::default()));
+    }
+    if let Some((_, listen)) = &mut self.listening {
+      listen.seconds += elapsed.as_secs_f32();
+      listen.loudness = self.loudness.lock().unwrap().integrated().or(listen.loudness);
+    }
+  }
+
+  /// Adds the listen so far to the statistics and saves them.
+  fn end_listen(&mut self) {
+    let Some((path, mut listen)) = self.listening.take() else {
+      return;
+    };
+    listen.bpm = self
+      .player
+      .grids
+      .get(&path)
+      .map(|grid| grid.bpm)
+      .or_else(|| self.characters.get(&path).map(|character| character.bpm));
+    self.stats.record(&path, listen);
+    if let Err(e) = self.stats.save() {
+      eprintln!("Failed to save listening stats: {}", e);
+    }
+  }
+
+  /// What's playing and how it looks now, to pick up from next launch.
+  fn session(&self) -> Session {
+    Session {
+      playlist: self.player.playlist().to_vec(),
+      track: self.player.track().0,
+      position_secs: self.player.position().as_secs_f32(),
+      volume: self.player.volume,
+      muted: self.player.is_muted,
+      // A capture is started again by hand
+      playing: self.player.is_playing && self.player.capture_source().is_none(),
+      visuals: Some(self.visuals.to_config(&self.analysis_settings.lock().unwrap())),
+    }
+  }
+
+  /// Holds the visuals back by the output latency while a file plays.
+  /// Captures go without: what they hear has already been played.
+  fn sync_latency(&self) {
+    let latency = match self.player.capture_source() {
+      None => self.player.output_latency,
+      Some(_) => Duration::ZERO,
+    };
+    self.audio_data.lock().unwrap().set_latency(latency);
+  }
+
+  /// Points the analysis at a freshly loaded track.
+  fn start_audio_analysis(&mut self, track: LoadedTrack) {
+    self.sample_rate = track.sample_rate;
+    self.channels = track.channels;
+    {
+      let mut settings = self.analysis_settings.lock().unwrap();
+      settings.channels = self.channels;
+      settings.sample_rate = self.sample_rate;
+    }
+
+    // Each track gets its own level distribution, integrated loudness and
+    // auto range
+    self.audio_data.lock().unwrap().clear();
+    self.auto_range.reset();
+    self.tempo.reset();
+    self.histogram.lock().unwrap().clear();
+    self.loudness.lock().unwrap().clear();
+
+    // Kick off the FFT thread
+    analysis::spawn(
+      track.samples,
+      self.analysis_settings.clone(),
+      self.audio_data.clone(),
+      self.histogram.clone(),
+      self.loudness.clone(),
+      self.shm.clone(),
+    );
+  }
+
+  /// The last `waveform_window_ms` of raw samples, mixed down to mono.
+  pub(crate) fn waveform_samples(&self) -> Vec<f32> {
+    let channels = self.channels.max(1) as usize;
+    let frames = (self.visuals.waveform_window_ms / 1000.0 * self.sample_rate as f32) as usize;
+
+    let ring = self.waveform.lock().unwrap();
+    let start = ring.len().saturating_sub(frames * channels);
+    let recent: Vec<f32> = ring.range(start..).cloned().collect();
+    drop(ring);
+
+    recent.chunks(channels).map(|frame| frame.iter().sum::<f32>() / frame.len() as f32).collect()
+  }
+
+  fn update_frequency_data(&mut self, frame: AnalysisFrame) {
+    // Group frequencies into bars for visualization
+    // self.frequency_data = self.group_frequencies_into_bars(magnitudes);
+    self.auto_range.update(&frame.mixed(), frame.elapsed);
+    self.tempo.update(&frame.mixed(), frame.elapsed);
+    let decibels = self.decibels();
+
+    // Keep a column of the waterfall for the spectrogram view
+    let column = self
+      .visuals
+      .frequency_scale
+      .bin(&frame.mixed(), self.sample_rate, SPECTROGRAM_ROWS)
+      .into_iter()
+      .map(|raw| decibels.normalise(raw))
+      .collect();
+    self.spectrogram.push_back(column);
+    self.trim_spectrogram();
+
+    if self.visuals.shows(VisualStyle::Particles) {
+      self.particles.listen(&self.visuals.emitters, &frame.mixed(), self.sample_rate, decibels);
+    }
+
+    self.energy.update(
+      &frame.mixed(),
+      self.sample_rate,
+      decibels,
+      &self.visuals.smoothing,
+      self.visuals.update_interval,
+    );
+
+    self.chroma.update(&frame.mixed(), self.sample_rate, decibels, self.visuals.update_interval);
+
+    let new_bars = self.group_frequencies_into_bars(&frame);
+    self.phase = frame.phase;
+    self.group_delay = frame.group_delay;
+    let calm = self.is_calm();
+    let smoothing = self.visuals.smoothing;
+    bars::smooth(
+      &mut self.frequency_data,
+      &new_bars,
+      self.sample_rate,
+      self.visuals.frequency_scale,
+      self.visuals.update_interval,
+      |region| if calm { CALM_ENVELOPE } else { smoothing.get(region) },
+    );
+
+    // The analysis thread shares the next frames the way they're now drawn
+    {
+      let mut shm = self.shm.lock().unwrap();
+      if shm.writer.is_some() {
+        shm.bar_count = self.bar_count();
+        shm.scale = self.visuals.frequency_scale;
+        shm.decibels = decibels;
+      }
+    }
+
+    self.canvas_cache.clear();
+  }
+
+  /// Moves the particle style on by one tick, spawning from the newest
+  /// spectrum only while `spawn`.
+  fn step_particles(&mut self, spawn: bool) {
+    if !self.visuals.shows(VisualStyle::Particles) {
+      self.particles.clear();
+      return;
+    }
+    let bands = match self.spectrogram.back() {
+      Some(column) if spawn => column.as_slice(),
+      _ => &[],
+    };
+    let share = self.degradation().particle_share();
+    self.particles.step(
+      bands,
+      &self.visuals.emitters,
+      self.visuals.update_interval.as_secs_f32(),
+      share,
+    );
+  }
+
+  fn trim_spectrogram(&mut self) {
+    let excess = self.spectrogram.len().saturating_sub(self.visuals.spectrogram_length as usize);
+    self.spectrogram.drain(..excess);
+  }
+
+  /// Puts up a seek the player had to give up on, which it's already logged.
+  fn warn_seek_error(&mut self) {
+    if let Some(e) = self.player.take_seek_error() {
+      self.warning = Some(e.summary());
+    }
+  }
+
+  fn group_frequencies_into_bars(&self, frame: &AnalysisFrame) -> Vec<f32> {
+    let levels = bars::levels(
+      &frame.spectra,
+      self.sample_rate,
+      self.bar_count(),
+      self.visuals.frequency_scale,
+      self.decibels(),
+    );
+    levels
+      .into_iter()
+      .map(|level| map_range(level, 0.0, 1.0, MIN_BAR_HEIGHT, MAX_BAR_HEIGHT))
+      .collect()
+  }
+
+  pub(crate) fn update(&mut self, message: Message) -> Command<Message> {
+    if self.recorder.is_recording() && message.is_recordable() {
+      self.recorder.record(self.player.position(), message.clone());
+    }
+
+    match message {
+      Message::Playback(message) => {
+        let was_playing = self.player.is_playing;
+        let is_stop = matches!(message, playback::Message::Stop);
+        // Frames queued from before a jump would play out over the new audio
+        if matches!(
+          message,
+          playback::Message::Stop
+            | playback::Message::Next
+            | playback::Message::Seek(_)
+            | playback::Message::SetLoopEnd
+        ) {
+          self.audio_data.lock().unwrap().clear();
+        }
+        match self.player.update(message) {
+          Ok(Some(track)) => self.start_audio_analysis(track),
+          Ok(None) => {}
+          Err(e) => {
+            eprintln!("Failed to start playback: {}", e);
+            self.warning = Some(e.summary());
+          }
+        }
+        self.warn_seek_error();
+        self.sync_latency();
+        // Feedback watch and recording only make sense on the live mic
+        if self.player.capture_source() != Some(CaptureSource::Microphone) {
+          self.feedback = None;
+          self.stop_mic_recording();
+        }
+        // Bars fall back down whenever playback halts
+        if self.player.is_playing {
+          self.is_decaying = false;
+        } else if was_playing || is_stop {
+          self.is_decaying = true;
+        }
+        self.canvas_cache.clear();
+        self.refresh_track()
+      }
+      Message::Analysis(message) => {
+        self.analysis_settings.lock().unwrap().apply(message);
+        Command::none()
+      }
+      Message::Visual(message) => {
+        if let Some(warning) = self.visuals.update(message) {
+          self.warning = Some(warning);
+        }
+        self.trim_spectrogram();
+        self.resize_bars();
+        self.canvas_cache.clear();
+        self.overview_cache.clear();
+        Command::none()
+      }
+      Message::Outputs(message) => self.update_outputs(message),
+      Message::Exports(message) => self.update_exports(message),
+      Message::Compare(message) => self.update_comparison(message),
+      Message::Measuring(message) => self.update_measuring(message),
+      Message::Track(message) => self.update_track(message),
+      Message::Looks(message) => self.update_looks(message),
+      Message::Windows(message) => self.update_windows(message),
+      Message::Display(message) => self.update_display(message),
+      Message::Preferences(message) => self.update_preferences(message),
+      // Beats don't pulse the visuals during speech
+      Message::Beat(_) if self.is_calm() => Command::none(),
+      Message::Beat(strength) => {
+        self.beat_pulse = 0.5 + 0.5 * strength;
+        self.tempo.on_beat();
+        if let Some(osc) = &self.osc {
+          osc.send_beat(strength);
+        }
+        if let Some(midi) = &mut self.midi {
+          midi.send_beat(strength);
+        }
+        if let Some(rumble) = &mut self.rumble {
+          rumble.send_beat(strength);
+        }
+        self.canvas_cache.clear();
+        Command::none()
+      }
+      Message::ToggleMacroRecording => {
+        if self.recorder.is_recording() {
+          self.recorder.stop();
+        } else {
+          self.recorder.start_recording();
+        }
+        Command::none()
+      }
+      Message::ToggleMacroReplay => {
+        if self.recorder.is_replaying() {
+          self.recorder.stop();
+          return Command::none();
+        }
+        // Replay always runs against the track from the top
+        if self.player.is_loaded {
+          if let Err(e) = self.player.seek(Duration::ZERO) {
+            eprintln!("Failed to rewind for macro replay: {}", e);
+            self.warning = Some(PlaybackError::Seek(e).summary());
+          }
+          self.recorder.start_replay();
+          return Command::done(Message::Playback(playback::Message::Play));
+        }
+        Command::none()
+      }
+      Message::DismissWarning => {
+        self.warning = None;
+        Command::none()
+      }
+      Message::KeyPressed(key) => {
+        let Some(name) = keymap::key_name(&key) else {
+          return Command::none();
+        };
+        // Escape cancels a rebind rather than becoming a shortcut
+        if let Some(action) = self.rebinding.take() {
+          if name != "Escape" {
+            self.keymap.bind(action, name);
+          }
+          return Command::none();
+        }
+        match self.keymap.action(&name) {
+          Some(action) => match self.shortcut(action) {
+            Some(message) => self.update(message),
+            None => Command::none(),
+          },
+          // Escape always leaves presentation mode
+          None if self.presenting && name == "Escape" => {
+            self.update(Message::Windows(windows::Message::TogglePresentation))
+          }
+          // Unbound number keys switch straight to a preset
+          None => match name.parse::<usize>() {
+            Ok(number @ 1..=HOTKEY_PRESETS) => {
+              self.update(Message::Looks(looks::Message::LoadPreset(number - 1)))
+            }
+            _ => Command::none(),
+          },
+        }
+      }
+      Message::AudioData(data) => {
+        self.update_frequency_data(AnalysisFrame {
+          spectra: vec![data],
+          produced_at: Instant::now(),
+          elapsed: self.visuals.update_interval,
+          beat: None,
+          phase: Vec::new(),
+          group_delay: Vec::new(),
+        });
+        // self.canvas_cache.clear();
+        Command::none()
+      }
+      // The mini window redraws too, which would tick twice a frame
+      Message::Frame(id, _) if Some(id) != self.main_window => Command::none(),
+      Message::Frame(_, at) => {
+        // Each uncapped tick moves on by as long as the last frame took
+        if let Some(last) = self.last_frame.replace(at) {
+          self.visuals.update_interval = at.saturating_duration_since(last).min(MAX_FRAME_INTERVAL);
+        }
+        self.update(Message::Tick)
+      }
+      Message::Tick => self.advance(),
+    }
+  }
+
+  /// Moves everything on by one tick: the playing audio's next frame, or
+  /// the bars falling back down once it's stopped.
+  fn advance(&mut self) -> Command<Message> {
+    self.tick += 1;
+    self.animation_time += self.visuals.update_interval.as_secs_f32();
+    self.spin_time += self.visuals.update_interval.as_secs_f32() * self.spin_rate();
+
+    if self.player.is_playing {
+      self.note_listening(self.visuals.update_interval);
+      let (track, _) = self.player.track();
+      self.player.advance();
+
+      // scope the lock so it's dropped before we call update_frequency_data
+      let maybe_frame = self.audio_data.lock().unwrap().take_due(self.now());
+
+      let mut messages = Vec::new();
+      if self.player.track().0 != track {
+        messages.push(Message::Track(track::Message::ReadCoverArt));
+        messages.push(Message::Track(track::Message::ReadMetadata));
+        messages.push(Message::Track(track::Message::AnalyseOutline));
+      }
+      if let Some(frame) = maybe_frame {
+        if let Some(strength) = frame.beat {
+          messages.push(Message::Beat(strength));
+        }
+        if let Some(log) = &mut self.inspector {
+          log.record(&frame);
+        }
+        self.classifier.process(&frame.mixed(), frame.produced_at);
+        if let Some(detector) = &mut self.feedback {
+          detector.process(&frame.mixed(), self.sample_rate, frame.produced_at);
+        }
+        self.update_frequency_data(frame);
+      }
+      self.update_peaks();
+      self.push_trail();
+      self.sync_comparison();
+      self.step_particles(true);
+      self.limit_strobes();
+      if let Some(cost) = self.draw_cost.take()
+        && self.budget.record(self.visuals.style, cost, self.visuals.update_interval)
+      {
+        self.resize_bars();
+      }
+      self.send_osc_bars();
+      if let Some(midi) = &mut self.midi {
+        midi.update(&self.energy);
+      }
+      if let Some(rumble) = &mut self.rumble {
+        rumble.update(&self.energy);
+      }
+      if self.visuals.shows(VisualStyle::Terrain) {
+        self.camera.follow(self.energy.overall(), self.visuals.update_interval.as_secs_f32());
+      }
+      self.canvas_cache.clear();
+
+      // Let the last beat's pulse fade out
+      if self.beat_pulse > 0.0 {
+        self.beat_pulse *= bars::fade(BEAT_PULSE_DECAY, self.visuals.update_interval);
+        if self.beat_pulse < 0.01 {
+          self.beat_pulse = 0.0;
+        }
+        self.canvas_cache.clear();
+      }
+
+      // Jitter keeps moving even when the spectrum doesn't
+      if self.visuals.noise_intensity > 0.0 {
+        self.canvas_cache.clear();
+      }
+
+      // Fire any recorded actions the playback has caught up with
+      messages.extend(self.recorder.due(self.player.position()));
+      if !messages.is_empty() {
+        return Command::batch(messages.into_iter().map(Command::done));
+      }
+    } else if self.is_decaying {
+      let any_above_min =
+        bars::decay(&mut self.frequency_data, MIN_BAR_HEIGHT, self.visuals.update_interval);
+
+      self.step_particles(false);
+      self.push_trail();
+      self.send_osc_bars();
+      let decibels = self.decibels();
+      self.energy.update(
+        &[],
+        self.sample_rate,
+        decibels,
+        &self.visuals.smoothing,
+        self.visuals.update_interval,
+      );
+      if let Some(midi) = &mut self.midi {
+        midi.update(&self.energy);
+      }
+      if let Some(rumble) = &mut self.rumble {
+        rumble.update(&self.energy);
+      }
+      self.chroma.update(&[], self.sample_rate, decibels, self.visuals.update_interval);
+      self.limit_strobes();
+
+      // Keep ticking until the peak markers, particles, energy and
+      // chroma have come down too
+      if !self.update_peaks()
+        && !any_above_min
+        && self.particles.is_empty()
+        && self.energy.is_silent()
+        && self.chroma.is_silent()
+      {
+        self.is_decaying = false;
+      }
+
+      self.canvas_cache.clear();
+    }
+
+    Command::none()
+  }
+}
+
+impl Default for AudioVisualizer {
+  fn default() -> Self {
+    let waveform = Arc::new(Mutex::new(VecDeque::with_capacity(WAVEFORM_CAPACITY)));
+    Self {
+      player: Player::new(waveform.clone()),
+      is_decaying: false,
+      audio_data: Arc::new(Mutex::new(FrameQueue::default())),
+      frequency_data: vec![MIN_BAR_HEIGHT; DEFAULT_NUM_BARS],
+      peak_data: vec![MIN_BAR_HEIGHT; DEFAULT_NUM_BARS],
+      peak_hold: vec![Duration::ZERO; DEFAULT_NUM_BARS],
+      trail: VecDeque::with_capacity(TRAIL_FRAMES),
+      tick: 0,
+      clock: None,
+      animation_time: 0.0,
+      last_frame: None,
+      canvas_cache: canvas::Cache::default(),
+      overview_cache: canvas::Cache::default(),
+      analysis_settings: Arc::new(Mutex::new(AnalysisSettings::default())),
+      visuals: VisualSettings::default(),
+      sample_rate: DEFAULT_SAMPLE_RATE,
+      channels: 2,
+      waveform,
+      spectrogram: VecDeque::new(),
+      particles: ParticleSystem::default(),
+      chroma: Chroma::default(),
+      auto_range: AutoRange::default(),
+      tempo: TempoTracker::default(),
+      spin_time: 0.0,
+      histogram: Arc::new(Mutex::new(AmplitudeHistogram::default())),
+      loudness: Arc::new(Mutex::new(LoudnessMeter::default())),
+      show_meters: true,
+      energy: BandEnergy::default(),
+      show_energy: false,
+      show_frequency_labels: false,
+      phase: Vec::new(),
+      group_delay: Vec::new(),
+      recorder: MacroRecorder::default(),
+      mic_recording: None,
+      beat_pulse: 0.0,
+      inspector: None,
+      show_settings: false,
+      measurement: None,
+      is_exporting: false,
+      save_stem: false,
+      is_exporting_markers: false,
+      clip_start: None,
+      clip_end: None,
+      clip_fade: 0.0,
+      is_exporting_clip: false,
+      comparison: None,
+      is_loading_comparison: false,
+      encode_settings: EncodeSettings::default(),
+      osc_settings: OscSettings::default(),
+      osc_host: OscSettings::default().host,
+      osc_port: OscSettings::default().port.to_string(),
+      osc: None,
+      shm_settings: ShmSettings::default(),
+      shm: Arc::new(Mutex::new(ShmOutput::default())),
+      midi_settings: MidiSettings::default(),
+      midi: None,
+      midi_ports: Vec::new(),
+      rumble_settings: RumbleSettings::default(),
+      rumble: None,
+      rumble_devices: Vec::new(),
+      remote_settings: RemoteSettings::default(),
+      remote_port: RemoteSettings::default().port.to_string(),
+      remote: None,
+      tray_settings: TraySettings::default(),
+      tray: None,
+      plugins: Vec::new(),
+      plugin: 0,
+      presets: PresetLibrary::default(),
+      preset_name: String::new(),
+      snapshot_resolution: Resolution::default(),
+      impulse: None,
+      is_measuring_impulse: false,
+      feedback: None,
+      classifier: ContentClassifier::default(),
+      speech_gate: SpeechGate::default(),
+      acoustid_key: String::new(),
+      identified: None,
+      is_identifying: false,
+      tag_review: None,
+      cover_source: None,
+      metadata_source: None,
+      metadata: None,
+      lyrics_source: None,
+      lyrics: None,
+      outline_source: None,
+      outline: None,
+      is_analysing_grids: false,
+      ungridded: HashSet::new(),
+      is_measuring_loudness: false,
+      unmeasurable: HashSet::new(),
+      presenting: false,
+      present_fullscreen: true,
+      strobe_safety: true,
+      pulse_limiter: StrobeLimiter::default(),
+      budget: PerformanceBudget::default(),
+      draw_cost: Cell::new(None),
+      background_limiter: StrobeLimiter::default(),
+      is_fullscreen: false,
+      cursor_moved_at: None,
+      main_window: None,
+      mini_window: None,
+      window_geometry: WindowGeometry::default(),
+      mini_hides_main: false,
+      session_settings: SessionSettings::default(),
+      stats: ListeningStats::default(),
+      show_stats: false,
+      listening: None,
+      graphics_settings: GraphicsSettings::default(),
+      software_requested: false,
+      warning: None,
+      camera: Camera::default(),
+      keymap: Keymap::default(),
+      rebinding: None,
+    }
+  }
+}
+
+/// Opens the visualiser, with the debug inspector if `inspector`.
+pub(crate) fn run(inspector: bool, graphics: GraphicsSettings) -> iced::Result {
+  // A daemon, so the mini window can open beside the main one
+  iced::daemon(AudioVisualizer::title, AudioVisualizer::update, AudioVisualizer::view)
+    .subscription(AudioVisualizer::subscription)
+    .run_with(move || AudioVisualizer::new(inspector, graphics))
+}
@@ -0,0 +1,347 @@
+//! Where the analysis goes besides the screen: OSC, shared memory, MIDI,
+//! controller rumble, the phone remote and the tray icon.
+
+use iced::Task as Command;
+use std::{net::SocketAddr, path::Path};
+
+use super::Message::{Looks, Outputs, Playback, Windows};
+use super::{AudioVisualizer, looks, windows};
+use crate::config::Config;
+use crate::midi::{self, MidiSender, MidiSettings};
+use crate::osc::{self, OscSender, OscSettings};
+use crate::playback;
+use crate::remote::{self, REMOTE_BARS, Remote, RemoteCommand, RemoteSettings, RemoteState};
+use crate::rumble::{self, RumbleSender, RumbleSettings};
+use crate::shm::{ShmSettings, ShmWriter};
+use crate::tray::{Tray, TrayAction, TraySettings, TrayState};
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  OscSettingsChanged(OscSettings),
+  OscHostEdited(String),
+  OscPortEdited(String),
+  /// Aims OSC at the host and port as typed.
+  ApplyOscTarget,
+  /// Where the host of these settings was found, to open the socket to.
+  OscResolved(OscSettings, Result<SocketAddr, String>),
+  ShmSettingsChanged(ShmSettings),
+  MidiSettingsChanged(MidiSettings),
+  RumbleSettingsChanged(RumbleSettings),
+  RemoteSettingsChanged(RemoteSettings),
+  RemotePortEdited(String),
+  /// Moves the remote to the port as typed, if it is one.
+  ApplyRemotePort,
+  /// Shows the current state on the phone remote and runs what it sent.
+  RemotePoll,
+  TraySettingsChanged(TraySettings),
+  /// Shows the track playing in the tray menu and runs what was clicked.
+  TrayPoll,
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_outputs(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::OscSettingsChanged(settings) => self.set_osc_settings(settings),
+      Message::OscHostEdited(host) => {
+        self.osc_host = host;
+        Command::none()
+      }
+      Message::OscPortEdited(port) => {
+        self.osc_port = port;
+        Command::none()
+      }
+      Message::ApplyOscTarget => {
+        let host = self.osc_host.trim().to_string();
+        // Like the seed, the port only changes once it parses
+        let port = self.osc_port.trim().parse().unwrap_or(self.osc_settings.port);
+        self.set_osc_settings(OscSettings { host, port, ..self.osc_settings.clone() })
+      }
+      Message::OscResolved(settings, target) => {
+        // Settings changed again while this one was being looked up
+        if settings != self.osc_settings || !settings.enabled {
+          return Command::none();
+        }
+        match target
+          .and_then(|target| OscSender::open(&settings, target).map_err(|e| e.to_string()))
+        {
+          Ok(osc) => self.osc = Some(osc),
+          Err(e) => {
+            eprintln!("Failed to open OSC to {}:{}: {}", settings.host, settings.port, e);
+            self.warning =
+              Some(format!("Couldn't open OSC to {}:{}", settings.host, settings.port));
+          }
+        }
+        Command::none()
+      }
+      Message::ShmSettingsChanged(settings) => {
+        self.set_shm_settings(settings);
+        Command::none()
+      }
+      Message::MidiSettingsChanged(settings) => {
+        self.set_midi_settings(settings);
+        Command::none()
+      }
+      Message::RumbleSettingsChanged(settings) => {
+        self.set_rumble_settings(settings);
+        Command::none()
+      }
+      Message::RemoteSettingsChanged(settings) => {
+        self.set_remote_settings(settings);
+        Command::none()
+      }
+      Message::RemotePortEdited(port) => {
+        self.remote_port = port;
+        Command::none()
+      }
+      Message::ApplyRemotePort => {
+        if let Some(port) = remote::parse_port(&self.remote_port) {
+          self.set_remote_settings(RemoteSettings { port, ..self.remote_settings.clone() });
+        }
+        Command::none()
+      }
+      Message::RemotePoll => {
+        let Some(remote) = &self.remote else {
+          return Command::none();
+        };
+        remote.publish(self.remote_state());
+        let commands = remote.commands();
+        let tasks: Vec<_> = commands
+          .into_iter()
+          .map(|command| {
+            let message = match command {
+              RemoteCommand::Play => Playback(playback::Message::Play),
+              RemoteCommand::Pause => Playback(playback::Message::Pause),
+              RemoteCommand::Stop => Playback(playback::Message::Stop),
+              RemoteCommand::ToggleMute => Playback(playback::Message::ToggleMute),
+              RemoteCommand::Volume { value } => {
+                Playback(playback::Message::VolumeChanged(value.clamp(0.0, 1.0)))
+              }
+              RemoteCommand::Preset { index } => Looks(looks::Message::LoadPreset(index)),
+            };
+            self.update(message)
+          })
+          .collect();
+        Command::batch(tasks)
+      }
+      Message::TraySettingsChanged(settings) => {
+        self.set_tray_settings(settings);
+        Command::none()
+      }
+      Message::TrayPoll => {
+        let state = self.tray_state();
+        let Some(tray) = &mut self.tray else {
+          return Command::none();
+        };
+        tray.show(state);
+        let actions = tray.actions();
+        let tasks: Vec<_> = actions
+          .into_iter()
+          .map(|action| match action {
+            TrayAction::PlayPause if self.player.is_playing => {
+              self.update(Playback(playback::Message::Pause))
+            }
+            TrayAction::PlayPause => self.update(Playback(playback::Message::Play)),
+            TrayAction::Stop => self.update(Playback(playback::Message::Stop)),
+            TrayAction::Next => self.update(Playback(playback::Message::Next)),
+            TrayAction::Show => self.update(Windows(windows::Message::ShowMainWindow)),
+            TrayAction::Quit => self.quit(),
+          })
+          .collect();
+        Command::batch(tasks)
+      }
+    }
+  }
+
+  /// Opens or closes the OSC, shared-memory, MIDI and rumble outputs as
+  /// `config` has them.
+  pub(crate) fn set_outputs(&mut self, config: &Config) -> Command<super::Message> {
+    self.set_shm_settings(config.shm.clone());
+    self.set_midi_settings(config.midi.clone());
+    self.set_rumble_settings(config.rumble.clone());
+    self.set_remote_settings(config.remote.clone());
+    self.set_tray_settings(config.tray);
+    self.set_osc_settings(config.osc.clone())
+  }
+
+  /// Closes the OSC socket for new settings and, while OSC is on, looks the
+  /// host up in the background for [`Message::OscResolved`] to reopen it.
+  fn set_osc_settings(&mut self, settings: OscSettings) -> Command<super::Message> {
+    if settings == self.osc_settings && (self.osc.is_some() == settings.enabled) {
+      return Command::none();
+    }
+    self.osc = None;
+    self.osc_host = settings.host.clone();
+    self.osc_port = settings.port.to_string();
+    self.osc_settings = settings.clone();
+    if !settings.enabled {
+      return Command::none();
+    }
+    let (host, port) = (settings.host.clone(), settings.port);
+    Command::perform(
+      async move {
+        tokio::task::spawn_blocking(move || osc::resolve(&host, port).map_err(|e| e.to_string()))
+          .await
+          .unwrap_or_else(|e| Err(e.to_string()))
+      },
+      move |target| Outputs(Message::OscResolved(settings.clone(), target)),
+    )
+  }
+
+  /// Maps the shared-memory file for new settings, or unmaps it when the
+  /// output is off.
+  fn set_shm_settings(&mut self, settings: ShmSettings) {
+    let mut shm = self.shm.lock().unwrap();
+    if settings == self.shm_settings && (shm.writer.is_some() == settings.enabled) {
+      return;
+    }
+    shm.writer = None;
+    if settings.enabled {
+      match ShmWriter::open(&settings) {
+        Ok(writer) => shm.writer = Some(writer),
+        Err(e) => {
+          eprintln!("Failed to map shared memory at {}: {}", settings.path, e);
+          self.warning = Some(format!("Couldn't share frames at {}", settings.path));
+        }
+      }
+    }
+    drop(shm);
+    self.shm_settings = settings;
+  }
+
+  /// Reconnects MIDI for new settings, or disconnects it when MIDI is off.
+  fn set_midi_settings(&mut self, settings: MidiSettings) {
+    // Outputs come and go with devices, so look again on switching on
+    if settings.enabled && !self.midi_settings.enabled {
+      self.midi_ports = midi::ports();
+    }
+    if settings == self.midi_settings && (self.midi.is_some() == settings.enabled) {
+      return;
+    }
+    self.midi = None;
+    if settings.enabled {
+      match MidiSender::open(&settings) {
+        Ok(midi) => self.midi = Some(midi),
+        Err(e) => {
+          eprintln!("Failed to open MIDI output: {}", e);
+          self.warning = Some(format!("Couldn't open MIDI output: {}", e));
+        }
+      }
+    }
+    self.midi_settings = settings;
+  }
+
+  /// Starts rumbling the picked controllers for new settings, or stops when
+  /// rumble is off.
+  fn set_rumble_settings(&mut self, settings: RumbleSettings) {
+    // Controllers come and go too
+    if settings.enabled && !self.rumble_settings.enabled {
+      self.rumble_devices = rumble::devices();
+    }
+    if settings == self.rumble_settings && (self.rumble.is_some() == settings.enabled) {
+      return;
+    }
+    self.rumble = None;
+    if settings.enabled {
+      match RumbleSender::open(&settings) {
+        Ok(rumble) => self.rumble = Some(rumble),
+        Err(e) => {
+          eprintln!("Failed to start rumble: {}", e);
+          self.warning = Some(format!("Couldn't start rumble: {}", e));
+        }
+      }
+    }
+    self.rumble_settings = settings;
+  }
+
+  /// Starts the phone remote's server for new settings, or stops it when
+  /// the remote is off.
+  fn set_remote_settings(&mut self, settings: RemoteSettings) {
+    if settings == self.remote_settings && (self.remote.is_some() == settings.enabled) {
+      return;
+    }
+    // Freed before the new one binds, in case the port stays the same
+    self.remote = None;
+    self.remote_port = settings.port.to_string();
+    if settings.enabled {
+      match Remote::start(&settings) {
+        Ok(remote) => self.remote = Some(remote),
+        Err(e) => {
+          eprintln!("Failed to serve the remote on port {}: {}", settings.port, e);
+          self.warning = Some(format!("Couldn't serve the remote on port {}", settings.port));
+        }
+      }
+    }
+    self.remote_settings = settings;
+  }
+
+  /// Puts the icon in the tray for new settings, or hides it when the tray
+  /// is off. It's only built the first time it's wanted and kept after.
+  fn set_tray_settings(&mut self, settings: TraySettings) {
+    if settings.enabled && self.tray.is_none() {
+      match Tray::new() {
+        Ok(tray) => self.tray = Some(tray),
+        Err(e) => {
+          eprintln!("Failed to add the tray icon: {}", e);
+          self.warning = Some(format!("Couldn't add the tray icon: {}", e));
+        }
+      }
+    }
+    if let Some(tray) = &mut self.tray {
+      tray.set_visible(settings.enabled);
+    }
+    self.tray_settings = settings;
+  }
+
+  /// Whether the icon's in the tray now.
+  pub(super) fn in_tray(&self) -> bool {
+    self.tray.as_ref().is_some_and(Tray::is_visible)
+  }
+
+  /// Sends the bars, as 0.0..=1.0, to OSC when it's on.
+  pub(super) fn send_osc_bars(&self) {
+    if let Some(osc) = &self.osc {
+      osc.send_bars(&self.bar_levels());
+    }
+  }
+
+  /// What the phone remote shows, with the bars averaged down to fit it.
+  fn remote_state(&self) -> RemoteState {
+    let levels = self.bar_levels();
+    let group = levels.len().div_ceil(REMOTE_BARS).max(1);
+    RemoteState {
+      title: self
+        .player
+        .file_path()
+        .and_then(|path| Path::new(path).file_stem())
+        .map(|stem| stem.to_string_lossy().into_owned()),
+      is_loaded: self.player.is_loaded,
+      is_playing: self.player.is_playing,
+      volume: self.player.volume,
+      is_muted: self.player.is_muted,
+      presets: self.presets.presets.iter().map(|preset| preset.name.clone()).collect(),
+      spectrum: levels
+        .chunks(group)
+        .map(|chunk| chunk.iter().sum::<f32>() / chunk.len() as f32)
+        .collect(),
+    }
+  }
+
+  /// What the tray menu shows: the playing file's title tag, or its name
+  /// when it has none.
+  fn tray_state(&self) -> TrayState {
+    let path = self.player.file_path();
+    let tagged = self
+      .metadata
+      .as_ref()
+      .filter(|_| path.is_some() && self.metadata_source.as_deref() == path)
+      .map(|metadata| metadata.tags.title.trim())
+      .filter(|title| !title.is_empty());
+    let title = match tagged {
+      Some(title) => Some(title.to_string()),
+      None => path
+        .and_then(|path| Path::new(path).file_stem())
+        .map(|stem| stem.to_string_lossy().into_owned()),
+    };
+    TrayState { title, is_playing: self.player.is_playing }
+  }
+}
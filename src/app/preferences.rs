@@ -0,0 +1,125 @@
+//! Saving and resetting the config, restoring the last session, the
+//! graphics backend and rebinding shortcuts.
+
+use iced::Task as Command;
+use std::time::Duration;
+
+use super::AudioVisualizer;
+use crate::config::Config;
+use crate::graphics::{self, GraphicsSettings};
+use crate::keymap::Action;
+use crate::playback::{self, OutputDevice};
+use crate::session::{Session, SessionSettings};
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  SessionSettingsChanged(SessionSettings),
+  /// Reopens what was playing when the app was last closed.
+  RestoreSession,
+  /// Takes effect after a restart.
+  GraphicsSettingsChanged(GraphicsSettings),
+  /// What iced drew the first window with.
+  GraphicsInformation(iced::system::Information),
+  SaveConfig,
+  /// Restores the built-in defaults (the file is only touched on save).
+  ResetConfig,
+  /// Waits for the next key press and binds it to the action.
+  RebindKey(Action),
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_preferences(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::SessionSettingsChanged(settings) => {
+        self.session_settings = settings;
+        Command::none()
+      }
+      Message::RestoreSession => {
+        let Some(session) = Session::load() else {
+          return Command::none();
+        };
+        if let Some(visuals) = &session.visuals {
+          self.visuals.apply_config(visuals, &mut self.analysis_settings.lock().unwrap());
+          self.trim_spectrogram();
+          self.resize_bars();
+        }
+        self.player.volume = session.volume.max(0.0);
+        self.player.is_muted = session.muted;
+        let position = Duration::from_secs_f32(session.position_secs.max(0.0));
+        match self.player.resume(session.playlist, session.track, position) {
+          Ok(Some(track)) => self.start_audio_analysis(track),
+          Ok(None) => return Command::none(),
+          Err(e) => {
+            eprintln!("Failed to restore the session: {}", e);
+            self.warning = Some(e.summary());
+            return Command::none();
+          }
+        }
+        self.warn_seek_error();
+        self.sync_latency();
+        let play = (session.playing && self.session_settings.resume_playing)
+          .then(|| Command::done(super::Message::Playback(playback::Message::Play)));
+        Command::batch([self.refresh_track()].into_iter().chain(play))
+      }
+      Message::GraphicsSettingsChanged(settings) => {
+        self.graphics_settings = settings;
+        Command::none()
+      }
+      Message::GraphicsInformation(information) => {
+        if graphics::is_software(&information) && !self.software_requested {
+          self.warning = Some(
+            "Hardware acceleration isn't available, so drawing falls back to the slower \
+             software renderer. Try another backend under Graphics in the settings."
+              .to_string(),
+          );
+        }
+        Command::none()
+      }
+      Message::SaveConfig => {
+        let mut config = self.visuals.to_config(&self.analysis_settings.lock().unwrap());
+        config.keymap = self.keymap.clone();
+        config.crossfade_seconds = self.player.crossfade.as_secs_f32();
+        config.auto_dj = self.player.auto_dj;
+        config.normalise = self.player.normalise;
+        config.equalizer = self.player.equalizer;
+        config.suggest = self.suggest_settings;
+        config.output_latency_ms = self.player.output_latency.as_secs_f32() * 1000.0;
+        config.output_device = match &self.player.output_device {
+          OutputDevice::Default => String::new(),
+          OutputDevice::Named(name) => name.clone(),
+        };
+        config.speech_gate = self.speech_gate;
+        config.acoustid_key = self.acoustid_key.clone();
+        config.encode = self.encode_settings;
+        config.osc = self.osc_settings.clone();
+        config.shm = self.shm_settings.clone();
+        config.midi = self.midi_settings.clone();
+        config.rumble = self.rumble_settings.clone();
+        config.remote = self.remote_settings.clone();
+        config.tray = self.tray_settings;
+        config.present_fullscreen = self.present_fullscreen;
+        config.strobe_safety = self.strobe_safety;
+        config.mini_hides_main = self.mini_hides_main;
+        config.session = self.session_settings;
+        config.graphics = self.graphics_settings;
+        config.window = self.saved_geometry();
+        config.plugin =
+          self.active_plugin().map_or_else(String::new, |plugin| plugin.name().to_string());
+        if let Err(e) = config.save() {
+          eprintln!("Failed to save config: {}", e);
+          self.warning = Some(format!("Couldn't save the settings: {}", e));
+        }
+        Command::none()
+      }
+      Message::ResetConfig => {
+        let config = Config::default();
+        self.apply_config(&config);
+        self.set_outputs(&config)
+      }
+      Message::RebindKey(action) => {
+        self.rebinding = Some(action);
+        Command::none()
+      }
+    }
+  }
+}
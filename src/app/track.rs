@@ -0,0 +1,451 @@
+//! What's read and worked out per track: cover art, tags, lyrics, the
+//! outline, beat grids and loudness, and identifying it.
+
+use iced::Task as Command;
+use std::path::Path;
+
+use super::{AudioVisualizer, Message::Track};
+use crate::autodj::BeatGrid;
+use crate::components::{backdrop::Backdrop, equalizer::EqSettings, gradient::Palette, palette};
+use crate::identify::{self, TrackInfo};
+use crate::lyrics::Lyrics;
+use crate::normalise::{self, NormaliseSettings};
+use crate::outline::TrackOutline;
+use crate::playback::CaptureSource;
+use crate::tags::{self, Metadata, TagField, TagReview, Tags};
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  /// Fingerprints the playing file or system audio and looks it up on AcoustID.
+  Identify,
+  Identified(Option<String>, Result<TrackInfo, String>),
+  TagReviewEdited(TagField, String),
+  /// Writes the reviewed tags into the file.
+  WriteTags,
+  DismissTagReview,
+  /// Turns the auto-DJ on or off; turning it on analyses any tracks it
+  /// hasn't got beat grids for.
+  AutoDjToggled(bool),
+  /// Each analysed track's grid, or `None` where it had no steady beat.
+  GridsAnalysed(Vec<(String, Option<BeatGrid>)>),
+  /// Turning normalisation on measures any tracks not measured yet.
+  NormaliseChanged(NormaliseSettings),
+  EqualizerChanged(EqSettings),
+  /// Each measured track's loudness in LUFS, or `None` where it couldn't
+  /// be measured.
+  LoudnessMeasured(Vec<(String, Option<f32>)>),
+  /// Reads the playing track's cover art for a matching gradient and backdrop.
+  ReadCoverArt,
+  CoverArtRead(String, Option<Palette>, Option<Backdrop>),
+  /// Reads the playing track's tags and length, for the header.
+  ReadMetadata,
+  MetadataRead(String, Option<Metadata>),
+  LyricsLoaded(String, Option<Lyrics>),
+  /// Finds the vocals and sections of the playing track, for the timeline.
+  AnalyseOutline,
+  OutlineAnalysed(String, Option<TrackOutline>),
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_track(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::Identify => {
+        let key = self.acoustid_key.clone();
+        let file = match self.player.capture_source() {
+          None => match self.player.file_path() {
+            Some(path) => Some(path.to_string()),
+            None => return Command::none(),
+          },
+          Some(CaptureSource::System) => None,
+          Some(CaptureSource::Microphone) => return Command::none(),
+        };
+        self.is_identifying = true;
+        // Decoding, recording and the lookup all block
+        Command::perform(
+          {
+            let file = file.clone();
+            async move {
+              tokio::task::spawn_blocking(move || {
+                match &file {
+                  Some(path) => identify::identify_file(Path::new(path), &key),
+                  None => identify::identify_system(&key),
+                }
+                .map_err(|e| e.to_string())
+              })
+              .await
+              .unwrap_or_else(|e| Err(e.to_string()))
+            }
+          },
+          move |result| Track(Message::Identified(file.clone(), result)),
+        )
+      }
+      Message::Identified(file, result) => {
+        self.is_identifying = false;
+        match result {
+          Ok(info) => {
+            // Files missing their basic tags get the match offered as a fix
+            if let Some(path) = &file {
+              match Tags::read(Path::new(path)) {
+                Ok(current) if current.is_poor() => {
+                  self.tag_review = Some(TagReview::new(path.clone(), current, &info));
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("Failed to read tags: {}", e),
+              }
+            }
+            self.identified = Some((file, info));
+          }
+          Err(e) => {
+            eprintln!("Failed to identify track: {}", e);
+            self.warning = Some(format!("Couldn't identify the track: {}", e));
+          }
+        }
+        Command::none()
+      }
+      Message::AutoDjToggled(on) => {
+        self.player.auto_dj = on;
+        self.analyse_grids()
+      }
+      Message::GridsAnalysed(grids) => {
+        self.is_analysing_grids = false;
+        for (path, grid) in grids {
+          match grid {
+            Some(grid) => self.player.grids.insert(path, grid),
+            None => {
+              self.ungridded.insert(path);
+            }
+          }
+        }
+        if let Err(e) = self.player.grids.save() {
+          eprintln!("Failed to save beat grids: {}", e);
+          self.warning = Some(format!("Couldn't save the beat grids: {}", e));
+        }
+        // The playlist may have changed while these were analysed
+        self.analyse_grids()
+      }
+      Message::NormaliseChanged(settings) => {
+        self.player.set_normalise(settings);
+        self.measure_loudness()
+      }
+      Message::EqualizerChanged(settings) => {
+        self.player.set_equalizer(settings);
+        self.canvas_cache.clear();
+        Command::none()
+      }
+      Message::LoudnessMeasured(measured) => {
+        self.is_measuring_loudness = false;
+        for (path, loudness) in measured {
+          match loudness {
+            Some(loudness) => self.player.set_loudness(path, loudness),
+            None => {
+              self.unmeasurable.insert(path);
+            }
+          }
+        }
+        self.measure_loudness()
+      }
+      Message::ReadCoverArt => self.refresh_cover_art(),
+      Message::ReadMetadata => self.refresh_metadata(),
+      Message::MetadataRead(path, metadata) => {
+        if self.metadata_source.as_deref() == Some(path.as_str()) {
+          self.metadata = metadata;
+        }
+        Command::none()
+      }
+      Message::LyricsLoaded(path, lyrics) => {
+        if self.lyrics_source.as_deref() == Some(path.as_str()) {
+          self.lyrics = lyrics;
+        }
+        Command::none()
+      }
+      Message::AnalyseOutline => self.refresh_outline(),
+      Message::OutlineAnalysed(path, outline) => {
+        if self.outline_source.as_deref() == Some(path.as_str()) {
+          self.outline = outline;
+          self.overview_cache.clear();
+        }
+        Command::none()
+      }
+      Message::CoverArtRead(path, palette, backdrop) => {
+        // A later track may have started while this one was read
+        if self.cover_source.as_deref() == Some(path.as_str()) {
+          self.visuals.set_track_palette(palette);
+          self.visuals.set_track_backdrop(backdrop);
+          self.canvas_cache.clear();
+          self.overview_cache.clear();
+        }
+        Command::none()
+      }
+      Message::TagReviewEdited(field, value) => {
+        if let Some(review) = &mut self.tag_review {
+          *review.proposed.get_mut(field) = value;
+        }
+        Command::none()
+      }
+      Message::WriteTags => {
+        if let Some(review) = self.tag_review.take()
+          && let Err(e) = review.write()
+        {
+          eprintln!("Failed to write tags: {}", e);
+          self.warning = Some(format!("Couldn't write the tags: {}", e));
+        }
+        Command::none()
+      }
+      Message::DismissTagReview => {
+        self.tag_review = None;
+        Command::none()
+      }
+    }
+  }
+
+  /// Identification needs a key and a file or system audio to fingerprint.
+  pub(crate) fn can_identify(&self) -> bool {
+    let has_source = match self.player.capture_source() {
+      None => self.player.is_loaded,
+      Some(CaptureSource::System) => true,
+      Some(CaptureSource::Microphone) => false,
+    };
+    has_source && !self.acoustid_key.is_empty() && !self.is_identifying
+  }
+
+  /// What's been identified about whatever is playing now.
+  pub(super) fn now_playing(&self) -> Option<&TrackInfo> {
+    let source = match self.player.capture_source() {
+      None => self.player.file_path(),
+      Some(CaptureSource::System) => None,
+      Some(CaptureSource::Microphone) => return None,
+    };
+    self.identified.as_ref().filter(|(file, _)| file.as_deref() == source).map(|(_, info)| info)
+  }
+
+  /// Catches everything read or analysed per track up with the one playing.
+  pub(super) fn refresh_track(&mut self) -> Command<super::Message> {
+    Command::batch([
+      self.refresh_cover_art(),
+      self.refresh_metadata(),
+      self.refresh_lyrics(),
+      self.refresh_outline(),
+      self.refresh_character(),
+      self.analyse_grids(),
+      self.measure_loudness(),
+    ])
+  }
+
+  /// Starts reading the cover art of the file playing now, if it changed.
+  /// Captures and files without art go back to the theme.
+  fn refresh_cover_art(&mut self) -> Command<super::Message> {
+    let source = match self.player.capture_source() {
+      None => self.player.file_path().map(str::to_string),
+      Some(_) => None,
+    };
+    if source == self.cover_source {
+      return Command::none();
+    }
+    self.cover_source = source.clone();
+    self.visuals.set_track_palette(None);
+    self.visuals.set_track_backdrop(None);
+    let Some(path) = source else {
+      return Command::none();
+    };
+
+    // Decoding the art would stall a frame or two
+    Command::perform(
+      {
+        let path = path.clone();
+        async move {
+          tokio::task::spawn_blocking(move || match tags::cover_art(Path::new(&path)) {
+            Ok(Some(bytes)) => {
+              let backdrop = Backdrop::from_bytes(&bytes)
+                .map_err(|e| eprintln!("Failed to decode cover art backdrop: {}", e))
+                .ok();
+              (palette::from_image(&bytes), backdrop)
+            }
+            Ok(None) => (None, None),
+            Err(e) => {
+              eprintln!("Failed to read cover art: {}", e);
+              (None, None)
+            }
+          })
+          .await
+          .unwrap_or((None, None))
+        }
+      },
+      move |(palette, backdrop)| Track(Message::CoverArtRead(path.clone(), palette, backdrop)),
+    )
+  }
+
+  /// Starts reading the tags of the file playing now, if it changed.
+  /// Captures have no header.
+  fn refresh_metadata(&mut self) -> Command<super::Message> {
+    let source = match self.player.capture_source() {
+      None => self.player.file_path().map(str::to_string),
+      Some(_) => None,
+    };
+    if source == self.metadata_source {
+      return Command::none();
+    }
+    self.metadata_source = source.clone();
+    self.metadata = None;
+    let Some(path) = source else {
+      return Command::none();
+    };
+
+    Command::perform(
+      {
+        let path = path.clone();
+        async move {
+          tokio::task::spawn_blocking(move || match Metadata::read(Path::new(&path)) {
+            Ok(metadata) => Some(metadata),
+            Err(e) => {
+              eprintln!("Failed to read tags: {}", e);
+              None
+            }
+          })
+          .await
+          .unwrap_or(None)
+        }
+      },
+      move |metadata| Track(Message::MetadataRead(path.clone(), metadata)),
+    )
+  }
+
+  /// Starts loading the lyrics of the file playing now, if it changed.
+  fn refresh_lyrics(&mut self) -> Command<super::Message> {
+    let source = match self.player.capture_source() {
+      None => self.player.file_path().map(str::to_string),
+      Some(_) => None,
+    };
+    if source == self.lyrics_source {
+      return Command::none();
+    }
+    self.lyrics_source = source.clone();
+    self.lyrics = None;
+    let Some(path) = source else {
+      return Command::none();
+    };
+
+    Command::perform(
+      {
+        let path = path.clone();
+        async move {
+          tokio::task::spawn_blocking(move || Lyrics::load(Path::new(&path))).await.unwrap_or(None)
+        }
+      },
+      move |lyrics| Track(Message::LyricsLoaded(path.clone(), lyrics)),
+    )
+  }
+
+  /// Starts analysing the outline of the file playing now, if it changed.
+  /// Captures have no timeline.
+  fn refresh_outline(&mut self) -> Command<super::Message> {
+    let source = match self.player.capture_source() {
+      None => self.player.file_path().map(str::to_string),
+      Some(_) => None,
+    };
+    if source == self.outline_source {
+      return Command::none();
+    }
+    self.outline_source = source.clone();
+    self.outline = None;
+    // Markers belong to the track they were set on
+    self.clip_start = None;
+    self.clip_end = None;
+    let Some(path) = source else {
+      return Command::none();
+    };
+
+    // Decoding the whole track takes a second or so
+    Command::perform(
+      {
+        let path = path.clone();
+        async move {
+          tokio::task::spawn_blocking(move || match TrackOutline::analyse(Path::new(&path)) {
+            Ok(outline) => Some(outline),
+            Err(e) => {
+              eprintln!("Failed to analyse track: {}", e);
+              None
+            }
+          })
+          .await
+          .unwrap_or(None)
+        }
+      },
+      move |outline| Track(Message::OutlineAnalysed(path.clone(), outline)),
+    )
+  }
+
+  /// Analyses the playlist tracks the auto-DJ has no beat grid for yet, one
+  /// batch at a time.
+  fn analyse_grids(&mut self) -> Command<super::Message> {
+    if !self.player.auto_dj || self.is_analysing_grids {
+      return Command::none();
+    }
+    let missing: Vec<String> = self
+      .player
+      .playlist()
+      .iter()
+      .filter(|path| self.player.grids.get(path).is_none() && !self.ungridded.contains(*path))
+      .cloned()
+      .collect();
+    if missing.is_empty() {
+      return Command::none();
+    }
+    self.is_analysing_grids = true;
+
+    // Decoding whole tracks takes a while
+    Command::perform(
+      async move {
+        tokio::task::spawn_blocking(move || {
+          missing
+            .into_iter()
+            .map(|path| match BeatGrid::analyse(Path::new(&path)) {
+              Ok(grid) => (path, Some(grid)),
+              Err(e) => {
+                eprintln!("Failed to analyse {}: {}", path, e);
+                (path, None)
+              }
+            })
+            .collect()
+        })
+        .await
+        .unwrap_or_default()
+      },
+      |grids| Track(Message::GridsAnalysed(grids)),
+    )
+  }
+
+  /// Measures how loud the playlist tracks not measured yet are, one batch
+  /// at a time, while normalisation is on.
+  fn measure_loudness(&mut self) -> Command<super::Message> {
+    if !self.player.normalise.enabled || self.is_measuring_loudness {
+      return Command::none();
+    }
+    let missing: Vec<String> =
+      self.player.unmeasured().filter(|path| !self.unmeasurable.contains(*path)).cloned().collect();
+    if missing.is_empty() {
+      return Command::none();
+    }
+    self.is_measuring_loudness = true;
+
+    // Tracks without gain tags are decoded whole
+    Command::perform(
+      async move {
+        tokio::task::spawn_blocking(move || {
+          missing
+            .into_iter()
+            .map(|path| match normalise::loudness(Path::new(&path)) {
+              Ok(loudness) => (path, Some(loudness)),
+              Err(e) => {
+                eprintln!("Failed to measure loudness of {}: {}", path, e);
+                (path, None)
+              }
+            })
+            .collect()
+        })
+        .await
+        .unwrap_or_default()
+      },
+      |measured| Track(Message::LoudnessMeasured(measured)),
+    )
+  }
+}
@@ -0,0 +1,339 @@
+//! What the app draws, and what it listens for between frames.
+
+use iced::{
+  Element, Length,
+  widget::{Canvas, button, column, container, mouse_area, row, shader, stack},
+  window,
+};
+use std::{path::Path, time::Duration};
+
+use super::{AudioVisualizer, Message, outputs, windows};
+use crate::compare::{self, CompareCanvas};
+use crate::components::{
+  energy::EnergyCanvas, loudness::MeterCanvas, overview::OverviewCanvas, postfx::PostFx,
+  terrain::Terrain, timeline::TimelineCanvas, visualiser::VisualStyle,
+};
+use crate::playback::CaptureSource;
+use crate::ui::{
+  self,
+  inspector::Snapshot,
+  scene::{Pane, Scene, SplitDirection},
+  settings::PanelInputs,
+};
+
+const METER_WIDTH: f32 = 110.0;
+const ENERGY_WIDTH: f32 = 240.0;
+const TIMELINE_HEIGHT: f32 = 24.0;
+const OVERVIEW_HEIGHT: f32 = 48.0;
+const COMPARE_HEIGHT: f32 = 160.0;
+/// How often the phone remote is brought up to date and its commands run.
+const REMOTE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the tray menu is brought up to date and its clicks run.
+const TRAY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+impl AudioVisualizer {
+  /// The scene, split between two styles when the split view is on.
+  fn visualizer(&self) -> Element<Message> {
+    let split = self.visuals.split;
+    if !split.enabled {
+      return self.pane(Pane::Main);
+    }
+    let first = (split.ratio * 100.0).round() as u16;
+    let (main, second) = (self.pane(Pane::Main), self.pane(Pane::Split));
+    match split.direction {
+      SplitDirection::Stacked => column![
+        container(main).height(Length::FillPortion(first)),
+        container(second).height(Length::FillPortion(100 - first)),
+      ]
+      .into(),
+      SplitDirection::SideBySide => row![
+        container(main).width(Length::FillPortion(first)),
+        container(second).width(Length::FillPortion(100 - first)),
+      ]
+      .into(),
+    }
+  }
+
+  /// The bars' effects over a pane, if it shows the bars and any are on.
+  pub(crate) fn postfx(&self, pane: Pane) -> Option<PostFx<'_>> {
+    let (style, gradient) = self.visuals.pane(pane);
+    let effects = self.visuals.effects;
+    (style == VisualStyle::Bars && effects.any()).then(|| PostFx {
+      frequency_data: &self.frequency_data,
+      trail: &self.trail,
+      layout: self.visuals.layout,
+      shape: self.visuals.shape(),
+      ring: self.visuals.ring,
+      time: self.spin_time(),
+      gradient,
+      pulse: self.pulse(),
+      energy: self.energy.overall(),
+      effects,
+      muted: self.player.is_muted,
+      pane,
+    })
+  }
+
+  /// One pane's scene, with the 3D terrain or the bars' effects drawn over
+  /// it by the GPU when they're called for.
+  fn pane(&self, pane: Pane) -> Element<Message> {
+    let scene =
+      Canvas::new(Scene { app: self, live: true, pane }).width(Length::Fill).height(Length::Fill);
+    if let Some(postfx) = self.postfx(pane) {
+      let postfx = shader(postfx).width(Length::Fill).height(Length::Fill);
+      return stack![scene, postfx].into();
+    }
+    let Some(terrain) = self.terrain(pane) else {
+      return scene.into();
+    };
+    let terrain = shader(terrain).width(Length::Fill).height(Length::Fill);
+    stack![scene, terrain].into()
+  }
+
+  /// The 3D terrain filling a pane, if it shows the terrain.
+  pub(crate) fn terrain(&self, pane: Pane) -> Option<Terrain<'_>> {
+    let (style, gradient) = self.visuals.pane(pane);
+    (style == VisualStyle::Terrain).then(|| Terrain {
+      columns: &self.spectrogram,
+      gradient,
+      camera: self.camera,
+      muted: self.player.is_muted,
+      pane,
+    })
+  }
+
+  pub(super) fn view(&self, window: window::Id) -> Element<Message> {
+    if Some(window) == self.mini_window {
+      // Drag anywhere to move it; right-click closes it
+      let visualizer = self.visualizer();
+      return mouse_area(visualizer)
+        .on_press(Message::Windows(windows::Message::DragMini))
+        .on_right_press(Message::Windows(windows::Message::ToggleMini))
+        .into();
+    }
+
+    let analysis_settings = *self.analysis_settings.lock().unwrap();
+    let controls = ui::controls::transport(&self.player, &analysis_settings);
+
+    if self.presenting {
+      // Just the visualiser, with the transport over it while the mouse moves
+      let visualizer = self.visualizer();
+      let overlay = self.cursor_moved_at.is_some().then(|| {
+        container(
+          column![
+            controls,
+            button("Exit presentation")
+              .on_press(Message::Windows(windows::Message::TogglePresentation))
+          ]
+          .spacing(10),
+        )
+        .padding(20)
+      });
+      return stack![visualizer].push_maybe(overlay).into();
+    }
+
+    let tools = ui::controls::tools(self);
+    let visual_controls = self.visuals.view(&analysis_settings);
+    let macro_controls = ui::controls::macros(&self.recorder, self.player.is_loaded);
+    let speech_controls = ui::controls::speech_gate(self);
+    let mic_controls = (self.player.capture_source() == Some(CaptureSource::Microphone))
+      .then(|| ui::controls::mic_recording(self.mic_recording.as_ref()));
+    let now_playing = self.now_playing().map(ui::controls::now_playing);
+    let tag_review = self.tag_review.as_ref().map(ui::controls::tag_review);
+    let timeline = self.outline.as_ref().map(|outline| {
+      Canvas::new(TimelineCanvas {
+        position: self.player.position().as_secs_f32(),
+        duration: outline.duration,
+        vocals: &outline.vocals,
+        sections: &outline.sections,
+        clip: self.clip(),
+        loop_start: self.player.loop_start.map(|start| start.as_secs_f32()),
+        loop_end: self.player.loop_end.map(|end| end.as_secs_f32()),
+        gradient: self.visuals.gradient,
+      })
+      .width(Length::Fill)
+      .height(TIMELINE_HEIGHT)
+    });
+
+    let visualizer = self.visualizer();
+    let meters = self.show_meters.then(|| {
+      let meter = self.loudness.lock().unwrap();
+      Canvas::new(MeterCanvas {
+        vu: meter.vu(),
+        momentary: meter.momentary(),
+        short_term: meter.short_term(),
+        integrated: meter.integrated(),
+        gradient: self.visuals.gradient,
+        muted: self.player.is_muted,
+      })
+      .width(METER_WIDTH)
+      .height(Length::Fill)
+    });
+
+    let energy = self.show_energy.then(|| {
+      Canvas::new(EnergyCanvas {
+        energy: self.energy,
+        gradient: self.visuals.gradient,
+        muted: self.player.is_muted,
+      })
+      .width(ENERGY_WIDTH)
+      .height(Length::Fill)
+    });
+
+    let overview = self.outline.as_ref().map(|outline| {
+      Canvas::new(OverviewCanvas {
+        envelope: &outline.envelope,
+        position: self.player.position().as_secs_f32(),
+        duration: outline.duration,
+        gradient: self.visuals.gradient,
+        cache: &self.overview_cache,
+      })
+      .width(Length::Fill)
+      .height(OVERVIEW_HEIGHT)
+    });
+    let lyrics =
+      self.lyrics.as_ref().map(|lyrics| ui::controls::lyrics(lyrics, self.player.position()));
+    let clip_controls = self.outline.is_some().then(|| ui::controls::clip(self));
+    let section_controls = self
+      .outline
+      .as_ref()
+      .filter(|outline| !outline.sections.is_empty())
+      .map(|outline| ui::controls::sections(self, &outline.sections));
+    let compare_controls = self.comparison.as_ref().map(ui::controls::comparison);
+    let comparison = self.comparison.as_ref().map(|comparison| {
+      let playing = self.player.file_path();
+      let other = comparison.other(playing);
+      Canvas::new(CompareCanvas {
+        playing: &self.frequency_data,
+        other: &comparison.bars,
+        names: (playing.map_or(String::new(), compare::file_name), compare::file_name(&other.path)),
+        view: comparison.view,
+        gradient: self.visuals.gradient,
+        muted: self.player.is_muted,
+      })
+      .width(Length::Fill)
+      .height(COMPARE_HEIGHT)
+    });
+    let header = self
+      .metadata
+      .as_ref()
+      .zip(self.metadata_source.as_deref())
+      .map(|(metadata, path)| ui::controls::track_header(metadata, Path::new(path)));
+
+    let main = column![]
+      .push_maybe(self.warning.as_deref().map(ui::controls::warning))
+      .push(controls)
+      .push_maybe(timeline)
+      .push_maybe(section_controls)
+      .push_maybe(clip_controls)
+      .push(tools)
+      .push(visual_controls)
+      .push_maybe(self.visuals.shows(VisualStyle::Plugin).then(|| ui::controls::plugins(self)))
+      .push(macro_controls)
+      .push(speech_controls)
+      .push_maybe(mic_controls)
+      .push_maybe(now_playing)
+      .push_maybe(self.show_stats.then(|| ui::controls::stats(&self.stats)))
+      .push_maybe(tag_review)
+      .push_maybe(self.inspector.is_some().then(|| ui::inspector::view(&Snapshot::capture(self))))
+      .push_maybe(header)
+      .push_maybe(self.tempo.bpm().map(|bpm| ui::controls::tempo(bpm, self.visuals.tempo_lock)))
+      .push(row![visualizer].push_maybe(energy).push_maybe(meters).spacing(20))
+      .push_maybe(overview)
+      .push_maybe(lyrics)
+      .push_maybe(compare_controls)
+      .push_maybe(comparison)
+      .spacing(20);
+
+    row![main]
+      .push_maybe(self.show_settings.then(|| {
+        self.visuals.panel(PanelInputs {
+          analysis_settings,
+          encode: self.encode_settings,
+          graphics: self.graphics_settings,
+          osc: &self.osc_settings,
+          osc_host: &self.osc_host,
+          osc_port: &self.osc_port,
+          shm: &self.shm_settings,
+          midi: &self.midi_settings,
+          midi_ports: &self.midi_ports,
+          rumble: &self.rumble_settings,
+          rumble_devices: &self.rumble_devices,
+          remote: &self.remote_settings,
+          remote_port: &self.remote_port,
+          tray: self.tray_settings,
+          normalise: self.player.normalise,
+          equalizer: self.player.equalizer,
+          session: self.session_settings,
+          presets: &self.presets,
+          preset_name: &self.preset_name,
+          suggest: self.suggest_settings,
+          suggested_preset: self.suggested_preset,
+          keymap: &self.keymap,
+          rebinding: self.rebinding,
+        })
+      }))
+      .spacing(20)
+      .padding(20)
+      .into()
+  }
+
+  pub(super) fn subscription(&self) -> iced::Subscription<Message> {
+    let tick = if self.player.is_playing || self.is_decaying {
+      match self.visuals.frame_rate.interval() {
+        Some(interval) => iced::time::every(interval).map(|_| Message::Tick),
+        None => iced::event::listen_raw(|event, _, id| match event {
+          iced::Event::Window(window::Event::RedrawRequested(at)) => Some(Message::Frame(id, at)),
+          _ => None,
+        }),
+      }
+    } else {
+      iced::Subscription::none()
+    };
+    // Keys typed into a focused text input never reach the shortcuts
+    let keys = iced::keyboard::on_key_press(|key, _| Some(Message::KeyPressed(key)));
+
+    // Presentation mode watches the mouse to bring the controls back, then
+    // polls until they can hide again
+    let presentation = if self.presenting {
+      let cursor = iced::event::listen_with(|event, _, _| match event {
+        iced::Event::Mouse(iced::mouse::Event::CursorMoved { .. }) => {
+          Some(Message::Windows(windows::Message::CursorMoved))
+        }
+        _ => None,
+      });
+      let timeout = if self.cursor_moved_at.is_some() {
+        iced::time::every(Duration::from_millis(500))
+          .map(|_| Message::Windows(windows::Message::PresentationTick))
+      } else {
+        iced::Subscription::none()
+      };
+      iced::Subscription::batch([cursor, timeout])
+    } else {
+      iced::Subscription::none()
+    };
+    let closed =
+      window::close_events().map(|id| Message::Windows(windows::Message::WindowClosed(id)));
+    let geometry = iced::event::listen_with(|event, _, id| match event {
+      iced::Event::Window(window::Event::Moved(position)) => {
+        Some(Message::Windows(windows::Message::WindowMoved(id, position)))
+      }
+      iced::Event::Window(window::Event::Resized(size)) => {
+        Some(Message::Windows(windows::Message::WindowResized(id, size)))
+      }
+      _ => None,
+    });
+    let remote = if self.remote.is_some() {
+      iced::time::every(REMOTE_POLL_INTERVAL)
+        .map(|_| Message::Outputs(outputs::Message::RemotePoll))
+    } else {
+      iced::Subscription::none()
+    };
+    let tray = if self.in_tray() {
+      iced::time::every(TRAY_POLL_INTERVAL).map(|_| Message::Outputs(outputs::Message::TrayPoll))
+    } else {
+      iced::Subscription::none()
+    };
+    iced::Subscription::batch([tick, keys, presentation, closed, geometry, remote, tray])
+  }
+}
@@ -0,0 +1,177 @@
+//! The main and mini windows: opening, closing to the tray, presentation
+//! mode and keeping track of where the main window is.
+
+use iced::{Size, Task as Command, window};
+use std::time::{Duration, Instant};
+
+use super::{AudioVisualizer, Message::Windows};
+use crate::config::Config;
+use crate::geometry::WindowGeometry;
+
+/// Starting size of the always-on-top mini window.
+const MINI_SIZE: Size = Size::new(320.0, 180.0);
+/// Presentation mode hides the controls again after the mouse rests this long.
+const PRESENTATION_CONTROLS_TIMEOUT: Duration = Duration::from_secs(3);
+
+#[derive(Debug, Clone)]
+pub(crate) enum Message {
+  /// Brings the main window back, reopening it if it was closed to the tray.
+  ShowMainWindow,
+  /// Hides everything but the visualiser, or brings it all back.
+  TogglePresentation,
+  PresentFullscreenToggled(bool),
+  /// Opens the small always-on-top window with just the visualiser, or closes it.
+  ToggleMini,
+  MiniHidesMainToggled(bool),
+  /// Moves the frameless mini window along with the mouse.
+  DragMini,
+  WindowClosed(window::Id),
+  MainWindowOpened(window::Id),
+  WindowMoved(window::Id, iced::Point),
+  WindowResized(window::Id, iced::Size),
+  /// The main window's new size, and whether it's maximised.
+  MainWindowResized(iced::Size, bool),
+  /// The mouse moved while presenting, so the controls show for a while.
+  CursorMoved,
+  /// Checks whether the presentation controls have timed out.
+  PresentationTick,
+}
+
+impl AudioVisualizer {
+  pub(super) fn update_windows(&mut self, message: Message) -> Command<super::Message> {
+    match message {
+      Message::ShowMainWindow => match self.main_window {
+        Some(main_window) => {
+          Command::batch([window::minimize(main_window, false), window::gain_focus(main_window)])
+        }
+        None => {
+          let (main_window, open) = window::open(self.window_geometry.settings());
+          self.main_window = Some(main_window);
+          open.map(|id| Windows(Message::MainWindowOpened(id)))
+        }
+      },
+      Message::TogglePresentation => {
+        self.presenting = !self.presenting;
+        self.cursor_moved_at = None;
+        // Fullscreen follows presentation, but only undoes what it did itself
+        let fullscreen = self.presenting && self.present_fullscreen;
+        if fullscreen == self.is_fullscreen {
+          return Command::none();
+        }
+        let Some(main_window) = self.main_window else {
+          return Command::none();
+        };
+        self.is_fullscreen = fullscreen;
+        let mode = if fullscreen { window::Mode::Fullscreen } else { window::Mode::Windowed };
+        window::change_mode(main_window, mode)
+      }
+      Message::ToggleMini => match self.mini_window.take() {
+        Some(mini_window) => Command::batch([window::close(mini_window), self.restore_main()]),
+        None => {
+          let (mini_window, open) = window::open(window::Settings {
+            size: MINI_SIZE,
+            decorations: false,
+            level: window::Level::AlwaysOnTop,
+            ..window::Settings::default()
+          });
+          self.mini_window = Some(mini_window);
+          let hide = match self.main_window {
+            Some(main_window) if self.mini_hides_main => window::minimize(main_window, true),
+            _ => Command::none(),
+          };
+          Command::batch([open.discard(), hide])
+        }
+      },
+      Message::MiniHidesMainToggled(hide) => {
+        self.mini_hides_main = hide;
+        Command::none()
+      }
+      Message::DragMini => match self.mini_window {
+        Some(mini_window) => window::drag(mini_window),
+        None => Command::none(),
+      },
+      Message::WindowClosed(id) => {
+        // Closing the main window quits, mini window or not, unless it
+        // goes to the tray; playback and the analysis carry on there
+        if Some(id) == self.main_window {
+          if self.in_tray() && self.tray_settings.close_to_tray {
+            self.main_window = None;
+            return Command::none();
+          }
+          return self.quit();
+        }
+        if Some(id) == self.mini_window {
+          self.mini_window = None;
+          return self.restore_main();
+        }
+        Command::none()
+      }
+      Message::MainWindowOpened(id) => self.window_geometry.restore(id),
+      Message::WindowMoved(id, position) => {
+        // A maximised or fullscreen window keeps the position it goes back to
+        if Some(id) == self.main_window && !self.window_geometry.maximized && !self.is_fullscreen {
+          self.window_geometry.x = Some(position.x);
+          self.window_geometry.y = Some(position.y);
+        }
+        Command::none()
+      }
+      Message::WindowResized(id, size) => match self.main_window {
+        Some(main_window) if id == main_window => window::get_maximized(main_window)
+          .map(move |maximized| Windows(Message::MainWindowResized(size, maximized))),
+        _ => Command::none(),
+      },
+      Message::MainWindowResized(size, maximized) => {
+        self.window_geometry.maximized = maximized;
+        // Minimising reports a zero size on some platforms
+        if !maximized && !self.is_fullscreen && size.width > 0.0 && size.height > 0.0 {
+          self.window_geometry.width = size.width;
+          self.window_geometry.height = size.height;
+        }
+        Command::none()
+      }
+      Message::PresentFullscreenToggled(fullscreen) => {
+        self.present_fullscreen = fullscreen;
+        Command::none()
+      }
+      Message::CursorMoved => {
+        self.cursor_moved_at = Some(Instant::now());
+        Command::none()
+      }
+      Message::PresentationTick => {
+        if self.cursor_moved_at.is_some_and(|at| at.elapsed() >= PRESENTATION_CONTROLS_TIMEOUT) {
+          self.cursor_moved_at = None;
+        }
+        Command::none()
+      }
+    }
+  }
+
+  /// Saves what's kept between runs and quits.
+  pub(super) fn quit(&mut self) -> Command<super::Message> {
+    self.end_listen();
+    let mut config = Config::load();
+    config.window = self.saved_geometry();
+    if let Err(e) = config.save() {
+      eprintln!("Failed to save the window's position: {}", e);
+    }
+    if self.session_settings.restore
+      && let Err(e) = self.session().save()
+    {
+      eprintln!("Failed to save the session: {}", e);
+    }
+    iced::exit()
+  }
+
+  /// The main window's geometry as it should be saved.
+  pub(super) fn saved_geometry(&self) -> WindowGeometry {
+    WindowGeometry { fullscreen: self.is_fullscreen, ..self.window_geometry }.with_monitor()
+  }
+
+  /// Brings the main window back if the mini window minimised it.
+  fn restore_main(&self) -> Command<super::Message> {
+    match self.main_window {
+      Some(main_window) if self.mini_hides_main => window::minimize(main_window, false),
+      _ => Command::none(),
+    }
+  }
+}
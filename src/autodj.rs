@@ -0,0 +1,193 @@
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::HashMap,
+  fmt, fs, io,
+  path::{Path, PathBuf},
+  time::{Duration, UNIX_EPOCH},
+};
+
+use crate::components::tempo::{self, MusicalKey};
+use crate::decode::{AudioDecoder, DecodeError};
+
+const CACHE_DIR: &str = "rust_audio_visualiser";
+const CACHE_FILE: &str = "beat_grids.json";
+/// Furthest the incoming track's speed is nudged to match tempos.
+pub const MAX_NUDGE: f32 = 0.08;
+
+#[derive(Debug)]
+pub enum GridError {
+  Io(io::Error),
+  Decode(DecodeError),
+  /// No steady beat to build a grid on.
+  NoBeat,
+}
+
+impl fmt::Display for GridError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      GridError::Io(e) => write!(f, "{}", e),
+      GridError::Decode(e) => write!(f, "{}", e),
+      GridError::NoBeat => f.write_str("no steady beat found"),
+    }
+  }
+}
+
+impl From<io::Error> for GridError {
+  fn from(e: io::Error) -> Self {
+    GridError::Io(e)
+  }
+}
+
+impl From<DecodeError> for GridError {
+  fn from(e: DecodeError) -> Self {
+    GridError::Decode(e)
+  }
+}
+
+/// What the auto-DJ knows about a track: its beat grid, key and length.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BeatGrid {
+  pub bpm: f32,
+  /// Seconds to the first beat.
+  pub first_beat: f32,
+  /// Length of the track, in seconds.
+  pub duration: f32,
+  pub key: MusicalKey,
+}
+
+impl BeatGrid {
+  /// Decodes the whole file and finds its grid and key. Takes a second or
+  /// two per track.
+  pub fn analyse(path: &Path) -> Result<Self, GridError> {
+    let decoder = AudioDecoder::open(path)?;
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels().max(1) as usize;
+    let interleaved: Vec<f32> = decoder.convert_samples::<f32>().collect();
+    let mono: Vec<f32> = interleaved
+      .chunks(channels)
+      .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+      .collect();
+
+    let tempo = tempo::estimate_tempo(&mono, sample_rate).ok_or(GridError::NoBeat)?;
+    Ok(Self {
+      bpm: tempo.bpm,
+      first_beat: tempo.first_beat,
+      duration: mono.len() as f32 / sample_rate as f32,
+      key: tempo::estimate_key(&mono, sample_rate),
+    })
+  }
+
+  fn period(&self) -> f32 {
+    60.0 / self.bpm
+  }
+
+  /// How far off a mix into `next` would be: 0.0 for the same tempo and
+  /// key, growing as the nudge needed nears [`MAX_NUDGE`] and for clashing keys.
+  pub fn distance(&self, next: &BeatGrid) -> f32 {
+    let (ratio, _) = self.nudge(next);
+    let tempo = (1.0 - ratio).abs() / MAX_NUDGE;
+    let key = match (self.key == next.key, self.key.is_compatible(next.key)) {
+      (true, _) => 0.0,
+      (false, true) => 0.5,
+      (false, false) => 2.0,
+    };
+    // Out of nudging range is worse than any key clash
+    tempo + key + if tempo > 1.0 { 10.0 } else { 0.0 }
+  }
+
+  /// Speed `next` would have to play at to match this tempo, and the
+  /// multiple of its tempo that's matched: half or double time when closer.
+  fn nudge(&self, next: &BeatGrid) -> (f32, f32) {
+    [0.5, 1.0, 2.0]
+      .map(|multiple| (self.bpm / (next.bpm * multiple), multiple))
+      .into_iter()
+      .min_by(|a, b| (1.0 - a.0).abs().total_cmp(&(1.0 - b.0).abs()))
+      .unwrap_or((1.0, 1.0))
+  }
+
+  /// Speed and start offset that make `next` come in on the beat under the
+  /// last `fade` of this track, played at `speed`.
+  ///
+  /// The speed matches the tempos (within [`MAX_NUDGE`] of normal); the
+  /// offset skips into `next` so that its beat has the phase this track's
+  /// beat has where the fade starts. The incoming track keeps the nudged
+  /// speed to its end.
+  pub fn transition(&self, speed: f32, next: &BeatGrid, fade: Duration) -> (f32, Duration) {
+    let (ratio, multiple) = self.nudge(next);
+    let next_speed = (speed * ratio).clamp(1.0 - MAX_NUDGE, 1.0 + MAX_NUDGE);
+
+    let fade_start = self.duration - fade.as_secs_f32() * speed;
+    let phase = ((fade_start - self.first_beat) / self.period()).rem_euclid(1.0);
+    let offset = next.first_beat + phase * next.period() / multiple;
+    (next_speed, Duration::from_secs_f32(offset.max(0.0)))
+  }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedGrid {
+  /// Modification time of the file when it was analysed, in seconds.
+  modified: u64,
+  grid: BeatGrid,
+}
+
+/// Beat grids of every track analysed so far, kept on disk so a track is
+/// only analysed once (and again if the file changes).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GridCache {
+  grids: HashMap<String, CachedGrid>,
+}
+
+impl GridCache {
+  /// `<platform cache dir>/rust_audio_visualiser/beat_grids.json`.
+  pub fn path() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(CACHE_DIR).join(CACHE_FILE))
+  }
+
+  /// Reads the cache, starting empty when it's missing or broken.
+  pub fn load() -> Self {
+    let Some(path) = Self::path() else {
+      return Self::default();
+    };
+    match fs::read_to_string(&path) {
+      Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+        eprintln!("Failed to parse beat grids {}: {}", path.display(), e);
+        Self::default()
+      }),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+      Err(e) => {
+        eprintln!("Failed to read beat grids {}: {}", path.display(), e);
+        Self::default()
+      }
+    }
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    let path =
+      Self::path().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no cache directory"))?;
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir)?;
+    }
+    fs::write(path, serde_json::to_string(self)?)
+  }
+
+  /// The grid of `path`, unless the file changed since it was analysed.
+  pub fn get(&self, path: &str) -> Option<&BeatGrid> {
+    self
+      .grids
+      .get(path)
+      .filter(|cached| modified(Path::new(path)) == Some(cached.modified))
+      .map(|cached| &cached.grid)
+  }
+
+  pub fn insert(&mut self, path: String, grid: BeatGrid) {
+    if let Some(modified) = modified(Path::new(&path)) {
+      self.grids.insert(path, CachedGrid { modified, grid });
+    }
+  }
+}
+
+fn modified(path: &Path) -> Option<u64> {
+  let modified = fs::metadata(path).and_then(|metadata| metadata.modified()).ok()?;
+  modified.duration_since(UNIX_EPOCH).ok().map(|since| since.as_secs())
+}
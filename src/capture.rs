@@ -0,0 +1,167 @@
+use rodio::cpal::{
+  self, BuildStreamError, DefaultStreamConfigError, Device, FromSample, PlayStreamError, Sample,
+  SampleFormat, SizedSample, Stream, StreamConfig, SupportedStreamConfig,
+  traits::{DeviceTrait, HostTrait, StreamTrait},
+};
+use std::{
+  collections::VecDeque,
+  fmt,
+  sync::{
+    Arc, Mutex,
+    mpsc::{self, Sender},
+  },
+};
+
+use crate::{
+  components::tap::{ChunkSlot, Chunker},
+  playback::LoadedTrack,
+};
+
+#[derive(Debug)]
+pub enum CaptureError {
+  /// No loopback or monitor device to record the system output from.
+  NoMonitor,
+  NoMicrophone,
+  Config(DefaultStreamConfigError),
+  Build(BuildStreamError),
+  Play(PlayStreamError),
+  UnsupportedFormat(SampleFormat),
+  /// The input runs at a different rate to the audio it's compared against.
+  SampleRateMismatch {
+    expected: u32,
+    found: u32,
+  },
+}
+
+impl fmt::Display for CaptureError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      CaptureError::NoMonitor => f.write_str("no system output monitor found"),
+      CaptureError::NoMicrophone => f.write_str("no input device found"),
+      CaptureError::Config(e) => write!(f, "{}", e),
+      CaptureError::Build(e) => write!(f, "{}", e),
+      CaptureError::Play(e) => write!(f, "{}", e),
+      CaptureError::UnsupportedFormat(format) => write!(f, "unsupported sample format {}", format),
+      CaptureError::SampleRateMismatch { expected, found } => {
+        write!(f, "input runs at {} Hz but the reference is {} Hz", found, expected)
+      }
+    }
+  }
+}
+
+/// A running recording from an input device. Recording stops when this is
+/// dropped.
+pub struct InputCapture {
+  _stream: Stream,
+}
+
+impl InputCapture {
+  /// Starts recording whatever the OS is playing, feeding it into the same
+  /// chunk pipeline as file playback.
+  pub fn system(
+    waveform: Arc<Mutex<VecDeque<f32>>>,
+    copy: ChunkSlot,
+  ) -> Result<(Self, LoadedTrack), CaptureError> {
+    let (device, config) = monitor_device()?;
+    let channels = config.channels();
+    Self::open(device, config, |sender| {
+      Chunker::new(sender).with_waveform(waveform, channels).with_copy(copy)
+    })
+  }
+
+  /// Starts recording the default input device, usually a microphone.
+  pub fn microphone() -> Result<(Self, LoadedTrack), CaptureError> {
+    let (device, config) = microphone_device()?;
+    Self::open(device, config, Chunker::new)
+  }
+
+  /// Starts recording the default input device into the same chunk pipeline
+  /// as file playback, to visualise a live room. `copy` gets the chunks too
+  /// while it's filled, to save them to a file.
+  pub fn live_microphone(
+    waveform: Arc<Mutex<VecDeque<f32>>>,
+    copy: ChunkSlot,
+  ) -> Result<(Self, LoadedTrack), CaptureError> {
+    let (device, config) = microphone_device()?;
+    let channels = config.channels();
+    Self::open(device, config, |sender| {
+      Chunker::new(sender).with_waveform(waveform, channels).with_copy(copy)
+    })
+  }
+
+  fn open(
+    device: Device,
+    config: SupportedStreamConfig,
+    chunker: impl FnOnce(Sender<Vec<f32>>) -> Chunker,
+  ) -> Result<(Self, LoadedTrack), CaptureError> {
+    let (sender, receiver) = mpsc::channel();
+    let chunker = chunker(sender);
+    let stream_config = config.config();
+
+    let stream = match config.sample_format() {
+      SampleFormat::F32 => build_stream::<f32>(&device, &stream_config, chunker),
+      SampleFormat::I16 => build_stream::<i16>(&device, &stream_config, chunker),
+      SampleFormat::U16 => build_stream::<u16>(&device, &stream_config, chunker),
+      SampleFormat::I32 => build_stream::<i32>(&device, &stream_config, chunker),
+      format => return Err(CaptureError::UnsupportedFormat(format)),
+    }
+    .map_err(CaptureError::Build)?;
+    stream.play().map_err(CaptureError::Play)?;
+
+    let track = LoadedTrack {
+      samples: receiver,
+      sample_rate: config.sample_rate().0,
+      channels: config.channels(),
+    };
+    Ok((Self { _stream: stream }, track))
+  }
+}
+
+fn microphone_device() -> Result<(Device, SupportedStreamConfig), CaptureError> {
+  let device = cpal::default_host().default_input_device().ok_or(CaptureError::NoMicrophone)?;
+  let config = device.default_input_config().map_err(CaptureError::Config)?;
+  Ok((device, config))
+}
+
+/// WASAPI opens the default output device for capture in loopback mode.
+#[cfg(target_os = "windows")]
+fn monitor_device() -> Result<(Device, SupportedStreamConfig), CaptureError> {
+  let device = cpal::default_host().default_output_device().ok_or(CaptureError::NoMonitor)?;
+  let config = device.default_output_config().map_err(CaptureError::Config)?;
+  Ok((device, config))
+}
+
+/// PulseAudio and PipeWire expose each sink's output as a "Monitor of ..."
+/// input source.
+#[cfg(not(target_os = "windows"))]
+fn monitor_device() -> Result<(Device, SupportedStreamConfig), CaptureError> {
+  let device = cpal::default_host()
+    .input_devices()
+    .map_err(|_| CaptureError::NoMonitor)?
+    .find(|device| device.name().is_ok_and(|name| name.to_lowercase().contains("monitor")))
+    .ok_or(CaptureError::NoMonitor)?;
+  let config = device.default_input_config().map_err(CaptureError::Config)?;
+  Ok((device, config))
+}
+
+fn build_stream<T>(
+  device: &Device,
+  config: &StreamConfig,
+  mut chunker: Chunker,
+) -> Result<Stream, BuildStreamError>
+where
+  T: SizedSample,
+  f32: FromSample<T>,
+{
+  device.build_input_stream(
+    config,
+    move |data: &[T], _: &cpal::InputCallbackInfo| {
+      for &sample in data {
+        chunker.push(sample.to_sample::<f32>());
+      }
+      chunker.show();
+    },
+    |e| eprintln!("Capture stream error: {}", e),
+    None,
+  )
+}
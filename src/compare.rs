@@ -0,0 +1,281 @@
+//! A/B comparison of two files, such as two masters of one mix: both are
+//! decoded up front, the one not playing is analysed at the playing one's
+//! position, and their spectra are drawn together. Swapping hands playback
+//! to the other file at the same point in the music.
+
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  widget::canvas::{self, Geometry, Path, Stroke},
+};
+use rodio::Source;
+use std::{fmt, ops::RangeInclusive, path::Path as FilePath, time::Duration};
+
+use crate::analysis::{Analyser, AnalysisSettings, DecibelRange, map_range};
+use crate::app::{MAX_BAR_HEIGHT, MIN_BAR_HEIGHT, Message};
+use crate::components::{bars, channels::ChannelMode, gradient::Gradient};
+use crate::decode::{AudioDecoder, DecodeError};
+use crate::ui::settings::VisualSettings;
+
+/// How far the second file can be shifted against the first, in seconds.
+pub const OFFSET_RANGE: RangeInclusive<f32> = -10.0..=10.0;
+/// Colour the second file's spectrum is drawn in, over the first's.
+const SECOND_COLOR: Color = Color::from_rgb(0.95, 0.95, 0.95);
+
+/// How the two spectra share the canvas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CompareView {
+  /// On the same axes, the second as a line over the first's bars.
+  #[default]
+  Overlaid,
+  /// Each on its own half.
+  SideBySide,
+}
+
+impl CompareView {
+  pub const ALL: [CompareView; 2] = [CompareView::Overlaid, CompareView::SideBySide];
+}
+
+impl fmt::Display for CompareView {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      CompareView::Overlaid => "Overlaid",
+      CompareView::SideBySide => "Side by side",
+    })
+  }
+}
+
+/// A whole file decoded and mixed down to mono, to analyse anywhere in it.
+#[derive(Clone)]
+pub struct ComparedTrack {
+  pub path: String,
+  samples: Vec<f32>,
+  sample_rate: u32,
+}
+
+impl fmt::Debug for ComparedTrack {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("ComparedTrack")
+      .field("path", &self.path)
+      .field("samples", &self.samples.len())
+      .field("sample_rate", &self.sample_rate)
+      .finish()
+  }
+}
+
+impl ComparedTrack {
+  /// Decodes the whole file; takes a second or so.
+  pub fn load(path: &FilePath) -> Result<Self, DecodeError> {
+    let decoder = AudioDecoder::open(path)?;
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels().max(1) as usize;
+    let interleaved: Vec<f32> = decoder.convert_samples::<f32>().collect();
+    Ok(Self {
+      path: path.to_string_lossy().into_owned(),
+      samples: interleaved
+        .chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+        .collect(),
+      sample_rate,
+    })
+  }
+
+  /// The `length` samples leading up to `seconds`, silence past either end.
+  fn window(&self, seconds: f32, length: usize) -> Vec<f32> {
+    let end = (seconds * self.sample_rate as f32).round() as i64;
+    (end - length as i64..end)
+      .map(|index| usize::try_from(index).ok().and_then(|i| self.samples.get(i)).copied())
+      .map(|sample| sample.unwrap_or(0.0))
+      .collect()
+  }
+}
+
+/// Two files being compared, and the bars of whichever isn't playing.
+pub struct Comparison {
+  /// The file that was playing when the comparison was opened, then the one
+  /// picked against it.
+  pub tracks: [ComparedTrack; 2],
+  pub view: CompareView,
+  /// Seconds the second file is ahead of the first, to line them up.
+  pub offset: f32,
+  /// Bar heights of the file not playing, smoothed like the main bars.
+  pub bars: Vec<f32>,
+  analyser: Analyser,
+}
+
+impl Comparison {
+  pub fn new(tracks: [ComparedTrack; 2]) -> Self {
+    Self {
+      tracks,
+      view: CompareView::default(),
+      offset: 0.0,
+      bars: Vec::new(),
+      analyser: Analyser::new(AnalysisSettings::default()),
+    }
+  }
+
+  /// Which of the tracks is playing, by path; the first unless it's the
+  /// second.
+  fn playing(&self, path: Option<&str>) -> usize {
+    usize::from(path == Some(self.tracks[1].path.as_str()))
+  }
+
+  /// The track not playing.
+  pub fn other(&self, path: Option<&str>) -> &ComparedTrack {
+    &self.tracks[1 - self.playing(path)]
+  }
+
+  /// Where in the track not playing lines up with `position` in the one
+  /// that is.
+  pub fn other_position(&self, path: Option<&str>, position: Duration) -> Duration {
+    let offset = if self.playing(path) == 0 { self.offset } else { -self.offset };
+    Duration::from_secs_f32((position.as_secs_f32() + offset).max(0.0))
+  }
+
+  /// Analyses the track not playing at the point matching `position` and
+  /// brings its bars along, one tick after the last time, over the same dB
+  /// range as the playing one's.
+  pub fn update(
+    &mut self,
+    path: Option<&str>,
+    position: Duration,
+    analysis_settings: &AnalysisSettings,
+    visuals: &VisualSettings,
+    decibels: DecibelRange,
+    bar_count: usize,
+  ) {
+    let seconds = self.other_position(path, position).as_secs_f32();
+    let other = &self.tracks[1 - self.playing(path)];
+    // Mixed down already, so one stream at the other file's own rate
+    self.analyser.configure(AnalysisSettings {
+      channel_mode: ChannelMode::Mono,
+      channels: 1,
+      sample_rate: other.sample_rate,
+      ..*analysis_settings
+    });
+    let fft_size = self.analyser.settings().fft_size;
+    let hop = visuals.update_interval.as_secs_f32();
+    let frame = self.analyser.frame(&[other.window(seconds, fft_size)], hop);
+    let target: Vec<f32> =
+      bars::levels(&frame.spectra, other.sample_rate, bar_count, visuals.frequency_scale, decibels)
+        .into_iter()
+        .map(|level| map_range(level, 0.0, 1.0, MIN_BAR_HEIGHT, MAX_BAR_HEIGHT))
+        .collect();
+    if self.bars.len() != target.len() {
+      self.bars = bars::rescale(&self.bars, target.len(), MIN_BAR_HEIGHT);
+    }
+    let smoothing = visuals.smoothing;
+    bars::smooth(
+      &mut self.bars,
+      &target,
+      other.sample_rate,
+      visuals.frequency_scale,
+      visuals.update_interval,
+      |region| smoothing.get(region),
+    );
+  }
+}
+
+/// The playing file's bars next to the other file's, named in the corner.
+pub struct CompareCanvas<'a> {
+  pub playing: &'a [f32],
+  pub other: &'a [f32],
+  pub names: (String, String),
+  pub view: CompareView,
+  pub gradient: Gradient,
+  pub muted: bool,
+}
+
+impl<'a> CompareCanvas<'a> {
+  /// Bars filling `area`, bottom up, coloured by height.
+  fn fill_bars(&self, frame: &mut canvas::Frame, bars: &[f32], area: Rectangle, alpha: f32) {
+    let width = area.width / bars.len().max(1) as f32;
+    for (i, &height) in bars.iter().enumerate() {
+      let level = level(height);
+      let color =
+        if self.muted { Color::from_rgb(0.5, 0.5, 0.5) } else { self.gradient.color(level) };
+      let bar_height = level * area.height;
+      frame.fill_rectangle(
+        Point::new(area.x + i as f32 * width, area.y + area.height - bar_height),
+        Size::new((width - 1.0).max(1.0), bar_height),
+        Color { a: alpha, ..color },
+      );
+    }
+  }
+}
+
+/// The file name in `path`, for labels.
+pub fn file_name(path: &str) -> String {
+  FilePath::new(path)
+    .file_name()
+    .map_or(path.to_string(), |name| name.to_string_lossy().into_owned())
+}
+
+/// Bar height as 0.0..=1.0 of the tallest a bar goes.
+fn level(height: f32) -> f32 {
+  ((height - MIN_BAR_HEIGHT) / (MAX_BAR_HEIGHT - MIN_BAR_HEIGHT)).clamp(0.0, 1.0)
+}
+
+impl<'a> canvas::Program<Message> for CompareCanvas<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    // Both spectra move every frame, so there's nothing worth caching
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    const LABEL_HEIGHT: f32 = 18.0;
+    let plot = Rectangle::new(
+      Point::new(0.0, LABEL_HEIGHT),
+      Size::new(bounds.width, (bounds.height - LABEL_HEIGHT).max(0.0)),
+    );
+    let label_color = Color::from_rgb(0.7, 0.7, 0.7);
+    let label = |frame: &mut canvas::Frame, content: String, x: f32, color: Color| {
+      frame.fill_text(canvas::Text {
+        content,
+        position: Point::new(x, 2.0),
+        color,
+        size: 12.0.into(),
+        ..canvas::Text::default()
+      });
+    };
+
+    match self.view {
+      CompareView::Overlaid => {
+        self.fill_bars(&mut frame, self.playing, plot, 0.8);
+        // The other file as a line along its bar tops
+        let width = plot.width / self.other.len().max(1) as f32;
+        let line = Path::new(|builder| {
+          for (i, &height) in self.other.iter().enumerate() {
+            let point = Point::new(
+              plot.x + (i as f32 + 0.5) * width,
+              plot.y + plot.height * (1.0 - level(height)),
+            );
+            if i == 0 {
+              builder.move_to(point);
+            } else {
+              builder.line_to(point);
+            }
+          }
+        });
+        frame.stroke(&line, Stroke::default().with_color(SECOND_COLOR).with_width(2.0));
+        label(&mut frame, format!("Playing: {}", self.names.0), 4.0, label_color);
+        label(&mut frame, format!("Line: {}", self.names.1), bounds.width / 2.0, SECOND_COLOR);
+      }
+      CompareView::SideBySide => {
+        let half = Size::new(plot.width / 2.0 - 4.0, plot.height);
+        self.fill_bars(&mut frame, self.playing, Rectangle::new(plot.position(), half), 1.0);
+        let right = Point::new(plot.x + plot.width / 2.0 + 4.0, plot.y);
+        self.fill_bars(&mut frame, self.other, Rectangle::new(right, half), 1.0);
+        label(&mut frame, format!("Playing: {}", self.names.0), 4.0, label_color);
+        label(&mut frame, self.names.1.clone(), right.x, label_color);
+      }
+    }
+
+    vec![frame.into_geometry()]
+  }
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+use crate::{analysis::DecibelRange, components::smoothing::Envelope};
+
+/// Frames whose loudest bin is under this, in dBFS, are silence and leave
+/// the range where it was rather than stretch it over the noise.
+const SILENCE_DB: f32 = -100.0;
+/// Share of the bins, quietest first, under the tracked floor.
+const FLOOR_PERCENTILE: f32 = 0.2;
+/// The top follows a louder passage at once and relaxes over a few seconds;
+/// the floor drifts both ways, so a break doesn't throw it about.
+const CEILING_ENVELOPE: Envelope = Envelope { attack_ms: 50.0, release_ms: 4000.0 };
+const FLOOR_ENVELOPE: Envelope = Envelope { attack_ms: 3000.0, release_ms: 3000.0 };
+/// Room kept over the loudest bin, so the peaks don't all sit at the top.
+const HEADROOM_DB: f32 = 3.0;
+/// Narrowest and widest the range gets, in dB.
+const MIN_SPAN_DB: f32 = 30.0;
+const MAX_SPAN_DB: f32 = 80.0;
+/// Where the range is kept between, in dBFS.
+const LOWEST_DB: f32 = -120.0;
+const HIGHEST_DB: f32 = 0.0;
+
+/// Follows the signal's recent floor and peaks in dB, to map onto the
+/// visuals in place of a fixed range: quiet tracks still fill the display
+/// and loud ones don't pin every bar at the top.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AutoRange {
+  /// Tracked floor and top, once anything's been heard.
+  tracked: Option<(f32, f32)>,
+}
+
+impl AutoRange {
+  /// Takes in a magnitude spectrum (half an FFT) `elapsed` after the last.
+  pub fn update(&mut self, spectrum: &[f32], elapsed: Duration) {
+    let mut levels: Vec<f32> = spectrum
+      .iter()
+      .skip(1)
+      .filter(|&&magnitude| magnitude > 0.0)
+      .map(|magnitude| 20.0 * magnitude.log10())
+      .collect();
+    let Some(peak) = levels.iter().copied().reduce(f32::max) else {
+      return;
+    };
+    if peak < SILENCE_DB {
+      return;
+    }
+    let index = ((levels.len() - 1) as f32 * FLOOR_PERCENTILE) as usize;
+    let (_, &mut floor, _) = levels.select_nth_unstable_by(index, f32::total_cmp);
+    self.tracked = Some(match self.tracked {
+      Some((old_floor, old_peak)) => (
+        FLOOR_ENVELOPE.apply(old_floor, floor, elapsed),
+        CEILING_ENVELOPE.apply(old_peak, peak, elapsed),
+      ),
+      None => (floor, peak),
+    });
+  }
+
+  /// The range to draw with, or `fallback` until something's been heard.
+  pub fn range(&self, fallback: DecibelRange) -> DecibelRange {
+    let Some((floor, peak)) = self.tracked else {
+      return fallback;
+    };
+    let max = (peak + HEADROOM_DB).clamp(LOWEST_DB + MIN_SPAN_DB, HIGHEST_DB);
+    let min = floor.clamp(max - MAX_SPAN_DB, max - MIN_SPAN_DB).max(LOWEST_DB);
+    DecibelRange { min, max }
+  }
+
+  /// Forgets the range, for a new track to find its own.
+  pub fn reset(&mut self) {
+    self.tracked = None;
+  }
+}
@@ -0,0 +1,142 @@
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  widget::{
+    canvas::{self, Geometry},
+    image::Handle,
+  },
+};
+use image::{DynamicImage, RgbaImage, imageops};
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path};
+
+use crate::app::Message;
+
+pub const DEFAULT_BACKDROP_BLUR: f32 = 0.5;
+pub const DEFAULT_BACKDROP_DARKEN: f32 = 0.5;
+/// Backdrops are shrunk to fit this before drawing; they sit behind
+/// everything else, so more detail wouldn't show.
+const SHARP_SIZE: u32 = 1280;
+/// Blurring works on a copy this small, which keeps it quick enough to
+/// redo as the slider moves, and the upscale blurs it further.
+const BLUR_SIZE: u32 = 192;
+/// Gaussian sigma of a full blur, in pixels of the small copy.
+const MAX_BLUR_SIGMA: f32 = 8.0;
+
+/// Where the picture behind the visualiser comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BackdropSource {
+  #[default]
+  Off,
+  /// The playing track's embedded cover art.
+  AlbumArt,
+  /// An image file picked by hand.
+  Image,
+}
+
+impl BackdropSource {
+  pub const ALL: [BackdropSource; 3] =
+    [BackdropSource::Off, BackdropSource::AlbumArt, BackdropSource::Image];
+}
+
+impl fmt::Display for BackdropSource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      BackdropSource::Off => "Off",
+      BackdropSource::AlbumArt => "Album art",
+      BackdropSource::Image => "Image",
+    })
+  }
+}
+
+/// A decoded backdrop, with a blurred copy kept for the current blur.
+#[derive(Debug, Clone)]
+pub struct Backdrop {
+  sharp: Handle,
+  /// Pixel size of `sharp`, for keeping the aspect ratio.
+  size: Size,
+  small: RgbaImage,
+  blurred: Handle,
+  /// 0.0 (sharp) to 1.0.
+  blur: f32,
+}
+
+impl Backdrop {
+  pub fn open(path: &Path) -> Result<Self, image::ImageError> {
+    Ok(Self::new(image::open(path)?))
+  }
+
+  /// Decodes encoded image bytes, such as embedded cover art.
+  pub fn from_bytes(bytes: &[u8]) -> Result<Self, image::ImageError> {
+    Ok(Self::new(image::load_from_memory(bytes)?))
+  }
+
+  fn new(image: DynamicImage) -> Self {
+    let sharp = image.thumbnail(SHARP_SIZE, SHARP_SIZE).to_rgba8();
+    let small = image.thumbnail(BLUR_SIZE, BLUR_SIZE).to_rgba8();
+    let size = Size::new(sharp.width() as f32, sharp.height() as f32);
+    let blurred = Handle::from_rgba(small.width(), small.height(), small.clone().into_raw());
+    Self {
+      sharp: Handle::from_rgba(sharp.width(), sharp.height(), sharp.into_raw()),
+      size,
+      small,
+      blurred,
+      blur: 0.0,
+    }
+  }
+
+  /// Re-blurs the small copy, if `blur` changed.
+  pub fn set_blur(&mut self, blur: f32) {
+    if blur == self.blur {
+      return;
+    }
+    self.blur = blur;
+    let blurred = imageops::blur(&self.small, blur * MAX_BLUR_SIGMA);
+    self.blurred = Handle::from_rgba(blurred.width(), blurred.height(), blurred.into_raw());
+  }
+
+  fn handle(&self) -> &Handle {
+    if self.blur > 0.0 { &self.blurred } else { &self.sharp }
+  }
+}
+
+/// The backdrop scaled to cover the whole canvas, darkened by `darken`
+/// (0.0..=1.0) so the visuals stay readable over it.
+pub struct BackdropCanvas<'a> {
+  pub backdrop: &'a Backdrop,
+  pub darken: f32,
+}
+
+impl<'a> canvas::Program<Message> for BackdropCanvas<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let canvas = Rectangle::with_size(bounds.size());
+
+    // Cover: fill both ways and crop whatever overhangs
+    let image = self.backdrop.size;
+    let scale = (bounds.width / image.width).max(bounds.height / image.height);
+    let size = Size::new(image.width * scale, image.height * scale);
+    let top_left =
+      Point::new((bounds.width - size.width) / 2.0, (bounds.height - size.height) / 2.0);
+    frame.with_clip(canvas, |frame| {
+      frame.draw_image(
+        Rectangle::new(top_left, size),
+        canvas::Image::new(self.backdrop.handle().clone()),
+      );
+    });
+    frame.fill_rectangle(
+      Point::ORIGIN,
+      bounds.size(),
+      Color::from_rgba(0.0, 0.0, 0.0, self.darken),
+    );
+    vec![frame.into_geometry()]
+  }
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use crate::analysis::DecibelRange;
+use crate::app::DEFAULT_UPDATE_INTERVAL;
+use crate::components::{
+  binning::FrequencyScale,
+  smoothing::{Envelope, Region},
+};
+
+/// Which stream and which band bar `i` of `bar_count` shows. The first half
+/// runs up the bands and the second back down them, so the halves mirror
+/// each other, with the top band alone in the middle of an odd count.
+pub fn band(i: usize, bar_count: usize) -> (usize, usize) {
+  let half_bars = bar_count.div_ceil(2);
+  if i < half_bars { (0, i) } else { (1, bar_count - 1 - i) }
+}
+
+/// Levels of `bar_count` bars, 0.0..=1.0 over the dB range, laid out like the
+/// visualiser's: each stream is binned into half the bars, and with split
+/// channels the first half shows the left and the second the right, while a
+/// single spectrum shows on both halves. See [`band`] for the order.
+pub fn levels(
+  spectra: &[Vec<f32>],
+  sample_rate: u32,
+  bar_count: usize,
+  scale: FrequencyScale,
+  decibels: DecibelRange,
+) -> Vec<f32> {
+  let half_bars = bar_count.div_ceil(2);
+  let sides: Vec<Vec<f32>> =
+    spectra.iter().map(|magnitudes| scale.bin(magnitudes, sample_rate, half_bars)).collect();
+  if sides.is_empty() {
+    return vec![0.0; bar_count];
+  }
+
+  (0..bar_count)
+    .map(|i| {
+      let (side, band) = band(i, bar_count);
+      decibels.normalise(sides[side.min(sides.len() - 1)][band])
+    })
+    .collect()
+}
+
+/// `values`, one per bar, stretched or squeezed onto `count` bars. Each half
+/// is refitted on its own, as the two sides of a split, so the bars carry on
+/// from where they are when their number changes instead of starting over.
+pub fn rescale<T: Copy>(values: &[T], count: usize, empty: T) -> Vec<T> {
+  let (old_half, new_half) = (values.len().div_ceil(2), count.div_ceil(2));
+  if old_half == 0 {
+    return vec![empty; count];
+  }
+  (0..count)
+    .map(|i| {
+      let along = ((i % new_half) as f32 + 0.5) / new_half as f32;
+      let index = i / new_half * old_half + ((along * old_half as f32) as usize).min(old_half - 1);
+      values[index.min(values.len() - 1)]
+    })
+    .collect()
+}
+
+/// Share of its height a bar keeps each 60 fps tick while falling back
+/// after playback halts.
+const DECAY_FACTOR: f32 = 0.95;
+
+/// What's left of something that keeps `factor` of itself every 60 fps
+/// tick after `elapsed`, so it fades as fast at any frame rate.
+pub fn fade(factor: f32, elapsed: Duration) -> f32 {
+  factor.powf(elapsed.as_secs_f32() / DEFAULT_UPDATE_INTERVAL.as_secs_f32())
+}
+
+/// Eases `bars` towards `target` over `elapsed`, each bar with the envelope
+/// of the region its band sits in.
+pub fn smooth(
+  bars: &mut [f32],
+  target: &[f32],
+  sample_rate: u32,
+  scale: FrequencyScale,
+  elapsed: Duration,
+  envelope: impl Fn(Region) -> Envelope,
+) {
+  let half_bars = bars.len().div_ceil(2);
+  let edges = scale.band_edges(half_bars, sample_rate);
+  let count = bars.len();
+  for (i, (old, &new)) in bars.iter_mut().zip(target).enumerate() {
+    let (_, band) = band(i, count);
+    let centre = (edges[band] + edges[band + 1]) / 2.0;
+    *old = envelope(Region::of(centre)).apply(*old, new, elapsed);
+  }
+}
+
+/// Lets `heights` fall back towards `floor` for `elapsed`, snapping them
+/// onto it once they're close. Returns whether any is still above it.
+pub fn decay(heights: &mut [f32], floor: f32, elapsed: Duration) -> bool {
+  let factor = fade(DECAY_FACTOR, elapsed);
+  let mut any_above = false;
+  for height in heights {
+    *height *= factor;
+    if *height > floor + 0.1 {
+      any_above = true;
+    } else {
+      *height = floor;
+    }
+  }
+  any_above
+}
@@ -0,0 +1,65 @@
+use std::collections::VecDeque;
+
+/// Seconds of flux history the adaptive threshold is computed over.
+const HISTORY_SECONDS: f32 = 1.0;
+/// How many standard deviations above the local mean a flux peak must reach.
+const THRESHOLD_DEVIATIONS: f32 = 1.5;
+/// Shortest gap between two beats (caps detection at 600 BPM).
+const MIN_BEAT_INTERVAL: f32 = 0.1;
+
+/// Onset detector based on spectral flux with an adaptive threshold.
+///
+/// Each spectrum is compared to the previous one; the summed increase in
+/// (log-compressed) energy is the flux. A beat fires when the flux peaks above
+/// the mean plus a multiple of the standard deviation of the recent history.
+#[derive(Debug, Default)]
+pub struct BeatDetector {
+  previous: Vec<f32>,
+  history: VecDeque<f32>,
+  /// Flux of the last two frames, to find local maxima.
+  last_flux: [f32; 2],
+  since_last_beat: f32,
+}
+
+impl BeatDetector {
+  /// Feeds one spectrum taken `hop_seconds` after the previous one. Returns the
+  /// beat strength (how far the flux cleared the threshold, 0.0..=1.0) when the
+  /// previous frame was a beat.
+  pub fn process(&mut self, spectrum: &[f32], hop_seconds: f32) -> Option<f32> {
+    let compressed: Vec<f32> = spectrum.iter().map(|&m| (1.0 + 1000.0 * m).ln()).collect();
+    let flux: f32 = if self.previous.len() == compressed.len() {
+      compressed.iter().zip(&self.previous).map(|(now, before)| (now - before).max(0.0)).sum()
+    } else {
+      0.0
+    };
+    self.previous = compressed;
+    self.since_last_beat += hop_seconds;
+
+    let capacity = (HISTORY_SECONDS / hop_seconds.max(f32::EPSILON)).ceil() as usize;
+    self.history.push_back(flux);
+    while self.history.len() > capacity.max(2) {
+      self.history.pop_front();
+    }
+
+    let count = self.history.len() as f32;
+    let mean = self.history.iter().sum::<f32>() / count;
+    let variance = self.history.iter().map(|f| (f - mean).powi(2)).sum::<f32>() / count;
+    let threshold = mean + THRESHOLD_DEVIATIONS * variance.sqrt();
+
+    // The previous frame is a beat if it's a local maximum above the threshold
+    let [before, candidate] = self.last_flux;
+    self.last_flux = [candidate, flux];
+    let is_peak = candidate > before && candidate >= flux && candidate > threshold;
+
+    if is_peak && self.since_last_beat >= MIN_BEAT_INTERVAL {
+      self.since_last_beat = 0.0;
+      Some(((candidate - threshold) / threshold.max(f32::EPSILON)).clamp(0.0, 1.0))
+    } else {
+      None
+    }
+  }
+
+  pub fn reset(&mut self) {
+    *self = Self::default();
+  }
+}
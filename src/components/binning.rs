@@ -0,0 +1,100 @@
+use std::fmt;
+
+/// Lowest frequency shown by the perceptual scales.
+const MIN_FREQUENCY: f32 = 20.0;
+/// Highest frequency shown by the perceptual scales (clamped to Nyquist).
+const MAX_FREQUENCY: f32 = 20_000.0;
+
+/// How FFT bins are spread across the bars.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrequencyScale {
+  Linear,
+  #[default]
+  Logarithmic,
+  Mel,
+}
+
+impl FrequencyScale {
+  pub const ALL: [FrequencyScale; 3] =
+    [FrequencyScale::Linear, FrequencyScale::Logarithmic, FrequencyScale::Mel];
+
+  /// Lowest and highest frequency shown, in Hz.
+  fn range(self, sample_rate: u32) -> (f32, f32) {
+    let nyquist = sample_rate as f32 / 2.0;
+    match self {
+      FrequencyScale::Linear => (0.0, nyquist),
+      _ => (MIN_FREQUENCY, MAX_FREQUENCY.min(nyquist)),
+    }
+  }
+
+  /// Returns `bands + 1` band edges in Hz, from low to high.
+  pub fn band_edges(self, bands: usize, sample_rate: u32) -> Vec<f32> {
+    let (low, high) = self.range(sample_rate);
+
+    (0..=bands)
+      .map(|i| {
+        let t = i as f32 / bands.max(1) as f32;
+        match self {
+          FrequencyScale::Linear => low + (high - low) * t,
+          FrequencyScale::Logarithmic => low * (high / low).powf(t),
+          FrequencyScale::Mel => mel_to_hz(hz_to_mel(low) + (hz_to_mel(high) - hz_to_mel(low)) * t),
+        }
+      })
+      .collect()
+  }
+
+  /// Where `frequency` sits along the scale: 0.0 at the lowest shown frequency,
+  /// 1.0 at the highest, outside that range beyond either end.
+  pub fn position(self, frequency: f32, sample_rate: u32) -> f32 {
+    let (low, high) = self.range(sample_rate);
+    match self {
+      FrequencyScale::Linear => (frequency - low) / (high - low),
+      FrequencyScale::Logarithmic => {
+        (frequency.max(f32::MIN_POSITIVE) / low).ln() / (high / low).ln()
+      }
+      FrequencyScale::Mel => {
+        (hz_to_mel(frequency) - hz_to_mel(low)) / (hz_to_mel(high) - hz_to_mel(low))
+      }
+    }
+  }
+
+  /// Reduces `magnitudes` (the lower half of an FFT) to `bands` values, taking
+  /// the peak bin inside each band.
+  pub fn bin(self, magnitudes: &[f32], sample_rate: u32, bands: usize) -> Vec<f32> {
+    if magnitudes.is_empty() {
+      return vec![0.0; bands];
+    }
+
+    let last = magnitudes.len() - 1;
+    let bin_width = sample_rate as f32 / (2.0 * magnitudes.len() as f32);
+    let edges = self.band_edges(bands, sample_rate);
+
+    edges
+      .windows(2)
+      .map(|edge| {
+        // Bands narrower than a bin still read the bin they fall into
+        let start = ((edge[0] / bin_width) as usize).min(last);
+        let end = ((edge[1] / bin_width).ceil() as usize).clamp(start + 1, last + 1);
+        magnitudes[start..end].iter().cloned().fold(0.0, f32::max)
+      })
+      .collect()
+  }
+}
+
+impl fmt::Display for FrequencyScale {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      FrequencyScale::Linear => "Linear",
+      FrequencyScale::Logarithmic => "Logarithmic",
+      FrequencyScale::Mel => "Mel",
+    })
+  }
+}
+
+fn hz_to_mel(hz: f32) -> f32 {
+  2595.0 * (1.0 + hz / 700.0).log10()
+}
+
+fn mel_to_hz(mel: f32) -> f32 {
+  700.0 * (10f32.powf(mel / 2595.0) - 1.0)
+}
@@ -0,0 +1,130 @@
+use std::{collections::VecDeque, time::Duration};
+
+use crate::components::tempo::tempo_lags;
+
+/// Seconds of onset envelope the tempo is estimated over.
+const HISTORY_SECONDS: f32 = 8.0;
+/// Least envelope needed before there's any estimate, in seconds.
+const MIN_HISTORY_SECONDS: f32 = 4.0;
+/// How often the estimate is redone, in seconds.
+const ESTIMATE_SECONDS: f32 = 0.5;
+/// Share of the envelope's energy the best lag has to match for the tempo
+/// to be shown at all.
+const MIN_CONFIDENCE: f32 = 0.15;
+/// A new estimate this far off the shown tempo replaces it outright, as a
+/// new song; closer ones are eased in.
+const JUMP_RATIO: f32 = 0.08;
+const EASE: f32 = 0.3;
+/// How much of the way a detected beat pulls the metronome onto itself.
+const PHASE_PULL: f32 = 0.25;
+/// How quickly the metronome's pulse fades over a beat.
+const PULSE_FALLOFF: f32 = 6.0;
+
+/// Live tempo from the autocorrelation of an onset envelope, the spectral
+/// flux of each frame, plus a metronome at that tempo kept in step with the
+/// detected beats for effects to lock to.
+#[derive(Debug, Clone, Default)]
+pub struct TempoTracker {
+  /// Spectral flux of recent frames, oldest first, one per `period`.
+  envelope: VecDeque<f32>,
+  /// Log magnitudes of the last frame, to take the flux against.
+  previous: Vec<f32>,
+  /// Seconds between envelope values.
+  period: f32,
+  since_estimate: f32,
+  bpm: Option<f32>,
+  /// How far through the current beat the metronome is, 0.0..1.0.
+  phase: f32,
+}
+
+impl TempoTracker {
+  /// Takes in a magnitude spectrum (half an FFT) `elapsed` after the last.
+  pub fn update(&mut self, spectrum: &[f32], elapsed: Duration) {
+    let elapsed = elapsed.as_secs_f32();
+    let current: Vec<f32> =
+      spectrum.iter().map(|magnitude| (1.0 + 1000.0 * magnitude).ln()).collect();
+    let flux = if current.len() == self.previous.len() {
+      current.iter().zip(&self.previous).map(|(now, before)| (now - before).max(0.0)).sum()
+    } else {
+      0.0
+    };
+    self.previous = current;
+
+    self.period = elapsed;
+    self.envelope.push_back(flux);
+    let capacity = (HISTORY_SECONDS / elapsed.max(f32::EPSILON)) as usize;
+    while self.envelope.len() > capacity.max(1) {
+      self.envelope.pop_front();
+    }
+
+    self.since_estimate += elapsed;
+    if self.since_estimate >= ESTIMATE_SECONDS {
+      self.since_estimate = 0.0;
+      self.estimate();
+    }
+    self.advance(elapsed);
+  }
+
+  /// Moves the metronome on by `elapsed` seconds.
+  fn advance(&mut self, elapsed: f32) {
+    if let Some(bpm) = self.bpm {
+      self.phase = (self.phase + elapsed * bpm / 60.0).fract();
+    }
+  }
+
+  /// Pulls the metronome towards a beat the detector just heard.
+  pub fn on_beat(&mut self) {
+    // How far off the nearest metronome beat, -0.5..0.5 of a beat
+    let error = if self.phase > 0.5 { self.phase - 1.0 } else { self.phase };
+    self.phase = (self.phase - error * PHASE_PULL).rem_euclid(1.0);
+  }
+
+  fn estimate(&mut self) {
+    if self.period <= 0.0 || (self.envelope.len() as f32) * self.period < MIN_HISTORY_SECONDS {
+      return;
+    }
+    let lags = tempo_lags(self.envelope.make_contiguous(), self.period);
+    if lags.len() < 2 {
+      return;
+    }
+    let Some(best) = lags.iter().max_by(|a, b| a.score.total_cmp(&b.score)) else {
+      return;
+    };
+    let (lag, confidence) = (best.lag, best.correlation);
+    if confidence < MIN_CONFIDENCE {
+      self.bpm = None;
+      return;
+    }
+
+    // Fit a parabola through the peak and its neighbours for a finer lag
+    let at = |lag: usize| lags.iter().find(|score| score.lag == lag).map(|score| score.correlation);
+    let offset = match (at(lag.wrapping_sub(1)), at(lag + 1)) {
+      (Some(before), Some(after)) => {
+        let curve = before - 2.0 * confidence + after;
+        if curve < 0.0 { (0.5 * (before - after) / curve).clamp(-0.5, 0.5) } else { 0.0 }
+      }
+      _ => 0.0,
+    };
+    let bpm = 60.0 / ((lag as f32 + offset) * self.period);
+    self.bpm = Some(match self.bpm {
+      Some(shown) if (bpm - shown).abs() / shown < JUMP_RATIO => shown + (bpm - shown) * EASE,
+      _ => bpm,
+    });
+  }
+
+  /// The tempo, once it's clear enough to show.
+  pub fn bpm(&self) -> Option<f32> {
+    self.bpm
+  }
+
+  /// A pulse on every metronome beat, 1.0 on the beat fading towards 0.0,
+  /// or `None` without a tempo.
+  pub fn pulse(&self) -> Option<f32> {
+    self.bpm.map(|_| (-PULSE_FALLOFF * self.phase).exp())
+  }
+
+  /// Forgets the tempo, for a new track to find its own.
+  pub fn reset(&mut self) {
+    *self = Self::default();
+  }
+}
@@ -0,0 +1,141 @@
+use std::{fmt, time::Duration};
+
+use crate::components::visualiser::VisualStyle;
+
+/// Share of each tick the visuals get to draw in; the rest is left for
+/// the renderer and everything else the UI does.
+const DRAW_SHARE: f32 = 0.5;
+/// Draw cost, as a share of the budget, that has to hold before effects
+/// come back.
+const RESTORE_BELOW: f32 = 0.5;
+/// How long the cost has to stay over budget before cutting back a step.
+const DEGRADE_AFTER: Duration = Duration::from_secs(1);
+/// How long it has to stay well under before restoring a step.
+const RESTORE_AFTER: Duration = Duration::from_secs(3);
+/// Weight of the newest draw in the running cost.
+const COST_SMOOTHING: f32 = 0.1;
+
+/// How far the expensive effects are cut back to keep drawing in budget.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Degradation {
+  #[default]
+  Off,
+  /// Half the particles, no jitter, at most 128 bars.
+  Light,
+  /// A quarter of the particles, at most 64 bars.
+  Medium,
+  /// A tenth of the particles, at most 32 bars.
+  Heavy,
+}
+
+impl Degradation {
+  pub const ALL: [Degradation; 4] =
+    [Degradation::Off, Degradation::Light, Degradation::Medium, Degradation::Heavy];
+
+  /// Share of the usual particles that get spawned and kept.
+  pub fn particle_share(self) -> f32 {
+    match self {
+      Degradation::Off => 1.0,
+      Degradation::Light => 0.5,
+      Degradation::Medium => 0.25,
+      Degradation::Heavy => 0.1,
+    }
+  }
+
+  /// Most bars drawn, whatever the setting asks for.
+  pub fn max_bars(self) -> Option<usize> {
+    match self {
+      Degradation::Off => None,
+      Degradation::Light => Some(128),
+      Degradation::Medium => Some(64),
+      Degradation::Heavy => Some(32),
+    }
+  }
+
+  pub fn allows_jitter(self) -> bool {
+    self == Degradation::Off
+  }
+
+  fn step(self, by: isize) -> Self {
+    let index = Self::ALL.iter().position(|&level| level == self).unwrap_or(0) as isize;
+    Self::ALL[(index + by).clamp(0, Self::ALL.len() as isize - 1) as usize]
+  }
+}
+
+impl fmt::Display for Degradation {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Degradation::Off => "full quality",
+      Degradation::Light => "lightly degraded",
+      Degradation::Medium => "degraded",
+      Degradation::Heavy => "heavily degraded",
+    })
+  }
+}
+
+/// Running draw cost and degradation of one style.
+#[derive(Debug, Clone, Copy, Default)]
+struct StyleBudget {
+  cost: Duration,
+  degradation: Degradation,
+  /// How long the cost has been over budget, or well under it.
+  over: Duration,
+  under: Duration,
+}
+
+/// Tracks how long each style takes to draw, and cuts back its expensive
+/// effects while it runs over the frame budget, restoring them once there's
+/// headroom again. Styles are tracked apart, so a cheap one never pays for
+/// an expensive one.
+#[derive(Debug, Clone, Default)]
+pub struct PerformanceBudget {
+  styles: [StyleBudget; VisualStyle::ALL.len()],
+}
+
+impl PerformanceBudget {
+  /// Takes in how long `style` took to draw, with ticks `interval` apart.
+  /// Returns whether its degradation changed.
+  pub fn record(&mut self, style: VisualStyle, cost: Duration, interval: Duration) -> bool {
+    let budget = interval.mul_f32(DRAW_SHARE);
+    let entry = &mut self.styles[index(style)];
+    entry.cost = if entry.cost.is_zero() {
+      cost
+    } else {
+      entry.cost.mul_f32(1.0 - COST_SMOOTHING) + cost.mul_f32(COST_SMOOTHING)
+    };
+
+    let before = entry.degradation;
+    if entry.cost > budget {
+      entry.over += interval;
+      entry.under = Duration::ZERO;
+      if entry.over >= DEGRADE_AFTER {
+        entry.degradation = entry.degradation.step(1);
+        entry.over = Duration::ZERO;
+      }
+    } else if entry.cost < budget.mul_f32(RESTORE_BELOW) {
+      entry.under += interval;
+      entry.over = Duration::ZERO;
+      if entry.under >= RESTORE_AFTER {
+        entry.degradation = entry.degradation.step(-1);
+        entry.under = Duration::ZERO;
+      }
+    } else {
+      entry.over = Duration::ZERO;
+      entry.under = Duration::ZERO;
+    }
+    entry.degradation != before
+  }
+
+  /// Smoothed draw cost of `style`; zero until it's been drawn.
+  pub fn cost(&self, style: VisualStyle) -> Duration {
+    self.styles[index(style)].cost
+  }
+
+  pub fn degradation(&self, style: VisualStyle) -> Degradation {
+    self.styles[index(style)].degradation
+  }
+}
+
+fn index(style: VisualStyle) -> usize {
+  VisualStyle::ALL.iter().position(|&each| each == style).unwrap_or(0)
+}
@@ -0,0 +1,55 @@
+use std::fmt;
+
+/// Which channels of the decoded audio get analysed, and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChannelMode {
+  /// All channels mixed down to one spectrum.
+  #[default]
+  Mono,
+  Left,
+  Right,
+  /// Left and right analysed separately, shown on either half of the visualiser.
+  Split,
+}
+
+impl ChannelMode {
+  pub const ALL: [ChannelMode; 4] =
+    [ChannelMode::Mono, ChannelMode::Left, ChannelMode::Right, ChannelMode::Split];
+
+  /// Number of separate sample streams this mode produces.
+  pub fn streams(self) -> usize {
+    match self {
+      ChannelMode::Split => 2,
+      _ => 1,
+    }
+  }
+
+  /// Appends one interleaved frame to the per-stream buffers.
+  ///
+  /// Mono sources feed both sides when a side is requested, so the view
+  /// never goes blank just because the file has one channel.
+  pub fn push_frame(self, frame: &[f32], streams: &mut [Vec<f32>]) {
+    let left = frame[0];
+    let right = frame[1.min(frame.len() - 1)];
+    match self {
+      ChannelMode::Mono => streams[0].push(frame.iter().sum::<f32>() / frame.len() as f32),
+      ChannelMode::Left => streams[0].push(left),
+      ChannelMode::Right => streams[0].push(right),
+      ChannelMode::Split => {
+        streams[0].push(left);
+        streams[1].push(right);
+      }
+    }
+  }
+}
+
+impl fmt::Display for ChannelMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      ChannelMode::Mono => "Mono mix",
+      ChannelMode::Left => "Left",
+      ChannelMode::Right => "Right",
+      ChannelMode::Split => "Left / Right",
+    })
+  }
+}
@@ -0,0 +1,161 @@
+use iced::{
+  Color, Point, Radians, Rectangle, Theme, Vector,
+  alignment::{Horizontal, Vertical},
+  widget::canvas::{self, Geometry, Path, path::Arc},
+};
+use std::{f32::consts::TAU, time::Duration};
+
+use crate::{analysis::DecibelRange, app::Message, components::smoothing::Envelope};
+
+/// Names of the pitch classes, from C.
+pub const PITCH_CLASSES: [&str; 12] =
+  ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+/// Range folded into pitch classes: C2 up to where harmonics swamp the notes.
+const LOWEST_HZ: f32 = 65.0;
+const HIGHEST_HZ: f32 = 5000.0;
+/// Quick enough to follow chord changes, slow enough not to flicker
+/// between every note of a melody.
+const ENVELOPE: Envelope = Envelope { attack_ms: 80.0, release_ms: 600.0 };
+/// Room left around the wheel for the labels, in pixels.
+const LABEL_MARGIN: f32 = 24.0;
+
+/// Spectral energy folded into the twelve pitch classes, each 0.0..=1.0:
+/// the strongest class at the overall level, the rest relative to it.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Chroma {
+  levels: [f32; 12],
+}
+
+impl Chroma {
+  /// Takes in a magnitude spectrum (half an FFT) `elapsed` after the last.
+  /// An empty spectrum lets every class fall back towards silence.
+  pub fn update(
+    &mut self,
+    spectrum: &[f32],
+    sample_rate: u32,
+    decibels: DecibelRange,
+    elapsed: Duration,
+  ) {
+    let bin_hz = sample_rate as f32 / (2 * spectrum.len().max(1)) as f32;
+    let mut power = [0.0f32; 12];
+    for (bin, magnitude) in spectrum.iter().enumerate().skip(1) {
+      let frequency = bin as f32 * bin_hz;
+      if !(LOWEST_HZ..=HIGHEST_HZ).contains(&frequency) {
+        continue;
+      }
+      // MIDI note number, where every multiple of 12 is a C
+      let note = (12.0 * (frequency / 440.0).log2() + 69.0).round() as i32;
+      power[note.rem_euclid(12) as usize] += magnitude * magnitude;
+    }
+
+    let strongest = power.iter().copied().fold(0.0, f32::max);
+    let level = decibels.normalise(power.iter().sum::<f32>().sqrt()).clamp(0.0, 1.0);
+    for (old, power) in self.levels.iter_mut().zip(power) {
+      let new = if strongest > 0.0 { power / strongest * level } else { 0.0 };
+      *old = ENVELOPE.apply(*old, new, elapsed);
+    }
+  }
+
+  pub fn levels(&self) -> [f32; 12] {
+    self.levels
+  }
+
+  /// The loudest pitch class, unless everything's near silent.
+  pub fn dominant(&self) -> Option<usize> {
+    let (class, &level) = self.levels.iter().enumerate().max_by(|a, b| a.1.total_cmp(b.1))?;
+    (level >= 0.05).then_some(class)
+  }
+
+  pub fn is_silent(&self) -> bool {
+    self.levels.iter().all(|&level| level < 0.01)
+  }
+}
+
+/// Colour of a pitch class on the wheel: the hues go round once an octave.
+fn class_color(class: usize) -> Color {
+  let hue = class as f32 / 12.0 * 6.0;
+  let x = 1.0 - (hue % 2.0 - 1.0).abs();
+  let (r, g, b) = match hue as u32 {
+    0 => (1.0, x, 0.0),
+    1 => (x, 1.0, 0.0),
+    2 => (0.0, 1.0, x),
+    3 => (0.0, x, 1.0),
+    4 => (x, 0.0, 1.0),
+    _ => (1.0, 0.0, x),
+  };
+  Color::from_rgb(r, g, b)
+}
+
+/// The pitch classes as a colour wheel, C at the top and going clockwise,
+/// each wedge reaching out as far as its class is loud. The loudest class
+/// is named in the middle.
+pub struct ChromaCanvas {
+  pub chroma: Chroma,
+  /// Draws the wheel greyed out while playback is muted.
+  pub muted: bool,
+}
+
+impl canvas::Program<Message> for ChromaCanvas {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    // Levels move every frame, so there's nothing worth caching
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let center = Point::new(bounds.width / 2.0, bounds.height / 2.0);
+    let radius = (bounds.width.min(bounds.height) / 2.0 - LABEL_MARGIN).max(1.0);
+    let step = TAU / 12.0;
+    let label_color = Color::from_rgb(0.7, 0.7, 0.7);
+
+    for (class, &level) in self.chroma.levels().iter().enumerate() {
+      // Up is -90°, and each wedge is centred on its class
+      let middle = class as f32 * step - TAU / 4.0;
+      let (start, end) = (middle - step / 2.0, middle + step / 2.0);
+      let color = if self.muted { Color::from_rgb(0.5, 0.5, 0.5) } else { class_color(class) };
+      let wedge = |reach: f32| {
+        Path::new(|builder| {
+          builder.move_to(center);
+          builder.arc(Arc {
+            center,
+            radius: reach,
+            start_angle: Radians(start),
+            end_angle: Radians(end),
+          });
+          builder.close();
+        })
+      };
+
+      frame.fill(&wedge(radius), Color { a: 0.12, ..color });
+      frame.fill(&wedge(radius * level.clamp(0.0, 1.0)), Color { a: 0.4 + 0.6 * level, ..color });
+      frame.fill_text(canvas::Text {
+        content: PITCH_CLASSES[class].to_string(),
+        position: center + Vector::new(middle.cos(), middle.sin()) * (radius + LABEL_MARGIN / 2.0),
+        color: label_color,
+        size: 14.0.into(),
+        horizontal_alignment: Horizontal::Center,
+        vertical_alignment: Vertical::Center,
+        ..canvas::Text::default()
+      });
+    }
+
+    if let Some(class) = self.chroma.dominant() {
+      frame.fill_text(canvas::Text {
+        content: PITCH_CLASSES[class].to_string(),
+        position: center,
+        color: Color::WHITE,
+        size: (radius / 4.0).max(12.0).into(),
+        horizontal_alignment: Horizontal::Center,
+        vertical_alignment: Vertical::Center,
+        ..canvas::Text::default()
+      });
+    }
+
+    vec![frame.into_geometry()]
+  }
+}
@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::VecDeque,
+  fmt,
+  time::{Duration, Instant},
+};
+
+use crate::{components::smoothing::Envelope, playback::CaptureSource};
+
+/// Span of recent frames the features are taken over.
+const WINDOW: Duration = Duration::from_millis(2500);
+/// How long a new verdict has to hold before the content switches.
+const HOLD: Duration = Duration::from_secs(3);
+/// A frame counts as low-energy below this fraction of the window's mean.
+const LOW_ENERGY_FRACTION: f32 = 0.5;
+/// Speech pauses between syllables and words, so a large share of its frames
+/// are low-energy; music rarely drops out that often.
+const SPEECH_LOW_ENERGY_RATIO: f32 = 0.4;
+/// Speech alternates tonal vowels with noisy consonants, so its flatness
+/// swings far more than a mix of instruments does.
+const SPEECH_FLATNESS_DEVIATION: f32 = 0.05;
+/// Windows quieter than this on average are left unclassified.
+const MIN_ENERGY: f32 = 1e-8;
+
+/// Smoothing used while speech is playing: slow to rise and slower to fall.
+pub const CALM_ENVELOPE: Envelope = Envelope { attack_ms: 70.0, release_ms: 190.0 };
+
+/// What kind of audio is playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Content {
+  #[default]
+  Music,
+  Speech,
+}
+
+impl fmt::Display for Content {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Content::Music => "Music",
+      Content::Speech => "Speech",
+    })
+  }
+}
+
+/// Tells speech from music with two cheap heuristics over the last few
+/// seconds: the share of low-energy frames and how much the spectral
+/// flatness fluctuates.
+#[derive(Debug, Default)]
+pub struct ContentClassifier {
+  /// Energy and flatness of each recent frame.
+  history: VecDeque<(Instant, f32, f32)>,
+  content: Content,
+  /// When the opposite verdict started holding, if it is.
+  switching_since: Option<Instant>,
+}
+
+impl ContentClassifier {
+  /// Feeds one magnitude spectrum and returns the current verdict.
+  pub fn process(&mut self, spectrum: &[f32], now: Instant) -> Content {
+    let power: Vec<f32> = spectrum.iter().map(|m| m * m).collect();
+    let energy = power.iter().sum::<f32>();
+    self.history.push_back((now, energy, flatness(&power)));
+    while self.history.front().is_some_and(|&(at, _, _)| now.duration_since(at) > WINDOW) {
+      self.history.pop_front();
+    }
+
+    let Some(verdict) = self.verdict() else {
+      return self.content;
+    };
+    if verdict == self.content {
+      self.switching_since = None;
+    } else {
+      let since = *self.switching_since.get_or_insert(now);
+      if now.duration_since(since) >= HOLD {
+        self.content = verdict;
+        self.switching_since = None;
+      }
+    }
+    self.content
+  }
+
+  fn verdict(&self) -> Option<Content> {
+    if self.history.len() < 2 {
+      return None;
+    }
+    let count = self.history.len() as f32;
+    let mean_energy = self.history.iter().map(|&(_, energy, _)| energy).sum::<f32>() / count;
+    if mean_energy < MIN_ENERGY {
+      return None;
+    }
+    let low_energy = self
+      .history
+      .iter()
+      .filter(|&&(_, energy, _)| energy < LOW_ENERGY_FRACTION * mean_energy)
+      .count() as f32
+      / count;
+
+    let mean_flatness = self.history.iter().map(|&(_, _, flatness)| flatness).sum::<f32>() / count;
+    let variance =
+      self.history.iter().map(|&(_, _, flatness)| (flatness - mean_flatness).powi(2)).sum::<f32>()
+        / count;
+
+    let is_speech =
+      low_energy >= SPEECH_LOW_ENERGY_RATIO && variance.sqrt() >= SPEECH_FLATNESS_DEVIATION;
+    Some(if is_speech { Content::Speech } else { Content::Music })
+  }
+
+  pub fn content(&self) -> Content {
+    self.content
+  }
+}
+
+/// Geometric over arithmetic mean of the power spectrum: near 1.0 for noise,
+/// near 0.0 for a few strong tones.
+fn flatness(power: &[f32]) -> f32 {
+  let count = power.len().max(1) as f32;
+  let arithmetic = power.iter().sum::<f32>() / count;
+  if arithmetic <= 0.0 {
+    return 0.0;
+  }
+  let geometric = (power.iter().map(|p| p.max(1e-12).ln()).sum::<f32>() / count).exp();
+  geometric / arithmetic
+}
+
+/// Which inputs calm the visuals down while speech is detected.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpeechGate {
+  pub file: bool,
+  pub system: bool,
+  pub microphone: bool,
+}
+
+impl SpeechGate {
+  /// Whether the gate is on for `source` (`None` being file playback).
+  pub fn enabled(self, source: Option<CaptureSource>) -> bool {
+    match source {
+      None => self.file,
+      Some(CaptureSource::System) => self.system,
+      Some(CaptureSource::Microphone) => self.microphone,
+    }
+  }
+
+  pub fn set(&mut self, source: Option<CaptureSource>, enabled: bool) {
+    match source {
+      None => self.file = enabled,
+      Some(CaptureSource::System) => self.system = enabled,
+      Some(CaptureSource::Microphone) => self.microphone = enabled,
+    }
+  }
+}
@@ -0,0 +1,136 @@
+use rodio::{Source, source::SeekError};
+use std::{
+  collections::VecDeque,
+  f32::consts::FRAC_PI_2,
+  mem,
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+/// The end of one playlist entry, passed on for the next to fade out under its start.
+pub type Handover = Arc<Mutex<Option<VecDeque<f32>>>>;
+
+/// One playlist entry that overlaps its neighbours.
+///
+/// With a following entry it holds back the last `fade` of its track and,
+/// when the track runs out, hands that tail over instead of playing it. The
+/// following entry then mixes the tail in under its own start with
+/// equal-power gains, so both tracks play (and reach the analyser) during the
+/// overlap. With a zero fade the entries simply play back to back.
+///
+/// Neighbouring entries must share a channel count and sample rate.
+pub struct Crossfade<S>
+where
+  S: Source<Item = f32>,
+{
+  inner: S,
+  /// Length of the held-back tail, in samples (whole frames).
+  fade_len: usize,
+  /// Samples read from `inner` but not played yet.
+  lookahead: VecDeque<f32>,
+  handover_in: Option<Handover>,
+  handover_out: Option<Handover>,
+  /// The previous entry's tail still fading out, and its full length.
+  outgoing: VecDeque<f32>,
+  outgoing_len: usize,
+}
+
+impl<S> Crossfade<S>
+where
+  S: Source<Item = f32>,
+{
+  pub fn new(
+    inner: S,
+    fade: Duration,
+    handover_in: Option<Handover>,
+    handover_out: Option<Handover>,
+  ) -> Self {
+    let frames = (fade.as_secs_f32() * inner.sample_rate() as f32) as usize;
+    let fade_len = frames * inner.channels() as usize;
+    Self {
+      inner,
+      fade_len,
+      lookahead: VecDeque::with_capacity(fade_len + 1),
+      handover_in,
+      handover_out,
+      outgoing: VecDeque::new(),
+      outgoing_len: 0,
+    }
+  }
+
+  /// The next sample of this entry's own track, keeping the tail held back
+  /// when another entry follows.
+  fn next_own(&mut self) -> Option<f32> {
+    let Some(handover) = &self.handover_out else {
+      return self.inner.next();
+    };
+    while self.lookahead.len() <= self.fade_len {
+      match self.inner.next() {
+        Some(sample) => self.lookahead.push_back(sample),
+        None => {
+          // Whatever is left is the tail
+          *handover.lock().unwrap() = Some(mem::take(&mut self.lookahead));
+          self.handover_out = None;
+          return None;
+        }
+      }
+    }
+    self.lookahead.pop_front()
+  }
+}
+
+impl<S> Iterator for Crossfade<S>
+where
+  S: Source<Item = f32>,
+{
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    // The previous entry has finished by the time this one plays
+    if let Some(handover) = self.handover_in.take()
+      && let Some(tail) = handover.lock().unwrap().take()
+    {
+      self.outgoing_len = tail.len();
+      self.outgoing = tail;
+    }
+
+    let own = self.next_own()?;
+    match self.outgoing.pop_front() {
+      Some(tail) => {
+        let t = 1.0 - self.outgoing.len() as f32 / self.outgoing_len as f32;
+        Some(own * (t * FRAC_PI_2).sin() + tail * (t * FRAC_PI_2).cos())
+      }
+      None => Some(own),
+    }
+  }
+}
+
+impl<S> Source for Crossfade<S>
+where
+  S: Source<Item = f32>,
+{
+  #[inline]
+  fn current_frame_len(&self) -> Option<usize> {
+    None
+  }
+  #[inline]
+  fn channels(&self) -> u16 {
+    self.inner.channels()
+  }
+  #[inline]
+  fn sample_rate(&self) -> u32 {
+    self.inner.sample_rate()
+  }
+  #[inline]
+  fn total_duration(&self) -> Option<Duration> {
+    self.inner.total_duration()
+  }
+
+  fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+    self.inner.try_seek(position)?;
+    // Both belong to where playback was
+    self.lookahead.clear();
+    self.outgoing.clear();
+    Ok(())
+  }
+}
@@ -0,0 +1,64 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+
+/// Speed of sound in air at room temperature, in metres per second.
+pub const SPEED_OF_SOUND: f32 = 343.0;
+/// A correlation peak has to stand this far above the average to count.
+const MIN_CONFIDENCE: f32 = 8.0;
+
+/// How far the measured signal lags the reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Delay {
+  pub seconds: f32,
+  /// Peak of the correlation over its average; higher is more trustworthy.
+  pub confidence: f32,
+}
+
+impl Delay {
+  pub fn millis(self) -> f32 {
+    self.seconds * 1000.0
+  }
+
+  /// Distance sound travels in that time.
+  pub fn metres(self) -> f32 {
+    self.seconds * SPEED_OF_SOUND
+  }
+}
+
+/// Finds the lag, up to `max_lag` samples, at which `measurement` best lines
+/// up with `reference`, using the phase transform (GCC-PHAT) so the sharp
+/// peak survives coloured and reverberant signals.
+pub fn estimate(
+  reference: &[f32],
+  measurement: &[f32],
+  max_lag: usize,
+  sample_rate: u32,
+) -> Option<Delay> {
+  let size = (reference.len() + measurement.len()).next_power_of_two();
+  let mut planner = FftPlanner::new();
+  let forward = planner.plan_fft_forward(size);
+
+  let spectrum = |signal: &[f32]| {
+    let mut buffer: Vec<Complex<f32>> = signal.iter().map(|&x| Complex::new(x, 0.0)).collect();
+    buffer.resize(size, Complex::new(0.0, 0.0));
+    forward.process(&mut buffer);
+    buffer
+  };
+  let mut cross: Vec<Complex<f32>> = spectrum(reference)
+    .iter()
+    .zip(spectrum(measurement))
+    .map(|(x, y)| {
+      let product = x.conj() * y;
+      // Whiten, keeping only the phase
+      product / product.norm().max(f32::MIN_POSITIVE)
+    })
+    .collect();
+  planner.plan_fft_inverse(size).process(&mut cross);
+
+  let lags = &cross[..max_lag.min(size)];
+  let (lag, peak) = lags.iter().map(|c| c.re).enumerate().max_by(|a, b| a.1.total_cmp(&b.1))?;
+  let average = lags.iter().map(|c| c.re.abs()).sum::<f32>() / lags.len() as f32;
+  let confidence = peak / average.max(f32::MIN_POSITIVE);
+
+  (confidence >= MIN_CONFIDENCE)
+    .then(|| Delay { seconds: lag as f32 / sample_rate as f32, confidence })
+}
@@ -0,0 +1,188 @@
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  widget::canvas::{self, Geometry},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+use crate::{
+  analysis::DecibelRange,
+  app::Message,
+  components::{
+    gradient::Gradient,
+    smoothing::{Region, RegionSmoothing},
+  },
+};
+
+/// Which band's energy drives an effect, if any.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum EnergyBinding {
+  #[default]
+  Off,
+  Bass,
+  Mids,
+  Treble,
+}
+
+impl EnergyBinding {
+  pub const ALL: [EnergyBinding; 4] =
+    [EnergyBinding::Off, EnergyBinding::Bass, EnergyBinding::Mids, EnergyBinding::Treble];
+
+  pub fn region(self) -> Option<Region> {
+    match self {
+      EnergyBinding::Off => None,
+      EnergyBinding::Bass => Some(Region::Low),
+      EnergyBinding::Mids => Some(Region::Mid),
+      EnergyBinding::Treble => Some(Region::High),
+    }
+  }
+}
+
+impl fmt::Display for EnergyBinding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      EnergyBinding::Off => "Off",
+      EnergyBinding::Bass => "Bass",
+      EnergyBinding::Mids => "Mids",
+      EnergyBinding::Treble => "Treble",
+    })
+  }
+}
+
+/// Energy of the bass, mids and treble, split at the smoothing regions'
+/// crossovers, each 0.0..=1.0 over the dB range.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BandEnergy {
+  levels: [f32; 3],
+}
+
+impl BandEnergy {
+  /// Takes in a magnitude spectrum (half an FFT) `elapsed` after the last,
+  /// smoothing each band with its region's envelope like the bars in it. An
+  /// empty spectrum lets every band fall back towards silence.
+  pub fn update(
+    &mut self,
+    spectrum: &[f32],
+    sample_rate: u32,
+    decibels: DecibelRange,
+    smoothing: &RegionSmoothing,
+    elapsed: Duration,
+  ) {
+    let bin_hz = sample_rate as f32 / (2 * spectrum.len().max(1)) as f32;
+    let mut power = [0.0f32; 3];
+    // The DC bin is no band's
+    for (bin, magnitude) in spectrum.iter().enumerate().skip(1) {
+      power[index(Region::of(bin as f32 * bin_hz))] += magnitude * magnitude;
+    }
+    for region in Region::ALL {
+      let level =
+        if spectrum.is_empty() { 0.0 } else { decibels.normalise(power[index(region)].sqrt()) };
+      let old = &mut self.levels[index(region)];
+      *old = smoothing.get(region).apply(*old, level.clamp(0.0, 1.0), elapsed);
+    }
+  }
+
+  pub fn level(&self, region: Region) -> f32 {
+    self.levels[index(region)]
+  }
+
+  /// The three bands' levels averaged.
+  pub fn overall(&self) -> f32 {
+    self.levels.iter().sum::<f32>() / self.levels.len() as f32
+  }
+
+  pub fn is_silent(&self) -> bool {
+    self.levels.iter().all(|&level| level < 0.01)
+  }
+}
+
+fn index(region: Region) -> usize {
+  match region {
+    Region::Low => 0,
+    Region::Mid => 1,
+    Region::High => 2,
+  }
+}
+
+/// Three big blocks, one per band, each filling up with its energy.
+pub struct EnergyCanvas {
+  pub energy: BandEnergy,
+  pub gradient: Gradient,
+  /// Draws the blocks greyed out while playback is muted.
+  pub muted: bool,
+}
+
+impl canvas::Program<Message> for EnergyCanvas {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    // Levels move every frame, so there's nothing worth caching
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    const LABEL_HEIGHT: f32 = 18.0;
+    const GAP: f32 = 8.0;
+    let block_height = (bounds.height - LABEL_HEIGHT).max(0.0);
+    let block_width = ((bounds.width - 2.0 * GAP) / 3.0).max(1.0);
+
+    for (i, binding) in
+      [EnergyBinding::Bass, EnergyBinding::Mids, EnergyBinding::Treble].into_iter().enumerate()
+    {
+      let level = binding.region().map_or(0.0, |region| self.energy.level(region));
+      let x = i as f32 * (block_width + GAP);
+      let color =
+        if self.muted { Color::from_rgb(0.5, 0.5, 0.5) } else { self.gradient.color(level) };
+
+      frame.fill_rectangle(
+        Point::new(x, 0.0),
+        Size::new(block_width, block_height),
+        Color { a: 0.15, ..color },
+      );
+      let height = level * block_height;
+      frame.fill_rectangle(
+        Point::new(x, block_height - height),
+        Size::new(block_width, height),
+        color,
+      );
+      frame.fill_text(canvas::Text {
+        content: format!("{} {:.0}%", binding, level * 100.0),
+        position: Point::new(x, block_height + 2.0),
+        color: Color::from_rgb(0.7, 0.7, 0.7),
+        size: 12.0.into(),
+        ..canvas::Text::default()
+      });
+    }
+
+    vec![frame.into_geometry()]
+  }
+}
+
+/// Tints the whole canvas with the gradient's colour for `level`, as
+/// strongly as the level is loud.
+pub struct EnergyBackground {
+  pub level: f32,
+  pub gradient: Gradient,
+}
+
+impl canvas::Program<Message> for EnergyBackground {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let color = self.gradient.color(self.level);
+    frame.fill_rectangle(Point::ORIGIN, bounds.size(), Color { a: 0.4 * self.level, ..color });
+    vec![frame.into_geometry()]
+  }
+}
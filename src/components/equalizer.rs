@@ -0,0 +1,386 @@
+use iced::{
+  Color, Point, Rectangle, Theme,
+  widget::canvas::{self, Geometry, Path, Stroke},
+};
+use rodio::{Source, source::SeekError};
+use rustfft::num_complex::Complex;
+use serde::{Deserialize, Serialize};
+use std::{
+  f32::consts::PI,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicU32, Ordering},
+  },
+  time::Duration,
+};
+
+use crate::{app::Message, components::binning::FrequencyScale};
+
+/// Centre of each band, an octave apart.
+pub const EQ_BANDS: [f32; 10] =
+  [31.25, 62.5, 125.0, 250.0, 500.0, 1000.0, 2000.0, 4000.0, 8000.0, 16000.0];
+/// Most a band cuts or boosts, in dB.
+pub const MAX_EQ_GAIN_DB: f32 = 12.0;
+/// Bandwidth of each band, about an octave.
+const EQ_Q: f32 = 1.41;
+/// Bands this close to Nyquist or over are left flat, as the filter can't
+/// be centred there.
+const MAX_CENTRE: f32 = 0.45;
+/// Points the response curve is drawn through, swept from `CURVE_LOW` Hz
+/// up to Nyquist.
+const CURVE_POINTS: usize = 200;
+const CURVE_LOW: f32 = 10.0;
+
+/// The graphic EQ's bands, in dB.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EqSettings {
+  pub enabled: bool,
+  pub gains_db: [f32; EQ_BANDS.len()],
+}
+
+impl EqSettings {
+  /// Whether it changes the sound at all.
+  pub fn is_active(&self) -> bool {
+    self.enabled && self.gains_db.iter().any(|&gain| gain != 0.0)
+  }
+
+  fn filters(&self, sample_rate: u32) -> [Biquad; EQ_BANDS.len()] {
+    std::array::from_fn(|band| Biquad::peaking(EQ_BANDS[band], self.gains_db[band], sample_rate))
+  }
+
+  /// What the bands add up to at `frequency`, in dB.
+  pub fn response_db(&self, frequency: f32, sample_rate: u32) -> f32 {
+    self.filters(sample_rate).iter().map(|filter| filter.response_db(frequency, sample_rate)).sum()
+  }
+
+  /// The settings with every gain a number within ±`MAX_EQ_GAIN_DB`, for
+  /// bands read from files; anything else is left flat.
+  pub fn repaired(self) -> Self {
+    Self {
+      gains_db: self.gains_db.map(|gain| {
+        if gain.is_finite() { gain.clamp(-MAX_EQ_GAIN_DB, MAX_EQ_GAIN_DB) } else { 0.0 }
+      }),
+      ..self
+    }
+  }
+
+  /// Gain put ahead of the bands so the loudest point of their curve sits
+  /// at 0 dB, keeping boosts from clipping. Nothing while no band boosts.
+  pub fn preamp_db(&self, sample_rate: u32) -> f32 {
+    if !self.gains_db.iter().any(|&gain| gain > 0.0) {
+      return 0.0;
+    }
+    let filters = self.filters(sample_rate);
+    let peak = sweep(sample_rate)
+      .chain(EQ_BANDS)
+      .map(|frequency| {
+        filters.iter().map(|filter| filter.response_db(frequency, sample_rate)).sum::<f32>()
+      })
+      .fold(0.0f32, f32::max);
+    -peak
+  }
+}
+
+/// `CURVE_POINTS` frequencies spaced evenly on a log scale from
+/// `CURVE_LOW` Hz up to Nyquist.
+fn sweep(sample_rate: u32) -> impl Iterator<Item = f32> {
+  let nyquist = sample_rate as f32 / 2.0;
+  (0..=CURVE_POINTS)
+    .map(move |i| CURVE_LOW * (nyquist / CURVE_LOW).powf(i as f32 / CURVE_POINTS as f32))
+}
+
+impl Default for EqSettings {
+  fn default() -> Self {
+    Self { enabled: false, gains_db: [0.0; EQ_BANDS.len()] }
+  }
+}
+
+/// A peaking filter, as in the RBJ cookbook, normalised so `a0` is 1.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Biquad {
+  b: [f32; 3],
+  a: [f32; 2],
+}
+
+impl Biquad {
+  const FLAT: Biquad = Biquad { b: [1.0, 0.0, 0.0], a: [0.0, 0.0] };
+
+  fn peaking(centre: f32, gain_db: f32, sample_rate: u32) -> Self {
+    if gain_db == 0.0 || centre >= MAX_CENTRE * sample_rate as f32 {
+      return Self::FLAT;
+    }
+    let amplitude = 10f32.powf(gain_db / 40.0);
+    let w0 = 2.0 * PI * centre / sample_rate as f32;
+    let alpha = w0.sin() / (2.0 * EQ_Q);
+    let cos = w0.cos();
+    let a0 = 1.0 + alpha / amplitude;
+    Self {
+      b: [(1.0 + alpha * amplitude) / a0, -2.0 * cos / a0, (1.0 - alpha * amplitude) / a0],
+      a: [-2.0 * cos / a0, (1.0 - alpha / amplitude) / a0],
+    }
+  }
+
+  /// Filters one sample, with the transposed direct form II state `z`.
+  #[inline]
+  fn process(&self, sample: f32, z: &mut [f32; 2]) -> f32 {
+    let out = self.b[0] * sample + z[0];
+    z[0] = self.b[1] * sample - self.a[0] * out + z[1];
+    z[1] = self.b[2] * sample - self.a[1] * out;
+    out
+  }
+
+  fn response_db(&self, frequency: f32, sample_rate: u32) -> f32 {
+    let w = 2.0 * PI * frequency / sample_rate as f32;
+    let z1 = Complex::from_polar(1.0, -w);
+    let z2 = z1 * z1;
+    let numerator = self.b[0] + z1 * self.b[1] + z2 * self.b[2];
+    let denominator = Complex::new(1.0, 0.0) + z1 * self.a[0] + z2 * self.a[1];
+    20.0 * (numerator.norm() / denominator.norm()).max(f32::MIN_POSITIVE).log10()
+  }
+}
+
+/// The EQ's bands, shared between the player and every entry it queues so
+/// a change reaches the one playing.
+#[derive(Debug, Clone)]
+pub struct EqControl {
+  enabled: Arc<AtomicBool>,
+  /// Each band's gain's f32 bits.
+  gains: Arc<[AtomicU32; EQ_BANDS.len()]>,
+  /// Bumped on every change, for the entries to notice.
+  version: Arc<AtomicU32>,
+}
+
+impl EqControl {
+  pub fn new() -> Self {
+    Self {
+      enabled: Arc::new(AtomicBool::new(false)),
+      gains: Arc::new(std::array::from_fn(|_| AtomicU32::new(0.0f32.to_bits()))),
+      version: Arc::new(AtomicU32::new(0)),
+    }
+  }
+
+  pub fn set(&self, settings: EqSettings) {
+    self.enabled.store(settings.enabled, Ordering::Relaxed);
+    for (slot, gain) in self.gains.iter().zip(settings.gains_db) {
+      slot.store(gain.to_bits(), Ordering::Relaxed);
+    }
+    self.version.fetch_add(1, Ordering::Release);
+  }
+
+  fn settings(&self) -> EqSettings {
+    EqSettings {
+      enabled: self.enabled.load(Ordering::Relaxed),
+      gains_db: std::array::from_fn(|band| {
+        f32::from_bits(self.gains[band].load(Ordering::Relaxed))
+      }),
+    }
+  }
+
+  fn version(&self) -> u32 {
+    self.version.load(Ordering::Acquire)
+  }
+}
+
+impl Default for EqControl {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// A `Source` wrapper that runs every channel through the EQ's bands,
+/// picking up changes from its [`EqControl`] between frames.
+pub struct Equalizer<S>
+where
+  S: Source<Item = f32>,
+{
+  inner: S,
+  control: EqControl,
+  /// Version of the control the filters were made from.
+  version: Option<u32>,
+  /// Whether there's anything to do, since a flat or disabled EQ passes the
+  /// samples straight through.
+  active: bool,
+  /// Linear gain applied ahead of the filters, from [`EqSettings::preamp_db`].
+  preamp: f32,
+  filters: [Biquad; EQ_BANDS.len()],
+  /// Filter state per channel, then per band.
+  state: Vec<[[f32; 2]; EQ_BANDS.len()]>,
+  /// Channel of the next sample.
+  channel: usize,
+  sample_rate: u32,
+}
+
+impl<S> Equalizer<S>
+where
+  S: Source<Item = f32>,
+{
+  pub fn new(inner: S, control: EqControl) -> Self {
+    Self {
+      inner,
+      control,
+      version: None,
+      active: false,
+      preamp: 1.0,
+      filters: [Biquad::FLAT; EQ_BANDS.len()],
+      state: Vec::new(),
+      channel: 0,
+      sample_rate: 0,
+    }
+  }
+
+  /// Remakes the filters if the bands or the format changed.
+  fn refresh(&mut self) {
+    let channels = self.inner.channels().max(1) as usize;
+    if self.state.len() != channels {
+      self.state = vec![[[0.0; 2]; EQ_BANDS.len()]; channels];
+    }
+    let version = self.control.version();
+    let sample_rate = self.inner.sample_rate();
+    if self.version == Some(version) && self.sample_rate == sample_rate {
+      return;
+    }
+    let settings = self.control.settings();
+    self.active = settings.is_active();
+    self.filters = settings.filters(sample_rate);
+    self.preamp = 10f32.powf(settings.preamp_db(sample_rate) / 20.0);
+    self.version = Some(version);
+    self.sample_rate = sample_rate;
+  }
+}
+
+impl<S> Iterator for Equalizer<S>
+where
+  S: Source<Item = f32>,
+{
+  type Item = f32;
+
+  #[inline]
+  fn next(&mut self) -> Option<f32> {
+    if self.channel == 0 {
+      self.refresh();
+    }
+    let sample = self.inner.next()?;
+    let channel = self.channel;
+    self.channel = (self.channel + 1) % self.state.len().max(1);
+    if !self.active {
+      return Some(sample);
+    }
+    let state = &mut self.state[channel];
+    Some(
+      self
+        .filters
+        .iter()
+        .zip(state.iter_mut())
+        .fold(sample * self.preamp, |sample, (filter, z)| filter.process(sample, z)),
+    )
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+impl<S> Source for Equalizer<S>
+where
+  S: Source<Item = f32>,
+{
+  #[inline]
+  fn current_frame_len(&self) -> Option<usize> {
+    self.inner.current_frame_len()
+  }
+
+  #[inline]
+  fn channels(&self) -> u16 {
+    self.inner.channels()
+  }
+
+  #[inline]
+  fn sample_rate(&self) -> u32 {
+    self.inner.sample_rate()
+  }
+
+  #[inline]
+  fn total_duration(&self) -> Option<Duration> {
+    self.inner.total_duration()
+  }
+
+  fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+    self.inner.try_seek(position)?;
+    // What was ringing before the seek doesn't belong after it
+    for state in &mut self.state {
+      *state = [[0.0; 2]; EQ_BANDS.len()];
+    }
+    self.channel = 0;
+    Ok(())
+  }
+}
+
+/// The EQ's curve and bands drawn over the analyser bars, so a change can
+/// be seen as it's heard.
+pub struct EqOverlay {
+  pub settings: EqSettings,
+  pub scale: FrequencyScale,
+  pub sample_rate: u32,
+}
+
+impl canvas::Program<Message> for EqOverlay {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let to_y = |db: f32| {
+      let db = db.clamp(-MAX_EQ_GAIN_DB, MAX_EQ_GAIN_DB);
+      bounds.height / 2.0 - db / MAX_EQ_GAIN_DB * bounds.height / 2.0
+    };
+
+    // Sampled evenly across a log sweep, then placed along the bars' scale
+    let points: Vec<Point> = sweep(self.sample_rate)
+      .map(|frequency| (frequency, self.scale.position(frequency, self.sample_rate)))
+      .filter(|(_, x)| (0.0..=1.0).contains(x))
+      .map(|(frequency, x)| {
+        Point::new(x * bounds.width, to_y(self.settings.response_db(frequency, self.sample_rate)))
+      })
+      .collect();
+    let curve = Path::new(|builder| {
+      for (i, &point) in points.iter().enumerate() {
+        if i == 0 {
+          builder.move_to(point);
+        } else {
+          builder.line_to(point);
+        }
+      }
+    });
+    let color = Color::from_rgba(1.0, 0.85, 0.3, 0.9);
+    frame.stroke(
+      &Path::line(Point::new(0.0, to_y(0.0)), Point::new(bounds.width, to_y(0.0))),
+      Stroke::default().with_color(Color { a: 0.3, ..color }).with_width(1.0),
+    );
+    frame.stroke(&curve, Stroke::default().with_color(color).with_width(2.0));
+
+    for (&centre, &gain) in EQ_BANDS.iter().zip(&self.settings.gains_db) {
+      let x = self.scale.position(centre, self.sample_rate);
+      if !(0.0..=1.0).contains(&x) {
+        continue;
+      }
+      frame.fill(&Path::circle(Point::new(x * bounds.width, to_y(gain)), 4.0), color);
+    }
+    frame.fill_text(canvas::Text {
+      content: format!("EQ (±{:.0} dB)", MAX_EQ_GAIN_DB),
+      position: Point::new(4.0, 4.0),
+      color,
+      size: 12.0.into(),
+      ..canvas::Text::default()
+    });
+
+    vec![frame.into_geometry()]
+  }
+}
@@ -0,0 +1,156 @@
+use iced::{
+  Color, Point, Rectangle, Theme,
+  widget::canvas::{self, Geometry, Path, Stroke},
+};
+use std::{
+  fmt,
+  time::{Duration, Instant},
+};
+
+use crate::{app::Message, components::binning::FrequencyScale};
+
+/// How far a bin must stand above its surroundings to count as narrowband.
+const MIN_PROMINENCE_DB: f32 = 15.0;
+/// Peaks quieter than this are left alone however narrow they are.
+const MIN_LEVEL_DB: f32 = -60.0;
+/// Bins either side that make up a peak's surroundings, skipping the closest
+/// two that the window's main lobe spills into.
+const NEIGHBOURHOOD: usize = 16;
+const MAIN_LOBE: usize = 2;
+/// How long a peak has to ring before it's flagged; music rarely holds a pure
+/// tone this steadily.
+const SUSTAIN: Duration = Duration::from_millis(1500);
+/// Most flagged frequencies reported at once, loudest first.
+const MAX_FLAGGED: usize = 3;
+
+/// A sustained narrowband peak, likely feedback.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ringing {
+  pub frequency: f32,
+  pub decibels: f32,
+  /// How long it has been ringing.
+  pub duration: Duration,
+}
+
+impl fmt::Display for Ringing {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if self.frequency >= 1000.0 {
+      write!(f, "{:.2} kHz ({:.0} dB)", self.frequency / 1000.0, self.decibels)
+    } else {
+      write!(f, "{:.0} Hz ({:.0} dB)", self.frequency, self.decibels)
+    }
+  }
+}
+
+/// Watches successive spectra for peaks that stay narrow, loud and put, the
+/// signature of a mic feeding back through the PA.
+#[derive(Debug, Default)]
+pub struct FeedbackDetector {
+  /// When each bin started being a narrowband peak, if it is one.
+  since: Vec<Option<Instant>>,
+  flagged: Vec<Ringing>,
+}
+
+impl FeedbackDetector {
+  /// Feeds one magnitude spectrum (the lower half of an FFT) and updates the
+  /// flagged frequencies.
+  pub fn process(&mut self, spectrum: &[f32], sample_rate: u32, now: Instant) {
+    let db: Vec<f32> = spectrum.iter().map(|&m| 20.0 * m.max(1e-9).log10()).collect();
+    if self.since.len() != db.len() {
+      self.since = vec![None; db.len()];
+    }
+
+    let mut since = vec![None; db.len()];
+    for bin in 1..db.len().saturating_sub(1) {
+      if !is_narrow_peak(&db, bin) {
+        continue;
+      }
+      // Feedback drifts by a bin now and then, so carry the earliest start
+      // of any neighbour over
+      let previous = self.since[bin - 1..=bin + 1].iter().flatten().min().copied();
+      since[bin] = Some(previous.unwrap_or(now));
+    }
+    self.since = since;
+
+    let bin_hz = sample_rate as f32 / (2.0 * db.len().max(1) as f32);
+    self.flagged = self
+      .since
+      .iter()
+      .enumerate()
+      .filter_map(|(bin, start)| {
+        let duration = now.duration_since((*start)?);
+        (duration >= SUSTAIN).then(|| Ringing {
+          frequency: bin as f32 * bin_hz,
+          decibels: db[bin],
+          duration,
+        })
+      })
+      .collect();
+    self.flagged.sort_by(|a, b| b.decibels.total_cmp(&a.decibels));
+    self.flagged.truncate(MAX_FLAGGED);
+  }
+
+  /// Frequencies ringing long enough to be feedback, loudest first.
+  pub fn flagged(&self) -> &[Ringing] {
+    &self.flagged
+  }
+}
+
+/// Whether `bin` is a local maximum standing well clear of its neighbourhood.
+fn is_narrow_peak(db: &[f32], bin: usize) -> bool {
+  let level = db[bin];
+  if level < MIN_LEVEL_DB || level < db[bin - 1] || level < db[bin + 1] {
+    return false;
+  }
+  let low = bin.saturating_sub(NEIGHBOURHOOD);
+  let high = (bin + NEIGHBOURHOOD).min(db.len() - 1);
+  let surroundings: Vec<f32> =
+    (low..=high).filter(|&i| i.abs_diff(bin) > MAIN_LOBE).map(|i| db[i]).collect();
+  if surroundings.is_empty() {
+    return false;
+  }
+  let average = surroundings.iter().sum::<f32>() / surroundings.len() as f32;
+  level - average >= MIN_PROMINENCE_DB
+}
+
+/// Marks each flagged frequency on the analyser with a line and its readout.
+pub struct FeedbackOverlay<'a> {
+  pub flagged: &'a [Ringing],
+  pub scale: FrequencyScale,
+  pub sample_rate: u32,
+}
+
+impl<'a> canvas::Program<Message> for FeedbackOverlay<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let color = Color::from_rgb(1.0, 0.2, 0.2);
+
+    for ringing in self.flagged {
+      let position = self.scale.position(ringing.frequency, self.sample_rate);
+      if !(0.0..=1.0).contains(&position) {
+        continue;
+      }
+      let x = position * bounds.width;
+      let line = Path::line(Point::new(x, 0.0), Point::new(x, bounds.height));
+      frame.stroke(&line, Stroke::default().with_color(color).with_width(2.0));
+      frame.fill_text(canvas::Text {
+        content: ringing.to_string(),
+        position: Point::new(x + 4.0, 20.0),
+        color,
+        size: 14.0.into(),
+        ..canvas::Text::default()
+      });
+    }
+
+    vec![frame.into_geometry()]
+  }
+}
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+/// How often the visuals move on while something's playing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FrameRate {
+  Fps30,
+  #[default]
+  Fps60,
+  Fps120,
+  /// Every frame the window redraws, usually the display's refresh rate.
+  Uncapped,
+}
+
+impl FrameRate {
+  pub const ALL: [FrameRate; 4] =
+    [FrameRate::Fps30, FrameRate::Fps60, FrameRate::Fps120, FrameRate::Uncapped];
+
+  /// Time between ticks, or `None` when the window's redraws set the pace.
+  pub fn interval(self) -> Option<Duration> {
+    match self {
+      FrameRate::Fps30 => Some(Duration::from_secs(1) / 30),
+      FrameRate::Fps60 => Some(Duration::from_secs(1) / 60),
+      FrameRate::Fps120 => Some(Duration::from_secs(1) / 120),
+      FrameRate::Uncapped => None,
+    }
+  }
+
+  /// The capped rate whose ticks come closest to every `interval_ms`.
+  pub fn nearest(interval_ms: u64) -> Self {
+    let interval = Duration::from_millis(interval_ms);
+    Self::ALL
+      .into_iter()
+      .filter_map(|rate| rate.interval().map(|ticks| (rate, ticks.abs_diff(interval))))
+      .min_by_key(|&(_, difference)| difference)
+      .map_or(FrameRate::default(), |(rate, _)| rate)
+  }
+}
+
+impl fmt::Display for FrameRate {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      FrameRate::Fps30 => "30 fps",
+      FrameRate::Fps60 => "60 fps",
+      FrameRate::Fps120 => "120 fps",
+      FrameRate::Uncapped => "Uncapped",
+    })
+  }
+}
@@ -0,0 +1,95 @@
+use rodio::{Source, source::SeekError};
+use std::{
+  sync::{
+    Arc,
+    atomic::{AtomicU32, Ordering},
+  },
+  time::Duration,
+};
+
+/// How far each sample moves towards a new gain, so a change glides in
+/// over a few milliseconds instead of clicking.
+const GLIDE: f32 = 0.002;
+
+/// A linear gain's f32 bits, shared between the player and one entry so a
+/// gain worked out after the entry was queued still reaches it.
+pub type GainSlot = Arc<AtomicU32>;
+
+pub fn gain_slot(gain: f32) -> GainSlot {
+  Arc::new(AtomicU32::new(gain.to_bits()))
+}
+
+pub fn set_gain(slot: &GainSlot, gain: f32) {
+  slot.store(gain.to_bits(), Ordering::Relaxed);
+}
+
+/// A `Source` wrapper that scales every sample by the gain in its
+/// [`GainSlot`].
+pub struct Gain<S>
+where
+  S: Source<Item = f32>,
+{
+  inner: S,
+  slot: GainSlot,
+  /// Gain applied to the last sample, on its way to the slot's.
+  current: f32,
+}
+
+impl<S> Gain<S>
+where
+  S: Source<Item = f32>,
+{
+  pub fn new(inner: S, slot: GainSlot) -> Self {
+    let current = f32::from_bits(slot.load(Ordering::Relaxed));
+    Self { inner, slot, current }
+  }
+}
+
+impl<S> Iterator for Gain<S>
+where
+  S: Source<Item = f32>,
+{
+  type Item = f32;
+
+  #[inline]
+  fn next(&mut self) -> Option<f32> {
+    let target = f32::from_bits(self.slot.load(Ordering::Relaxed));
+    self.current += (target - self.current) * GLIDE;
+    self.inner.next().map(|sample| sample * self.current)
+  }
+
+  #[inline]
+  fn size_hint(&self) -> (usize, Option<usize>) {
+    self.inner.size_hint()
+  }
+}
+
+impl<S> Source for Gain<S>
+where
+  S: Source<Item = f32>,
+{
+  #[inline]
+  fn current_frame_len(&self) -> Option<usize> {
+    self.inner.current_frame_len()
+  }
+
+  #[inline]
+  fn channels(&self) -> u16 {
+    self.inner.channels()
+  }
+
+  #[inline]
+  fn sample_rate(&self) -> u32 {
+    self.inner.sample_rate()
+  }
+
+  #[inline]
+  fn total_duration(&self) -> Option<Duration> {
+    self.inner.total_duration()
+  }
+
+  #[inline]
+  fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+    self.inner.try_seek(position)
+  }
+}
@@ -0,0 +1,48 @@
+use iced::Color;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+pub const BRIGHTNESS_RANGE: RangeInclusive<f32> = -0.5..=0.5;
+pub const CONTRAST_RANGE: RangeInclusive<f32> = 0.0..=2.0;
+pub const SATURATION_RANGE: RangeInclusive<f32> = 0.0..=2.0;
+pub const GAMMA_RANGE: RangeInclusive<f32> = 0.2..=3.0;
+
+/// Colour correction over everything the visuals draw, for matching a
+/// projector or screen at a venue. The defaults leave colours untouched.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColorGrade {
+  /// Added to every channel.
+  pub brightness: f32,
+  /// Spread of each channel around mid-grey; 1.0 leaves it.
+  pub contrast: f32,
+  /// Distance from the colour's own grey; 0.0 is monochrome.
+  pub saturation: f32,
+  /// Above 1.0 lifts the shadows, below darkens them.
+  pub gamma: f32,
+}
+
+impl ColorGrade {
+  /// Grades in two passes: saturation, contrast and brightness on the
+  /// colour as a whole, then gamma on each clamped channel. Alpha is kept.
+  pub fn apply(&self, color: Color) -> Color {
+    if *self == Self::default() {
+      return color;
+    }
+    // Rec. 709 luma
+    let luma = 0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b;
+    let gamma = self.gamma.max(*GAMMA_RANGE.start());
+    let channel = |value: f32| {
+      let value = luma + (value - luma) * self.saturation;
+      let value = (value - 0.5) * self.contrast + 0.5 + self.brightness;
+      value.clamp(0.0, 1.0).powf(1.0 / gamma)
+    };
+    Color { r: channel(color.r), g: channel(color.g), b: channel(color.b), a: color.a }
+  }
+}
+
+impl Default for ColorGrade {
+  fn default() -> Self {
+    Self { brightness: 0.0, contrast: 1.0, saturation: 1.0, gamma: 1.0 }
+  }
+}
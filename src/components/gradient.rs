@@ -0,0 +1,145 @@
+use std::fmt;
+
+use iced::Color;
+use serde::{Deserialize, Serialize};
+
+use super::grade::ColorGrade;
+
+/// Named colour themes; every visual style draws through the selected one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ColorTheme {
+  /// The original magenta look.
+  #[default]
+  Classic,
+  Rainbow,
+  Fire,
+  Ocean,
+  Monochrome,
+  /// A two-stop gradient between user-picked colours.
+  Custom,
+}
+
+pub const DEFAULT_CUSTOM_START: &str = "#1447e6";
+pub const DEFAULT_CUSTOM_END: &str = "#e60076";
+/// Stops in a palette taken from cover art.
+pub const PALETTE_SIZE: usize = 5;
+
+/// Gradient stops picked for one track, quiet to loud.
+pub type Palette = [[u8; 3]; PALETTE_SIZE];
+
+const CLASSIC: [[u8; 3]; 2] = [[0xe6, 0x4d, 0xe6], [0xff, 0xb3, 0xff]];
+
+const RAINBOW: [[u8; 3]; 7] = [
+  [0xe5, 0x39, 0x35],
+  [0xfb, 0x8c, 0x00],
+  [0xfd, 0xd8, 0x35],
+  [0x43, 0xa0, 0x47],
+  [0x00, 0xac, 0xc1],
+  [0x1e, 0x88, 0xe5],
+  [0x8e, 0x24, 0xaa],
+];
+
+const FIRE: [[u8; 3]; 5] = [
+  [0x80, 0x10, 0x00],
+  [0xd0, 0x28, 0x00],
+  [0xff, 0x78, 0x00],
+  [0xff, 0xc8, 0x20],
+  [0xff, 0xf8, 0xd0],
+];
+
+const OCEAN: [[u8; 3]; 5] = [
+  [0x0b, 0x1f, 0x5c],
+  [0x13, 0x4e, 0xa0],
+  [0x00, 0x87, 0xb8],
+  [0x1c, 0xc4, 0xc4],
+  [0xb8, 0xf4, 0xf0],
+];
+
+const MONOCHROME: [[u8; 3]; 2] = [[0x50, 0x50, 0x50], [0xff, 0xff, 0xff]];
+
+impl ColorTheme {
+  pub const ALL: [ColorTheme; 6] = [
+    ColorTheme::Classic,
+    ColorTheme::Rainbow,
+    ColorTheme::Fire,
+    ColorTheme::Ocean,
+    ColorTheme::Monochrome,
+    ColorTheme::Custom,
+  ];
+
+  fn stops(self) -> Option<&'static [[u8; 3]]> {
+    match self {
+      ColorTheme::Classic => Some(&CLASSIC),
+      ColorTheme::Rainbow => Some(&RAINBOW),
+      ColorTheme::Fire => Some(&FIRE),
+      ColorTheme::Ocean => Some(&OCEAN),
+      ColorTheme::Monochrome => Some(&MONOCHROME),
+      ColorTheme::Custom => None,
+    }
+  }
+}
+
+impl fmt::Display for ColorTheme {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      ColorTheme::Classic => "Classic",
+      ColorTheme::Rainbow => "Rainbow",
+      ColorTheme::Fire => "Fire",
+      ColorTheme::Ocean => "Ocean",
+      ColorTheme::Monochrome => "Monochrome",
+      ColorTheme::Custom => "Custom",
+    })
+  }
+}
+
+/// The active theme, plus the endpoints used when it's [`ColorTheme::Custom`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gradient {
+  pub theme: ColorTheme,
+  pub start: Color,
+  pub end: Color,
+  /// Colours from the playing track's cover art, used instead of the theme.
+  pub palette: Option<Palette>,
+  /// Correction applied on top of whichever colours are picked.
+  pub grade: ColorGrade,
+}
+
+impl Gradient {
+  /// Maps `value` in 0.0..=1.0 (quiet to loud) to a colour.
+  pub fn color(&self, value: f32) -> Color {
+    let value = value.clamp(0.0, 1.0);
+    let color = match (&self.palette, self.theme.stops()) {
+      (Some(palette), _) => interpolate(palette, value),
+      (None, Some(stops)) => interpolate(stops, value),
+      (None, None) => Color::from_rgb(
+        self.start.r + (self.end.r - self.start.r) * value,
+        self.start.g + (self.end.g - self.start.g) * value,
+        self.start.b + (self.end.b - self.start.b) * value,
+      ),
+    };
+    self.grade.apply(color)
+  }
+}
+
+impl Default for Gradient {
+  fn default() -> Self {
+    Self {
+      theme: ColorTheme::default(),
+      start: Color::parse(DEFAULT_CUSTOM_START).unwrap(),
+      end: Color::parse(DEFAULT_CUSTOM_END).unwrap(),
+      palette: None,
+      grade: ColorGrade::default(),
+    }
+  }
+}
+
+/// Samples evenly spaced RGB stops at `value` in 0.0..=1.0.
+pub fn interpolate(stops: &[[u8; 3]], value: f32) -> Color {
+  let position = value.clamp(0.0, 1.0) * (stops.len() - 1) as f32;
+  let index = (position as usize).min(stops.len() - 2);
+  let t = position - index as f32;
+  let (a, b) = (stops[index], stops[index + 1]);
+  let mix = |i: usize| (a[i] as f32 + (b[i] as f32 - a[i] as f32) * t) / 255.0;
+
+  Color::from_rgb(mix(0), mix(1), mix(2))
+}
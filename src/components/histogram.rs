@@ -0,0 +1,119 @@
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  widget::canvas::{self, Geometry},
+};
+
+use crate::{app::Message, components::gradient::Gradient};
+
+/// Quietest level the histogram resolves; anything below lands in the first bin.
+pub const HISTOGRAM_FLOOR_DB: f32 = -60.0;
+pub const HISTOGRAM_BINS: usize = 120;
+
+/// Running distribution of sample magnitudes (in dBFS) over the whole track.
+/// Heavily limited masters pile up against 0 dBFS; dynamic ones spread out.
+#[derive(Debug, Clone)]
+pub struct AmplitudeHistogram {
+  bins: Vec<u64>,
+  total: u64,
+}
+
+impl AmplitudeHistogram {
+  pub fn new(bins: usize) -> Self {
+    Self { bins: vec![0; bins.max(1)], total: 0 }
+  }
+
+  pub fn add(&mut self, samples: &[f32]) {
+    let last = self.bins.len() - 1;
+    for &sample in samples {
+      let magnitude = sample.abs();
+      let db = if magnitude > 0.0 { 20.0 * magnitude.log10() } else { HISTOGRAM_FLOOR_DB };
+      let position = (1.0 - db / HISTOGRAM_FLOOR_DB).clamp(0.0, 1.0);
+      self.bins[((position * last as f32).round() as usize).min(last)] += 1;
+    }
+    self.total += samples.len() as u64;
+  }
+
+  pub fn clear(&mut self) {
+    self.bins.iter_mut().for_each(|bin| *bin = 0);
+    self.total = 0;
+  }
+
+  pub fn total(&self) -> u64 {
+    self.total
+  }
+
+  /// Each bin relative to the fullest one, 0.0..=1.0.
+  pub fn normalised(&self) -> Vec<f32> {
+    let max = self.bins.iter().copied().max().unwrap_or(0).max(1) as f32;
+    self.bins.iter().map(|&count| count as f32 / max).collect()
+  }
+}
+
+impl Default for AmplitudeHistogram {
+  fn default() -> Self {
+    Self::new(HISTOGRAM_BINS)
+  }
+}
+
+/// Live plot of an [`AmplitudeHistogram`], quiet on the left and 0 dBFS on the right.
+pub struct HistogramCanvas<'a> {
+  /// Normalised bin heights.
+  pub levels: Vec<f32>,
+  pub total: u64,
+  pub gradient: Gradient,
+  pub muted: bool,
+  pub cache: &'a canvas::Cache,
+}
+
+impl<'a> canvas::Program<Message> for HistogramCanvas<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
+      const LABEL_HEIGHT: f32 = 20.0;
+      let plot_height = (bounds.height - LABEL_HEIGHT).max(0.0);
+      let bin_width = bounds.width / self.levels.len().max(1) as f32;
+
+      for (i, &level) in self.levels.iter().enumerate() {
+        let height = level * plot_height;
+        let position = i as f32 / (self.levels.len().max(2) - 1) as f32;
+        let color =
+          if self.muted { Color::from_rgb(0.5, 0.5, 0.5) } else { self.gradient.color(position) };
+        frame.fill_rectangle(
+          Point::new(i as f32 * bin_width, plot_height - height),
+          Size::new((bin_width - 1.0).max(1.0), height),
+          color,
+        );
+      }
+
+      // dBFS axis every 12 dB
+      let label_color = Color::from_rgb(0.7, 0.7, 0.7);
+      for db in (HISTOGRAM_FLOOR_DB as i32..=0).step_by(12) {
+        let x = (1.0 - db as f32 / HISTOGRAM_FLOOR_DB) * bounds.width;
+        frame.fill_text(canvas::Text {
+          content: format!("{} dB", db),
+          position: Point::new(x.min(bounds.width - 40.0), plot_height + 4.0),
+          color: label_color,
+          size: 12.0.into(),
+          ..canvas::Text::default()
+        });
+      }
+      frame.fill_text(canvas::Text {
+        content: format!("{} samples", self.total),
+        position: Point::new(4.0, 4.0),
+        color: label_color,
+        size: 12.0.into(),
+        ..canvas::Text::default()
+      });
+    });
+
+    vec![geometry]
+  }
+}
@@ -0,0 +1,695 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::RangeInclusive};
+
+use iced::{Point, Rectangle, Vector};
+use svgtypes::{SimplePathSegment, SimplifyingPathParser};
+use ttf_parser::OutlineBuilder;
+
+use crate::{
+  app::{DEFAULT_BAR_GAP, DEFAULT_BAR_WIDTH, MAX_BAR_HEIGHT, MIN_BAR_HEIGHT, MIN_BAR_WIDTH},
+  components::visualiser::resample_bars,
+};
+
+/// Segments used to flatten each Bézier curve of a custom path.
+const CURVE_STEPS: usize = 16;
+/// Mask images are downscaled to fit this many pixels per side before edge detection.
+const MASK_RESOLUTION: u32 = 256;
+/// Fastest spin offered, in degrees per second either way.
+pub const MAX_SPIN: f32 = 180.0;
+/// Inner radius, as a share of half the canvas's shorter side.
+pub const INNER_RADIUS_RANGE: RangeInclusive<f32> = 0.1..=0.8;
+const DEFAULT_INNER_RADIUS: f32 = 0.4;
+/// Shorter side of the canvas, in logical pixels, the bar constants are
+/// sized for; bars on other canvases are scaled by the ratio, so a resized
+/// window or a high-DPI display keeps the same proportions.
+const REFERENCE_SIZE: f32 = 500.0;
+const SCALE_RANGE: RangeInclusive<f32> = 0.25..=4.0;
+
+/// The shape the bars are arranged along.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LayoutKind {
+  #[default]
+  Circle,
+  Ellipse,
+  RoundedRect,
+  CustomPath,
+  Text,
+  Mask,
+}
+
+impl LayoutKind {
+  pub const ALL: [LayoutKind; 6] = [
+    LayoutKind::Circle,
+    LayoutKind::Ellipse,
+    LayoutKind::RoundedRect,
+    LayoutKind::CustomPath,
+    LayoutKind::Text,
+    LayoutKind::Mask,
+  ];
+}
+
+impl fmt::Display for LayoutKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      LayoutKind::Circle => "Circle",
+      LayoutKind::Ellipse => "Ellipse",
+      LayoutKind::RoundedRect => "Rounded rect",
+      LayoutKind::CustomPath => "Custom path",
+      LayoutKind::Text => "Text",
+      LayoutKind::Mask => "Image mask",
+    })
+  }
+}
+
+/// Closed polylines in their own coordinate space; fitted to the canvas at draw time.
+#[derive(Debug, Clone, Default)]
+pub struct Contours(pub Vec<Vec<Point>>);
+
+impl Contours {
+  /// Flattens SVG path data (the `d` attribute) into closed polylines.
+  pub fn from_svg_path(data: &str) -> Result<Self, svgtypes::Error> {
+    let mut builder = ContourBuilder::default();
+
+    for segment in SimplifyingPathParser::from(data) {
+      match segment? {
+        SimplePathSegment::MoveTo { x, y } => builder.move_to(x as f32, y as f32),
+        SimplePathSegment::LineTo { x, y } => builder.line_to(x as f32, y as f32),
+        SimplePathSegment::Quadratic { x1, y1, x, y } => {
+          builder.quad_to(x1 as f32, y1 as f32, x as f32, y as f32)
+        }
+        SimplePathSegment::CurveTo { x1, y1, x2, y2, x, y } => {
+          builder.curve_to(x1 as f32, y1 as f32, x2 as f32, y2 as f32, x as f32, y as f32)
+        }
+        SimplePathSegment::ClosePath => builder.close(),
+      }
+    }
+
+    Ok(builder.finish())
+  }
+
+  /// Lays `text` out on a single line with the given font and flattens the glyph outlines.
+  pub fn from_text(font_data: &[u8], text: &str) -> Result<Self, ttf_parser::FaceParsingError> {
+    let face = ttf_parser::Face::parse(font_data, 0)?;
+    let mut builder = ContourBuilder { flip_y: true, ..ContourBuilder::default() };
+
+    for ch in text.chars() {
+      let Some(glyph) = face.glyph_index(ch) else {
+        continue;
+      };
+      face.outline_glyph(glyph, &mut builder);
+      builder.origin_x += face.glyph_hor_advance(glyph).unwrap_or(0) as f32;
+    }
+
+    Ok(builder.finish())
+  }
+
+  /// Reads the first `<path d="...">` of an SVG document.
+  pub fn from_svg_document(document: &str) -> Result<Self, SvgError> {
+    let document = roxmltree::Document::parse(document).map_err(SvgError::Xml)?;
+    let data = document
+      .descendants()
+      .filter(|node| node.tag_name().name() == "path")
+      .find_map(|node| node.attribute("d"))
+      .ok_or(SvgError::NoPath)?;
+    Self::from_svg_path(data).map_err(SvgError::Path)
+  }
+
+  fn bounds(&self) -> Option<Rectangle> {
+    let mut points = self.0.iter().flatten();
+    let first = points.next()?;
+    let (mut min, mut max) = (*first, *first);
+    for p in points {
+      min = Point::new(min.x.min(p.x), min.y.min(p.y));
+      max = Point::new(max.x.max(p.x), max.y.max(p.y));
+    }
+    Some(Rectangle::new(min, iced::Size::new(max.x - min.x, max.y - min.y)))
+  }
+
+  /// Scales the contours uniformly to fit inside `area`, centered.
+  fn fitted(&self, area: Rectangle) -> Vec<Vec<Point>> {
+    let Some(source) = self.bounds() else {
+      return Vec::new();
+    };
+    let scale = (area.width / source.width.max(f32::EPSILON))
+      .min(area.height / source.height.max(f32::EPSILON));
+    let offset = Vector::new(
+      area.x + (area.width - source.width * scale) / 2.0,
+      area.y + (area.height - source.height * scale) / 2.0,
+    );
+
+    self
+      .0
+      .iter()
+      .map(|contour| {
+        contour
+          .iter()
+          .map(|p| {
+            Point::new((p.x - source.x) * scale + offset.x, (p.y - source.y) * scale + offset.y)
+          })
+          .collect()
+      })
+      .collect()
+  }
+}
+
+/// Why an SVG document gave no contours.
+#[derive(Debug)]
+pub enum SvgError {
+  Xml(roxmltree::Error),
+  /// Nothing like `<path d="...">` in it.
+  NoPath,
+  Path(svgtypes::Error),
+}
+
+impl fmt::Display for SvgError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      SvgError::Xml(e) => write!(f, "not valid SVG: {}", e),
+      SvgError::NoPath => write!(f, "no <path> element found"),
+      SvgError::Path(e) => write!(f, "bad path data: {}", e),
+    }
+  }
+}
+
+/// Edge pixels of a black/white mask image, each with the direction pointing
+/// away from the shape. Sorted by angle around the shape's centroid so the
+/// frequency bands sweep around it like they do on the ring.
+#[derive(Debug, Clone, Default)]
+pub struct MaskEdges {
+  edges: Vec<Anchor>,
+  size: iced::Size,
+}
+
+impl MaskEdges {
+  pub fn from_image(path: &std::path::Path) -> Result<Self, image::ImageError> {
+    let mask = image::open(path)?.thumbnail(MASK_RESOLUTION, MASK_RESOLUTION).to_luma8();
+    let (width, height) = mask.dimensions();
+
+    // Whatever colour dominates the border is background, the rest is the shape
+    let border: Vec<bool> = (0..width)
+      .flat_map(|x| [(x, 0), (x, height - 1)])
+      .chain((0..height).flat_map(|y| [(0, y), (width - 1, y)]))
+      .map(|(x, y)| mask.get_pixel(x, y)[0] >= 128)
+      .collect();
+    let background_is_light = border.iter().filter(|&&light| light).count() * 2 > border.len();
+
+    let inside = |x: i64, y: i64| -> f32 {
+      if x < 0 || y < 0 || x >= width as i64 || y >= height as i64 {
+        return 0.0;
+      }
+      let light = mask.get_pixel(x as u32, y as u32)[0] >= 128;
+      if light != background_is_light { 1.0 } else { 0.0 }
+    };
+
+    let mut edges = Vec::new();
+    for y in 0..height as i64 {
+      for x in 0..width as i64 {
+        if inside(x, y) == 0.0 {
+          continue;
+        }
+        // Sobel gradient of the shape points inwards, so the normal is its opposite
+        let gx = (inside(x + 1, y - 1) + 2.0 * inside(x + 1, y) + inside(x + 1, y + 1))
+          - (inside(x - 1, y - 1) + 2.0 * inside(x - 1, y) + inside(x - 1, y + 1));
+        let gy = (inside(x - 1, y + 1) + 2.0 * inside(x, y + 1) + inside(x + 1, y + 1))
+          - (inside(x - 1, y - 1) + 2.0 * inside(x, y - 1) + inside(x + 1, y - 1));
+        let length = (gx * gx + gy * gy).sqrt();
+        if length > f32::EPSILON {
+          edges.push(Anchor {
+            position: Point::new(x as f32 + 0.5, y as f32 + 0.5),
+            normal: Vector::new(-gx / length, -gy / length),
+          });
+        }
+      }
+    }
+
+    let count = edges.len().max(1) as f32;
+    let centroid = edges.iter().fold(Point::ORIGIN, |sum, e| {
+      Point::new(sum.x + e.position.x / count, sum.y + e.position.y / count)
+    });
+    edges.sort_by(|a, b| {
+      let angle_a = (a.position.y - centroid.y).atan2(a.position.x - centroid.x);
+      let angle_b = (b.position.y - centroid.y).atan2(b.position.x - centroid.x);
+      angle_a.total_cmp(&angle_b)
+    });
+
+    Ok(Self { edges, size: iced::Size::new(width as f32, height as f32) })
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.edges.is_empty()
+  }
+}
+
+/// The pre-computed geometry a layout needs beyond its kind.
+#[derive(Debug, Clone, Copy)]
+pub enum Shape<'a> {
+  Contours(&'a Contours),
+  Mask(&'a MaskEdges),
+}
+
+/// Collects flattened outlines from path commands.
+///
+/// Font outlines are y-up, so glyph points get mirrored vertically; the fit to the
+/// canvas happens later and doesn't care about the absolute position.
+#[derive(Default)]
+struct ContourBuilder {
+  contours: Vec<Vec<Point>>,
+  current: Vec<Point>,
+  origin_x: f32,
+  flip_y: bool,
+}
+
+impl ContourBuilder {
+  fn point(&self, x: f32, y: f32) -> Point {
+    Point::new(self.origin_x + x, if self.flip_y { -y } else { y })
+  }
+
+  fn last(&self) -> Point {
+    self.current.last().copied().unwrap_or(Point::ORIGIN)
+  }
+
+  fn finish(mut self) -> Contours {
+    self.close();
+    Contours(self.contours)
+  }
+}
+
+impl OutlineBuilder for ContourBuilder {
+  fn move_to(&mut self, x: f32, y: f32) {
+    self.close();
+    self.current.push(self.point(x, y));
+  }
+
+  fn line_to(&mut self, x: f32, y: f32) {
+    self.current.push(self.point(x, y));
+  }
+
+  fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+    let (start, control, end) = (self.last(), self.point(x1, y1), self.point(x, y));
+    for step in 1..=CURVE_STEPS {
+      let t = step as f32 / CURVE_STEPS as f32;
+      let mt = 1.0 - t;
+      self.current.push(Point::new(
+        mt * mt * start.x + 2.0 * mt * t * control.x + t * t * end.x,
+        mt * mt * start.y + 2.0 * mt * t * control.y + t * t * end.y,
+      ));
+    }
+  }
+
+  fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+    let (start, c1, c2, end) =
+      (self.last(), self.point(x1, y1), self.point(x2, y2), self.point(x, y));
+    for step in 1..=CURVE_STEPS {
+      let t = step as f32 / CURVE_STEPS as f32;
+      let mt = 1.0 - t;
+      let (a, b, c, d) = (mt * mt * mt, 3.0 * mt * mt * t, 3.0 * mt * t * t, t * t * t);
+      self.current.push(Point::new(
+        a * start.x + b * c1.x + c * c2.x + d * end.x,
+        a * start.y + b * c1.y + c * c2.y + d * end.y,
+      ));
+    }
+  }
+
+  fn close(&mut self) {
+    if self.current.len() > 2 {
+      self.contours.push(std::mem::take(&mut self.current));
+    }
+    self.current.clear();
+  }
+}
+
+/// How the bars repeat around the layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Mirror {
+  /// The spectrum once round, lowest bar first.
+  #[default]
+  None,
+  /// Out to the top of the spectrum over one half and back over the other.
+  Half,
+  /// Out and back twice, so each quarter mirrors its neighbours.
+  Quarter,
+}
+
+impl Mirror {
+  pub const ALL: [Mirror; 3] = [Mirror::None, Mirror::Half, Mirror::Quarter];
+
+  fn folds(self) -> usize {
+    match self {
+      Mirror::None => 1,
+      Mirror::Half => 2,
+      Mirror::Quarter => 4,
+    }
+  }
+
+  /// Which of `distinct` bars anchor `index` of `anchors` shows: each fold
+  /// runs out through them and the next runs back.
+  pub fn source(self, index: usize, anchors: usize, distinct: usize) -> usize {
+    let folds = self.folds();
+    if folds == 1 {
+      return index;
+    }
+    let along = (index as f32 + 0.5) / anchors as f32 * folds as f32;
+    let within =
+      if (along as usize).is_multiple_of(2) { along.fract() } else { 1.0 - along.fract() };
+    ((within * distinct as f32) as usize).min(distinct.saturating_sub(1))
+  }
+
+  /// How many bars `anchors` anchors draw for `count` values, and how many
+  /// of those differ.
+  pub fn counts(self, count: usize, anchors: usize) -> (usize, usize) {
+    match self.folds() {
+      1 => (anchors.min(count), anchors.min(count)),
+      folds => (anchors, anchors.div_ceil(folds).min(count)),
+    }
+  }
+
+  /// `data` arranged over `anchors` bars.
+  pub fn arrange(self, data: &[f32], anchors: usize) -> Vec<f32> {
+    let (drawn, distinct) = self.counts(data.len(), anchors);
+    let distinct = resample_bars(data, distinct);
+    (0..drawn)
+      .map(|index| {
+        distinct.get(self.source(index, drawn, distinct.len())).copied().unwrap_or(MIN_BAR_HEIGHT)
+      })
+      .collect()
+  }
+}
+
+impl fmt::Display for Mirror {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Mirror::None => "No mirror",
+      Mirror::Half => "Half mirror",
+      Mirror::Quarter => "Quarter mirror",
+    })
+  }
+}
+
+/// Where the circle layout starts, how it spins, mirrors and how far out
+/// its bars start.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RingSettings {
+  /// Where the first bar sits, in degrees clockwise from three o'clock.
+  pub start_angle: f32,
+  /// In degrees per second; negative spins anticlockwise.
+  pub spin: f32,
+  pub mirror: Mirror,
+  /// Radius the bars grow out from, as a share of half the canvas's
+  /// shorter side.
+  pub inner_radius: f32,
+}
+
+impl RingSettings {
+  /// Angle of the first bar `time` seconds in, in radians.
+  pub fn angle(self, time: f32) -> f32 {
+    (self.start_angle + self.spin * time).rem_euclid(360.0).to_radians()
+  }
+}
+
+impl Default for RingSettings {
+  fn default() -> Self {
+    Self { start_angle: 0.0, spin: 0.0, mirror: Mirror::None, inner_radius: DEFAULT_INNER_RADIUS }
+  }
+}
+
+/// A point on the layout a bar grows out of, and the unit direction it grows in.
+#[derive(Debug, Clone, Copy)]
+pub struct Anchor {
+  pub position: Point,
+  pub normal: Vector,
+}
+
+/// Where every bar goes for one frame.
+pub struct Placement {
+  pub anchors: Vec<Anchor>,
+  pub bar_width: f32,
+  pub max_bar_height: f32,
+  /// How much bigger than the reference canvas this one is.
+  pub scale: f32,
+}
+
+impl Placement {
+  pub fn compute(
+    kind: LayoutKind,
+    shape: Option<Shape<'_>>,
+    bounds: Rectangle,
+    requested_bars: usize,
+    ring: RingSettings,
+    time: f32,
+  ) -> Self {
+    let scale = (bounds.width.min(bounds.height) / REFERENCE_SIZE)
+      .clamp(*SCALE_RANGE.start(), *SCALE_RANGE.end());
+    // Non-circular shapes leave this much room around them for the bars to grow into
+    let margin = bounds.width.min(bounds.height) * 0.2;
+    let inset = Rectangle::new(
+      Point::new(margin, margin),
+      iced::Size::new(
+        (bounds.width - 2.0 * margin).max(1.0),
+        (bounds.height - 2.0 * margin).max(1.0),
+      ),
+    );
+
+    match (kind, shape) {
+      (LayoutKind::Ellipse, _) => {
+        let contour = ellipse(inset, 256);
+        Self::along(&[contour], requested_bars, margin, scale)
+      }
+      (LayoutKind::RoundedRect, _) => {
+        let contour = rounded_rect(inset, inset.width.min(inset.height) * 0.25);
+        Self::along(&[contour], requested_bars, margin, scale)
+      }
+      (LayoutKind::CustomPath | LayoutKind::Text, Some(Shape::Contours(contours)))
+        if !contours.0.is_empty() =>
+      {
+        Self::along(&contours.fitted(inset), requested_bars, margin, scale)
+      }
+      (LayoutKind::Mask, Some(Shape::Mask(mask))) if !mask.is_empty() => {
+        Self::from_mask(mask, inset, requested_bars, margin, scale)
+      }
+      _ => Self::ring(bounds, requested_bars, ring, time, scale),
+    }
+  }
+
+  /// How tall a bar of `height`, in the analyser's `MIN_BAR_HEIGHT` to
+  /// `MAX_BAR_HEIGHT` units, is drawn on this canvas.
+  pub fn bar_height(&self, height: f32) -> f32 {
+    let scaled = MIN_BAR_HEIGHT + (height - MIN_BAR_HEIGHT) * self.scale;
+    scaled.max(MIN_BAR_HEIGHT).min(self.max_bar_height)
+  }
+
+  /// Where `bar_height` puts a bar between the shortest and the tallest
+  /// that fits, 0.0..=1.0.
+  pub fn intensity(&self, bar_height: f32) -> f32 {
+    ((bar_height - MIN_BAR_HEIGHT) / (self.max_bar_height - MIN_BAR_HEIGHT).max(1.0))
+      .clamp(0.0, 1.0)
+  }
+
+  fn ring(
+    bounds: Rectangle,
+    requested_bars: usize,
+    settings: RingSettings,
+    time: f32,
+    scale: f32,
+  ) -> Self {
+    let center = Point::new(bounds.width * 0.5, bounds.height * 0.5);
+    let half = bounds.width.min(bounds.height) / 2.0;
+    let radius =
+      half * settings.inner_radius.clamp(*INNER_RADIUS_RANGE.start(), *INNER_RADIUS_RANGE.end());
+    let max_bar_height = half - radius;
+    let starting_angle = settings.angle(time);
+
+    let ring = RingLayout::fit(radius, requested_bars, scale);
+    let anchors = (0..ring.bars)
+      .map(|i| {
+        let angle = (i as f32 * ring.angle_step) + starting_angle;
+        let normal = Vector::new(angle.cos(), angle.sin());
+        Anchor { position: center + normal * radius, normal }
+      })
+      .collect();
+
+    Self { anchors, bar_width: ring.bar_width, max_bar_height, scale }
+  }
+
+  /// Picks evenly spread mask edge pixels, scaled uniformly into `area`.
+  fn from_mask(
+    mask: &MaskEdges,
+    area: Rectangle,
+    requested_bars: usize,
+    max_bar_height: f32,
+    bar_scale: f32,
+  ) -> Self {
+    let scale = (area.width / mask.size.width).min(area.height / mask.size.height);
+    let offset = Vector::new(
+      area.x + (area.width - mask.size.width * scale) / 2.0,
+      area.y + (area.height - mask.size.height * scale) / 2.0,
+    );
+
+    // Each edge pixel stands for roughly one pixel of outline
+    let outline = mask.edges.len() as f32 * scale;
+    let bars = requested_bars
+      .min((outline / (MIN_BAR_WIDTH + DEFAULT_BAR_GAP)).floor() as usize)
+      .min(mask.edges.len());
+    if bars == 0 {
+      return Self { anchors: Vec::new(), bar_width: 0.0, max_bar_height, scale: bar_scale };
+    }
+    let bar_width =
+      (DEFAULT_BAR_WIDTH * 1.2 * bar_scale).min(outline / bars as f32 - DEFAULT_BAR_GAP);
+
+    let anchors = (0..bars)
+      .map(|i| {
+        let edge = mask.edges[i * mask.edges.len() / bars];
+        Anchor {
+          position: Point::new(
+            edge.position.x * scale + offset.x,
+            edge.position.y * scale + offset.y,
+          ),
+          normal: edge.normal,
+        }
+      })
+      .collect();
+
+    Self { anchors, bar_width, max_bar_height, scale: bar_scale }
+  }
+
+  /// Spaces bars evenly by arc length along closed contours.
+  fn along(
+    contours: &[Vec<Point>],
+    requested_bars: usize,
+    max_bar_height: f32,
+    scale: f32,
+  ) -> Self {
+    let lengths: Vec<f32> = contours.iter().map(|c| perimeter(c)).collect();
+    let total: f32 = lengths.iter().sum();
+
+    let mut bars = requested_bars;
+    if bars > 0 && total / bars as f32 - DEFAULT_BAR_GAP < MIN_BAR_WIDTH {
+      bars = ((total / (MIN_BAR_WIDTH + DEFAULT_BAR_GAP)).floor() as usize).min(requested_bars);
+    }
+    if bars == 0 || total <= 0.0 {
+      return Self { anchors: Vec::new(), bar_width: 0.0, max_bar_height, scale };
+    }
+
+    let spacing = total / bars as f32;
+    let bar_width = (DEFAULT_BAR_WIDTH * 1.2 * scale).min(spacing - DEFAULT_BAR_GAP);
+
+    // Orient every normal by the winding of the largest contour, so the holes of
+    // glyph-like shapes still point away from the filled area
+    let outward = contours
+      .iter()
+      .map(|c| signed_area(c))
+      .max_by(|a, b| a.abs().total_cmp(&b.abs()))
+      .map_or(1.0, |area| area.signum());
+
+    let mut anchors = Vec::with_capacity(bars);
+    let mut contour_start = 0.0;
+    let mut next = spacing / 2.0;
+    for (contour, &length) in contours.iter().zip(&lengths) {
+      let mut travelled = contour_start;
+      for (i, &a) in contour.iter().enumerate() {
+        let b = contour[(i + 1) % contour.len()];
+        let segment = b - a;
+        let segment_length = (segment.x * segment.x + segment.y * segment.y).sqrt();
+        if segment_length <= f32::EPSILON {
+          continue;
+        }
+        let tangent = segment * (1.0 / segment_length);
+        let normal = Vector::new(tangent.y, -tangent.x) * outward;
+
+        while next < travelled + segment_length && anchors.len() < bars {
+          let t = (next - travelled) / segment_length;
+          anchors.push(Anchor { position: a + segment * t, normal });
+          next += spacing;
+        }
+        travelled += segment_length;
+      }
+      contour_start += length;
+    }
+
+    Self { anchors, bar_width, max_bar_height, scale }
+  }
+}
+
+/// How the bars actually fit around a ring of a given radius.
+///
+/// Bar width and the gap between bars are measured along the inner edge of
+/// the ring, so adjacent bars never touch even when the radius gets small.
+struct RingLayout {
+  bars: usize,
+  angle_step: f32,
+  bar_width: f32,
+}
+
+impl RingLayout {
+  fn fit(radius: f32, requested_bars: usize, scale: f32) -> Self {
+    let circumference = 2.0 * std::f32::consts::PI * radius;
+
+    // Shrink the bars first, then drop bars once they'd get thinner than MIN_BAR_WIDTH
+    let mut bars = requested_bars;
+    if bars > 0 && circumference / bars as f32 - DEFAULT_BAR_GAP < MIN_BAR_WIDTH {
+      bars = (circumference / (MIN_BAR_WIDTH + DEFAULT_BAR_GAP)).floor().max(1.0) as usize;
+      bars = bars.min(requested_bars);
+    }
+    if bars == 0 || radius <= 0.0 {
+      return Self { bars: 0, angle_step: 0.0, bar_width: 0.0 };
+    }
+
+    let angle_step = 2.0 * std::f32::consts::PI / bars as f32;
+
+    // A flat bar of width w touches the ring at half-angle atan(w / 2r), so the widest bar
+    // that still leaves DEFAULT_BAR_GAP of arc to its neighbour is 2r * tan((step - gap / r) / 2)
+    let free_angle = (angle_step - DEFAULT_BAR_GAP / radius).max(0.0);
+    let max_width = 2.0 * radius * (free_angle / 2.0).tan();
+    let bar_width = (DEFAULT_BAR_WIDTH * 1.2 * scale).min(max_width);
+
+    Self { bars, angle_step, bar_width }
+  }
+}
+
+fn ellipse(area: Rectangle, steps: usize) -> Vec<Point> {
+  let center = area.center();
+  (0..steps)
+    .map(|i| {
+      let angle = 2.0 * std::f32::consts::PI * i as f32 / steps as f32;
+      Point::new(
+        center.x + area.width / 2.0 * angle.cos(),
+        center.y + area.height / 2.0 * angle.sin(),
+      )
+    })
+    .collect()
+}
+
+fn rounded_rect(area: Rectangle, corner: f32) -> Vec<Point> {
+  const CORNER_STEPS: usize = 16;
+  // Corner centres and the angle each quarter arc starts at, clockwise on screen
+  let corners = [
+    (Point::new(area.x + area.width - corner, area.y + area.height - corner), 0.0),
+    (Point::new(area.x + corner, area.y + area.height - corner), 0.5),
+    (Point::new(area.x + corner, area.y + corner), 1.0),
+    (Point::new(area.x + area.width - corner, area.y + corner), 1.5),
+  ];
+
+  corners
+    .iter()
+    .flat_map(|&(center, start)| {
+      (0..=CORNER_STEPS).map(move |i| {
+        let angle = std::f32::consts::PI * (start + 0.5 * i as f32 / CORNER_STEPS as f32);
+        Point::new(center.x + corner * angle.cos(), center.y + corner * angle.sin())
+      })
+    })
+    .collect()
+}
+
+fn perimeter(contour: &[Point]) -> f32 {
+  (0..contour.len()).map(|i| contour[i].distance(contour[(i + 1) % contour.len()])).sum()
+}
+
+fn signed_area(contour: &[Point]) -> f32 {
+  (0..contour.len())
+    .map(|i| {
+      let (a, b) = (contour[i], contour[(i + 1) % contour.len()]);
+      a.x * b.y - b.x * a.y
+    })
+    .sum::<f32>()
+    / 2.0
+}
@@ -0,0 +1,119 @@
+use rodio::{Source, source::SeekError};
+use std::{
+  sync::{Arc, Mutex},
+  time::Duration,
+};
+
+/// Start and end of the A/B loop, shared between the player and the entry
+/// playing.
+pub type LoopRegion = Arc<Mutex<Option<(Duration, Duration)>>>;
+
+/// A `Source` wrapper that counts the frames it plays, so it knows to the
+/// sample where in the track it is, and jumps from the end of the
+/// [`LoopRegion`] straight back to its start.
+///
+/// Sits right on the decoder, so it counts in the track's own time
+/// whatever the playback rate, and the jump lands between two samples
+/// without a gap.
+pub struct Looping<S>
+where
+  S: Source<Item = f32>,
+{
+  inner: S,
+  region: LoopRegion,
+  /// The loop's start, and its end in frames, as of the last packet.
+  looped: Option<(Duration, u64)>,
+  /// Frames into the track of the next sample.
+  frame: u64,
+  /// Samples of that frame already played.
+  channel: u16,
+}
+
+impl<S> Looping<S>
+where
+  S: Source<Item = f32>,
+{
+  pub fn new(inner: S, region: LoopRegion) -> Self {
+    let mut looping = Looping { inner, region, looped: None, frame: 0, channel: 0 };
+    looping.refresh();
+    looping
+  }
+
+  /// Picks up the loop as the player has it now. Only tried, so the
+  /// audio thread never waits on the UI.
+  fn refresh(&mut self) {
+    if let Ok(region) = self.region.try_lock() {
+      let sample_rate = self.inner.sample_rate();
+      self.looped = region.map(|(start, end)| (start, frames(end, sample_rate)));
+    }
+  }
+}
+
+/// Whole frames in `duration` at `sample_rate`.
+fn frames(duration: Duration, sample_rate: u32) -> u64 {
+  (duration.as_secs_f64() * sample_rate as f64).round() as u64
+}
+
+impl<S> Iterator for Looping<S>
+where
+  S: Source<Item = f32>,
+{
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    if self.channel == 0 {
+      if self.inner.current_frame_len() == Some(0) {
+        self.refresh();
+      }
+      if let Some((start, end)) = self.looped
+        && self.frame == end
+      {
+        match self.inner.try_seek(start) {
+          Ok(()) => self.frame = frames(start, self.inner.sample_rate()),
+          Err(e) => {
+            eprintln!("Failed to loop: {}", e);
+            self.looped = None;
+          }
+        }
+      }
+    }
+
+    let sample = self.inner.next()?;
+    self.channel += 1;
+    if self.channel >= self.inner.channels() {
+      self.channel = 0;
+      self.frame += 1;
+    }
+    Some(sample)
+  }
+}
+
+impl<S> Source for Looping<S>
+where
+  S: Source<Item = f32>,
+{
+  #[inline]
+  fn current_frame_len(&self) -> Option<usize> {
+    self.inner.current_frame_len()
+  }
+  #[inline]
+  fn channels(&self) -> u16 {
+    self.inner.channels()
+  }
+  #[inline]
+  fn sample_rate(&self) -> u32 {
+    self.inner.sample_rate()
+  }
+  #[inline]
+  fn total_duration(&self) -> Option<Duration> {
+    self.inner.total_duration()
+  }
+
+  fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+    self.inner.try_seek(position)?;
+    self.frame = frames(position, self.inner.sample_rate());
+    self.channel = 0;
+    self.refresh();
+    Ok(())
+  }
+}
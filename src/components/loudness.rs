@@ -0,0 +1,267 @@
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  widget::canvas::{self, Geometry},
+};
+use std::{collections::VecDeque, f64::consts::PI};
+
+use crate::{app::Message, components::gradient::Gradient};
+
+/// Quietest level the VU bars show.
+pub const VU_FLOOR_DB: f32 = -60.0;
+/// Integration time of a VU meter.
+const VU_SECONDS: f64 = 0.3;
+/// Gating blocks advance in steps of this long (75% overlap of 400 ms).
+const STEP_SECONDS: f64 = 0.1;
+/// Steps in a momentary (400 ms) and short-term (3 s) window.
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+/// EBU R128 gates for the integrated loudness, in LUFS and LU.
+const ABSOLUTE_GATE: f64 = -70.0;
+const RELATIVE_GATE: f64 = -10.0;
+
+/// Second-order IIR section, in transposed direct form II.
+#[derive(Debug, Clone, Copy, Default)]
+struct Biquad {
+  b: [f64; 3],
+  a: [f64; 2],
+  z: [f64; 2],
+}
+
+impl Biquad {
+  fn process(&mut self, x: f64) -> f64 {
+    let y = self.b[0] * x + self.z[0];
+    self.z[0] = self.b[1] * x - self.a[0] * y + self.z[1];
+    self.z[1] = self.b[2] * x - self.a[1] * y;
+    y
+  }
+}
+
+/// The BS.1770 K-weighting filter: a high shelf for the head's acoustic
+/// effect, then a high-pass (RLB) curve. Recomputed for any sample rate the
+/// same way libebur128 does, rather than using the 48 kHz coefficients.
+fn k_weighting(sample_rate: u32) -> [Biquad; 2] {
+  let rate = sample_rate.max(1) as f64;
+
+  let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+  let k = (PI * f0 / rate).tan();
+  let vh = 10f64.powf(gain / 20.0);
+  let vb = vh.powf(0.4996667741545416);
+  let a0 = 1.0 + k / q + k * k;
+  let shelf = Biquad {
+    b: [(vh + vb * k / q + k * k) / a0, 2.0 * (k * k - vh) / a0, (vh - vb * k / q + k * k) / a0],
+    a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    z: [0.0; 2],
+  };
+
+  let (f0, q) = (38.13547087602444, 0.5003270373238773);
+  let k = (PI * f0 / rate).tan();
+  let a0 = 1.0 + k / q + k * k;
+  let high_pass = Biquad {
+    b: [1.0, -2.0, 1.0],
+    a: [2.0 * (k * k - 1.0) / a0, (1.0 - k / q + k * k) / a0],
+    z: [0.0; 2],
+  };
+
+  [shelf, high_pass]
+}
+
+/// Loudness of a K-weighted mean square, in LUFS.
+fn lufs(power: f64) -> f64 {
+  -0.691 + 10.0 * power.max(1e-20).log10()
+}
+
+fn mean(values: impl ExactSizeIterator<Item = f64>) -> f64 {
+  let count = values.len().max(1) as f64;
+  values.sum::<f64>() / count
+}
+
+/// A true RMS VU level per channel and EBU R128 loudness (momentary,
+/// short-term and gated integrated) of everything fed in since the last clear.
+///
+/// Channels are summed unweighted, which matches BS.1770 for mono and
+/// stereo; surround channels aren't given their extra weight.
+#[derive(Debug, Clone)]
+pub struct LoudnessMeter {
+  channels: usize,
+  sample_rate: u32,
+  filters: Vec<[Biquad; 2]>,
+  /// Running mean square of each channel, unweighted.
+  vu_power: Vec<f64>,
+  /// Per-sample decay of `vu_power`.
+  vu_decay: f64,
+  /// Interleaved samples that don't make up a whole frame yet.
+  pending: Vec<f32>,
+  /// K-weighted energy of the current step, summed over channels.
+  step_energy: f64,
+  step_frames: usize,
+  /// Mean square of each recent step, newest last.
+  steps: VecDeque<f64>,
+  /// Mean square of every 400 ms block so far, for gating.
+  blocks: Vec<f64>,
+}
+
+impl LoudnessMeter {
+  pub fn new(channels: u16, sample_rate: u32) -> Self {
+    let channels = channels.max(1) as usize;
+    Self {
+      channels,
+      sample_rate,
+      filters: vec![k_weighting(sample_rate); channels],
+      vu_power: vec![0.0; channels],
+      vu_decay: (-1.0 / (VU_SECONDS * sample_rate.max(1) as f64)).exp(),
+      pending: Vec::new(),
+      step_energy: 0.0,
+      step_frames: 0,
+      steps: VecDeque::with_capacity(SHORT_TERM_STEPS + 1),
+      blocks: Vec::new(),
+    }
+  }
+
+  /// Feeds interleaved samples. A change of format starts over.
+  pub fn add(&mut self, samples: &[f32], channels: u16, sample_rate: u32) {
+    if channels.max(1) as usize != self.channels || sample_rate != self.sample_rate {
+      *self = Self::new(channels, sample_rate);
+    }
+    let step_len = ((STEP_SECONDS * self.sample_rate as f64) as usize).max(1);
+
+    self.pending.extend_from_slice(samples);
+    let whole = self.pending.len() / self.channels * self.channels;
+    for f in 0..whole / self.channels {
+      for c in 0..self.channels {
+        let x = self.pending[f * self.channels + c] as f64;
+        self.vu_power[c] = self.vu_decay * self.vu_power[c] + (1.0 - self.vu_decay) * x * x;
+        let [shelf, high_pass] = &mut self.filters[c];
+        let weighted = high_pass.process(shelf.process(x));
+        self.step_energy += weighted * weighted;
+      }
+      self.step_frames += 1;
+      if self.step_frames == step_len {
+        self.finish_step();
+      }
+    }
+    self.pending.drain(..whole);
+  }
+
+  fn finish_step(&mut self) {
+    self.steps.push_back(self.step_energy / self.step_frames as f64);
+    if self.steps.len() > SHORT_TERM_STEPS {
+      self.steps.pop_front();
+    }
+    self.step_energy = 0.0;
+    self.step_frames = 0;
+
+    if self.steps.len() >= MOMENTARY_STEPS {
+      let block = mean(self.steps.iter().rev().take(MOMENTARY_STEPS).copied());
+      self.blocks.push(block);
+    }
+  }
+
+  pub fn clear(&mut self) {
+    *self = Self::new(self.channels as u16, self.sample_rate);
+  }
+
+  /// RMS level of each channel, in dBFS.
+  pub fn vu(&self) -> Vec<f32> {
+    self.vu_power.iter().map(|&power| (10.0 * power.max(1e-12).log10()) as f32).collect()
+  }
+
+  /// Loudness of the last 400 ms, once that much has been fed in.
+  pub fn momentary(&self) -> Option<f32> {
+    self.blocks.last().map(|&block| lufs(block) as f32)
+  }
+
+  /// Loudness of the last 3 s, once that much has been fed in.
+  pub fn short_term(&self) -> Option<f32> {
+    (self.steps.len() >= SHORT_TERM_STEPS).then(|| lufs(mean(self.steps.iter().copied())) as f32)
+  }
+
+  /// Gated loudness of everything so far: blocks below -70 LUFS are
+  /// dropped, then those more than 10 LU under the rest's loudness.
+  pub fn integrated(&self) -> Option<f32> {
+    let audible: Vec<f64> =
+      self.blocks.iter().copied().filter(|&block| lufs(block) > ABSOLUTE_GATE).collect();
+    if audible.is_empty() {
+      return None;
+    }
+    let gate = lufs(mean(audible.iter().copied())) + RELATIVE_GATE;
+    let gated: Vec<f64> = audible.into_iter().filter(|&block| lufs(block) > gate).collect();
+    Some(lufs(mean(gated.into_iter())) as f32)
+  }
+}
+
+impl Default for LoudnessMeter {
+  fn default() -> Self {
+    Self::new(2, crate::analysis::DEFAULT_SAMPLE_RATE)
+  }
+}
+
+/// VU bars per channel with the loudness readouts under them.
+pub struct MeterCanvas {
+  /// dBFS of each channel.
+  pub vu: Vec<f32>,
+  pub momentary: Option<f32>,
+  pub short_term: Option<f32>,
+  pub integrated: Option<f32>,
+  pub gradient: Gradient,
+  pub muted: bool,
+}
+
+impl canvas::Program<Message> for MeterCanvas {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    // Levels move every frame, so there's nothing worth caching
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    const READOUT_HEIGHT: f32 = 60.0;
+    const LABEL_HEIGHT: f32 = 16.0;
+    let label_color = Color::from_rgb(0.7, 0.7, 0.7);
+    let meter_height = (bounds.height - READOUT_HEIGHT - LABEL_HEIGHT).max(0.0);
+    let bar_width = bounds.width / self.vu.len().max(1) as f32;
+
+    for (c, &db) in self.vu.iter().enumerate() {
+      let level = (1.0 - db / VU_FLOOR_DB).clamp(0.0, 1.0);
+      let height = level * meter_height;
+      let color =
+        if self.muted { Color::from_rgb(0.5, 0.5, 0.5) } else { self.gradient.color(level) };
+      let x = c as f32 * bar_width;
+      frame.fill_rectangle(
+        Point::new(x, meter_height - height),
+        Size::new((bar_width - 4.0).max(1.0), height),
+        color,
+      );
+      let name = match (self.vu.len(), c) {
+        (2, 0) => "L".to_string(),
+        (2, 1) => "R".to_string(),
+        _ => (c + 1).to_string(),
+      };
+      frame.fill_text(canvas::Text {
+        content: name,
+        position: Point::new(x, meter_height + 2.0),
+        color: label_color,
+        size: 12.0.into(),
+        ..canvas::Text::default()
+      });
+    }
+
+    let readouts = [("M", self.momentary), ("S", self.short_term), ("I", self.integrated)];
+    for (i, (name, lufs)) in readouts.into_iter().enumerate() {
+      let value = lufs.map_or("--".to_string(), |lufs| format!("{:.1}", lufs));
+      frame.fill_text(canvas::Text {
+        content: format!("{} {} LUFS", name, value),
+        position: Point::new(0.0, meter_height + LABEL_HEIGHT + 4.0 + i as f32 * 18.0),
+        color: label_color,
+        size: 14.0.into(),
+        ..canvas::Text::default()
+      });
+    }
+
+    vec![frame.into_geometry()]
+  }
+}
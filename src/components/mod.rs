@@ -1,2 +1,50 @@
+pub mod auto_range;
+pub mod backdrop;
+pub mod bars;
+pub mod beat;
+pub mod binning;
+pub mod bpm;
+pub mod budget;
+pub mod channels;
+pub mod chroma;
+pub mod classifier;
+pub mod crossfade;
+pub mod delay;
+pub mod energy;
+pub mod equalizer;
+pub mod feedback;
+pub mod frame_rate;
+pub mod gain;
+pub mod grade;
+pub mod gradient;
+pub mod histogram;
+pub mod layout;
+pub mod looping;
+pub mod loudness;
+pub mod noise;
+pub mod overview;
+pub mod palette;
+pub mod particles;
+pub mod phase;
+pub mod phase_plot;
+pub mod postfx;
+pub mod rate;
+pub mod readout;
+pub mod recorder;
+pub mod response;
+pub mod sections;
+pub mod smoothing;
+pub mod spectrogram;
+pub mod stretch;
+pub mod strobe;
+pub mod sweep;
 pub mod tap;
+pub mod tempo;
+pub mod terrain;
+pub mod timeline;
+pub mod transfer;
 pub mod visualiser;
+pub mod vocals;
+pub mod waveform;
+pub mod weighting;
+pub mod window_fn;
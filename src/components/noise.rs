@@ -0,0 +1,65 @@
+/// Seeded 2D Perlin noise. The same seed always produces the same field, so
+/// organic jitter stays reproducible across runs and exports.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+  seed: u64,
+  permutation: [u8; 512],
+}
+
+impl Perlin {
+  pub fn new(seed: u64) -> Self {
+    let mut table: Vec<u8> = (0..=255).collect();
+    fastrand::Rng::with_seed(seed).shuffle(&mut table);
+
+    let mut permutation = [0; 512];
+    for (i, slot) in permutation.iter_mut().enumerate() {
+      *slot = table[i & 255];
+    }
+    Self { seed, permutation }
+  }
+
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+
+  /// Samples the field at `(x, y)`; the result is roughly in -1.0..=1.0.
+  pub fn get(&self, x: f32, y: f32) -> f32 {
+    let (xi, yi) = (x.floor() as i32 & 255, y.floor() as i32 & 255);
+    let (xf, yf) = (x - x.floor(), y - y.floor());
+    let (u, v) = (fade(xf), fade(yf));
+
+    let p = &self.permutation;
+    let hash = |i: i32, j: i32| p[(p[(i & 255) as usize] as i32 + j) as usize & 511];
+
+    let top = lerp(gradient(hash(xi, yi), xf, yf), gradient(hash(xi + 1, yi), xf - 1.0, yf), u);
+    let bottom = lerp(
+      gradient(hash(xi, yi + 1), xf, yf - 1.0),
+      gradient(hash(xi + 1, yi + 1), xf - 1.0, yf - 1.0),
+      u,
+    );
+
+    // 2D Perlin peaks at about ±0.7, stretch it back to ±1
+    (lerp(top, bottom, v) * std::f32::consts::SQRT_2).clamp(-1.0, 1.0)
+  }
+}
+
+fn fade(t: f32) -> f32 {
+  t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+  a + (b - a) * t
+}
+
+fn gradient(hash: u8, x: f32, y: f32) -> f32 {
+  match hash & 7 {
+    0 => x + y,
+    1 => -x + y,
+    2 => x - y,
+    3 => -x - y,
+    4 => x,
+    5 => -x,
+    6 => y,
+    _ => -y,
+  }
+}
@@ -0,0 +1,89 @@
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  mouse::{self, Cursor},
+  widget::canvas::{self, Event, Geometry, Path, Stroke, event},
+};
+use std::time::Duration;
+
+use crate::{app::Message, components::gradient::Gradient, playback};
+
+/// The whole track's waveform as a strip, mirrored about its middle, with
+/// what's still to come dimmed behind the playhead; click to seek.
+pub struct OverviewCanvas<'a> {
+  /// Loudest sample of each slice of the track, 0.0..=1.0.
+  pub envelope: &'a [f32],
+  /// Seconds into the track.
+  pub position: f32,
+  /// Length of the track, in seconds.
+  pub duration: f32,
+  pub gradient: Gradient,
+  /// Keeps the waveform, which only changes with the track or the colours.
+  pub cache: &'a canvas::Cache,
+}
+
+impl<'a> canvas::Program<Message> for OverviewCanvas<'a> {
+  type State = ();
+
+  fn update(
+    &self,
+    _state: &mut Self::State,
+    event: Event,
+    bounds: Rectangle,
+    cursor: Cursor,
+  ) -> (event::Status, Option<Message>) {
+    let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event else {
+      return (event::Status::Ignored, None);
+    };
+    let Some(at) = cursor.position_in(bounds) else {
+      return (event::Status::Ignored, None);
+    };
+    let seconds = (at.x / bounds.width).clamp(0.0, 1.0) * self.duration;
+    let seek = playback::Message::Seek(Duration::from_secs_f32(seconds));
+    (event::Status::Captured, Some(Message::Playback(seek)))
+  }
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: Cursor,
+  ) -> Vec<Geometry> {
+    let waveform = self.cache.draw(renderer, bounds.size(), |frame| {
+      let middle = bounds.height / 2.0;
+      // One column per pixel at most, each the loudest of the slices it covers
+      let columns = (bounds.width as usize).clamp(1, self.envelope.len().max(1));
+      let width = bounds.width / columns as f32;
+      for column in 0..columns {
+        let start = column * self.envelope.len() / columns;
+        let end = ((column + 1) * self.envelope.len() / columns).max(start + 1);
+        let peak = self
+          .envelope
+          .get(start..end)
+          .map_or(0.0, |slice| slice.iter().copied().fold(0.0, f32::max));
+        let half = (peak.clamp(0.0, 1.0) * middle).max(0.5);
+        frame.fill_rectangle(
+          Point::new(column as f32 * width, middle - half),
+          Size::new(width.max(1.0), half * 2.0),
+          self.gradient.color(peak),
+        );
+      }
+    });
+
+    // The playhead moves every frame, so only the waveform is cached
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let x = (self.position / self.duration.max(f32::EPSILON)).clamp(0.0, 1.0) * bounds.width;
+    frame.fill_rectangle(
+      Point::new(x, 0.0),
+      Size::new(bounds.width - x, bounds.height),
+      Color::from_rgba(0.0, 0.0, 0.0, 0.5),
+    );
+    frame.stroke(
+      &Path::line(Point::new(x, 0.0), Point::new(x, bounds.height)),
+      Stroke::default().with_color(Color::WHITE).with_width(2.0),
+    );
+
+    vec![waveform, frame.into_geometry()]
+  }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+
+use crate::components::gradient::{PALETTE_SIZE, Palette, interpolate};
+
+/// Cover art is shrunk to this before counting colours.
+const THUMBNAIL_SIZE: u32 = 64;
+/// Bits kept per channel when grouping similar colours.
+const QUANTISE_BITS: u8 = 4;
+/// Picked colours are at least this far apart in RGB.
+const MIN_DISTANCE: f32 = 60.0;
+
+/// The dominant colours of an image as gradient stops, darkest first so
+/// quiet bands take the shadows and loud ones the highlights.
+///
+/// Colours are grouped coarsely and ranked by how much of the image they
+/// cover, with saturated ones favoured over the greys most covers are full
+/// of. `None` when the bytes aren't an image.
+pub fn from_image(bytes: &[u8]) -> Option<Palette> {
+  let image = match image::load_from_memory(bytes) {
+    Ok(image) => image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE).to_rgb8(),
+    Err(e) => {
+      eprintln!("Failed to decode cover art: {}", e);
+      return None;
+    }
+  };
+
+  // Count and summed colour of each coarse bucket
+  let shift = 8 - QUANTISE_BITS;
+  let mut buckets: HashMap<[u8; 3], (u32, [u32; 3])> = HashMap::new();
+  for pixel in image.pixels() {
+    let [r, g, b] = pixel.0;
+    let (count, sum) = buckets.entry([r >> shift, g >> shift, b >> shift]).or_default();
+    *count += 1;
+    sum[0] += r as u32;
+    sum[1] += g as u32;
+    sum[2] += b as u32;
+  }
+
+  let mut ranked: Vec<([f32; 3], f32)> = buckets
+    .into_values()
+    .map(|(count, sum)| {
+      let color = sum.map(|channel| channel as f32 / count as f32);
+      (color, count as f32 * (0.2 + saturation(color)))
+    })
+    .collect();
+  ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+  let mut picked: Vec<[f32; 3]> = Vec::with_capacity(PALETTE_SIZE);
+  for (color, _) in ranked {
+    if picked.iter().all(|&other| distance(color, other) >= MIN_DISTANCE) {
+      picked.push(color);
+      if picked.len() == PALETTE_SIZE {
+        break;
+      }
+    }
+  }
+  // A single-colour cover still needs somewhere to fade from
+  match picked.as_slice() {
+    [] => return None,
+    &[only] => picked.insert(0, only.map(|channel| channel * 0.3)),
+    _ => {}
+  }
+  picked.sort_by(|a, b| luminance(*a).total_cmp(&luminance(*b)));
+
+  // Spread whatever was found over the fixed number of stops
+  let stops: Vec<[u8; 3]> = picked.iter().map(|color| color.map(|channel| channel as u8)).collect();
+  Some(std::array::from_fn(|i| {
+    let color = interpolate(&stops, i as f32 / (PALETTE_SIZE - 1) as f32);
+    [color.r, color.g, color.b].map(|channel| (channel * 255.0).round() as u8)
+  }))
+}
+
+fn saturation([r, g, b]: [f32; 3]) -> f32 {
+  let max = r.max(g).max(b);
+  let min = r.min(g).min(b);
+  if max > 0.0 { (max - min) / max } else { 0.0 }
+}
+
+fn luminance([r, g, b]: [f32; 3]) -> f32 {
+  0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+  a.iter().zip(b).map(|(x, y)| (x - y).powi(2)).sum::<f32>().sqrt()
+}
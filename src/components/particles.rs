@@ -0,0 +1,379 @@
+use iced::{
+  Color, Point, Rectangle, Theme, Vector,
+  mouse::{self, Cursor},
+  widget::canvas::{self, Event, Geometry, Path, Stroke, event},
+};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::TAU;
+
+use crate::{analysis::DecibelRange, app::Message, components::gradient::Gradient, ui::settings};
+
+/// Live particles are capped so a loud track can't run away with the frame time.
+const MAX_PARTICLES: usize = 1500;
+/// Particles spawned per second at full bass.
+const MAX_SPAWN_RATE: f32 = 600.0;
+/// Share of the bands, from the bottom, counted as bass.
+const BASS_SHARE: f32 = 0.15;
+/// Launch speed, in radii per second, at full energy in the particle's band.
+const LAUNCH_SPEED: f32 = 0.9;
+/// Outward acceleration at full bass, in radii per second squared.
+const BASS_PUSH: f32 = 2.5;
+/// Downward acceleration, in radii per second squared.
+const GRAVITY: f32 = 0.5;
+/// Fraction of its velocity a particle loses per second.
+const DRAG: f32 = 0.8;
+/// Lifetimes of the lowest and highest bands' particles, in seconds.
+const BASS_LIFETIME: f32 = 3.0;
+const TREBLE_LIFETIME: f32 = 1.0;
+/// Radius of a new particle, in pixels.
+const PARTICLE_SIZE: f32 = 4.0;
+/// Radius of the ring emitters sit on, in units of the canvas's half-size.
+const RING_RADIUS: f32 = 0.5;
+/// How far either side of straight out an emitter's burst spreads, in radians.
+const BURST_SPREAD: f32 = 0.6;
+/// Lifetime of a burst's particles, in seconds.
+const BURST_LIFETIME: f32 = 1.2;
+/// How far below its threshold an emitter's band has to fall before it can
+/// fire again, so a level hovering on the threshold doesn't fire every frame.
+const REARM_MARGIN: f32 = 0.1;
+pub const MAX_BURST: u16 = 200;
+/// Radius of an emitter's dot in the editor, in pixels.
+const EMITTER_DOT: f32 = 6.0;
+
+/// Fires a burst of particles from its spot on the ring each time the
+/// energy in its band rises past the threshold, like cymbal sparkles
+/// off the hi-hats.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Emitter {
+  /// The band it listens to, in Hz.
+  pub low_hz: f32,
+  pub high_hz: f32,
+  /// Where on the ring it sits, in degrees clockwise from the top.
+  pub angle: f32,
+  /// As typed; the burst takes the gradient's colours while it doesn't parse.
+  pub color: String,
+  /// Particles per burst.
+  pub burst: u16,
+  /// Band level, 0.0..=1.0 over the dB range, that fires it.
+  pub threshold: f32,
+}
+
+impl Emitter {
+  fn color(&self) -> Option<Color> {
+    Color::parse(self.color.trim())
+  }
+
+  /// Unit vector from the centre out to it.
+  fn direction(&self) -> Vector {
+    let angle = self.angle.to_radians();
+    Vector::new(angle.sin(), -angle.cos())
+  }
+}
+
+impl Default for Emitter {
+  fn default() -> Self {
+    Self {
+      low_hz: 6000.0,
+      high_hz: 16000.0,
+      angle: 0.0,
+      color: "#ffffff".to_string(),
+      burst: 40,
+      threshold: 0.6,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Particle {
+  /// Offset from the centre, in units of the canvas's half-size.
+  position: Vector,
+  velocity: Vector,
+  age: f32,
+  lifetime: f32,
+  /// Position of the band it came from, 0.0 (lowest) to 1.0.
+  band: f32,
+  /// Its emitter's colour, instead of the band's.
+  color: Option<Color>,
+}
+
+/// Particles that bass energy spawns at the centre and flings outwards.
+///
+/// Unlike the other styles this carries state from frame to frame, so it's
+/// stepped on every tick and only drawn by [`ParticleCanvas`]. Each particle
+/// belongs to a frequency band, picked in proportion to the band's energy,
+/// which sets its launch speed, lifetime and colour. [`Emitter`]s add
+/// bursts of their own on top.
+#[derive(Debug)]
+pub struct ParticleSystem {
+  particles: Vec<Particle>,
+  /// Fractional particles owed from previous steps.
+  spawn_debt: f32,
+  /// Whether each emitter can fire, having dropped back below its threshold.
+  armed: Vec<bool>,
+  /// Emitters that fired since the last step.
+  fired: Vec<usize>,
+  rng: fastrand::Rng,
+}
+
+impl ParticleSystem {
+  /// Advances the simulation by `dt` seconds. `bands` are normalised band
+  /// energies, lowest first; empty while nothing is playing. `share` scales
+  /// how many particles spawn and live at once, 1.0 for all of them.
+  pub fn step(&mut self, bands: &[f32], emitters: &[Emitter], dt: f32, share: f32) {
+    let max_particles = (MAX_PARTICLES as f32 * share) as usize;
+    let bass_bands = ((bands.len() as f32 * BASS_SHARE).ceil() as usize).min(bands.len());
+    let bass = bands[..bass_bands].iter().sum::<f32>() / bass_bands.max(1) as f32;
+
+    for particle in &mut self.particles {
+      let distance = (particle.position.x.powi(2) + particle.position.y.powi(2)).sqrt();
+      if distance > f32::EPSILON {
+        particle.velocity =
+          particle.velocity + particle.position * (BASS_PUSH * bass / distance * dt);
+      }
+      particle.velocity.y += GRAVITY * dt;
+      particle.velocity = particle.velocity * (1.0 - DRAG * dt).max(0.0);
+      particle.position = particle.position + particle.velocity * dt;
+      particle.age += dt;
+    }
+    // Dead or well out of view
+    self.particles.retain(|particle| {
+      particle.age < particle.lifetime
+        && particle.position.x.abs() < 2.0
+        && particle.position.y.abs() < 2.0
+    });
+    // The oldest go first when the cap comes down
+    let excess = self.particles.len().saturating_sub(max_particles);
+    self.particles.drain(..excess);
+
+    self.spawn_debt += MAX_SPAWN_RATE * share * bass * bass * dt;
+    let total = bands.iter().sum::<f32>();
+    while self.spawn_debt >= 1.0 && self.particles.len() < max_particles && total > 0.0 {
+      self.spawn_debt -= 1.0;
+      self.spawn(bands, total);
+    }
+    self.spawn_debt = self.spawn_debt.min(1.0);
+
+    for index in std::mem::take(&mut self.fired) {
+      let Some(emitter) = emitters.get(index) else {
+        continue;
+      };
+      let count = (emitter.burst.min(MAX_BURST) as f32 * share).ceil() as usize;
+      for _ in 0..count.min(max_particles.saturating_sub(self.particles.len())) {
+        self.burst(emitter);
+      }
+    }
+  }
+
+  /// Checks each emitter's band in a magnitude spectrum (half an FFT) and
+  /// fires those whose level just rose past their threshold. Their bursts
+  /// go off on the next step.
+  pub fn listen(
+    &mut self,
+    emitters: &[Emitter],
+    spectrum: &[f32],
+    sample_rate: u32,
+    decibels: DecibelRange,
+  ) {
+    self.armed.resize(emitters.len(), true);
+    let bin_hz = sample_rate as f32 / (2 * spectrum.len().max(1)) as f32;
+    for (index, emitter) in emitters.iter().enumerate() {
+      // The DC bin is no band's
+      let power = spectrum
+        .iter()
+        .enumerate()
+        .skip(1)
+        .filter(|(bin, _)| (emitter.low_hz..emitter.high_hz).contains(&(*bin as f32 * bin_hz)))
+        .map(|(_, magnitude)| magnitude * magnitude)
+        .sum::<f32>();
+      let level = decibels.normalise(power.sqrt());
+      if level >= emitter.threshold && self.armed[index] {
+        self.armed[index] = false;
+        self.fired.push(index);
+      } else if level < emitter.threshold - REARM_MARGIN {
+        self.armed[index] = true;
+      }
+    }
+  }
+
+  fn spawn(&mut self, bands: &[f32], total: f32) {
+    // Louder bands get more of the particles
+    let mut pick = self.rng.f32() * total;
+    let index = bands
+      .iter()
+      .position(|&energy| {
+        pick -= energy;
+        pick <= 0.0
+      })
+      .unwrap_or(bands.len() - 1);
+    let band = index as f32 / (bands.len().max(2) - 1) as f32;
+
+    let angle = self.rng.f32() * TAU;
+    let speed = LAUNCH_SPEED * (0.3 + 0.7 * bands[index]) * (0.75 + 0.5 * self.rng.f32());
+    self.particles.push(Particle {
+      position: Vector::new(0.0, 0.0),
+      velocity: Vector::new(angle.cos(), angle.sin()) * speed,
+      age: 0.0,
+      lifetime: BASS_LIFETIME + (TREBLE_LIFETIME - BASS_LIFETIME) * band,
+      band,
+      color: None,
+    });
+  }
+
+  fn burst(&mut self, emitter: &Emitter) {
+    let direction = emitter.direction();
+    let spread = (self.rng.f32() * 2.0 - 1.0) * BURST_SPREAD;
+    let (sin, cos) = spread.sin_cos();
+    let heading =
+      Vector::new(direction.x * cos - direction.y * sin, direction.x * sin + direction.y * cos);
+    let speed = LAUNCH_SPEED * (0.5 + 0.5 * self.rng.f32());
+    self.particles.push(Particle {
+      position: direction * RING_RADIUS,
+      velocity: heading * speed,
+      age: 0.0,
+      lifetime: BURST_LIFETIME * (0.75 + 0.5 * self.rng.f32()),
+      band: 1.0,
+      color: emitter.color(),
+    });
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.particles.is_empty()
+  }
+
+  pub fn clear(&mut self) {
+    self.particles.clear();
+    self.spawn_debt = 0.0;
+    self.armed.clear();
+    self.fired.clear();
+  }
+}
+
+impl Default for ParticleSystem {
+  fn default() -> Self {
+    Self {
+      particles: Vec::with_capacity(MAX_PARTICLES),
+      spawn_debt: 0.0,
+      armed: Vec::new(),
+      fired: Vec::new(),
+      rng: fastrand::Rng::new(),
+    }
+  }
+}
+
+pub struct ParticleCanvas<'a> {
+  pub system: &'a ParticleSystem,
+  pub gradient: Gradient,
+  /// Draws the particles greyed out while playback is muted.
+  pub muted: bool,
+}
+
+impl<'a> canvas::Program<Message> for ParticleCanvas<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    // Everything moves every frame, so there's nothing worth caching
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let centre = frame.center();
+    let scale = bounds.width.min(bounds.height) / 2.0;
+
+    for particle in &self.system.particles {
+      let life = 1.0 - particle.age / particle.lifetime;
+      let color = if self.muted {
+        Color::from_rgb(0.5, 0.5, 0.5)
+      } else {
+        particle.color.unwrap_or_else(|| self.gradient.color(particle.band))
+      };
+      frame.fill(
+        &Path::circle(centre + particle.position * scale, PARTICLE_SIZE * (0.4 + 0.6 * life)),
+        Color { a: life, ..color },
+      );
+    }
+
+    vec![frame.into_geometry()]
+  }
+}
+
+/// The emitters on their ring, for the settings panel: click one to pick
+/// it, or anywhere else to move the picked one round to there.
+pub struct EmitterRing<'a> {
+  pub emitters: &'a [Emitter],
+  pub selected: usize,
+  pub gradient: Gradient,
+}
+
+impl<'a> EmitterRing<'a> {
+  fn radius(bounds: Rectangle) -> f32 {
+    bounds.width.min(bounds.height) / 2.0 - 2.0 * EMITTER_DOT
+  }
+}
+
+impl<'a> canvas::Program<Message> for EmitterRing<'a> {
+  type State = ();
+
+  fn update(
+    &self,
+    _state: &mut Self::State,
+    event: Event,
+    bounds: Rectangle,
+    cursor: Cursor,
+  ) -> (event::Status, Option<Message>) {
+    let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event else {
+      return (event::Status::Ignored, None);
+    };
+    let Some(at) = cursor.position_in(bounds) else {
+      return (event::Status::Ignored, None);
+    };
+    let centre = Point::new(bounds.width / 2.0, bounds.height / 2.0);
+    let radius = Self::radius(bounds);
+    let clicked = self.emitters.iter().position(|emitter| {
+      (centre + emitter.direction() * radius).distance(at) <= 2.0 * EMITTER_DOT
+    });
+    let message = match (clicked, self.emitters.get(self.selected)) {
+      (Some(index), _) => settings::Message::EmitterSelected(index),
+      (None, Some(selected)) => {
+        let offset = at - centre;
+        let angle = offset.x.atan2(-offset.y).to_degrees().rem_euclid(360.0).round();
+        settings::Message::EmitterChanged(self.selected, Emitter { angle, ..selected.clone() })
+      }
+      (None, None) => return (event::Status::Ignored, None),
+    };
+    (event::Status::Captured, Some(Message::Visual(message)))
+  }
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: Cursor,
+  ) -> Vec<Geometry> {
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let centre = frame.center();
+    let radius = Self::radius(bounds);
+    frame.stroke(
+      &Path::circle(centre, radius),
+      Stroke::default().with_color(Color::from_rgba(1.0, 1.0, 1.0, 0.3)).with_width(1.0),
+    );
+    for (index, emitter) in self.emitters.iter().enumerate() {
+      let at = centre + emitter.direction() * radius;
+      let color = emitter.color().unwrap_or_else(|| self.gradient.color(1.0));
+      frame.fill(&Path::circle(at, EMITTER_DOT), color);
+      if index == self.selected {
+        frame.stroke(
+          &Path::circle(at, EMITTER_DOT + 3.0),
+          Stroke::default().with_color(Color::WHITE).with_width(2.0),
+        );
+      }
+    }
+    vec![frame.into_geometry()]
+  }
+}
@@ -0,0 +1,62 @@
+use std::f32::consts::{PI, TAU};
+
+/// Bins averaged on either side when smoothing phase across frequency.
+const SMOOTHING_RADIUS: usize = 2;
+
+/// Removes the 2π jumps from a wrapped phase spectrum so it reads as one
+/// continuous curve.
+pub fn unwrap(wrapped: &[f32]) -> Vec<f32> {
+  let mut offset = 0.0;
+  let mut previous = match wrapped.first() {
+    Some(&first) => first,
+    None => return Vec::new(),
+  };
+
+  wrapped
+    .iter()
+    .map(|&phase| {
+      let step = phase - previous;
+      if step > PI {
+        offset -= TAU;
+      } else if step < -PI {
+        offset += TAU;
+      }
+      previous = phase;
+      phase + offset
+    })
+    .collect()
+}
+
+/// Moving average over `radius` neighbouring bins on each side.
+pub fn smooth(values: &[f32], radius: usize) -> Vec<f32> {
+  (0..values.len())
+    .map(|i| {
+      let window = &values[i.saturating_sub(radius)..(i + radius + 1).min(values.len())];
+      window.iter().sum::<f32>() / window.len() as f32
+    })
+    .collect()
+}
+
+/// Group delay in seconds, `-dφ/dω`, from an unwrapped phase spectrum whose
+/// bins are `bin_hz` apart.
+pub fn group_delay(unwrapped: &[f32], bin_hz: f32) -> Vec<f32> {
+  if unwrapped.len() < 2 {
+    return vec![0.0; unwrapped.len()];
+  }
+
+  let bin_omega = TAU * bin_hz;
+  (0..unwrapped.len())
+    .map(|i| {
+      // Central differences inside, one-sided at the ends
+      let (lo, hi) = (i.saturating_sub(1), (i + 1).min(unwrapped.len() - 1));
+      -(unwrapped[hi] - unwrapped[lo]) / ((hi - lo) as f32 * bin_omega)
+    })
+    .collect()
+}
+
+/// Unwrapped, smoothed phase and the group delay derived from it.
+pub fn analyse(wrapped: &[f32], bin_hz: f32) -> (Vec<f32>, Vec<f32>) {
+  let phase = smooth(&unwrap(wrapped), SMOOTHING_RADIUS);
+  let delay = group_delay(&phase, bin_hz);
+  (phase, delay)
+}
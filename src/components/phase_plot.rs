@@ -0,0 +1,115 @@
+use iced::{
+  Color, Point, Rectangle, Theme,
+  widget::canvas::{self, Geometry, Path, Stroke, gradient::Linear},
+};
+
+use crate::{
+  app::Message,
+  components::{binning::FrequencyScale, gradient::Gradient},
+};
+
+/// Group delay beyond this is clipped, so one wild bin doesn't flatten the plot.
+const MAX_GROUP_DELAY_MS: f32 = 50.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PhaseView {
+  /// Unwrapped phase, in degrees.
+  Phase,
+  /// Group delay, in milliseconds.
+  GroupDelay,
+}
+
+/// Line plot of the phase or group delay of the latest frame across frequency.
+pub struct PhaseCanvas<'a> {
+  /// One value per FFT bin: radians for phase, seconds for group delay.
+  pub values: &'a [f32],
+  pub view: PhaseView,
+  pub scale: FrequencyScale,
+  pub sample_rate: u32,
+  pub gradient: Gradient,
+  pub muted: bool,
+  pub cache: &'a canvas::Cache,
+}
+
+impl<'a> canvas::Program<Message> for PhaseCanvas<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
+      let (unit, values): (&str, Vec<f32>) = match self.view {
+        PhaseView::Phase => ("°", self.values.iter().map(|v| v.to_degrees()).collect()),
+        PhaseView::GroupDelay => (
+          "ms",
+          self
+            .values
+            .iter()
+            .map(|v| (v * 1000.0).clamp(-MAX_GROUP_DELAY_MS, MAX_GROUP_DELAY_MS))
+            .collect(),
+        ),
+      };
+      let bin_hz = self.sample_rate as f32 / (2.0 * values.len().max(1) as f32);
+
+      // Only bins that land inside the visible frequency range
+      let points: Vec<(f32, f32)> = values
+        .iter()
+        .enumerate()
+        .map(|(i, &value)| (self.scale.position(i as f32 * bin_hz, self.sample_rate), value))
+        .filter(|(x, _)| (0.0..=1.0).contains(x))
+        .collect();
+
+      // Symmetric autoscale around zero
+      let extent = points.iter().map(|(_, v)| v.abs()).fold(1.0, f32::max);
+      let to_y = |value: f32| bounds.height / 2.0 - value / extent * bounds.height * 0.45;
+
+      let label_color = Color::from_rgb(0.7, 0.7, 0.7);
+      frame.stroke(
+        &Path::line(Point::new(0.0, to_y(0.0)), Point::new(bounds.width, to_y(0.0))),
+        Stroke::default().with_color(Color { a: 0.25, ..label_color }).with_width(1.0),
+      );
+      for value in [extent, -extent] {
+        frame.fill_text(canvas::Text {
+          content: format!("{:+.1} {}", value, unit),
+          position: Point::new(4.0, to_y(value) - if value > 0.0 { 0.0 } else { 14.0 }),
+          color: label_color,
+          size: 12.0.into(),
+          ..canvas::Text::default()
+        });
+      }
+
+      if points.len() > 1 {
+        let line = Path::new(|builder| {
+          for (i, &(x, value)) in points.iter().enumerate() {
+            let point = Point::new(x * bounds.width, to_y(value));
+            if i == 0 {
+              builder.move_to(point);
+            } else {
+              builder.line_to(point);
+            }
+          }
+        });
+
+        let style = if self.muted {
+          canvas::Style::Solid(Color::from_rgb(0.5, 0.5, 0.5))
+        } else {
+          // Low to high frequencies run through the theme
+          canvas::Style::Gradient(canvas::Gradient::Linear(
+            Linear::new(Point::ORIGIN, Point::new(bounds.width, 0.0))
+              .add_stop(0.0, self.gradient.color(0.0))
+              .add_stop(0.5, self.gradient.color(0.5))
+              .add_stop(1.0, self.gradient.color(1.0)),
+          ))
+        };
+        frame.stroke(&line, Stroke { style, width: 2.0, ..Stroke::default() });
+      }
+    });
+
+    vec![geometry]
+  }
+}
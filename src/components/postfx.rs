@@ -0,0 +1,394 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  ops::RangeInclusive,
+};
+
+use bytemuck::{Pod, Zeroable};
+use iced::{
+  Point, Rectangle,
+  widget::shader::{self, Viewport, wgpu},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  app::Message,
+  components::{
+    gradient::Gradient,
+    layout::{LayoutKind, Placement, RingSettings, Shape},
+    visualiser::PULSE_SCALE,
+  },
+  ui::scene::Pane,
+};
+
+pub const EFFECT_RANGE: RangeInclusive<f32> = 0.0..=1.0;
+/// Earlier frames of the bars kept for the motion trails.
+pub const TRAIL_FRAMES: usize = 8;
+/// Reach of the glow at full bloom, in pixels, before the energy widens it.
+const GLOW_RADIUS: f32 = 6.0;
+/// How much wider the glow gets at full energy.
+const ENERGY_GLOW: f32 = 1.5;
+/// Distance the colour fringes are pulled apart at full aberration, in pixels.
+const MAX_ABERRATION: f32 = 6.0;
+/// How bright the newest trail frame is, at full trails.
+const TRAIL_STRENGTH: f32 = 0.6;
+const SHADER: &str = include_str!("postfx.wgsl");
+
+/// Effects drawn over the bars by the GPU, each 0.0 (off) to 1.0.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Effects {
+  /// Glow around the bars, spreading further the more energy there is.
+  pub bloom: f32,
+  /// Fading copies of the last few frames left behind above the bars.
+  pub trails: f32,
+  /// Red and blue fringes pulled apart at the bars' edges.
+  pub aberration: f32,
+}
+
+impl Effects {
+  pub fn any(&self) -> bool {
+    self.bloom > 0.0 || self.trails > 0.0 || self.aberration > 0.0
+  }
+}
+
+/// Bloom, trails and chromatic aberration for the bars, placed exactly
+/// where the canvas puts them (less the jitter) and added onto them in a
+/// shader pass.
+///
+/// Only the GPU renderer can draw it; the software renderer and off-screen
+/// exports show the bars plain.
+pub struct PostFx<'a> {
+  pub frequency_data: &'a [f32],
+  /// Earlier frames of `frequency_data`, oldest first.
+  pub trail: &'a VecDeque<Vec<f32>>,
+  pub layout: LayoutKind,
+  pub shape: Option<Shape<'a>>,
+  pub ring: RingSettings,
+  pub time: f32,
+  pub gradient: Gradient,
+  /// Beat pulse, grown by like the bars themselves.
+  pub pulse: f32,
+  /// Overall energy, 0.0..=1.0, which widens the bloom.
+  pub energy: f32,
+  pub effects: Effects,
+  pub muted: bool,
+  /// Which pane it's drawn over, which keeps its own buffers on the GPU.
+  pub pane: Pane,
+}
+
+impl<'a> shader::Program<Message> for PostFx<'a> {
+  type State = ();
+  type Primitive = PostFxPrimitive;
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    _cursor: iced::mouse::Cursor,
+    bounds: Rectangle,
+  ) -> Self::Primitive {
+    let placement = Placement::compute(
+      self.layout,
+      self.shape,
+      Rectangle::with_size(bounds.size()),
+      self.frequency_data.len(),
+      self.ring,
+      self.time,
+    );
+    let center = Point::new(bounds.width / 2.0, bounds.height / 2.0);
+    let grow = 1.0 + self.pulse * PULSE_SCALE;
+    let bars = |data: &[f32]| {
+      let arranged = self.ring.mirror.arrange(data, placement.anchors.len());
+      placement.anchors.iter().zip(arranged).map(move |(anchor, height)| {
+        let height = placement.bar_height(height);
+        (center + (anchor.position - center) * grow, anchor.normal, height)
+      })
+    };
+    let color = |height: f32| {
+      let intensity = placement.intensity(height);
+      if self.muted {
+        let grey = 0.4 + intensity * 0.2;
+        [grey, grey, grey, 1.0]
+      } else {
+        let color = self.gradient.color(intensity);
+        [color.r, color.g, color.b, 1.0]
+      }
+    };
+    let half_width = placement.bar_width / 2.0;
+    let now: Vec<f32> = bars(self.frequency_data).map(|(_, _, height)| height).collect();
+
+    let mut instances = Vec::new();
+    // Older frames fade out, and only show where they stood above the bars now
+    if self.effects.trails > 0.0 {
+      let frames = self.trail.len();
+      for (age, frame) in self.trail.iter().enumerate() {
+        let fade = (age + 1) as f32 / (frames + 1) as f32 * TRAIL_STRENGTH * self.effects.trails;
+        for ((position, normal, height), &current) in bars(frame.as_slice()).zip(&now) {
+          instances.push(Instance {
+            origin: [position.x, position.y, normal.x, normal.y],
+            size: [half_width, height, current, 0.0],
+            color: color(height),
+            effects: [fade, 0.0, 0.0, 0.0],
+          });
+        }
+      }
+    }
+    if self.effects.bloom > 0.0 || self.effects.aberration > 0.0 {
+      for (position, normal, height) in bars(self.frequency_data) {
+        instances.push(Instance {
+          origin: [position.x, position.y, normal.x, normal.y],
+          size: [half_width, height, 0.0, 0.0],
+          color: color(height),
+          effects: [0.0, self.effects.bloom, 1.0, 0.0],
+        });
+      }
+    }
+
+    PostFxPrimitive {
+      pane: self.pane,
+      instances,
+      uniforms: Uniforms {
+        size: [bounds.width.max(1.0), bounds.height.max(1.0)],
+        glow_radius: GLOW_RADIUS
+          * self.effects.bloom
+          * (1.0 + ENERGY_GLOW * self.energy.clamp(0.0, 1.0)),
+        aberration: MAX_ABERRATION * self.effects.aberration,
+      },
+    }
+  }
+}
+
+/// One bar: its foot and direction, half its width, its length and the
+/// length of the bar now, its colour, then how strong the trail body, glow
+/// and fringes are.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Instance {
+  origin: [f32; 4],
+  size: [f32; 4],
+  color: [f32; 4],
+  effects: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Uniforms {
+  size: [f32; 2],
+  glow_radius: f32,
+  aberration: f32,
+}
+
+#[derive(Debug)]
+pub struct PostFxPrimitive {
+  pane: Pane,
+  instances: Vec<Instance>,
+  uniforms: Uniforms,
+}
+
+impl PostFxPrimitive {
+  /// The pane whose buffers it's prepared into.
+  pub fn pane(&self) -> Pane {
+    self.pane
+  }
+}
+
+impl shader::Primitive for PostFxPrimitive {
+  fn prepare(
+    &self,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    storage: &mut shader::Storage,
+    bounds: &Rectangle,
+    viewport: &Viewport,
+  ) {
+    if !storage.has::<Pipeline>() {
+      storage.store(Pipeline::new(device, format));
+    }
+    let pipeline = storage.get_mut::<Pipeline>().expect("stored above");
+    pipeline.prepare(device, queue, self, *bounds * viewport.scale_factor() as f32);
+  }
+
+  fn render(
+    &self,
+    encoder: &mut wgpu::CommandEncoder,
+    storage: &shader::Storage,
+    target: &wgpu::TextureView,
+    clip_bounds: &Rectangle<u32>,
+  ) {
+    if let Some(pipeline) = storage.get::<Pipeline>() {
+      pipeline.render(encoder, target, *clip_bounds, self);
+    }
+  }
+}
+
+/// The GPU side, made once and kept in the shader storage.
+struct Pipeline {
+  pipeline: wgpu::RenderPipeline,
+  bind_group_layout: wgpu::BindGroupLayout,
+  /// Every primitive is prepared before any is rendered, so each pane
+  /// needs buffers of its own or they'd all draw the last one prepared.
+  panes: HashMap<Pane, Buffers>,
+}
+
+/// What one pane draws with.
+struct Buffers {
+  instances: wgpu::Buffer,
+  instance_capacity: usize,
+  uniforms: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+  /// Where the widget is on the target, in physical pixels.
+  bounds: Rectangle,
+}
+
+impl Pipeline {
+  fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("post-processing shader"),
+      source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("post-processing bind group layout"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+          ty: wgpu::BufferBindingType::Uniform,
+          has_dynamic_offset: false,
+          min_binding_size: None,
+        },
+        count: None,
+      }],
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("post-processing pipeline layout"),
+      bind_group_layouts: &[&bind_group_layout],
+      push_constant_ranges: &[],
+    });
+
+    // Everything is light added onto the bars already drawn
+    let additive = wgpu::BlendComponent {
+      src_factor: wgpu::BlendFactor::One,
+      dst_factor: wgpu::BlendFactor::One,
+      operation: wgpu::BlendOperation::Add,
+    };
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("post-processing pipeline"),
+      layout: Some(&layout),
+      vertex: wgpu::VertexState {
+        module: &shader,
+        entry_point: "vs_main",
+        buffers: &[wgpu::VertexBufferLayout {
+          array_stride: std::mem::size_of::<Instance>() as u64,
+          step_mode: wgpu::VertexStepMode::Instance,
+          attributes: &wgpu::vertex_attr_array![
+            0 => Float32x4, 1 => Float32x4, 2 => Float32x4, 3 => Float32x4
+          ],
+        }],
+      },
+      fragment: Some(wgpu::FragmentState {
+        module: &shader,
+        entry_point: "fs_main",
+        targets: &[Some(wgpu::ColorTargetState {
+          format,
+          blend: Some(wgpu::BlendState { color: additive, alpha: additive }),
+          write_mask: wgpu::ColorWrites::ALL,
+        })],
+      }),
+      primitive: wgpu::PrimitiveState::default(),
+      depth_stencil: None,
+      multisample: wgpu::MultisampleState::default(),
+      multiview: None,
+    });
+
+    Self { pipeline, bind_group_layout, panes: HashMap::new() }
+  }
+
+  fn prepare(
+    &mut self,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    primitive: &PostFxPrimitive,
+    bounds: Rectangle,
+  ) {
+    let buffers = self
+      .panes
+      .entry(primitive.pane)
+      .or_insert_with(|| Buffers::new(device, &self.bind_group_layout));
+    if primitive.instances.len() > buffers.instance_capacity {
+      buffers.instance_capacity = primitive.instances.len().next_power_of_two();
+      buffers.instances = instance_buffer(device, buffers.instance_capacity);
+    }
+    queue.write_buffer(&buffers.uniforms, 0, bytemuck::bytes_of(&primitive.uniforms));
+    queue.write_buffer(&buffers.instances, 0, bytemuck::cast_slice(&primitive.instances));
+    buffers.bounds = bounds;
+  }
+
+  fn render(
+    &self,
+    encoder: &mut wgpu::CommandEncoder,
+    target: &wgpu::TextureView,
+    clip_bounds: Rectangle<u32>,
+    primitive: &PostFxPrimitive,
+  ) {
+    let Some(buffers) = self.panes.get(&primitive.pane) else {
+      return;
+    };
+    let instances = primitive.instances.len() as u32;
+    if instances == 0 || clip_bounds.width == 0 || clip_bounds.height == 0 {
+      return;
+    }
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("post-processing pass"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+        view: target,
+        resolve_target: None,
+        ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+      })],
+      depth_stencil_attachment: None,
+      timestamp_writes: None,
+      occlusion_query_set: None,
+    });
+
+    // Positions are in the widget's own pixels; the scissor keeps the glow
+    // inside what's visible
+    let bounds = buffers.bounds;
+    pass.set_viewport(bounds.x, bounds.y, bounds.width, bounds.height, 0.0, 1.0);
+    pass.set_scissor_rect(clip_bounds.x, clip_bounds.y, clip_bounds.width, clip_bounds.height);
+    pass.set_pipeline(&self.pipeline);
+    pass.set_bind_group(0, &buffers.bind_group, &[]);
+    pass.set_vertex_buffer(0, buffers.instances.slice(..));
+    pass.draw(0..6, 0..instances);
+  }
+}
+
+impl Buffers {
+  fn new(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+    let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("post-processing uniforms"),
+      size: std::mem::size_of::<Uniforms>() as u64,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("post-processing bind group"),
+      layout: bind_group_layout,
+      entries: &[wgpu::BindGroupEntry { binding: 0, resource: uniforms.as_entire_binding() }],
+    });
+    Self {
+      instances: instance_buffer(device, 1),
+      instance_capacity: 1,
+      uniforms,
+      bind_group,
+      bounds: Rectangle::default(),
+    }
+  }
+}
+
+fn instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+  device.create_buffer(&wgpu::BufferDescriptor {
+    label: Some("post-processing instances"),
+    size: (capacity * std::mem::size_of::<Instance>()) as u64,
+    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    mapped_at_creation: false,
+  })
+}
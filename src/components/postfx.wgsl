@@ -0,0 +1,97 @@
+struct Uniforms {
+  // Width and height of the widget in logical pixels, the glow's reach and
+  // how far apart the colour fringes are pulled
+  size: vec2<f32>,
+  glow_radius: f32,
+  aberration: f32,
+};
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct Instance {
+  // Foot of the bar, then the way it grows
+  @location(0) origin: vec4<f32>,
+  // Half its width, its length and, for a trail, the length of the bar now
+  @location(1) size: vec4<f32>,
+  @location(2) color: vec4<f32>,
+  // Strength of the trail body, the glow and the fringes
+  @location(3) effects: vec4<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  // Across and along the bar, in pixels from its foot
+  @location(0) local: vec2<f32>,
+  @location(1) size: vec4<f32>,
+  @location(2) color: vec4<f32>,
+  @location(3) effects: vec4<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, instance: Instance) -> VertexOutput {
+  // Two triangles covering the bar and everything its glow reaches
+  var corners = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, 0.0), vec2<f32>(1.0, 0.0), vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, 0.0), vec2<f32>(1.0, 1.0), vec2<f32>(-1.0, 1.0),
+  );
+  let corner = corners[index];
+  let margin = uniforms.glow_radius * 3.0 + uniforms.aberration;
+  let across = corner.x * (instance.size.x + margin);
+  let along = -margin + corner.y * (instance.size.y + 2.0 * margin);
+
+  let normal = instance.origin.zw;
+  let side = vec2<f32>(-normal.y, normal.x);
+  let position = instance.origin.xy + normal * along + side * across;
+
+  var output: VertexOutput;
+  output.clip_position = vec4<f32>(
+    position.x / uniforms.size.x * 2.0 - 1.0,
+    1.0 - position.y / uniforms.size.y * 2.0,
+    0.0,
+    1.0,
+  );
+  output.local = vec2<f32>(across, along);
+  output.size = instance.size;
+  output.color = instance.color;
+  output.effects = instance.effects;
+  return output;
+}
+
+// Distance outside a bar `extent` long and twice `half_width` wide
+fn bar_distance(local: vec2<f32>, half_width: f32, extent: f32) -> f32 {
+  let box_half = vec2<f32>(half_width, extent * 0.5);
+  let offset = abs(local - vec2<f32>(0.0, extent * 0.5)) - box_half;
+  return length(max(offset, vec2<f32>(0.0))) + min(max(offset.x, offset.y), 0.0);
+}
+
+// How much of the pixel the bar covers, antialiased over a pixel
+fn coverage(local: vec2<f32>, half_width: f32, extent: f32) -> f32 {
+  return clamp(0.5 - bar_distance(local, half_width, extent), 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+  let half_width = input.size.x;
+  let extent = input.size.y;
+  let outside = bar_distance(input.local, half_width, extent);
+  var light = vec3<f32>(0.0);
+
+  // A trail only shows where it reaches past the bar now
+  let body = coverage(input.local, half_width, extent)
+    * (1.0 - coverage(input.local, half_width, input.size.z));
+  light += input.color.rgb * body * input.effects.x;
+
+  // Glow falls off away from the bar's edge and softly brightens inside it
+  let glow = exp(-max(outside, 0.0) / max(uniforms.glow_radius, 0.001));
+  light += input.color.rgb * glow * input.effects.y * select(1.0, 0.3, outside < 0.0);
+
+  // Red pulled one way across the bar and blue the other
+  let shift = vec2<f32>(uniforms.aberration, 0.0);
+  let here = coverage(input.local, half_width, extent);
+  let red = max(coverage(input.local + shift, half_width, extent) - here, 0.0);
+  let blue = max(coverage(input.local - shift, half_width, extent) - here, 0.0);
+  light += vec3<f32>(red, 0.0, blue) * input.effects.z;
+
+  // Added onto what's drawn already, leaving its alpha alone
+  return vec4<f32>(light * input.color.a, 0.0);
+}
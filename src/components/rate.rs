@@ -0,0 +1,129 @@
+use rodio::{
+  Source,
+  source::{SeekError, Speed},
+};
+use std::{
+  sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicU32, Ordering},
+  },
+  time::Duration,
+};
+
+/// The playback rate and how it's applied, shared between the player and
+/// every entry it queues so a change reaches the one playing.
+#[derive(Debug, Clone)]
+pub struct RateControl {
+  /// The rate's f32 bits.
+  rate: Arc<AtomicU32>,
+  preserve_pitch: Arc<AtomicBool>,
+}
+
+impl RateControl {
+  pub fn new() -> Self {
+    Self {
+      rate: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+      preserve_pitch: Arc::new(AtomicBool::new(false)),
+    }
+  }
+
+  pub fn set(&self, rate: f32, preserve_pitch: bool) {
+    self.rate.store(rate.to_bits(), Ordering::Relaxed);
+    self.preserve_pitch.store(preserve_pitch, Ordering::Relaxed);
+  }
+
+  pub fn rate(&self) -> f32 {
+    f32::from_bits(self.rate.load(Ordering::Relaxed))
+  }
+
+  /// Speed-up applied by resampling, which moves the pitch with it.
+  pub fn varispeed(&self) -> f32 {
+    if self.preserve_pitch.load(Ordering::Relaxed) { 1.0 } else { self.rate() }
+  }
+
+  /// Speed-up applied by time-stretching, which keeps the pitch.
+  pub fn stretch(&self) -> f32 {
+    if self.preserve_pitch.load(Ordering::Relaxed) { self.rate() } else { 1.0 }
+  }
+}
+
+impl Default for RateControl {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// A source sped up or slowed down by rodio's [`Speed`], on top of a fixed
+/// `base` speed. The factor follows a [`RateControl`], picked up between
+/// the inner source's frames so a resampler after it sees each frame at a
+/// single rate.
+pub struct Varispeed<S>
+where
+  S: Source<Item = f32>,
+{
+  inner: Speed<S>,
+  base: f32,
+  control: RateControl,
+}
+
+impl<S> Varispeed<S>
+where
+  S: Source<Item = f32>,
+{
+  pub fn new(source: S, base: f32, control: RateControl) -> Self {
+    let factor = base * control.varispeed();
+    Self { inner: source.speed(factor), base, control }
+  }
+
+  fn refresh(&mut self) {
+    self.inner.set_factor(self.base * self.control.varispeed());
+  }
+}
+
+impl<S> Iterator for Varispeed<S>
+where
+  S: Source<Item = f32>,
+{
+  type Item = f32;
+
+  #[inline]
+  fn next(&mut self) -> Option<f32> {
+    let sample = self.inner.next();
+    if self.inner.current_frame_len() == Some(0) {
+      self.refresh();
+    }
+    sample
+  }
+}
+
+impl<S> Source for Varispeed<S>
+where
+  S: Source<Item = f32>,
+{
+  #[inline]
+  fn current_frame_len(&self) -> Option<usize> {
+    self.inner.current_frame_len()
+  }
+
+  #[inline]
+  fn channels(&self) -> u16 {
+    self.inner.channels()
+  }
+
+  #[inline]
+  fn sample_rate(&self) -> u32 {
+    self.inner.sample_rate()
+  }
+
+  #[inline]
+  fn total_duration(&self) -> Option<Duration> {
+    self.inner.total_duration()
+  }
+
+  fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+    // Positions are track time; Speed would scale them by its factor
+    self.inner.inner_mut().try_seek(position)?;
+    self.refresh();
+    Ok(())
+  }
+}
@@ -0,0 +1,176 @@
+use iced::{
+  Color, Point, Rectangle, Size, Theme, Vector,
+  mouse::Cursor,
+  widget::canvas::{self, Geometry},
+};
+
+use crate::{
+  analysis::{DecibelRange, map_range},
+  app::{MAX_BAR_HEIGHT, MIN_BAR_HEIGHT, Message},
+  components::{
+    bars,
+    binning::FrequencyScale,
+    layout::{LayoutKind, Placement, RingSettings, Shape},
+  },
+};
+
+/// Closest labels are allowed to sit, in pixels; denser bars get every few.
+const LABEL_SPACING: f32 = 36.0;
+/// How far beyond the foot of a bar its label sits.
+const LABEL_OFFSET: f32 = 12.0;
+/// How far past a bar's sides the mouse still counts as on it.
+const HOVER_SLACK: f32 = 4.0;
+const TOOLTIP_SIZE: Size = Size::new(140.0, 38.0);
+
+/// Each bar's centre frequency, and the frequencies and level of the bar
+/// under the mouse, over the bars.
+pub struct BarReadout<'a> {
+  /// Bar heights, as the bars are drawn from.
+  pub frequency_data: &'a [f32],
+  pub layout: LayoutKind,
+  pub shape: Option<Shape<'a>>,
+  pub scale: FrequencyScale,
+  pub sample_rate: u32,
+  pub decibels: DecibelRange,
+  /// Labels the bars; the tooltip shows either way.
+  pub labels: bool,
+  pub ring: RingSettings,
+  /// Seconds in, for the ring's spin.
+  pub time: f32,
+}
+
+impl<'a> BarReadout<'a> {
+  /// Lowest and highest frequency drawn bar `index` of `drawn` covers, and
+  /// its height. Bars are drawn from groups of the data when there's no
+  /// room for all of it, mirrored bars show the same group, and the second
+  /// half of the data runs back down the bands for the other channel.
+  fn bar(&self, index: usize, drawn: usize, edges: &[f32]) -> (f32, f32, f32) {
+    let count = self.frequency_data.len();
+    let (drawn, distinct) = self.ring.mirror.counts(count, drawn);
+    let (index, drawn) = (self.ring.mirror.source(index, drawn, distinct), distinct);
+    let (start, end) = if drawn >= count {
+      (index, index + 1)
+    } else {
+      let start = index * count / drawn;
+      (start, ((index + 1) * count / drawn).max(start + 1))
+    };
+    (start..end).fold((f32::MAX, 0.0, MIN_BAR_HEIGHT), |(low, high, height), i| {
+      let (_, band) = bars::band(i, count);
+      (low.min(edges[band]), high.max(edges[band + 1]), height.max(self.frequency_data[i]))
+    })
+  }
+}
+
+impl<'a> canvas::Program<Message> for BarReadout<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    cursor: Cursor,
+  ) -> Vec<Geometry> {
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    if self.frequency_data.is_empty() {
+      return vec![frame.into_geometry()];
+    }
+    let placement = Placement::compute(
+      self.layout,
+      self.shape,
+      bounds,
+      self.frequency_data.len(),
+      self.ring,
+      self.time,
+    );
+    let (drawn, _) = self.ring.mirror.counts(self.frequency_data.len(), placement.anchors.len());
+    let edges = self.scale.band_edges(self.frequency_data.len().div_ceil(2), self.sample_rate);
+
+    if self.labels && drawn > 0 {
+      let spacing = match placement.anchors.get(..2) {
+        Some([first, second]) => first.position.distance(second.position),
+        _ => LABEL_SPACING,
+      };
+      let every = (LABEL_SPACING / spacing.max(f32::EPSILON)).ceil().max(1.0) as usize;
+      for (index, anchor) in placement.anchors.iter().take(drawn).enumerate().step_by(every) {
+        let (low, high, _) = self.bar(index, drawn, &edges);
+        frame.fill_text(canvas::Text {
+          content: short_hz((low + high) / 2.0),
+          position: anchor.position - anchor.normal * LABEL_OFFSET,
+          color: Color::from_rgba(1.0, 1.0, 1.0, 0.7),
+          size: 10.0.into(),
+          horizontal_alignment: iced::alignment::Horizontal::Center,
+          vertical_alignment: iced::alignment::Vertical::Center,
+          ..canvas::Text::default()
+        });
+      }
+    }
+
+    let Some(at) = cursor.position_in(bounds) else {
+      return vec![frame.into_geometry()];
+    };
+    // The bar whose column the mouse is in, nearest its middle
+    let hovered = placement
+      .anchors
+      .iter()
+      .take(drawn)
+      .enumerate()
+      .filter_map(|(index, anchor)| {
+        let offset = at - anchor.position;
+        let along = offset.x * anchor.normal.x + offset.y * anchor.normal.y;
+        let across = (offset.y * anchor.normal.x - offset.x * anchor.normal.y).abs();
+        (across <= placement.bar_width / 2.0 + HOVER_SLACK
+          && (-HOVER_SLACK..=placement.max_bar_height).contains(&along))
+        .then_some((index, across))
+      })
+      .min_by(|a, b| a.1.total_cmp(&b.1));
+    let Some((index, _)) = hovered else {
+      return vec![frame.into_geometry()];
+    };
+
+    let (low, high, height) = self.bar(index, drawn, &edges);
+    let decibels =
+      map_range(height, MIN_BAR_HEIGHT, MAX_BAR_HEIGHT, self.decibels.min, self.decibels.max)
+        .clamp(self.decibels.min, self.decibels.max);
+    // Beside the mouse, flipped to stay inside the canvas
+    let mut corner = at + Vector::new(12.0, 12.0);
+    if corner.x + TOOLTIP_SIZE.width > bounds.width {
+      corner.x = at.x - 12.0 - TOOLTIP_SIZE.width;
+    }
+    if corner.y + TOOLTIP_SIZE.height > bounds.height {
+      corner.y = at.y - 12.0 - TOOLTIP_SIZE.height;
+    }
+    frame.fill_rectangle(corner, TOOLTIP_SIZE, Color::from_rgba(0.0, 0.0, 0.0, 0.75));
+    for (line, content) in
+      [format!("{} - {}", hz(low), hz(high)), format!("{:.1} dB", decibels)].into_iter().enumerate()
+    {
+      frame.fill_text(canvas::Text {
+        content,
+        position: Point::new(corner.x + 6.0, corner.y + 4.0 + line as f32 * 16.0),
+        color: Color::WHITE,
+        size: 12.0.into(),
+        ..canvas::Text::default()
+      });
+    }
+
+    vec![frame.into_geometry()]
+  }
+}
+
+fn hz(frequency: f32) -> String {
+  if frequency >= 1000.0 {
+    format!("{:.2} kHz", frequency / 1000.0)
+  } else {
+    format!("{:.0} Hz", frequency)
+  }
+}
+
+/// Short enough to fit under a bar.
+fn short_hz(frequency: f32) -> String {
+  if frequency >= 1000.0 {
+    format!("{:.1}k", frequency / 1000.0)
+  } else {
+    format!("{:.0}", frequency)
+  }
+}
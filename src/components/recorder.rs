@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use crate::app::Message;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum RecorderState {
+  #[default]
+  Idle,
+  Recording,
+  Replaying,
+}
+
+/// Captures control actions against the track position so they can be played
+/// back later, e.g. for a repeatable demo reel.
+#[derive(Debug, Default)]
+pub struct MacroRecorder {
+  state: RecorderState,
+  events: Vec<(Duration, Message)>,
+  /// Index of the next event to replay.
+  cursor: usize,
+}
+
+impl MacroRecorder {
+  pub fn is_recording(&self) -> bool {
+    self.state == RecorderState::Recording
+  }
+
+  pub fn is_replaying(&self) -> bool {
+    self.state == RecorderState::Replaying
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.events.is_empty()
+  }
+
+  pub fn len(&self) -> usize {
+    self.events.len()
+  }
+
+  /// Starts a fresh recording, discarding the previous one.
+  pub fn start_recording(&mut self) {
+    self.events.clear();
+    self.state = RecorderState::Recording;
+  }
+
+  pub fn record(&mut self, at: Duration, message: Message) {
+    if self.is_recording() {
+      self.events.push((at, message));
+    }
+  }
+
+  pub fn start_replay(&mut self) {
+    self.cursor = 0;
+    self.state = RecorderState::Replaying;
+  }
+
+  pub fn stop(&mut self) {
+    // Seeking around while recording can produce out-of-order timestamps
+    self.events.sort_by_key(|(at, _)| *at);
+    self.state = RecorderState::Idle;
+  }
+
+  /// Returns every recorded action up to `position` that hasn't been replayed yet.
+  pub fn due(&mut self, position: Duration) -> Vec<Message> {
+    if !self.is_replaying() {
+      return Vec::new();
+    }
+
+    let start = self.cursor;
+    while self.cursor < self.events.len() && self.events[self.cursor].0 <= position {
+      self.cursor += 1;
+    }
+    if self.cursor >= self.events.len() {
+      self.state = RecorderState::Idle;
+    }
+
+    self.events[start..self.cursor].iter().map(|(_, message)| message.clone()).collect()
+  }
+}
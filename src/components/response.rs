@@ -0,0 +1,73 @@
+use iced::{
+  Color, Point, Rectangle, Theme,
+  widget::canvas::{self, Geometry, Path, Stroke},
+};
+
+use crate::{app::Message, components::binning::FrequencyScale};
+
+/// The overlay spans ± this many dB around the response's average level.
+const RESPONSE_RANGE_DB: f32 = 24.0;
+
+/// A measured frequency response drawn as a line over the live analyser.
+pub struct ResponseOverlay<'a> {
+  /// Magnitude in dB per FFT bin, up to Nyquist.
+  pub response: &'a [f32],
+  pub scale: FrequencyScale,
+  pub sample_rate: u32,
+}
+
+impl<'a> canvas::Program<Message> for ResponseOverlay<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+
+    let bin_hz = self.sample_rate as f32 / (2.0 * self.response.len().max(1) as f32);
+    let points: Vec<(f32, f32)> = self
+      .response
+      .iter()
+      .enumerate()
+      .map(|(i, &db)| (self.scale.position(i as f32 * bin_hz, self.sample_rate), db))
+      .filter(|(x, _)| (0.0..=1.0).contains(x))
+      .collect();
+    if points.len() < 2 {
+      return vec![frame.into_geometry()];
+    }
+
+    // Only the shape matters, so the average level sits in the middle
+    let average = points.iter().map(|(_, db)| db).sum::<f32>() / points.len() as f32;
+    let to_y = |db: f32| {
+      let db = (db - average).clamp(-RESPONSE_RANGE_DB, RESPONSE_RANGE_DB);
+      bounds.height / 2.0 - db / RESPONSE_RANGE_DB * bounds.height / 2.0
+    };
+
+    let line = Path::new(|builder| {
+      for (i, &(x, db)) in points.iter().enumerate() {
+        let point = Point::new(x * bounds.width, to_y(db));
+        if i == 0 {
+          builder.move_to(point);
+        } else {
+          builder.line_to(point);
+        }
+      }
+    });
+    let color = Color::from_rgba(1.0, 1.0, 1.0, 0.8);
+    frame.stroke(&line, Stroke::default().with_color(color).with_width(2.0));
+    frame.fill_text(canvas::Text {
+      content: format!("Room response (±{:.0} dB)", RESPONSE_RANGE_DB),
+      position: Point::new(4.0, 4.0),
+      color,
+      size: 12.0.into(),
+      ..canvas::Text::default()
+    });
+
+    vec![frame.into_geometry()]
+  }
+}
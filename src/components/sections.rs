@@ -0,0 +1,257 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::fmt;
+
+/// Feature blocks are this long, in seconds, and don't overlap.
+const BLOCK_SECONDS: f32 = 0.5;
+/// FFT size taken at the start of every block.
+const BLOCK_FFT: usize = 4096;
+/// Half-width of the novelty kernel, in blocks: 8 s either side.
+const KERNEL_BLOCKS: usize = 16;
+/// Sections are at least this long, in seconds.
+const MIN_SECTION_SECONDS: f32 = 8.0;
+/// A boundary needs novelty this many deviations above the track's mean.
+const PEAK_DEVIATIONS: f32 = 0.5;
+/// Sections this similar (cosine of their mean features) count as repeats.
+const REPEAT_SIMILARITY: f32 = 0.6;
+/// Edges of the coarse log-spaced bands, in Hz.
+const BAND_EDGES: [f32; 9] = [40.0, 100.0, 200.0, 400.0, 800.0, 1600.0, 3200.0, 6400.0, 12800.0];
+
+/// What part of a song a section plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionKind {
+  Intro,
+  Verse,
+  Chorus,
+  Bridge,
+  Outro,
+}
+
+impl fmt::Display for SectionKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      SectionKind::Intro => "Intro",
+      SectionKind::Verse => "Verse",
+      SectionKind::Chorus => "Chorus",
+      SectionKind::Bridge => "Bridge",
+      SectionKind::Outro => "Outro",
+    })
+  }
+}
+
+/// A section of a song; it lasts until the next one starts.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Section {
+  /// Seconds into the track.
+  pub start: f32,
+  pub kind: SectionKind,
+}
+
+/// Splits mono samples into sections and guesses what each one is.
+///
+/// Every block gets a feature vector of its chroma (harmony) and coarse
+/// band energies (timbre). A checkerboard kernel slid along the diagonal of
+/// their self-similarity matrix scores how much the music changes at each
+/// block, and the clear peaks of that novelty become boundaries. Sections
+/// that sound alike are grouped; the loudest repeated group is taken as the
+/// chorus, other repeats as verses, and one-offs as intro, bridge or outro
+/// by where they fall.
+pub fn detect(samples: &[f32], sample_rate: u32) -> Vec<Section> {
+  let block = ((BLOCK_SECONDS * sample_rate as f32) as usize).max(BLOCK_FFT);
+  let features = block_features(samples, sample_rate, block);
+  if features.is_empty() {
+    return Vec::new();
+  }
+  let novelty = novelty(&features);
+  let boundaries = boundaries(&novelty);
+
+  // Mean features and loudness of every section
+  let spans: Vec<(usize, usize)> = boundaries
+    .iter()
+    .zip(boundaries.iter().skip(1).chain([&features.len()]))
+    .map(|(&start, &end)| (start, end))
+    .collect();
+  let summaries: Vec<(Vec<f32>, f32)> = spans
+    .iter()
+    .map(|&(start, end)| {
+      let blocks = &features[start..end];
+      let mut mean = vec![0.0; blocks[0].vector.len()];
+      for block in blocks {
+        for (sum, value) in mean.iter_mut().zip(&block.vector) {
+          *sum += value / blocks.len() as f32;
+        }
+      }
+      let loudness = blocks.iter().map(|block| block.loudness).sum::<f32>() / blocks.len() as f32;
+      (mean, loudness)
+    })
+    .collect();
+
+  // Each section joins the first earlier group it sounds like
+  let mut groups: Vec<usize> = Vec::with_capacity(summaries.len());
+  for (i, (mean, _)) in summaries.iter().enumerate() {
+    let group = (0..i)
+      .find(|&earlier| cosine(mean, &summaries[earlier].0) >= REPEAT_SIMILARITY)
+      .map_or(i, |earlier| groups[earlier]);
+    groups.push(group);
+  }
+  let repeats = |group: usize| groups.iter().filter(|&&other| other == group).count();
+  let group_loudness = |group: usize| {
+    let members = groups.iter().zip(&summaries).filter(|&(&other, _)| other == group);
+    members.map(|(_, &(_, loudness))| loudness).sum::<f32>() / repeats(group).max(1) as f32
+  };
+  let chorus = groups
+    .iter()
+    .copied()
+    .filter(|&group| repeats(group) > 1)
+    .max_by(|&a, &b| group_loudness(a).total_cmp(&group_loudness(b)));
+
+  let last = spans.len() - 1;
+  spans
+    .iter()
+    .zip(&groups)
+    .enumerate()
+    .map(|(i, (&(start, _), &group))| {
+      let kind = match group {
+        _ if Some(group) == chorus => SectionKind::Chorus,
+        _ if repeats(group) > 1 => SectionKind::Verse,
+        _ if i == 0 => SectionKind::Intro,
+        _ if i == last => SectionKind::Outro,
+        _ => SectionKind::Bridge,
+      };
+      Section { start: start as f32 * block as f32 / sample_rate as f32, kind }
+    })
+    .collect()
+}
+
+/// Jump targets: the start of the section after `position`, if any.
+pub fn next_start(sections: &[Section], position: f32) -> Option<f32> {
+  // A little slack, so a jump that lands just short doesn't stick
+  sections.iter().map(|section| section.start).find(|&start| start > position + 0.5)
+}
+
+/// The start of the section playing at `position`, or of the one before
+/// if it's only just begun, like a previous-track button.
+pub fn previous_start(sections: &[Section], position: f32) -> Option<f32> {
+  sections.iter().map(|section| section.start).rev().find(|&start| start < position - 2.0)
+}
+
+struct BlockFeatures {
+  /// Chroma then band energies, each normalised, with the track's mean taken off.
+  vector: Vec<f32>,
+  /// Mean square of the block's samples.
+  loudness: f32,
+}
+
+fn block_features(samples: &[f32], sample_rate: u32, block: usize) -> Vec<BlockFeatures> {
+  let fft = FftPlanner::new().plan_fft_forward(BLOCK_FFT);
+  let window: Vec<f32> = (0..BLOCK_FFT)
+    .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / BLOCK_FFT as f32).cos())
+    .collect();
+  let bin_hz = sample_rate as f32 / BLOCK_FFT as f32;
+
+  let mut features: Vec<BlockFeatures> = samples
+    .chunks_exact(block)
+    .map(|chunk| {
+      let mut buffer: Vec<Complex<f32>> =
+        chunk[..BLOCK_FFT].iter().zip(&window).map(|(&x, &w)| Complex::new(x * w, 0.0)).collect();
+      fft.process(&mut buffer);
+
+      let mut chroma = [0.0f32; 12];
+      let mut bands = [0.0f32; BAND_EDGES.len() - 1];
+      for (bin, value) in buffer[1..BLOCK_FFT / 2].iter().enumerate() {
+        let hz = (bin + 1) as f32 * bin_hz;
+        let magnitude = value.norm();
+        if (55.0..=2000.0).contains(&hz) {
+          chroma[(12.0 * (hz / 440.0).log2() + 69.0).round() as usize % 12] += magnitude;
+        }
+        if let Some(band) = BAND_EDGES.windows(2).position(|edge| (edge[0]..edge[1]).contains(&hz))
+        {
+          bands[band] += magnitude * magnitude;
+        }
+      }
+      let bands = bands.map(|energy| (1.0 + energy).ln());
+
+      let mut vector = normalised(&chroma);
+      vector.extend(normalised(&bands));
+      let loudness = chunk.iter().map(|x| x * x).sum::<f32>() / chunk.len() as f32;
+      BlockFeatures { vector, loudness }
+    })
+    .collect();
+
+  // Taking off what every block shares leaves what tells them apart
+  if let Some(first) = features.first() {
+    let mut mean = vec![0.0; first.vector.len()];
+    for block in &features {
+      for (sum, value) in mean.iter_mut().zip(&block.vector) {
+        *sum += value / features.len() as f32;
+      }
+    }
+    for block in &mut features {
+      for (value, mean) in block.vector.iter_mut().zip(&mean) {
+        *value -= mean;
+      }
+    }
+  }
+  features
+}
+
+/// Foote novelty: a Gaussian-tapered checkerboard kernel correlated along
+/// the diagonal of the self-similarity matrix, which peaks where the blocks
+/// before are alike, the blocks after are alike, and the two differ.
+fn novelty(features: &[BlockFeatures]) -> Vec<f32> {
+  let count = features.len();
+  let half = KERNEL_BLOCKS as isize;
+  let taper = |offset: isize| (-0.5 * (offset as f32 / (half as f32 / 2.0)).powi(2)).exp();
+
+  (0..count)
+    .map(|i| {
+      let mut score = 0.0;
+      for a in -half..half {
+        for b in -half..half {
+          let (x, y) = (i as isize + a, i as isize + b);
+          if x < 0 || y < 0 || x >= count as isize || y >= count as isize {
+            continue;
+          }
+          // Same side of the boundary counts for, across it against
+          let sign = if (a < 0) == (b < 0) { 1.0 } else { -1.0 };
+          let similarity = cosine(&features[x as usize].vector, &features[y as usize].vector);
+          score += sign * taper(a) * taper(b) * similarity;
+        }
+      }
+      score.max(0.0)
+    })
+    .collect()
+}
+
+/// Block indices where sections start, always beginning with 0.
+fn boundaries(novelty: &[f32]) -> Vec<usize> {
+  let count = novelty.len() as f32;
+  let mean = novelty.iter().sum::<f32>() / count;
+  let deviation = (novelty.iter().map(|n| (n - mean).powi(2)).sum::<f32>() / count).sqrt();
+  let threshold = mean + PEAK_DEVIATIONS * deviation;
+  let min_blocks = (MIN_SECTION_SECONDS / BLOCK_SECONDS) as usize;
+
+  let mut boundaries = vec![0];
+  for i in 1..novelty.len() {
+    let neighbourhood =
+      &novelty[i.saturating_sub(min_blocks / 2)..(i + min_blocks / 2 + 1).min(novelty.len())];
+    let is_peak = novelty[i] >= threshold && neighbourhood.iter().all(|&n| n <= novelty[i]);
+    let far_enough = boundaries.last().is_some_and(|&last| i - last >= min_blocks);
+    // The last section needs room too
+    if is_peak && far_enough && novelty.len() - i >= min_blocks {
+      boundaries.push(i);
+    }
+  }
+  boundaries
+}
+
+fn normalised(values: &[f32]) -> Vec<f32> {
+  let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+  values.iter().map(|v| if norm > 0.0 { v / norm } else { 0.0 }).collect()
+}
+
+fn cosine(a: &[f32], b: &[f32]) -> f32 {
+  let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+  let norm =
+    a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+  if norm > 0.0 { dot / norm } else { 0.0 }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+/// Slowest rise the attack slider allows, in milliseconds.
+pub const MAX_ATTACK_MS: f32 = 500.0;
+/// Slowest fall the release slider allows, in milliseconds.
+pub const MAX_RELEASE_MS: f32 = 2000.0;
+/// Upper edge of the low region, in Hz.
+const LOW_CROSSOVER: f32 = 250.0;
+/// Lower edge of the high region, in Hz.
+const HIGH_CROSSOVER: f32 = 4000.0;
+
+/// Frequency regions that get their own smoothing, so kicks can linger while
+/// hi-hats stay crisp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Region {
+  #[default]
+  Low,
+  Mid,
+  High,
+}
+
+impl Region {
+  pub const ALL: [Region; 3] = [Region::Low, Region::Mid, Region::High];
+
+  pub fn of(frequency: f32) -> Self {
+    if frequency < LOW_CROSSOVER {
+      Region::Low
+    } else if frequency < HIGH_CROSSOVER {
+      Region::Mid
+    } else {
+      Region::High
+    }
+  }
+}
+
+impl fmt::Display for Region {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Region::Low => "Low",
+      Region::Mid => "Mid",
+      Region::High => "High",
+    })
+  }
+}
+
+/// Time constants for rising and falling bars, in milliseconds: how long a
+/// bar takes to cover about two thirds of a jump. 0 follows instantly.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+  pub attack_ms: f32,
+  pub release_ms: f32,
+}
+
+impl Envelope {
+  /// Moves `old` towards `new` by as much as `elapsed` allows, so the
+  /// speed doesn't depend on how often it's called.
+  pub fn apply(self, old: f32, new: f32, elapsed: Duration) -> f32 {
+    let time_constant = if new > old { self.attack_ms } else { self.release_ms };
+    if time_constant <= 0.0 {
+      return new;
+    }
+    let factor = (-elapsed.as_secs_f32() * 1000.0 / time_constant).exp();
+    old * factor + new * (1.0 - factor)
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RegionSmoothing {
+  pub low: Envelope,
+  pub mid: Envelope,
+  pub high: Envelope,
+}
+
+impl RegionSmoothing {
+  pub fn get(&self, region: Region) -> Envelope {
+    match region {
+      Region::Low => self.low,
+      Region::Mid => self.mid,
+      Region::High => self.high,
+    }
+  }
+
+  pub fn get_mut(&mut self, region: Region) -> &mut Envelope {
+    match region {
+      Region::Low => &mut self.low,
+      Region::Mid => &mut self.mid,
+      Region::High => &mut self.high,
+    }
+  }
+}
+
+impl Default for RegionSmoothing {
+  fn default() -> Self {
+    // Bass falls slowly, highs snap back almost immediately
+    Self {
+      low: Envelope { attack_ms: 10.0, release_ms: 30.0 },
+      mid: Envelope { attack_ms: 10.0, release_ms: 15.0 },
+      high: Envelope { attack_ms: 7.0, release_ms: 8.0 },
+    }
+  }
+}
@@ -0,0 +1,124 @@
+use std::{collections::VecDeque, fmt};
+
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  widget::canvas::{self, Geometry},
+};
+
+use crate::{
+  app::Message,
+  components::gradient::{Gradient, interpolate},
+};
+
+/// Colour maps for the spectrogram; the fixed ones are sampled at nine evenly spaced stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Colormap {
+  #[default]
+  Viridis,
+  Magma,
+  Grayscale,
+  /// Follows the colour theme used by the other views.
+  Theme,
+}
+
+const VIRIDIS: [[u8; 3]; 9] = [
+  [0x44, 0x01, 0x54],
+  [0x48, 0x28, 0x78],
+  [0x3e, 0x49, 0x89],
+  [0x31, 0x68, 0x8e],
+  [0x26, 0x82, 0x8e],
+  [0x1f, 0x9e, 0x89],
+  [0x35, 0xb7, 0x79],
+  [0x6e, 0xce, 0x58],
+  [0xfd, 0xe7, 0x25],
+];
+
+const MAGMA: [[u8; 3]; 9] = [
+  [0x00, 0x00, 0x04],
+  [0x1c, 0x10, 0x44],
+  [0x4f, 0x12, 0x7b],
+  [0x81, 0x25, 0x81],
+  [0xb5, 0x36, 0x7a],
+  [0xe5, 0x50, 0x64],
+  [0xfb, 0x87, 0x61],
+  [0xfe, 0xc2, 0x87],
+  [0xfc, 0xfd, 0xbf],
+];
+
+impl Colormap {
+  pub const ALL: [Colormap; 4] =
+    [Colormap::Viridis, Colormap::Magma, Colormap::Grayscale, Colormap::Theme];
+
+  /// Maps `value` in 0.0..=1.0 to a colour.
+  pub fn color(self, value: f32, gradient: &Gradient) -> Color {
+    let value = value.clamp(0.0, 1.0);
+    // The theme is graded already; the fixed maps get the same grade
+    let color = match self {
+      Colormap::Viridis => interpolate(&VIRIDIS, value),
+      Colormap::Magma => interpolate(&MAGMA, value),
+      Colormap::Grayscale => Color::from_rgb(value, value, value),
+      Colormap::Theme => return gradient.color(value),
+    };
+    gradient.grade.apply(color)
+  }
+}
+
+impl fmt::Display for Colormap {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Colormap::Viridis => "Viridis",
+      Colormap::Magma => "Magma",
+      Colormap::Grayscale => "Grayscale",
+      Colormap::Theme => "Theme",
+    })
+  }
+}
+
+/// Scrolling waterfall of past spectra, newest on the right and low frequencies at the bottom.
+pub struct SpectrogramCanvas<'a> {
+  /// Frames of normalised (0.0..=1.0) band levels, oldest first.
+  pub history: &'a VecDeque<Vec<f32>>,
+  /// How many frames the full width represents.
+  pub history_length: usize,
+  pub colormap: Colormap,
+  pub gradient: Gradient,
+  pub cache: &'a canvas::Cache,
+}
+
+impl<'a> canvas::Program<Message> for SpectrogramCanvas<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
+      frame.fill_rectangle(Point::ORIGIN, bounds.size(), self.colormap.color(0.0, &self.gradient));
+
+      let column_width = bounds.width / self.history_length.max(1) as f32;
+      // Right-align so the newest frame always sits at the right edge
+      let first_x = bounds.width - self.history.len() as f32 * column_width;
+
+      for (column, levels) in self.history.iter().enumerate() {
+        let row_height = bounds.height / levels.len().max(1) as f32;
+        let x = first_x + column as f32 * column_width;
+
+        for (row, &level) in levels.iter().enumerate() {
+          let y = bounds.height - (row + 1) as f32 * row_height;
+          // Slight overlap hides hairline seams between cells
+          frame.fill_rectangle(
+            Point::new(x, y),
+            Size::new(column_width + 0.5, row_height + 0.5),
+            self.colormap.color(level, &self.gradient),
+          );
+        }
+      }
+    });
+
+    vec![geometry]
+  }
+}
@@ -0,0 +1,264 @@
+use rodio::{Source, source::SeekError};
+use rustfft::{Fft, FftPlanner, num_complex::Complex};
+use std::{
+  collections::VecDeque,
+  f32::consts::{PI, TAU},
+  sync::Arc,
+  time::Duration,
+};
+
+use crate::components::{rate::RateControl, window_fn::WindowFunction};
+
+/// Samples per channel in each analysed frame.
+const FRAME: usize = 2048;
+/// Output step between frames; at a quarter frame the squared Hann windows
+/// overlap to a flat gain.
+const HOP: usize = FRAME / 4;
+/// That gain, divided back out.
+const WINDOW_GAIN: f32 = 1.5;
+
+/// One channel's share of the vocoder.
+struct ChannelState {
+  /// Input from the current frame's start on.
+  input: VecDeque<f32>,
+  /// Overlap-added output, from the next sample due.
+  output: Vec<f32>,
+  /// Each bin's phase in the last analysed frame.
+  analysed: Vec<f32>,
+  /// Each bin's phase as resynthesised.
+  synthesised: Vec<f32>,
+}
+
+impl ChannelState {
+  fn new() -> Self {
+    Self {
+      input: VecDeque::with_capacity(FRAME * 2),
+      output: vec![0.0; FRAME],
+      analysed: vec![0.0; FRAME / 2 + 1],
+      synthesised: vec![0.0; FRAME / 2 + 1],
+    }
+  }
+}
+
+/// Plays a source faster or slower at the same pitch, following the
+/// stretch of a [`RateControl`].
+///
+/// A phase vocoder: frames are read a rate-scaled step apart and written a
+/// fixed [`HOP`] apart, with each bin's phase advanced by the frequency it
+/// measured so the overlapping frames line up again. At normal speed the
+/// source passes straight through.
+pub struct TimeStretch<S>
+where
+  S: Source<Item = f32>,
+{
+  inner: S,
+  control: RateControl,
+  channels: Vec<ChannelState>,
+  window: Vec<f32>,
+  forward: Arc<dyn Fft<f32>>,
+  inverse: Arc<dyn Fft<f32>>,
+  buffer: Vec<Complex<f32>>,
+  /// Input step the current frame was reached by; none for the first frame.
+  last_step: Option<usize>,
+  /// Fraction of a sample the steps so far have fallen short by.
+  carry: f64,
+  /// Stretched samples ready to play, interleaved.
+  ready: VecDeque<f32>,
+  exhausted: bool,
+}
+
+impl<S> TimeStretch<S>
+where
+  S: Source<Item = f32>,
+{
+  pub fn new(inner: S, control: RateControl) -> Self {
+    let mut planner = FftPlanner::new();
+    let channels = (0..inner.channels().max(1)).map(|_| ChannelState::new()).collect();
+    Self {
+      inner,
+      control,
+      channels,
+      window: WindowFunction::Hann.coefficients(FRAME),
+      forward: planner.plan_fft_forward(FRAME),
+      inverse: planner.plan_fft_inverse(FRAME),
+      buffer: vec![Complex::new(0.0, 0.0); FRAME],
+      last_step: None,
+      carry: 0.0,
+      ready: VecDeque::with_capacity(HOP * 2),
+      exhausted: false,
+    }
+  }
+
+  fn is_stretching(&self) -> bool {
+    self.last_step.is_some() || self.channels.iter().any(|channel| !channel.input.is_empty())
+  }
+
+  /// Forgets every frame, to start over from wherever the source is now.
+  fn reset(&mut self) {
+    for channel in &mut self.channels {
+      *channel = ChannelState::new();
+    }
+    self.last_step = None;
+    self.carry = 0.0;
+    self.ready.clear();
+  }
+
+  /// Back at normal speed: plays out the input already read as it was, so
+  /// nothing is skipped, and passes through from then on.
+  fn flush(&mut self) {
+    let remaining = self.channels[0].input.len();
+    for i in 0..remaining {
+      for channel in &self.channels {
+        self.ready.push_back(channel.input[i]);
+      }
+    }
+    let ready = std::mem::take(&mut self.ready);
+    self.reset();
+    self.ready = ready;
+  }
+
+  /// Reads the input up to a whole frame on every channel.
+  fn fill(&mut self) {
+    while !self.exhausted && self.channels[0].input.len() < FRAME {
+      for index in 0..self.channels.len() {
+        let sample = match self.inner.next() {
+          Some(sample) => sample,
+          None => {
+            self.exhausted = true;
+            if index == 0 {
+              return;
+            }
+            // Keeps the channels the same length
+            0.0
+          }
+        };
+        self.channels[index].input.push_back(sample);
+      }
+    }
+  }
+
+  /// Stretches one frame into the next [`HOP`] samples of output. Returns
+  /// false once the input has all been played.
+  fn step(&mut self, rate: f32) -> bool {
+    self.fill();
+    let available = self.channels[0].input.len();
+    if available == 0 {
+      return false;
+    }
+
+    for channel in &mut self.channels {
+      // The source's last frame is padded out with silence
+      channel.input.resize(FRAME.max(available), 0.0);
+      for (i, value) in self.buffer.iter_mut().enumerate() {
+        *value = Complex::new(channel.input[i] * self.window[i], 0.0);
+      }
+      self.forward.process(&mut self.buffer);
+
+      for bin in 0..=FRAME / 2 {
+        let (magnitude, phase) = self.buffer[bin].to_polar();
+        channel.synthesised[bin] = match self.last_step {
+          Some(step) => {
+            // How far the phase moved beyond a whole bin's worth tells the
+            // bin's true frequency, which then runs on for one output hop
+            let expected = TAU * bin as f32 / FRAME as f32;
+            let deviation = wrap(phase - channel.analysed[bin] - expected * step as f32);
+            let frequency = expected + deviation / step as f32;
+            wrap(channel.synthesised[bin] + frequency * HOP as f32)
+          }
+          None => phase,
+        };
+        channel.analysed[bin] = phase;
+        self.buffer[bin] = Complex::from_polar(magnitude, channel.synthesised[bin]);
+      }
+      // Mirrored so the inverse comes out real
+      for bin in 1..FRAME / 2 {
+        self.buffer[FRAME - bin] = self.buffer[bin].conj();
+      }
+      self.inverse.process(&mut self.buffer);
+
+      let scale = 1.0 / (FRAME as f32 * WINDOW_GAIN);
+      for (i, output) in channel.output.iter_mut().enumerate() {
+        *output += self.buffer[i].re * self.window[i] * scale;
+      }
+    }
+
+    for i in 0..HOP {
+      for channel in &self.channels {
+        self.ready.push_back(channel.output[i]);
+      }
+    }
+
+    let step = HOP as f64 * rate as f64 + self.carry;
+    let whole = (step.floor() as usize).max(1);
+    self.carry = (step - whole as f64).max(0.0);
+    for channel in &mut self.channels {
+      channel.output.copy_within(HOP.., 0);
+      channel.output[FRAME - HOP..].fill(0.0);
+      channel.input.truncate(available);
+      channel.input.drain(..whole.min(available));
+    }
+    self.last_step = Some(whole);
+    true
+  }
+}
+
+/// An angle brought back into -π..=π.
+fn wrap(angle: f32) -> f32 {
+  angle - TAU * ((angle + PI) / TAU).floor()
+}
+
+impl<S> Iterator for TimeStretch<S>
+where
+  S: Source<Item = f32>,
+{
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    loop {
+      if let Some(sample) = self.ready.pop_front() {
+        return Some(sample);
+      }
+      let rate = self.control.stretch();
+      if rate == 1.0 {
+        if !self.is_stretching() {
+          return self.inner.next();
+        }
+        self.flush();
+        continue;
+      }
+      if !self.step(rate) {
+        return None;
+      }
+    }
+  }
+}
+
+impl<S> Source for TimeStretch<S>
+where
+  S: Source<Item = f32>,
+{
+  #[inline]
+  fn current_frame_len(&self) -> Option<usize> {
+    None
+  }
+  #[inline]
+  fn channels(&self) -> u16 {
+    self.inner.channels()
+  }
+  #[inline]
+  fn sample_rate(&self) -> u32 {
+    self.inner.sample_rate()
+  }
+  #[inline]
+  fn total_duration(&self) -> Option<Duration> {
+    // Depends on a rate that can change at any time
+    None
+  }
+
+  fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+    self.inner.try_seek(position)?;
+    self.reset();
+    self.exhausted = false;
+    Ok(())
+  }
+}
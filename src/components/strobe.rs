@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+/// Highest a limited effect gets, out of 1.0.
+const MAX_LEVEL: f32 = 0.6;
+/// Most flashes a second the limiter lets through; the usual
+/// photosensitivity guidance is no more than three.
+const MAX_FLASHES_PER_SECOND: f32 = 3.0;
+/// Fastest a limited effect can rise or fall, per second. A full flash up
+/// to `MAX_LEVEL` and back down then takes at least a third of a second.
+const MAX_RATE: f32 = MAX_LEVEL * 2.0 * MAX_FLASHES_PER_SECOND;
+
+/// Keeps a beat-reactive effect (0.0..=1.0) from strobing: caps how bright
+/// it gets and how fast it can change, which also caps how often it can
+/// flash. Follows its input unchanged while off.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct StrobeLimiter {
+  level: f32,
+}
+
+impl StrobeLimiter {
+  /// Moves towards `target` over `elapsed`, returning the limited level.
+  pub fn update(&mut self, target: f32, elapsed: Duration, enabled: bool) -> f32 {
+    let target = target.clamp(0.0, 1.0);
+    self.level = if enabled {
+      let step = MAX_RATE * elapsed.as_secs_f32();
+      let target = target.min(MAX_LEVEL);
+      self.level + (target - self.level).clamp(-step, step)
+    } else {
+      target
+    };
+    self.level
+  }
+
+  pub fn level(&self) -> f32 {
+    self.level
+  }
+}
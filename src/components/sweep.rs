@@ -0,0 +1,88 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::f32::consts::TAU;
+
+/// Fade at either end of the sweep, in seconds, so it starts and stops without a click.
+const FADE_SECONDS: f32 = 0.01;
+
+/// Exponential sine sweep from `low` to `high` Hz over `seconds`, with the
+/// matching inverse filter: the sweep reversed and tilted down 6 dB per octave,
+/// so that convolving the two gives a single impulse.
+pub struct Sweep {
+  pub samples: Vec<f32>,
+  inverse: Vec<f32>,
+  /// Peak of `samples` convolved with `inverse`, so a bare wire measures as 1.0.
+  gain: f32,
+}
+
+impl Sweep {
+  pub fn new(low: f32, high: f32, seconds: f32, sample_rate: u32, amplitude: f32) -> Self {
+    let length = (seconds * sample_rate as f32) as usize;
+    let rate = (high / low).ln();
+    let fade = ((FADE_SECONDS * sample_rate as f32) as usize).max(1);
+
+    let samples: Vec<f32> = (0..length)
+      .map(|n| {
+        let t = n as f32 / sample_rate as f32;
+        let phase = TAU * low * seconds / rate * ((t / seconds * rate).exp() - 1.0);
+        let envelope = (n.min(length - 1 - n) as f32 / fade as f32).min(1.0);
+        amplitude * envelope * phase.sin()
+      })
+      .collect();
+
+    // The reversed sweep runs high to low, so the decay tames the low end
+    let inverse: Vec<f32> = samples
+      .iter()
+      .rev()
+      .enumerate()
+      .map(|(n, &sample)| sample * (-(n as f32 / length as f32) * rate).exp())
+      .collect();
+
+    let gain = samples.iter().zip(inverse.iter().rev()).map(|(a, b)| a * b).sum::<f32>();
+    Self { samples, inverse, gain }
+  }
+
+  /// Deconvolves a recording of the sweep into the impulse response of
+  /// whatever it passed through. The result starts `pre_samples` before the
+  /// direct sound and runs for `length` samples.
+  pub fn impulse_response(&self, recording: &[f32], pre_samples: usize, length: usize) -> Vec<f32> {
+    let size = (recording.len() + self.inverse.len()).next_power_of_two();
+    let mut planner = FftPlanner::new();
+    let forward = planner.plan_fft_forward(size);
+    let inverse = planner.plan_fft_inverse(size);
+
+    let spectrum = |signal: &[f32]| {
+      let mut buffer: Vec<Complex<f32>> = signal.iter().map(|&x| Complex::new(x, 0.0)).collect();
+      buffer.resize(size, Complex::new(0.0, 0.0));
+      forward.process(&mut buffer);
+      buffer
+    };
+    let mut product: Vec<Complex<f32>> =
+      spectrum(recording).iter().zip(spectrum(&self.inverse)).map(|(x, h)| x * h).collect();
+    inverse.process(&mut product);
+
+    // rustfft leaves the inverse unscaled
+    let scale = 1.0 / (size as f32 * self.gain.max(f32::MIN_POSITIVE));
+    let convolved: Vec<f32> = product.iter().map(|c| c.re * scale).collect();
+
+    // Harmonic distortion lands before the linear response, so the loudest
+    // peak is the direct sound
+    let peak = convolved
+      .iter()
+      .enumerate()
+      .max_by(|a, b| a.1.abs().total_cmp(&b.1.abs()))
+      .map_or(0, |(i, _)| i);
+    let start = peak.saturating_sub(pre_samples);
+    let end = (start + length).min(convolved.len());
+    convolved[start..end].to_vec()
+  }
+}
+
+/// Magnitude response of an impulse response in dB, one value per bin of an
+/// `fft_size` FFT up to Nyquist.
+pub fn frequency_response(impulse: &[f32], fft_size: usize) -> Vec<f32> {
+  let mut buffer: Vec<Complex<f32>> =
+    impulse.iter().take(fft_size).map(|&x| Complex::new(x, 0.0)).collect();
+  buffer.resize(fft_size, Complex::new(0.0, 0.0));
+  FftPlanner::new().plan_fft_forward(fft_size).process(&mut buffer);
+  buffer[..fft_size / 2].iter().map(|c| 20.0 * c.norm().max(1e-9).log10()).collect()
+}
@@ -1,26 +1,120 @@
-use std::sync::mpsc::Sender;
+use std::{
+  collections::VecDeque,
+  sync::{Arc, Mutex, mpsc::Sender},
+};
 
-use rodio::Source;
+use rodio::{Source, source::SeekError};
 
-use crate::BUFFER_SIZE;
+use crate::{analysis::BUFFER_SIZE, app::WAVEFORM_CAPACITY};
 
-/// A `Source` wrapper that forwards every sample to the sender in
-/// fixed‐size chunks, then plays the sample through unchanged.
+/// Frames gathered before they're added to the waveform, far fewer than a
+/// chunk so the time-domain views keep up with the audio.
+const WAVEFORM_BLOCK_FRAMES: usize = 256;
+
+/// Collects samples into fixed‐size chunks and hands each full chunk to the
+/// FFT thread.
+///
+/// The samples also go to a shared ring buffer of raw samples for the
+/// time-domain views, in whole frames and far more often than a chunk.
+pub struct Chunker {
+  buf: Vec<f32>,
+  sender: Sender<Vec<f32>>,
+  waveform: Option<Arc<Mutex<VecDeque<f32>>>>,
+  /// Interleaved channels, so the waveform only ever takes whole frames.
+  channels: usize,
+  /// Samples the waveform hasn't had yet.
+  unshown: Vec<f32>,
+  copy: Option<ChunkSlot>,
+}
+
+/// Optional second destination for chunks, filled in while something else
+/// (like a transfer-function measurement) wants a copy of the stream.
+pub type ChunkSlot = Arc<Mutex<Option<Sender<Vec<f32>>>>>;
+
+impl Chunker {
+  pub fn new(sender: Sender<Vec<f32>>) -> Self {
+    Chunker {
+      buf: Vec::with_capacity(BUFFER_SIZE),
+      sender,
+      waveform: None,
+      channels: 1,
+      unshown: Vec::new(),
+      copy: None,
+    }
+  }
+
+  /// Also feeds `waveform` with the stream, which has `channels` interleaved.
+  pub fn with_waveform(mut self, waveform: Arc<Mutex<VecDeque<f32>>>, channels: u16) -> Self {
+    self.waveform = Some(waveform);
+    self.channels = channels.max(1) as usize;
+    self.unshown = Vec::with_capacity(WAVEFORM_BLOCK_FRAMES * self.channels);
+    self
+  }
+
+  pub fn with_copy(mut self, slot: ChunkSlot) -> Self {
+    self.copy = Some(slot);
+    self
+  }
+
+  pub fn push(&mut self, sample: f32) {
+    self.buf.push(sample);
+    if self.waveform.is_some() {
+      self.unshown.push(sample);
+      if self.unshown.len() >= WAVEFORM_BLOCK_FRAMES * self.channels {
+        self.show();
+      }
+    }
+    if self.buf.len() >= BUFFER_SIZE {
+      if let Some(slot) = &self.copy
+        && let Ok(copy) = slot.lock()
+        && let Some(sender) = copy.as_ref()
+      {
+        let _ = sender.send(self.buf.clone());
+      }
+
+      // Send the chunk off to your FFT thread
+      let full = std::mem::take(&mut self.buf);
+      let _ = self.sender.send(full);
+      self.buf = Vec::with_capacity(BUFFER_SIZE);
+    }
+  }
+
+  /// Adds the whole frames taken in since the last call to the waveform,
+  /// keeping any part of a frame for next time. Capture calls this after
+  /// each buffer the device hands over.
+  pub fn show(&mut self) {
+    let Some(waveform) = &self.waveform else {
+      return;
+    };
+    let whole = self.unshown.len() / self.channels * self.channels;
+    if whole == 0 {
+      return;
+    }
+    if let Ok(mut ring) = waveform.lock() {
+      ring.extend(self.unshown.drain(..whole));
+      // Dropped a whole frame at a time, so the ring still ends on a frame
+      let excess = ring.len().saturating_sub(WAVEFORM_CAPACITY).next_multiple_of(self.channels);
+      ring.drain(..excess.min(ring.len()));
+    }
+  }
+}
+
+/// A `Source` wrapper that forwards every sample through a [`Chunker`], then
+/// plays the sample through unchanged.
 pub struct Tap<S>
 where
   S: Source<Item = f32>,
 {
   inner: S,
-  buf: Vec<f32>,
-  sender: Sender<Vec<f32>>,
+  chunker: Chunker,
 }
 
 impl<S> Tap<S>
 where
   S: Source<Item = f32>,
 {
-  pub fn new(source: S, sender: Sender<Vec<f32>>) -> Self {
-    Tap { inner: source, buf: Vec::with_capacity(BUFFER_SIZE), sender }
+  pub fn new(source: S, chunker: Chunker) -> Self {
+    Tap { inner: source, chunker }
   }
 }
 
@@ -33,13 +127,7 @@ where
   fn next(&mut self) -> Option<f32> {
     // Pull the next sample from the inner source
     if let Some(sample) = self.inner.next() {
-      self.buf.push(sample);
-      if self.buf.len() >= BUFFER_SIZE {
-        // Send the chunk off to your FFT thread
-        let full = std::mem::take(&mut self.buf);
-        let _ = self.sender.send(full);
-        self.buf = Vec::with_capacity(BUFFER_SIZE);
-      }
+      self.chunker.push(sample);
       Some(sample)
     } else {
       None
@@ -67,4 +155,8 @@ where
   fn total_duration(&self) -> Option<std::time::Duration> {
     self.inner.total_duration()
   }
+  #[inline]
+  fn try_seek(&mut self, position: std::time::Duration) -> Result<(), SeekError> {
+    self.inner.try_seek(position)
+  }
 }
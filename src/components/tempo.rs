@@ -0,0 +1,247 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// FFT size and hop of the onset envelope.
+const ONSET_FFT: usize = 1024;
+const ONSET_HOP: usize = 512;
+/// Tempo range searched; anything outside is taken as half or double time.
+const MIN_BPM: f32 = 70.0;
+const MAX_BPM: f32 = 180.0;
+/// Tempo the search leans towards when two octaves score alike, and how
+/// wide the lean is, in octaves.
+const PREFERRED_BPM: f32 = 120.0;
+const PREFERENCE_OCTAVES: f32 = 1.0;
+/// The coarse tempo is refined this far either side, in this step.
+const REFINE_SPAN_BPM: f32 = 2.0;
+const REFINE_STEP_BPM: f32 = 0.02;
+/// FFT size for the chroma, and the pitch range it's taken over.
+const CHROMA_FFT: usize = 8192;
+const CHROMA_MIN_HZ: f32 = 55.0;
+const CHROMA_MAX_HZ: f32 = 2000.0;
+
+/// Krumhansl–Kessler key profiles, from the tonic upwards.
+const MAJOR_PROFILE: [f32; 12] =
+  [6.35, 2.23, 3.48, 2.33, 4.38, 4.09, 2.52, 5.19, 2.39, 3.66, 2.29, 2.88];
+const MINOR_PROFILE: [f32; 12] =
+  [6.33, 2.68, 3.52, 5.38, 2.60, 3.53, 2.54, 4.75, 3.98, 2.69, 3.34, 3.17];
+const PITCH_NAMES: [&str; 12] = ["C", "C#", "D", "Eb", "E", "F", "F#", "G", "Ab", "A", "Bb", "B"];
+
+/// A key as a pitch class (0 is C) and mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MusicalKey {
+  pub tonic: u8,
+  pub minor: bool,
+}
+
+impl MusicalKey {
+  /// Position on the Camelot wheel, 1..=12; neighbours are a fifth apart.
+  pub fn camelot(self) -> u8 {
+    // Minor keys share a number with their relative major
+    let major_tonic = if self.minor { (self.tonic + 3) % 12 } else { self.tonic };
+    (7 * major_tonic + 7) % 12 + 1
+  }
+
+  /// Whether the keys mix without clashing: the same key, the relative
+  /// major or minor, or a fifth either way in the same mode.
+  pub fn is_compatible(self, other: MusicalKey) -> bool {
+    let (a, b) = (self.camelot(), other.camelot());
+    if self.minor != other.minor {
+      return a == b;
+    }
+    a == b || a % 12 + 1 == b || b % 12 + 1 == a
+  }
+}
+
+impl fmt::Display for MusicalKey {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mode = if self.minor { "minor" } else { "major" };
+    write!(f, "{} {}", PITCH_NAMES[self.tonic as usize % 12], mode)
+  }
+}
+
+/// Tempo and phase of a track's beat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tempo {
+  pub bpm: f32,
+  /// Seconds to the first beat; every other falls a whole period after it.
+  pub first_beat: f32,
+}
+
+/// One lag of an onset envelope's autocorrelation, from [`tempo_lags`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TempoLag {
+  /// In envelope values.
+  pub lag: usize,
+  /// Share of the envelope's energy the lag matches.
+  pub correlation: f32,
+  /// `correlation`, leaning towards [`PREFERRED_BPM`] so a tempo wins over
+  /// its half and double time.
+  pub score: f32,
+}
+
+/// Autocorrelation of an onset envelope with values `period` seconds apart,
+/// at every lag in [`MIN_BPM`]..=[`MAX_BPM`]. Empty when the envelope's too
+/// short to hold the slowest lag or has no variation at all.
+pub fn tempo_lags(envelope: &[f32], period: f32) -> Vec<TempoLag> {
+  if period <= 0.0 || envelope.is_empty() {
+    return Vec::new();
+  }
+  let mean = envelope.iter().sum::<f32>() / envelope.len() as f32;
+  let centred: Vec<f32> = envelope.iter().map(|value| value - mean).collect();
+  let energy: f32 = centred.iter().map(|value| value * value).sum();
+  if energy <= 0.0 {
+    return Vec::new();
+  }
+  let lag_of = |bpm: f32| 60.0 / (bpm * period);
+  let shortest = lag_of(MAX_BPM).floor().max(1.0) as usize;
+  let longest = (lag_of(MIN_BPM).ceil() as usize).min(centred.len() - 1);
+  (shortest..=longest)
+    .map(|lag| {
+      let correlation =
+        centred.iter().zip(&centred[lag..]).map(|(a, b)| a * b).sum::<f32>() / energy;
+      let octaves = (60.0 / (lag as f32 * period) / PREFERRED_BPM).log2() / PREFERENCE_OCTAVES;
+      TempoLag { lag, correlation, score: correlation * (-0.5 * octaves * octaves).exp() }
+    })
+    .collect()
+}
+
+/// Finds a steady beat in mono samples. `None` when there's no clear pulse.
+///
+/// The onset envelope (positive log-spectral flux) is autocorrelated over
+/// [`MIN_BPM`]..=[`MAX_BPM`] for a rough tempo, which is then refined along
+/// with the phase by laying a beat comb over the whole track, so the grid
+/// still lines up at the end of a long track.
+pub fn estimate_tempo(samples: &[f32], sample_rate: u32) -> Option<Tempo> {
+  let envelope = onset_envelope(samples);
+  let frame_rate = sample_rate as f32 / ONSET_HOP as f32;
+  let lag = |bpm: f32| 60.0 * frame_rate / bpm;
+  if envelope.len() < 2 * lag(MIN_BPM) as usize {
+    return None;
+  }
+
+  // Coarse tempo from the autocorrelation, leaning towards typical tempos
+  let coarse_lag = tempo_lags(&envelope, 1.0 / frame_rate)
+    .into_iter()
+    .max_by(|a, b| a.score.total_cmp(&b.score))?
+    .lag;
+  let coarse = 60.0 * frame_rate / coarse_lag as f32;
+
+  // Refine tempo and phase together against the whole envelope
+  let steps = (2.0 * REFINE_SPAN_BPM / REFINE_STEP_BPM) as usize;
+  let mut best = (0.0, coarse, 0.0);
+  for step in 0..=steps {
+    let bpm = coarse - REFINE_SPAN_BPM + step as f32 * REFINE_STEP_BPM;
+    let period = lag(bpm);
+    for offset in 0..period.ceil() as usize {
+      let score = comb(&envelope, offset as f32, period);
+      if score > best.0 {
+        best = (score, bpm, offset as f32);
+      }
+    }
+  }
+  let (score, bpm, offset) = best;
+  (score > 0.0).then(|| Tempo { bpm, first_beat: offset / frame_rate })
+}
+
+/// Mean envelope under a beat comb starting at `offset` frames.
+fn comb(envelope: &[f32], offset: f32, period: f32) -> f32 {
+  let mut sum = 0.0;
+  let mut count = 0;
+  let mut position = offset;
+  while (position as usize) < envelope.len() {
+    sum += envelope[(position.round() as usize).min(envelope.len() - 1)];
+    count += 1;
+    position += period;
+  }
+  sum / count.max(1) as f32
+}
+
+/// Positive log-spectral flux per hop, with its local mean taken off so
+/// only sudden changes stand out.
+fn onset_envelope(samples: &[f32]) -> Vec<f32> {
+  let fft = FftPlanner::new().plan_fft_forward(ONSET_FFT);
+  let window: Vec<f32> = (0..ONSET_FFT)
+    .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / ONSET_FFT as f32).cos())
+    .collect();
+
+  let mut previous = vec![0.0; ONSET_FFT / 2];
+  let mut flux = Vec::with_capacity(samples.len() / ONSET_HOP);
+  for start in (0..samples.len().saturating_sub(ONSET_FFT)).step_by(ONSET_HOP) {
+    let mut buffer: Vec<Complex<f32>> = samples[start..start + ONSET_FFT]
+      .iter()
+      .zip(&window)
+      .map(|(&x, &w)| Complex::new(x * w, 0.0))
+      .collect();
+    fft.process(&mut buffer);
+    let mut total = 0.0;
+    for (bin, before) in buffer[..ONSET_FFT / 2].iter().zip(previous.iter_mut()) {
+      let now = (1.0 + 100.0 * bin.norm()).ln();
+      total += (now - *before).max(0.0);
+      *before = now;
+    }
+    flux.push(total);
+  }
+
+  // About a third of a second of local mean
+  const MEAN_FRAMES: usize = 16;
+  (0..flux.len())
+    .map(|i| {
+      let window = &flux[i.saturating_sub(MEAN_FRAMES)..(i + MEAN_FRAMES + 1).min(flux.len())];
+      (flux[i] - window.iter().sum::<f32>() / window.len() as f32).max(0.0)
+    })
+    .collect()
+}
+
+/// Best-fitting key for mono samples, from their overall pitch-class
+/// profile against the Krumhansl–Kessler major and minor profiles.
+pub fn estimate_key(samples: &[f32], sample_rate: u32) -> MusicalKey {
+  let fft = FftPlanner::new().plan_fft_forward(CHROMA_FFT);
+  let bin_hz = sample_rate as f32 / CHROMA_FFT as f32;
+  // Pitch class of every bin in range, worked out once
+  let classes: Vec<Option<usize>> = (0..CHROMA_FFT / 2)
+    .map(|bin| {
+      let hz = bin as f32 * bin_hz;
+      (CHROMA_MIN_HZ..=CHROMA_MAX_HZ)
+        .contains(&hz)
+        .then(|| (12.0 * (hz / 440.0).log2() + 69.0).round() as usize % 12)
+    })
+    .collect();
+
+  let mut chroma = [0.0f32; 12];
+  for chunk in samples.chunks_exact(CHROMA_FFT) {
+    let mut buffer: Vec<Complex<f32>> = chunk.iter().map(|&x| Complex::new(x, 0.0)).collect();
+    fft.process(&mut buffer);
+    for (bin, class) in buffer.iter().zip(&classes) {
+      if let Some(class) = class {
+        chroma[*class] += bin.norm();
+      }
+    }
+  }
+
+  let mut best = (f32::MIN, MusicalKey { tonic: 0, minor: false });
+  for tonic in 0..12 {
+    for (profile, minor) in [(&MAJOR_PROFILE, false), (&MINOR_PROFILE, true)] {
+      let rotated: Vec<f32> = (0..12).map(|i| chroma[(tonic + i) % 12]).collect();
+      let score = correlation(&rotated, profile);
+      if score > best.0 {
+        best = (score, MusicalKey { tonic: tonic as u8, minor });
+      }
+    }
+  }
+  best.1
+}
+
+/// Pearson correlation of two equally long series.
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+  let count = a.len() as f32;
+  let (mean_a, mean_b) = (a.iter().sum::<f32>() / count, b.iter().sum::<f32>() / count);
+  let mut covariance = 0.0;
+  let (mut variance_a, mut variance_b) = (0.0, 0.0);
+  for (x, y) in a.iter().zip(b) {
+    covariance += (x - mean_a) * (y - mean_b);
+    variance_a += (x - mean_a).powi(2);
+    variance_b += (y - mean_b).powi(2);
+  }
+  covariance / (variance_a * variance_b).sqrt().max(f32::EPSILON)
+}
@@ -0,0 +1,527 @@
+use std::{
+  collections::{HashMap, VecDeque},
+  f32::consts::TAU,
+};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use iced::{
+  Point, Rectangle,
+  advanced::Shell,
+  event, mouse,
+  widget::shader::{self, Event, Viewport, wgpu},
+};
+
+use crate::{
+  app::{Message, display},
+  components::gradient::Gradient,
+  ui::scene::Pane,
+};
+
+/// Most spectrogram columns drawn as rows of the terrain, newest in front.
+pub const TERRAIN_DEPTH: usize = 64;
+/// Radians per second the camera swings around the terrain at, at full energy.
+const ORBIT_SPEED: f32 = 0.3;
+/// Share of the orbit speed kept in silence, so it never quite stops.
+const IDLE_ORBIT: f32 = 0.2;
+/// How much closer the camera comes at full energy, as a share of its distance.
+const ZOOM_DEPTH: f32 = 0.35;
+/// Seconds the automatic zoom takes to settle on a new distance.
+const ZOOM_EASE: f32 = 2.0;
+const MIN_DISTANCE: f32 = 0.4;
+const MAX_DISTANCE: f32 = 2.0;
+/// Radians the camera swings per pixel dragged.
+const DRAG_SPEED: f32 = 0.01;
+/// Share of the distance one notch of the wheel zooms by.
+const WHEEL_ZOOM: f32 = 0.1;
+/// Height of a full-level bar, in bar widths.
+const MAX_HEIGHT: f32 = 24.0;
+/// Gap left between neighbouring bars, as a share of a bar's slot.
+const BAR_GAP: f32 = 0.15;
+const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+const SHADER: &str = include_str!("terrain.wgsl");
+
+/// Where the terrain is seen from. It orbits by itself, faster and closer
+/// the more energy there is, until it's dragged or zoomed by hand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+  /// Where the camera is on its orbit, in radians.
+  pub angle: f32,
+  /// Distance from the terrain, as a share of the default.
+  pub distance: f32,
+  /// Moved by hand, which holds it still until it's reset.
+  pub manual: bool,
+}
+
+/// A change to the camera made with the mouse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CameraMove {
+  /// Swing around by this many radians.
+  Orbit(f32),
+  /// Scale the distance by this much.
+  Zoom(f32),
+}
+
+impl Camera {
+  /// Moves the camera on by `elapsed` seconds at `energy` in 0.0..=1.0,
+  /// unless it's been moved by hand.
+  pub fn follow(&mut self, energy: f32, elapsed: f32) {
+    if self.manual {
+      return;
+    }
+    let energy = energy.clamp(0.0, 1.0);
+    self.angle += ORBIT_SPEED * (IDLE_ORBIT + (1.0 - IDLE_ORBIT) * energy) * elapsed;
+    let target = 1.0 - ZOOM_DEPTH * energy;
+    self.distance += (target - self.distance) * (elapsed / ZOOM_EASE).min(1.0);
+  }
+
+  pub fn apply(&mut self, movement: CameraMove) {
+    match movement {
+      CameraMove::Orbit(radians) => self.angle += radians,
+      CameraMove::Zoom(factor) => {
+        self.distance = (self.distance * factor).clamp(MIN_DISTANCE, MAX_DISTANCE);
+      }
+    }
+    self.manual = true;
+  }
+}
+
+impl Default for Camera {
+  fn default() -> Self {
+    Self { angle: 0.0, distance: 1.0, manual: false }
+  }
+}
+
+/// The spectrogram history as extruded 3D bars, one row per column and
+/// fading into the distance, drawn on the GPU with a camera swinging around
+/// it. Dragging swings the camera and the wheel zooms it.
+///
+/// Only the GPU renderer can draw it; the software renderer and off-screen
+/// exports show the flat bars instead.
+pub struct Terrain<'a> {
+  /// Normalised levels, oldest first, as kept for the spectrogram.
+  pub columns: &'a VecDeque<Vec<f32>>,
+  pub gradient: Gradient,
+  pub camera: Camera,
+  pub muted: bool,
+  /// Which pane it fills, which keeps its own buffers on the GPU.
+  pub pane: Pane,
+}
+
+impl<'a> shader::Program<Message> for Terrain<'a> {
+  /// Where the mouse was last seen while dragging.
+  type State = Option<Point>;
+  type Primitive = TerrainPrimitive;
+
+  fn update(
+    &self,
+    state: &mut Self::State,
+    event: Event,
+    bounds: Rectangle,
+    cursor: mouse::Cursor,
+    _shell: &mut Shell<'_, Message>,
+  ) -> (event::Status, Option<Message>) {
+    let Event::Mouse(event) = event else {
+      return (event::Status::Ignored, None);
+    };
+    let movement = match event {
+      mouse::Event::ButtonPressed(mouse::Button::Left) => {
+        let Some(at) = cursor.position_over(bounds) else {
+          return (event::Status::Ignored, None);
+        };
+        *state = Some(at);
+        return (event::Status::Captured, None);
+      }
+      mouse::Event::ButtonReleased(mouse::Button::Left) if state.is_some() => {
+        *state = None;
+        return (event::Status::Captured, None);
+      }
+      mouse::Event::CursorMoved { position } => {
+        let Some(last) = state.as_mut() else {
+          return (event::Status::Ignored, None);
+        };
+        let dx = position.x - last.x;
+        *last = position;
+        CameraMove::Orbit(dx * DRAG_SPEED)
+      }
+      mouse::Event::WheelScrolled { delta } if cursor.is_over(bounds) => {
+        let notches = match delta {
+          mouse::ScrollDelta::Lines { y, .. } => y,
+          mouse::ScrollDelta::Pixels { y, .. } => y / 40.0,
+        };
+        CameraMove::Zoom(1.0 - notches * WHEEL_ZOOM)
+      }
+      _ => return (event::Status::Ignored, None),
+    };
+    (event::Status::Captured, Some(Message::Display(display::Message::MoveCamera(movement))))
+  }
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    _cursor: mouse::Cursor,
+    bounds: Rectangle,
+  ) -> Self::Primitive {
+    let rows: Vec<&Vec<f32>> = self.columns.iter().rev().take(TERRAIN_DEPTH).collect();
+    let bands = rows.first().map_or(0, |row| row.len());
+
+    let mut instances = Vec::with_capacity(rows.len() * bands);
+    for (depth, row) in rows.iter().enumerate() {
+      // Older rows recede and fade towards the background
+      let fade = 1.0 - depth as f32 / TERRAIN_DEPTH as f32;
+      for (band, &level) in row.iter().enumerate() {
+        let level = level.clamp(0.0, 1.0);
+        let color = if self.muted {
+          iced::Color::from_rgb(0.4, 0.4, 0.4)
+        } else {
+          self.gradient.color(level)
+        };
+        instances.push(Instance {
+          placement: [
+            band as f32 - bands as f32 / 2.0,
+            depth as f32,
+            level * MAX_HEIGHT + 0.05,
+            1.0 - BAR_GAP,
+          ],
+          color: [color.r * fade, color.g * fade, color.b * fade, 1.0],
+        });
+      }
+    }
+
+    // Swing around the front of the terrain, looking down on it from above
+    let size = bands.max(TERRAIN_DEPTH) as f32;
+    let centre = Vec3::new(0.0, 0.0, TERRAIN_DEPTH as f32 / 3.0);
+    let radius = size * 0.8 * self.camera.distance;
+    let angle = self.camera.angle;
+    let eye = centre
+      + Vec3::new(
+        radius * angle.sin(),
+        size * 0.45 * self.camera.distance,
+        -radius * angle.cos().abs().max(0.3),
+      );
+    let view = Mat4::look_at_rh(eye, centre, Vec3::Y);
+    let aspect = bounds.width / bounds.height.max(1.0);
+    let projection = Mat4::perspective_rh(TAU / 8.0, aspect, 0.5, size * 4.0);
+
+    TerrainPrimitive {
+      pane: self.pane,
+      instances,
+      uniforms: Uniforms {
+        view_projection: (projection * view).to_cols_array_2d(),
+        light: [0.4, 1.0, -0.6, 0.0],
+      },
+    }
+  }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Vertex {
+  position: [f32; 3],
+  normal: [f32; 3],
+}
+
+/// One bar: x, z, height and width, then its colour.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Instance {
+  placement: [f32; 4],
+  color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct Uniforms {
+  view_projection: [[f32; 4]; 4],
+  /// Direction light comes from; w is unused.
+  light: [f32; 4],
+}
+
+#[derive(Debug)]
+pub struct TerrainPrimitive {
+  pane: Pane,
+  instances: Vec<Instance>,
+  uniforms: Uniforms,
+}
+
+impl TerrainPrimitive {
+  /// The pane whose buffers it's prepared into.
+  pub fn pane(&self) -> Pane {
+    self.pane
+  }
+}
+
+impl shader::Primitive for TerrainPrimitive {
+  fn prepare(
+    &self,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    format: wgpu::TextureFormat,
+    storage: &mut shader::Storage,
+    bounds: &Rectangle,
+    viewport: &Viewport,
+  ) {
+    if !storage.has::<Pipeline>() {
+      storage.store(Pipeline::new(device, format));
+    }
+    let pipeline = storage.get_mut::<Pipeline>().expect("stored above");
+    pipeline.prepare(device, queue, self, *bounds * viewport.scale_factor() as f32, viewport);
+  }
+
+  fn render(
+    &self,
+    encoder: &mut wgpu::CommandEncoder,
+    storage: &shader::Storage,
+    target: &wgpu::TextureView,
+    clip_bounds: &Rectangle<u32>,
+  ) {
+    if let Some(pipeline) = storage.get::<Pipeline>() {
+      pipeline.render(encoder, target, *clip_bounds, self);
+    }
+  }
+}
+
+/// The GPU side, made once and kept in the shader storage.
+struct Pipeline {
+  pipeline: wgpu::RenderPipeline,
+  bind_group_layout: wgpu::BindGroupLayout,
+  cube: wgpu::Buffer,
+  /// Cleared at the start of every pass, so the panes can share it.
+  depth: wgpu::TextureView,
+  depth_size: (u32, u32),
+  /// Every primitive is prepared before any is rendered, so each pane
+  /// needs buffers of its own or they'd all draw the last one prepared.
+  panes: HashMap<Pane, Buffers>,
+}
+
+/// What one pane draws with.
+struct Buffers {
+  instances: wgpu::Buffer,
+  instance_capacity: usize,
+  uniforms: wgpu::Buffer,
+  bind_group: wgpu::BindGroup,
+  /// Where the widget is on the target, in physical pixels.
+  bounds: Rectangle,
+}
+
+impl Pipeline {
+  fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+    use wgpu::util::DeviceExt;
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+      label: Some("terrain shader"),
+      source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+    });
+    let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+      label: Some("terrain bind group layout"),
+      entries: &[wgpu::BindGroupLayoutEntry {
+        binding: 0,
+        visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+        ty: wgpu::BindingType::Buffer {
+          ty: wgpu::BufferBindingType::Uniform,
+          has_dynamic_offset: false,
+          min_binding_size: None,
+        },
+        count: None,
+      }],
+    });
+    let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+      label: Some("terrain pipeline layout"),
+      bind_group_layouts: &[&bind_group_layout],
+      push_constant_ranges: &[],
+    });
+
+    let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+      label: Some("terrain pipeline"),
+      layout: Some(&layout),
+      vertex: wgpu::VertexState {
+        module: &shader,
+        entry_point: "vs_main",
+        buffers: &[
+          wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Vertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+          },
+          wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Instance>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &wgpu::vertex_attr_array![2 => Float32x4, 3 => Float32x4],
+          },
+        ],
+      },
+      fragment: Some(wgpu::FragmentState {
+        module: &shader,
+        entry_point: "fs_main",
+        targets: &[Some(wgpu::ColorTargetState {
+          format,
+          blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+          write_mask: wgpu::ColorWrites::ALL,
+        })],
+      }),
+      primitive: wgpu::PrimitiveState { cull_mode: Some(wgpu::Face::Back), ..Default::default() },
+      depth_stencil: Some(wgpu::DepthStencilState {
+        format: DEPTH_FORMAT,
+        depth_write_enabled: true,
+        depth_compare: wgpu::CompareFunction::Less,
+        stencil: wgpu::StencilState::default(),
+        bias: wgpu::DepthBiasState::default(),
+      }),
+      multisample: wgpu::MultisampleState::default(),
+      multiview: None,
+    });
+
+    let cube = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+      label: Some("terrain cube"),
+      contents: bytemuck::cast_slice(&cube_vertices()),
+      usage: wgpu::BufferUsages::VERTEX,
+    });
+    let (depth, depth_size) = (depth_view(device, 1, 1), (1, 1));
+
+    Self { pipeline, bind_group_layout, cube, depth, depth_size, panes: HashMap::new() }
+  }
+
+  fn prepare(
+    &mut self,
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    primitive: &TerrainPrimitive,
+    bounds: Rectangle,
+    viewport: &Viewport,
+  ) {
+    let size = viewport.physical_size();
+    if self.depth_size != (size.width, size.height) {
+      self.depth = depth_view(device, size.width, size.height);
+      self.depth_size = (size.width, size.height);
+    }
+    let buffers = self
+      .panes
+      .entry(primitive.pane)
+      .or_insert_with(|| Buffers::new(device, &self.bind_group_layout));
+    if primitive.instances.len() > buffers.instance_capacity {
+      buffers.instance_capacity = primitive.instances.len().next_power_of_two();
+      buffers.instances = instance_buffer(device, buffers.instance_capacity);
+    }
+    queue.write_buffer(&buffers.uniforms, 0, bytemuck::bytes_of(&primitive.uniforms));
+    queue.write_buffer(&buffers.instances, 0, bytemuck::cast_slice(&primitive.instances));
+    buffers.bounds = bounds;
+  }
+
+  fn render(
+    &self,
+    encoder: &mut wgpu::CommandEncoder,
+    target: &wgpu::TextureView,
+    clip_bounds: Rectangle<u32>,
+    primitive: &TerrainPrimitive,
+  ) {
+    let Some(buffers) = self.panes.get(&primitive.pane) else {
+      return;
+    };
+    let instances = primitive.instances.len() as u32;
+    if instances == 0 || clip_bounds.width == 0 || clip_bounds.height == 0 {
+      return;
+    }
+    let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+      label: Some("terrain pass"),
+      color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+        view: target,
+        resolve_target: None,
+        ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
+      })],
+      depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+        view: &self.depth,
+        depth_ops: Some(wgpu::Operations {
+          load: wgpu::LoadOp::Clear(1.0),
+          store: wgpu::StoreOp::Discard,
+        }),
+        stencil_ops: None,
+      }),
+      timestamp_writes: None,
+      occlusion_query_set: None,
+    });
+
+    // The projection fills the widget; the scissor keeps it inside what's visible
+    let bounds = buffers.bounds;
+    pass.set_viewport(bounds.x, bounds.y, bounds.width, bounds.height, 0.0, 1.0);
+    pass.set_scissor_rect(clip_bounds.x, clip_bounds.y, clip_bounds.width, clip_bounds.height);
+    pass.set_pipeline(&self.pipeline);
+    pass.set_bind_group(0, &buffers.bind_group, &[]);
+    pass.set_vertex_buffer(0, self.cube.slice(..));
+    pass.set_vertex_buffer(1, buffers.instances.slice(..));
+    pass.draw(0..36, 0..instances);
+  }
+}
+
+impl Buffers {
+  fn new(device: &wgpu::Device, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+    let uniforms = device.create_buffer(&wgpu::BufferDescriptor {
+      label: Some("terrain uniforms"),
+      size: std::mem::size_of::<Uniforms>() as u64,
+      usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+      mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+      label: Some("terrain bind group"),
+      layout: bind_group_layout,
+      entries: &[wgpu::BindGroupEntry { binding: 0, resource: uniforms.as_entire_binding() }],
+    });
+    Self {
+      instances: instance_buffer(device, 1),
+      instance_capacity: 1,
+      uniforms,
+      bind_group,
+      bounds: Rectangle::default(),
+    }
+  }
+}
+
+fn instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+  device.create_buffer(&wgpu::BufferDescriptor {
+    label: Some("terrain instances"),
+    size: (capacity * std::mem::size_of::<Instance>()) as u64,
+    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+    mapped_at_creation: false,
+  })
+}
+
+fn depth_view(device: &wgpu::Device, width: u32, height: u32) -> wgpu::TextureView {
+  device
+    .create_texture(&wgpu::TextureDescriptor {
+      label: Some("terrain depth"),
+      size: wgpu::Extent3d { width: width.max(1), height: height.max(1), depth_or_array_layers: 1 },
+      mip_level_count: 1,
+      sample_count: 1,
+      dimension: wgpu::TextureDimension::D2,
+      format: DEPTH_FORMAT,
+      usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+      view_formats: &[],
+    })
+    .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// A unit cube standing on the origin, x and z centred, as 12 triangles
+/// wound counter-clockwise from outside.
+fn cube_vertices() -> Vec<Vertex> {
+  // Each face: its normal, then two axes spanning it with their cross
+  // product along the normal
+  let faces: [([f32; 3], [f32; 3], [f32; 3]); 6] = [
+    ([1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]),
+    ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+    ([0.0, 1.0, 0.0], [0.0, 0.0, 1.0], [1.0, 0.0, 0.0]),
+    ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+    ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ([0.0, 0.0, -1.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]),
+  ];
+
+  let mut vertices = Vec::with_capacity(36);
+  for (normal, u, v) in faces {
+    let corner = |a: f32, b: f32| {
+      let point = Vec3::from(normal) * 0.5 + Vec3::from(u) * a + Vec3::from(v) * b;
+      // Centred on x/z, standing on y = 0
+      Vertex { position: [point.x, point.y + 0.5, point.z], normal }
+    };
+    let (a, b, c, d) = (corner(-0.5, -0.5), corner(0.5, -0.5), corner(0.5, 0.5), corner(-0.5, 0.5));
+    vertices.extend([a, b, c, a, c, d]);
+  }
+  vertices
+}
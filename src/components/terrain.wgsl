@@ -0,0 +1,41 @@
+struct Uniforms {
+  view_projection: mat4x4<f32>,
+  light: vec4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> uniforms: Uniforms;
+
+struct VertexInput {
+  @location(0) position: vec3<f32>,
+  @location(1) normal: vec3<f32>,
+  // x, z, height and width of the bar
+  @location(2) placement: vec4<f32>,
+  @location(3) color: vec4<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) normal: vec3<f32>,
+  @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+  let world = vec3<f32>(
+    input.placement.x + input.position.x * input.placement.w,
+    input.position.y * input.placement.z,
+    input.placement.y + input.position.z * input.placement.w,
+  );
+
+  var output: VertexOutput;
+  output.clip_position = uniforms.view_projection * vec4<f32>(world, 1.0);
+  output.normal = input.normal;
+  output.color = input.color;
+  return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+  let diffuse = max(dot(normalize(input.normal), normalize(uniforms.light.xyz)), 0.0);
+  return vec4<f32>(input.color.rgb * (0.35 + 0.65 * diffuse), input.color.a);
+}
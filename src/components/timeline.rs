@@ -0,0 +1,138 @@
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  mouse::{self, Cursor},
+  widget::canvas::{self, Event, Geometry, Path, Stroke, event},
+};
+use std::{ops::Range, time::Duration};
+
+use crate::{
+  app::Message,
+  components::{gradient::Gradient, sections::Section},
+  playback,
+};
+
+/// Seek bar with the track's vocal regions shaded in, a marker where each
+/// section starts, the clip outlined and the A/B loop shaded; click to seek.
+pub struct TimelineCanvas<'a> {
+  /// Seconds into the track.
+  pub position: f32,
+  /// Length of the track, in seconds.
+  pub duration: f32,
+  pub vocals: &'a [Range<f32>],
+  pub sections: &'a [Section],
+  /// The span between the clip markers, in seconds.
+  pub clip: Option<Range<f32>>,
+  /// A/B loop points, in seconds.
+  pub loop_start: Option<f32>,
+  pub loop_end: Option<f32>,
+  pub gradient: Gradient,
+}
+
+impl<'a> canvas::Program<Message> for TimelineCanvas<'a> {
+  type State = ();
+
+  fn update(
+    &self,
+    _state: &mut Self::State,
+    event: Event,
+    bounds: Rectangle,
+    cursor: Cursor,
+  ) -> (event::Status, Option<Message>) {
+    let Event::Mouse(mouse::Event::ButtonPressed(mouse::Button::Left)) = event else {
+      return (event::Status::Ignored, None);
+    };
+    let Some(at) = cursor.position_in(bounds) else {
+      return (event::Status::Ignored, None);
+    };
+    let seconds = (at.x / bounds.width).clamp(0.0, 1.0) * self.duration;
+    let seek = playback::Message::Seek(Duration::from_secs_f32(seconds));
+    (event::Status::Captured, Some(Message::Playback(seek)))
+  }
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: Cursor,
+  ) -> Vec<Geometry> {
+    // The playhead moves every frame, so there's nothing worth caching
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let x_at =
+      |seconds: f32| (seconds / self.duration.max(f32::EPSILON)).clamp(0.0, 1.0) * bounds.width;
+
+    frame.fill_rectangle(Point::ORIGIN, bounds.size(), Color::from_rgba(0.5, 0.5, 0.5, 0.2));
+
+    let vocal = self.gradient.color(0.75);
+    for region in self.vocals {
+      let start = x_at(region.start);
+      frame.fill_rectangle(
+        Point::new(start, 0.0),
+        Size::new(x_at(region.end) - start, bounds.height),
+        Color { a: 0.45, ..vocal },
+      );
+    }
+
+    // The first section starts at 0, so its marker would only be a label
+    for section in self.sections {
+      let x = x_at(section.start);
+      if x > 0.0 {
+        frame.stroke(
+          &Path::line(Point::new(x, 0.0), Point::new(x, bounds.height)),
+          Stroke::default().with_color(Color::from_rgba(1.0, 1.0, 1.0, 0.6)).with_width(1.0),
+        );
+      }
+      frame.fill_text(canvas::Text {
+        content: section.kind.to_string(),
+        position: Point::new(x + 3.0, 1.0),
+        color: Color::from_rgba(1.0, 1.0, 1.0, 0.8),
+        size: 10.0.into(),
+        ..canvas::Text::default()
+      });
+    }
+
+    if let Some(clip) = &self.clip {
+      let start = x_at(clip.start);
+      frame.stroke(
+        &Path::rectangle(
+          Point::new(start, 1.0),
+          Size::new(x_at(clip.end) - start, bounds.height - 2.0),
+        ),
+        Stroke::default().with_color(Color::from_rgb(1.0, 0.8, 0.2)).with_width(2.0),
+      );
+    }
+
+    let loop_color = Color::from_rgb(0.3, 0.9, 1.0);
+    for point in [self.loop_start, self.loop_end].into_iter().flatten() {
+      let x = x_at(point);
+      frame.stroke(
+        &Path::line(Point::new(x, 0.0), Point::new(x, bounds.height)),
+        Stroke::default().with_color(loop_color).with_width(2.0),
+      );
+    }
+    if let (Some(start), Some(end)) = (self.loop_start, self.loop_end) {
+      let start = x_at(start);
+      frame.fill_rectangle(
+        Point::new(start, 0.0),
+        Size::new(x_at(end) - start, bounds.height),
+        Color { a: 0.25, ..loop_color },
+      );
+    }
+
+    // Played part as a thin strip along the bottom, so the shading stays visible
+    let played = x_at(self.position);
+    let strip = bounds.height * 0.25;
+    frame.fill_rectangle(
+      Point::new(0.0, bounds.height - strip),
+      Size::new(played, strip),
+      self.gradient.color(0.3),
+    );
+    frame.stroke(
+      &Path::line(Point::new(played, 0.0), Point::new(played, bounds.height)),
+      Stroke::default().with_color(Color::WHITE).with_width(2.0),
+    );
+
+    vec![frame.into_geometry()]
+  }
+}
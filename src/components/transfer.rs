@@ -0,0 +1,231 @@
+use iced::{
+  Color, Point, Rectangle, Theme,
+  widget::canvas::{self, Frame, Geometry, Path, Stroke, gradient::Linear},
+};
+use rustfft::num_complex::Complex;
+
+use crate::{
+  app::Message,
+  components::{binning::FrequencyScale, delay::Delay, gradient::Gradient},
+};
+
+/// Weight each new block gets in the running averages; lower is steadier.
+const AVERAGING: f32 = 0.1;
+/// The magnitude plot spans ± this many dB around unity gain.
+const MAGNITUDE_RANGE_DB: f32 = 30.0;
+
+/// Running cross- and auto-spectra of a reference signal and the same signal
+/// measured after the system under test, from which the transfer function and
+/// coherence are read.
+#[derive(Debug, Clone)]
+pub struct TransferFunction {
+  cross: Vec<Complex<f32>>,
+  reference_power: Vec<f32>,
+  measurement_power: Vec<f32>,
+  blocks: u64,
+}
+
+impl TransferFunction {
+  pub fn new(bins: usize) -> Self {
+    Self {
+      cross: vec![Complex::new(0.0, 0.0); bins],
+      reference_power: vec![0.0; bins],
+      measurement_power: vec![0.0; bins],
+      blocks: 0,
+    }
+  }
+
+  /// Folds in one pair of spectra (the lower half of each FFT).
+  pub fn add(&mut self, reference: &[Complex<f32>], measurement: &[Complex<f32>]) {
+    // Start from the first block rather than fading in from zero
+    let weight = if self.blocks == 0 { 1.0 } else { AVERAGING };
+    for (i, (x, y)) in reference.iter().zip(measurement).enumerate().take(self.cross.len()) {
+      self.cross[i] += (x.conj() * y - self.cross[i]) * weight;
+      self.reference_power[i] += (x.norm_sqr() - self.reference_power[i]) * weight;
+      self.measurement_power[i] += (y.norm_sqr() - self.measurement_power[i]) * weight;
+    }
+    self.blocks += 1;
+  }
+
+  pub fn blocks(&self) -> u64 {
+    self.blocks
+  }
+
+  /// Gain of the measured signal over the reference, in dB per bin.
+  pub fn magnitude(&self) -> Vec<f32> {
+    self
+      .cross
+      .iter()
+      .zip(&self.reference_power)
+      .map(|(cross, &power)| 20.0 * (cross.norm() / power.max(f32::MIN_POSITIVE)).log10())
+      .collect()
+  }
+
+  /// Phase of the measured signal relative to the reference, in radians per bin.
+  pub fn phase(&self) -> Vec<f32> {
+    self.cross.iter().map(|cross| cross.arg()).collect()
+  }
+
+  /// How much of the measured signal is explained by the reference, 0.0..=1.0
+  /// per bin. Noise, reverb and misalignment all pull it down.
+  pub fn coherence(&self) -> Vec<f32> {
+    self
+      .cross
+      .iter()
+      .zip(self.reference_power.iter().zip(&self.measurement_power))
+      .map(|(cross, (&x, &y))| (cross.norm_sqr() / (x * y).max(f32::MIN_POSITIVE)).min(1.0))
+      .collect()
+  }
+}
+
+/// Magnitude (top) and phase (bottom) of a transfer function across
+/// frequency, with the coherence drawn over the magnitude.
+pub struct TransferCanvas<'a> {
+  pub magnitude: Vec<f32>,
+  pub phase: Vec<f32>,
+  pub coherence: Vec<f32>,
+  /// Blocks averaged so far.
+  pub blocks: u64,
+  pub delay: Option<Delay>,
+  pub scale: FrequencyScale,
+  pub sample_rate: u32,
+  pub gradient: Gradient,
+  pub cache: &'a canvas::Cache,
+}
+
+impl<'a> TransferCanvas<'a> {
+  /// Bins that land inside the visible frequency range, as (0.0..=1.0, value).
+  fn points(&self, values: &[f32]) -> Vec<(f32, f32)> {
+    let bin_hz = self.sample_rate as f32 / (2.0 * values.len().max(1) as f32);
+    values
+      .iter()
+      .enumerate()
+      .map(|(i, &value)| (self.scale.position(i as f32 * bin_hz, self.sample_rate), value))
+      .filter(|(x, _)| (0.0..=1.0).contains(x))
+      .collect()
+  }
+}
+
+fn line(points: &[(f32, f32)], width: f32, to_y: impl Fn(f32) -> f32) -> Path {
+  Path::new(|builder| {
+    for (i, &(x, value)) in points.iter().enumerate() {
+      let point = Point::new(x * width, to_y(value));
+      if i == 0 {
+        builder.move_to(point);
+      } else {
+        builder.line_to(point);
+      }
+    }
+  })
+}
+
+fn label(frame: &mut Frame, content: String, position: Point, color: Color) {
+  frame.fill_text(canvas::Text {
+    content,
+    position,
+    color,
+    size: 12.0.into(),
+    ..canvas::Text::default()
+  });
+}
+
+impl<'a> canvas::Program<Message> for TransferCanvas<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
+      const GAP: f32 = 10.0;
+      let magnitude_height = (bounds.height - GAP) * 0.6;
+      let phase_top = magnitude_height + GAP;
+      let phase_height = bounds.height - phase_top;
+
+      let label_color = Color::from_rgb(0.7, 0.7, 0.7);
+      let axis = Stroke::default().with_color(Color { a: 0.25, ..label_color }).with_width(1.0);
+
+      let magnitude_y = |db: f32| {
+        let db = db.clamp(-MAGNITUDE_RANGE_DB, MAGNITUDE_RANGE_DB);
+        magnitude_height / 2.0 - db / MAGNITUDE_RANGE_DB * magnitude_height / 2.0
+      };
+      let coherence_y = |value: f32| magnitude_height - value * magnitude_height;
+      let phase_y = |radians: f32| {
+        phase_top + phase_height / 2.0 - radians / std::f32::consts::PI * phase_height / 2.0
+      };
+
+      // Unity gain and zero phase
+      frame.stroke(
+        &Path::line(Point::new(0.0, magnitude_y(0.0)), Point::new(bounds.width, magnitude_y(0.0))),
+        axis,
+      );
+      frame.stroke(
+        &Path::line(Point::new(0.0, phase_y(0.0)), Point::new(bounds.width, phase_y(0.0))),
+        axis,
+      );
+      label(frame, format!("+{:.0} dB", MAGNITUDE_RANGE_DB), Point::new(4.0, 0.0), label_color);
+      label(
+        frame,
+        format!("-{:.0} dB", MAGNITUDE_RANGE_DB),
+        Point::new(4.0, magnitude_height - 14.0),
+        label_color,
+      );
+      label(frame, "+180°".to_string(), Point::new(4.0, phase_top), label_color);
+      label(frame, "-180°".to_string(), Point::new(4.0, bounds.height - 14.0), label_color);
+      let delay = match self.delay {
+        Some(delay) => format!("delay {:.2} ms ({:.2} m)", delay.millis(), delay.metres()),
+        None => "delay unknown".to_string(),
+      };
+      label(
+        frame,
+        format!("{} blocks averaged, {}", self.blocks, delay),
+        Point::new(bounds.width / 2.0 - 100.0, 0.0),
+        label_color,
+      );
+
+      let coherence = self.points(&self.coherence);
+      if coherence.len() > 1 {
+        frame.stroke(
+          &line(&coherence, bounds.width, coherence_y),
+          Stroke::default().with_color(Color { a: 0.5, ..label_color }).with_width(1.0),
+        );
+        label(
+          frame,
+          "Coherence".to_string(),
+          Point::new(bounds.width - 70.0, coherence_y(1.0)),
+          label_color,
+        );
+      }
+
+      // Low to high frequencies run through the theme
+      let style = canvas::Style::Gradient(canvas::Gradient::Linear(
+        Linear::new(Point::ORIGIN, Point::new(bounds.width, 0.0))
+          .add_stop(0.0, self.gradient.color(0.0))
+          .add_stop(0.5, self.gradient.color(0.5))
+          .add_stop(1.0, self.gradient.color(1.0)),
+      ));
+
+      let magnitude = self.points(&self.magnitude);
+      if magnitude.len() > 1 {
+        frame.stroke(
+          &line(&magnitude, bounds.width, magnitude_y),
+          Stroke { style, width: 2.0, ..Stroke::default() },
+        );
+      }
+
+      let phase = self.points(&self.phase);
+      if phase.len() > 1 {
+        frame.stroke(
+          &line(&phase, bounds.width, phase_y),
+          Stroke { style, width: 1.5, ..Stroke::default() },
+        );
+      }
+    });
+
+    vec![geometry]
+  }
+}
@@ -1,13 +1,127 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
 use iced::{
-  Color, Point, Rectangle, Theme,
+  Color, Point, Rectangle, Theme, Vector,
   widget::canvas::{self, Geometry, Path},
 };
 
-use crate::{DEFAULT_BAR_WIDTH, DEFAULT_NUM_BARS, DEFAULT_STARTING_ANGLE, MIN_BAR_HEIGHT, Message};
+use crate::{
+  app::{MIN_BAR_HEIGHT, Message},
+  components::{
+    gradient::Gradient,
+    layout::{LayoutKind, Placement, RingSettings, Shape},
+    noise::Perlin,
+  },
+};
+
+/// Largest height offset, in pixels, that full-intensity jitter adds to a bar.
+const JITTER_HEIGHT: f32 = 24.0;
+/// How much a full beat pulse grows the layout.
+pub const PULSE_SCALE: f32 = 0.08;
+/// How far towards white a full beat pulse flashes the bars.
+const PULSE_FLASH: f32 = 0.35;
+/// Thickness of the peak-hold markers, in pixels.
+const PEAK_THICKNESS: f32 = 3.0;
+
+/// What the main canvas shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum VisualStyle {
+  #[default]
+  Bars,
+  Waveform,
+  Spectrogram,
+  /// Distribution of sample levels over the track so far.
+  Histogram,
+  Phase,
+  GroupDelay,
+  /// Mic against the playing audio, while a measurement runs.
+  Transfer,
+  /// Particles flung out from the centre by the bass.
+  Particles,
+  /// The spectrogram history as 3D bars, drawn on the GPU.
+  Terrain,
+  /// Energy per pitch class as a colour wheel, for following the harmony.
+  Chroma,
+  /// Drawn by a plugin loaded from the plugin directory.
+  Plugin,
+}
+
+impl VisualStyle {
+  pub const ALL: [VisualStyle; 11] = [
+    VisualStyle::Bars,
+    VisualStyle::Waveform,
+    VisualStyle::Spectrogram,
+    VisualStyle::Histogram,
+    VisualStyle::Phase,
+    VisualStyle::GroupDelay,
+    VisualStyle::Transfer,
+    VisualStyle::Particles,
+    VisualStyle::Terrain,
+    VisualStyle::Chroma,
+    VisualStyle::Plugin,
+  ];
+}
+
+impl fmt::Display for VisualStyle {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      VisualStyle::Bars => "Bars",
+      VisualStyle::Waveform => "Waveform",
+      VisualStyle::Spectrogram => "Spectrogram",
+      VisualStyle::Histogram => "Histogram",
+      VisualStyle::Phase => "Phase",
+      VisualStyle::GroupDelay => "Group delay",
+      VisualStyle::Transfer => "Transfer function",
+      VisualStyle::Particles => "Particles",
+      VisualStyle::Terrain => "3D terrain",
+      VisualStyle::Chroma => "Chromagram",
+      VisualStyle::Plugin => "Plugin",
+    })
+  }
+}
 
 pub struct VisualizerCanvas<'a> {
   pub frequency_data: &'a [f32],
+  /// Held peak of each bar, same length as `frequency_data`.
+  pub peak_data: &'a [f32],
   pub cache: &'a canvas::Cache,
+  pub layout: LayoutKind,
+  pub shape: Option<Shape<'a>>,
+  pub jitter: Option<Jitter<'a>>,
+  pub ring: RingSettings,
+  /// Seconds in, for the ring's spin.
+  pub time: f32,
+  pub gradient: Gradient,
+  /// Draws the bars greyed out while playback is muted.
+  pub muted: bool,
+  /// Beat pulse, 0.0..=1.0; grows the layout and flashes the bars.
+  pub pulse: f32,
+}
+
+/// Low-amplitude noise applied to bar heights and colours at draw time.
+pub struct Jitter<'a> {
+  pub noise: &'a Perlin,
+  /// 0.0 (off) to 1.0.
+  pub intensity: f32,
+  /// Seconds; advances the noise field.
+  pub time: f32,
+}
+
+/// Reduces `data` to `bars` values by taking the loudest bar of each group, so
+/// dropping bars on a small ring never hides a peak.
+pub fn resample_bars(data: &[f32], bars: usize) -> Vec<f32> {
+  if bars >= data.len() {
+    return data.to_vec();
+  }
+
+  (0..bars)
+    .map(|i| {
+      let start = i * data.len() / bars;
+      let end = ((i + 1) * data.len() / bars).max(start + 1);
+      data[start..end].iter().cloned().fold(MIN_BAR_HEIGHT, f32::max)
+    })
+    .collect()
 }
 
 impl<'a> canvas::Program<Message> for VisualizerCanvas<'a> {
@@ -22,48 +136,89 @@ impl<'a> canvas::Program<Message> for VisualizerCanvas<'a> {
     _cursor: iced::mouse::Cursor,
   ) -> Vec<Geometry> {
     let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
-      let center = Point::new(bounds.width * 0.5, bounds.height * 0.5);
-      let radius = (bounds.width * bounds.width + bounds.height * bounds.height).sqrt() / 8.0;
-      let angle_interval = 2.0 * std::f32::consts::PI / DEFAULT_NUM_BARS as f32;
-      let max_bar_height = bounds.width.min(bounds.height) / 2.0 - radius;
-
-      // Draw circular bars similar to the React version
-      for (i, &height) in self.frequency_data.iter().enumerate() {
-        // always draw every bar from the ring, capping at max_bar_height
-        let bar_height = height.min(max_bar_height);
-        let angle = (i as f32 * angle_interval) + DEFAULT_STARTING_ANGLE;
-
-        let inner_x = center.x + radius * angle.cos();
-        let inner_y = center.y + radius * angle.sin();
-        // outer is simply radius + bar_height
-        let outer_x = center.x + (radius + bar_height) * angle.cos();
-        let outer_y = center.y + (radius + bar_height) * angle.sin();
+      let placement = Placement::compute(
+        self.layout,
+        self.shape,
+        bounds,
+        self.frequency_data.len(),
+        self.ring,
+        self.time,
+      );
 
-        // Create a rectangular bar
-        let bar_path = Path::new(|builder| {
-          // Perpendicular angle for bar width (subtract 90 degrees like React)
-          let perpendicular_angle = angle - std::f32::consts::PI / 2.0;
-          let half_width = (DEFAULT_BAR_WIDTH * 1.2) / 2.0;
+      // Beats push every bar outward from the centre
+      let center = Point::new(bounds.width / 2.0, bounds.height / 2.0);
+      let grow = 1.0 + self.pulse * PULSE_SCALE;
+      let bars = self.ring.mirror.arrange(self.frequency_data, placement.anchors.len());
+      let peaks = self.ring.mirror.arrange(self.peak_data, placement.anchors.len());
+
+      // Draw bars along the layout, similar to the React version
+      for (i, ((anchor, &height), &peak)) in
+        placement.anchors.iter().zip(bars.iter()).zip(peaks.iter()).enumerate()
+      {
+        // Neighbouring bars sample nearby noise so the motion looks like a slow swell
+        let (height_noise, color_noise) = match &self.jitter {
+          Some(jitter) => (
+            jitter.noise.get(i as f32 * 0.15, jitter.time * 0.6) * jitter.intensity,
+            jitter.noise.get(i as f32 * 0.15 + 100.0, jitter.time * 0.4) * jitter.intensity,
+          ),
+          None => (0.0, 0.0),
+        };
+
+        // always draw every bar from the layout, scaled to the canvas and capped at max_bar_height
+        let bar_height = placement.bar_height(height + height_noise * JITTER_HEIGHT);
 
-          let dx = half_width * perpendicular_angle.cos();
-          let dy = half_width * perpendicular_angle.sin();
+        let inner = center + (anchor.position - center) * grow;
+        // outer is simply the anchor pushed out along its normal
+        let outer = inner + anchor.normal * bar_height;
 
-          builder.move_to(Point::new(inner_x - dx, inner_y - dy));
-          builder.line_to(Point::new(inner_x + dx, inner_y + dy));
-          builder.line_to(Point::new(outer_x + dx, outer_y + dy));
-          builder.line_to(Point::new(outer_x - dx, outer_y - dy));
+        // Perpendicular to the normal for bar width
+        let side = Vector::new(-anchor.normal.y, anchor.normal.x) * (placement.bar_width / 2.0);
+
+        // Create a rectangular bar
+        let bar_path = Path::new(|builder| {
+          builder.move_to(inner - side);
+          builder.line_to(inner + side);
+          builder.line_to(outer + side);
+          builder.line_to(outer - side);
           builder.close();
         });
 
-        // Color based on frequency intensity - more vibrant like the React version
-        let intensity = (bar_height - MIN_BAR_HEIGHT) / (max_bar_height - MIN_BAR_HEIGHT);
+        // Color based on frequency intensity
+        let intensity = (placement.intensity(bar_height) + color_noise * 0.2).clamp(0.0, 1.0);
+        let color = if self.muted {
+          let grey = 0.4 + intensity * 0.2;
+          Color::from_rgb(grey, grey, grey)
+        } else {
+          self.gradient.color(intensity)
+        };
+        // Flash towards white on beats
+        let flash = self.pulse * PULSE_FLASH;
         let color = Color::from_rgb(
-          0.9 + intensity * 0.1, // Higher base red for more magenta
-          0.3 + intensity * 0.4, // Lower green component
-          0.9 + intensity * 0.1, // Higher base blue for more magenta
+          color.r + (1.0 - color.r) * flash,
+          color.g + (1.0 - color.g) * flash,
+          color.b + (1.0 - color.b) * flash,
         );
 
         frame.fill(&bar_path, color);
+
+        // Peak marker: a thin cap sitting at the held maximum, unaffected by jitter
+        let peak_height = placement.bar_height(peak);
+        let peak_base = inner + anchor.normal * peak_height;
+        let peak_top = peak_base + anchor.normal * (PEAK_THICKNESS * placement.scale);
+        let peak_path = Path::new(|builder| {
+          builder.move_to(peak_base - side);
+          builder.line_to(peak_base + side);
+          builder.line_to(peak_top + side);
+          builder.line_to(peak_top - side);
+          builder.close();
+        });
+        let peak_intensity = placement.intensity(peak_height);
+        let peak_color = if self.muted {
+          Color::from_rgb(0.7, 0.7, 0.7)
+        } else {
+          self.gradient.color(peak_intensity)
+        };
+        frame.fill(&peak_path, peak_color);
       }
     });
 
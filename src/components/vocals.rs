@@ -0,0 +1,135 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::ops::Range;
+
+/// FFT size of each frame; frames don't overlap.
+const FRAME_SIZE: usize = 2048;
+/// Where most of a voice's energy sits: fundamentals up to the formants.
+const VOICE_MIN_HZ: f32 = 250.0;
+const VOICE_MAX_HZ: f32 = 4000.0;
+/// Frames are judged together in segments this long, in seconds.
+const SEGMENT_SECONDS: f32 = 1.0;
+/// A segment counts as vocal when it scores this far above the track's median.
+const VOCAL_MARGIN: f32 = 1.25;
+/// Segments quieter than this share of the loudest are never vocal.
+const SILENCE_FRACTION: f32 = 0.05;
+/// Vocal regions closer than this are joined, and shorter ones dropped, in seconds.
+const MAX_GAP: f32 = 2.0;
+const MIN_REGION: f32 = 2.0;
+
+/// Finds the spans of mono samples with vocals, in seconds, in order.
+///
+/// A rough heuristic, not source separation: a voice puts tonal energy in
+/// the mid band and comes and goes with its syllables, so every segment is
+/// scored by how tonal and mid-heavy its frames are times how much their
+/// mid-band energy swings. Segments well above the track's own median
+/// count as vocal, which adapts to how busy the mix is as a whole.
+pub fn detect(samples: &[f32], sample_rate: u32) -> Vec<Range<f32>> {
+  let duration = samples.len() as f32 / sample_rate.max(1) as f32;
+  let frames = voice_frames(samples, sample_rate);
+  let frames_per_segment =
+    ((SEGMENT_SECONDS * sample_rate as f32 / FRAME_SIZE as f32) as usize).max(2);
+
+  // Score and loudness of every segment
+  let segments: Vec<(f32, f32)> = frames
+    .chunks(frames_per_segment)
+    .map(|segment| {
+      let count = segment.len() as f32;
+      let tonal = segment.iter().map(|frame| frame.share * frame.tonality).sum::<f32>() / count;
+      let mean = segment.iter().map(|frame| frame.voice).sum::<f32>() / count;
+      let deviation =
+        (segment.iter().map(|frame| (frame.voice - mean).powi(2)).sum::<f32>() / count).sqrt();
+      let swing = if mean > 0.0 { deviation / mean } else { 0.0 };
+      let loudness = segment.iter().map(|frame| frame.total).sum::<f32>() / count;
+      (tonal * swing, loudness)
+    })
+    .collect();
+  if segments.is_empty() {
+    return Vec::new();
+  }
+
+  let mut scores: Vec<f32> = segments.iter().map(|&(score, _)| score).collect();
+  scores.sort_by(f32::total_cmp);
+  let median = scores[scores.len() / 2];
+  let loudest = segments.iter().map(|&(_, loudness)| loudness).fold(0.0, f32::max);
+  let vocal: Vec<bool> = segments
+    .iter()
+    .map(|&(score, loudness)| {
+      score > median * VOCAL_MARGIN && loudness > loudest * SILENCE_FRACTION
+    })
+    .collect();
+
+  // Majority of each segment and its neighbours, so one-offs don't flicker
+  let smoothed = (0..vocal.len()).map(|i| {
+    let neighbours = &vocal[i.saturating_sub(1)..(i + 2).min(vocal.len())];
+    neighbours.iter().filter(|&&is_vocal| is_vocal).count() * 2 > neighbours.len()
+  });
+
+  let segment_seconds = frames_per_segment as f32 * FRAME_SIZE as f32 / sample_rate as f32;
+  let mut regions: Vec<Range<f32>> = Vec::new();
+  for (i, is_vocal) in smoothed.enumerate() {
+    if !is_vocal {
+      continue;
+    }
+    let start = i as f32 * segment_seconds;
+    let end = (start + segment_seconds).min(duration);
+    match regions.last_mut() {
+      Some(last) if start - last.end < MAX_GAP => last.end = end,
+      _ => regions.push(start..end),
+    }
+  }
+  regions.retain(|region| region.end - region.start >= MIN_REGION);
+  regions
+}
+
+/// What a frame contributes to its segment's score.
+struct VoiceFrame {
+  /// Power in the voice band.
+  voice: f32,
+  /// Power over the whole spectrum.
+  total: f32,
+  /// Share of the power in the voice band.
+  share: f32,
+  /// 1.0 minus the voice band's spectral flatness: high for harmonics.
+  tonality: f32,
+}
+
+fn voice_frames(samples: &[f32], sample_rate: u32) -> Vec<VoiceFrame> {
+  let fft = FftPlanner::new().plan_fft_forward(FRAME_SIZE);
+  let window: Vec<f32> = (0..FRAME_SIZE)
+    .map(|i| 0.5 - 0.5 * (std::f32::consts::TAU * i as f32 / FRAME_SIZE as f32).cos())
+    .collect();
+  let bin_hz = sample_rate as f32 / FRAME_SIZE as f32;
+  let voice_bins = (VOICE_MIN_HZ / bin_hz).ceil() as usize..(VOICE_MAX_HZ / bin_hz) as usize;
+
+  samples
+    .chunks_exact(FRAME_SIZE)
+    .map(|chunk| {
+      let mut buffer: Vec<Complex<f32>> =
+        chunk.iter().zip(&window).map(|(&x, &w)| Complex::new(x * w, 0.0)).collect();
+      fft.process(&mut buffer);
+      let power: Vec<f32> = buffer[..FRAME_SIZE / 2].iter().map(|bin| bin.norm_sqr()).collect();
+
+      let band = &power[voice_bins.start.min(power.len())..voice_bins.end.min(power.len())];
+      let voice = band.iter().sum::<f32>();
+      let total = power.iter().sum::<f32>();
+      VoiceFrame {
+        voice,
+        total,
+        share: if total > 0.0 { voice / total } else { 0.0 },
+        tonality: 1.0 - flatness(band),
+      }
+    })
+    .collect()
+}
+
+/// Geometric over arithmetic mean of the power spectrum: near 1.0 for noise,
+/// near 0.0 for a few strong tones.
+fn flatness(power: &[f32]) -> f32 {
+  let count = power.len().max(1) as f32;
+  let arithmetic = power.iter().sum::<f32>() / count;
+  if arithmetic <= 0.0 {
+    return 1.0;
+  }
+  let geometric = (power.iter().map(|p| p.max(1e-12).ln()).sum::<f32>() / count).exp();
+  geometric / arithmetic
+}
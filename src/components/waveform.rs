@@ -0,0 +1,78 @@
+use iced::{
+  Color, Point, Rectangle, Theme,
+  widget::canvas::{self, Geometry, Path, Stroke, gradient::Linear},
+};
+
+use crate::{app::Message, components::gradient::Gradient};
+
+/// Oscilloscope view of the most recent raw samples.
+pub struct WaveformCanvas {
+  /// Mono samples in -1.0..=1.0, oldest first.
+  pub samples: Vec<f32>,
+  /// Draws the line greyed out while playback is muted.
+  pub muted: bool,
+  pub gradient: Gradient,
+}
+
+impl canvas::Program<Message> for WaveformCanvas {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: iced::mouse::Cursor,
+  ) -> Vec<Geometry> {
+    // The waveform changes every frame, so there's nothing worth caching
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let grey = Color::from_rgb(0.5, 0.5, 0.5);
+    let color_at = |level: f32| if self.muted { grey } else { self.gradient.color(level) };
+    let mid_y = bounds.height / 2.0;
+    let amplitude = bounds.height / 2.0 * 0.9;
+
+    // Baseline
+    frame.stroke(
+      &Path::line(Point::new(0.0, mid_y), Point::new(bounds.width, mid_y)),
+      Stroke::default().with_color(Color { a: 0.25, ..color_at(0.0) }).with_width(1.0),
+    );
+
+    if self.samples.len() > 1 {
+      // Never draw more than a couple of points per pixel
+      let step = (self.samples.len() as f32 / (bounds.width * 2.0)).max(1.0);
+      let points = (self.samples.len() as f32 / step) as usize;
+      let x_scale = bounds.width / (points.max(2) - 1) as f32;
+
+      let line = Path::new(|builder| {
+        for i in 0..points {
+          let sample = self.samples[((i as f32 * step) as usize).min(self.samples.len() - 1)];
+          let point = Point::new(i as f32 * x_scale, mid_y - sample.clamp(-1.0, 1.0) * amplitude);
+          if i == 0 {
+            builder.move_to(point);
+          } else {
+            builder.line_to(point);
+          }
+        }
+      });
+
+      // Colour by distance from the baseline, so peaks take the loud end of the theme
+      let gradient = Linear::new(Point::ORIGIN, Point::new(0.0, bounds.height))
+        .add_stop(0.0, color_at(1.0))
+        .add_stop(0.25, color_at(0.5))
+        .add_stop(0.5, color_at(0.0))
+        .add_stop(0.75, color_at(0.5))
+        .add_stop(1.0, color_at(1.0));
+      frame.stroke(
+        &line,
+        Stroke {
+          style: canvas::Style::Gradient(canvas::Gradient::Linear(gradient)),
+          width: 2.0,
+          ..Stroke::default()
+        },
+      );
+    }
+
+    vec![frame.into_geometry()]
+  }
+}
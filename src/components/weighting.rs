@@ -0,0 +1,52 @@
+use std::fmt;
+
+/// Frequency weighting applied to the spectrum before it's converted to dB,
+/// so the display follows perceived loudness rather than raw energy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Weighting {
+  /// Follows the ear at moderate levels, cutting lows and the very top.
+  A,
+  /// Nearly flat, rolling off only at the extremes; suits loud material.
+  C,
+  /// Flat: no weighting.
+  #[default]
+  Z,
+}
+
+impl Weighting {
+  pub const ALL: [Weighting; 3] = [Weighting::A, Weighting::C, Weighting::Z];
+
+  /// Amplitude gain for each of the `fft_size / 2` bins, normalised to unity at 1 kHz.
+  pub fn gains(self, fft_size: usize, sample_rate: u32) -> Vec<f32> {
+    let bin_hz = sample_rate as f64 / fft_size as f64;
+    let reference = self.response(1000.0);
+    (0..fft_size / 2).map(|bin| (self.response(bin as f64 * bin_hz) / reference) as f32).collect()
+  }
+
+  /// Unnormalised IEC 61672 response at `f` Hz.
+  fn response(self, f: f64) -> f64 {
+    const F1: f64 = 20.6;
+    const F2: f64 = 107.7;
+    const F3: f64 = 737.9;
+    const F4: f64 = 12194.0;
+    let f2 = f * f;
+    match self {
+      Weighting::A => {
+        F4 * F4 * f2 * f2
+          / ((f2 + F1 * F1) * ((f2 + F2 * F2) * (f2 + F3 * F3)).sqrt() * (f2 + F4 * F4))
+      }
+      Weighting::C => F4 * F4 * f2 / ((f2 + F1 * F1) * (f2 + F4 * F4)),
+      Weighting::Z => 1.0,
+    }
+  }
+}
+
+impl fmt::Display for Weighting {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Weighting::A => "A-weighted",
+      Weighting::C => "C-weighted",
+      Weighting::Z => "Z (flat)",
+    })
+  }
+}
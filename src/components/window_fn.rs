@@ -0,0 +1,50 @@
+use std::fmt;
+
+/// Window applied to each chunk before the FFT to reduce spectral leakage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowFunction {
+  Rectangular,
+  #[default]
+  Hann,
+  Hamming,
+  BlackmanHarris,
+}
+
+impl WindowFunction {
+  pub const ALL: [WindowFunction; 4] = [
+    WindowFunction::Rectangular,
+    WindowFunction::Hann,
+    WindowFunction::Hamming,
+    WindowFunction::BlackmanHarris,
+  ];
+
+  /// Builds the `size` window coefficients.
+  pub fn coefficients(self, size: usize) -> Vec<f32> {
+    let denom = (size.max(2) - 1) as f32;
+    (0..size)
+      .map(|n| {
+        let phase = 2.0 * std::f32::consts::PI * n as f32 / denom;
+        match self {
+          WindowFunction::Rectangular => 1.0,
+          WindowFunction::Hann => 0.5 - 0.5 * phase.cos(),
+          WindowFunction::Hamming => 0.54 - 0.46 * phase.cos(),
+          WindowFunction::BlackmanHarris => {
+            0.35875 - 0.48829 * phase.cos() + 0.14128 * (2.0 * phase).cos()
+              - 0.01168 * (3.0 * phase).cos()
+          }
+        }
+      })
+      .collect()
+  }
+}
+
+impl fmt::Display for WindowFunction {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      WindowFunction::Rectangular => "Rectangular",
+      WindowFunction::Hann => "Hann",
+      WindowFunction::Hamming => "Hamming",
+      WindowFunction::BlackmanHarris => "Blackman-Harris",
+    })
+  }
+}
@@ -0,0 +1,223 @@
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use std::{fs, io, path::PathBuf};
+
+use crate::analysis::{BUFFER_SIZE, DecibelRange, Overlap};
+use crate::app::DEFAULT_NUM_BARS;
+use crate::components::{
+  backdrop::{BackdropSource, DEFAULT_BACKDROP_BLUR, DEFAULT_BACKDROP_DARKEN},
+  classifier::SpeechGate,
+  energy::EnergyBinding,
+  equalizer::EqSettings,
+  frame_rate::FrameRate,
+  grade::ColorGrade,
+  gradient::{ColorTheme, DEFAULT_CUSTOM_END, DEFAULT_CUSTOM_START},
+  layout::RingSettings,
+  particles::Emitter,
+  postfx::Effects,
+  smoothing::RegionSmoothing,
+};
+use crate::encode::EncodeSettings;
+use crate::geometry::WindowGeometry;
+use crate::graphics::GraphicsSettings;
+use crate::keymap::Keymap;
+use crate::midi::MidiSettings;
+use crate::normalise::NormaliseSettings;
+use crate::osc::OscSettings;
+use crate::playback::DEFAULT_OUTPUT_LATENCY_MS;
+use crate::remote::RemoteSettings;
+use crate::rumble::RumbleSettings;
+use crate::session::SessionSettings;
+use crate::shm::ShmSettings;
+use crate::suggest::SuggestSettings;
+use crate::tray::TraySettings;
+use crate::ui::scene::SplitView;
+
+const CONFIG_DIR: &str = "rust_audio_visualiser";
+const CONFIG_FILE: &str = "config.json";
+
+/// Settings persisted between runs. Missing keys fall back to their defaults,
+/// so older config files keep loading as new settings are added.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+  pub bar_count: usize,
+  /// Attack and release times per region. Older files kept per-tick
+  /// factors under `smoothing`, which is ignored so they get the defaults.
+  pub envelopes: RegionSmoothing,
+  pub decibels: DecibelRange,
+  /// Follows the signal's level instead of `decibels`.
+  pub auto_range: bool,
+  pub fft_size: usize,
+  pub overlap: Overlap,
+  pub theme: ColorTheme,
+  pub custom_start: String,
+  pub custom_end: String,
+  /// Keeps the chosen theme instead of following each track's cover art.
+  pub lock_theme: bool,
+  pub frame_rate: FrameRate,
+  /// The tick length older files kept instead of `frame_rate`; read to
+  /// pick the nearest rate, and never written back.
+  #[serde(skip_serializing)]
+  pub update_interval_ms: Option<u64>,
+  /// Band whose energy tints the background; off by default.
+  pub background_binding: EnergyBinding,
+  /// Band whose energy drives the pulse; off leaves it on the beats.
+  pub pulse_binding: EnergyBinding,
+  /// Spin and pulse follow the detected tempo.
+  pub tempo_lock: bool,
+  pub backdrop_source: BackdropSource,
+  pub backdrop_blur: f32,
+  pub backdrop_darken: f32,
+  /// Colour correction over the visuals, for matching projectors.
+  pub grade: ColorGrade,
+  /// Particle bursts fired by the energy in their bands.
+  pub particle_emitters: Vec<Emitter>,
+  /// Angle, spin, mirroring and inner radius of the circle layout.
+  pub ring: RingSettings,
+  /// A second style drawn beside the first.
+  pub split: SplitView,
+  /// Bloom, trails and aberration drawn over the bars.
+  pub effects: Effects,
+  /// Name of the plugin the Plugin style draws; empty for the first found.
+  pub plugin: String,
+  pub keymap: Keymap,
+  pub crossfade_seconds: f32,
+  /// Whether the auto-DJ picks and beat-matches the next track.
+  pub auto_dj: bool,
+  /// Whether tracks are brought to one loudness, and which.
+  pub normalise: NormaliseSettings,
+  /// The graphic EQ on playback.
+  pub equalizer: EqSettings,
+  /// Whether a preset is suggested or picked for each track.
+  pub suggest: SuggestSettings,
+  /// Name of the output device; empty for the system default.
+  pub output_device: String,
+  /// How far the speakers lag the analysis, in milliseconds.
+  pub output_latency_ms: f32,
+  /// Quality of every format audio is exported in.
+  pub encode: EncodeSettings,
+  pub speech_gate: SpeechGate,
+  /// AcoustID API key; track identification stays off while it's empty.
+  pub acoustid_key: String,
+  /// Whether presentation mode also switches the window to fullscreen.
+  pub present_fullscreen: bool,
+  /// Limits how bright and how often beat effects flash; on unless
+  /// explicitly turned off.
+  pub strobe_safety: bool,
+  /// Whether the mini window minimises the main one while it's open.
+  pub mini_hides_main: bool,
+  /// Whether the last session is reopened at launch.
+  pub session: SessionSettings,
+  /// Backend and GPU windows are drawn with, read at startup.
+  pub graphics: GraphicsSettings,
+  /// Where the bars and beats are streamed for lighting rigs.
+  pub osc: OscSettings,
+  /// The file each analysis frame is shared through, for local processes.
+  pub shm: ShmSettings,
+  pub midi: MidiSettings,
+  /// Game controller rumble from the bass and beats.
+  pub rumble: RumbleSettings,
+  /// The web remote for phones on the network.
+  pub remote: RemoteSettings,
+  /// The tray icon, and whether closing the window hides it there.
+  pub tray: TraySettings,
+  /// Where the main window was left, restored at startup.
+  pub window: WindowGeometry,
+}
+
+impl Config {
+  /// `<platform config dir>/rust_audio_visualiser/config.json`.
+  pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE))
+  }
+
+  /// Reads the config file, falling back to defaults when it's missing or broken.
+  pub fn load() -> Self {
+    load_json(CONFIG_FILE, "config").unwrap_or_default()
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    save_json(CONFIG_FILE, self)
+  }
+}
+
+/// Reads `file` from next to the config file, `None` when it's missing or
+/// broken; `what` names it in the error printed.
+pub fn load_json<T: DeserializeOwned>(file: &str, what: &str) -> Option<T> {
+  let path = Config::path()?.with_file_name(file);
+  match fs::read_to_string(&path) {
+    Ok(contents) => serde_json::from_str(&contents)
+      .map_err(|e| eprintln!("Failed to parse {} {}: {}", what, path.display(), e))
+      .ok(),
+    Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+    Err(e) => {
+      eprintln!("Failed to read {} {}: {}", what, path.display(), e);
+      None
+    }
+  }
+}
+
+/// Writes `value` to `file` next to the config file, creating the directory.
+pub fn save_json<T: Serialize>(file: &str, value: &T) -> io::Result<()> {
+  let path = Config::path()
+    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?
+    .with_file_name(file);
+  if let Some(dir) = path.parent() {
+    fs::create_dir_all(dir)?;
+  }
+  fs::write(path, serde_json::to_string_pretty(value)?)
+}
+
+impl Default for Config {
+  fn default() -> Self {
+    Self {
+      bar_count: DEFAULT_NUM_BARS,
+      envelopes: RegionSmoothing::default(),
+      decibels: DecibelRange::default(),
+      auto_range: false,
+      fft_size: BUFFER_SIZE,
+      overlap: Overlap::default(),
+      theme: ColorTheme::default(),
+      custom_start: DEFAULT_CUSTOM_START.to_string(),
+      custom_end: DEFAULT_CUSTOM_END.to_string(),
+      lock_theme: false,
+      frame_rate: FrameRate::default(),
+      update_interval_ms: None,
+      background_binding: EnergyBinding::default(),
+      pulse_binding: EnergyBinding::default(),
+      tempo_lock: false,
+      backdrop_source: BackdropSource::default(),
+      backdrop_blur: DEFAULT_BACKDROP_BLUR,
+      backdrop_darken: DEFAULT_BACKDROP_DARKEN,
+      grade: ColorGrade::default(),
+      particle_emitters: Vec::new(),
+      ring: RingSettings::default(),
+      split: SplitView::default(),
+      effects: Effects::default(),
+      plugin: String::new(),
+      keymap: Keymap::default(),
+      crossfade_seconds: 0.0,
+      auto_dj: false,
+      normalise: NormaliseSettings::default(),
+      equalizer: EqSettings::default(),
+      suggest: SuggestSettings::default(),
+      output_device: String::new(),
+      output_latency_ms: DEFAULT_OUTPUT_LATENCY_MS,
+      encode: EncodeSettings::default(),
+      speech_gate: SpeechGate::default(),
+      acoustid_key: String::new(),
+      present_fullscreen: true,
+      strobe_safety: true,
+      mini_hides_main: false,
+      session: SessionSettings::default(),
+      graphics: GraphicsSettings::default(),
+      osc: OscSettings::default(),
+      shm: ShmSettings::default(),
+      midi: MidiSettings::default(),
+      rumble: RumbleSettings::default(),
+      remote: RemoteSettings::default(),
+      tray: TraySettings::default(),
+      window: WindowGeometry::default(),
+    }
+  }
+}
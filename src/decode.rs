@@ -0,0 +1,194 @@
+use rodio::{Source, source::SeekError};
+use std::{fmt, fs::File, io, path::Path, time::Duration};
+use symphonia::core::{
+  audio::SampleBuffer,
+  codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions},
+  errors::Error,
+  formats::{FormatOptions, FormatReader, SeekMode, SeekTo},
+  io::MediaSourceStream,
+  meta::MetadataOptions,
+  probe::Hint,
+  units::{Time, TimeBase},
+};
+
+/// Extensions the open dialog offers.
+pub const EXTENSIONS: [&str; 11] =
+  ["mp3", "wav", "flac", "ogg", "oga", "m4a", "mp4", "aac", "aif", "aiff", "caf"];
+/// Packets in a row that can fail to decode before the rest of the file is
+/// given up on; a single bad packet is only skipped.
+const MAX_DECODE_RETRIES: usize = 3;
+
+#[derive(Debug)]
+pub enum DecodeError {
+  Io(io::Error),
+  /// The container or codec isn't supported, or the file is broken.
+  Format(Error),
+  /// The file has no audio track.
+  NoTrack,
+}
+
+impl fmt::Display for DecodeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DecodeError::Io(e) => write!(f, "{}", e),
+      DecodeError::Format(e) => write!(f, "{}", e),
+      DecodeError::NoTrack => write!(f, "no audio track"),
+    }
+  }
+}
+
+impl From<io::Error> for DecodeError {
+  fn from(e: io::Error) -> Self {
+    DecodeError::Io(e)
+  }
+}
+
+impl From<Error> for DecodeError {
+  fn from(e: Error) -> Self {
+    DecodeError::Format(e)
+  }
+}
+
+/// Decodes the first audio track of a file with symphonia, as interleaved
+/// `f32` samples, so it can be played through rodio or read whole.
+///
+/// Handles everything symphonia does: MP3, FLAC, Vorbis, WAV, AIFF, CAF,
+/// and AAC or ALAC in MP4/M4A or MKV.
+pub struct AudioDecoder {
+  format: Box<dyn FormatReader>,
+  decoder: Box<dyn Decoder>,
+  track_id: u32,
+  time_base: Option<TimeBase>,
+  frames: Option<u64>,
+  channels: u16,
+  sample_rate: u32,
+  /// The last decoded packet, interleaved.
+  buffer: Vec<f32>,
+  /// Next sample of `buffer` to hand out.
+  offset: usize,
+}
+
+impl AudioDecoder {
+  pub fn open(path: &Path) -> Result<Self, DecodeError> {
+    let stream = MediaSourceStream::new(Box::new(File::open(path)?), Default::default());
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|extension| extension.to_str()) {
+      hint.with_extension(extension);
+    }
+    let options = FormatOptions { enable_gapless: true, ..Default::default() };
+    let probed = symphonia::default::get_probe().format(
+      &hint,
+      stream,
+      &options,
+      &MetadataOptions::default(),
+    )?;
+
+    let format = probed.format;
+    let track = format
+      .tracks()
+      .iter()
+      .find(|track| track.codec_params.codec != CODEC_TYPE_NULL)
+      .ok_or(DecodeError::NoTrack)?;
+    let (track_id, params) = (track.id, track.codec_params.clone());
+    let decoder = symphonia::default::get_codecs().make(&params, &DecoderOptions::default())?;
+    let mut audio = Self {
+      format,
+      decoder,
+      track_id,
+      time_base: params.time_base,
+      frames: params.n_frames,
+      channels: params.channels.map_or(0, |channels| channels.count() as u16),
+      sample_rate: params.sample_rate.unwrap_or(0),
+      buffer: Vec::new(),
+      offset: 0,
+    };
+
+    // Some containers only give the format away once a packet is decoded
+    if !audio.refill() || audio.channels == 0 || audio.sample_rate == 0 {
+      return Err(DecodeError::NoTrack);
+    }
+    Ok(audio)
+  }
+
+  /// Decodes the next packet of the track into `buffer`. Returns false at
+  /// the end of the file or once it stops decoding.
+  fn refill(&mut self) -> bool {
+    let mut failures = 0;
+    loop {
+      let Ok(packet) = self.format.next_packet() else {
+        return false;
+      };
+      if packet.track_id() != self.track_id {
+        continue;
+      }
+      match self.decoder.decode(&packet) {
+        Ok(decoded) => {
+          let spec = *decoded.spec();
+          self.channels = spec.channels.count() as u16;
+          self.sample_rate = spec.rate;
+          let mut samples = SampleBuffer::<f32>::new(decoded.capacity() as u64, spec);
+          samples.copy_interleaved_ref(decoded);
+          self.buffer.clear();
+          self.buffer.extend_from_slice(samples.samples());
+          self.offset = 0;
+          if !self.buffer.is_empty() {
+            return true;
+          }
+        }
+        Err(Error::DecodeError(_)) if failures < MAX_DECODE_RETRIES => failures += 1,
+        Err(_) => return false,
+      }
+    }
+  }
+}
+
+impl Iterator for AudioDecoder {
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    if self.offset >= self.buffer.len() && !self.refill() {
+      return None;
+    }
+    let sample = self.buffer[self.offset];
+    self.offset += 1;
+    Some(sample)
+  }
+}
+
+impl Source for AudioDecoder {
+  fn current_frame_len(&self) -> Option<usize> {
+    Some(self.buffer.len() - self.offset)
+  }
+
+  fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  fn total_duration(&self) -> Option<Duration> {
+    let time = self.time_base?.calc_time(self.frames?);
+    Some(Duration::from_secs(time.seconds) + Duration::from_secs_f64(time.frac))
+  }
+
+  fn try_seek(&mut self, position: Duration) -> Result<(), SeekError> {
+    let to =
+      SeekTo::Time { time: Time::from(position.as_secs_f64()), track_id: Some(self.track_id) };
+    let seeked =
+      self.format.seek(SeekMode::Accurate, to).map_err(|e| SeekError::Other(Box::new(e)))?;
+    self.decoder.reset();
+    self.buffer.clear();
+    self.offset = 0;
+
+    // The reader lands on the packet holding the position; decode up to it
+    let mut skip =
+      seeked.required_ts.saturating_sub(seeked.actual_ts) as usize * self.channels as usize;
+    while skip > 0 && self.refill() {
+      self.offset = skip.min(self.buffer.len());
+      skip -= self.offset;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::{
+  fmt, fs,
+  io::{self, Write},
+  path::Path,
+  process::{Command, ExitStatus, Stdio},
+};
+
+pub const FLAC_LEVELS: std::ops::RangeInclusive<u8> = 0..=8;
+pub const MP3_BITRATES: [u32; 6] = [96, 128, 160, 192, 256, 320];
+pub const OPUS_BITRATES: [u32; 6] = [48, 64, 96, 128, 160, 256];
+
+#[derive(Debug)]
+pub enum EncodeError {
+  Io(io::Error),
+  /// ffmpeg ran but didn't finish cleanly.
+  Encoder(ExitStatus),
+  /// The extension isn't one of the formats below.
+  UnknownFormat(String),
+}
+
+impl fmt::Display for EncodeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      EncodeError::Io(e) => write!(f, "{}", e),
+      EncodeError::Encoder(status) => write!(f, "ffmpeg {}", status),
+      EncodeError::UnknownFormat(extension) => write!(f, "unknown audio format .{}", extension),
+    }
+  }
+}
+
+impl From<io::Error> for EncodeError {
+  fn from(e: io::Error) -> Self {
+    EncodeError::Io(e)
+  }
+}
+
+/// Audio formats exports can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AudioFormat {
+  #[default]
+  Wav,
+  Flac,
+  Mp3,
+  Opus,
+}
+
+impl AudioFormat {
+  /// Every extension save dialogs should offer.
+  pub const EXTENSIONS: [&'static str; 5] = ["wav", "flac", "mp3", "opus", "ogg"];
+
+  /// The format a path's extension asks for.
+  pub fn from_path(path: &Path) -> Option<Self> {
+    let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+    match extension.as_str() {
+      "wav" => Some(AudioFormat::Wav),
+      "flac" => Some(AudioFormat::Flac),
+      "mp3" => Some(AudioFormat::Mp3),
+      "opus" | "ogg" => Some(AudioFormat::Opus),
+      _ => None,
+    }
+  }
+}
+
+/// Sample format of WAV files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WavDepth {
+  Pcm16,
+  Pcm24,
+  #[default]
+  Float32,
+}
+
+impl WavDepth {
+  pub const ALL: [WavDepth; 3] = [WavDepth::Pcm16, WavDepth::Pcm24, WavDepth::Float32];
+
+  pub fn bits(self) -> u16 {
+    match self {
+      WavDepth::Pcm16 => 16,
+      WavDepth::Pcm24 => 24,
+      WavDepth::Float32 => 32,
+    }
+  }
+}
+
+impl fmt::Display for WavDepth {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      WavDepth::Pcm16 => "16-bit",
+      WavDepth::Pcm24 => "24-bit",
+      WavDepth::Float32 => "32-bit float",
+    })
+  }
+}
+
+/// Quality of each format, shared by everything that writes audio.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct EncodeSettings {
+  pub wav_depth: WavDepth,
+  /// FLAC compression level; higher is smaller and slower, never lossy.
+  pub flac_level: u8,
+  /// Constant bitrates, in kbit/s.
+  pub mp3_bitrate: u32,
+  pub opus_bitrate: u32,
+}
+
+impl Default for EncodeSettings {
+  fn default() -> Self {
+    Self { wav_depth: WavDepth::default(), flac_level: 5, mp3_bitrate: 192, opus_bitrate: 128 }
+  }
+}
+
+/// Writes interleaved samples to `path` in the format its extension names.
+///
+/// WAV is written directly; FLAC, MP3 and Opus are encoded by `ffmpeg`,
+/// which then has to be on the `PATH`.
+pub fn encode(
+  path: &Path,
+  samples: &[f32],
+  channels: u16,
+  sample_rate: u32,
+  settings: &EncodeSettings,
+) -> Result<(), EncodeError> {
+  let format = AudioFormat::from_path(path).ok_or_else(|| {
+    let extension = path.extension().map(|e| e.to_string_lossy().into_owned());
+    EncodeError::UnknownFormat(extension.unwrap_or_default())
+  })?;
+  let codec: Vec<String> = match format {
+    AudioFormat::Wav => {
+      return Ok(write_wav(path, samples, channels, sample_rate, settings.wav_depth)?);
+    }
+    AudioFormat::Flac => {
+      let level = settings.flac_level.clamp(*FLAC_LEVELS.start(), *FLAC_LEVELS.end());
+      vec!["-c:a".into(), "flac".into(), "-compression_level".into(), level.to_string()]
+    }
+    AudioFormat::Mp3 => {
+      vec!["-c:a".into(), "libmp3lame".into(), "-b:a".into(), format!("{}k", settings.mp3_bitrate)]
+    }
+    AudioFormat::Opus => {
+      vec!["-c:a".into(), "libopus".into(), "-b:a".into(), format!("{}k", settings.opus_bitrate)]
+    }
+  };
+
+  let mut encoder = Command::new("ffmpeg")
+    .args(["-y", "-loglevel", "error", "-f", "f32le"])
+    .args(["-ar", &sample_rate.to_string(), "-ac", &channels.to_string(), "-i", "-"])
+    .args(codec)
+    .arg(path)
+    .stdin(Stdio::piped())
+    .spawn()?;
+  let mut stdin = encoder.stdin.take().expect("ffmpeg stdin is piped");
+  let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+  stdin.write_all(&bytes)?;
+
+  // Closing stdin tells ffmpeg the audio is complete
+  drop(stdin);
+  let status = encoder.wait()?;
+  if !status.success() {
+    return Err(EncodeError::Encoder(status));
+  }
+  Ok(())
+}
+
+/// Writes interleaved samples as a WAV file, clipping integer depths at full scale.
+pub fn write_wav(
+  path: &Path,
+  samples: &[f32],
+  channels: u16,
+  sample_rate: u32,
+  depth: WavDepth,
+) -> io::Result<()> {
+  let bytes_per_sample = depth.bits() / 8;
+  let data_size = samples.len() as u32 * bytes_per_sample as u32;
+  let block_align = channels * bytes_per_sample;
+  let format: u16 = if depth == WavDepth::Float32 { 3 } else { 1 }; // IEEE float or PCM
+
+  let mut wav = Vec::with_capacity(44 + data_size as usize);
+  wav.extend_from_slice(b"RIFF");
+  wav.extend_from_slice(&(36 + data_size).to_le_bytes());
+  wav.extend_from_slice(b"WAVEfmt ");
+  wav.extend_from_slice(&16u32.to_le_bytes());
+  wav.extend_from_slice(&format.to_le_bytes());
+  wav.extend_from_slice(&channels.to_le_bytes());
+  wav.extend_from_slice(&sample_rate.to_le_bytes());
+  wav.extend_from_slice(&(sample_rate * block_align as u32).to_le_bytes());
+  wav.extend_from_slice(&block_align.to_le_bytes());
+  wav.extend_from_slice(&depth.bits().to_le_bytes());
+  wav.extend_from_slice(b"data");
+  wav.extend_from_slice(&data_size.to_le_bytes());
+  for &sample in samples {
+    match depth {
+      WavDepth::Pcm16 => {
+        wav.extend_from_slice(&((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes())
+      }
+      WavDepth::Pcm24 => {
+        let value = (sample.clamp(-1.0, 1.0) * 8_388_607.0) as i32;
+        wav.extend_from_slice(&value.to_le_bytes()[..3]);
+      }
+      WavDepth::Float32 => wav.extend_from_slice(&sample.to_le_bytes()),
+    }
+  }
+  fs::write(path, wav)
+}
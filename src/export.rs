@@ -0,0 +1,467 @@
+use iced::{
+  Color, Font, Pixels, Rectangle, Size, Theme, Vector,
+  advanced::{
+    Renderer as _,
+    graphics::{Viewport, geometry::Renderer as _},
+  },
+  mouse,
+  widget::canvas::Program,
+};
+use rodio::Source;
+use std::{
+  fmt, fs,
+  io::{self, Write},
+  ops::Range,
+  path::Path,
+  process::{Child, ChildStdin, Command, ExitStatus, Stdio},
+  time::{Duration, Instant},
+};
+
+use crate::analysis::{Analyser, AnalysisFrame, AnalysisSettings};
+use crate::app::{AudioVisualizer, Message, WAVEFORM_CAPACITY};
+use crate::components::layout::Placement;
+use crate::decode::{AudioDecoder, DecodeError};
+use crate::encode::{self, EncodeError, EncodeSettings};
+use crate::stem::{StemHeader, StemReader, StemWriter};
+use crate::ui::{
+  scene::{Pane, Scene},
+  settings::VisualSettings,
+};
+
+pub const EXPORT_WIDTH: u32 = 1280;
+pub const EXPORT_HEIGHT: u32 = 720;
+pub const EXPORT_FPS: u32 = 30;
+/// Longest fade a clip gets at either end, in seconds.
+pub const MAX_CLIP_FADE_SECONDS: f32 = 5.0;
+
+#[derive(Debug)]
+pub enum ExportError {
+  Io(io::Error),
+  Decode(DecodeError),
+  /// ffmpeg ran but didn't finish cleanly.
+  Encoder(ExitStatus),
+  Encode(EncodeError),
+}
+
+impl fmt::Display for ExportError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ExportError::Io(e) => write!(f, "{}", e),
+      ExportError::Decode(e) => write!(f, "{}", e),
+      ExportError::Encoder(status) => write!(f, "ffmpeg {}", status),
+      ExportError::Encode(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl From<io::Error> for ExportError {
+  fn from(e: io::Error) -> Self {
+    ExportError::Io(e)
+  }
+}
+
+impl From<EncodeError> for ExportError {
+  fn from(e: EncodeError) -> Self {
+    ExportError::Encode(e)
+  }
+}
+
+impl From<DecodeError> for ExportError {
+  fn from(e: DecodeError) -> Self {
+    ExportError::Decode(e)
+  }
+}
+
+/// Renders `input` with the given settings to a video at `output`, with the
+/// original audio muxed back in, saving the analysis as a stem at `stem`
+/// too if given.
+///
+/// Frames are piped to `ffmpeg`, which has to be on the `PATH`; the output
+/// extension picks the container (`.webm`, otherwise MP4).
+pub fn render_video(
+  input: &Path,
+  output: &Path,
+  stem: Option<&Path>,
+  visuals: VisualSettings,
+  analysis_settings: AnalysisSettings,
+) -> Result<(), ExportError> {
+  let mut encoder = spawn_encoder(Some(input), output, EXPORT_FPS)?;
+  let mut stdin = encoder.stdin.take().expect("ffmpeg stdin is piped");
+  render_frames(input, stem, visuals, analysis_settings, |pixmap| {
+    Ok(stdin.write_all(pixmap.data())?)
+  })?;
+  finish_encoder(encoder, stdin)
+}
+
+/// Renders a video from the analysis stem at `stem` with another look,
+/// without touching the audio beyond muxing it back in. A stem whose audio
+/// has moved renders silent, and the waveform and histogram, which draw
+/// from the samples themselves, stay empty.
+pub fn render_stem_video(
+  stem: &Path,
+  output: &Path,
+  visuals: VisualSettings,
+) -> Result<(), ExportError> {
+  let mut reader = StemReader::open(stem)?;
+  let StemHeader { audio, sample_rate, channels, fps, .. } = reader.header.clone();
+  let audio = Some(Path::new(&audio)).filter(|audio| audio.exists());
+  let mut encoder = spawn_encoder(audio, output, fps)?;
+  let mut stdin = encoder.stdin.take().expect("ffmpeg stdin is piped");
+
+  let mut app = offline_app(visuals, sample_rate, channels, fps);
+  let mut canvas = Offscreen::new(EXPORT_WIDTH, EXPORT_HEIGHT);
+  while let Some(frame) = reader.next_frame()? {
+    present(&mut app, frame, &mut canvas);
+    stdin.write_all(canvas.pixmap.data())?;
+  }
+  finish_encoder(encoder, stdin)
+}
+
+/// Closes ffmpeg's input, which tells it the video is complete, and waits
+/// for it to finish.
+fn finish_encoder(mut encoder: Child, stdin: ChildStdin) -> Result<(), ExportError> {
+  drop(stdin);
+  let status = encoder.wait()?;
+  if !status.success() {
+    return Err(ExportError::Encoder(status));
+  }
+  Ok(())
+}
+
+/// Renders `input` like [`render_video`], but as numbered PNGs in `dir`
+/// (`frame_00000.png` on), for compositing elsewhere. Returns how many
+/// frames were written.
+pub fn render_frame_sequence(
+  input: &Path,
+  dir: &Path,
+  visuals: VisualSettings,
+  analysis_settings: AnalysisSettings,
+) -> Result<usize, ExportError> {
+  fs::create_dir_all(dir)?;
+  let mut count = 0;
+  render_frames(input, None, visuals, analysis_settings, |pixmap| {
+    let png = pixmap.encode_png().map_err(io::Error::other)?;
+    fs::write(dir.join(format!("frame_{:05}.png", count)), png)?;
+    count += 1;
+    Ok(())
+  })?;
+  Ok(count)
+}
+
+/// Draws every frame of `input` at [`EXPORT_FPS`] and hands each to `emit`,
+/// writing the analysis to a stem at `stem` along the way if given.
+///
+/// The whole file is decoded up front and analysed frame by frame,
+/// independent of real time, and frames are drawn off-screen with the
+/// software renderer.
+fn render_frames(
+  input: &Path,
+  stem: Option<&Path>,
+  visuals: VisualSettings,
+  analysis_settings: AnalysisSettings,
+  mut emit: impl FnMut(&tiny_skia::Pixmap) -> Result<(), ExportError>,
+) -> Result<(), ExportError> {
+  let decoder = AudioDecoder::open(input)?;
+  let sample_rate = decoder.sample_rate();
+  let channels = decoder.channels();
+  let samples: Vec<f32> = decoder.convert_samples::<f32>().collect();
+  let channel_count = channels.max(1) as usize;
+  let audio_frames = samples.len() / channel_count;
+
+  let mut app = offline_app(visuals, sample_rate, channels, EXPORT_FPS);
+
+  let analysis_settings = AnalysisSettings { channels, sample_rate, ..analysis_settings };
+  let mut analyser = Analyser::new(analysis_settings);
+  let fft_size = analysis_settings.fft_size;
+
+  let mut canvas = Offscreen::new(EXPORT_WIDTH, EXPORT_HEIGHT);
+
+  let video_frames = audio_frames as u64 * EXPORT_FPS as u64 / sample_rate.max(1) as u64;
+  let mut stem = match stem {
+    Some(path) => Some(StemWriter::create(
+      path,
+      &StemHeader {
+        audio: input.to_string_lossy().to_string(),
+        sample_rate,
+        channels,
+        fps: EXPORT_FPS,
+        fft_size,
+        frames: video_frames,
+      },
+    )?),
+    None => None,
+  };
+  let mut previous_end = 0;
+  for index in 0..video_frames {
+    let end = (index * sample_rate as u64 / EXPORT_FPS as u64) as usize;
+
+    // Everything since the previous video frame feeds the waveform and histogram
+    let fresh = &samples[previous_end * channel_count..end * channel_count];
+    {
+      let mut ring = app.waveform.lock().unwrap();
+      ring.extend(fresh.iter());
+      let excess = ring.len().saturating_sub(WAVEFORM_CAPACITY).next_multiple_of(channel_count);
+      ring.drain(..excess.min(ring.len()));
+    }
+    app.histogram.lock().unwrap().add(fresh);
+    previous_end = end;
+
+    // Analyse the audio leading up to this frame, silence-padded at the start
+    let start = end.saturating_sub(fft_size);
+    let mut streams =
+      vec![vec![0.0; fft_size - (end - start)]; analysis_settings.channel_mode.streams()];
+    for frame in samples[start * channel_count..end * channel_count].chunks_exact(channel_count) {
+      analysis_settings.channel_mode.push_frame(frame, &mut streams);
+    }
+    let frame = analyser.frame(&streams, 1.0 / EXPORT_FPS as f32);
+    if let Some(stem) = &mut stem {
+      stem.push(&frame)?;
+    }
+
+    present(&mut app, frame, &mut canvas);
+    emit(&canvas.pixmap)?;
+  }
+  if let Some(stem) = stem {
+    stem.finish()?;
+  }
+  Ok(())
+}
+
+/// A private copy of the app for rendering at `fps`, so the live view's
+/// state is left alone.
+fn offline_app(
+  visuals: VisualSettings,
+  sample_rate: u32,
+  channels: u16,
+  fps: u32,
+) -> AudioVisualizer {
+  let mut app = AudioVisualizer { visuals, sample_rate, channels, ..AudioVisualizer::default() };
+  app.visuals.update_interval = Duration::from_secs(1) / fps.max(1);
+  app.resize_bars();
+  // Ticks only take in new frames while playing
+  app.player.is_playing = true;
+  app
+}
+
+/// Takes in one analysis frame and draws the scene it leads to.
+fn present(app: &mut AudioVisualizer, mut frame: AnalysisFrame, canvas: &mut Offscreen) {
+  // Same path as the live view: the beat, then the tick that takes in the frame
+  if let Some(strength) = frame.beat.take() {
+    let _ = app.update(Message::Beat(strength));
+  }
+  app.audio_data.lock().unwrap().push(frame, Instant::now());
+  let _ = app.update(Message::Tick);
+  canvas.draw(app);
+}
+
+/// A software renderer and the pixmap it draws the scene into.
+struct Offscreen {
+  renderer: iced::Renderer,
+  pixmap: tiny_skia::Pixmap,
+  clip_mask: tiny_skia::Mask,
+  viewport: Viewport,
+  bounds: Rectangle,
+  theme: Theme,
+}
+
+impl Offscreen {
+  fn new(width: u32, height: u32) -> Self {
+    Self {
+      renderer: iced::Renderer::Secondary(iced_tiny_skia::Renderer::new(
+        Font::default(),
+        Pixels(16.0),
+      )),
+      pixmap: tiny_skia::Pixmap::new(width, height).expect("non-zero size"),
+      clip_mask: tiny_skia::Mask::new(width, height).expect("non-zero size"),
+      viewport: Viewport::with_physical_size(Size::new(width, height), 1.0),
+      bounds: Rectangle::with_size(Size::new(width as f32, height as f32)),
+      theme: Theme::default(),
+    }
+  }
+
+  /// Draws `app`'s scene over the theme's background, replacing the last frame.
+  fn draw(&mut self, app: &AudioVisualizer) {
+    let geometry = Scene { app, live: false, pane: Pane::Main }.draw(
+      &(),
+      &self.renderer,
+      &self.theme,
+      self.bounds,
+      mouse::Cursor::Unavailable,
+    );
+    for layer in geometry {
+      self.renderer.draw_geometry(layer);
+    }
+    if let iced::Renderer::Secondary(software) = &mut self.renderer {
+      let overlay: &[&str] = &[];
+      software.draw(
+        &mut self.pixmap.as_mut(),
+        &mut self.clip_mask,
+        &self.viewport,
+        &[self.bounds],
+        self.theme.palette().background,
+        overlay,
+      );
+    }
+    self.renderer.clear();
+  }
+}
+
+/// Starts ffmpeg reading raw RGBA frames at `fps` from stdin and the audio
+/// from `audio`, if there is any.
+fn spawn_encoder(audio: Option<&Path>, output: &Path, fps: u32) -> io::Result<Child> {
+  let codecs: &[&str] = match output.extension().and_then(|extension| extension.to_str()) {
+    Some("webm") => &["-c:v", "libvpx-vp9", "-c:a", "libopus"],
+    _ => &["-c:v", "libx264", "-pix_fmt", "yuv420p", "-c:a", "aac"],
+  };
+
+  let mut command = Command::new("ffmpeg");
+  command
+    .args(["-y", "-loglevel", "error", "-f", "rawvideo", "-pix_fmt", "rgba"])
+    .args(["-s", &format!("{}x{}", EXPORT_WIDTH, EXPORT_HEIGHT)])
+    .args(["-r", &fps.to_string(), "-i", "-"]);
+  match audio {
+    Some(audio) => command.arg("-i").arg(audio).args(["-map", "0:v", "-map", "1:a"]),
+    None => command.arg("-an"),
+  };
+  command.args(codecs).arg("-shortest").arg(output).stdin(Stdio::piped()).spawn()
+}
+
+/// Cuts `range` (in seconds) out of `input` and writes it to `output`, fading
+/// in and out over `fade`, in the format the output's extension names.
+pub fn export_clip(
+  input: &Path,
+  output: &Path,
+  range: Range<f32>,
+  fade: Duration,
+  settings: &EncodeSettings,
+) -> Result<(), ExportError> {
+  let decoder = AudioDecoder::open(input)?;
+  let sample_rate = decoder.sample_rate();
+  let channels = decoder.channels();
+  let samples: Vec<f32> = decoder.convert_samples::<f32>().collect();
+  let channel_count = channels.max(1) as usize;
+
+  let frame_at = |seconds: f32| {
+    ((seconds.max(0.0) * sample_rate as f32) as usize).min(samples.len() / channel_count)
+  };
+  let (start, end) = (frame_at(range.start), frame_at(range.end));
+  let mut clip = samples[start * channel_count..end.max(start) * channel_count].to_vec();
+
+  // Linear fades, never longer than half the clip so they can't overlap
+  let frames = clip.len() / channel_count;
+  let fade_frames = ((fade.as_secs_f32() * sample_rate as f32) as usize).min(frames / 2);
+  for i in 0..fade_frames {
+    let gain = i as f32 / fade_frames as f32;
+    for frame in [i, frames - 1 - i] {
+      for sample in &mut clip[frame * channel_count..(frame + 1) * channel_count] {
+        *sample *= gain;
+      }
+    }
+  }
+
+  encode::encode(output, &clip, channels, sample_rate, settings)?;
+  Ok(())
+}
+
+/// Sizes a snapshot can be saved at, whatever the window's size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+  pub width: u32,
+  pub height: u32,
+}
+
+impl Resolution {
+  pub const ALL: [Resolution; 4] = [
+    Resolution { width: 1280, height: 720 },
+    Resolution { width: 1920, height: 1080 },
+    Resolution { width: 2560, height: 1440 },
+    Resolution { width: 3840, height: 2160 },
+  ];
+}
+
+impl Default for Resolution {
+  fn default() -> Self {
+    Resolution { width: 1920, height: 1080 }
+  }
+}
+
+impl fmt::Display for Resolution {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}×{}", self.width, self.height)
+  }
+}
+
+/// Saves the frame `app` shows now to `output` at `resolution`.
+///
+/// `.svg` writes only the bars, as one polygon each along the current
+/// layout, without jitter or the beat pulse; anything else renders the whole
+/// scene off-screen to a PNG.
+pub fn save_snapshot(
+  app: &AudioVisualizer,
+  resolution: Resolution,
+  output: &Path,
+) -> Result<(), ExportError> {
+  let is_svg = output.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("svg"));
+  if is_svg {
+    return Ok(fs::write(output, bars_svg(app, resolution))?);
+  }
+
+  // The live cache holds the window renderer's geometry, which the software
+  // renderer can't draw
+  app.canvas_cache.clear();
+  let mut canvas = Offscreen::new(resolution.width, resolution.height);
+  canvas.draw(app);
+  app.canvas_cache.clear();
+
+  let png = canvas.pixmap.encode_png().map_err(io::Error::other)?;
+  fs::write(output, png)?;
+  Ok(())
+}
+
+/// The bars as an SVG document, coloured like the live view.
+fn bars_svg(app: &AudioVisualizer, resolution: Resolution) -> String {
+  let (width, height) = (resolution.width as f32, resolution.height as f32);
+  let visuals = &app.visuals;
+  let placement = Placement::compute(
+    visuals.layout,
+    visuals.shape(),
+    Rectangle::with_size(Size::new(width, height)),
+    app.frequency_data.len(),
+    visuals.ring,
+    app.spin_time(),
+  );
+  let bars = visuals.ring.mirror.arrange(&app.frequency_data, placement.anchors.len());
+
+  let mut svg = format!(
+    "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+    width, height
+  );
+  svg += &format!(
+    "  <rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n",
+    hex(Theme::default().palette().background)
+  );
+  for (anchor, &height) in placement.anchors.iter().zip(&bars) {
+    let bar_height = placement.bar_height(height);
+    let inner = anchor.position;
+    let outer = inner + anchor.normal * bar_height;
+    let side = Vector::new(-anchor.normal.y, anchor.normal.x) * (placement.bar_width / 2.0);
+    let intensity = placement.intensity(bar_height);
+
+    let corners = [inner - side, inner + side, outer + side, outer - side];
+    let points: Vec<String> =
+      corners.iter().map(|corner| format!("{:.2},{:.2}", corner.x, corner.y)).collect();
+    svg += &format!(
+      "  <polygon points=\"{}\" fill=\"{}\"/>\n",
+      points.join(" "),
+      hex(visuals.gradient.color(intensity))
+    );
+  }
+  svg += "</svg>\n";
+  svg
+}
+
+/// `#rrggbb`, dropping alpha.
+fn hex(color: Color) -> String {
+  let [r, g, b, _] = color.into_rgba8();
+  format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
@@ -0,0 +1,306 @@
+//! `--extract`: the raw FFT magnitudes of every analysis frame of a file,
+//! with the settings they were taken with, for ML pipelines to train on.
+//!
+//! ```sh
+//! rust_audio_visualiser --extract track.flac --out track.ndjson
+//! ```
+//!
+//! Newline-delimited JSON has a metadata line first, then a line per frame
+//! with its `index`, `time`, `beat` and `magnitudes` (one array of bins per
+//! analysed stream). An `.h5` or `.hdf5` output is written as HDF5 instead,
+//! with `time`, `beat` and `magnitudes` (frames × streams × bins) datasets
+//! and the metadata as attributes on the root; that needs building with
+//! `cargo build --features hdf5`, and the HDF5 library installed.
+
+use rodio::Source;
+use serde::Serialize;
+use std::{
+  fmt,
+  fs::File,
+  io::{self, BufWriter, Write},
+  path::Path,
+};
+
+use crate::analysis::{Analyser, AnalysisFrame, AnalysisSettings, StreamBuffers};
+use crate::app::AudioVisualizer;
+use crate::config::Config;
+use crate::decode::{AudioDecoder, DecodeError};
+use crate::tags::Tags;
+
+/// Analysis hops fed in at a time, so the FFTs of a batch run side by side
+/// without the whole file's windows being held at once.
+const HOPS_PER_CHUNK: usize = 32;
+
+#[derive(Debug)]
+pub enum FeatureError {
+  Io(io::Error),
+  Decode(DecodeError),
+  Json(serde_json::Error),
+  /// HDF5 output was asked for, but isn't built in.
+  NoHdf5,
+  #[cfg(feature = "hdf5")]
+  Hdf5(hdf5::Error),
+}
+
+impl fmt::Display for FeatureError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      FeatureError::Io(e) => write!(f, "{}", e),
+      FeatureError::Decode(e) => write!(f, "{}", e),
+      FeatureError::Json(e) => write!(f, "{}", e),
+      FeatureError::NoHdf5 => write!(f, "HDF5 output needs building with the hdf5 feature"),
+      #[cfg(feature = "hdf5")]
+      FeatureError::Hdf5(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl From<io::Error> for FeatureError {
+  fn from(e: io::Error) -> Self {
+    FeatureError::Io(e)
+  }
+}
+
+impl From<DecodeError> for FeatureError {
+  fn from(e: DecodeError) -> Self {
+    FeatureError::Decode(e)
+  }
+}
+
+impl From<serde_json::Error> for FeatureError {
+  fn from(e: serde_json::Error) -> Self {
+    FeatureError::Json(e)
+  }
+}
+
+#[cfg(feature = "hdf5")]
+impl From<hdf5::Error> for FeatureError {
+  fn from(e: hdf5::Error) -> Self {
+    FeatureError::Hdf5(e)
+  }
+}
+
+/// Options for `--extract`, read from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeatureArgs {
+  pub input: String,
+  pub output: String,
+}
+
+impl FeatureArgs {
+  /// `--extract <file> [--out <path>]`, or `None` without `--extract`.
+  pub fn parse(args: &[String]) -> Option<Self> {
+    let value =
+      |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1)).cloned();
+    Some(Self {
+      input: value("--extract")?,
+      output: value("--out").unwrap_or_else(|| "features.ndjson".to_string()),
+    })
+  }
+}
+
+/// What the frames are written as, going by the output's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FeatureFormat {
+  Ndjson,
+  Hdf5,
+}
+
+impl FeatureFormat {
+  fn for_path(path: &Path) -> Self {
+    let is_hdf5 = path.extension().is_some_and(|extension| {
+      extension.eq_ignore_ascii_case("h5") || extension.eq_ignore_ascii_case("hdf5")
+    });
+    if is_hdf5 { FeatureFormat::Hdf5 } else { FeatureFormat::Ndjson }
+  }
+}
+
+/// The file and the settings its frames were analysed with.
+#[derive(Debug, Clone, Serialize)]
+struct FeatureMetadata {
+  source: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  title: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  artist: Option<String>,
+  sample_rate: u32,
+  channels: u16,
+  fft_size: usize,
+  /// Samples from one frame to the next.
+  hop_size: usize,
+  window: String,
+  weighting: String,
+  channel_mode: String,
+  /// Spectra per frame: one for a mix, two for split channels.
+  streams: usize,
+  /// Bins per spectrum, the first half of the FFT.
+  bins: usize,
+  /// Width of a bin, in Hz.
+  bin_hz: f32,
+  frames: usize,
+}
+
+impl FeatureMetadata {
+  fn new(input: &Path, settings: AnalysisSettings, audio_frames: usize) -> Self {
+    let tags = Tags::read(input).unwrap_or_else(|e| {
+      eprintln!("Failed to read the tags of {}: {}", input.display(), e);
+      Tags::default()
+    });
+    let named = |value: String| (!value.trim().is_empty()).then_some(value);
+    let hop_size = settings.overlap.hop(settings.fft_size);
+    Self {
+      source: input.display().to_string(),
+      title: named(tags.title),
+      artist: named(tags.artist),
+      sample_rate: settings.sample_rate,
+      channels: settings.channels,
+      fft_size: settings.fft_size,
+      hop_size,
+      window: settings.window.to_string(),
+      weighting: settings.weighting.to_string(),
+      channel_mode: settings.channel_mode.to_string(),
+      streams: settings.channel_mode.streams(),
+      bins: settings.fft_size / 2,
+      bin_hz: settings.sample_rate as f32 / settings.fft_size as f32,
+      frames: audio_frames.checked_sub(settings.fft_size).map_or(0, |rest| rest / hop_size + 1),
+    }
+  }
+
+  /// Seconds into the file of the middle of frame `index`'s window.
+  fn time(&self, index: usize) -> f32 {
+    (index * self.hop_size + self.fft_size / 2) as f32 / self.sample_rate as f32
+  }
+}
+
+#[derive(Serialize)]
+struct FeatureFrame<'a> {
+  index: usize,
+  /// Seconds into the file of the middle of the window.
+  time: f32,
+  /// Strength of a beat detected at this frame.
+  beat: Option<f32>,
+  /// Amplitude of each bin, per stream.
+  magnitudes: &'a [Vec<f32>],
+}
+
+/// Analyses `args.input` with the saved analysis settings and writes every
+/// frame to `args.output`. Returns how many frames were written.
+pub fn run(args: &FeatureArgs) -> Result<usize, FeatureError> {
+  let input = Path::new(&args.input);
+  let output = Path::new(&args.output);
+  let format = FeatureFormat::for_path(output);
+  if format == FeatureFormat::Hdf5 && !cfg!(feature = "hdf5") {
+    return Err(FeatureError::NoHdf5);
+  }
+
+  let decoder = AudioDecoder::open(input)?;
+  let sample_rate = decoder.sample_rate();
+  let channels = decoder.channels();
+  let samples: Vec<f32> = decoder.convert_samples::<f32>().collect();
+
+  let mut app = AudioVisualizer::default();
+  app.apply_config(&Config::load());
+  let settings =
+    AnalysisSettings { channels, sample_rate, ..*app.analysis_settings.lock().unwrap() };
+  let metadata = FeatureMetadata::new(input, settings, samples.len() / channels.max(1) as usize);
+
+  match format {
+    FeatureFormat::Ndjson => {
+      let mut writer = BufWriter::new(File::create(output)?);
+      serde_json::to_writer(&mut writer, &metadata)?;
+      writeln!(writer)?;
+      let written = extract(&samples, settings, |index, frame| {
+        let line = FeatureFrame {
+          index,
+          time: metadata.time(index),
+          beat: frame.beat,
+          magnitudes: &frame.spectra,
+        };
+        serde_json::to_writer(&mut writer, &line)?;
+        writeln!(writer)?;
+        Ok(())
+      })?;
+      writer.flush()?;
+      Ok(written)
+    }
+    FeatureFormat::Hdf5 => write_hdf5(output, &metadata, &samples, settings),
+  }
+}
+
+/// Runs the analysis over the whole of `samples`, handing each frame and
+/// its index to `each` in order. Returns how many frames there were.
+fn extract(
+  samples: &[f32],
+  settings: AnalysisSettings,
+  mut each: impl FnMut(usize, &AnalysisFrame) -> Result<(), FeatureError>,
+) -> Result<usize, FeatureError> {
+  let mut analyser = Analyser::new(settings);
+  let mut streams = StreamBuffers::new(settings);
+  let chunk = settings.overlap.hop(settings.fft_size) * HOPS_PER_CHUNK * settings.channels as usize;
+  let mut count = 0;
+  for samples in samples.chunks(chunk.max(1)) {
+    streams.push(samples, settings);
+    while let Some((frame, _)) = streams.next_frame(&mut analyser) {
+      each(count, &frame)?;
+      count += 1;
+    }
+  }
+  Ok(count)
+}
+
+#[cfg(feature = "hdf5")]
+fn write_hdf5(
+  output: &Path,
+  metadata: &FeatureMetadata,
+  samples: &[f32],
+  settings: AnalysisSettings,
+) -> Result<usize, FeatureError> {
+  use hdf5::types::VarLenUnicode;
+
+  let (mut times, mut beats, mut magnitudes) = (Vec::new(), Vec::new(), Vec::new());
+  let count = extract(samples, settings, |index, frame| {
+    times.push(metadata.time(index));
+    beats.push(frame.beat.unwrap_or(0.0));
+    for spectrum in &frame.spectra {
+      magnitudes.extend_from_slice(spectrum);
+    }
+    Ok(())
+  })?;
+
+  let file = hdf5::File::create(output)?;
+  file.new_dataset::<f32>().shape(count).create("time")?.write_raw(&times)?;
+  file.new_dataset::<f32>().shape(count).create("beat")?.write_raw(&beats)?;
+  file
+    .new_dataset::<f32>()
+    .shape((count, metadata.streams, metadata.bins))
+    .create("magnitudes")?
+    .write_raw(&magnitudes)?;
+
+  let text = |name: &str, value: &str| -> Result<(), FeatureError> {
+    let value: VarLenUnicode = value.parse().unwrap_or_default();
+    file.new_attr::<VarLenUnicode>().shape(()).create(name)?.write_scalar(&value)?;
+    Ok(())
+  };
+  text("source", &metadata.source)?;
+  text("title", metadata.title.as_deref().unwrap_or_default())?;
+  text("artist", metadata.artist.as_deref().unwrap_or_default())?;
+  text("window", &metadata.window)?;
+  text("weighting", &metadata.weighting)?;
+  text("channel_mode", &metadata.channel_mode)?;
+  file.new_attr::<u32>().shape(()).create("sample_rate")?.write_scalar(&metadata.sample_rate)?;
+  file.new_attr::<u16>().shape(()).create("channels")?.write_scalar(&metadata.channels)?;
+  file.new_attr::<u64>().shape(()).create("fft_size")?.write_scalar(&(metadata.fft_size as u64))?;
+  file.new_attr::<u64>().shape(()).create("hop_size")?.write_scalar(&(metadata.hop_size as u64))?;
+  file.new_attr::<f32>().shape(()).create("bin_hz")?.write_scalar(&metadata.bin_hz)?;
+  Ok(count)
+}
+
+#[cfg(not(feature = "hdf5"))]
+fn write_hdf5(
+  _output: &Path,
+  _metadata: &FeatureMetadata,
+  _samples: &[f32],
+  _settings: AnalysisSettings,
+) -> Result<usize, FeatureError> {
+  Err(FeatureError::NoHdf5)
+}
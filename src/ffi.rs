@@ -0,0 +1,129 @@
+//! C ABI over the analysis, for hosts that aren't written in Rust (C++, Unity
+//! native plugins). Bars come out binned, mirrored and smoothed exactly as
+//! the app draws them with its default settings. Build the shared library
+//! with
+//!
+//! ```sh
+//! cargo build --lib --release --features ffi
+//! ```
+//!
+//! and see `include/rust_audio_visualiser.h` for the declarations.
+
+use std::{ptr, slice, time::Duration};
+
+use crate::analysis::{Analyser, AnalysisSettings, DecibelRange, StreamBuffers};
+use crate::components::{bars, binning::FrequencyScale, smoothing::RegionSmoothing};
+
+/// Most bars `create_analyzer` will shape.
+const MAX_BAR_COUNT: usize = 512;
+
+/// What a handle from `create_analyzer` points at; opaque to C.
+pub struct Analyzer {
+  analyser: Analyser,
+  streams: StreamBuffers,
+  /// Smoothed levels, 0.0..=1.0.
+  bars: Vec<f32>,
+  scale: FrequencyScale,
+  decibels: DecibelRange,
+  smoothing: RegionSmoothing,
+}
+
+/// Starts an analyser for interleaved samples with `channels` channels at
+/// `sample_rate`, shaping `bar_count` bars. Returns null when any of them is
+/// zero or `bar_count` is over 512; free the handle with `destroy_analyzer`.
+#[unsafe(no_mangle)]
+pub extern "C" fn create_analyzer(
+  sample_rate: u32,
+  channels: u16,
+  bar_count: usize,
+) -> *mut Analyzer {
+  if sample_rate == 0 || channels == 0 || bar_count == 0 || bar_count > MAX_BAR_COUNT {
+    return ptr::null_mut();
+  }
+  let settings = AnalysisSettings { channels, sample_rate, ..AnalysisSettings::default() };
+  Box::into_raw(Box::new(Analyzer {
+    analyser: Analyser::new(settings),
+    streams: StreamBuffers::new(settings),
+    bars: vec![0.0; bar_count],
+    scale: FrequencyScale::default(),
+    decibels: DecibelRange::default(),
+    smoothing: RegionSmoothing::default(),
+  }))
+}
+
+/// Feeds `len` interleaved samples, moving the bars on by every frame they
+/// complete. Returns how many frames that was.
+///
+/// # Safety
+///
+/// `analyzer` must come from `create_analyzer` and not be destroyed yet, and
+/// `samples` must point at `len` readable floats.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn feed_samples(
+  analyzer: *mut Analyzer,
+  samples: *const f32,
+  len: usize,
+) -> usize {
+  // SAFETY: the caller guarantees both pointers, per the docs above
+  let (Some(analyzer), false) = (unsafe { analyzer.as_mut() }, samples.is_null()) else {
+    return 0;
+  };
+  let samples = unsafe { slice::from_raw_parts(samples, len) };
+
+  let settings = analyzer.analyser.settings();
+  analyzer.streams.push(samples, settings);
+  let mut count = 0;
+  while let Some((frame, _)) = analyzer.streams.next_frame(&mut analyzer.analyser) {
+    let target = bars::levels(
+      &frame.spectra,
+      settings.sample_rate,
+      analyzer.bars.len(),
+      analyzer.scale,
+      analyzer.decibels,
+    );
+    let smoothing = analyzer.smoothing;
+    let hop_size = settings.overlap.hop(settings.fft_size);
+    let hop = Duration::from_secs_f32(hop_size as f32 / settings.sample_rate as f32);
+    bars::smooth(
+      &mut analyzer.bars,
+      &target,
+      settings.sample_rate,
+      analyzer.scale,
+      hop,
+      |region| smoothing.get(region),
+    );
+    count += 1;
+  }
+  count
+}
+
+/// Copies up to `len` bar levels, each 0.0..=1.0, into `out`. Returns how
+/// many were copied.
+///
+/// # Safety
+///
+/// `analyzer` must come from `create_analyzer` and not be destroyed yet, and
+/// `out` must point at `len` writable floats.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn get_bars(analyzer: *const Analyzer, out: *mut f32, len: usize) -> usize {
+  // SAFETY: the caller guarantees both pointers, per the docs above
+  let (Some(analyzer), false) = (unsafe { analyzer.as_ref() }, out.is_null()) else {
+    return 0;
+  };
+  let count = len.min(analyzer.bars.len());
+  unsafe { slice::from_raw_parts_mut(out, count) }.copy_from_slice(&analyzer.bars[..count]);
+  count
+}
+
+/// Frees an analyser. Null is ignored.
+///
+/// # Safety
+///
+/// `analyzer` must come from `create_analyzer`, and isn't usable afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn destroy_analyzer(analyzer: *mut Analyzer) {
+  if !analyzer.is_null() {
+    // SAFETY: it was made by Box::into_raw in create_analyzer
+    drop(unsafe { Box::from_raw(analyzer) });
+  }
+}
@@ -0,0 +1,116 @@
+use iced::{Point, Size, Task as Command, window};
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// How much of a restored window has to land on the monitor, in logical
+/// pixels, for its saved position to be kept once the layout has changed.
+const MIN_VISIBLE: f32 = 100.0;
+
+/// The geometry being restored, for `place`, which can't capture it.
+static RESTORING: OnceLock<WindowGeometry> = OnceLock::new();
+/// Resolution of the monitor the main window opened on.
+static MONITOR: OnceLock<Size> = OnceLock::new();
+
+/// Where the main window was left, so the next launch opens it there.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowGeometry {
+  /// Size while neither maximised nor fullscreen, in logical pixels.
+  pub width: f32,
+  pub height: f32,
+  /// Top-left corner on the desktop; unset until the window is first moved.
+  pub x: Option<f32>,
+  pub y: Option<f32>,
+  pub maximized: bool,
+  pub fullscreen: bool,
+  /// Resolution of the monitor the window opened on, to tell when the
+  /// monitor layout has changed since; zero when it isn't known.
+  pub monitor_width: f32,
+  pub monitor_height: f32,
+}
+
+impl WindowGeometry {
+  /// Settings to open the main window with at the saved size and position.
+  /// Maximising and fullscreen are put back by `restore` once it's open.
+  pub fn settings(self) -> window::Settings {
+    // Only the first main window is restored
+    let _ = RESTORING.set(self);
+    window::Settings {
+      size: Size::new(self.width, self.height),
+      position: window::Position::SpecificWith(place),
+      ..window::Settings::default()
+    }
+  }
+
+  /// Shrinks the just-opened window onto its monitor if it no longer fits,
+  /// then maximises it or makes it fullscreen as it was left.
+  pub fn restore<T>(self, id: window::Id) -> Command<T> {
+    let mut commands = Vec::new();
+    if let Some(monitor) = MONITOR.get() {
+      let fitted = Size::new(self.width.min(monitor.width), self.height.min(monitor.height));
+      if fitted != Size::new(self.width, self.height) {
+        commands.push(window::resize(id, fitted));
+      }
+    }
+    if self.maximized {
+      commands.push(window::maximize(id, true));
+    }
+    if self.fullscreen {
+      commands.push(window::change_mode(id, window::Mode::Fullscreen));
+    }
+    Command::batch(commands)
+  }
+
+  /// Records the monitor the window opened on, keeping the saved one when
+  /// it never got to open.
+  pub fn with_monitor(self) -> Self {
+    match MONITOR.get() {
+      Some(monitor) => {
+        Self { monitor_width: monitor.width, monitor_height: monitor.height, ..self }
+      }
+      None => self,
+    }
+  }
+}
+
+impl Default for WindowGeometry {
+  fn default() -> Self {
+    let size = window::Settings::default().size;
+    Self {
+      width: size.width,
+      height: size.height,
+      x: None,
+      y: None,
+      maximized: false,
+      fullscreen: false,
+      monitor_width: 0.0,
+      monitor_height: 0.0,
+    }
+  }
+}
+
+/// Where the main window opens, given its size and its monitor's resolution.
+/// The saved position is kept while that monitor is unchanged, since the
+/// window may have been left on another one beside it. Otherwise it's kept
+/// only if enough of the window stays on screen, and the window is centred
+/// when it wouldn't.
+fn place(size: Size, monitor: Size) -> Point {
+  let _ = MONITOR.set(monitor);
+  let centred = Point::new(
+    ((monitor.width - size.width) / 2.0).max(0.0),
+    ((monitor.height - size.height) / 2.0).max(0.0),
+  );
+  let Some(saved) = RESTORING.get() else {
+    return centred;
+  };
+  let (Some(x), Some(y)) = (saved.x, saved.y) else {
+    return centred;
+  };
+
+  let unchanged = saved.monitor_width == monitor.width && saved.monitor_height == monitor.height;
+  let on_screen = x + size.width >= MIN_VISIBLE
+    && x <= monitor.width - MIN_VISIBLE
+    && y >= 0.0
+    && y <= monitor.height - MIN_VISIBLE;
+  if unchanged || on_screen { Point::new(x, y) } else { centred }
+}
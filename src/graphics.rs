@@ -0,0 +1,153 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// What iced reports as the backend when it drew with the CPU.
+const SOFTWARE_BACKEND: &str = "tiny-skia";
+
+/// The graphics API windows are drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RendererBackend {
+  /// The platform's best GPU API, or software when there's none.
+  #[default]
+  Auto,
+  Vulkan,
+  Metal,
+  Dx12,
+  Gl,
+  /// Always the CPU; slower, but works in VMs and over remote desktop.
+  Software,
+}
+
+impl RendererBackend {
+  pub const ALL: [RendererBackend; 6] = [
+    RendererBackend::Auto,
+    RendererBackend::Vulkan,
+    RendererBackend::Metal,
+    RendererBackend::Dx12,
+    RendererBackend::Gl,
+    RendererBackend::Software,
+  ];
+
+  /// Parses a `--renderer` value: `auto`, `vulkan`, `metal`, `dx12`, `gl` or `software`.
+  pub fn from_arg(arg: &str) -> Option<Self> {
+    Self::ALL.into_iter().find(|backend| backend.arg() == arg.to_ascii_lowercase())
+  }
+
+  fn arg(self) -> &'static str {
+    match self {
+      RendererBackend::Auto => "auto",
+      RendererBackend::Vulkan => "vulkan",
+      RendererBackend::Metal => "metal",
+      RendererBackend::Dx12 => "dx12",
+      RendererBackend::Gl => "gl",
+      RendererBackend::Software => "software",
+    }
+  }
+}
+
+impl fmt::Display for RendererBackend {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      RendererBackend::Auto => "Automatic",
+      RendererBackend::Vulkan => "Vulkan",
+      RendererBackend::Metal => "Metal",
+      RendererBackend::Dx12 => "DirectX 12",
+      RendererBackend::Gl => "OpenGL",
+      RendererBackend::Software => "Software",
+    })
+  }
+}
+
+/// Which GPU to pick when there's more than one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PowerPreference {
+  #[default]
+  Default,
+  /// Usually the integrated GPU, to save battery.
+  LowPower,
+  /// Usually the discrete GPU.
+  HighPerformance,
+}
+
+impl PowerPreference {
+  pub const ALL: [PowerPreference; 3] =
+    [PowerPreference::Default, PowerPreference::LowPower, PowerPreference::HighPerformance];
+
+  /// Parses a `--gpu` value: `default`, `low-power` or `high-performance`.
+  pub fn from_arg(arg: &str) -> Option<Self> {
+    match arg.to_ascii_lowercase().as_str() {
+      "default" => Some(PowerPreference::Default),
+      "low-power" => Some(PowerPreference::LowPower),
+      "high-performance" => Some(PowerPreference::HighPerformance),
+      _ => None,
+    }
+  }
+}
+
+impl fmt::Display for PowerPreference {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      PowerPreference::Default => "Default",
+      PowerPreference::LowPower => "Integrated",
+      PowerPreference::HighPerformance => "Discrete",
+    })
+  }
+}
+
+/// How windows are drawn. Only read at startup, so changes apply after a restart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+  pub backend: RendererBackend,
+  pub power: PowerPreference,
+}
+
+impl GraphicsSettings {
+  /// `saved`, overridden by `--renderer <backend>` and `--gpu <preference>`.
+  pub fn from_args(args: &[String], saved: GraphicsSettings) -> Self {
+    let value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+    Self {
+      backend: value("--renderer")
+        .and_then(|arg| RendererBackend::from_arg(arg))
+        .unwrap_or(saved.backend),
+      power: value("--gpu").and_then(|arg| PowerPreference::from_arg(arg)).unwrap_or(saved.power),
+    }
+  }
+
+  /// Points iced at the chosen backend and GPU through the environment it
+  /// reads them from. A GPU API that fails to start still falls back to
+  /// software. Has to run before the first window opens, while the app is
+  /// still single-threaded.
+  pub fn apply(self) {
+    let wgpu_backend = match self.backend {
+      RendererBackend::Auto => None,
+      RendererBackend::Software => {
+        set_env("ICED_BACKEND", SOFTWARE_BACKEND);
+        None
+      }
+      RendererBackend::Vulkan => Some("vulkan"),
+      RendererBackend::Metal => Some("metal"),
+      RendererBackend::Dx12 => Some("dx12"),
+      RendererBackend::Gl => Some("gl"),
+    };
+    if let Some(wgpu_backend) = wgpu_backend {
+      set_env("ICED_BACKEND", &format!("wgpu,{}", SOFTWARE_BACKEND));
+      set_env("WGPU_BACKEND", wgpu_backend);
+    }
+    match self.power {
+      PowerPreference::Default => {}
+      PowerPreference::LowPower => set_env("WGPU_POWER_PREF", "low"),
+      PowerPreference::HighPerformance => set_env("WGPU_POWER_PREF", "high"),
+    }
+  }
+}
+
+fn set_env(key: &str, value: &str) {
+  // SAFETY: only called from `apply`, before any other thread is started
+  unsafe { std::env::set_var(key, value) };
+}
+
+/// Whether iced ended up drawing with the CPU.
+pub fn is_software(information: &iced::system::Information) -> bool {
+  information.graphics_backend == SOFTWARE_BACKEND
+}
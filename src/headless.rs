@@ -0,0 +1,226 @@
+use rodio::Source;
+use serde::Serialize;
+use std::{
+  fmt,
+  fs::File,
+  io::{self, BufWriter, Write},
+  path::Path,
+  time::{Duration, Instant},
+};
+
+use crate::analysis::{Analyser, AnalysisSettings};
+use crate::app::{AudioVisualizer, Message};
+use crate::components::{energy::BandEnergy, loudness::LoudnessMeter};
+use crate::config::Config;
+use crate::decode::{AudioDecoder, DecodeError};
+
+/// Frames dumped per second of audio.
+pub const HEADLESS_FPS: u32 = 60;
+
+#[derive(Debug)]
+pub enum HeadlessError {
+  Io(io::Error),
+  Decode(DecodeError),
+  Json(serde_json::Error),
+}
+
+impl fmt::Display for HeadlessError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      HeadlessError::Io(e) => write!(f, "{}", e),
+      HeadlessError::Decode(e) => write!(f, "{}", e),
+      HeadlessError::Json(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl From<io::Error> for HeadlessError {
+  fn from(e: io::Error) -> Self {
+    HeadlessError::Io(e)
+  }
+}
+
+impl From<DecodeError> for HeadlessError {
+  fn from(e: DecodeError) -> Self {
+    HeadlessError::Decode(e)
+  }
+}
+
+impl From<serde_json::Error> for HeadlessError {
+  fn from(e: serde_json::Error) -> Self {
+    HeadlessError::Json(e)
+  }
+}
+
+/// Options for `--headless`, read from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadlessArgs {
+  pub input: String,
+  pub output: String,
+  /// Also dump each frame's FFT magnitudes, not just the bars.
+  pub fft: bool,
+}
+
+impl HeadlessArgs {
+  /// `--headless <file> [--out <path>] [--fft]`, or `None` without `--headless`.
+  pub fn parse(args: &[String]) -> Option<Self> {
+    let value =
+      |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1)).cloned();
+    Some(Self {
+      input: value("--headless")?,
+      output: value("--out").unwrap_or_else(|| "spectrum.json".to_string()),
+      fft: args.iter().any(|arg| arg == "--fft"),
+    })
+  }
+}
+
+#[derive(Serialize)]
+struct SpectrumDump {
+  sample_rate: u32,
+  fft_size: usize,
+  fps: u32,
+  frames: Vec<SpectrumFrame>,
+}
+
+#[derive(Serialize)]
+struct SpectrumFrame {
+  /// Seconds into the file.
+  time: f32,
+  /// Bar heights exactly as the window would draw them.
+  bars: Vec<f32>,
+  /// Magnitude of each FFT bin, all channels mixed.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  fft: Option<Vec<f32>>,
+}
+
+/// Everything the offline analysis finds at one point in a file.
+#[derive(Debug, Clone)]
+pub struct OfflineFrame {
+  /// Seconds into the file.
+  pub time: f32,
+  /// Bar heights exactly as the window would draw them.
+  pub bars: Vec<f32>,
+  /// Strength of a beat detected since the last frame.
+  pub beat: Option<f32>,
+  /// Loudness in LUFS, once enough of the file has gone by.
+  pub momentary: Option<f32>,
+  pub short_term: Option<f32>,
+  /// Bass, mid and treble energy, smoothed like the live view's.
+  pub energy: BandEnergy,
+  /// Magnitude of each FFT bin, all channels mixed, if asked for.
+  pub fft: Option<Vec<f32>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct OfflineAnalysis {
+  pub sample_rate: u32,
+  pub fft_size: usize,
+  pub frames: Vec<OfflineFrame>,
+}
+
+/// Analyses a file offline with the saved settings.
+///
+/// Frames are taken at [`HEADLESS_FPS`] and go through the same smoothing
+/// as the live view, independent of real time and without opening a window.
+pub fn analyse(input: &Path, fft: bool) -> Result<OfflineAnalysis, HeadlessError> {
+  let decoder = AudioDecoder::open(input)?;
+  let sample_rate = decoder.sample_rate();
+  let channels = decoder.channels();
+  let samples: Vec<f32> = decoder.convert_samples::<f32>().collect();
+  let channel_count = channels.max(1) as usize;
+  let audio_frames = samples.len() / channel_count;
+
+  let mut app = AudioVisualizer { sample_rate, channels, ..AudioVisualizer::default() };
+  app.apply_config(&Config::load());
+  app.visuals.update_interval = Duration::from_secs(1) / HEADLESS_FPS;
+  // Ticks only take in new frames while playing
+  app.player.is_playing = true;
+
+  let analysis_settings =
+    AnalysisSettings { channels, sample_rate, ..*app.analysis_settings.lock().unwrap() };
+  let mut analyser = Analyser::new(analysis_settings);
+  let mut meter = LoudnessMeter::new(channels, sample_rate);
+  let fft_size = analysis_settings.fft_size;
+
+  let count = audio_frames as u64 * HEADLESS_FPS as u64 / sample_rate.max(1) as u64;
+  let mut frames = Vec::with_capacity(count as usize);
+  let mut metered = 0;
+  for index in 0..count {
+    let end = (index * sample_rate as u64 / HEADLESS_FPS as u64) as usize;
+    meter.add(&samples[metered * channel_count..end * channel_count], channels, sample_rate);
+    metered = end;
+
+    // The audio leading up to this frame, silence-padded at the start
+    let start = end.saturating_sub(fft_size);
+    let mut streams =
+      vec![vec![0.0; fft_size - (end - start)]; analysis_settings.channel_mode.streams()];
+    for frame in samples[start * channel_count..end * channel_count].chunks_exact(channel_count) {
+      analysis_settings.channel_mode.push_frame(frame, &mut streams);
+    }
+    let mut frame = analyser.frame(&streams, 1.0 / HEADLESS_FPS as f32);
+    let fft = fft.then(|| frame.mixed());
+
+    let beat = frame.beat.take();
+    if let Some(strength) = beat {
+      let _ = app.update(Message::Beat(strength));
+    }
+    app.audio_data.lock().unwrap().push(frame, Instant::now());
+    let _ = app.update(Message::Tick);
+
+    frames.push(OfflineFrame {
+      time: end as f32 / sample_rate as f32,
+      bars: app.frequency_data.clone(),
+      beat,
+      momentary: meter.momentary(),
+      short_term: meter.short_term(),
+      energy: app.energy,
+      fft,
+    });
+  }
+  Ok(OfflineAnalysis { sample_rate, fft_size, frames })
+}
+
+/// Analyses `args.input` offline and writes the frames to `args.output`:
+/// CSV for a `.csv` extension, otherwise JSON. Returns how many frames
+/// were written.
+pub fn run(args: &HeadlessArgs) -> Result<usize, HeadlessError> {
+  let OfflineAnalysis { sample_rate, fft_size, frames } =
+    analyse(Path::new(&args.input), args.fft)?;
+  let frames: Vec<SpectrumFrame> = frames
+    .into_iter()
+    .map(|frame| SpectrumFrame { time: frame.time, bars: frame.bars, fft: frame.fft })
+    .collect();
+
+  let written = frames.len();
+  let mut writer = BufWriter::new(File::create(&args.output)?);
+  let is_csv = Path::new(&args.output)
+    .extension()
+    .is_some_and(|extension| extension.eq_ignore_ascii_case("csv"));
+  if is_csv {
+    write_csv(&mut writer, &frames)?;
+  } else {
+    let dump = SpectrumDump { sample_rate, fft_size, fps: HEADLESS_FPS, frames };
+    serde_json::to_writer(&mut writer, &dump)?;
+  }
+  writer.flush()?;
+  Ok(written)
+}
+
+/// One row per frame: the time, then each bar, then each FFT bin if dumped.
+fn write_csv(writer: &mut impl Write, frames: &[SpectrumFrame]) -> io::Result<()> {
+  let Some(first) = frames.first() else {
+    return Ok(());
+  };
+  let mut header = vec!["time".to_string()];
+  header.extend((0..first.bars.len()).map(|i| format!("bar_{}", i)));
+  header.extend((0..first.fft.as_ref().map_or(0, Vec::len)).map(|i| format!("bin_{}", i)));
+  writeln!(writer, "{}", header.join(","))?;
+
+  for frame in frames {
+    let values = frame.bars.iter().chain(frame.fft.iter().flatten());
+    let row: Vec<String> =
+      std::iter::once(frame.time).chain(values.copied()).map(|value| value.to_string()).collect();
+    writeln!(writer, "{}", row.join(","))?;
+  }
+  Ok(())
+}
@@ -0,0 +1,318 @@
+use iced::widget::image;
+use rodio::Source;
+use rusty_chromaprint::{Configuration, FingerprintCompressor, Fingerprinter};
+use serde::Deserialize;
+use std::{
+  collections::VecDeque,
+  fmt,
+  io::{self, Read},
+  path::Path,
+  sync::{Arc, Mutex},
+  time::{Duration, Instant},
+};
+
+use crate::capture::{CaptureError, InputCapture};
+use crate::decode::{AudioDecoder, DecodeError};
+
+const ACOUSTID_URL: &str = "https://api.acoustid.org/v2/lookup";
+const MUSICBRAINZ_URL: &str = "https://musicbrainz.org/ws/2/recording";
+const COVER_ART_URL: &str = "https://coverartarchive.org/release-group";
+/// MusicBrainz asks every client to identify itself.
+const USER_AGENT: &str = concat!(
+  "rust_audio_visualiser/",
+  env!("CARGO_PKG_VERSION"),
+  " ( https://github.com/paulm17/rust_audio_visualiser )"
+);
+/// AcoustID only needs the opening of a track to match it.
+const FINGERPRINT_SECONDS: u64 = 120;
+/// How long loopback audio is recorded for a fingerprint.
+const CAPTURE_SECONDS: u64 = 30;
+/// Matches scoring below this are treated as no match.
+const MIN_SCORE: f32 = 0.5;
+
+#[derive(Debug)]
+pub enum IdentifyError {
+  Io(io::Error),
+  Decode(DecodeError),
+  Capture(CaptureError),
+  /// The audio format was one the fingerprinter can't take.
+  Fingerprint,
+  Http(Box<ureq::Error>),
+  /// AcoustID answered but with an error of its own.
+  Service(String),
+  NoMatch,
+}
+
+impl fmt::Display for IdentifyError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      IdentifyError::Io(e) => write!(f, "{}", e),
+      IdentifyError::Decode(e) => write!(f, "{}", e),
+      IdentifyError::Capture(e) => write!(f, "{}", e),
+      IdentifyError::Fingerprint => f.write_str("couldn't fingerprint the audio"),
+      IdentifyError::Http(e) => write!(f, "{}", e),
+      IdentifyError::Service(message) => write!(f, "AcoustID: {}", message),
+      IdentifyError::NoMatch => f.write_str("no matching recording found"),
+    }
+  }
+}
+
+impl From<io::Error> for IdentifyError {
+  fn from(e: io::Error) -> Self {
+    IdentifyError::Io(e)
+  }
+}
+
+impl From<DecodeError> for IdentifyError {
+  fn from(e: DecodeError) -> Self {
+    IdentifyError::Decode(e)
+  }
+}
+
+impl From<ureq::Error> for IdentifyError {
+  fn from(e: ureq::Error) -> Self {
+    IdentifyError::Http(Box::new(e))
+  }
+}
+
+/// What's known about the track playing, for the now-playing overlay.
+#[derive(Debug, Clone)]
+pub struct TrackInfo {
+  pub title: String,
+  pub artist: String,
+  pub album: Option<String>,
+  pub art: Option<image::Handle>,
+  /// MusicBrainz recording the match came from.
+  pub recording_id: Option<String>,
+}
+
+/// Fingerprints the opening of `path`, looks it up on AcoustID and fills in
+/// the details from MusicBrainz. Blocks on decoding and the network.
+pub fn identify_file(path: &Path, api_key: &str) -> Result<TrackInfo, IdentifyError> {
+  let decoder = AudioDecoder::open(path)?;
+  let sample_rate = decoder.sample_rate();
+  let channels = decoder.channels();
+  let total = decoder.total_duration();
+
+  // The lookup needs the whole track's length, so count past the fingerprint
+  // when the decoder can't say
+  let keep = (FINGERPRINT_SECONDS * sample_rate as u64 * channels as u64) as usize;
+  let mut samples = Vec::with_capacity(keep);
+  let mut count = 0usize;
+  for sample in decoder {
+    if samples.len() < keep {
+      samples.push(sample);
+    } else if total.is_some() {
+      break;
+    }
+    count += 1;
+  }
+  let duration = total.unwrap_or_else(|| {
+    Duration::from_secs_f64(count as f64 / (sample_rate as f64 * channels.max(1) as f64))
+  });
+
+  let fingerprint = fingerprint(&samples, sample_rate, channels)?;
+  let mut info = lookup(api_key, &fingerprint, duration)?;
+  // AcoustID's metadata is enough to show, so a failed fetch only loses detail
+  if let Err(e) = musicbrainz(&mut info) {
+    eprintln!("Failed to fetch MusicBrainz metadata: {}", e);
+  }
+  Ok(info)
+}
+
+/// Records whatever the OS is playing for a while and looks it up. AcoustID
+/// matches from the start of a recording, so this works best started along
+/// with the track. Blocks for the recording and the network.
+pub fn identify_system(api_key: &str) -> Result<TrackInfo, IdentifyError> {
+  let waveform = Arc::new(Mutex::new(VecDeque::new()));
+  let (capture, track) =
+    InputCapture::system(waveform, Arc::new(Mutex::new(None))).map_err(IdentifyError::Capture)?;
+
+  let length = Duration::from_secs(CAPTURE_SECONDS);
+  let mut samples = Vec::new();
+  let deadline = Instant::now() + length;
+  while let Some(remaining) = deadline.checked_duration_since(Instant::now())
+    && let Ok(chunk) = track.samples.recv_timeout(remaining)
+  {
+    samples.extend(chunk.iter().map(|&x| (x.clamp(-1.0, 1.0) * i16::MAX as f32) as i16));
+  }
+  drop(capture);
+
+  let fingerprint = fingerprint(&samples, track.sample_rate, track.channels)?;
+  lookup(api_key, &fingerprint, length)
+}
+
+/// Chromaprint fingerprint of interleaved samples, compressed and encoded the
+/// way AcoustID expects.
+fn fingerprint(samples: &[i16], sample_rate: u32, channels: u16) -> Result<String, IdentifyError> {
+  let config = Configuration::preset_test2();
+  let mut printer = Fingerprinter::new(&config);
+  printer.start(sample_rate, channels as u32).map_err(|_| IdentifyError::Fingerprint)?;
+  printer.consume(samples);
+  printer.finish();
+  let compressed = FingerprintCompressor::from(&config).compress(printer.fingerprint());
+  Ok(base64_url(&compressed))
+}
+
+#[derive(Deserialize)]
+struct LookupResponse {
+  status: String,
+  #[serde(default)]
+  results: Vec<LookupResult>,
+  error: Option<ServiceError>,
+}
+
+#[derive(Deserialize)]
+struct ServiceError {
+  message: String,
+}
+
+#[derive(Deserialize)]
+struct LookupResult {
+  score: f32,
+  #[serde(default)]
+  recordings: Vec<Recording>,
+}
+
+#[derive(Deserialize)]
+struct Recording {
+  id: String,
+  title: Option<String>,
+  #[serde(default)]
+  artists: Vec<Named>,
+  #[serde(default)]
+  releasegroups: Vec<ReleaseGroup>,
+}
+
+#[derive(Deserialize)]
+struct Named {
+  name: String,
+}
+
+#[derive(Deserialize)]
+struct ReleaseGroup {
+  id: String,
+  title: Option<String>,
+}
+
+/// Asks AcoustID for the best recording matching the fingerprint, then
+/// fetches its release group's front cover if the Cover Art Archive has one.
+fn lookup(
+  api_key: &str,
+  fingerprint: &str,
+  duration: Duration,
+) -> Result<TrackInfo, IdentifyError> {
+  let response: LookupResponse = ureq::get(ACOUSTID_URL)
+    .query("client", api_key)
+    .query("duration", &duration.as_secs().to_string())
+    .query("fingerprint", fingerprint)
+    .query("meta", "recordings releasegroups")
+    .call()?
+    .into_json()?;
+  if response.status != "ok" {
+    let message = response.error.map_or(response.status, |e| e.message);
+    return Err(IdentifyError::Service(message));
+  }
+
+  let recording = response
+    .results
+    .into_iter()
+    .filter(|result| result.score >= MIN_SCORE)
+    .flat_map(|result| result.recordings)
+    .find(|recording| recording.title.is_some())
+    .ok_or(IdentifyError::NoMatch)?;
+  let release_group = recording.releasegroups.into_iter().next();
+
+  // Missing art shouldn't lose the match
+  let art = release_group.as_ref().and_then(|group| match front_cover(&group.id) {
+    Ok(bytes) => Some(image::Handle::from_bytes(bytes)),
+    Err(e) => {
+      eprintln!("Failed to fetch cover art: {}", e);
+      None
+    }
+  });
+
+  Ok(TrackInfo {
+    title: recording.title.unwrap_or_default(),
+    artist: recording.artists.into_iter().map(|artist| artist.name).collect::<Vec<_>>().join(", "),
+    album: release_group.and_then(|group| group.title),
+    art,
+    recording_id: Some(recording.id),
+  })
+}
+
+#[derive(Deserialize)]
+struct MbRecording {
+  title: String,
+  #[serde(rename = "artist-credit", default)]
+  artist_credit: Vec<ArtistCredit>,
+  #[serde(default)]
+  releases: Vec<MbRelease>,
+}
+
+#[derive(Deserialize)]
+struct ArtistCredit {
+  name: String,
+  #[serde(default)]
+  joinphrase: String,
+}
+
+#[derive(Deserialize)]
+struct MbRelease {
+  title: String,
+  status: Option<String>,
+}
+
+/// Replaces AcoustID's title, artist and album with MusicBrainz's own for
+/// the matched recording, which credits artists as printed on the release.
+fn musicbrainz(info: &mut TrackInfo) -> Result<(), IdentifyError> {
+  let Some(id) = &info.recording_id else {
+    return Ok(());
+  };
+  let recording: MbRecording = ureq::get(&format!("{}/{}", MUSICBRAINZ_URL, id))
+    .set("User-Agent", USER_AGENT)
+    .query("inc", "artist-credits releases")
+    .query("fmt", "json")
+    .call()?
+    .into_json()?;
+
+  info.title = recording.title;
+  if !recording.artist_credit.is_empty() {
+    info.artist = recording
+      .artist_credit
+      .iter()
+      .map(|credit| credit.name.clone() + &credit.joinphrase)
+      .collect();
+  }
+  // Official releases over bootlegs and promos
+  let release = recording
+    .releases
+    .iter()
+    .find(|release| release.status.as_deref() == Some("Official"))
+    .or(recording.releases.first());
+  if let Some(release) = release {
+    info.album = Some(release.title.clone());
+  }
+  Ok(())
+}
+
+fn front_cover(release_group: &str) -> Result<Vec<u8>, IdentifyError> {
+  let url = format!("{}/{}/front-250", COVER_ART_URL, release_group);
+  let mut bytes = Vec::new();
+  ureq::get(&url).call()?.into_reader().read_to_end(&mut bytes)?;
+  Ok(bytes)
+}
+
+/// URL-safe base64 without padding, as Chromaprint encodes fingerprints.
+fn base64_url(bytes: &[u8]) -> String {
+  const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+  let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+  for chunk in bytes.chunks(3) {
+    let group =
+      chunk.iter().enumerate().fold(0u32, |group, (i, &b)| group | ((b as u32) << (16 - 8 * i)));
+    for i in 0..=chunk.len() {
+      encoded.push(ALPHABET[((group >> (18 - 6 * i)) & 0x3f) as usize] as char);
+    }
+  }
+  encoded
+}
@@ -0,0 +1,97 @@
+use rodio::{OutputStream, PlayError, Sink, StreamError, buffer::SamplesBuffer};
+use std::{
+  fmt, io,
+  path::Path,
+  time::{Duration, Instant},
+};
+
+use crate::capture::{CaptureError, InputCapture};
+use crate::components::sweep::{self, Sweep};
+use crate::encode::{self, WavDepth};
+
+const SWEEP_LOW_HZ: f32 = 20.0;
+const SWEEP_HIGH_HZ: f32 = 20_000.0;
+const SWEEP_SECONDS: f32 = 5.0;
+/// Keeps the sweep well clear of clipping on the way out.
+const SWEEP_AMPLITUDE: f32 = 0.5;
+/// Recording keeps going this long after the sweep, for the room's decay.
+const TAIL_SECONDS: f32 = 1.5;
+/// Kept before the direct sound so its onset isn't cut off.
+const PRE_SECONDS: f32 = 0.005;
+const IMPULSE_SECONDS: f32 = 1.0;
+/// FFT size the frequency response is read at.
+const RESPONSE_FFT_SIZE: usize = 16384;
+
+#[derive(Debug)]
+pub enum ImpulseError {
+  Capture(CaptureError),
+  Output(StreamError),
+  Play(PlayError),
+  /// The mic stopped delivering before the sweep was recorded.
+  NoRecording,
+}
+
+impl fmt::Display for ImpulseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      ImpulseError::Capture(e) => write!(f, "{}", e),
+      ImpulseError::Output(e) => write!(f, "{}", e),
+      ImpulseError::Play(e) => write!(f, "{}", e),
+      ImpulseError::NoRecording => f.write_str("nothing was recorded"),
+    }
+  }
+}
+
+/// A measured impulse response and the frequency response read from it.
+#[derive(Debug, Clone)]
+pub struct ImpulseResponse {
+  pub samples: Vec<f32>,
+  pub sample_rate: u32,
+  /// Magnitude in dB per bin, `sample_rate / RESPONSE_FFT_SIZE` Hz apart.
+  pub response: Vec<f32>,
+}
+
+impl ImpulseResponse {
+  /// Plays a log sweep through the default output while recording the
+  /// default input, then deconvolves the recording. Blocks for the length of
+  /// the sweep plus its tail.
+  pub fn measure() -> Result<Self, ImpulseError> {
+    // Recording starts first so the sweep's onset is never missed
+    let (microphone, track) = InputCapture::microphone().map_err(ImpulseError::Capture)?;
+    let sample_rate = track.sample_rate;
+    let channels = track.channels.max(1) as usize;
+
+    let high = SWEEP_HIGH_HZ.min(sample_rate as f32 * 0.45);
+    let sweep = Sweep::new(SWEEP_LOW_HZ, high, SWEEP_SECONDS, sample_rate, SWEEP_AMPLITUDE);
+
+    let (_stream, handle) = OutputStream::try_default().map_err(ImpulseError::Output)?;
+    let sink = Sink::try_new(&handle).map_err(ImpulseError::Play)?;
+    sink.append(SamplesBuffer::new(1, sample_rate, sweep.samples.clone()));
+
+    let mut recording = Vec::new();
+    let deadline = Instant::now() + Duration::from_secs_f32(SWEEP_SECONDS + TAIL_SECONDS);
+    while let Some(remaining) = deadline.checked_duration_since(Instant::now())
+      && let Ok(chunk) = track.samples.recv_timeout(remaining)
+    {
+      recording
+        .extend(chunk.chunks(channels).map(|frame| frame.iter().sum::<f32>() / frame.len() as f32));
+    }
+    drop(microphone);
+    if recording.is_empty() {
+      return Err(ImpulseError::NoRecording);
+    }
+
+    let samples = sweep.impulse_response(
+      &recording,
+      (PRE_SECONDS * sample_rate as f32) as usize,
+      (IMPULSE_SECONDS * sample_rate as f32) as usize,
+    );
+    let response = sweep::frequency_response(&samples, RESPONSE_FFT_SIZE);
+    Ok(Self { samples, sample_rate, response })
+  }
+
+  /// Writes the impulse response as a mono 32-bit float WAV file.
+  pub fn save_wav(&self, path: &Path) -> io::Result<()> {
+    encode::write_wav(path, &self.samples, 1, self.sample_rate, WavDepth::Float32)
+  }
+}
@@ -0,0 +1,146 @@
+use iced::keyboard::Key;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Something a keyboard shortcut can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+  PlayPause,
+  Stop,
+  OpenFile,
+  SeekBackward,
+  SeekForward,
+  VolumeUp,
+  VolumeDown,
+  NextSection,
+  PreviousSection,
+  Presentation,
+  Snapshot,
+}
+
+impl Action {
+  pub const ALL: [Action; 11] = [
+    Action::PlayPause,
+    Action::Stop,
+    Action::OpenFile,
+    Action::SeekBackward,
+    Action::SeekForward,
+    Action::VolumeUp,
+    Action::VolumeDown,
+    Action::NextSection,
+    Action::PreviousSection,
+    Action::Presentation,
+    Action::Snapshot,
+  ];
+}
+
+impl fmt::Display for Action {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Action::PlayPause => "Play / pause",
+      Action::Stop => "Stop",
+      Action::OpenFile => "Open file",
+      Action::SeekBackward => "Seek back",
+      Action::SeekForward => "Seek forward",
+      Action::VolumeUp => "Volume up",
+      Action::VolumeDown => "Volume down",
+      Action::NextSection => "Next section",
+      Action::PreviousSection => "Previous section",
+      Action::Presentation => "Presentation mode",
+      Action::Snapshot => "Save snapshot",
+    })
+  }
+}
+
+/// Which key triggers each action, by the names [`key_name`] gives them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+  pub play_pause: String,
+  pub stop: String,
+  pub open_file: String,
+  pub seek_backward: String,
+  pub seek_forward: String,
+  pub volume_up: String,
+  pub volume_down: String,
+  pub next_section: String,
+  pub previous_section: String,
+  pub presentation: String,
+  pub snapshot: String,
+}
+
+impl Keymap {
+  pub fn key(&self, action: Action) -> &str {
+    match action {
+      Action::PlayPause => &self.play_pause,
+      Action::Stop => &self.stop,
+      Action::OpenFile => &self.open_file,
+      Action::SeekBackward => &self.seek_backward,
+      Action::SeekForward => &self.seek_forward,
+      Action::VolumeUp => &self.volume_up,
+      Action::VolumeDown => &self.volume_down,
+      Action::NextSection => &self.next_section,
+      Action::PreviousSection => &self.previous_section,
+      Action::Presentation => &self.presentation,
+      Action::Snapshot => &self.snapshot,
+    }
+  }
+
+  fn key_mut(&mut self, action: Action) -> &mut String {
+    match action {
+      Action::PlayPause => &mut self.play_pause,
+      Action::Stop => &mut self.stop,
+      Action::OpenFile => &mut self.open_file,
+      Action::SeekBackward => &mut self.seek_backward,
+      Action::SeekForward => &mut self.seek_forward,
+      Action::VolumeUp => &mut self.volume_up,
+      Action::VolumeDown => &mut self.volume_down,
+      Action::NextSection => &mut self.next_section,
+      Action::PreviousSection => &mut self.previous_section,
+      Action::Presentation => &mut self.presentation,
+      Action::Snapshot => &mut self.snapshot,
+    }
+  }
+
+  /// Binds `key` to `action`. An action already on that key swaps over to
+  /// `action`'s old key, so no key ever does two things.
+  pub fn bind(&mut self, action: Action, key: String) {
+    if let Some(other) = self.action(&key) {
+      let previous = self.key(action).to_string();
+      *self.key_mut(other) = previous;
+    }
+    *self.key_mut(action) = key;
+  }
+
+  pub fn action(&self, key: &str) -> Option<Action> {
+    Action::ALL.into_iter().find(|&action| self.key(action) == key)
+  }
+}
+
+impl Default for Keymap {
+  fn default() -> Self {
+    Self {
+      play_pause: "Space".to_string(),
+      stop: "S".to_string(),
+      open_file: "O".to_string(),
+      seek_backward: "ArrowLeft".to_string(),
+      seek_forward: "ArrowRight".to_string(),
+      volume_up: "ArrowUp".to_string(),
+      volume_down: "ArrowDown".to_string(),
+      next_section: "PageDown".to_string(),
+      previous_section: "PageUp".to_string(),
+      presentation: "F11".to_string(),
+      snapshot: "F12".to_string(),
+    }
+  }
+}
+
+/// Stable name for a key, as stored in the keymap: named keys by their name
+/// (`Space`, `ArrowLeft`), characters upper-cased.
+pub fn key_name(key: &Key) -> Option<String> {
+  match key {
+    Key::Named(named) => Some(format!("{:?}", named)),
+    Key::Character(character) => Some(character.to_uppercase()),
+    Key::Unidentified => None,
+  }
+}
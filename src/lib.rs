@@ -0,0 +1,98 @@
+//! A music visualiser: the app itself runs through [`run`], and hosts that
+//! bring their own audio, like games, can reuse its analysis through
+//! [`watch`]. The offline analysis is also scriptable from Python with the
+//! `python` feature; see `pyproject.toml`.
+
+mod analysis;
+mod app;
+mod autodj;
+mod capture;
+mod compare;
+mod components;
+mod config;
+mod decode;
+mod encode;
+mod export;
+mod features;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+mod geometry;
+mod graphics;
+mod headless;
+mod identify;
+mod impulse;
+mod keymap;
+mod lyrics;
+mod markers;
+mod measurement;
+mod midi;
+mod normalise;
+mod osc;
+mod outline;
+mod playback;
+pub mod plugins;
+mod presets;
+#[cfg(feature = "python")]
+mod python;
+mod quality;
+mod recording;
+mod remote;
+mod rumble;
+mod server;
+mod session;
+mod shm;
+mod stats;
+mod stem;
+mod suggest;
+mod tags;
+// Only for the integration tests to drive the app with
+#[cfg(feature = "testing")]
+#[doc(hidden)]
+pub mod testing;
+mod tray;
+mod ui;
+pub mod watch;
+use crate::config::Config;
+use crate::features::FeatureArgs;
+use crate::graphics::GraphicsSettings;
+use crate::headless::HeadlessArgs;
+use crate::server::ServerArgs;
+
+/// Runs the visualiser, or a headless analysis or render server when the
+/// arguments ask for one.
+pub fn run() -> iced::Result {
+  let args: Vec<String> = std::env::args().collect();
+  // Headless runs and the server never open a window
+  if let Some(server) = ServerArgs::parse(&args) {
+    if let Err(e) = server::run(&server) {
+      eprintln!("Failed to start the render server: {}", e);
+      std::process::exit(1);
+    }
+    return Ok(());
+  }
+  if let Some(headless) = HeadlessArgs::parse(&args) {
+    match headless::run(&headless) {
+      Ok(frames) => println!("Wrote {} frames to {}", frames, headless.output),
+      Err(e) => {
+        eprintln!("Failed to analyse {}: {}", headless.input, e);
+        std::process::exit(1);
+      }
+    }
+    return Ok(());
+  }
+  if let Some(features) = FeatureArgs::parse(&args) {
+    match features::run(&features) {
+      Ok(frames) => println!("Wrote {} frames to {}", frames, features.output),
+      Err(e) => {
+        eprintln!("Failed to extract features from {}: {}", features.input, e);
+        std::process::exit(1);
+      }
+    }
+    return Ok(());
+  }
+
+  let inspector = args.iter().any(|arg| arg == "--inspector");
+  let graphics = GraphicsSettings::from_args(&args, Config::load().graphics);
+  graphics.apply();
+  app::run(inspector, graphics)
+}
@@ -0,0 +1,93 @@
+//! Time-synced lyrics from an LRC file next to the track, or from its
+//! lyrics tag when that's in LRC form, shown line by line as it plays.
+
+use std::{fs, io, path::Path, time::Duration};
+
+use crate::tags;
+
+/// One line and when it's sung.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LyricLine {
+  pub time: Duration,
+  pub text: String,
+}
+
+/// A track's lyrics, in the order they're sung.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lyrics {
+  pub lines: Vec<LyricLine>,
+}
+
+impl Lyrics {
+  /// Parses LRC: `[mm:ss.xx]` stamps before each line, several when a line
+  /// repeats, and an optional `[offset:±ms]` that moves them all. Other
+  /// tags and unstamped lines are skipped.
+  pub fn parse(lrc: &str) -> Self {
+    let mut offset_ms = 0i64;
+    let mut lines = Vec::new();
+    for line in lrc.lines() {
+      let mut rest = line.trim();
+      let mut times = Vec::new();
+      while let Some(tag) = rest.strip_prefix('[') {
+        let Some((tag, after)) = tag.split_once(']') else {
+          break;
+        };
+        rest = after;
+        if let Some(offset) = tag.strip_prefix("offset:") {
+          offset_ms = offset.trim().parse().unwrap_or(0);
+        } else if let Some(time) = parse_time(tag) {
+          times.push(time);
+        }
+      }
+      let text = rest.trim();
+      lines.extend(times.into_iter().map(|time| (time, text.to_string())));
+    }
+    // A positive offset shows the lines sooner
+    let mut lines: Vec<LyricLine> = lines
+      .into_iter()
+      .map(|(time, text)| LyricLine { time: shift(time, offset_ms), text })
+      .collect();
+    lines.sort_by_key(|line| line.time);
+    Self { lines }
+  }
+
+  /// `<track>.lrc` beside the file, or else an LRC lyrics tag. `None` when
+  /// there's neither, or the tag's lyrics aren't timed.
+  pub fn load(path: &Path) -> Option<Self> {
+    let lrc = match fs::read_to_string(path.with_extension("lrc")) {
+      Ok(lrc) => Some(lrc),
+      Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+      Err(e) => {
+        eprintln!("Failed to read lyrics for {}: {}", path.display(), e);
+        None
+      }
+    };
+    let lrc = lrc.or_else(|| {
+      tags::lyrics(path).unwrap_or_else(|e| {
+        eprintln!("Failed to read the lyrics tag: {}", e);
+        None
+      })
+    })?;
+    let lyrics = Self::parse(&lrc);
+    (!lyrics.lines.is_empty()).then_some(lyrics)
+  }
+
+  /// The line being sung at `position`, if the first has started.
+  pub fn current(&self, position: Duration) -> Option<usize> {
+    self.lines.partition_point(|line| line.time <= position).checked_sub(1)
+  }
+}
+
+/// `mm:ss`, `mm:ss.xx` or `mm:ss:xx`; `None` for any other tag.
+fn parse_time(tag: &str) -> Option<Duration> {
+  let (minutes, seconds) = tag.split_once(':')?;
+  let minutes: u64 = minutes.trim().parse().ok()?;
+  let seconds: f64 = seconds.trim().replacen(':', ".", 1).parse().ok()?;
+  (seconds.is_finite() && seconds >= 0.0)
+    .then(|| Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
+
+fn shift(time: Duration, offset_ms: i64) -> Duration {
+  let offset = Duration::from_millis(offset_ms.unsigned_abs());
+  if offset_ms > 0 { time.saturating_sub(offset) } else { time + offset }
+}
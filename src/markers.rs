@@ -0,0 +1,307 @@
+//! Beats, sections and band hits as markers for video editors, so footage
+//! can be cut to the music. The output extension picks the format:
+//! `.fcpxml` for Final Cut Pro, `.xml` (Final Cut 7 XML) for Premiere,
+//! `.edl` for Resolve and Avid, otherwise CSV.
+
+use std::{
+  fmt, fs,
+  io::{self, BufWriter, Write},
+  path::Path,
+};
+
+use crate::components::{sections::SectionKind, smoothing::Region};
+use crate::export::EXPORT_FPS;
+use crate::headless::{self, HeadlessError, OfflineAnalysis};
+use crate::outline::{OutlineError, TrackOutline};
+
+/// Band energy that counts as a hit.
+const HIT_LEVEL: f32 = 0.7;
+/// Energy a band has to drop back under before it can hit again.
+const REARM_LEVEL: f32 = 0.55;
+/// Shortest gap between two hits in one band, in seconds.
+const MIN_HIT_GAP: f32 = 0.25;
+
+#[derive(Debug)]
+pub enum MarkerError {
+  Io(io::Error),
+  Analysis(HeadlessError),
+  Outline(OutlineError),
+}
+
+impl fmt::Display for MarkerError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MarkerError::Io(e) => write!(f, "{}", e),
+      MarkerError::Analysis(e) => write!(f, "{}", e),
+      MarkerError::Outline(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl From<io::Error> for MarkerError {
+  fn from(e: io::Error) -> Self {
+    MarkerError::Io(e)
+  }
+}
+
+impl From<HeadlessError> for MarkerError {
+  fn from(e: HeadlessError) -> Self {
+    MarkerError::Analysis(e)
+  }
+}
+
+impl From<OutlineError> for MarkerError {
+  fn from(e: OutlineError) -> Self {
+    MarkerError::Outline(e)
+  }
+}
+
+/// What a marker marks.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerKind {
+  Beat,
+  Section(SectionKind),
+  /// A band's energy jumping up, like a kick in the bass.
+  Hit(Region),
+}
+
+impl fmt::Display for MarkerKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MarkerKind::Beat => f.write_str("Beat"),
+      MarkerKind::Section(kind) => write!(f, "{}", kind),
+      MarkerKind::Hit(Region::Low) => f.write_str("Bass hit"),
+      MarkerKind::Hit(Region::Mid) => f.write_str("Mid hit"),
+      MarkerKind::Hit(Region::High) => f.write_str("Treble hit"),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marker {
+  /// Seconds into the track.
+  pub time: f32,
+  pub kind: MarkerKind,
+}
+
+/// Marker file formats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerFormat {
+  FinalCut,
+  Premiere,
+  Edl,
+  Csv,
+}
+
+impl MarkerFormat {
+  pub const EXTENSIONS: [&str; 4] = ["fcpxml", "xml", "edl", "csv"];
+
+  pub fn from_path(path: &Path) -> Self {
+    match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+      Some("fcpxml") => MarkerFormat::FinalCut,
+      Some("xml") => MarkerFormat::Premiere,
+      Some("edl") => MarkerFormat::Edl,
+      _ => MarkerFormat::Csv,
+    }
+  }
+}
+
+/// Every beat, section start and band hit, in time order.
+pub fn collect(analysis: &OfflineAnalysis, outline: &TrackOutline) -> Vec<Marker> {
+  let mut markers: Vec<Marker> = analysis
+    .frames
+    .iter()
+    .filter(|frame| frame.beat.is_some())
+    .map(|frame| Marker { time: frame.time, kind: MarkerKind::Beat })
+    .collect();
+  markers.extend(
+    outline
+      .sections
+      .iter()
+      .map(|section| Marker { time: section.start, kind: MarkerKind::Section(section.kind) }),
+  );
+
+  for region in Region::ALL {
+    let mut armed = true;
+    let mut last_hit = f32::NEG_INFINITY;
+    for frame in &analysis.frames {
+      let level = frame.energy.level(region);
+      if armed && level >= HIT_LEVEL && frame.time - last_hit >= MIN_HIT_GAP {
+        markers.push(Marker { time: frame.time, kind: MarkerKind::Hit(region) });
+        armed = false;
+        last_hit = frame.time;
+      } else if level < REARM_LEVEL {
+        armed = true;
+      }
+    }
+  }
+
+  markers.sort_by(|a, b| a.time.total_cmp(&b.time));
+  markers
+}
+
+/// Analyses `input` and writes its markers to `output`, reusing `outline`
+/// when the track's already been outlined. Returns how many were written.
+pub fn export_markers(
+  input: &Path,
+  output: &Path,
+  outline: Option<TrackOutline>,
+) -> Result<usize, MarkerError> {
+  let analysis = headless::analyse(input, false)?;
+  let outline = match outline {
+    Some(outline) => outline,
+    None => TrackOutline::analyse(input)?,
+  };
+  let markers = collect(&analysis, &outline);
+  let name = input.file_stem().map_or("Track".into(), |stem| stem.to_string_lossy());
+
+  let mut writer = BufWriter::new(fs::File::create(output)?);
+  write(&mut writer, MarkerFormat::from_path(output), &name, outline.duration, &markers)?;
+  writer.flush()?;
+  Ok(markers.len())
+}
+
+pub fn write(
+  writer: &mut impl Write,
+  format: MarkerFormat,
+  name: &str,
+  duration: f32,
+  markers: &[Marker],
+) -> io::Result<()> {
+  match format {
+    MarkerFormat::FinalCut => write_fcpxml(writer, name, duration, markers),
+    MarkerFormat::Premiere => write_xmeml(writer, name, duration, markers),
+    MarkerFormat::Edl => write_edl(writer, name, markers),
+    MarkerFormat::Csv => write_csv(writer, markers),
+  }
+}
+
+/// Final Cut Pro X: markers on a gap as long as the track, which the audio
+/// can be dropped over.
+fn write_fcpxml(
+  writer: &mut impl Write,
+  name: &str,
+  duration: f32,
+  markers: &[Marker],
+) -> io::Result<()> {
+  let name = escape(name);
+  let length = rational(frames(duration));
+  writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+  writeln!(writer, "<!DOCTYPE fcpxml>")?;
+  writeln!(writer, r#"<fcpxml version="1.9">"#)?;
+  writeln!(writer, "  <resources>")?;
+  writeln!(
+    writer,
+    r#"    <format id="r1" frameDuration="1/{}s" width="1920" height="1080"/>"#,
+    EXPORT_FPS
+  )?;
+  writeln!(writer, "  </resources>")?;
+  writeln!(writer, r#"  <library><event name="{}"><project name="{}">"#, name, name)?;
+  writeln!(
+    writer,
+    r#"    <sequence format="r1" duration="{}" tcStart="0s" tcFormat="NDF">"#,
+    length
+  )?;
+  writeln!(writer, "      <spine>")?;
+  writeln!(
+    writer,
+    r#"        <gap name="{}" offset="0s" start="0s" duration="{}">"#,
+    name, length
+  )?;
+  for marker in markers {
+    writeln!(
+      writer,
+      r#"          <marker start="{}" duration="{}" value="{}"/>"#,
+      rational(frames(marker.time)),
+      rational(1),
+      escape(&marker.kind.to_string())
+    )?;
+  }
+  writeln!(writer, "        </gap>")?;
+  writeln!(writer, "      </spine>")?;
+  writeln!(writer, "    </sequence>")?;
+  writeln!(writer, "  </project></event></library>")?;
+  writeln!(writer, "</fcpxml>")
+}
+
+/// Final Cut 7 XML, which Premiere imports as a sequence with markers.
+fn write_xmeml(
+  writer: &mut impl Write,
+  name: &str,
+  duration: f32,
+  markers: &[Marker],
+) -> io::Result<()> {
+  writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+  writeln!(writer, "<!DOCTYPE xmeml>")?;
+  writeln!(writer, r#"<xmeml version="5">"#)?;
+  writeln!(writer, "  <sequence>")?;
+  writeln!(writer, "    <name>{}</name>", escape(name))?;
+  writeln!(writer, "    <duration>{}</duration>", frames(duration))?;
+  writeln!(writer, "    <rate><timebase>{}</timebase><ntsc>FALSE</ntsc></rate>", EXPORT_FPS)?;
+  for marker in markers {
+    writeln!(
+      writer,
+      "    <marker><name>{}</name><comment></comment><in>{}</in><out>-1</out></marker>",
+      escape(&marker.kind.to_string()),
+      frames(marker.time)
+    )?;
+  }
+  writeln!(writer, "    <media><video></video><audio></audio></media>")?;
+  writeln!(writer, "  </sequence>")?;
+  writeln!(writer, "</xmeml>")
+}
+
+/// CMX 3600 with one single-frame event per marker and the marker in its
+/// notes, as Resolve writes them.
+fn write_edl(writer: &mut impl Write, name: &str, markers: &[Marker]) -> io::Result<()> {
+  writeln!(writer, "TITLE: {}", name)?;
+  writeln!(writer, "FCM: NON-DROP FRAME")?;
+  for (index, marker) in markers.iter().enumerate() {
+    let start = frames(marker.time);
+    let (from, to) = (timecode(start), timecode(start + 1));
+    writeln!(writer)?;
+    writeln!(writer, "{:03}  001      V     C        {} {} {} {}", index + 1, from, to, from, to)?;
+    writeln!(writer, " |C:{} |M:{} |D:1", colour(marker.kind), marker.kind)?;
+  }
+  Ok(())
+}
+
+fn write_csv(writer: &mut impl Write, markers: &[Marker]) -> io::Result<()> {
+  writeln!(writer, "time,timecode,marker")?;
+  for marker in markers {
+    writeln!(writer, "{:.3},{},{}", marker.time, timecode(frames(marker.time)), marker.kind)?;
+  }
+  Ok(())
+}
+
+/// Whole frames at the export frame rate.
+fn frames(seconds: f32) -> u64 {
+  (seconds.max(0.0) * EXPORT_FPS as f32).round() as u64
+}
+
+/// A frame count as FCPXML time.
+fn rational(frames: u64) -> String {
+  format!("{}/{}s", frames, EXPORT_FPS)
+}
+
+/// HH:MM:SS:FF, non-drop.
+fn timecode(frames: u64) -> String {
+  let fps = EXPORT_FPS as u64;
+  let seconds = frames / fps;
+  format!("{:02}:{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60, frames % fps)
+}
+
+/// Resolve's marker colours, so each kind stands apart on the timeline.
+fn colour(kind: MarkerKind) -> &'static str {
+  match kind {
+    MarkerKind::Beat => "ResolveColorBlue",
+    MarkerKind::Section(_) => "ResolveColorGreen",
+    MarkerKind::Hit(Region::Low) => "ResolveColorRed",
+    MarkerKind::Hit(Region::Mid) => "ResolveColorYellow",
+    MarkerKind::Hit(Region::High) => "ResolveColorCyan",
+  }
+}
+
+fn escape(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,144 @@
+use rustfft::{FftPlanner, num_complex::Complex};
+use std::{
+  sync::{Arc, Mutex, mpsc},
+  thread,
+};
+
+use crate::capture::{CaptureError, InputCapture};
+use crate::components::{
+  delay::{self, Delay},
+  tap::ChunkSlot,
+  transfer::TransferFunction,
+  window_fn::WindowFunction,
+};
+
+/// Longest acoustic delay searched for.
+const MAX_DELAY_SECONDS: f32 = 0.5;
+/// How often the delay is re-estimated.
+const DELAY_INTERVAL_SECONDS: f32 = 0.5;
+
+/// A running dual-channel measurement: whatever is playing is the reference,
+/// the default input (a measurement mic) is the system under test.
+pub struct Measurement {
+  _microphone: InputCapture,
+  reference: ChunkSlot,
+  pub transfer: Arc<Mutex<TransferFunction>>,
+  /// Latest estimate of how far the mic lags the reference.
+  pub delay: Arc<Mutex<Option<Delay>>>,
+  pub sample_rate: u32,
+}
+
+impl Measurement {
+  /// Opens the mic and starts comparing it against the chunks sent to
+  /// `reference`, which carry `reference_channels` interleaved channels at
+  /// `reference_rate`.
+  pub fn start(
+    reference: ChunkSlot,
+    reference_channels: u16,
+    reference_rate: u32,
+    fft_size: usize,
+  ) -> Result<Self, CaptureError> {
+    let (microphone, track) = InputCapture::microphone()?;
+    if track.sample_rate != reference_rate {
+      return Err(CaptureError::SampleRateMismatch {
+        expected: reference_rate,
+        found: track.sample_rate,
+      });
+    }
+
+    let (sender, reference_samples) = mpsc::channel();
+    *reference.lock().unwrap() = Some(sender);
+
+    let transfer = Arc::new(Mutex::new(TransferFunction::new(fft_size / 2)));
+    let shared = transfer.clone();
+    let delay = Arc::new(Mutex::new(None));
+    let shared_delay = delay.clone();
+    let measurement_channels = track.channels;
+    thread::spawn(move || {
+      let mut planner = FftPlanner::new();
+      let fft = planner.plan_fft_forward(fft_size);
+      let window = WindowFunction::Hann.coefficients(fft_size);
+      let hop_size = fft_size / 2;
+      // Drop the oldest audio if one side runs ahead, e.g. while playback is paused
+      let max_buffered = fft_size * 4;
+
+      let mut reference_buffer: Vec<f32> = Vec::new();
+      let mut measurement_buffer: Vec<f32> = Vec::new();
+
+      // Longer stretches of both sides for the delay search, which needs the
+      // reference to still hold what the mic is only now hearing
+      let max_lag = (MAX_DELAY_SECONDS * reference_rate as f32) as usize;
+      let history_length = max_lag * 2;
+      let delay_interval = (DELAY_INTERVAL_SECONDS * reference_rate as f32) as usize;
+      let mut reference_history: Vec<f32> = Vec::new();
+      let mut measurement_history: Vec<f32> = Vec::new();
+      let mut since_estimate = 0;
+
+      let spectrum = |samples: &[f32]| {
+        let mut buffer: Vec<Complex<f32>> =
+          samples.iter().zip(&window).map(|(&x, &w)| Complex::new(x * w, 0.0)).collect();
+        fft.process(&mut buffer);
+        buffer.truncate(fft_size / 2);
+        buffer
+      };
+
+      // The mic never stops, so it paces the loop; it hangs up once the
+      // measurement is dropped
+      while let Ok(samples) = track.samples.recv() {
+        let mixed = mix(&samples, measurement_channels);
+        since_estimate += mixed.len();
+        measurement_buffer.extend_from_slice(&mixed);
+        measurement_history.extend(mixed);
+        for samples in reference_samples.try_iter() {
+          let mixed = mix(&samples, reference_channels);
+          reference_buffer.extend_from_slice(&mixed);
+          reference_history.extend(mixed);
+        }
+        for buffer in [&mut reference_buffer, &mut measurement_buffer] {
+          let excess = buffer.len().saturating_sub(max_buffered);
+          buffer.drain(..excess);
+        }
+        for history in [&mut reference_history, &mut measurement_history] {
+          let excess = history.len().saturating_sub(history_length);
+          history.drain(..excess);
+        }
+
+        if since_estimate >= delay_interval
+          && reference_history.len() == history_length
+          && measurement_history.len() == history_length
+        {
+          since_estimate = 0;
+          // Keep the last good estimate through quiet passages
+          if let Some(estimate) =
+            delay::estimate(&reference_history, &measurement_history, max_lag, reference_rate)
+          {
+            *shared_delay.lock().unwrap() = Some(estimate);
+          }
+        }
+
+        while reference_buffer.len() >= fft_size && measurement_buffer.len() >= fft_size {
+          let reference = spectrum(&reference_buffer[..fft_size]);
+          let measurement = spectrum(&measurement_buffer[..fft_size]);
+          shared.lock().unwrap().add(&reference, &measurement);
+          reference_buffer.drain(..hop_size);
+          measurement_buffer.drain(..hop_size);
+        }
+      }
+    });
+
+    Ok(Self { _microphone: microphone, reference, transfer, delay, sample_rate: reference_rate })
+  }
+}
+
+impl Drop for Measurement {
+  fn drop(&mut self) {
+    // Stop the player copying chunks nobody reads
+    *self.reference.lock().unwrap() = None;
+  }
+}
+
+/// Mixes interleaved `samples` down to mono.
+fn mix(samples: &[f32], channels: u16) -> Vec<f32> {
+  let channels = channels.max(1) as usize;
+  samples.chunks(channels).map(|frame| frame.iter().sum::<f32>() / frame.len() as f32).collect()
+}
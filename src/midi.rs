@@ -0,0 +1,188 @@
+//! MIDI output, so hardware synths and lighting controllers can follow the
+//! analysis: every beat plays a note with its strength as the velocity, and
+//! the bass, mid and treble energy go out as control changes.
+
+use midir::{MidiOutput, MidiOutputConnection};
+use serde::{Deserialize, Serialize};
+use std::{
+  fmt,
+  time::{Duration, Instant},
+};
+
+use crate::components::{energy::BandEnergy, smoothing::Region};
+
+/// How the app shows up to other MIDI software.
+const CLIENT_NAME: &str = "Rust Audio Visualiser";
+/// How long each beat's note is held.
+const NOTE_LENGTH: Duration = Duration::from_millis(100);
+const NOTE_OFF: u8 = 0x80;
+const NOTE_ON: u8 = 0x90;
+const CONTROL_CHANGE: u8 = 0xB0;
+/// Largest note, velocity or controller value.
+pub const MAX_DATA: u8 = 127;
+pub const CHANNELS: std::ops::RangeInclusive<u8> = 1..=16;
+
+/// Where MIDI goes and what it's sent as.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiSettings {
+  pub enabled: bool,
+  /// Output port by name; empty for the first there is.
+  pub port: String,
+  /// 1..=16, as synths number them.
+  pub channel: u8,
+  pub beat_note: u8,
+  /// Controllers the bass, mid and treble energy go to.
+  pub bass_cc: u8,
+  pub mid_cc: u8,
+  pub treble_cc: u8,
+}
+
+impl MidiSettings {
+  fn controller(&self, region: Region) -> u8 {
+    match region {
+      Region::Low => self.bass_cc,
+      Region::Mid => self.mid_cc,
+      Region::High => self.treble_cc,
+    }
+  }
+}
+
+impl Default for MidiSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      port: String::new(),
+      channel: 1,
+      // C1, a kick on General MIDI drums
+      beat_note: 36,
+      // General purpose controllers 1 to 3 are free for anything
+      bass_cc: 16,
+      mid_cc: 17,
+      treble_cc: 18,
+    }
+  }
+}
+
+#[derive(Debug)]
+pub enum MidiError {
+  Init(midir::InitError),
+  NoPorts,
+  PortNotFound(String),
+  Connect(String),
+}
+
+impl fmt::Display for MidiError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      MidiError::Init(e) => write!(f, "MIDI unavailable: {}", e),
+      MidiError::NoPorts => write!(f, "no MIDI outputs"),
+      MidiError::PortNotFound(name) => write!(f, "no MIDI output called {}", name),
+      MidiError::Connect(e) => write!(f, "couldn't connect: {}", e),
+    }
+  }
+}
+
+/// A note or controller number typed in, once it parses and is in range.
+pub fn parse_data(input: &str) -> Option<u8> {
+  input.trim().parse().ok().filter(|&value| value <= MAX_DATA)
+}
+
+/// Names of the MIDI outputs there are now.
+pub fn ports() -> Vec<String> {
+  let Ok(output) = MidiOutput::new(CLIENT_NAME) else {
+    return Vec::new();
+  };
+  output.ports().iter().filter_map(|port| output.port_name(port).ok()).collect()
+}
+
+/// A connection to the configured output.
+pub struct MidiSender {
+  connection: MidiOutputConnection,
+  settings: MidiSettings,
+  /// When the beat note playing now is due to be let go.
+  release_at: Option<Instant>,
+  /// Last value sent to each band's controller, so unchanged ones aren't
+  /// resent every tick.
+  sent: [Option<u8>; 3],
+}
+
+impl MidiSender {
+  pub fn open(settings: &MidiSettings) -> Result<Self, MidiError> {
+    let output = MidiOutput::new(CLIENT_NAME).map_err(MidiError::Init)?;
+    let ports = output.ports();
+    let port = if settings.port.is_empty() {
+      ports.first().ok_or(MidiError::NoPorts)?
+    } else {
+      ports
+        .iter()
+        .find(|port| output.port_name(port).is_ok_and(|name| name == settings.port))
+        .ok_or_else(|| MidiError::PortNotFound(settings.port.clone()))?
+    };
+    let connection =
+      output.connect(port, CLIENT_NAME).map_err(|e| MidiError::Connect(e.to_string()))?;
+    // Out-of-range values from a hand-edited config would corrupt the stream
+    let settings = MidiSettings {
+      channel: settings.channel.clamp(*CHANNELS.start(), *CHANNELS.end()),
+      beat_note: settings.beat_note.min(MAX_DATA),
+      bass_cc: settings.bass_cc.min(MAX_DATA),
+      mid_cc: settings.mid_cc.min(MAX_DATA),
+      treble_cc: settings.treble_cc.min(MAX_DATA),
+      ..settings.clone()
+    };
+    Ok(Self { connection, settings, release_at: None, sent: [None; 3] })
+  }
+
+  /// Plays the beat note for a beat of `strength`, 0.0..=1.0.
+  pub fn send_beat(&mut self, strength: f32) {
+    // A note still held from the last beat is let go first, so it retriggers
+    self.release();
+    let velocity = (strength.clamp(0.0, 1.0) * MAX_DATA as f32).round().max(1.0) as u8;
+    self.send(&[NOTE_ON | self.status_channel(), self.settings.beat_note, velocity]);
+    self.release_at = Some(Instant::now() + NOTE_LENGTH);
+  }
+
+  /// Sends any band energy that's changed, and lets go of the beat note
+  /// once it's been held long enough.
+  pub fn update(&mut self, energy: &BandEnergy) {
+    if self.release_at.is_some_and(|at| Instant::now() >= at) {
+      self.release();
+    }
+    let status = CONTROL_CHANGE | self.status_channel();
+    for (sent, region) in self.sent.iter_mut().zip(Region::ALL) {
+      let value = (energy.level(region).clamp(0.0, 1.0) * MAX_DATA as f32).round() as u8;
+      if *sent != Some(value) {
+        *sent = Some(value);
+        send(&mut self.connection, &[status, self.settings.controller(region), value]);
+      }
+    }
+  }
+
+  fn release(&mut self) {
+    if self.release_at.take().is_some() {
+      self.send(&[NOTE_OFF | self.status_channel(), self.settings.beat_note, 0]);
+    }
+  }
+
+  /// The channel as it goes in a status byte, 0..=15.
+  fn status_channel(&self) -> u8 {
+    self.settings.channel - 1
+  }
+
+  fn send(&mut self, message: &[u8]) {
+    send(&mut self.connection, message);
+  }
+}
+
+impl Drop for MidiSender {
+  /// Never leaves a note hanging on the synth.
+  fn drop(&mut self) {
+    self.release();
+  }
+}
+
+fn send(connection: &mut MidiOutputConnection, message: &[u8]) {
+  if let Err(e) = connection.send(message) {
+    eprintln!("Failed to send MIDI: {}", e);
+  }
+}
@@ -0,0 +1,125 @@
+//! Loudness normalisation, so every track plays and visualises at about the
+//! same level. A track's loudness comes from its ReplayGain or R128 tags
+//! when it has them, and from decoding and measuring it otherwise.
+
+use lofty::{
+  error::LoftyError,
+  file::TaggedFileExt,
+  tag::{ItemKey, Tag},
+};
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::{fmt, path::Path};
+
+use crate::components::loudness::LoudnessMeter;
+use crate::decode::{AudioDecoder, DecodeError};
+
+/// ReplayGain 2.0's reference level.
+pub const DEFAULT_TARGET_LUFS: f32 = -18.0;
+pub const MIN_TARGET_LUFS: f32 = -30.0;
+pub const MAX_TARGET_LUFS: f32 = -8.0;
+/// Quiet tracks are lifted no further than this, in dB, so a near-silent
+/// one doesn't come out as loud noise.
+const MAX_BOOST_DB: f32 = 12.0;
+/// What ReplayGain 2.0 gains are relative to.
+const REPLAYGAIN_REFERENCE_LUFS: f32 = -18.0;
+/// What Opus R128 gains are relative to.
+const R128_REFERENCE_LUFS: f32 = -23.0;
+/// Samples measured at a time while scanning.
+const SCAN_CHUNK: usize = 8192;
+
+/// Whether tracks are normalised, and to what.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NormaliseSettings {
+  pub enabled: bool,
+  pub target_lufs: f32,
+}
+
+impl NormaliseSettings {
+  /// Linear gain that brings a track of `loudness` LUFS to the target; 1.0
+  /// while normalisation is off.
+  pub fn gain(self, loudness: f32) -> f32 {
+    if !self.enabled {
+      return 1.0;
+    }
+    let target = self.target_lufs.clamp(MIN_TARGET_LUFS, MAX_TARGET_LUFS);
+    10f32.powf((target - loudness).min(MAX_BOOST_DB) / 20.0)
+  }
+}
+
+impl Default for NormaliseSettings {
+  fn default() -> Self {
+    Self { enabled: false, target_lufs: DEFAULT_TARGET_LUFS }
+  }
+}
+
+#[derive(Debug)]
+pub enum NormaliseError {
+  Decode(DecodeError),
+  /// Nothing above the gate to measure.
+  Silent,
+}
+
+impl fmt::Display for NormaliseError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      NormaliseError::Decode(e) => write!(f, "{}", e),
+      NormaliseError::Silent => f.write_str("too quiet to measure"),
+    }
+  }
+}
+
+impl From<DecodeError> for NormaliseError {
+  fn from(e: DecodeError) -> Self {
+    NormaliseError::Decode(e)
+  }
+}
+
+/// Integrated loudness of the file at `path`, in LUFS: from its tags when
+/// it has them, or by decoding and measuring the whole of it.
+pub fn loudness(path: &Path) -> Result<f32, NormaliseError> {
+  match tagged_loudness(path) {
+    Ok(Some(loudness)) => return Ok(loudness),
+    Ok(None) => {}
+    Err(e) => eprintln!("Failed to read gain tags of {}: {}", path.display(), e),
+  }
+  scan(path)
+}
+
+/// Loudness the track's ReplayGain or R128 gain was worked out from.
+fn tagged_loudness(path: &Path) -> Result<Option<f32>, LoftyError> {
+  let tagged = lofty::read_from_path(path)?;
+  Ok(tagged.tags().iter().find_map(|tag| replaygain(tag).or_else(|| r128(tag))))
+}
+
+/// `REPLAYGAIN_TRACK_GAIN`, written like "-6.52 dB".
+fn replaygain(tag: &Tag) -> Option<f32> {
+  let gain = tag.get_string(&ItemKey::ReplayGainTrackGain)?;
+  let gain: f32 = gain.trim().trim_end_matches("dB").trim().parse().ok()?;
+  Some(REPLAYGAIN_REFERENCE_LUFS - gain)
+}
+
+/// `R128_TRACK_GAIN`, in 1/256 dB steps, as Opus files carry it.
+fn r128(tag: &Tag) -> Option<f32> {
+  let gain = tag.get_string(&ItemKey::Unknown("R128_TRACK_GAIN".to_string()))?;
+  let gain: i16 = gain.trim().parse().ok()?;
+  Some(R128_REFERENCE_LUFS - gain as f32 / 256.0)
+}
+
+/// Decodes the whole file through a loudness meter.
+fn scan(path: &Path) -> Result<f32, NormaliseError> {
+  let decoder = AudioDecoder::open(path)?;
+  let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+  let mut meter = LoudnessMeter::new(channels, sample_rate);
+  let mut chunk = Vec::with_capacity(SCAN_CHUNK);
+  for sample in decoder {
+    chunk.push(sample);
+    if chunk.len() == SCAN_CHUNK {
+      meter.add(&chunk, channels, sample_rate);
+      chunk.clear();
+    }
+  }
+  meter.add(&chunk, channels, sample_rate);
+  meter.integrated().ok_or(NormaliseError::Silent)
+}
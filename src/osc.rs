@@ -0,0 +1,132 @@
+//! OSC output over UDP, so lighting desks and DMX software can follow the
+//! analysis live.
+//!
+//! Every tick sends the bar levels, each 0.0..=1.0, and every beat sends its
+//! strength. A bars address with `{}` in it sends one message per bar with
+//! the bar's index in place of the `{}` (`/bars/{}` → `/bars/0`, `/bars/1`,
+//! ...), bundled into one packet; otherwise one message carries them all.
+
+use serde::{Deserialize, Serialize};
+use std::{
+  io,
+  net::{SocketAddr, ToSocketAddrs, UdpSocket},
+};
+
+pub const DEFAULT_OSC_PORT: u16 = 9000;
+/// Where a bars address takes each bar's index.
+const INDEX_PLACEHOLDER: &str = "{}";
+/// A bundle timetag meaning "as soon as it arrives".
+const IMMEDIATELY: u64 = 1;
+
+/// Where OSC goes and what it's addressed to.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OscSettings {
+  pub enabled: bool,
+  pub host: String,
+  pub port: u16,
+  pub bars_address: String,
+  pub beat_address: String,
+}
+
+impl Default for OscSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      host: "127.0.0.1".to_string(),
+      port: DEFAULT_OSC_PORT,
+      bars_address: "/visualiser/bars".to_string(),
+      beat_address: "/visualiser/beat".to_string(),
+    }
+  }
+}
+
+/// A socket aimed at the configured host.
+pub struct OscSender {
+  socket: UdpSocket,
+  target: SocketAddr,
+  bars_address: String,
+  beat_address: String,
+}
+
+/// Looks up where `host` is. A name can take a while to resolve, so this
+/// wants to run off the UI thread.
+pub fn resolve(host: &str, port: u16) -> io::Result<SocketAddr> {
+  (host, port)
+    .to_socket_addrs()?
+    .next()
+    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("{} has no address", host)))
+}
+
+impl OscSender {
+  /// Opens a socket aimed at `target`, `settings`' host as [`resolve`]d.
+  pub fn open(settings: &OscSettings, target: SocketAddr) -> io::Result<Self> {
+    let local: SocketAddr =
+      if target.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0; 16], 0).into() };
+    let socket = UdpSocket::bind(local)?;
+    // A tick is never held up by a busy network
+    socket.set_nonblocking(true)?;
+    Ok(Self {
+      socket,
+      target,
+      bars_address: settings.bars_address.clone(),
+      beat_address: settings.beat_address.clone(),
+    })
+  }
+
+  /// Sends the bar levels, each 0.0..=1.0.
+  pub fn send_bars(&self, levels: &[f32]) {
+    let packet = if self.bars_address.contains(INDEX_PLACEHOLDER) {
+      let messages = levels.iter().enumerate().map(|(index, &level)| {
+        message(&self.bars_address.replace(INDEX_PLACEHOLDER, &index.to_string()), &[level])
+      });
+      bundle(messages)
+    } else {
+      message(&self.bars_address, levels)
+    };
+    self.send(&packet);
+  }
+
+  /// Sends a beat's strength, 0.0..=1.0.
+  pub fn send_beat(&self, strength: f32) {
+    self.send(&message(&self.beat_address, &[strength]));
+  }
+
+  fn send(&self, packet: &[u8]) {
+    // Dropped packets are normal for OSC, so only real faults are reported
+    if let Err(e) = self.socket.send_to(packet, self.target)
+      && e.kind() != io::ErrorKind::WouldBlock
+    {
+      eprintln!("Failed to send OSC to {}: {}", self.target, e);
+    }
+  }
+}
+
+/// An OSC message with float arguments.
+fn message(address: &str, arguments: &[f32]) -> Vec<u8> {
+  let mut packet = Vec::with_capacity(address.len() + arguments.len() * 5 + 8);
+  push_string(&mut packet, address);
+  push_string(&mut packet, &format!(",{}", "f".repeat(arguments.len())));
+  for argument in arguments {
+    packet.extend_from_slice(&argument.to_be_bytes());
+  }
+  packet
+}
+
+fn bundle(messages: impl Iterator<Item = Vec<u8>>) -> Vec<u8> {
+  let mut packet = Vec::new();
+  push_string(&mut packet, "#bundle");
+  packet.extend_from_slice(&IMMEDIATELY.to_be_bytes());
+  for message in messages {
+    packet.extend_from_slice(&(message.len() as i32).to_be_bytes());
+    packet.extend_from_slice(&message);
+  }
+  packet
+}
+
+/// Appends a string null-terminated and padded to a multiple of four bytes.
+fn push_string(packet: &mut Vec<u8>, string: &str) {
+  packet.extend_from_slice(string.as_bytes());
+  let padding = 4 - string.len() % 4;
+  packet.extend(std::iter::repeat_n(0, padding));
+}
@@ -0,0 +1,85 @@
+use rodio::Source;
+use std::{fmt, io, ops::Range, path::Path};
+
+use crate::components::{
+  sections::{self, Section},
+  vocals,
+};
+use crate::decode::{AudioDecoder, DecodeError};
+
+/// Slices the waveform overview keeps, plenty for a strip across a wide
+/// window.
+const ENVELOPE_POINTS: usize = 2000;
+
+#[derive(Debug)]
+pub enum OutlineError {
+  Io(io::Error),
+  Decode(DecodeError),
+}
+
+impl fmt::Display for OutlineError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      OutlineError::Io(e) => write!(f, "{}", e),
+      OutlineError::Decode(e) => write!(f, "{}", e),
+    }
+  }
+}
+
+impl From<io::Error> for OutlineError {
+  fn from(e: io::Error) -> Self {
+    OutlineError::Io(e)
+  }
+}
+
+impl From<DecodeError> for OutlineError {
+  fn from(e: DecodeError) -> Self {
+    OutlineError::Decode(e)
+  }
+}
+
+/// The shape of a whole track, as drawn on the timeline.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrackOutline {
+  /// Length of the track, in seconds.
+  pub duration: f32,
+  /// Spans with vocals, in seconds, in order.
+  pub vocals: Vec<Range<f32>>,
+  /// Where each section starts, in order; the first starts at 0.
+  pub sections: Vec<Section>,
+  /// Loudest sample of each of [`ENVELOPE_POINTS`] equal slices, 0.0..=1.0,
+  /// for the waveform overview.
+  pub envelope: Vec<f32>,
+}
+
+impl TrackOutline {
+  /// Decodes the whole file once and runs every offline analysis over it.
+  /// Takes a second or two per track.
+  pub fn analyse(path: &Path) -> Result<Self, OutlineError> {
+    let decoder = AudioDecoder::open(path)?;
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels().max(1) as usize;
+    let interleaved: Vec<f32> = decoder.convert_samples::<f32>().collect();
+    let mono: Vec<f32> = interleaved
+      .chunks(channels)
+      .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+      .collect();
+
+    Ok(Self {
+      duration: mono.len() as f32 / sample_rate.max(1) as f32,
+      vocals: vocals::detect(&mono, sample_rate),
+      sections: sections::detect(&mono, sample_rate),
+      envelope: envelope(&mono, ENVELOPE_POINTS),
+    })
+  }
+}
+
+/// `samples` cut into `points` slices, each as its loudest sample; fewer
+/// when there aren't that many samples.
+fn envelope(samples: &[f32], points: usize) -> Vec<f32> {
+  let slice = samples.len().div_ceil(points).max(1);
+  samples
+    .chunks(slice)
+    .map(|chunk| chunk.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs())).min(1.0))
+    .collect()
+}
@@ -0,0 +1,886 @@
+use rodio::{
+  OutputStream, OutputStreamHandle, PlayError, Sink, Source, StreamError,
+  cpal::{
+    self,
+    traits::{DeviceTrait, HostTrait},
+  },
+  source::{SeekError, SkipDuration, UniformSourceIterator},
+};
+use std::{
+  collections::{HashMap, VecDeque},
+  fmt,
+  path::Path,
+  sync::{
+    Arc, Mutex,
+    mpsc::{Receiver, Sender},
+  },
+  time::Duration,
+};
+
+use crate::{
+  analysis::DEFAULT_SAMPLE_RATE,
+  autodj::GridCache,
+  capture::{CaptureError, InputCapture},
+  components::{
+    crossfade::{Crossfade, Handover},
+    equalizer::{EqControl, EqSettings, Equalizer},
+    gain::{self, Gain, GainSlot},
+    looping::{LoopRegion, Looping},
+    rate::{RateControl, Varispeed},
+    stretch::TimeStretch,
+    tap::{ChunkSlot, Chunker, Tap},
+  },
+  decode::{self, AudioDecoder, DecodeError},
+  normalise::NormaliseSettings,
+};
+
+const DEFAULT_VOLUME: f32 = 1.0;
+pub const MAX_CROSSFADE_SECONDS: f32 = 10.0;
+/// Typical for the default shared-mode buffers on desktop systems.
+pub const DEFAULT_OUTPUT_LATENCY_MS: f32 = 100.0;
+pub const MAX_OUTPUT_LATENCY_MS: f32 = 500.0;
+pub const MIN_RATE: f32 = 0.5;
+pub const MAX_RATE: f32 = 2.0;
+/// Shortest A/B loop, so a double click on the buttons can't make one.
+const MIN_LOOP: Duration = Duration::from_millis(100);
+
+/// A playlist entry as queued on the sink.
+type Entry = Tap<
+  Crossfade<
+    Equalizer<
+      Gain<TimeStretch<UniformSourceIterator<Varispeed<SkipDuration<Looping<AudioDecoder>>>, f32>>>,
+    >,
+  >,
+>;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+  LoadFile,
+  Play,
+  Pause,
+  Stop,
+  /// Skips to the next playlist track, still playing if it was.
+  Next,
+  VolumeChanged(f32),
+  ToggleMute,
+  /// Jumps to a position in the current track.
+  Seek(Duration),
+  /// Overlap between playlist tracks, in seconds; 0 plays them gaplessly.
+  CrossfadeChanged(f32),
+  /// How far the speakers lag the tap, in milliseconds.
+  OutputLatencyChanged(f32),
+  /// Playback speed, from [`MIN_RATE`] to [`MAX_RATE`].
+  RateChanged(f32),
+  /// Whether a changed speed keeps the pitch.
+  PreservePitchToggled(bool),
+  /// Marks where the A/B loop starts, at the current position.
+  SetLoopStart,
+  /// Marks where it ends and jumps back to its start.
+  SetLoopEnd,
+  ClearLoop,
+  /// Visualise whatever the OS is playing instead of a file.
+  ToggleCapture,
+  /// Visualise the microphone instead of a file.
+  ToggleMicrophone,
+  /// Moves playback to another output device without a restart.
+  OutputDeviceSelected(OutputDevice),
+  /// Re-lists the output devices, as the picker opens.
+  RefreshOutputDevices,
+}
+
+/// Where playback is heard.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum OutputDevice {
+  /// Whatever the OS has as its default output.
+  #[default]
+  Default,
+  Named(String),
+}
+
+impl OutputDevice {
+  /// The default plus every output device the host has.
+  pub fn all() -> Vec<OutputDevice> {
+    let named = cpal::default_host()
+      .output_devices()
+      .into_iter()
+      .flatten()
+      .filter_map(|device| device.name().ok())
+      .map(OutputDevice::Named);
+    std::iter::once(OutputDevice::Default).chain(named).collect()
+  }
+
+  /// Opens this device, falling back to the default when it's gone.
+  fn open(&self) -> Result<(OutputStream, OutputStreamHandle), StreamError> {
+    if let OutputDevice::Named(name) = self {
+      let device = cpal::default_host()
+        .output_devices()
+        .into_iter()
+        .flatten()
+        .find(|device| device.name().is_ok_and(|device_name| device_name == *name));
+      match device {
+        Some(device) => return OutputStream::try_from_device(&device),
+        None => eprintln!("Output device {} not found, using the default", name),
+      }
+    }
+    OutputStream::try_default()
+  }
+}
+
+impl fmt::Display for OutputDevice {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      OutputDevice::Default => f.write_str("Default output"),
+      OutputDevice::Named(name) => f.write_str(name),
+    }
+  }
+}
+
+/// Where live audio is captured from instead of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureSource {
+  System,
+  Microphone,
+}
+
+impl fmt::Display for CaptureSource {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      CaptureSource::System => "system audio",
+      CaptureSource::Microphone => "microphone",
+    })
+  }
+}
+
+/// Why a track, capture or output couldn't be started.
+#[derive(Debug)]
+pub enum PlaybackError {
+  /// The output device wouldn't open, usually because it's gone or another
+  /// program holds it.
+  Output(StreamError),
+  Sink(PlayError),
+  Decode {
+    path: String,
+    error: DecodeError,
+  },
+  Capture {
+    source: CaptureSource,
+    error: CaptureError,
+  },
+  Seek(SeekError),
+}
+
+impl PlaybackError {
+  /// A line for the banner; the details go to the log.
+  pub fn summary(&self) -> String {
+    match self {
+      PlaybackError::Output(_) | PlaybackError::Sink(_) => {
+        "Device busy: the audio output couldn't be opened".to_string()
+      }
+      PlaybackError::Decode { path, error } => {
+        let name = Path::new(path)
+          .file_name()
+          .map_or(path.clone(), |name| name.to_string_lossy().to_string());
+        match error {
+          DecodeError::Format(_) => format!("Unsupported format: {}", name),
+          DecodeError::Io(_) => format!("Couldn't open {}", name),
+          DecodeError::NoTrack => format!("No audio in {}", name),
+        }
+      }
+      PlaybackError::Capture { source, error } => match error {
+        CaptureError::NoMonitor | CaptureError::NoMicrophone => {
+          format!("Nothing to capture {} from", source)
+        }
+        CaptureError::UnsupportedFormat(_) | CaptureError::SampleRateMismatch { .. } => {
+          format!("Unsupported format: can't capture {}", source)
+        }
+        CaptureError::Config(_) | CaptureError::Build(_) | CaptureError::Play(_) => {
+          format!("Device busy: can't capture {}", source)
+        }
+      },
+      PlaybackError::Seek(_) => "Couldn't seek in this track".to_string(),
+    }
+  }
+}
+
+impl fmt::Display for PlaybackError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PlaybackError::Output(e) => write!(f, "couldn't open the output: {}", e),
+      PlaybackError::Sink(e) => write!(f, "couldn't start the output: {}", e),
+      PlaybackError::Decode { path, error } => write!(f, "couldn't decode {}: {}", path, error),
+      PlaybackError::Capture { source, error } => {
+        write!(f, "couldn't capture {}: {}", source, error)
+      }
+      PlaybackError::Seek(e) => write!(f, "couldn't seek: {}", e),
+    }
+  }
+}
+
+/// A freshly opened track: the tapped samples plus what's needed to interpret them.
+pub struct LoadedTrack {
+  pub samples: Receiver<Vec<f32>>,
+  pub sample_rate: u32,
+  pub channels: u16,
+}
+
+/// Owns the audio output and the transport state of the current track.
+pub struct Player {
+  pub is_playing: bool,
+  pub is_loaded: bool,
+  sink: Option<Sink>,
+  _stream: Option<OutputStream>,
+  pub output_device: OutputDevice,
+  /// How long samples take from the tap to the speakers; the visuals are
+  /// held back by this much so they match what's heard.
+  pub output_latency: Duration,
+  /// Devices offered in the picker, as of the last refresh.
+  output_devices: Vec<OutputDevice>,
+  /// Files picked on load, played one after another.
+  playlist: Vec<String>,
+  /// Playlist index of the track playing now.
+  track: usize,
+  /// Playlist index of the last entry appended to the sink.
+  queued: usize,
+  /// Where the last queued entry leaves its tail for the next one.
+  handover: Option<Handover>,
+  /// Channels and sample rate every entry is converted to, from the first
+  /// track loaded, so the analysis never has to switch mid-stream.
+  format: (u16, u32),
+  pub crossfade: Duration,
+  /// Picks each next track by tempo and key and mixes it in on the beat.
+  pub auto_dj: bool,
+  /// Beat grids the auto-DJ picks and aligns tracks with.
+  pub grids: GridCache,
+  /// Speed the last queued entry plays at, after any auto-DJ nudge.
+  speed: f32,
+  /// Playback speed picked on top of that, for every entry.
+  pub rate: f32,
+  /// Whether the rate time-stretches instead of resampling, so the pitch
+  /// stays where it was.
+  pub preserve_pitch: bool,
+  /// Hands the rate to the queued entries while they play.
+  rate_control: RateControl,
+  /// Sink position and track position as of the last rate change, seek or
+  /// track change. The sink counts time as it's heard, which the track
+  /// moves through at the rate.
+  anchor: (Duration, Duration),
+  /// A/B loop points in the current track; the loop plays once both are set.
+  pub loop_start: Option<Duration>,
+  pub loop_end: Option<Duration>,
+  /// Hands the loop to the entry playing.
+  loop_region: LoopRegion,
+  pub normalise: NormaliseSettings,
+  /// Integrated loudness of the playlist tracks measured so far, in LUFS.
+  loudness: HashMap<String, f32>,
+  /// Gain of each queued entry, by playlist index.
+  gains: Vec<(usize, GainSlot)>,
+  pub equalizer: EqSettings,
+  /// Hands the EQ's bands to the queued entries while they play.
+  eq_control: EqControl,
+  tap_sender: Arc<Mutex<Option<Sender<Vec<f32>>>>>,
+  /// Ring of raw samples the tap feeds for the waveform view.
+  waveform: Arc<Mutex<VecDeque<f32>>>,
+  // Kept across track loads and applied to every new sink
+  pub volume: f32,
+  pub is_muted: bool,
+  /// Live capture, while that mode is on.
+  capture: Option<(CaptureSource, InputCapture)>,
+  /// Gets a copy of every chunk while a measurement wants the reference signal.
+  reference: ChunkSlot,
+  /// Gets a copy of every chunk from the live mic while it's being recorded.
+  recording: ChunkSlot,
+  /// The last seek that went wrong as part of something that otherwise
+  /// worked, for [`Player::take_seek_error`].
+  seek_error: Option<SeekError>,
+}
+
+impl Player {
+  pub fn new(waveform: Arc<Mutex<VecDeque<f32>>>) -> Self {
+    Self {
+      is_playing: false,
+      is_loaded: false,
+      sink: None,
+      _stream: None,
+      output_device: OutputDevice::Default,
+      output_latency: Duration::from_secs_f32(DEFAULT_OUTPUT_LATENCY_MS / 1000.0),
+      output_devices: vec![OutputDevice::Default],
+      playlist: Vec::new(),
+      track: 0,
+      queued: 0,
+      handover: None,
+      format: (2, DEFAULT_SAMPLE_RATE),
+      crossfade: Duration::ZERO,
+      auto_dj: false,
+      grids: GridCache::default(),
+      speed: 1.0,
+      rate: 1.0,
+      preserve_pitch: false,
+      rate_control: RateControl::new(),
+      anchor: (Duration::ZERO, Duration::ZERO),
+      loop_start: None,
+      loop_end: None,
+      loop_region: Arc::new(Mutex::new(None)),
+      normalise: NormaliseSettings::default(),
+      loudness: HashMap::new(),
+      gains: Vec::new(),
+      equalizer: EqSettings::default(),
+      eq_control: EqControl::new(),
+      tap_sender: Arc::new(Mutex::new(None)),
+      waveform,
+      volume: DEFAULT_VOLUME,
+      is_muted: false,
+      capture: None,
+      reference: Arc::new(Mutex::new(None)),
+      recording: Arc::new(Mutex::new(None)),
+      seek_error: None,
+    }
+  }
+
+  /// Handles a transport message. Returns the new track when one was (re)loaded.
+  pub fn update(&mut self, message: Message) -> Result<Option<LoadedTrack>, PlaybackError> {
+    match message {
+      Message::LoadFile => {
+        if let Some(paths) =
+          rfd::FileDialog::new().add_filter("Audio", &decode::EXTENSIONS).pick_files()
+        {
+          self.playlist = paths.iter().map(|path| path.to_string_lossy().to_string()).collect();
+          self.track = 0;
+          self.capture = None;
+          self.clear_loop();
+          return self.load_audio_file();
+        }
+        Ok(None)
+      }
+      Message::Play => {
+        let mut loaded = None;
+        // The analysis thread follows the capture while it runs, so leaving
+        // capture mode reloads the file to point it back
+        let was_capturing = self.capture.take().is_some();
+        if was_capturing {
+          self.is_playing = false;
+        }
+        if (self.sink.is_none() || was_capturing) && !self.playlist.is_empty() {
+          loaded = self.load_audio_file()?;
+        }
+        if let Some(sink) = &self.sink {
+          sink.play();
+          self.is_playing = true;
+        }
+        Ok(loaded)
+      }
+      Message::Pause => {
+        if self.capture.take().is_some() {
+          self.is_playing = false;
+        }
+        if let Some(sink) = &self.sink {
+          sink.pause();
+          self.is_playing = false;
+        }
+        Ok(None)
+      }
+      Message::Stop => {
+        // Tear down the current sink (drains the queue)
+        if let Some(sink) = &self.sink {
+          sink.stop();
+        }
+        self.capture = None;
+        self.is_playing = false;
+        // And immediately rebuild it (paused at start)
+        self.load_audio_file()
+      }
+      Message::Next => {
+        if self.capture.is_some() || self.track + 1 >= self.playlist.len() {
+          return Ok(None);
+        }
+        self.track += 1;
+        self.clear_loop();
+        let loaded = self.load_audio_file()?;
+        if self.is_playing
+          && let Some(sink) = &self.sink
+        {
+          sink.play();
+        }
+        Ok(loaded)
+      }
+      Message::VolumeChanged(volume) => {
+        self.volume = volume;
+        // Dragging the slider up is an implicit unmute
+        if volume > 0.0 {
+          self.is_muted = false;
+        }
+        self.apply_volume();
+        Ok(None)
+      }
+      Message::ToggleMute => {
+        self.is_muted = !self.is_muted;
+        self.apply_volume();
+        Ok(None)
+      }
+      Message::Seek(position) => {
+        if let Err(e) = self.seek(position) {
+          self.seek_failed(e);
+        }
+        Ok(None)
+      }
+      Message::CrossfadeChanged(seconds) => {
+        // Tracks already queued keep the fade they were built with
+        self.crossfade = Duration::from_secs_f32(seconds.clamp(0.0, MAX_CROSSFADE_SECONDS));
+        Ok(None)
+      }
+      Message::OutputLatencyChanged(ms) => {
+        self.output_latency =
+          Duration::from_secs_f32(ms.clamp(0.0, MAX_OUTPUT_LATENCY_MS) / 1000.0);
+        Ok(None)
+      }
+      Message::RateChanged(rate) => {
+        self.set_rate(rate, self.preserve_pitch);
+        Ok(None)
+      }
+      Message::PreservePitchToggled(preserve_pitch) => {
+        self.set_rate(self.rate, preserve_pitch);
+        Ok(None)
+      }
+      Message::SetLoopStart => {
+        let start = self.position();
+        self.loop_start = Some(start);
+        if self.loop_end.is_some_and(|end| end < start + MIN_LOOP) {
+          self.loop_end = None;
+        }
+        self.apply_loop();
+        Ok(None)
+      }
+      Message::SetLoopEnd => {
+        let end = self.position();
+        if let Some(start) = self.loop_start
+          && end >= start + MIN_LOOP
+        {
+          self.loop_end = Some(end);
+          self.apply_loop();
+          // The entry has already read past here, so it starts on the loop
+          // from the top
+          if let Err(e) = self.seek(start) {
+            self.seek_failed(e);
+          }
+        }
+        Ok(None)
+      }
+      Message::ClearLoop => {
+        self.clear_loop();
+        Ok(None)
+      }
+      Message::ToggleCapture => self.toggle_capture(CaptureSource::System),
+      Message::ToggleMicrophone => self.toggle_capture(CaptureSource::Microphone),
+      Message::OutputDeviceSelected(device) => {
+        self.switch_output(device)?;
+        Ok(None)
+      }
+      Message::RefreshOutputDevices => {
+        self.output_devices = OutputDevice::all();
+        Ok(None)
+      }
+    }
+  }
+
+  fn seek_failed(&mut self, e: SeekError) {
+    eprintln!("Failed to seek: {}", e);
+    self.seek_error = Some(e);
+  }
+
+  /// The seek that last went wrong without failing the call it was part of,
+  /// once, for the banner.
+  pub fn take_seek_error(&mut self) -> Option<PlaybackError> {
+    self.seek_error.take().map(PlaybackError::Seek)
+  }
+
+  pub fn output_devices(&self) -> &[OutputDevice] {
+    &self.output_devices
+  }
+
+  /// Moves playback to `device`, carrying on from the same position. The
+  /// new sink feeds the same tap, so the analysis carries on as if nothing
+  /// happened. The old device keeps playing if the new one won't open.
+  fn switch_output(&mut self, device: OutputDevice) -> Result<(), PlaybackError> {
+    if device == self.output_device {
+      return Ok(());
+    }
+    let Some(old) = &self.sink else {
+      // Nothing playing yet; the next load opens it
+      self.output_device = device;
+      return Ok(());
+    };
+    let (stream, handle) = device.open().map_err(PlaybackError::Output)?;
+    let sink = Sink::try_new(&handle).map_err(PlaybackError::Sink)?;
+    let position = self.position();
+    old.stop();
+    self.output_device = device;
+
+    // The current track again, without any crossfade it was part of
+    self.handover = None;
+    self.speed = 1.0;
+    self.queued = self.track;
+    self.gains.clear();
+    if let Some(entry) = self.entry(self.track) {
+      sink.append(entry);
+    }
+    sink.set_volume(self.effective_volume());
+    if let Err(e) = sink.try_seek(position) {
+      self.seek_failed(e);
+    }
+    self.anchor = (position, position);
+    if self.is_playing && self.capture.is_none() {
+      sink.play();
+    } else {
+      sink.pause();
+    }
+    self.sink = Some(sink);
+    self._stream = Some(stream);
+    Ok(())
+  }
+
+  /// Reopens `playlist` at `track`, paused at `position`, as a past session
+  /// left it.
+  pub fn resume(
+    &mut self,
+    playlist: Vec<String>,
+    track: usize,
+    position: Duration,
+  ) -> Result<Option<LoadedTrack>, PlaybackError> {
+    if track >= playlist.len() {
+      return Ok(None);
+    }
+    self.playlist = playlist;
+    self.track = track;
+    self.capture = None;
+    self.clear_loop();
+    let loaded = self.load_audio_file()?;
+    if let Err(e) = self.seek(position) {
+      self.seek_failed(e);
+    }
+    Ok(loaded)
+  }
+
+  /// Puts `path` in the playlist in place of the track playing and goes on
+  /// from `position` in it, still playing if it was, to A/B two files.
+  pub fn swap_track(
+    &mut self,
+    path: String,
+    position: Duration,
+  ) -> Result<Option<LoadedTrack>, PlaybackError> {
+    let Some(current) = self.playlist.get_mut(self.track) else {
+      return Ok(None);
+    };
+    let previous = std::mem::replace(current, path);
+    self.clear_loop();
+    let loaded = match self.load_audio_file() {
+      Ok(loaded) => loaded,
+      Err(e) => {
+        self.playlist[self.track] = previous;
+        return Err(e);
+      }
+    };
+    if let Err(e) = self.seek(position) {
+      self.seek_failed(e);
+    }
+    if self.is_playing
+      && let Some(sink) = &self.sink
+    {
+      sink.play();
+    }
+    Ok(loaded)
+  }
+
+  /// Starts capturing from `source`, replacing any other capture, or stops if
+  /// `source` is already running.
+  fn toggle_capture(
+    &mut self,
+    source: CaptureSource,
+  ) -> Result<Option<LoadedTrack>, PlaybackError> {
+    if let Some((current, _)) = self.capture.take() {
+      self.is_playing = false;
+      if current == source {
+        return Ok(None);
+      }
+    }
+    // The file would be mixed into the capture otherwise
+    if let Some(sink) = &self.sink {
+      sink.pause();
+    }
+    self.is_playing = false;
+    let capture = match source {
+      CaptureSource::System => InputCapture::system(self.waveform.clone(), self.reference.clone()),
+      CaptureSource::Microphone => {
+        InputCapture::live_microphone(self.waveform.clone(), self.recording.clone())
+      }
+    };
+    let (capture, track) = capture.map_err(|error| PlaybackError::Capture { source, error })?;
+    self.capture = Some((source, capture));
+    self.is_playing = true;
+    Ok(Some(track))
+  }
+
+  pub fn capture_source(&self) -> Option<CaptureSource> {
+    self.capture.as_ref().map(|(source, _)| *source)
+  }
+
+  /// Slot that receives a copy of whatever is being played or captured.
+  pub fn reference(&self) -> ChunkSlot {
+    self.reference.clone()
+  }
+
+  /// Slot that receives a copy of the live mic, for recording it.
+  pub fn recording(&self) -> ChunkSlot {
+    self.recording.clone()
+  }
+
+  /// Rebuilds the sink around the current playlist track, paused at its start.
+  fn load_audio_file(&mut self) -> Result<Option<LoadedTrack>, PlaybackError> {
+    let Some(path) = self.playlist.get(self.track) else {
+      return Ok(None);
+    };
+    // Open audio output
+    let (stream, stream_handle) = self.output_device.open().map_err(PlaybackError::Output)?;
+    // Create a sink attached to the stream handle
+    let sink = Sink::try_new(&stream_handle).map_err(PlaybackError::Sink)?;
+    // Open the file just to learn its format; the entry decodes it again
+    let decoder = AudioDecoder::open(Path::new(path))
+      .map_err(|error| PlaybackError::Decode { path: path.clone(), error })?;
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels();
+    self.format = (channels, sample_rate);
+
+    // Set up our channel for tapping
+    let (sender, receiver) = std::sync::mpsc::channel();
+    *self.tap_sender.lock().unwrap() = Some(sender);
+
+    // Append to sink (playback) and start paused
+    self.handover = None;
+    self.speed = 1.0;
+    self.queued = self.track;
+    self.anchor = (Duration::ZERO, Duration::ZERO);
+    self.gains.clear();
+    let Some(entry) = self.entry(self.track) else {
+      // Only a file changed since it was opened above gets here, and the
+      // entry has said why
+      return Ok(None);
+    };
+    sink.append(entry);
+    sink.pause();
+    sink.set_volume(self.effective_volume());
+
+    // Store the sink and stream so they live as long as we need
+    self.sink = Some(sink);
+    self._stream = Some(stream);
+    self.is_loaded = true;
+
+    Ok(Some(LoadedTrack { samples: receiver, sample_rate, channels }))
+  }
+
+  /// Decodes playlist track `index` into a sink entry: looped between any
+  /// A/B points, played at the rate, converted to the shared format,
+  /// normalised, equalised, overlapped with its neighbours and tapped for
+  /// analysis, which so hears the track as sped up or slowed, as loud as the
+  /// rest and through the EQ.
+  /// The auto-DJ also nudges its speed and start to land on the beat of the
+  /// track fading out under it.
+  fn entry(&mut self, index: usize) -> Option<Entry> {
+    let path = self.playlist.get(index)?;
+    let decoder = match AudioDecoder::open(Path::new(path)) {
+      Ok(decoder) => decoder,
+      Err(e) => {
+        eprintln!("Failed to decode {}: {}", path, e);
+        return None;
+      }
+    };
+
+    let grids = index
+      .checked_sub(1)
+      .and_then(|previous| self.grids.get(&self.playlist[previous]))
+      .zip(self.grids.get(path));
+    let (speed, offset) = match grids {
+      Some((outgoing, incoming))
+        if self.auto_dj && self.handover.is_some() && !self.crossfade.is_zero() =>
+      {
+        outgoing.transition(self.speed, incoming, self.crossfade)
+      }
+      _ => (1.0, Duration::ZERO),
+    };
+    self.speed = speed;
+
+    let (channels, sample_rate) = self.format;
+    let looping = Looping::new(decoder, self.loop_region.clone());
+    let varispeed = Varispeed::new(looping.skip_duration(offset), speed, self.rate_control.clone());
+    let stretched = TimeStretch::new(
+      UniformSourceIterator::new(varispeed, channels, sample_rate),
+      self.rate_control.clone(),
+    );
+    let slot = gain::gain_slot(self.gain(index));
+    self.gains.push((index, slot.clone()));
+    let source = Equalizer::new(Gain::new(stretched, slot), self.eq_control.clone());
+
+    // Only a track with another after it holds its tail back
+    let handover_out = (index + 1 < self.playlist.len()).then(Handover::default);
+    let faded = Crossfade::new(source, self.crossfade, self.handover.take(), handover_out.clone());
+    self.handover = handover_out;
+
+    // Wrap in our Tap adapter, which implements rodio::Source
+    let sender = self.tap_sender.lock().unwrap().clone()?;
+    let chunker = Chunker::new(sender)
+      .with_waveform(self.waveform.clone(), faded.channels())
+      .with_copy(self.reference.clone());
+    Some(Tap::new(faded, chunker))
+  }
+
+  /// Keeps the next playlist track queued behind the playing one so it
+  /// follows without a gap, and notes which one is playing.
+  pub fn advance(&mut self) {
+    let Some(remaining) = self.sink.as_ref().map(Sink::len) else {
+      return;
+    };
+    let track = (self.queued + 1).saturating_sub(remaining).min(self.queued);
+    if track != self.track {
+      // The sink counts each entry's position from its own start
+      self.anchor = (Duration::ZERO, Duration::ZERO);
+      self.track = track;
+      self.clear_loop();
+      self.gains.retain(|(index, _)| *index >= track);
+    }
+    if remaining <= 1 && self.queued + 1 < self.playlist.len() {
+      if self.auto_dj {
+        self.pick_next();
+      }
+      self.queued += 1;
+      if let Some(entry) = self.entry(self.queued)
+        && let Some(sink) = &self.sink
+      {
+        sink.append(entry);
+      }
+    }
+  }
+
+  /// Moves the upcoming track that mixes best out of the last queued one
+  /// up to play next. Tracks without a grid yet are left for last.
+  fn pick_next(&mut self) {
+    let Some(current) = self.grids.get(&self.playlist[self.queued]).copied() else {
+      return;
+    };
+    let next = self.queued + 1;
+    let best = (next..self.playlist.len())
+      .map(|i| {
+        let distance =
+          self.grids.get(&self.playlist[i]).map_or(f32::MAX, |grid| current.distance(grid));
+        (i, distance)
+      })
+      .min_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((best, _)) = best {
+      self.playlist.swap(next, best);
+    }
+  }
+
+  /// Paths of every playlist track.
+  pub fn playlist(&self) -> &[String] {
+    &self.playlist
+  }
+
+  /// The track playing now.
+  pub fn file_path(&self) -> Option<&str> {
+    self.playlist.get(self.track).map(String::as_str)
+  }
+
+  /// Playlist position of the current track and the playlist length.
+  pub fn track(&self) -> (usize, usize) {
+    (self.track, self.playlist.len())
+  }
+
+  /// How far into the current track playback is.
+  pub fn position(&self) -> Duration {
+    let Some(sink) = &self.sink else {
+      return Duration::ZERO;
+    };
+    let (heard, track) = self.anchor;
+    let position = track + sink.get_pos().saturating_sub(heard).mul_f32(self.rate);
+    // The sink keeps counting on through every pass of the loop
+    match self.loop_start.zip(self.loop_end) {
+      Some((start, end)) if track < end && position >= end => {
+        let length = (end - start).as_secs_f64();
+        start + Duration::from_secs_f64((position - start).as_secs_f64() % length)
+      }
+      _ => position,
+    }
+  }
+
+  pub fn seek(&mut self, position: Duration) -> Result<(), SeekError> {
+    if let Some(sink) = &self.sink {
+      sink.try_seek(position)?;
+      self.anchor = (position, position);
+    }
+    Ok(())
+  }
+
+  fn apply_loop(&self) {
+    *self.loop_region.lock().unwrap() = self.loop_start.zip(self.loop_end);
+  }
+
+  fn clear_loop(&mut self) {
+    self.loop_start = None;
+    self.loop_end = None;
+    self.apply_loop();
+  }
+
+  /// Switches every entry, including the one playing, to `rate`, keeping
+  /// the position where it is.
+  fn set_rate(&mut self, rate: f32, preserve_pitch: bool) {
+    let position = self.position();
+    self.anchor = (self.sink.as_ref().map_or(Duration::ZERO, Sink::get_pos), position);
+    self.rate = rate.clamp(MIN_RATE, MAX_RATE);
+    self.preserve_pitch = preserve_pitch;
+    self.rate_control.set(self.rate, preserve_pitch);
+  }
+
+  /// Paths of the playlist tracks whose loudness isn't known yet.
+  pub fn unmeasured(&self) -> impl Iterator<Item = &String> {
+    self.playlist.iter().filter(|path| !self.loudness.contains_key(*path))
+  }
+
+  /// Notes the loudness of the track at `path` and brings any entry of it
+  /// to the level.
+  pub fn set_loudness(&mut self, path: String, loudness: f32) {
+    self.loudness.insert(path, loudness);
+    self.apply_gains();
+  }
+
+  pub fn set_normalise(&mut self, settings: NormaliseSettings) {
+    self.normalise = settings;
+    self.apply_gains();
+  }
+
+  pub fn set_equalizer(&mut self, settings: EqSettings) {
+    let settings = settings.repaired();
+    self.equalizer = settings;
+    self.eq_control.set(settings);
+  }
+
+  /// Gain for playlist track `index`; tracks not measured yet play as they are.
+  fn gain(&self, index: usize) -> f32 {
+    self
+      .playlist
+      .get(index)
+      .and_then(|path| self.loudness.get(path))
+      .map_or(1.0, |loudness| self.normalise.gain(*loudness))
+  }
+
+  fn apply_gains(&self) {
+    for (index, slot) in &self.gains {
+      gain::set_gain(slot, self.gain(*index));
+    }
+  }
+
+  fn effective_volume(&self) -> f32 {
+    if self.is_muted { 0.0 } else { self.volume }
+  }
+
+  fn apply_volume(&self) {
+    if let Some(sink) = &self.sink {
+      sink.set_volume(self.effective_volume());
+    }
+  }
+}
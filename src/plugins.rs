@@ -0,0 +1,451 @@
+//! Visualisers from outside the app, loaded at startup from dynamic
+//! libraries in `<platform config dir>/rust_audio_visualiser/plugins`, and
+//! picked from the Plugin style.
+//!
+//! The boundary is a C ABI, so a plugin doesn't have to be built with the
+//! same compiler as the app, or in Rust at all. Each frame the plugin is
+//! handed the bars, the waveform and the beat-reactive levels, and fills in
+//! a list of shapes in the canvas's pixels, which the app draws.
+//!
+//! In Rust, implement [`VisualizerPlugin`] on a type with a `Default` and
+//! export it from a `cdylib` with
+//!
+//! ```ignore
+//! rust_audio_visualiser::export_visualizer!(MyVisualizer);
+//! ```
+//!
+//! Elsewhere, export `visualiser_plugin_api`, returning
+//! [`PLUGIN_API_VERSION`], and `visualiser_plugin`, returning a
+//! [`PluginVTable`]. Plugins built for another version are skipped.
+
+use iced::{
+  Color, Point, Rectangle, Size, Theme,
+  mouse::Cursor,
+  widget::canvas::{self, Geometry, Path, Stroke},
+};
+use libloading::{Library, Symbol};
+use std::{
+  ffi::{CStr, CString, c_char, c_void},
+  fmt, fs, io,
+  panic::{self, AssertUnwindSafe},
+  path::{Path as FilePath, PathBuf},
+  ptr, slice,
+  sync::Mutex,
+};
+
+use crate::{app::Message, config::Config};
+
+/// Bumped whenever the frame, shape or vtable layout changes.
+pub const PLUGIN_API_VERSION: u32 = 1;
+const API_SYMBOL: &[u8] = b"visualiser_plugin_api\0";
+const VTABLE_SYMBOL: &[u8] = b"visualiser_plugin\0";
+const PLUGIN_DIR: &str = "plugins";
+/// Most shapes a plugin draws in a frame; any more are dropped.
+const MAX_SHAPES: usize = 16384;
+
+/// Fills `x, y, a, b` as a rectangle's corner and size.
+pub const SHAPE_RECTANGLE: u32 = 0;
+/// Strokes from `x, y` to `a, b`, `width` wide.
+pub const SHAPE_LINE: u32 = 1;
+/// Fills a circle around `x, y` with radius `a`; `b` is unused.
+pub const SHAPE_CIRCLE: u32 = 2;
+
+/// What a plugin is drawing from, as it crosses the C ABI. The pointers are
+/// only valid during the call.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct FrameInput {
+  pub bars: *const f32,
+  pub bar_count: usize,
+  pub waveform: *const f32,
+  pub waveform_len: usize,
+  pub width: f32,
+  pub height: f32,
+  pub time: f32,
+  pub pulse: f32,
+  pub bass: f32,
+  pub mid: f32,
+  pub treble: f32,
+  /// The live tempo, or 0.0 until there is one.
+  pub bpm: f32,
+}
+
+/// What a plugin is drawing from.
+#[derive(Debug, Clone, Copy)]
+pub struct PluginFrame<'a> {
+  /// The bars, each 0.0..=1.0, lowest frequency first.
+  pub bars: &'a [f32],
+  /// The latest samples, mixed to mono, -1.0..=1.0.
+  pub waveform: &'a [f32],
+  /// Size of the canvas, in pixels.
+  pub width: f32,
+  pub height: f32,
+  /// Seconds the animations have run for.
+  pub time: f32,
+  /// Beat pulse, 0.0..=1.0.
+  pub pulse: f32,
+  /// Energy of each band, 0.0..=1.0.
+  pub bass: f32,
+  pub mid: f32,
+  pub treble: f32,
+  pub bpm: Option<f32>,
+}
+
+impl<'a> PluginFrame<'a> {
+  fn input(&self) -> FrameInput {
+    FrameInput {
+      bars: self.bars.as_ptr(),
+      bar_count: self.bars.len(),
+      waveform: self.waveform.as_ptr(),
+      waveform_len: self.waveform.len(),
+      width: self.width,
+      height: self.height,
+      time: self.time,
+      pulse: self.pulse,
+      bass: self.bass,
+      mid: self.mid,
+      treble: self.treble,
+      bpm: self.bpm.unwrap_or(0.0),
+    }
+  }
+
+  /// # Safety
+  ///
+  /// The pointers in `input` must point at as many readable floats as it
+  /// says, for `'a`.
+  unsafe fn from_input(input: &'a FrameInput) -> Self {
+    let view = |data: *const f32, len: usize| {
+      if data.is_null() { &[][..] } else { unsafe { slice::from_raw_parts(data, len) } }
+    };
+    Self {
+      bars: view(input.bars, input.bar_count),
+      waveform: view(input.waveform, input.waveform_len),
+      width: input.width,
+      height: input.height,
+      time: input.time,
+      pulse: input.pulse,
+      bass: input.bass,
+      mid: input.mid,
+      treble: input.treble,
+      bpm: (input.bpm > 0.0).then_some(input.bpm),
+    }
+  }
+}
+
+/// One shape a plugin draws, in the canvas's pixels; see the `SHAPE_`
+/// constants for what the fields mean for each kind. Unknown kinds are
+/// skipped.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PluginShape {
+  pub kind: u32,
+  pub x: f32,
+  pub y: f32,
+  pub a: f32,
+  pub b: f32,
+  pub width: f32,
+  /// Red, green, blue and alpha, each 0.0..=1.0.
+  pub color: [f32; 4],
+}
+
+impl PluginShape {
+  pub fn rectangle(x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) -> Self {
+    Self { kind: SHAPE_RECTANGLE, x, y, a: width, b: height, width: 0.0, color }
+  }
+
+  pub fn line(from: (f32, f32), to: (f32, f32), width: f32, color: [f32; 4]) -> Self {
+    Self { kind: SHAPE_LINE, x: from.0, y: from.1, a: to.0, b: to.1, width, color }
+  }
+
+  pub fn circle(x: f32, y: f32, radius: f32, color: [f32; 4]) -> Self {
+    Self { kind: SHAPE_CIRCLE, x, y, a: radius, b: 0.0, width: 0.0, color }
+  }
+}
+
+/// A visualiser written against the app, for [`export_visualizer!`] to put
+/// behind the C ABI.
+pub trait VisualizerPlugin {
+  /// Shown in the plugin picker.
+  fn name(&self) -> &str;
+
+  /// Adds this frame's shapes to `shapes`, which starts empty.
+  fn draw(&mut self, frame: &PluginFrame, shapes: &mut Vec<PluginShape>);
+}
+
+/// The functions a plugin exports, behind `visualiser_plugin`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PluginVTable {
+  /// Makes the plugin's state, or returns null if it can't.
+  pub create: unsafe extern "C" fn() -> *mut c_void,
+  /// A nul-terminated name, living as long as the state.
+  pub name: unsafe extern "C" fn(*mut c_void) -> *const c_char,
+  /// Writes up to `capacity` shapes to `out`, returning how many it wrote.
+  pub draw: unsafe extern "C" fn(*mut c_void, *const FrameInput, *mut PluginShape, usize) -> usize,
+  pub destroy: unsafe extern "C" fn(*mut c_void),
+}
+
+/// Puts a [`VisualizerPlugin`] with a `Default` behind the C ABI, as the
+/// library's plugin.
+#[macro_export]
+macro_rules! export_visualizer {
+  ($plugin:ty) => {
+    #[unsafe(no_mangle)]
+    pub extern "C" fn visualiser_plugin_api() -> u32 {
+      $crate::plugins::PLUGIN_API_VERSION
+    }
+
+    #[unsafe(no_mangle)]
+    pub extern "C" fn visualiser_plugin() -> $crate::plugins::PluginVTable {
+      $crate::plugins::vtable::<$plugin>()
+    }
+  };
+}
+
+/// The vtable for `P`, for [`export_visualizer!`].
+pub fn vtable<P: VisualizerPlugin + Default>() -> PluginVTable {
+  PluginVTable { create: create::<P>, name: name::<P>, draw: draw::<P>, destroy: destroy::<P> }
+}
+
+/// A plugin's state behind the vtable, with its name kept nul-terminated.
+struct Adapter<P> {
+  plugin: P,
+  name: CString,
+  shapes: Vec<PluginShape>,
+}
+
+// A panic can't unwind into the app, so each of these catches its own
+
+unsafe extern "C" fn create<P: VisualizerPlugin + Default>() -> *mut c_void {
+  panic::catch_unwind(|| {
+    let plugin = P::default();
+    let name = CString::new(plugin.name().replace('\0', "")).unwrap_or_default();
+    Box::into_raw(Box::new(Adapter { plugin, name, shapes: Vec::new() })) as *mut c_void
+  })
+  .unwrap_or(ptr::null_mut())
+}
+
+unsafe extern "C" fn name<P: VisualizerPlugin>(state: *mut c_void) -> *const c_char {
+  // SAFETY: the host only passes back what `create` returned
+  unsafe { &*(state as *const Adapter<P>) }.name.as_ptr()
+}
+
+unsafe extern "C" fn draw<P: VisualizerPlugin>(
+  state: *mut c_void,
+  input: *const FrameInput,
+  out: *mut PluginShape,
+  capacity: usize,
+) -> usize {
+  // SAFETY: the host passes back what `create` returned, a frame valid for
+  // the call and room for `capacity` shapes
+  let adapter = unsafe { &mut *(state as *mut Adapter<P>) };
+  let frame = unsafe { PluginFrame::from_input(&*input) };
+  let drawn = panic::catch_unwind(AssertUnwindSafe(|| {
+    adapter.shapes.clear();
+    adapter.plugin.draw(&frame, &mut adapter.shapes);
+  }));
+  if drawn.is_err() {
+    return 0;
+  }
+  let count = adapter.shapes.len().min(capacity);
+  unsafe { slice::from_raw_parts_mut(out, count) }.copy_from_slice(&adapter.shapes[..count]);
+  count
+}
+
+unsafe extern "C" fn destroy<P: VisualizerPlugin>(state: *mut c_void) {
+  // SAFETY: it was made by Box::into_raw in `create`
+  let adapter = unsafe { Box::from_raw(state as *mut Adapter<P>) };
+  let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(adapter)));
+}
+
+#[derive(Debug)]
+pub enum PluginError {
+  Load(libloading::Error),
+  /// Built against another version of the plugin API.
+  Version(u32),
+  /// `create` returned null.
+  Create,
+}
+
+impl fmt::Display for PluginError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      PluginError::Load(e) => write!(f, "{}", e),
+      PluginError::Version(version) => {
+        write!(f, "built for plugin API {}, not {}", version, PLUGIN_API_VERSION)
+      }
+      PluginError::Create => write!(f, "the plugin failed to start"),
+    }
+  }
+}
+
+/// The plugin's state, only ever touched through the vtable, and the buffer
+/// it draws its shapes into, kept from frame to frame.
+struct Instance {
+  state: *mut c_void,
+  shapes: Vec<PluginShape>,
+}
+
+// SAFETY: the state is only reached through the mutex around it
+unsafe impl Send for Instance {}
+
+/// A loaded plugin, unloaded when dropped.
+pub struct Plugin {
+  name: String,
+  vtable: PluginVTable,
+  instance: Mutex<Instance>,
+  /// Dropped after the instance is destroyed, which `Drop` sees to.
+  _library: Library,
+}
+
+impl Plugin {
+  /// `<platform config dir>/rust_audio_visualiser/plugins`.
+  pub fn dir() -> Option<PathBuf> {
+    Config::path().and_then(|path| Some(path.parent()?.join(PLUGIN_DIR)))
+  }
+
+  /// Every plugin in the plugin directory, by name. Any that won't load are
+  /// reported and skipped.
+  pub fn load_all() -> Vec<Plugin> {
+    let Some(dir) = Self::dir() else {
+      return Vec::new();
+    };
+    let entries = match fs::read_dir(&dir) {
+      Ok(entries) => entries,
+      Err(e) if e.kind() == io::ErrorKind::NotFound => return Vec::new(),
+      Err(e) => {
+        eprintln!("Failed to read plugins from {}: {}", dir.display(), e);
+        return Vec::new();
+      }
+    };
+    let mut plugins: Vec<Plugin> = entries
+      .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+      .filter(|path| {
+        path.extension().is_some_and(|extension| extension == std::env::consts::DLL_EXTENSION)
+      })
+      .filter_map(|path| {
+        Self::load(&path)
+          .map_err(|e| eprintln!("Failed to load plugin {}: {}", path.display(), e))
+          .ok()
+      })
+      .collect();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+  }
+
+  pub fn load(path: &FilePath) -> Result<Self, PluginError> {
+    // SAFETY: loading runs the library's initialisers; plugins are code the
+    // user chose to install, and the symbols are declared as the API has them
+    let (library, vtable) = unsafe {
+      let library = Library::new(path).map_err(PluginError::Load)?;
+      let api: Symbol<unsafe extern "C" fn() -> u32> =
+        library.get(API_SYMBOL).map_err(PluginError::Load)?;
+      let version = api();
+      if version != PLUGIN_API_VERSION {
+        return Err(PluginError::Version(version));
+      }
+      let entry: Symbol<unsafe extern "C" fn() -> PluginVTable> =
+        library.get(VTABLE_SYMBOL).map_err(PluginError::Load)?;
+      let vtable = entry();
+      (library, vtable)
+    };
+    // SAFETY: calling into the plugin as the API has it
+    let instance = unsafe { (vtable.create)() };
+    if instance.is_null() {
+      return Err(PluginError::Create);
+    }
+    let name = unsafe { (vtable.name)(instance) };
+    let name = if name.is_null() {
+      String::new()
+    } else {
+      unsafe { CStr::from_ptr(name) }.to_string_lossy().trim().to_string()
+    };
+    // Nameless plugins go by their file
+    let name = if name.is_empty() {
+      path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+    } else {
+      name
+    };
+    let instance = Instance { state: instance, shapes: Vec::with_capacity(MAX_SHAPES) };
+    Ok(Self { name, vtable, instance: Mutex::new(instance), _library: library })
+  }
+
+  pub fn name(&self) -> &str {
+    &self.name
+  }
+
+  /// Passes each of the plugin's shapes for `frame` to `shape`.
+  pub fn draw(&self, frame: &PluginFrame, shape: impl FnMut(&PluginShape)) {
+    let input = frame.input();
+    let mut instance = self.instance.lock().unwrap();
+    let Instance { state, shapes } = &mut *instance;
+    shapes.clear();
+    // SAFETY: the instance is the plugin's own, the frame outlives the call
+    // and there's room for MAX_SHAPES; it says how many it wrote
+    unsafe {
+      let count = (self.vtable.draw)(*state, &input, shapes.as_mut_ptr(), MAX_SHAPES);
+      shapes.set_len(count.min(MAX_SHAPES));
+    }
+    shapes.iter().for_each(shape);
+  }
+}
+
+impl Drop for Plugin {
+  fn drop(&mut self) {
+    let instance = self.instance.get_mut().unwrap_or_else(|e| e.into_inner());
+    // SAFETY: the instance came from `create` and isn't used again
+    unsafe { (self.vtable.destroy)(instance.state) };
+  }
+}
+
+impl fmt::Debug for Plugin {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("Plugin").field("name", &self.name).finish_non_exhaustive()
+  }
+}
+
+/// A plugin's shapes on the main canvas.
+pub struct PluginCanvas<'a> {
+  pub plugin: &'a Plugin,
+  pub frame: PluginFrame<'a>,
+  pub cache: &'a canvas::Cache,
+  /// Greys the shapes out while playback is muted.
+  pub muted: bool,
+}
+
+impl<'a> canvas::Program<Message> for PluginCanvas<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: Cursor,
+  ) -> Vec<Geometry> {
+    let geometry = self.cache.draw(renderer, bounds.size(), |frame| {
+      self.plugin.draw(&self.frame, |shape| {
+        let [r, g, b, a] = shape.color;
+        let color = if self.muted {
+          let grey = (r + g + b) / 3.0;
+          Color::from_rgba(grey, grey, grey, a * 0.5)
+        } else {
+          Color::from_rgba(r, g, b, a)
+        };
+        match shape.kind {
+          SHAPE_RECTANGLE => {
+            frame.fill_rectangle(Point::new(shape.x, shape.y), Size::new(shape.a, shape.b), color)
+          }
+          SHAPE_LINE => frame.stroke(
+            &Path::line(Point::new(shape.x, shape.y), Point::new(shape.a, shape.b)),
+            Stroke::default().with_color(color).with_width(shape.width.max(0.5)),
+          ),
+          SHAPE_CIRCLE => frame.fill(&Path::circle(Point::new(shape.x, shape.y), shape.a), color),
+          _ => {}
+        }
+      });
+    });
+    vec![geometry]
+  }
+}
@@ -0,0 +1,132 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::analysis::{AnalysisSettings, DecibelRange, FFT_SIZES, Overlap};
+use crate::components::{
+  grade::ColorGrade, gradient::ColorTheme, particles::Emitter, smoothing::RegionSmoothing,
+  visualiser::VisualStyle,
+};
+use crate::config::{self, Config};
+use crate::ui::settings::{self, VisualSettings};
+
+const PRESETS_FILE: &str = "presets.json";
+/// Presets reachable from the number keys.
+pub const HOTKEY_PRESETS: usize = 9;
+
+/// A named look: the style, bars, colours, smoothing and FFT settings,
+/// switched between in one go.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preset {
+  pub name: String,
+  pub style: VisualStyle,
+  pub bar_count: usize,
+  pub theme: ColorTheme,
+  pub custom_start: String,
+  pub custom_end: String,
+  pub grade: ColorGrade,
+  pub emitters: Vec<Emitter>,
+  pub envelopes: RegionSmoothing,
+  pub decibels: DecibelRange,
+  pub auto_range: bool,
+  pub fft_size: usize,
+  pub overlap: Overlap,
+}
+
+impl Preset {
+  /// The current settings, under `name`.
+  pub fn capture(name: String, visuals: &VisualSettings, analysis: &AnalysisSettings) -> Self {
+    let config = visuals.to_config(analysis);
+    Self {
+      name,
+      style: visuals.style,
+      bar_count: config.bar_count,
+      theme: config.theme,
+      custom_start: config.custom_start,
+      custom_end: config.custom_end,
+      grade: config.grade,
+      emitters: config.particle_emitters,
+      envelopes: config.envelopes,
+      decibels: config.decibels,
+      auto_range: config.auto_range,
+      fft_size: config.fft_size,
+      overlap: config.overlap,
+    }
+  }
+
+  /// Switches the visuals and analysis over to the preset, leaving
+  /// everything it doesn't cover alone.
+  pub fn apply(&self, visuals: &mut VisualSettings, analysis: &mut AnalysisSettings) {
+    visuals.update(settings::Message::StyleSelected(self.style));
+    visuals.update(settings::Message::BarCountChanged(self.bar_count as u16));
+    visuals.update(settings::Message::ThemeSelected(self.theme));
+    visuals.update(settings::Message::CustomStartChanged(self.custom_start.clone()));
+    visuals.update(settings::Message::CustomEndChanged(self.custom_end.clone()));
+    visuals.update(settings::Message::GradeChanged(self.grade));
+    visuals.emitters = self.emitters.clone();
+    visuals.smoothing = self.envelopes;
+    visuals.decibels = self.decibels.repaired();
+    visuals.auto_range = self.auto_range;
+    if FFT_SIZES.contains(&self.fft_size) {
+      analysis.fft_size = self.fft_size;
+    }
+    analysis.overlap = self.overlap;
+  }
+}
+
+impl Default for Preset {
+  fn default() -> Self {
+    let config = Config::default();
+    Self {
+      name: String::new(),
+      style: VisualStyle::default(),
+      bar_count: config.bar_count,
+      theme: config.theme,
+      custom_start: config.custom_start,
+      custom_end: config.custom_end,
+      grade: config.grade,
+      emitters: config.particle_emitters,
+      envelopes: config.envelopes,
+      decibels: config.decibels,
+      auto_range: config.auto_range,
+      fft_size: config.fft_size,
+      overlap: config.overlap,
+    }
+  }
+}
+
+/// Every saved preset, in the order the number keys pick them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetLibrary {
+  pub presets: Vec<Preset>,
+}
+
+impl PresetLibrary {
+  /// Reads the presets, starting empty when the file is missing or broken.
+  pub fn load() -> Self {
+    config::load_json(PRESETS_FILE, "presets").unwrap_or_default()
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    config::save_json(PRESETS_FILE, self)
+  }
+
+  /// Adds `preset`, replacing one with the same name where it was.
+  pub fn insert(&mut self, preset: Preset) {
+    match self.presets.iter_mut().find(|existing| existing.name == preset.name) {
+      Some(existing) => *existing = preset,
+      None => self.presets.push(preset),
+    }
+  }
+
+  pub fn remove(&mut self, index: usize) {
+    if index < self.presets.len() {
+      self.presets.remove(index);
+    }
+  }
+
+  pub fn get(&self, index: usize) -> Option<&Preset> {
+    self.presets.get(index)
+  }
+}
@@ -0,0 +1,64 @@
+//! Python bindings for the offline analysis, so batch jobs get exactly the
+//! bars, beats and loudness the app shows. Build and install into the
+//! active environment with `maturin develop --release`, then
+//!
+//! ```python
+//! import rust_audio_visualiser as rav
+//!
+//! frames = rav.analyse("track.flac")
+//! frames["bars"].shape  # (frame count, bar count)
+//! ```
+
+use numpy::{PyArray1, PyArray2};
+use pyo3::{exceptions::PyIOError, prelude::*, types::PyDict};
+use std::path::PathBuf;
+
+use crate::headless::{self, HEADLESS_FPS, OfflineAnalysis};
+
+/// Analyses a file at 60 frames a second with the app's saved settings.
+///
+/// Returns a dict of numpy float32 arrays, one row per frame: `time`
+/// (seconds), `bars` (frames × bars, heights as drawn), `beat` (strength,
+/// 0 where there's none), `momentary` and `short_term` (LUFS, NaN until
+/// enough audio has gone by), plus `fft` (frames × bins) when asked for.
+/// Also has the `sample_rate`, `fft_size` and `fps` it was analysed with.
+#[pyfunction]
+#[pyo3(signature = (path, fft = false))]
+fn analyse(py: Python<'_>, path: PathBuf, fft: bool) -> PyResult<Bound<'_, PyDict>> {
+  let OfflineAnalysis { sample_rate, fft_size, frames } = py
+    .allow_threads(|| headless::analyse(&path, fft))
+    .map_err(|e| PyIOError::new_err(format!("Failed to analyse {}: {}", path.display(), e)))?;
+
+  let column =
+    |value: fn(&headless::OfflineFrame) -> f32| -> Vec<f32> { frames.iter().map(value).collect() };
+  let bars: Vec<Vec<f32>> = frames.iter().map(|frame| frame.bars.clone()).collect();
+
+  let dict = PyDict::new_bound(py);
+  dict.set_item("time", PyArray1::from_vec_bound(py, column(|frame| frame.time)))?;
+  dict.set_item("bars", PyArray2::from_vec2_bound(py, &bars)?)?;
+  dict.set_item("beat", PyArray1::from_vec_bound(py, column(|frame| frame.beat.unwrap_or(0.0))))?;
+  dict.set_item(
+    "momentary",
+    PyArray1::from_vec_bound(py, column(|frame| frame.momentary.unwrap_or(f32::NAN))),
+  )?;
+  dict.set_item(
+    "short_term",
+    PyArray1::from_vec_bound(py, column(|frame| frame.short_term.unwrap_or(f32::NAN))),
+  )?;
+  if fft {
+    let bins: Vec<Vec<f32>> =
+      frames.iter().map(|frame| frame.fft.clone().unwrap_or_default()).collect();
+    dict.set_item("fft", PyArray2::from_vec2_bound(py, &bins)?)?;
+  }
+  dict.set_item("sample_rate", sample_rate)?;
+  dict.set_item("fft_size", fft_size)?;
+  dict.set_item("fps", HEADLESS_FPS)?;
+  Ok(dict)
+}
+
+#[pymodule]
+#[pyo3(name = "rust_audio_visualiser")]
+fn module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+  m.add_function(wrap_pyfunction!(analyse, m)?)?;
+  Ok(())
+}
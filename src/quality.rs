@@ -0,0 +1,90 @@
+use std::fmt;
+
+use crate::analysis::{AnalysisSettings, BUFFER_SIZE, Overlap};
+use crate::components::{
+  frame_rate::FrameRate,
+  smoothing::{Envelope, RegionSmoothing},
+};
+use crate::ui::settings::VisualSettings;
+
+/// Bundles of analysis settings picked from one dropdown, for anyone who'd
+/// rather not weigh FFT sizes against hop sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QualityProfile {
+  /// Small windows, half as many frames and 30 fps, for weak laptops.
+  /// Slower envelopes hide the lower frame rate.
+  Low,
+  /// The defaults.
+  Balanced,
+  /// Large, heavily overlapped windows at the fastest frame rate.
+  High,
+}
+
+impl QualityProfile {
+  pub const ALL: [QualityProfile; 3] =
+    [QualityProfile::Low, QualityProfile::Balanced, QualityProfile::High];
+
+  fn fft_size(self) -> usize {
+    match self {
+      QualityProfile::Low => 1024,
+      QualityProfile::Balanced => BUFFER_SIZE,
+      QualityProfile::High => 4096,
+    }
+  }
+
+  fn overlap(self) -> Overlap {
+    match self {
+      QualityProfile::Low => Overlap::Half,
+      QualityProfile::Balanced => Overlap::ThreeQuarters,
+      QualityProfile::High => Overlap::SevenEighths,
+    }
+  }
+
+  fn frame_rate(self) -> FrameRate {
+    match self {
+      QualityProfile::Low => FrameRate::Fps30,
+      QualityProfile::Balanced => FrameRate::Fps60,
+      QualityProfile::High => FrameRate::Fps120,
+    }
+  }
+
+  fn smoothing(self) -> RegionSmoothing {
+    match self {
+      QualityProfile::Low => RegionSmoothing {
+        low: Envelope { attack_ms: 20.0, release_ms: 60.0 },
+        mid: Envelope { attack_ms: 20.0, release_ms: 40.0 },
+        high: Envelope { attack_ms: 15.0, release_ms: 30.0 },
+      },
+      QualityProfile::Balanced | QualityProfile::High => RegionSmoothing::default(),
+    }
+  }
+
+  /// Switches the analysis and visuals over to this profile.
+  pub fn apply(self, analysis: &mut AnalysisSettings, visuals: &mut VisualSettings) {
+    analysis.fft_size = self.fft_size();
+    analysis.overlap = self.overlap();
+    visuals.set_frame_rate(self.frame_rate());
+    visuals.smoothing = self.smoothing();
+  }
+
+  /// The profile the settings are on, or `None` once any of them has been
+  /// changed by hand.
+  pub fn matching(analysis: &AnalysisSettings, visuals: &VisualSettings) -> Option<Self> {
+    Self::ALL.into_iter().find(|profile| {
+      analysis.fft_size == profile.fft_size()
+        && analysis.overlap == profile.overlap()
+        && visuals.frame_rate == profile.frame_rate()
+        && visuals.smoothing == profile.smoothing()
+    })
+  }
+}
+
+impl fmt::Display for QualityProfile {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      QualityProfile::Low => "Low",
+      QualityProfile::Balanced => "Balanced",
+      QualityProfile::High => "High",
+    })
+  }
+}
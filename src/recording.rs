@@ -0,0 +1,89 @@
+use hound::{SampleFormat, WavSpec, WavWriter};
+use std::{
+  fs, io,
+  path::{Path, PathBuf},
+  sync::mpsc,
+  thread::{self, JoinHandle},
+  time::{Duration, Instant},
+};
+
+use crate::components::tap::ChunkSlot;
+use crate::encode::WavDepth;
+
+/// Writes the live microphone to a WAV file as it comes in, so recordings
+/// can run as long as the disk allows. The file starts out in the temp
+/// directory and is moved wherever it's saved once recording stops.
+pub struct MicRecording {
+  /// The capture's copy slot, emptied again to stop the writer.
+  slot: ChunkSlot,
+  writer: JoinHandle<Result<(), hound::Error>>,
+  path: PathBuf,
+  started: Instant,
+}
+
+impl MicRecording {
+  /// Starts writing every chunk `slot` gets, as interleaved samples with
+  /// `channels` channels at `sample_rate`.
+  pub fn start(
+    slot: ChunkSlot,
+    channels: u16,
+    sample_rate: u32,
+    depth: WavDepth,
+  ) -> Result<Self, hound::Error> {
+    let path = std::env::temp_dir()
+      .join(format!("rust_audio_visualiser-recording-{}.wav", std::process::id()));
+    let spec = WavSpec {
+      channels,
+      sample_rate,
+      bits_per_sample: depth.bits(),
+      sample_format: if depth == WavDepth::Float32 {
+        SampleFormat::Float
+      } else {
+        SampleFormat::Int
+      },
+    };
+    let mut wav = WavWriter::create(&path, spec)?;
+
+    let (sender, receiver) = mpsc::channel::<Vec<f32>>();
+    let writer = thread::spawn(move || {
+      // Ends once the slot lets go of the sender
+      for chunk in receiver {
+        for sample in chunk {
+          let sample = sample.clamp(-1.0, 1.0);
+          match depth {
+            WavDepth::Pcm16 => wav.write_sample((sample * i16::MAX as f32) as i16)?,
+            WavDepth::Pcm24 => wav.write_sample((sample * 8_388_607.0) as i32)?,
+            WavDepth::Float32 => wav.write_sample(sample)?,
+          }
+        }
+      }
+      wav.finalize()
+    });
+    *slot.lock().unwrap() = Some(sender);
+
+    Ok(Self { slot, writer, path, started: Instant::now() })
+  }
+
+  pub fn elapsed(&self) -> Duration {
+    self.started.elapsed()
+  }
+
+  /// Stops recording and finishes the file, returning where it was written.
+  pub fn finish(self) -> Result<PathBuf, hound::Error> {
+    *self.slot.lock().unwrap() = None;
+    match self.writer.join() {
+      Ok(result) => result.map(|()| self.path),
+      Err(_) => Err(hound::Error::IoError(io::Error::other("recording thread panicked"))),
+    }
+  }
+}
+
+/// Moves a finished recording to `output`, copying when the temp directory
+/// is on another filesystem.
+pub fn save(recording: &Path, output: &Path) -> io::Result<()> {
+  if fs::rename(recording, output).is_err() {
+    fs::copy(recording, output)?;
+    fs::remove_file(recording)?;
+  }
+  Ok(())
+}
@@ -0,0 +1,164 @@
+//! A remote for a phone on the same network: transport buttons, volume,
+//! the presets and a small live spectrum, served by the running app so the
+//! machine hooked up to the TV can be driven from the couch.
+//!
+//! `GET /` is the page and `GET /state` what it shows, as JSON. `POST
+//! /command` with a command as its body does what the button would:
+//!
+//! ```sh
+//! curl -d '{"command": "volume", "value": 0.5}' http://tv.local:8090/command
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::{
+  io::{self, Read},
+  sync::{
+    Arc, Mutex,
+    mpsc::{self, Receiver, Sender},
+  },
+  thread,
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+pub const DEFAULT_REMOTE_PORT: u16 = 8090;
+/// Bars in the page's spectrum; plenty for a phone screen.
+pub const REMOTE_BARS: usize = 32;
+/// Longest command body read; real ones are a few dozen bytes.
+const MAX_COMMAND_BYTES: u64 = 1024;
+const PAGE: &str = include_str!("remote.html");
+
+/// Whether the remote is served, and where.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteSettings {
+  pub enabled: bool,
+  pub port: u16,
+}
+
+impl Default for RemoteSettings {
+  fn default() -> Self {
+    Self { enabled: false, port: DEFAULT_REMOTE_PORT }
+  }
+}
+
+/// The port typed in `text`, if it's one that can be served on.
+pub fn parse_port(text: &str) -> Option<u16> {
+  text.trim().parse().ok().filter(|&port| port != 0)
+}
+
+/// What the page's buttons send.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum RemoteCommand {
+  Play,
+  Pause,
+  Stop,
+  ToggleMute,
+  /// 0.0..=1.0.
+  Volume {
+    value: f32,
+  },
+  Preset {
+    index: usize,
+  },
+}
+
+/// What the page shows, as of the app's last poll.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RemoteState {
+  /// The track playing, by file name.
+  pub title: Option<String>,
+  pub is_loaded: bool,
+  pub is_playing: bool,
+  pub volume: f32,
+  pub is_muted: bool,
+  /// Preset names, in the order a `preset` command picks them by.
+  pub presets: Vec<String>,
+  /// [`REMOTE_BARS`] levels, each 0.0..=1.0.
+  pub spectrum: Vec<f32>,
+}
+
+/// The remote's server, answering on its own thread until dropped.
+pub struct Remote {
+  server: Arc<Server>,
+  commands: Receiver<RemoteCommand>,
+  state: Arc<Mutex<RemoteState>>,
+}
+
+impl Remote {
+  pub fn start(settings: &RemoteSettings) -> io::Result<Self> {
+    let server = Arc::new(Server::http(("0.0.0.0", settings.port)).map_err(io::Error::other)?);
+    let (sender, commands) = mpsc::channel();
+    let state = Arc::new(Mutex::new(RemoteState::default()));
+    let (listener, shown) = (server.clone(), state.clone());
+    thread::spawn(move || {
+      for request in listener.incoming_requests() {
+        respond(request, &sender, &shown);
+      }
+    });
+    Ok(Self { server, commands, state })
+  }
+
+  /// Updates what the page shows.
+  pub fn publish(&self, state: RemoteState) {
+    *self.state.lock().unwrap() = state;
+  }
+
+  /// Commands sent since the last call, oldest first.
+  pub fn commands(&self) -> Vec<RemoteCommand> {
+    self.commands.try_iter().collect()
+  }
+}
+
+impl Drop for Remote {
+  /// Stops the server thread, which frees the port.
+  fn drop(&mut self) {
+    self.server.unblock();
+  }
+}
+
+fn respond(mut request: Request, sender: &Sender<RemoteCommand>, state: &Mutex<RemoteState>) {
+  let method = request.method().clone();
+  let path = request.url().split('?').next().unwrap_or_default().to_string();
+  let response = match (method, path.as_str()) {
+    (Method::Get, "/") => {
+      Response::from_string(PAGE).with_header(content_type("text/html; charset=utf-8"))
+    }
+    (Method::Get, "/state") => match serde_json::to_vec(&*state.lock().unwrap()) {
+      Ok(json) => Response::from_data(json).with_header(content_type("application/json")),
+      Err(e) => plain(500, &e.to_string()),
+    },
+    (Method::Post, "/command") => match read_command(&mut request) {
+      Ok(command) => {
+        // The app only goes away along with this thread
+        let _ = sender.send(command);
+        Response::from_data(Vec::new()).with_status_code(204)
+      }
+      Err(reason) => plain(400, &reason),
+    },
+    _ => plain(404, "the remote serves /, /state and /command"),
+  };
+  if let Err(e) = request.respond(response) {
+    eprintln!("Failed to answer remote: {}", e);
+  }
+}
+
+fn read_command(request: &mut Request) -> Result<RemoteCommand, String> {
+  let mut body = String::new();
+  request
+    .as_reader()
+    .take(MAX_COMMAND_BYTES)
+    .read_to_string(&mut body)
+    .map_err(|e| e.to_string())?;
+  serde_json::from_str(&body).map_err(|e| format!("invalid command: {}", e))
+}
+
+fn plain(status: u16, message: &str) -> Response<io::Cursor<Vec<u8>>> {
+  Response::from_string(format!("{}\n", message))
+    .with_status_code(status)
+    .with_header(content_type("text/plain; charset=utf-8"))
+}
+
+fn content_type(value: &str) -> Header {
+  Header::from_bytes("Content-Type", value).expect("content types are valid headers")
+}
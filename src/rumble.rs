@@ -0,0 +1,194 @@
+//! Game controller rumble, so a pad in hand can feel the music: the bass
+//! energy drives the heavy motor and every beat kicks the light one.
+
+use gilrs::{
+  GamepadId, Gilrs,
+  ff::{self, BaseEffect, BaseEffectType, Effect, EffectBuilder, Repeat, Replay, Ticks},
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::components::{energy::BandEnergy, smoothing::Region};
+
+/// How long each beat's kick lasts.
+const KICK_MS: u32 = 120;
+/// Change in motor strength, 0.0..=1.0, worth telling the pad about.
+const MIN_CHANGE: f32 = 0.02;
+
+/// How the bass energy is turned into motor strength.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RumbleCurve {
+  #[default]
+  Linear,
+  /// Quiet bass barely moves the motor; only the loud parts shake.
+  Gentle,
+  /// Even quiet bass is felt.
+  Strong,
+}
+
+impl RumbleCurve {
+  pub const ALL: [RumbleCurve; 3] = [RumbleCurve::Linear, RumbleCurve::Gentle, RumbleCurve::Strong];
+
+  fn apply(self, level: f32) -> f32 {
+    let level = level.clamp(0.0, 1.0);
+    match self {
+      RumbleCurve::Linear => level,
+      RumbleCurve::Gentle => level * level,
+      RumbleCurve::Strong => level.sqrt(),
+    }
+  }
+}
+
+impl fmt::Display for RumbleCurve {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      RumbleCurve::Linear => "Linear",
+      RumbleCurve::Gentle => "Gentle",
+      RumbleCurve::Strong => "Strong",
+    })
+  }
+}
+
+/// Which controller rumbles, and how hard.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RumbleSettings {
+  pub enabled: bool,
+  /// Controller by name; empty for every one that can rumble.
+  pub device: String,
+  /// Strongest the motors run, 0.0..=1.0.
+  pub intensity: f32,
+  pub curve: RumbleCurve,
+}
+
+impl Default for RumbleSettings {
+  fn default() -> Self {
+    Self { enabled: false, device: String::new(), intensity: 0.7, curve: RumbleCurve::default() }
+  }
+}
+
+#[derive(Debug)]
+pub enum RumbleError {
+  Init(gilrs::Error),
+  NoDevices,
+  DeviceNotFound(String),
+  Effect(ff::Error),
+}
+
+impl fmt::Display for RumbleError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      RumbleError::Init(e) => write!(f, "controllers unavailable: {}", e),
+      RumbleError::NoDevices => write!(f, "no controller that can rumble"),
+      RumbleError::DeviceNotFound(name) => write!(f, "no controller called {}", name),
+      RumbleError::Effect(e) => write!(f, "couldn't set up the rumble: {}", e),
+    }
+  }
+}
+
+impl From<ff::Error> for RumbleError {
+  fn from(e: ff::Error) -> Self {
+    RumbleError::Effect(e)
+  }
+}
+
+/// Names of the connected controllers that can rumble.
+pub fn devices() -> Vec<String> {
+  let Ok(gilrs) = Gilrs::new() else {
+    return Vec::new();
+  };
+  gilrs
+    .gamepads()
+    .filter(|(_, gamepad)| gamepad.is_ff_supported())
+    .map(|(_, gamepad)| gamepad.name().to_string())
+    .collect()
+}
+
+/// Rumble running on the configured controllers.
+pub struct RumbleSender {
+  gilrs: Gilrs,
+  settings: RumbleSettings,
+  /// Runs for as long as the sender does, as strong as the bass.
+  bass: Effect,
+  /// Played once per beat.
+  kick: Effect,
+  /// Strength the bass motor was last set to.
+  sent: f32,
+}
+
+impl RumbleSender {
+  pub fn open(settings: &RumbleSettings) -> Result<Self, RumbleError> {
+    let mut gilrs = Gilrs::new().map_err(RumbleError::Init)?;
+    let pads: Vec<GamepadId> = gilrs
+      .gamepads()
+      .filter(|(_, gamepad)| {
+        gamepad.is_ff_supported()
+          && (settings.device.is_empty() || gamepad.name() == settings.device)
+      })
+      .map(|(id, _)| id)
+      .collect();
+    if pads.is_empty() {
+      return Err(if settings.device.is_empty() {
+        RumbleError::NoDevices
+      } else {
+        RumbleError::DeviceNotFound(settings.device.clone())
+      });
+    }
+
+    let bass = EffectBuilder::new()
+      .add_effect(motor(BaseEffectType::Strong { magnitude: u16::MAX }, Ticks::from_ms(1000)))
+      .gamepads(&pads)
+      .repeat(Repeat::Infinitely)
+      .gain(0.0)
+      .finish(&mut gilrs)?;
+    bass.play()?;
+    let kick = EffectBuilder::new()
+      .add_effect(motor(BaseEffectType::Weak { magnitude: u16::MAX }, Ticks::from_ms(KICK_MS)))
+      .gamepads(&pads)
+      .finish(&mut gilrs)?;
+
+    let settings =
+      RumbleSettings { intensity: settings.intensity.clamp(0.0, 1.0), ..settings.clone() };
+    Ok(Self { gilrs, settings, bass, kick, sent: 0.0 })
+  }
+
+  /// Kicks the light motor for a beat of `strength`, 0.0..=1.0.
+  pub fn send_beat(&mut self, strength: f32) {
+    let gain = strength.clamp(0.0, 1.0) * self.settings.intensity;
+    if let Err(e) = self.kick.set_gain(gain).and_then(|()| self.kick.play()) {
+      eprintln!("Failed to rumble: {}", e);
+    }
+  }
+
+  /// Sets the heavy motor to the bass energy, when it's moved enough to notice.
+  pub fn update(&mut self, energy: &BandEnergy) {
+    // Controllers coming and going are only noticed while events are read
+    while self.gilrs.next_event().is_some() {}
+    let strength = self.settings.curve.apply(energy.level(Region::Low)) * self.settings.intensity;
+    if (strength - self.sent).abs() < MIN_CHANGE {
+      return;
+    }
+    self.sent = strength;
+    if let Err(e) = self.bass.set_gain(strength) {
+      eprintln!("Failed to rumble: {}", e);
+    }
+  }
+}
+
+impl Drop for RumbleSender {
+  /// Never leaves a controller shaking.
+  fn drop(&mut self) {
+    if let Err(e) = self.bass.stop() {
+      eprintln!("Failed to stop the rumble: {}", e);
+    }
+  }
+}
+
+/// One motor at full strength for `length`.
+fn motor(kind: BaseEffectType, length: Ticks) -> BaseEffect {
+  BaseEffect {
+    kind,
+    scheduling: Replay { play_for: length, ..Replay::default() },
+    ..BaseEffect::default()
+  }
+}
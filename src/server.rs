@@ -0,0 +1,301 @@
+//! `--server`: renders over HTTP, for CI jobs and render farms.
+//!
+//! `POST /render?format=mp4` with the audio file as the body renders it
+//! with the saved settings and replies with the video. `format` can also be
+//! `webm`, or `png` for a tar of numbered frames. A preset, in the same JSON
+//! as the config file, can be sent in an `X-Preset` header to render with
+//! that instead; keys it leaves out keep their defaults.
+//!
+//! It listens on the loopback address unless `--bind` says otherwise, say
+//! `--bind 0.0.0.0` to take jobs from the rest of the network. A few renders
+//! run at once and a few more wait their turn; past that a request is turned
+//! away with 503.
+//!
+//! ```sh
+//! curl --data-binary @track.flac -H "X-Preset: $(cat preset.json)" \
+//!   "http://localhost:8080/render?format=webm" -o track.webm
+//! ```
+
+use std::{
+  fmt, fs,
+  io::{self, Read},
+  path::{Path, PathBuf},
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicUsize, Ordering},
+    mpsc::{self, Receiver, TrySendError},
+  },
+  thread,
+};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::app::AudioVisualizer;
+use crate::config::Config;
+use crate::export::{self, ExportError};
+
+pub const DEFAULT_SERVER_PORT: u16 = 8080;
+pub const DEFAULT_SERVER_BIND: &str = "127.0.0.1";
+const PRESET_HEADER: &str = "X-Preset";
+/// Largest audio file taken, in bytes.
+const MAX_BODY_BYTES: u64 = 512 * 1024 * 1024;
+/// Renders run side by side, and requests left waiting for one to finish.
+const RENDER_WORKERS: usize = 2;
+const QUEUED_RENDERS: usize = 8;
+
+/// Numbers each job's scratch directory.
+static JOBS: AtomicUsize = AtomicUsize::new(0);
+
+/// Options for `--server`, read from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerArgs {
+  /// Address listened on.
+  pub bind: String,
+  pub port: u16,
+}
+
+impl ServerArgs {
+  /// `--server [--bind <address>] [--port <port>]`, or `None` without
+  /// `--server`.
+  pub fn parse(args: &[String]) -> Option<Self> {
+    if !args.iter().any(|arg| arg == "--server") {
+      return None;
+    }
+    let value = |flag: &str| args.iter().position(|arg| arg == flag).and_then(|i| args.get(i + 1));
+    let bind = value("--bind").cloned().unwrap_or_else(|| DEFAULT_SERVER_BIND.to_string());
+    let port = value("--port").and_then(|port| port.parse().ok()).unwrap_or(DEFAULT_SERVER_PORT);
+    Some(Self { bind, port })
+  }
+}
+
+/// What a render comes back as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RenderFormat {
+  Mp4,
+  Webm,
+  /// A tar of PNG frames.
+  Png,
+}
+
+impl RenderFormat {
+  fn parse(name: &str) -> Option<Self> {
+    match name {
+      "mp4" => Some(RenderFormat::Mp4),
+      "webm" => Some(RenderFormat::Webm),
+      "png" => Some(RenderFormat::Png),
+      _ => None,
+    }
+  }
+
+  fn content_type(self) -> &'static str {
+    match self {
+      RenderFormat::Mp4 => "video/mp4",
+      RenderFormat::Webm => "video/webm",
+      RenderFormat::Png => "application/x-tar",
+    }
+  }
+}
+
+/// Why a request got no render, with the status it's answered with.
+#[derive(Debug)]
+enum Rejection {
+  BadRequest(String),
+  NotFound,
+  MethodNotAllowed,
+  TooLarge,
+  Busy,
+  Failed(ExportError),
+}
+
+impl Rejection {
+  fn status(&self) -> u16 {
+    match self {
+      Rejection::BadRequest(_) => 400,
+      Rejection::NotFound => 404,
+      Rejection::MethodNotAllowed => 405,
+      Rejection::TooLarge => 413,
+      Rejection::Busy => 503,
+      Rejection::Failed(_) => 500,
+    }
+  }
+}
+
+impl fmt::Display for Rejection {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Rejection::BadRequest(reason) => write!(f, "{}", reason),
+      Rejection::NotFound => write!(f, "only /render is served"),
+      Rejection::MethodNotAllowed => write!(f, "/render takes a POST"),
+      Rejection::TooLarge => {
+        write!(f, "the audio can be at most {} MiB", MAX_BODY_BYTES / 1024 / 1024)
+      }
+      Rejection::Busy => write!(f, "too many renders waiting, try again later"),
+      Rejection::Failed(e) => write!(f, "render failed: {}", e),
+    }
+  }
+}
+
+impl From<io::Error> for Rejection {
+  fn from(e: io::Error) -> Self {
+    Rejection::Failed(ExportError::Io(e))
+  }
+}
+
+impl From<ExportError> for Rejection {
+  fn from(e: ExportError) -> Self {
+    Rejection::Failed(e)
+  }
+}
+
+/// Serves renders on `args.bind`:`args.port` until the process is stopped,
+/// on `RENDER_WORKERS` threads.
+pub fn run(args: &ServerArgs) -> io::Result<()> {
+  let server = Server::http((args.bind.as_str(), args.port)).map_err(io::Error::other)?;
+  eprintln!("Rendering on http://{}:{}/render", args.bind, args.port);
+
+  let (sender, receiver) = mpsc::sync_channel(QUEUED_RENDERS);
+  let receiver = Arc::new(Mutex::new(receiver));
+  for _ in 0..RENDER_WORKERS {
+    let receiver = receiver.clone();
+    thread::spawn(move || work(&receiver));
+  }
+  for request in server.incoming_requests() {
+    match sender.try_send(request) {
+      Ok(()) => {}
+      Err(TrySendError::Full(request)) => reject(request, Rejection::Busy),
+      Err(TrySendError::Disconnected(_)) => break,
+    }
+  }
+  Ok(())
+}
+
+/// Answers queued requests one at a time until the server goes away.
+fn work(receiver: &Mutex<Receiver<Request>>) {
+  loop {
+    // Only held while waiting, so the other workers can take the next one
+    let next = receiver.lock().unwrap().recv();
+    let Ok(mut request) = next else {
+      return;
+    };
+    match render(&mut request) {
+      Ok((body, format)) => {
+        let response = Response::from_data(body).with_header(content_type(format.content_type()));
+        if let Err(e) = request.respond(response) {
+          eprintln!("Failed to send render: {}", e);
+        }
+      }
+      Err(rejection) => reject(request, rejection),
+    }
+  }
+}
+
+fn reject(request: Request, rejection: Rejection) {
+  eprintln!("Failed to render {}: {}", request.url(), rejection);
+  let response = Response::from_data(format!("{}\n", rejection).into_bytes())
+    .with_status_code(rejection.status())
+    .with_header(content_type("text/plain; charset=utf-8"));
+  if let Err(e) = request.respond(response) {
+    eprintln!("Failed to send render: {}", e);
+  }
+}
+
+fn content_type(value: &str) -> Header {
+  Header::from_bytes("Content-Type", value).expect("content types are valid headers")
+}
+
+/// Renders the request's audio, returning the file (or tar of frames) to send back.
+fn render(request: &mut Request) -> Result<(Vec<u8>, RenderFormat), Rejection> {
+  let (path, query) = request.url().split_once('?').unwrap_or((request.url(), ""));
+  if path != "/render" {
+    return Err(Rejection::NotFound);
+  }
+  if *request.method() != Method::Post {
+    return Err(Rejection::MethodNotAllowed);
+  }
+
+  let format = query
+    .split('&')
+    .find_map(|pair| pair.strip_prefix("format="))
+    .map_or(Some(RenderFormat::Mp4), RenderFormat::parse)
+    .ok_or_else(|| Rejection::BadRequest("format must be mp4, webm or png".to_string()))?;
+  let config = match request.headers().iter().find(|header| header.field.equiv(PRESET_HEADER)) {
+    Some(header) => serde_json::from_str(header.value.as_str())
+      .map_err(|e| Rejection::BadRequest(format!("invalid preset: {}", e)))?,
+    None => Config::load(),
+  };
+
+  if request.body_length().is_some_and(|length| length as u64 > MAX_BODY_BYTES) {
+    return Err(Rejection::TooLarge);
+  }
+  // One byte over the limit is enough to tell a body that didn't say how long it was
+  let mut audio = Vec::new();
+  request.as_reader().take(MAX_BODY_BYTES + 1).read_to_end(&mut audio)?;
+  if audio.len() as u64 > MAX_BODY_BYTES {
+    return Err(Rejection::TooLarge);
+  }
+  if audio.is_empty() {
+    return Err(Rejection::BadRequest("send the audio file as the body".to_string()));
+  }
+
+  let job = Job::new()?;
+  let result = job.render(&audio, &config, format);
+  job.clean_up();
+  Ok((result?, format))
+}
+
+/// A scratch directory for one render, since decoding and ffmpeg both
+/// want files.
+struct Job {
+  dir: PathBuf,
+}
+
+impl Job {
+  fn new() -> io::Result<Self> {
+    let id = JOBS.fetch_add(1, Ordering::Relaxed);
+    let dir =
+      std::env::temp_dir().join(format!("rust_audio_visualiser-{}-{}", std::process::id(), id));
+    fs::create_dir_all(&dir)?;
+    Ok(Self { dir })
+  }
+
+  fn render(
+    &self,
+    audio: &[u8],
+    config: &Config,
+    format: RenderFormat,
+  ) -> Result<Vec<u8>, Rejection> {
+    let input = self.dir.join("input");
+    fs::write(&input, audio)?;
+
+    let mut app = AudioVisualizer::default();
+    app.apply_config(config);
+    let visuals = app.visuals.clone();
+    let analysis_settings = *app.analysis_settings.lock().unwrap();
+
+    match format {
+      RenderFormat::Mp4 | RenderFormat::Webm => {
+        let extension = if format == RenderFormat::Webm { "webm" } else { "mp4" };
+        let output = self.dir.join("output").with_extension(extension);
+        export::render_video(&input, &output, None, visuals, analysis_settings)?;
+        Ok(fs::read(output)?)
+      }
+      RenderFormat::Png => {
+        let frames = self.dir.join("frames");
+        export::render_frame_sequence(&input, &frames, visuals, analysis_settings)?;
+        tar_frames(&frames)
+      }
+    }
+  }
+
+  fn clean_up(self) {
+    if let Err(e) = fs::remove_dir_all(&self.dir) {
+      eprintln!("Failed to remove {}: {}", self.dir.display(), e);
+    }
+  }
+}
+
+/// `dir`'s files, tarred under `frames/`.
+fn tar_frames(dir: &Path) -> Result<Vec<u8>, Rejection> {
+  let mut archive = tar::Builder::new(Vec::new());
+  archive.append_dir_all("frames", dir)?;
+  Ok(archive.into_inner()?)
+}
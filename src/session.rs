@@ -0,0 +1,66 @@
+//! The last session: what was playing, where, how loud and how it looked,
+//! saved on exit so the next launch picks up where it left off.
+
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::config::{self, Config};
+
+const SESSION_FILE: &str = "session.json";
+
+/// Whether the last session is reopened at launch, and how.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SessionSettings {
+  pub restore: bool,
+  /// Carries on playing a track that was playing on exit, rather than
+  /// waiting paused at the saved position.
+  pub resume_playing: bool,
+}
+
+impl Default for SessionSettings {
+  fn default() -> Self {
+    Self { restore: true, resume_playing: false }
+  }
+}
+
+/// Everything that's put back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Session {
+  pub playlist: Vec<String>,
+  /// Position in the playlist of the track that was playing.
+  pub track: usize,
+  pub position_secs: f32,
+  pub volume: f32,
+  pub muted: bool,
+  pub playing: bool,
+  /// The visual settings as they were, in the config's shape, whether or
+  /// not they were saved.
+  pub visuals: Option<Config>,
+}
+
+impl Session {
+  /// Reads the last session, if there's one to read.
+  pub fn load() -> Option<Self> {
+    config::load_json(SESSION_FILE, "session")
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    config::save_json(SESSION_FILE, self)
+  }
+}
+
+impl Default for Session {
+  fn default() -> Self {
+    Self {
+      playlist: Vec::new(),
+      track: 0,
+      position_secs: 0.0,
+      volume: 1.0,
+      muted: false,
+      playing: false,
+      visuals: None,
+    }
+  }
+}
@@ -0,0 +1,207 @@
+//! Shared-memory output: the latest analysis frame written into a
+//! memory-mapped file, so game engines and VJ software on the same machine
+//! can read the spectrum with no IPC at all.
+//!
+//! The file has a fixed size and is little-endian, laid out as
+//!
+//! | offset | type | |
+//! |---|---|---|
+//! | 0 | `[u8; 4]` | magic, `RAVS` |
+//! | 4 | `u32` | layout version, 1 |
+//! | 8 | `u64` | sequence, odd while a frame is being written |
+//! | 16 | `u64` | frames written since the output was opened |
+//! | 24 | `u64` | microseconds since the Unix epoch, when the frame was written |
+//! | 32 | `u32` | sample rate |
+//! | 36 | `f32` | beat strength, 0.0 when there was no beat |
+//! | 40 | `u32` | bars in use |
+//! | 44 | `u32` | bins in use |
+//! | 48 | `u32` | bar capacity |
+//! | 52 | `u32` | bin capacity |
+//! | 64 | `f32` × bar capacity | bar levels, each 0.0..=1.0 |
+//! | 64 + 4 × bar capacity | `f32` × bin capacity | magnitudes of the mixed spectrum |
+//!
+//! It's a seqlock, so the writer never waits on a reader: read the
+//! sequence, copy what's wanted, read the sequence again, and keep the copy
+//! only if both reads were the same even number.
+
+use memmap2::MmapMut;
+use serde::{Deserialize, Serialize};
+use std::{
+  fs::OpenOptions,
+  io,
+  path::PathBuf,
+  sync::atomic::{AtomicU64, Ordering, fence},
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+  analysis::{AnalysisFrame, DecibelRange, FFT_SIZES},
+  components::{bars, binning::FrequencyScale},
+};
+
+const MAGIC: &[u8; 4] = b"RAVS";
+const LAYOUT_VERSION: u32 = 1;
+/// Room for the most bars the panel offers.
+const BAR_CAPACITY: usize = 512;
+/// Room for the bins of the largest FFT.
+const BIN_CAPACITY: usize = FFT_SIZES[FFT_SIZES.len() - 1] / 2;
+const HEADER_SIZE: usize = 64;
+const REGION_SIZE: usize = HEADER_SIZE + (BAR_CAPACITY + BIN_CAPACITY) * size_of::<f32>();
+
+/// Where each header field sits, in bytes from the start.
+const VERSION_AT: usize = 4;
+const SEQUENCE_AT: usize = 8;
+const FRAMES_AT: usize = 16;
+const TIMESTAMP_AT: usize = 24;
+const SAMPLE_RATE_AT: usize = 32;
+const BEAT_AT: usize = 36;
+const BARS_AT: usize = 40;
+const BINS_AT: usize = 44;
+const BAR_CAPACITY_AT: usize = 48;
+const BIN_CAPACITY_AT: usize = 52;
+const BAR_DATA_AT: usize = HEADER_SIZE;
+const BIN_DATA_AT: usize = BAR_DATA_AT + BAR_CAPACITY * size_of::<f32>();
+
+/// Whether the frames are shared, and the file they're shared through.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ShmSettings {
+  pub enabled: bool,
+  pub path: String,
+}
+
+impl Default for ShmSettings {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      path: default_dir().join("rust_audio_visualiser.shm").display().to_string(),
+    }
+  }
+}
+
+/// `/dev/shm` where there is one, so the file lives in memory rather than
+/// on disk, or else the temporary directory.
+fn default_dir() -> PathBuf {
+  let shm = PathBuf::from("/dev/shm");
+  if cfg!(target_os = "linux") && shm.is_dir() { shm } else { std::env::temp_dir() }
+}
+
+/// One analysis frame, as it's shared.
+pub struct ShmFrame<'a> {
+  /// Each 0.0..=1.0.
+  pub bars: &'a [f32],
+  /// Magnitudes of the mixed spectrum, half an FFT.
+  pub spectrum: &'a [f32],
+  pub beat: Option<f32>,
+  pub sample_rate: u32,
+}
+
+/// The output as the analysis thread sees it: the writer while it's on,
+/// and how the view currently turns a spectrum into bars, so each frame is
+/// shared the moment it's analysed rather than when it's drawn.
+#[derive(Default)]
+pub struct ShmOutput {
+  pub writer: Option<ShmWriter>,
+  pub bar_count: usize,
+  pub scale: FrequencyScale,
+  pub decibels: DecibelRange,
+}
+
+impl ShmOutput {
+  /// Shares `frame`, if the output is on, with its bars worked out the way
+  /// the view would before smoothing.
+  pub fn publish(&mut self, frame: &AnalysisFrame, sample_rate: u32) {
+    let Some(writer) = &mut self.writer else {
+      return;
+    };
+    let bars = bars::levels(&frame.spectra, sample_rate, self.bar_count, self.scale, self.decibels);
+    writer.publish(&ShmFrame {
+      bars: &bars,
+      spectrum: &frame.mixed(),
+      beat: frame.beat,
+      sample_rate,
+    });
+  }
+}
+
+/// The mapped region frames are written into.
+pub struct ShmWriter {
+  map: MmapMut,
+  frames: u64,
+}
+
+impl ShmWriter {
+  /// Maps the file at `settings.path`, creating it at the region's size,
+  /// and writes a fresh header.
+  pub fn open(settings: &ShmSettings) -> io::Result<Self> {
+    let file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .truncate(false)
+      .open(&settings.path)?;
+    file.set_len(REGION_SIZE as u64)?;
+    // SAFETY: other processes only ever read the file, and the map is
+    // kept to the size just set
+    let mut map = unsafe { MmapMut::map_mut(&file)? };
+    map[..HEADER_SIZE].fill(0);
+    map[..MAGIC.len()].copy_from_slice(MAGIC);
+    for (at, value) in [
+      (VERSION_AT, LAYOUT_VERSION),
+      (BAR_CAPACITY_AT, BAR_CAPACITY as u32),
+      (BIN_CAPACITY_AT, BIN_CAPACITY as u32),
+    ] {
+      map[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    Ok(Self { map, frames: 0 })
+  }
+
+  /// Replaces the shared frame with `frame`, cutting the bars and bins
+  /// down to the region's capacity.
+  pub fn publish(&mut self, frame: &ShmFrame) {
+    let bars = &frame.bars[..frame.bars.len().min(BAR_CAPACITY)];
+    let bins = &frame.spectrum[..frame.spectrum.len().min(BIN_CAPACITY)];
+    let timestamp =
+      SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_micros() as u64);
+    self.frames += 1;
+
+    let base = self.map.as_mut_ptr();
+    // SAFETY: every write lands inside the region, which `open` sized, and
+    // the map starts on a page so the sequence is aligned for a u64
+    unsafe {
+      let sequence = &*(base.add(SEQUENCE_AT) as *const AtomicU64);
+      let start = sequence.load(Ordering::Relaxed);
+      sequence.store(start.wrapping_add(1), Ordering::Relaxed);
+      fence(Ordering::Release);
+
+      write(base, FRAMES_AT, &self.frames.to_le_bytes());
+      write(base, TIMESTAMP_AT, &timestamp.to_le_bytes());
+      write(base, SAMPLE_RATE_AT, &frame.sample_rate.to_le_bytes());
+      write(base, BEAT_AT, &frame.beat.unwrap_or(0.0).to_le_bytes());
+      write(base, BARS_AT, &(bars.len() as u32).to_le_bytes());
+      write(base, BINS_AT, &(bins.len() as u32).to_le_bytes());
+      write_floats(base, BAR_DATA_AT, bars);
+      write_floats(base, BIN_DATA_AT, bins);
+
+      sequence.store(start.wrapping_add(2), Ordering::Release);
+    }
+  }
+}
+
+/// Copies `bytes` into the region `offset` bytes in.
+///
+/// # Safety
+///
+/// `offset + bytes.len()` must be inside the region `base` starts.
+unsafe fn write(base: *mut u8, offset: usize, bytes: &[u8]) {
+  unsafe { std::ptr::copy_nonoverlapping(bytes.as_ptr(), base.add(offset), bytes.len()) };
+}
+
+/// # Safety
+///
+/// As for [`write`], with the floats' bytes.
+unsafe fn write_floats(base: *mut u8, offset: usize, values: &[f32]) {
+  for (i, value) in values.iter().enumerate() {
+    unsafe { write(base, offset + i * size_of::<f32>(), &value.to_le_bytes()) };
+  }
+}
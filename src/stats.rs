@@ -0,0 +1,137 @@
+//! Listening statistics: how often and how long each track has been played,
+//! how loud it was and its tempo, kept next to the config across runs.
+
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::BTreeMap,
+  io::{self, Write},
+};
+
+use crate::config;
+
+const STATS_FILE: &str = "listening_stats.json";
+/// Seconds of a track that have to be heard for it to count as a play.
+pub const MIN_PLAY_SECONDS: f32 = 30.0;
+/// Width of the tempo ranges tracks are counted in.
+const BPM_RANGE: u32 = 10;
+
+/// One track's listening so far.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrackStats {
+  pub plays: u32,
+  pub seconds: f64,
+  /// Each listen's loudness in LUFS times its length, and the total length
+  /// that had a loudness, for a time-weighted average.
+  loudness_sum: f64,
+  loudness_seconds: f64,
+  pub bpm: Option<f32>,
+}
+
+impl TrackStats {
+  /// Average loudness over every listen, in LUFS.
+  pub fn loudness(&self) -> Option<f32> {
+    (self.loudness_seconds > 0.0).then(|| (self.loudness_sum / self.loudness_seconds) as f32)
+  }
+}
+
+/// One stretch of listening to a track, from when it started to when
+/// another took over or the app closed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Listen {
+  pub seconds: f32,
+  /// Integrated loudness heard, in LUFS.
+  pub loudness: Option<f32>,
+  pub bpm: Option<f32>,
+}
+
+/// Every track listened to, by path.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListeningStats {
+  pub tracks: BTreeMap<String, TrackStats>,
+}
+
+impl ListeningStats {
+  /// Reads the statistics, starting empty when the file is missing or broken.
+  pub fn load() -> Self {
+    config::load_json(STATS_FILE, "listening stats").unwrap_or_default()
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    config::save_json(STATS_FILE, self)
+  }
+
+  /// Adds a listen to `path`'s statistics; long enough ones count as a play.
+  pub fn record(&mut self, path: &str, listen: Listen) {
+    if listen.seconds <= 0.0 {
+      return;
+    }
+    let stats = self.tracks.entry(path.to_string()).or_default();
+    if listen.seconds >= MIN_PLAY_SECONDS {
+      stats.plays += 1;
+    }
+    stats.seconds += listen.seconds as f64;
+    if let Some(loudness) = listen.loudness {
+      stats.loudness_sum += loudness as f64 * listen.seconds as f64;
+      stats.loudness_seconds += listen.seconds as f64;
+    }
+    if listen.bpm.is_some() {
+      stats.bpm = listen.bpm;
+    }
+  }
+
+  pub fn plays(&self) -> u32 {
+    self.tracks.values().map(|stats| stats.plays).sum()
+  }
+
+  pub fn seconds(&self) -> f64 {
+    self.tracks.values().map(|stats| stats.seconds).sum()
+  }
+
+  /// Loudness over all listening, weighted by time, in LUFS.
+  pub fn loudness(&self) -> Option<f32> {
+    let (sum, seconds) = self.tracks.values().fold((0.0, 0.0), |(sum, seconds), stats| {
+      (sum + stats.loudness_sum, seconds + stats.loudness_seconds)
+    });
+    (seconds > 0.0).then(|| (sum / seconds) as f32)
+  }
+
+  /// The tempo range most plays fell in, as its lowest and highest BPM.
+  pub fn common_bpm_range(&self) -> Option<(u32, u32)> {
+    let mut ranges = BTreeMap::new();
+    for stats in self.tracks.values() {
+      if let Some(bpm) = stats.bpm {
+        *ranges.entry(bpm as u32 / BPM_RANGE).or_insert(0) += stats.plays;
+      }
+    }
+    let (range, plays) = ranges.into_iter().max_by_key(|&(_, plays)| plays)?;
+    (plays > 0).then(|| (range * BPM_RANGE, (range + 1) * BPM_RANGE))
+  }
+
+  /// The `count` most played tracks, most played first.
+  pub fn top(&self, count: usize) -> Vec<(&str, &TrackStats)> {
+    let mut tracks: Vec<_> =
+      self.tracks.iter().map(|(path, stats)| (path.as_str(), stats)).collect();
+    tracks.sort_by(|a, b| b.1.plays.cmp(&a.1.plays).then(b.1.seconds.total_cmp(&a.1.seconds)));
+    tracks.truncate(count);
+    tracks
+  }
+
+  /// One row per track, with a header.
+  pub fn write_csv(&self, writer: &mut impl Write) -> io::Result<()> {
+    writeln!(writer, "path,plays,seconds,loudness_lufs,bpm")?;
+    for (path, stats) in &self.tracks {
+      writeln!(
+        writer,
+        "\"{}\",{},{:.0},{},{}",
+        path.replace('"', "\"\""),
+        stats.plays,
+        stats.seconds,
+        stats.loudness().map_or(String::new(), |loudness| format!("{:.1}", loudness)),
+        stats.bpm.map_or(String::new(), |bpm| format!("{:.1}", bpm)),
+      )?;
+    }
+    Ok(())
+  }
+}
@@ -0,0 +1,140 @@
+//! Analysis stems: every frame of a video export's analysis, saved next to
+//! the video so the song can be rendered again with another look without
+//! decoding or analysing it again.
+//!
+//! A stem is [`MAGIC`], the [`StemHeader`] as JSON behind its length, then
+//! each frame in turn: the beat strength (NaN for none), the number of
+//! spectra, then each spectrum, the phase and the group delay as a length
+//! and that many floats. Numbers are little-endian.
+//!
+//! Only what comes after the analysis can change on a re-render: the
+//! style, bars, colours and smoothing. The FFT size, window and weighting
+//! are the stem's.
+
+use serde::{Deserialize, Serialize};
+use std::{
+  fs::File,
+  io::{self, BufReader, BufWriter, Read, Write},
+  path::Path,
+  time::{Duration, Instant},
+};
+
+use crate::analysis::AnalysisFrame;
+
+pub const STEM_EXTENSION: &str = "avstem";
+const MAGIC: &[u8; 8] = b"AVSTEM01";
+/// Longest header read, against a file that only looks like a stem.
+const MAX_HEADER_BYTES: u32 = 1 << 20;
+/// Most floats one list can hold, for the same reason.
+const MAX_VALUES: u32 = 1 << 20;
+
+/// What a stem was analysed from, and how.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StemHeader {
+  /// The file analysed; muxed back in on a re-render while it's still there.
+  pub audio: String,
+  pub sample_rate: u32,
+  pub channels: u16,
+  /// Frames per second of video, one analysis frame each.
+  pub fps: u32,
+  pub fft_size: usize,
+  pub frames: u64,
+}
+
+/// Writes a stem one frame at a time, as the video renders.
+pub struct StemWriter {
+  writer: BufWriter<File>,
+}
+
+impl StemWriter {
+  pub fn create(path: &Path, header: &StemHeader) -> io::Result<Self> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    let json = serde_json::to_vec(header)?;
+    writer.write_all(MAGIC)?;
+    writer.write_all(&(json.len() as u32).to_le_bytes())?;
+    writer.write_all(&json)?;
+    Ok(Self { writer })
+  }
+
+  pub fn push(&mut self, frame: &AnalysisFrame) -> io::Result<()> {
+    self.writer.write_all(&frame.beat.unwrap_or(f32::NAN).to_le_bytes())?;
+    self.writer.write_all(&(frame.spectra.len() as u32).to_le_bytes())?;
+    for values in frame.spectra.iter().chain([&frame.phase, &frame.group_delay]) {
+      self.writer.write_all(&(values.len() as u32).to_le_bytes())?;
+      for value in values {
+        self.writer.write_all(&value.to_le_bytes())?;
+      }
+    }
+    Ok(())
+  }
+
+  pub fn finish(mut self) -> io::Result<()> {
+    self.writer.flush()
+  }
+}
+
+/// Reads a stem back one frame at a time.
+pub struct StemReader {
+  pub header: StemHeader,
+  reader: BufReader<File>,
+}
+
+impl StemReader {
+  pub fn open(path: &Path) -> io::Result<Self> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0; MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "not an analysis stem"));
+    }
+    let length = read_u32(&mut reader)?;
+    if length > MAX_HEADER_BYTES {
+      return Err(io::Error::new(io::ErrorKind::InvalidData, "stem header too long"));
+    }
+    let mut json = vec![0; length as usize];
+    reader.read_exact(&mut json)?;
+    let header = serde_json::from_slice(&json)?;
+    Ok(Self { header, reader })
+  }
+
+  /// The next frame, or `None` at the end of the stem.
+  pub fn next_frame(&mut self) -> io::Result<Option<AnalysisFrame>> {
+    let beat = match read_f32(&mut self.reader) {
+      Ok(beat) => beat,
+      Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+      Err(e) => return Err(e),
+    };
+    let streams = read_u32(&mut self.reader)?;
+    let spectra = (0..streams).map(|_| read_values(&mut self.reader)).collect::<io::Result<_>>()?;
+    let phase = read_values(&mut self.reader)?;
+    let group_delay = read_values(&mut self.reader)?;
+    Ok(Some(AnalysisFrame {
+      spectra,
+      produced_at: Instant::now(),
+      elapsed: Duration::from_secs_f32(1.0 / self.header.fps.max(1) as f32),
+      beat: (!beat.is_nan()).then_some(beat),
+      phase,
+      group_delay,
+    }))
+  }
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+  let mut bytes = [0; 4];
+  reader.read_exact(&mut bytes)?;
+  Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_f32(reader: &mut impl Read) -> io::Result<f32> {
+  let mut bytes = [0; 4];
+  reader.read_exact(&mut bytes)?;
+  Ok(f32::from_le_bytes(bytes))
+}
+
+fn read_values(reader: &mut impl Read) -> io::Result<Vec<f32>> {
+  let length = read_u32(reader)?;
+  if length > MAX_VALUES {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "stem frame too long"));
+  }
+  (0..length).map(|_| read_f32(reader)).collect()
+}
@@ -0,0 +1,215 @@
+//! Preset suggestions for each track from its tempo, spectral balance and
+//! dynamics. Once presets have been picked by hand, the one picked for the
+//! most similar track wins; until then, livelier tracks get the presets
+//! with the snappiest smoothing.
+
+use rodio::Source;
+use serde::{Deserialize, Serialize};
+use std::{f32::consts::TAU, fmt, io, path::Path};
+
+use crate::components::{smoothing::Region, tempo};
+use crate::config;
+use crate::decode::{AudioDecoder, DecodeError};
+use crate::presets::{Preset, PresetLibrary};
+
+const CHOICES_FILE: &str = "preset_choices.json";
+/// Hand picks remembered; the oldest go first.
+const MAX_CHOICES: usize = 200;
+/// Below this, in Hz, counts as bass.
+const BASS_CUTOFF: f32 = 200.0;
+/// Length of the windows loudness is compared over, in seconds.
+const WINDOW_SECONDS: f32 = 0.5;
+/// Windows quieter than this, in dB, are gaps rather than quiet playing.
+const GATE_DB: f32 = -60.0;
+/// Tempo range mapped onto calm to driving.
+const SLOW_BPM: f32 = 70.0;
+const FAST_BPM: f32 = 150.0;
+/// Spread in dB between loud and quiet moments that counts as fully dynamic.
+const WIDE_DYNAMICS_DB: f32 = 20.0;
+
+/// Whether presets are suggested when a track starts, and whether the
+/// suggestion is switched to straight away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SuggestMode {
+  #[default]
+  Off,
+  Suggest,
+  AutoPick,
+}
+
+impl SuggestMode {
+  pub const ALL: [SuggestMode; 3] = [SuggestMode::Off, SuggestMode::Suggest, SuggestMode::AutoPick];
+}
+
+impl fmt::Display for SuggestMode {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      SuggestMode::Off => "No suggestions",
+      SuggestMode::Suggest => "Suggest a preset",
+      SuggestMode::AutoPick => "Pick a preset",
+    })
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SuggestSettings {
+  pub mode: SuggestMode,
+  /// Remembers the presets picked by hand for each kind of track.
+  pub learn: bool,
+}
+
+impl Default for SuggestSettings {
+  fn default() -> Self {
+    Self { mode: SuggestMode::Off, learn: true }
+  }
+}
+
+/// What a track is like, as far as picking a look goes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TrackCharacter {
+  pub bpm: f32,
+  /// Share of the power below [`BASS_CUTOFF`], 0.0..=1.0.
+  pub bass: f32,
+  /// Spread between the track's loud and quiet moments, in dB.
+  pub dynamics: f32,
+}
+
+impl TrackCharacter {
+  /// Decodes the whole file. `bpm` saves finding the tempo again when the
+  /// auto-DJ already has it.
+  pub fn analyse(path: &Path, bpm: Option<f32>) -> Result<Self, DecodeError> {
+    let decoder = AudioDecoder::open(path)?;
+    let sample_rate = decoder.sample_rate();
+    let channels = decoder.channels().max(1) as usize;
+    let interleaved: Vec<f32> = decoder.convert_samples::<f32>().collect();
+    let mono: Vec<f32> = interleaved
+      .chunks(channels)
+      .map(|frame| frame.iter().sum::<f32>() / frame.len() as f32)
+      .collect();
+
+    // A one-pole low-pass splits off the bass
+    let coefficient = 1.0 - (-TAU * BASS_CUTOFF / sample_rate as f32).exp();
+    let mut low = 0.0;
+    let (mut bass_power, mut total_power) = (0.0f64, 0.0f64);
+    let window = ((sample_rate as f32 * WINDOW_SECONDS) as usize).max(1);
+    let mut levels = Vec::with_capacity(mono.len() / window + 1);
+    for chunk in mono.chunks(window) {
+      let mut power = 0.0f64;
+      for &sample in chunk {
+        low += (sample - low) * coefficient;
+        bass_power += (low * low) as f64;
+        power += (sample * sample) as f64;
+      }
+      total_power += power;
+      let decibels = 10.0 * (power / chunk.len() as f64).max(1e-12).log10() as f32;
+      if decibels > GATE_DB {
+        levels.push(decibels);
+      }
+    }
+    levels.sort_by(f32::total_cmp);
+    let dynamics = match levels.len() {
+      0 => 0.0,
+      count => levels[count * 9 / 10] - levels[count / 10],
+    };
+
+    Ok(Self {
+      bpm: bpm
+        .or_else(|| tempo::estimate_tempo(&mono, sample_rate).map(|tempo| tempo.bpm))
+        .unwrap_or(SLOW_BPM),
+      bass: if total_power > 0.0 { (bass_power / total_power) as f32 } else { 0.0 },
+      dynamics,
+    })
+  }
+
+  /// Tempo, bass and dynamics each mapped to 0.0..=1.0, with compressed
+  /// tracks counting as the more intense.
+  fn features(&self) -> [f32; 3] {
+    [
+      ((self.bpm - SLOW_BPM) / (FAST_BPM - SLOW_BPM)).clamp(0.0, 1.0),
+      self.bass.clamp(0.0, 1.0),
+      1.0 - (self.dynamics / WIDE_DYNAMICS_DB).clamp(0.0, 1.0),
+    ]
+  }
+
+  /// 0.0 for calm and ambient up to 1.0 for fast, heavy and loud.
+  pub fn intensity(&self) -> f32 {
+    let [tempo, bass, density] = self.features();
+    0.4 * tempo + 0.3 * bass + 0.3 * density
+  }
+
+  fn distance(&self, other: &TrackCharacter) -> f32 {
+    self.features().iter().zip(other.features()).map(|(a, b)| (a - b) * (a - b)).sum::<f32>().sqrt()
+  }
+}
+
+/// A preset picked by hand while a track was playing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Choice {
+  pub character: TrackCharacter,
+  pub preset: String,
+}
+
+/// Every remembered hand pick, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresetChoices {
+  pub choices: Vec<Choice>,
+}
+
+impl PresetChoices {
+  /// Reads the picks, starting empty when the file is missing or broken.
+  pub fn load() -> Self {
+    config::load_json(CHOICES_FILE, "preset choices").unwrap_or_default()
+  }
+
+  pub fn save(&self) -> io::Result<()> {
+    config::save_json(CHOICES_FILE, self)
+  }
+
+  pub fn record(&mut self, character: TrackCharacter, preset: String) {
+    self.choices.push(Choice { character, preset });
+    if self.choices.len() > MAX_CHOICES {
+      self.choices.remove(0);
+    }
+  }
+}
+
+/// Index of the preset that suits `character`, if there are any presets.
+pub fn suggest(
+  character: &TrackCharacter,
+  presets: &PresetLibrary,
+  choices: &PresetChoices,
+) -> Option<usize> {
+  let learned = choices
+    .choices
+    .iter()
+    .filter_map(|choice| {
+      let index = presets.presets.iter().position(|preset| preset.name == choice.preset)?;
+      Some((index, character.distance(&choice.character)))
+    })
+    .min_by(|a, b| a.1.total_cmp(&b.1));
+  if let Some((index, _)) = learned {
+    return Some(index);
+  }
+
+  // Calmest first, then the track's intensity picks along the line
+  let mut ranked: Vec<usize> = (0..presets.presets.len()).collect();
+  ranked.sort_by(|&a, &b| {
+    response_ms(&presets.presets[b]).total_cmp(&response_ms(&presets.presets[a]))
+  });
+  let last = ranked.len().checked_sub(1)?;
+  Some(ranked[(character.intensity() * last as f32).round() as usize])
+}
+
+/// How slowly a preset's bars follow the music: its attack and release
+/// times added up over the regions.
+fn response_ms(preset: &Preset) -> f32 {
+  Region::ALL
+    .iter()
+    .map(|&region| {
+      let envelope = preset.envelopes.get(region);
+      envelope.attack_ms + envelope.release_ms
+    })
+    .sum()
+}
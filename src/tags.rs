@@ -0,0 +1,161 @@
+use lofty::{
+  config::WriteOptions,
+  error::LoftyError,
+  file::{AudioFile, TaggedFile, TaggedFileExt},
+  picture::PictureType,
+  tag::{Accessor, ItemKey, Tag, TagExt},
+};
+use std::{fmt, path::Path, time::Duration};
+
+use crate::identify::TrackInfo;
+
+/// A tag the visualiser reads and can correct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagField {
+  Title,
+  Artist,
+  Album,
+}
+
+impl TagField {
+  pub const ALL: [TagField; 3] = [TagField::Title, TagField::Artist, TagField::Album];
+}
+
+impl fmt::Display for TagField {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      TagField::Title => "Title",
+      TagField::Artist => "Artist",
+      TagField::Album => "Album",
+    })
+  }
+}
+
+/// The basic tags of a file; empty when missing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tags {
+  pub title: String,
+  pub artist: String,
+  pub album: String,
+}
+
+impl Tags {
+  pub fn read(path: &Path) -> Result<Self, LoftyError> {
+    Ok(Self::from_tagged(&lofty::read_from_path(path)?))
+  }
+
+  fn from_tagged(tagged: &TaggedFile) -> Self {
+    let Some(tag) = tagged.primary_tag().or_else(|| tagged.first_tag()) else {
+      return Self::default();
+    };
+    Self {
+      title: tag.title().map(|title| title.to_string()).unwrap_or_default(),
+      artist: tag.artist().map(|artist| artist.to_string()).unwrap_or_default(),
+      album: tag.album().map(|album| album.to_string()).unwrap_or_default(),
+    }
+  }
+
+  /// Missing the title or artist, so worth offering a correction for.
+  pub fn is_poor(&self) -> bool {
+    self.title.trim().is_empty() || self.artist.trim().is_empty()
+  }
+
+  pub fn get(&self, field: TagField) -> &str {
+    match field {
+      TagField::Title => &self.title,
+      TagField::Artist => &self.artist,
+      TagField::Album => &self.album,
+    }
+  }
+
+  pub fn get_mut(&mut self, field: TagField) -> &mut String {
+    match field {
+      TagField::Title => &mut self.title,
+      TagField::Artist => &mut self.artist,
+      TagField::Album => &mut self.album,
+    }
+  }
+
+  /// Writes these tags into the file's primary tag, creating one if needed.
+  /// Empty fields are removed.
+  pub fn write(&self, path: &Path, recording_id: Option<&str>) -> Result<(), LoftyError> {
+    let mut tagged = lofty::read_from_path(path)?;
+    if tagged.primary_tag().is_none() {
+      tagged.insert_tag(Tag::new(tagged.primary_tag_type()));
+    }
+    let Some(tag) = tagged.primary_tag_mut() else {
+      return Ok(());
+    };
+
+    for field in TagField::ALL {
+      let value = self.get(field).trim().to_string();
+      match (field, value.is_empty()) {
+        (TagField::Title, false) => tag.set_title(value),
+        (TagField::Title, true) => tag.remove_title(),
+        (TagField::Artist, false) => tag.set_artist(value),
+        (TagField::Artist, true) => tag.remove_artist(),
+        (TagField::Album, false) => tag.set_album(value),
+        (TagField::Album, true) => tag.remove_album(),
+      }
+    }
+    if let Some(id) = recording_id {
+      tag.insert_text(ItemKey::MusicBrainzRecordingId, id.to_string());
+    }
+    tag.save_to_path(path, WriteOptions::default())
+  }
+}
+
+/// What the header above the visualiser shows for a file.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+  pub tags: Tags,
+  pub duration: Duration,
+}
+
+impl Metadata {
+  pub fn read(path: &Path) -> Result<Self, LoftyError> {
+    let tagged = lofty::read_from_path(path)?;
+    Ok(Self { tags: Tags::from_tagged(&tagged), duration: tagged.properties().duration() })
+  }
+}
+
+/// The embedded front cover, or failing that any embedded picture.
+pub fn cover_art(path: &Path) -> Result<Option<Vec<u8>>, LoftyError> {
+  let tagged = lofty::read_from_path(path)?;
+  let pictures = || tagged.tags().iter().flat_map(|tag| tag.pictures());
+  let picture = pictures()
+    .find(|picture| picture.pic_type() == PictureType::CoverFront)
+    .or_else(|| pictures().next());
+  Ok(picture.map(|picture| picture.data().to_vec()))
+}
+
+/// The lyrics tag of any of the file's tags, as written.
+pub fn lyrics(path: &Path) -> Result<Option<String>, LoftyError> {
+  let tagged = lofty::read_from_path(path)?;
+  Ok(tagged.tags().iter().find_map(|tag| tag.get_string(&ItemKey::Lyrics)).map(str::to_string))
+}
+
+/// A correction offered for a file's tags, awaiting the user's go-ahead.
+#[derive(Debug, Clone)]
+pub struct TagReview {
+  pub path: String,
+  pub current: Tags,
+  /// What will be written; starts as the looked-up values and can be edited.
+  pub proposed: Tags,
+  pub recording_id: Option<String>,
+}
+
+impl TagReview {
+  pub fn new(path: String, current: Tags, info: &TrackInfo) -> Self {
+    let proposed = Tags {
+      title: info.title.clone(),
+      artist: info.artist.clone(),
+      album: info.album.clone().unwrap_or_else(|| current.album.clone()),
+    };
+    Self { path, current, proposed, recording_id: info.recording_id.clone() }
+  }
+
+  pub fn write(&self) -> Result<(), LoftyError> {
+    self.proposed.write(Path::new(&self.path), self.recording_id.as_deref())
+  }
+}
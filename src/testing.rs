@@ -0,0 +1,342 @@
+//! A fake audio source and clock for driving the app from integration
+//! tests: samples go through the real [`Tap`] into the analyser, frames
+//! wait in the app's latency queue, and every tick is the app's own
+//! `Message::Tick`, so the bars rise while playing and fall back once the
+//! source runs dry just as in the window, all on a clock that only moves
+//! when told to. Nothing here touches an audio device or the window, so
+//! every run of the same source is identical.
+
+use iced::{Rectangle, Size, mouse, widget::shader::Program};
+use rodio::Source;
+use std::{
+  sync::mpsc::{self, Receiver},
+  time::{Duration, Instant},
+};
+
+use crate::analysis::{self, Analyser, StreamBuffers};
+use crate::components::{
+  postfx::Effects,
+  tap::{Chunker, Tap},
+  visualiser::VisualStyle,
+};
+use crate::ui::inspector::FrameLog;
+use crate::{
+  app::{AudioVisualizer, DEFAULT_UPDATE_INTERVAL, Message},
+  playback,
+};
+
+pub use crate::analysis::{AnalysisFrame, AnalysisSettings};
+pub use crate::ui::scene::Pane;
+
+/// Length of each click's burst of noise.
+const CLICK_LENGTH: Duration = Duration::from_millis(10);
+/// Seed of the clicks' noise, so they're the same every run.
+const NOISE_SEED: u64 = 0x5eed;
+
+/// What a [`FakeSource`] plays, on every channel.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Signal {
+  Silence,
+  Sine {
+    frequency: f32,
+    amplitude: f32,
+  },
+  /// Short bursts of noise, the first at the start.
+  Clicks {
+    every: Duration,
+    amplitude: f32,
+  },
+}
+
+/// A rodio source that plays a [`Signal`] for a fixed time.
+pub struct FakeSource {
+  signal: Signal,
+  channels: u16,
+  sample_rate: u32,
+  duration: Duration,
+  /// Samples in all, interleaved.
+  length: usize,
+  position: usize,
+  noise: fastrand::Rng,
+}
+
+impl FakeSource {
+  pub fn new(signal: Signal, channels: u16, sample_rate: u32, duration: Duration) -> Self {
+    let channels = channels.max(1);
+    let frames = (duration.as_secs_f64() * sample_rate as f64) as usize;
+    Self {
+      signal,
+      channels,
+      sample_rate,
+      duration,
+      length: frames * channels as usize,
+      position: 0,
+      noise: fastrand::Rng::with_seed(NOISE_SEED),
+    }
+  }
+
+  fn sample(&mut self, seconds: f32) -> f32 {
+    match self.signal {
+      Signal::Silence => 0.0,
+      Signal::Sine { frequency, amplitude } => {
+        amplitude * (std::f32::consts::TAU * frequency * seconds).sin()
+      }
+      Signal::Clicks { every, amplitude } => {
+        let since = seconds % every.as_secs_f32().max(f32::EPSILON);
+        if since < CLICK_LENGTH.as_secs_f32() {
+          amplitude * (self.noise.f32() * 2.0 - 1.0)
+        } else {
+          0.0
+        }
+      }
+    }
+  }
+}
+
+impl Iterator for FakeSource {
+  type Item = f32;
+
+  fn next(&mut self) -> Option<f32> {
+    if self.position >= self.length {
+      return None;
+    }
+    let frame = self.position / self.channels as usize;
+    self.position += 1;
+    Some(self.sample(frame as f32 / self.sample_rate as f32))
+  }
+}
+
+impl Source for FakeSource {
+  fn current_frame_len(&self) -> Option<usize> {
+    Some(self.length - self.position)
+  }
+
+  fn channels(&self) -> u16 {
+    self.channels
+  }
+
+  fn sample_rate(&self) -> u32 {
+    self.sample_rate
+  }
+
+  fn total_duration(&self) -> Option<Duration> {
+    Some(self.duration)
+  }
+}
+
+/// A clock that stands still until it's advanced.
+#[derive(Debug, Clone, Copy)]
+pub struct FakeClock {
+  start: Instant,
+  elapsed: Duration,
+}
+
+impl FakeClock {
+  pub fn new() -> Self {
+    Self { start: Instant::now(), elapsed: Duration::ZERO }
+  }
+
+  pub fn now(&self) -> Instant {
+    self.start + self.elapsed
+  }
+
+  pub fn elapsed(&self) -> Duration {
+    self.elapsed
+  }
+
+  pub fn advance(&mut self, by: Duration) {
+    self.elapsed += by;
+  }
+}
+
+impl Default for FakeClock {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Where the bars are at, as in the app's tick.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+  /// Following the analysis.
+  Playing,
+  /// The source has run dry and the bars are falling back.
+  Decaying,
+  /// Everything is back on the floor.
+  Idle,
+}
+
+/// What came through the app on a tick.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ticked {
+  /// Strength of a beat detected in the frame.
+  pub beat: Option<f32>,
+}
+
+/// The app itself, fed from the tap a tick at a time: every tick is a real
+/// `Message::Tick` through its `update`.
+pub struct Pipeline {
+  tap: Tap<FakeSource>,
+  chunks: Receiver<Vec<f32>>,
+  analyser: Analyser,
+  streams: StreamBuffers,
+  clock: FakeClock,
+  /// Samples pulled from the tap so far, interleaved.
+  pulled: u64,
+  app: AudioVisualizer,
+}
+
+impl Pipeline {
+  /// Plays `source` into `bar_count` bars with the default settings.
+  pub fn new(source: FakeSource, bar_count: usize) -> Self {
+    let settings = AnalysisSettings {
+      channels: source.channels(),
+      sample_rate: source.sample_rate(),
+      ..AnalysisSettings::default()
+    };
+    let mut app = AudioVisualizer {
+      sample_rate: settings.sample_rate,
+      channels: settings.channels,
+      inspector: Some(FrameLog::default()),
+      ..AudioVisualizer::default()
+    };
+    *app.analysis_settings.lock().unwrap() = settings;
+    app.visuals.update_interval = DEFAULT_UPDATE_INTERVAL;
+    app.visuals.bar_count = bar_count.max(1);
+    app.resize_bars();
+    // As if a track had started, without an output to play it on
+    app.player.is_playing = true;
+
+    let (sender, chunks) = mpsc::channel();
+    Self {
+      tap: Tap::new(source, Chunker::new(sender)),
+      chunks,
+      analyser: Analyser::new(settings),
+      streams: StreamBuffers::new(settings),
+      clock: FakeClock::new(),
+      pulled: 0,
+      app,
+    }
+  }
+
+  /// Holds frames back by `latency`, as for the output's buffering.
+  pub fn with_latency(self, latency: Duration) -> Self {
+    self.app.audio_data.lock().unwrap().set_latency(latency);
+    self
+  }
+
+  /// Analyses with `settings` from the next chunk on. The channels and
+  /// sample rate stay the source's.
+  pub fn configure(&mut self, settings: AnalysisSettings) {
+    let settings = AnalysisSettings {
+      channels: self.tap.channels(),
+      sample_rate: self.tap.sample_rate(),
+      ..settings
+    };
+    self.streams.configure(self.analyser.settings(), settings);
+    self.analyser.configure(settings);
+    *self.app.analysis_settings.lock().unwrap() = settings;
+  }
+
+  /// Moves the clock on by a tick, pulls the audio an output would have in
+  /// that time, and ticks the app. Returns what came through, if a frame
+  /// became due.
+  pub fn tick(&mut self) -> Option<Ticked> {
+    self.clock.advance(self.app.visuals.update_interval);
+    let now = self.clock.now();
+    if self.app.player.is_playing {
+      self.pull();
+      let settings = self.analyser.settings();
+      while let Ok(chunk) = self.chunks.try_recv() {
+        let queue = &self.app.audio_data;
+        analysis::analyse_chunk(
+          &mut self.analyser,
+          &mut self.streams,
+          &chunk,
+          settings,
+          now,
+          |frame, pulled_at| queue.lock().unwrap().push(frame, pulled_at),
+        );
+      }
+    }
+
+    let received = |app: &AudioVisualizer| app.inspector.as_ref().map_or(0, |log| log.frames);
+    let before = received(&self.app);
+    self.app.clock = Some(now);
+    let _ = self.app.update(Message::Tick);
+    (received(&self.app) > before)
+      .then(|| Ticked { beat: self.app.inspector.as_ref().and_then(|log| log.last_beat) })
+  }
+
+  /// Pulls samples through the tap up to where the clock is, stopping the
+  /// playback once the source runs out.
+  fn pull(&mut self) {
+    let rate = self.tap.sample_rate() as f64 * self.tap.channels() as f64;
+    let due = (self.clock.elapsed().as_secs_f64() * rate) as u64;
+    while self.pulled < due {
+      if self.tap.next().is_none() {
+        let _ = self.app.update(Message::Playback(playback::Message::Stop));
+        return;
+      }
+      self.pulled += 1;
+    }
+  }
+
+  pub fn state(&self) -> State {
+    if self.app.player.is_playing {
+      State::Playing
+    } else if self.app.is_decaying {
+      State::Decaying
+    } else {
+      State::Idle
+    }
+  }
+
+  pub fn clock(&self) -> FakeClock {
+    self.clock
+  }
+
+  /// Frames analysed but not yet due.
+  pub fn queued(&self) -> usize {
+    self.app.audio_data.lock().unwrap().len()
+  }
+
+  /// Bar levels, each 0.0..=1.0.
+  pub fn levels(&self) -> Vec<f32> {
+    self.app.bar_levels()
+  }
+
+  /// Splits the view with the bars and their effects on both sides, and
+  /// returns the pane each side's effects keep their GPU buffers under.
+  pub fn split_effects(&mut self) -> Vec<Pane> {
+    self.split(VisualStyle::Bars);
+    self.app.visuals.effects = Effects { bloom: 1.0, trails: 1.0, aberration: 1.0 };
+    [Pane::Main, Pane::Split]
+      .into_iter()
+      .filter_map(|pane| self.app.postfx(pane))
+      .map(|postfx| postfx.draw(&(), mouse::Cursor::Unavailable, bounds()).pane())
+      .collect()
+  }
+
+  /// Splits the view with the 3D terrain on both sides, and returns the
+  /// pane each side keeps its GPU buffers under.
+  pub fn split_terrain(&mut self) -> Vec<Pane> {
+    self.split(VisualStyle::Terrain);
+    [Pane::Main, Pane::Split]
+      .into_iter()
+      .filter_map(|pane| self.app.terrain(pane))
+      .map(|terrain| terrain.draw(&None, mouse::Cursor::Unavailable, bounds()).pane())
+      .collect()
+  }
+
+  fn split(&mut self, style: VisualStyle) {
+    self.app.visuals.style = style;
+    self.app.visuals.split.enabled = true;
+    self.app.visuals.split.style = style;
+  }
+}
+
+/// Where a pane is drawn, for the GPU layers.
+fn bounds() -> Rectangle {
+  Rectangle::with_size(Size::new(640.0, 360.0))
+}
@@ -0,0 +1,275 @@
+//! An icon in the system tray with the transport and the track playing, so
+//! the window can be closed while the music and the outputs fed by the
+//! analysis carry on.
+//!
+//! The menu is the OS's, so it's built wherever the OS wants it: on the
+//! main thread on Windows and macOS, where the app's event loop serves it,
+//! and on a GTK thread of its own on Linux. Clicks come back over
+//! `tray-icon`'s channel either way, for the app to poll.
+//!
+//! GTK can only ever be started on one thread, so the icon is built once,
+//! the first time the tray's turned on, and after that only hidden and
+//! shown again.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use tray_icon::{
+  BadIcon, Icon, TrayIcon, TrayIconBuilder,
+  menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem},
+};
+
+/// Side of the icon drawn for the tray, in pixels.
+const ICON_SIZE: u32 = 32;
+/// Heights of the icon's bars, as fractions of it.
+const ICON_BARS: [f32; 4] = [0.45, 0.85, 0.6, 0.3];
+const NOTHING_PLAYING: &str = "Nothing playing";
+const TOOLTIP: &str = "Audio visualiser";
+
+/// Whether there's a tray icon, and what closing the window does.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraySettings {
+  pub enabled: bool,
+  /// Closing the main window hides it to the tray instead of quitting.
+  pub close_to_tray: bool,
+}
+
+/// What the tray menu's items ask for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayAction {
+  PlayPause,
+  Stop,
+  Next,
+  /// Brings the main window back, reopening it if it was closed to the tray.
+  Show,
+  Quit,
+}
+
+impl TrayAction {
+  const ALL: [TrayAction; 5] =
+    [TrayAction::PlayPause, TrayAction::Stop, TrayAction::Next, TrayAction::Show, TrayAction::Quit];
+
+  /// Menu item id, which is how clicks are told apart.
+  fn id(self) -> &'static str {
+    match self {
+      TrayAction::PlayPause => "play_pause",
+      TrayAction::Stop => "stop",
+      TrayAction::Next => "next",
+      TrayAction::Show => "show",
+      TrayAction::Quit => "quit",
+    }
+  }
+}
+
+/// What the menu shows about playback.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrayState {
+  pub title: Option<String>,
+  pub is_playing: bool,
+}
+
+#[derive(Debug)]
+pub enum TrayError {
+  Menu(tray_icon::menu::Error),
+  Icon(BadIcon),
+  Tray(tray_icon::Error),
+  /// GTK wouldn't start, or its thread stopped before the icon was up.
+  Gtk(String),
+}
+
+impl fmt::Display for TrayError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      TrayError::Menu(e) => write!(f, "couldn't build the menu: {}", e),
+      TrayError::Icon(e) => write!(f, "bad icon: {}", e),
+      TrayError::Tray(e) => write!(f, "{}", e),
+      TrayError::Gtk(e) => write!(f, "GTK unavailable: {}", e),
+    }
+  }
+}
+
+/// The icon and the menu items that change.
+struct TrayMenu {
+  icon: TrayIcon,
+  title: MenuItem,
+  play_pause: MenuItem,
+}
+
+impl TrayMenu {
+  fn build() -> Result<Self, TrayError> {
+    let title = MenuItem::new(NOTHING_PLAYING, false, None);
+    let play_pause = MenuItem::with_id(TrayAction::PlayPause.id(), "Play", true, None);
+    let item = |action: TrayAction, label: &str| MenuItem::with_id(action.id(), label, true, None);
+    let menu = Menu::new();
+    menu
+      .append_items(&[
+        &title,
+        &PredefinedMenuItem::separator(),
+        &play_pause,
+        &item(TrayAction::Stop, "Stop"),
+        &item(TrayAction::Next, "Next"),
+        &PredefinedMenuItem::separator(),
+        &item(TrayAction::Show, "Show window"),
+        &item(TrayAction::Quit, "Quit"),
+      ])
+      .map_err(TrayError::Menu)?;
+    let icon = TrayIconBuilder::new()
+      .with_menu(Box::new(menu))
+      .with_tooltip(TOOLTIP)
+      .with_icon(icon().map_err(TrayError::Icon)?)
+      .build()
+      .map_err(TrayError::Tray)?;
+    Ok(Self { icon, title, play_pause })
+  }
+
+  fn show(&self, state: &TrayState) {
+    let title = state.title.as_deref().unwrap_or(NOTHING_PLAYING);
+    self.title.set_text(title);
+    self.play_pause.set_text(if state.is_playing { "Pause" } else { "Play" });
+    let tooltip = match &state.title {
+      Some(title) => format!("{} - {}", TOOLTIP, title),
+      None => TOOLTIP.to_string(),
+    };
+    if let Err(e) = self.icon.set_tooltip(Some(tooltip)) {
+      eprintln!("Failed to update the tray tooltip: {}", e);
+    }
+  }
+
+  fn set_visible(&self, visible: bool) {
+    if let Err(e) = self.icon.set_visible(visible) {
+      eprintln!("Failed to {} the tray icon: {}", if visible { "show" } else { "hide" }, e);
+    }
+  }
+}
+
+/// What the app hands the GTK thread, which owns the menu.
+#[cfg(target_os = "linux")]
+enum TrayUpdate {
+  Show(TrayState),
+  Visible(bool),
+}
+
+/// The tray icon, there until dropped.
+pub struct Tray {
+  #[cfg(not(target_os = "linux"))]
+  menu: TrayMenu,
+  #[cfg(target_os = "linux")]
+  updates: std::sync::mpsc::Sender<TrayUpdate>,
+  /// What the menu shows now, so it's only touched on a change.
+  shown: TrayState,
+  visible: bool,
+}
+
+impl Tray {
+  #[cfg(not(target_os = "linux"))]
+  pub fn new() -> Result<Self, TrayError> {
+    Ok(Self { menu: TrayMenu::build()?, shown: TrayState::default(), visible: true })
+  }
+
+  /// Starts the GTK thread and builds the icon on it. Only the first call
+  /// gets that far; GTK would panic on a second thread.
+  #[cfg(target_os = "linux")]
+  pub fn new() -> Result<Self, TrayError> {
+    use gtk::glib::{self, ControlFlow};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, TryRecvError};
+    use std::time::Duration;
+
+    static STARTED: AtomicBool = AtomicBool::new(false);
+    if STARTED.swap(true, Ordering::SeqCst) {
+      return Err(TrayError::Gtk("GTK was already started for the tray".to_string()));
+    }
+    let (updates, received) = mpsc::channel::<TrayUpdate>();
+    let (ready, started) = mpsc::channel();
+    std::thread::spawn(move || {
+      if let Err(e) = gtk::init() {
+        let _ = ready.send(Err(TrayError::Gtk(e.to_string())));
+        return;
+      }
+      let menu = match TrayMenu::build() {
+        Ok(menu) => menu,
+        Err(e) => {
+          let _ = ready.send(Err(e));
+          return;
+        }
+      };
+      let _ = ready.send(Ok(()));
+      // Dropping the tray hangs up, which takes the icon down with the loop
+      glib::timeout_add_local(Duration::from_millis(100), move || {
+        loop {
+          match received.try_recv() {
+            Ok(TrayUpdate::Show(state)) => menu.show(&state),
+            Ok(TrayUpdate::Visible(visible)) => menu.set_visible(visible),
+            Err(TryRecvError::Empty) => return ControlFlow::Continue,
+            Err(TryRecvError::Disconnected) => {
+              gtk::main_quit();
+              return ControlFlow::Break;
+            }
+          }
+        }
+      });
+      gtk::main();
+    });
+    started.recv().map_err(|_| TrayError::Gtk("the tray thread stopped".to_string()))??;
+    Ok(Self { updates, shown: TrayState::default(), visible: true })
+  }
+
+  /// Shows the track playing and whether it is.
+  pub fn show(&mut self, state: TrayState) {
+    if state == self.shown {
+      return;
+    }
+    #[cfg(not(target_os = "linux"))]
+    self.menu.show(&state);
+    #[cfg(target_os = "linux")]
+    let _ = self.updates.send(TrayUpdate::Show(state.clone()));
+    self.shown = state;
+  }
+
+  /// Hides the icon or brings it back, keeping it built.
+  pub fn set_visible(&mut self, visible: bool) {
+    if visible == self.visible {
+      return;
+    }
+    #[cfg(not(target_os = "linux"))]
+    self.menu.set_visible(visible);
+    #[cfg(target_os = "linux")]
+    let _ = self.updates.send(TrayUpdate::Visible(visible));
+    self.visible = visible;
+  }
+
+  pub fn is_visible(&self) -> bool {
+    self.visible
+  }
+
+  /// Items clicked since the last call, oldest first.
+  pub fn actions(&self) -> Vec<TrayAction> {
+    MenuEvent::receiver()
+      .try_iter()
+      .filter_map(|event| TrayAction::ALL.into_iter().find(|action| event.id == action.id()))
+      .collect()
+  }
+}
+
+/// A few blue bars rising from the bottom.
+fn icon() -> Result<Icon, BadIcon> {
+  let mut rgba = vec![0u8; (ICON_SIZE * ICON_SIZE * 4) as usize];
+  let bar_width = ICON_SIZE / ICON_BARS.len() as u32;
+  for (i, &height) in ICON_BARS.iter().enumerate() {
+    let top = ICON_SIZE - (height * ICON_SIZE as f32) as u32;
+    for y in top..ICON_SIZE {
+      // One pixel between bars
+      for x in i as u32 * bar_width + 1..(i as u32 + 1) * bar_width {
+        let pixel = ((y * ICON_SIZE + x) * 4) as usize;
+        let level = 1.0 - y as f32 / ICON_SIZE as f32;
+        rgba[pixel..pixel + 4].copy_from_slice(&[
+          (40.0 + 60.0 * level) as u8,
+          (120.0 + 100.0 * level) as u8,
+          255,
+          255,
+        ]);
+      }
+    }
+  }
+  Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE)
+}
@@ -0,0 +1,567 @@
+use iced::{
+  Alignment, Background, Color, Element,
+  widget::{button, checkbox, column, container, image, pick_list, row, slider, text, text_input},
+};
+
+use std::{path::Path, time::Duration};
+
+use crate::analysis::{self, AnalysisSettings};
+use crate::app::{
+  AudioVisualizer, Message, comparison, display, exports, looks, measuring, track, windows,
+};
+use crate::compare::{self, CompareView, Comparison};
+use crate::components::{
+  recorder::MacroRecorder, sections::Section, weighting::Weighting, window_fn::WindowFunction,
+};
+use crate::export;
+use crate::identify::TrackInfo;
+use crate::keymap::Action;
+use crate::lyrics::Lyrics;
+use crate::playback::{self, CaptureSource, Player};
+use crate::plugins::Plugin;
+use crate::recording::MicRecording;
+use crate::stats::ListeningStats;
+use crate::tags::{Metadata, TagField, TagReview};
+
+/// Tracks listed under the listening statistics.
+const TOP_TRACKS: usize = 10;
+/// Lines of lyrics shown either side of the one being sung.
+const LYRIC_CONTEXT: usize = 1;
+
+/// Transport, volume and window controls.
+pub fn transport<'a>(
+  player: &Player,
+  analysis_settings: &AnalysisSettings,
+) -> Element<'a, Message> {
+  let btn_loadfile_color = if !player.is_loaded {
+    // Not loaded: blue
+    Color::parse("#1447e6").unwrap()
+  } else {
+    // Loaded: gray
+    Color::parse("#99a1af").unwrap()
+  };
+
+  let btn_play_color = if !player.is_loaded {
+    // Not loaded: gray
+    Color::parse("#99a1af").unwrap()
+  } else if player.is_playing {
+    // Playing: gray
+    Color::parse("#99a1af").unwrap()
+  } else {
+    // Loaded but not playing: green
+    Color::parse("#007a55").unwrap()
+  };
+
+  let btn_pause_color = if !player.is_loaded {
+    // Not loaded: gray
+    Color::parse("#99a1af").unwrap()
+  } else if player.is_playing {
+    // Playing: blue
+    Color::parse("#1447e6").unwrap()
+  } else {
+    // Loaded but not playing: gray
+    Color::parse("#99a1af").unwrap()
+  };
+
+  let btn_stop_color = if !player.is_loaded {
+    // Not loaded: gray
+    Color::parse("#99a1af").unwrap()
+  } else if player.is_playing {
+    // Playing: blue
+    Color::parse("#1447e6").unwrap()
+  } else {
+    // Loaded but not playing: gray
+    Color::parse("#99a1af").unwrap()
+  };
+
+  let capture = player.capture_source();
+
+  row![
+    button("Load File").on_press(Message::Playback(playback::Message::LoadFile)).style(
+      move |_, _| {
+        button::Style {
+          background: Some(Background::Color(btn_loadfile_color)),
+          ..button::Style::default()
+        }
+      }
+    ),
+    button("Play").on_press(Message::Playback(playback::Message::Play)).style(move |_, _| {
+      button::Style {
+        background: Some(Background::Color(btn_play_color)),
+        ..button::Style::default()
+      }
+    }),
+    button("Pause").on_press(Message::Playback(playback::Message::Pause)).style(move |_, _| {
+      button::Style {
+        background: Some(Background::Color(btn_pause_color)),
+        ..button::Style::default()
+      }
+    }),
+    button("Stop").on_press(Message::Playback(playback::Message::Stop)).style(move |_, _| {
+      button::Style {
+        background: Some(Background::Color(btn_stop_color)),
+        ..button::Style::default()
+      }
+    }),
+    button("Loop A").on_press_maybe(
+      player.is_loaded.then_some(Message::Playback(playback::Message::SetLoopStart))
+    ),
+    button("Loop B").on_press_maybe(
+      player.loop_start.is_some().then_some(Message::Playback(playback::Message::SetLoopEnd))
+    ),
+    button("Clear loop").on_press_maybe(
+      player.loop_start.is_some().then_some(Message::Playback(playback::Message::ClearLoop))
+    ),
+    button(if capture == Some(CaptureSource::System) { "Stop capture" } else { "Capture system" })
+      .on_press(Message::Playback(playback::Message::ToggleCapture)),
+    button(if capture == Some(CaptureSource::Microphone) { "Stop mic" } else { "Live mic" })
+      .on_press(Message::Playback(playback::Message::ToggleMicrophone)),
+    button(if player.is_muted { "Unmute" } else { "Mute" })
+      .on_press(Message::Playback(playback::Message::ToggleMute)),
+    slider(0.0..=1.0, player.volume, |volume| {
+      Message::Playback(playback::Message::VolumeChanged(volume))
+    })
+    .step(0.01)
+    .width(100),
+    pick_list(player.output_devices(), Some(player.output_device.clone()), |device| {
+      Message::Playback(playback::Message::OutputDeviceSelected(device))
+    })
+    .on_open(Message::Playback(playback::Message::RefreshOutputDevices))
+    .width(160),
+    text(format!("Sync {:.0} ms", player.output_latency.as_secs_f32() * 1000.0)),
+    slider(
+      0.0..=playback::MAX_OUTPUT_LATENCY_MS,
+      player.output_latency.as_secs_f32() * 1000.0,
+      |ms| { Message::Playback(playback::Message::OutputLatencyChanged(ms)) }
+    )
+    .step(5.0)
+    .width(80),
+    text(if player.is_muted {
+      "Muted".to_string()
+    } else {
+      format!("{:.0}%", player.volume * 100.0)
+    }),
+    text(format!("Crossfade {:.1} s", player.crossfade.as_secs_f32())),
+    slider(0.0..=playback::MAX_CROSSFADE_SECONDS, player.crossfade.as_secs_f32(), |seconds| {
+      Message::Playback(playback::Message::CrossfadeChanged(seconds))
+    })
+    .step(0.5)
+    .width(80),
+    checkbox("Auto-DJ", player.auto_dj)
+      .on_toggle(|auto_dj| Message::Track(track::Message::AutoDjToggled(auto_dj))),
+    text(format!("Speed {:.2}x", player.rate)),
+    slider(playback::MIN_RATE..=playback::MAX_RATE, player.rate, |rate| {
+      Message::Playback(playback::Message::RateChanged(rate))
+    })
+    .step(0.05)
+    .width(80),
+    checkbox("Keep pitch", player.preserve_pitch)
+      .on_toggle(|on| Message::Playback(playback::Message::PreservePitchToggled(on))),
+    text("Window"),
+    pick_list(WindowFunction::ALL, Some(analysis_settings.window), |window| {
+      Message::Analysis(analysis::Message::WindowSelected(window))
+    }),
+    text("Weighting"),
+    pick_list(Weighting::ALL, Some(analysis_settings.weighting), |weighting| {
+      Message::Analysis(analysis::Message::WeightingSelected(weighting))
+    }),
+    button("Settings").on_press(Message::Display(display::Message::ToggleSettings)),
+  ]
+  .push_maybe(match player.track() {
+    (track, count) if count > 1 => Some(text(format!("Track {}/{}", track + 1, count))),
+    _ => None,
+  })
+  .push_maybe(
+    player
+      .file_path()
+      .and_then(|path| player.grids.get(path))
+      .filter(|_| player.auto_dj)
+      .map(|grid| text(format!("{:.1} BPM, {}", grid.bpm, grid.key))),
+  )
+  .spacing(10)
+  .align_y(iced::Alignment::Center)
+  .into()
+}
+
+/// Clip markers and the clip export.
+pub fn clip<'a>(app: &AudioVisualizer) -> Element<'a, Message> {
+  let marker = |seconds: Option<f32>| match seconds {
+    Some(seconds) => format!("{}:{:04.1}", (seconds / 60.0) as u32, seconds % 60.0),
+    None => "--".to_string(),
+  };
+  row![
+    text("Clip"),
+    button("Set start").on_press(Message::Exports(exports::Message::SetClipStart)),
+    button("Set end").on_press(Message::Exports(exports::Message::SetClipEnd)),
+    button("Section").on_press(Message::Exports(exports::Message::ClipToSection)),
+    text(format!("{} to {}", marker(app.clip_start), marker(app.clip_end))),
+    text(format!("Fade {:.1} s", app.clip_fade)),
+    slider(0.0..=export::MAX_CLIP_FADE_SECONDS, app.clip_fade, |fade| Message::Exports(
+      exports::Message::ClipFadeChanged(fade)
+    ))
+    .step(0.1)
+    .width(80),
+    button(if app.is_exporting_clip { "Exporting..." } else { "Export clip" }).on_press_maybe(
+      (app.clip().is_some() && !app.is_exporting_clip)
+        .then_some(Message::Exports(exports::Message::ExportClip))
+    ),
+  ]
+  .spacing(10)
+  .align_y(iced::Alignment::Center)
+  .into()
+}
+
+/// The song's sections as buttons that jump to them, the playing one
+/// highlighted, between buttons for the previous and next.
+pub fn sections<'a>(app: &AudioVisualizer, sections: &[Section]) -> Element<'a, Message> {
+  let position = app.player.position().as_secs_f32();
+  let playing = sections.iter().rposition(|section| section.start <= position);
+  let jumps = sections.iter().enumerate().fold(row![].spacing(4), |jumps, (index, section)| {
+    let seek = playback::Message::Seek(Duration::from_secs_f32(section.start));
+    let style = if Some(index) == playing { button::primary } else { button::secondary };
+    jumps
+      .push(button(text(section.kind.to_string())).style(style).on_press(Message::Playback(seek)))
+  });
+  row![
+    text("Sections"),
+    button("Previous").on_press_maybe(app.shortcut(Action::PreviousSection)),
+    jumps,
+    button("Next").on_press_maybe(app.shortcut(Action::NextSection)),
+  ]
+  .spacing(10)
+  .align_y(iced::Alignment::Center)
+  .into()
+}
+
+/// How the compared files are shown and lined up, and swapping which plays.
+pub fn comparison<'a>(comparison: &Comparison) -> Element<'a, Message> {
+  row![
+    text(format!(
+      "Comparing {} and {}",
+      compare::file_name(&comparison.tracks[0].path),
+      compare::file_name(&comparison.tracks[1].path)
+    )),
+    pick_list(CompareView::ALL, Some(comparison.view), |view| Message::Compare(
+      comparison::Message::ViewSelected(view)
+    )),
+    text(format!("Offset {:+.2} s", comparison.offset)),
+    slider(compare::OFFSET_RANGE, comparison.offset, |offset| Message::Compare(
+      comparison::Message::OffsetChanged(offset)
+    ))
+    .step(0.01)
+    .width(160),
+    button("Swap").on_press(Message::Compare(comparison::Message::Swap)),
+    button("Close").on_press(Message::Compare(comparison::Message::Close)),
+  ]
+  .spacing(10)
+  .align_y(iced::Alignment::Center)
+  .into()
+}
+
+/// Measurement and export tools.
+pub fn tools<'a>(app: &AudioVisualizer) -> Element<'a, Message> {
+  row![
+    button(if app.measurement.is_some() { "Stop measuring" } else { "Transfer function" })
+      .on_press(Message::Measuring(measuring::Message::ToggleMeasurement)),
+    button(if app.is_measuring_impulse { "Measuring room..." } else { "Measure room" })
+      .on_press_maybe(
+        (!app.is_measuring_impulse)
+          .then_some(Message::Measuring(measuring::Message::MeasureImpulse))
+      ),
+    button("Export IR").on_press_maybe(
+      app.impulse.is_some().then_some(Message::Measuring(measuring::Message::ExportImpulse))
+    ),
+    button(if app.is_exporting { "Exporting..." } else { "Export video" }).on_press_maybe(
+      (app.player.is_loaded && !app.is_exporting)
+        .then_some(Message::Exports(exports::Message::ExportVideo))
+    ),
+    checkbox("Save stem", app.save_stem)
+      .on_toggle(|save| Message::Exports(exports::Message::SaveStemToggled(save))),
+    button("Render stem").on_press_maybe(
+      (!app.is_exporting).then_some(Message::Exports(exports::Message::RenderStem))
+    ),
+    button(if app.is_exporting_markers { "Exporting..." } else { "Export markers" })
+      .on_press_maybe(
+        (app.player.is_loaded && !app.is_exporting_markers)
+          .then_some(Message::Exports(exports::Message::ExportMarkers))
+      ),
+    pick_list(export::Resolution::ALL, Some(app.snapshot_resolution), |resolution| {
+      Message::Exports(exports::Message::SnapshotResolutionSelected(resolution))
+    }),
+    button("Snapshot").on_press(Message::Exports(exports::Message::SaveSnapshot)),
+    button(if app.is_loading_comparison { "Loading..." } else { "Compare" }).on_press_maybe(
+      (app.player.capture_source().is_none()
+        && app.player.file_path().is_some()
+        && !app.is_loading_comparison)
+        .then_some(Message::Compare(comparison::Message::Load))
+    ),
+    button(if app.feedback.is_some() { "Stop feedback watch" } else { "Feedback watch" })
+      .on_press(Message::Measuring(measuring::Message::ToggleFeedback)),
+    button(if app.is_identifying { "Identifying..." } else { "Identify" })
+      .on_press_maybe(app.can_identify().then_some(Message::Track(track::Message::Identify))),
+    checkbox("Meters", app.show_meters)
+      .on_toggle(|show| Message::Display(display::Message::MetersToggled(show))),
+    checkbox("Energy", app.show_energy)
+      .on_toggle(|show| Message::Display(display::Message::EnergyToggled(show))),
+    checkbox("Frequencies", app.show_frequency_labels)
+      .on_toggle(|show| { Message::Display(display::Message::FrequencyLabelsToggled(show)) }),
+    button(if app.show_stats { "Hide stats" } else { "Stats" })
+      .on_press(Message::Display(display::Message::ToggleStats)),
+    button("Present").on_press(Message::Windows(windows::Message::TogglePresentation)),
+    checkbox("Fullscreen", app.present_fullscreen).on_toggle(|fullscreen| Message::Windows(
+      windows::Message::PresentFullscreenToggled(fullscreen)
+    )),
+    checkbox("Strobe safety", app.strobe_safety)
+      .on_toggle(|safe| Message::Display(display::Message::StrobeSafetyToggled(safe))),
+    button(if app.mini_window.is_some() { "Close mini" } else { "Mini" })
+      .on_press(Message::Windows(windows::Message::ToggleMini)),
+    checkbox("Hide main", app.mini_hides_main)
+      .on_toggle(|hides| Message::Windows(windows::Message::MiniHidesMainToggled(hides))),
+  ]
+  .push_maybe(app.feedback.as_ref().map(|detector| match detector.flagged().first() {
+    Some(ringing) => text(format!("Feedback at {}", ringing)),
+    None => text("No feedback"),
+  }))
+  .spacing(10)
+  .align_y(iced::Alignment::Center)
+  .into()
+}
+
+/// What the classifier hears, and which inputs calm the visuals for speech.
+pub fn speech_gate<'a>(app: &AudioVisualizer) -> Element<'a, Message> {
+  let gate = app.speech_gate;
+  row![
+    text(format!("Hearing {}", app.classifier.content())),
+    text("Calm visuals for speech on"),
+    checkbox("Files", gate.file)
+      .on_toggle(|on| Message::Display(display::Message::SpeechGateToggled(None, on))),
+    checkbox("System", gate.system).on_toggle(|on| {
+      Message::Display(display::Message::SpeechGateToggled(Some(CaptureSource::System), on))
+    }),
+    checkbox("Mic", gate.microphone).on_toggle(|on| {
+      Message::Display(display::Message::SpeechGateToggled(Some(CaptureSource::Microphone), on))
+    }),
+  ]
+  .spacing(10)
+  .align_y(iced::Alignment::Center)
+  .into()
+}
+
+/// Record button for the live mic, with how long it's been recording.
+pub fn mic_recording<'a>(recording: Option<&MicRecording>) -> Element<'a, Message> {
+  let elapsed = recording.map(|recording| {
+    let seconds = recording.elapsed().as_secs();
+    text(format!("Recording {}:{:02}", seconds / 60, seconds % 60))
+  });
+  row![
+    button(if recording.is_some() { "Stop and save" } else { "Record mic" })
+      .on_press(Message::Measuring(measuring::Message::ToggleRecording))
+  ]
+  .push_maybe(elapsed)
+  .spacing(10)
+  .align_y(iced::Alignment::Center)
+  .into()
+}
+
+/// Cover art and details of the identified track.
+pub fn now_playing<'a>(info: &TrackInfo) -> Element<'a, Message> {
+  let details = column![text(info.title.clone()).size(18), text(info.artist.clone())]
+    .push_maybe(info.album.clone().map(text))
+    .spacing(4);
+  row![]
+    .push_maybe(info.art.clone().map(|art| image(art).width(64).height(64)))
+    .push(details)
+    .spacing(10)
+    .align_y(iced::Alignment::Center)
+    .into()
+}
+
+/// Totals across every run, then the most played tracks.
+pub fn stats<'a>(stats: &ListeningStats) -> Element<'a, Message> {
+  let loudness = stats.loudness().map_or("-".to_string(), |lufs| format!("{:.1} LUFS", lufs));
+  let tempo =
+    stats.common_bpm_range().map_or("-".to_string(), |(low, high)| format!("{}-{} BPM", low, high));
+  let totals = row![
+    text(format!("{} plays", stats.plays())),
+    text(format!("{} listened", duration(stats.seconds()))),
+    text(format!("Average {}", loudness)),
+    text(format!("Mostly {}", tempo)),
+    button("Export CSV").on_press(Message::Exports(exports::Message::ExportStats)),
+  ]
+  .spacing(20)
+  .align_y(Alignment::Center);
+
+  stats
+    .top(TOP_TRACKS)
+    .into_iter()
+    .fold(column![totals].spacing(4), |tracks, (path, track)| {
+      let name = Path::new(path)
+        .file_name()
+        .map_or(path.to_string(), |name| name.to_string_lossy().into_owned());
+      tracks.push(
+        row![
+          text(name).width(iced::Length::Fill),
+          text(format!("{} plays", track.plays)),
+          text(duration(track.seconds)),
+        ]
+        .spacing(20),
+      )
+    })
+    .into()
+}
+
+/// Hours and minutes, or minutes and seconds under an hour.
+fn duration(seconds: f64) -> String {
+  let seconds = seconds as u64;
+  if seconds >= 3600 {
+    format!("{}h {:02}m", seconds / 3600, seconds / 60 % 60)
+  } else {
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+  }
+}
+
+/// A warning across the top of the window until it's dismissed.
+pub fn warning<'a>(message: &str) -> Element<'a, Message> {
+  container(
+    row![
+      text(message.to_string()).width(iced::Length::Fill),
+      button("Dismiss").on_press(Message::DismissWarning),
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center),
+  )
+  .padding(10)
+  .style(|_| container::Style {
+    background: Some(Background::Color(Color::parse("#7b3306").unwrap())),
+    text_color: Some(Color::WHITE),
+    ..container::Style::default()
+  })
+  .into()
+}
+
+/// The line of the lyrics being sung at `position`, with the ones around it
+/// dimmed, under the visualiser.
+pub fn lyrics<'a>(lyrics: &Lyrics, position: Duration) -> Element<'a, Message> {
+  let current = lyrics.current(position);
+  // Before the first line, it's what's coming up
+  let middle = current.unwrap_or(0);
+  let first = middle.saturating_sub(LYRIC_CONTEXT);
+  let last = (middle + LYRIC_CONTEXT).min(lyrics.lines.len().saturating_sub(1));
+  let dim = Color::from_rgb(0.55, 0.55, 0.55);
+
+  lyrics
+    .lines
+    .get(first..=last)
+    .unwrap_or_default()
+    .iter()
+    .enumerate()
+    .fold(column![].spacing(4).align_x(Alignment::Center), |lines, (offset, line)| {
+      let line = if Some(first + offset) == current {
+        text(line.text.clone()).size(22)
+      } else {
+        text(line.text.clone()).size(16).color(dim)
+      };
+      lines.push(line)
+    })
+    .width(iced::Length::Fill)
+    .into()
+}
+
+/// Picks the plugin the Plugin style draws, or says where to put one.
+pub fn plugins<'a>(app: &AudioVisualizer) -> Element<'a, Message> {
+  let names: Vec<String> = app.plugins.iter().map(|plugin| plugin.name().to_string()).collect();
+  let picker: Element<'a, Message> = if names.is_empty() {
+    let dir =
+      Plugin::dir().map_or("the plugin directory".to_string(), |dir| dir.display().to_string());
+    text(format!("No plugins found in {}", dir)).into()
+  } else {
+    let active = app.active_plugin().map(|plugin| plugin.name().to_string());
+    pick_list(names, active, |name| Message::Looks(looks::Message::PluginSelected(name))).into()
+  };
+  row![
+    text("Plugin"),
+    picker,
+    button("Reload plugins").on_press(Message::Looks(looks::Message::ReloadPlugins))
+  ]
+  .spacing(10)
+  .align_y(Alignment::Center)
+  .into()
+}
+
+/// The live tempo, and whether the effects are locked to it.
+pub fn tempo<'a>(bpm: f32, locked: bool) -> Element<'a, Message> {
+  row![text(format!("{:.0} BPM", bpm)).size(18)]
+    .push_maybe(locked.then(|| text("Locked").color(Color::from_rgb(0.55, 0.55, 0.55))))
+    .spacing(10)
+    .align_y(Alignment::Center)
+    .into()
+}
+
+/// Title, artist, album and length of the file playing, above the
+/// visualiser. Untagged files go by their file name.
+pub fn track_header<'a>(metadata: &Metadata, path: &Path) -> Element<'a, Message> {
+  let tags = &metadata.tags;
+  let title = if tags.title.trim().is_empty() {
+    path.file_stem().map_or(String::new(), |stem| stem.to_string_lossy().into_owned())
+  } else {
+    tags.title.clone()
+  };
+  let details: Vec<&str> =
+    [tags.artist.as_str(), tags.album.as_str()].into_iter().filter(|s| !s.is_empty()).collect();
+  let seconds = metadata.duration.as_secs();
+
+  row![text(title).size(22)]
+    .push_maybe((!details.is_empty()).then(|| text(details.join(" — ")).size(16)))
+    .push(text(format!("{}:{:02}", seconds / 60, seconds % 60)).size(16))
+    .spacing(16)
+    .align_y(Alignment::End)
+    .into()
+}
+
+/// The looked-up tags for a poorly tagged file next to what it has now, for
+/// editing before they're written.
+pub fn tag_review<'a>(review: &TagReview) -> Element<'a, Message> {
+  let file = std::path::Path::new(&review.path)
+    .file_name()
+    .map_or(review.path.clone(), |name| name.to_string_lossy().into_owned());
+  let fields = TagField::ALL.into_iter().map(|field| {
+    let current = review.current.get(field);
+    row![
+      text(field.to_string()).width(60),
+      text(if current.is_empty() { "(none)".to_string() } else { current.to_string() }).width(200),
+      text_input(&field.to_string(), review.proposed.get(field))
+        .on_input(move |value| Message::Track(track::Message::TagReviewEdited(field, value)))
+        .width(250),
+    ]
+    .spacing(10)
+    .align_y(iced::Alignment::Center)
+    .into()
+  });
+  column![text(format!("Correct the tags of {}?", file)).size(18)]
+    .extend(fields)
+    .push(
+      row![
+        button("Write tags").on_press(Message::Track(track::Message::WriteTags)),
+        button("Skip").on_press(Message::Track(track::Message::DismissTagReview)),
+      ]
+      .spacing(10),
+    )
+    .spacing(10)
+    .into()
+}
+
+/// Macro recording and replay.
+pub fn macros<'a>(recorder: &MacroRecorder, is_loaded: bool) -> Element<'a, Message> {
+  row![
+    button(if recorder.is_recording() { "Stop recording" } else { "Record macro" })
+      .on_press(Message::ToggleMacroRecording),
+    button(if recorder.is_replaying() { "Stop replay" } else { "Replay macro" }).on_press_maybe(
+      (!recorder.is_empty() && !recorder.is_recording() && is_loaded)
+        .then_some(Message::ToggleMacroReplay),
+    ),
+    text(format!("{} recorded actions", recorder.len())),
+  ]
+  .spacing(10)
+  .align_y(iced::Alignment::Center)
+  .into()
+}
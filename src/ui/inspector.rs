@@ -0,0 +1,219 @@
+use iced::{
+  Element,
+  widget::{button, column, text},
+};
+use serde::Serialize;
+use std::{
+  fmt::Debug,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use crate::analysis::{AnalysisFrame, AnalysisSettings};
+use crate::app::{AudioVisualizer, Message, exports};
+use crate::ui::settings::VisualSettings;
+
+/// Frame timing collected while the inspector is enabled.
+#[derive(Debug, Default)]
+pub struct FrameLog {
+  pub(crate) frames: u64,
+  last_frame_at: Option<Instant>,
+  /// How long the last frame waited between the analysis thread and the UI.
+  last_latency: Duration,
+  /// Strength of the last frame's beat, if it had one.
+  pub(crate) last_beat: Option<f32>,
+}
+
+impl FrameLog {
+  pub fn record(&mut self, frame: &AnalysisFrame) {
+    let now = Instant::now();
+    self.frames += 1;
+    self.last_frame_at = Some(now);
+    self.last_latency = now.saturating_duration_since(frame.produced_at);
+    self.last_beat = frame.beat;
+  }
+}
+
+/// Point-in-time copy of the application state, for the panel and for bug reports.
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+  captured_at_unix_ms: u128,
+  playback: PlaybackState,
+  frames: FrameState,
+  queue: QueueState,
+  /// Settings that differ from their defaults.
+  settings_diff: Vec<SettingDiff>,
+}
+
+#[derive(Debug, Serialize)]
+struct PlaybackState {
+  file: Option<String>,
+  is_loaded: bool,
+  is_playing: bool,
+  is_decaying: bool,
+  position_ms: u128,
+  volume: f32,
+  is_muted: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct FrameState {
+  ticks: u64,
+  frames_received: u64,
+  last_frame_age_ms: Option<u128>,
+  last_frame_latency_ms: u128,
+  last_frame_beat: Option<f32>,
+  beat_pulse: f32,
+  /// Smoothed time the current style takes to draw.
+  draw_cost_us: u128,
+  degradation: String,
+}
+
+#[derive(Debug, Serialize)]
+struct QueueState {
+  /// Analysed frames waiting for their audio to be heard.
+  queued_frames: usize,
+  waveform_samples: usize,
+  spectrogram_columns: usize,
+  macro_events: usize,
+  macro_state: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct SettingDiff {
+  name: &'static str,
+  default: String,
+  current: String,
+}
+
+impl Snapshot {
+  pub fn capture(app: &AudioVisualizer) -> Self {
+    let log = app.inspector.as_ref();
+    let macro_state = if app.recorder.is_recording() {
+      "recording"
+    } else if app.recorder.is_replaying() {
+      "replaying"
+    } else {
+      "idle"
+    };
+
+    Self {
+      captured_at_unix_ms: SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis()),
+      playback: PlaybackState {
+        file: app.player.file_path().map(str::to_string),
+        is_loaded: app.player.is_loaded,
+        is_playing: app.player.is_playing,
+        is_decaying: app.is_decaying,
+        position_ms: app.player.position().as_millis(),
+        volume: app.player.volume,
+        is_muted: app.player.is_muted,
+      },
+      frames: FrameState {
+        ticks: app.tick,
+        frames_received: log.map_or(0, |log| log.frames),
+        last_frame_age_ms: log.and_then(|log| log.last_frame_at).map(|at| at.elapsed().as_millis()),
+        last_frame_latency_ms: log.map_or(0, |log| log.last_latency.as_millis()),
+        last_frame_beat: log.and_then(|log| log.last_beat),
+        beat_pulse: app.beat_pulse,
+        draw_cost_us: app.budget.cost(app.visuals.style).as_micros(),
+        degradation: app.degradation().to_string(),
+      },
+      queue: QueueState {
+        queued_frames: app.audio_data.lock().unwrap().len(),
+        waveform_samples: app.waveform.lock().unwrap().len(),
+        spectrogram_columns: app.spectrogram.len(),
+        macro_events: app.recorder.len(),
+        macro_state,
+      },
+      settings_diff: settings_diff(&app.analysis_settings.lock().unwrap(), &app.visuals),
+    }
+  }
+
+  pub fn to_json(&self) -> serde_json::Result<String> {
+    serde_json::to_string_pretty(self)
+  }
+}
+
+fn settings_diff(analysis: &AnalysisSettings, visuals: &VisualSettings) -> Vec<SettingDiff> {
+  let default_analysis = AnalysisSettings::default();
+  let default_visuals = VisualSettings::default();
+
+  fn diff(name: &'static str, default: impl Debug, current: impl Debug) -> Option<SettingDiff> {
+    let (default, current) = (format!("{:?}", default), format!("{:?}", current));
+    (default != current).then_some(SettingDiff { name, default, current })
+  }
+
+  [
+    diff("fft_size", default_analysis.fft_size, analysis.fft_size),
+    diff("window", default_analysis.window, analysis.window),
+    diff("channel_mode", default_analysis.channel_mode, analysis.channel_mode),
+    diff("weighting", default_analysis.weighting, analysis.weighting),
+    diff("style", default_visuals.style, visuals.style),
+    diff("layout", default_visuals.layout, visuals.layout),
+    diff("frequency_scale", default_visuals.frequency_scale, visuals.frequency_scale),
+    diff("waveform_window_ms", default_visuals.waveform_window_ms, visuals.waveform_window_ms),
+    diff("colormap", default_visuals.colormap, visuals.colormap),
+    diff("spectrogram_length", default_visuals.spectrogram_length, visuals.spectrogram_length),
+    diff("noise_intensity", default_visuals.noise_intensity, visuals.noise_intensity),
+    diff("noise_seed", default_visuals.noise.seed(), visuals.noise.seed()),
+    diff("gradient", default_visuals.gradient, visuals.gradient),
+    diff("smoothing", default_visuals.smoothing, visuals.smoothing),
+    diff("bar_count", default_visuals.bar_count, visuals.bar_count),
+    diff("decibels", default_visuals.decibels, visuals.decibels),
+    diff("auto_range", default_visuals.auto_range, visuals.auto_range),
+    diff("tempo_lock", default_visuals.tempo_lock, visuals.tempo_lock),
+    diff("frame_rate", default_visuals.frame_rate, visuals.frame_rate),
+  ]
+  .into_iter()
+  .flatten()
+  .collect()
+}
+
+pub fn view<'a>(snapshot: &Snapshot) -> Element<'a, Message> {
+  let playback = &snapshot.playback;
+  let frames = &snapshot.frames;
+  let queue = &snapshot.queue;
+
+  let changed = if snapshot.settings_diff.is_empty() {
+    "all defaults".to_string()
+  } else {
+    snapshot
+      .settings_diff
+      .iter()
+      .map(|diff| format!("{}: {} -> {}", diff.name, diff.default, diff.current))
+      .collect::<Vec<_>>()
+      .join(", ")
+  };
+
+  column![
+    text(format!(
+      "Playback: {} | loaded {} | decaying {} | {:.1} s | {}",
+      if playback.is_playing { "playing" } else { "stopped" },
+      playback.is_loaded,
+      playback.is_decaying,
+      playback.position_ms as f32 / 1000.0,
+      playback.file.as_deref().unwrap_or("no file"),
+    )),
+    text(format!(
+      "Frames: {} received | last {} | latency {} ms | tick {}",
+      frames.frames_received,
+      frames.last_frame_age_ms.map_or("never".to_string(), |age| format!("{} ms ago", age)),
+      frames.last_frame_latency_ms,
+      frames.ticks,
+    )),
+    text(format!("Draw: {:.2} ms | {}", frames.draw_cost_us as f32 / 1000.0, frames.degradation,)),
+    text(format!(
+      "Queue: {} frames | waveform {} samples | spectrogram {} columns | macro {} ({})",
+      queue.queued_frames,
+      queue.waveform_samples,
+      queue.spectrogram_columns,
+      queue.macro_events,
+      queue.macro_state,
+    )),
+    text(format!("Changed settings: {}", changed)),
+    button("Dump state to JSON").on_press(Message::Exports(exports::Message::DumpState)),
+  ]
+  .spacing(4)
+  .into()
+}
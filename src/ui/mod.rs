@@ -0,0 +1,4 @@
+pub mod controls;
+pub mod inspector;
+pub mod scene;
+pub mod settings;
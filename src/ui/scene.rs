@@ -0,0 +1,386 @@
+use iced::{
+  Rectangle, Theme, mouse,
+  widget::canvas::{self, Geometry},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, ops::RangeInclusive, time::Instant};
+
+use crate::app::{AudioVisualizer, Message};
+use crate::components::{
+  backdrop::BackdropCanvas,
+  chroma::ChromaCanvas,
+  energy::EnergyBackground,
+  equalizer::EqOverlay,
+  feedback::FeedbackOverlay,
+  gradient::ColorTheme,
+  histogram::HistogramCanvas,
+  particles::ParticleCanvas,
+  phase_plot::{PhaseCanvas, PhaseView},
+  readout::BarReadout,
+  response::ResponseOverlay,
+  smoothing::Region,
+  spectrogram::SpectrogramCanvas,
+  transfer::{TransferCanvas, TransferFunction},
+  visualiser::{Jitter, VisualStyle, VisualizerCanvas},
+  waveform::WaveformCanvas,
+};
+use crate::plugins::{PluginCanvas, PluginFrame};
+
+/// Share of the window the first pane may take, either way.
+pub const SPLIT_RATIO_RANGE: RangeInclusive<f32> = 0.2..=0.8;
+
+/// Which way the window is split between the two panes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SplitDirection {
+  /// The first pane on top.
+  #[default]
+  Stacked,
+  /// The first pane on the left.
+  SideBySide,
+}
+
+impl SplitDirection {
+  pub const ALL: [SplitDirection; 2] = [SplitDirection::Stacked, SplitDirection::SideBySide];
+}
+
+impl fmt::Display for SplitDirection {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      SplitDirection::Stacked => "Stacked",
+      SplitDirection::SideBySide => "Side by side",
+    })
+  }
+}
+
+/// A second style shown next to the first, with its own colours, drawn
+/// from the same analysis.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SplitView {
+  pub enabled: bool,
+  pub direction: SplitDirection,
+  pub style: VisualStyle,
+  pub theme: ColorTheme,
+  /// Share of the window the first pane takes.
+  pub ratio: f32,
+}
+
+impl Default for SplitView {
+  fn default() -> Self {
+    Self {
+      enabled: false,
+      direction: SplitDirection::Stacked,
+      style: VisualStyle::Waveform,
+      theme: ColorTheme::Ocean,
+      ratio: 0.5,
+    }
+  }
+}
+
+/// Which of the split view's panes a scene fills.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pane {
+  /// The selected style; the only pane unless the view is split.
+  Main,
+  /// The split view's second style.
+  Split,
+}
+
+/// Whichever canvas the selected style calls for, drawn from the app state.
+/// Shared by the window and the off-screen video export.
+pub struct Scene<'a> {
+  pub app: &'a AudioVisualizer,
+  /// Drawn in a window, where the 3D terrain is left to a shader widget on
+  /// top; off-screen renders have no GPU and draw flat bars instead.
+  pub live: bool,
+  pub pane: Pane,
+}
+
+fn draw_program<P>(
+  program: P,
+  renderer: &iced::Renderer,
+  theme: &Theme,
+  bounds: Rectangle,
+  cursor: mouse::Cursor,
+) -> Vec<Geometry>
+where
+  P: canvas::Program<Message, State = ()>,
+{
+  program.draw(&(), renderer, theme, bounds, cursor)
+}
+
+impl<'a> canvas::Program<Message> for Scene<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    theme: &Theme,
+    bounds: Rectangle,
+    cursor: mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let started = Instant::now();
+    let app = self.app;
+    let visuals = &app.visuals;
+    let (style, gradient) = visuals.pane(self.pane);
+    let style = match style {
+      VisualStyle::Terrain if !self.live => VisualStyle::Bars,
+      style => style,
+    };
+    // The second pane redraws every frame rather than share the first's cache
+    let uncached = canvas::Cache::new();
+    let cache = match self.pane {
+      Pane::Main => &app.canvas_cache,
+      Pane::Split => &uncached,
+    };
+
+    // The backdrop, then a bound background, go under whichever style is drawn
+    let mut geometry = match visuals.backdrop() {
+      Some(backdrop) => draw_program(
+        BackdropCanvas { backdrop, darken: visuals.backdrop_darken },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ),
+      None => Vec::new(),
+    };
+    if visuals.background_binding.region().is_some() {
+      geometry.extend(draw_program(
+        EnergyBackground { level: app.background_limiter.level(), gradient },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ));
+    }
+
+    geometry.extend(match style {
+      VisualStyle::Bars => draw_program(
+        VisualizerCanvas {
+          frequency_data: &app.frequency_data,
+          peak_data: &app.peak_data,
+          cache,
+          layout: visuals.layout,
+          shape: visuals.shape(),
+          jitter: (visuals.noise_intensity > 0.0
+            && !app.is_calm()
+            && app.degradation().allows_jitter())
+          .then(|| Jitter {
+            noise: &visuals.noise,
+            intensity: visuals.noise_intensity,
+            time: app.animation_time(),
+          }),
+          ring: visuals.ring,
+          time: app.spin_time(),
+          muted: app.player.is_muted,
+          pulse: app.pulse(),
+          gradient,
+        },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ),
+      VisualStyle::Spectrogram => draw_program(
+        SpectrogramCanvas {
+          history: &app.spectrogram,
+          history_length: visuals.spectrogram_length as usize,
+          colormap: visuals.colormap,
+          gradient,
+          cache,
+        },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ),
+      VisualStyle::Waveform => draw_program(
+        WaveformCanvas { samples: app.waveform_samples(), muted: app.player.is_muted, gradient },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ),
+      VisualStyle::Histogram => {
+        let histogram = app.histogram.lock().unwrap();
+        draw_program(
+          HistogramCanvas {
+            levels: histogram.normalised(),
+            total: histogram.total(),
+            gradient,
+            muted: app.player.is_muted,
+            cache,
+          },
+          renderer,
+          theme,
+          bounds,
+          cursor,
+        )
+      }
+      VisualStyle::Phase | VisualStyle::GroupDelay => {
+        let (values, view) = match style {
+          VisualStyle::Phase => (&app.phase, PhaseView::Phase),
+          _ => (&app.group_delay, PhaseView::GroupDelay),
+        };
+        draw_program(
+          PhaseCanvas {
+            values,
+            view,
+            scale: visuals.frequency_scale,
+            sample_rate: app.sample_rate,
+            gradient,
+            muted: app.player.is_muted,
+            cache,
+          },
+          renderer,
+          theme,
+          bounds,
+          cursor,
+        )
+      }
+      VisualStyle::Transfer => {
+        let transfer = match &app.measurement {
+          Some(measurement) => measurement.transfer.lock().unwrap().clone(),
+          None => TransferFunction::new(0),
+        };
+        draw_program(
+          TransferCanvas {
+            magnitude: transfer.magnitude(),
+            phase: transfer.phase(),
+            coherence: transfer.coherence(),
+            blocks: transfer.blocks(),
+            delay: app.measurement.as_ref().and_then(|m| *m.delay.lock().unwrap()),
+            scale: visuals.frequency_scale,
+            sample_rate: app.measurement.as_ref().map_or(app.sample_rate, |m| m.sample_rate),
+            gradient,
+            cache,
+          },
+          renderer,
+          theme,
+          bounds,
+          cursor,
+        )
+      }
+      VisualStyle::Particles => draw_program(
+        ParticleCanvas { system: &app.particles, gradient, muted: app.player.is_muted },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ),
+      VisualStyle::Chroma => draw_program(
+        ChromaCanvas { chroma: app.chroma, muted: app.player.is_muted },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ),
+      VisualStyle::Plugin => match app.active_plugin() {
+        Some(plugin) => {
+          let bars = app.bar_levels();
+          let waveform = app.waveform_samples();
+          let frame = PluginFrame {
+            bars: &bars,
+            waveform: &waveform,
+            width: bounds.width,
+            height: bounds.height,
+            time: app.animation_time(),
+            pulse: app.pulse(),
+            bass: app.energy.level(Region::Low),
+            mid: app.energy.level(Region::Mid),
+            treble: app.energy.level(Region::High),
+            bpm: app.tempo.bpm(),
+          };
+          draw_program(
+            PluginCanvas { plugin, frame, cache, muted: app.player.is_muted },
+            renderer,
+            theme,
+            bounds,
+            cursor,
+          )
+        }
+        None => Vec::new(),
+      },
+      VisualStyle::Terrain => Vec::new(),
+    });
+
+    // A measured room response sits over the analyser bars
+    if style == VisualStyle::Bars
+      && let Some(impulse) = &app.impulse
+    {
+      geometry.extend(draw_program(
+        ResponseOverlay {
+          response: &impulse.response,
+          scale: visuals.frequency_scale,
+          sample_rate: impulse.sample_rate,
+        },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ));
+    }
+
+    // And the EQ while it's on, only on screen
+    if self.live && style == VisualStyle::Bars && app.player.equalizer.enabled {
+      geometry.extend(draw_program(
+        EqOverlay {
+          settings: app.player.equalizer,
+          scale: visuals.frequency_scale,
+          sample_rate: app.sample_rate,
+        },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ));
+    }
+
+    // So are the frequencies flagged as feedback
+    if style == VisualStyle::Bars
+      && let Some(detector) = &app.feedback
+    {
+      geometry.extend(draw_program(
+        FeedbackOverlay {
+          flagged: detector.flagged(),
+          scale: visuals.frequency_scale,
+          sample_rate: app.sample_rate,
+        },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ));
+    }
+
+    // Frequency labels and the readout under the mouse only help in a window
+    if self.live && style == VisualStyle::Bars {
+      geometry.extend(draw_program(
+        BarReadout {
+          frequency_data: &app.frequency_data,
+          layout: visuals.layout,
+          shape: visuals.shape(),
+          scale: visuals.frequency_scale,
+          sample_rate: app.sample_rate,
+          decibels: app.decibels(),
+          labels: app.show_frequency_labels,
+          ring: visuals.ring,
+          time: app.spin_time(),
+        },
+        renderer,
+        theme,
+        bounds,
+        cursor,
+      ));
+    }
+
+    // Off-screen renders take as long as they need, so only the window
+    // counts against the budget, which is kept for the main style
+    if self.live && self.pane == Pane::Main {
+      app.draw_cost.set(Some(started.elapsed()));
+    }
+    geometry
+  }
+}
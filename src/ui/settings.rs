@@ -0,0 +1,1341 @@
+use iced::{
+  Color, Element, Length,
+  widget::{
+    Canvas, button, checkbox, column, pick_list, row, scrollable, slider, text, text_input,
+    vertical_slider,
+  },
+};
+use std::time::Duration;
+
+use crate::analysis::{
+  self, AnalysisSettings, DecibelRange, FFT_SIZES, MAX_DECIBEL_RANGE, MIN_DECIBEL_GAP,
+  MIN_DECIBEL_RANGE, Overlap,
+};
+use crate::app::Message::{Analysis, Display, Exports, Looks, Outputs, Preferences, Track, Visual};
+use crate::app::{
+  DEFAULT_NUM_BARS, DEFAULT_UPDATE_INTERVAL, display, exports, looks, outputs, preferences, track,
+};
+use crate::components::{
+  backdrop::{Backdrop, BackdropSource, DEFAULT_BACKDROP_BLUR, DEFAULT_BACKDROP_DARKEN},
+  binning::FrequencyScale,
+  channels::ChannelMode,
+  energy::EnergyBinding,
+  equalizer::{EQ_BANDS, EqSettings, MAX_EQ_GAIN_DB},
+  frame_rate::FrameRate,
+  grade::{BRIGHTNESS_RANGE, CONTRAST_RANGE, ColorGrade, GAMMA_RANGE, SATURATION_RANGE},
+  gradient::{ColorTheme, DEFAULT_CUSTOM_END, DEFAULT_CUSTOM_START, Gradient, Palette},
+  layout::{
+    Contours, INNER_RADIUS_RANGE, LayoutKind, MAX_SPIN, MaskEdges, Mirror, RingSettings, Shape,
+  },
+  noise::Perlin,
+  particles::{Emitter, EmitterRing, MAX_BURST},
+  postfx::{EFFECT_RANGE, Effects},
+  smoothing::{MAX_ATTACK_MS, MAX_RELEASE_MS, Region, RegionSmoothing},
+  spectrogram::Colormap,
+  visualiser::VisualStyle,
+};
+use crate::config::Config;
+use crate::encode::{EncodeSettings, FLAC_LEVELS, MP3_BITRATES, OPUS_BITRATES, WavDepth};
+use crate::graphics::{GraphicsSettings, PowerPreference, RendererBackend};
+use crate::keymap::{Action, Keymap};
+use crate::midi::{self, MidiSettings};
+use crate::normalise::{self, NormaliseSettings};
+use crate::osc::OscSettings;
+use crate::presets::{HOTKEY_PRESETS, PresetLibrary};
+use crate::quality::QualityProfile;
+use crate::remote::{RemoteSettings, parse_port};
+use crate::rumble::{RumbleCurve, RumbleSettings};
+use crate::session::SessionSettings;
+use crate::shm::ShmSettings;
+use crate::suggest::{SuggestMode, SuggestSettings};
+use crate::tray::TraySettings;
+use crate::ui::scene::{Pane, SPLIT_RATIO_RANGE, SplitDirection, SplitView};
+
+const DEFAULT_LAYOUT_TEXT: &str = "LIVE";
+/// Fonts tried for the text layout until the user picks one.
+const FALLBACK_FONTS: [&str; 5] = [
+  "/System/Library/Fonts/Supplemental/Arial Bold.ttf",
+  "/Library/Fonts/Arial Bold.ttf",
+  "/usr/share/fonts/truetype/dejavu/DejaVuSans-Bold.ttf",
+  "/usr/share/fonts/TTF/DejaVuSans-Bold.ttf",
+  "C:\\Windows\\Fonts\\arialbd.ttf",
+];
+const DEFAULT_WAVEFORM_WINDOW_MS: f32 = 50.0;
+const DEFAULT_SPECTROGRAM_LENGTH: u16 = 300;
+const DEFAULT_NOISE_SEED: u64 = 1;
+const MIN_BAR_COUNT: usize = 8;
+const MAX_BAR_COUNT: usize = 512;
+
+#[derive(Debug, Clone)]
+pub enum Message {
+  StyleSelected(VisualStyle),
+  LayoutSelected(LayoutKind),
+  LoadCustomPath,
+  LoadMask,
+  LayoutTextChanged(String),
+  /// Redraws the text layout with the text as typed.
+  ApplyLayoutText,
+  LoadFont,
+  ScaleSelected(FrequencyScale),
+  WaveformWindowChanged(f32),
+  ColormapSelected(Colormap),
+  SpectrogramLengthChanged(u16),
+  NoiseIntensityChanged(f32),
+  NoiseSeedChanged(String),
+  ThemeSelected(ColorTheme),
+  CustomStartChanged(String),
+  CustomEndChanged(String),
+  /// Keeps the chosen theme instead of following each track's cover art.
+  LockThemeToggled(bool),
+  SmoothingRegionSelected(Region),
+  /// Attack and release times of the selected region, in milliseconds.
+  AttackChanged(f32),
+  ReleaseChanged(f32),
+  BarCountChanged(u16),
+  MinDecibelChanged(f32),
+  MaxDecibelChanged(f32),
+  /// Follows the signal's recent floor and peaks instead of the fixed range.
+  AutoRangeToggled(bool),
+  FrameRateSelected(FrameRate),
+  /// Band whose energy tints the background.
+  BackgroundBindingSelected(EnergyBinding),
+  /// Band whose energy drives the pulse, instead of the beats.
+  PulseBindingSelected(EnergyBinding),
+  /// Spins and pulses with the detected tempo.
+  TempoLockToggled(bool),
+  BackdropSourceSelected(BackdropSource),
+  LoadBackdrop,
+  BackdropBlurChanged(f32),
+  BackdropDarkenChanged(f32),
+  GradeChanged(ColorGrade),
+  RingChanged(RingSettings),
+  AddEmitter,
+  /// Removes the emitter picked in the editor.
+  RemoveEmitter,
+  EmitterSelected(usize),
+  EmitterChanged(usize, Emitter),
+  SplitChanged(SplitView),
+  EffectsChanged(Effects),
+}
+
+/// Everything that changes how the analysis is drawn, as opposed to what gets analysed.
+#[derive(Clone)]
+pub struct VisualSettings {
+  pub style: VisualStyle,
+  pub layout: LayoutKind,
+  pub ring: RingSettings,
+  custom_path: Option<Contours>,
+  layout_text: String,
+  font_data: Option<Vec<u8>>,
+  text_shape: Option<Contours>,
+  /// Why the text layout couldn't be drawn, shown by its input.
+  text_error: Option<String>,
+  mask: Option<MaskEdges>,
+  pub frequency_scale: FrequencyScale,
+  pub waveform_window_ms: f32,
+  pub colormap: Colormap,
+  pub spectrogram_length: u16,
+  pub noise: Perlin,
+  pub noise_intensity: f32,
+  noise_seed_input: String,
+  pub gradient: Gradient,
+  custom_start_input: String,
+  custom_end_input: String,
+  /// Palette from the current track's cover art, applied unless the theme is locked.
+  track_palette: Option<Palette>,
+  lock_theme: bool,
+  pub smoothing: RegionSmoothing,
+  /// Region the attack/release sliders currently edit.
+  smoothing_region: Region,
+  pub bar_count: usize,
+  pub decibels: DecibelRange,
+  /// Maps the signal's recent floor and peaks onto the visuals, with
+  /// `decibels` only until something's been heard.
+  pub auto_range: bool,
+  pub frame_rate: FrameRate,
+  /// Time the current tick moves the visuals on by: the frame rate's
+  /// interval, or how long the last frame took while it's uncapped.
+  pub update_interval: Duration,
+  pub background_binding: EnergyBinding,
+  pub pulse_binding: EnergyBinding,
+  /// Ring spin runs at the detected tempo, as if set for 120 BPM, and the
+  /// beat pulse comes on the tempo's beats rather than each one detected.
+  pub tempo_lock: bool,
+  pub backdrop_source: BackdropSource,
+  /// Picked with "Load backdrop"; kept while album art is shown instead.
+  image_backdrop: Option<Backdrop>,
+  /// From the current track's cover art.
+  track_backdrop: Option<Backdrop>,
+  /// 0.0 (sharp) to 1.0.
+  backdrop_blur: f32,
+  /// How far towards black the backdrop is drawn, 0.0 to 1.0.
+  pub backdrop_darken: f32,
+  /// Bursts of particles fired by the energy in their bands.
+  pub emitters: Vec<Emitter>,
+  /// Emitter the editor shows.
+  selected_emitter: usize,
+  pub split: SplitView,
+  pub effects: Effects,
+}
+
+/// What the settings panel shows besides the visual settings themselves.
+pub struct PanelInputs<'a> {
+  pub analysis_settings: AnalysisSettings,
+  pub encode: EncodeSettings,
+  pub graphics: GraphicsSettings,
+  pub osc: &'a OscSettings,
+  /// The OSC host and port as they're being typed.
+  pub osc_host: &'a str,
+  pub osc_port: &'a str,
+  pub shm: &'a ShmSettings,
+  pub midi: &'a MidiSettings,
+  /// MIDI outputs to pick from.
+  pub midi_ports: &'a [String],
+  pub rumble: &'a RumbleSettings,
+  /// Controllers to pick from.
+  pub rumble_devices: &'a [String],
+  pub remote: &'a RemoteSettings,
+  /// The remote's port as it's being typed.
+  pub remote_port: &'a str,
+  pub tray: TraySettings,
+  pub normalise: NormaliseSettings,
+  pub equalizer: EqSettings,
+  pub session: SessionSettings,
+  pub presets: &'a PresetLibrary,
+  /// Name typed in for the next saved preset.
+  pub preset_name: &'a str,
+  pub suggest: SuggestSettings,
+  /// Preset suggested for the playing track.
+  pub suggested_preset: Option<usize>,
+  pub keymap: &'a Keymap,
+  /// The shortcut waiting for its new key, if any.
+  pub rebinding: Option<Action>,
+}
+
+impl VisualSettings {
+  /// Applies a change from the panel. Returns why, when it picked a file
+  /// that couldn't be loaded, for the warning banner.
+  pub fn update(&mut self, message: Message) -> Option<String> {
+    match message {
+      Message::StyleSelected(style) => self.style = style,
+      Message::LayoutSelected(layout) => {
+        if layout == LayoutKind::CustomPath && self.custom_path.is_none() {
+          return self.load_custom_path().err();
+        } else if layout == LayoutKind::Mask && self.mask.is_none() {
+          return self.load_mask().err();
+        } else {
+          self.layout = layout;
+          if layout == LayoutKind::Text && self.text_shape.is_none() {
+            self.rebuild_text_shape();
+          }
+        }
+      }
+      Message::LoadCustomPath => return self.load_custom_path().err(),
+      Message::LoadMask => return self.load_mask().err(),
+      Message::LayoutTextChanged(layout_text) => self.layout_text = layout_text,
+      Message::ApplyLayoutText => self.rebuild_text_shape(),
+      Message::LoadFont => {
+        if let Some(path) = rfd::FileDialog::new().add_filter("Font", &["ttf", "otf"]).pick_file() {
+          match std::fs::read(&path) {
+            Ok(font) => {
+              self.font_data = Some(font);
+              self.rebuild_text_shape();
+            }
+            Err(e) => self.text_error = Some(format!("Couldn't read {}: {}", path.display(), e)),
+          }
+        }
+      }
+      Message::ScaleSelected(scale) => self.frequency_scale = scale,
+      Message::WaveformWindowChanged(window_ms) => self.waveform_window_ms = window_ms,
+      Message::ColormapSelected(colormap) => self.colormap = colormap,
+      Message::SpectrogramLengthChanged(length) => self.spectrogram_length = length,
+      Message::NoiseIntensityChanged(intensity) => self.noise_intensity = intensity,
+      Message::NoiseSeedChanged(input) => {
+        // Only reseed on valid input, but keep whatever is typed
+        if let Ok(seed) = input.trim().parse::<u64>()
+          && seed != self.noise.seed()
+        {
+          self.noise = Perlin::new(seed);
+        }
+        self.noise_seed_input = input;
+      }
+      Message::ThemeSelected(theme) => self.gradient.theme = theme,
+      // Like the seed, colours only apply once they parse
+      Message::CustomStartChanged(input) => {
+        if let Some(color) = Color::parse(input.trim()) {
+          self.gradient.start = color;
+        }
+        self.custom_start_input = input;
+      }
+      Message::CustomEndChanged(input) => {
+        if let Some(color) = Color::parse(input.trim()) {
+          self.gradient.end = color;
+        }
+        self.custom_end_input = input;
+      }
+      Message::LockThemeToggled(lock) => {
+        self.lock_theme = lock;
+        self.apply_palette();
+      }
+      Message::SmoothingRegionSelected(region) => self.smoothing_region = region,
+      Message::AttackChanged(attack) => {
+        self.smoothing.get_mut(self.smoothing_region).attack_ms = attack
+      }
+      Message::ReleaseChanged(release) => {
+        self.smoothing.get_mut(self.smoothing_region).release_ms = release
+      }
+      Message::BarCountChanged(count) => {
+        self.bar_count = (count as usize).clamp(MIN_BAR_COUNT, MAX_BAR_COUNT)
+      }
+      // Keep at least 10 dB between the ends so the mapping never divides by zero
+      Message::MinDecibelChanged(min) => {
+        self.decibels.min = min.min(self.decibels.max - MIN_DECIBEL_GAP)
+      }
+      Message::MaxDecibelChanged(max) => {
+        self.decibels.max = max.max(self.decibels.min + MIN_DECIBEL_GAP)
+      }
+      Message::AutoRangeToggled(auto_range) => self.auto_range = auto_range,
+      Message::FrameRateSelected(frame_rate) => self.set_frame_rate(frame_rate),
+      Message::BackgroundBindingSelected(binding) => self.background_binding = binding,
+      Message::PulseBindingSelected(binding) => self.pulse_binding = binding,
+      Message::TempoLockToggled(tempo_lock) => self.tempo_lock = tempo_lock,
+      Message::BackdropSourceSelected(source) => {
+        if source == BackdropSource::Image && self.image_backdrop.is_none() {
+          return self.load_backdrop().err();
+        } else {
+          self.backdrop_source = source;
+        }
+      }
+      Message::LoadBackdrop => return self.load_backdrop().err(),
+      Message::BackdropBlurChanged(blur) => {
+        self.backdrop_blur = blur;
+        for backdrop in [&mut self.image_backdrop, &mut self.track_backdrop].into_iter().flatten() {
+          backdrop.set_blur(blur);
+        }
+      }
+      Message::BackdropDarkenChanged(darken) => self.backdrop_darken = darken,
+      Message::GradeChanged(grade) => self.gradient.grade = grade,
+      Message::RingChanged(ring) => self.ring = ring,
+      Message::SplitChanged(split) => self.split = split,
+      Message::EffectsChanged(effects) => self.effects = effects,
+      Message::AddEmitter => {
+        self.emitters.push(Emitter::default());
+        self.selected_emitter = self.emitters.len() - 1;
+      }
+      Message::RemoveEmitter => {
+        if self.selected_emitter < self.emitters.len() {
+          self.emitters.remove(self.selected_emitter);
+        }
+        self.selected_emitter = self.selected_emitter.min(self.emitters.len().saturating_sub(1));
+      }
+      Message::EmitterSelected(index) => self.selected_emitter = index,
+      Message::EmitterChanged(index, emitter) => {
+        if let Some(existing) = self.emitters.get_mut(index) {
+          *existing = emitter;
+        }
+      }
+    }
+    None
+  }
+
+  /// The particle emitters on their ring, with the picked one's settings.
+  fn emitter_editor(&self) -> Element<'_, crate::app::Message> {
+    let ring = Canvas::new(EmitterRing {
+      emitters: &self.emitters,
+      selected: self.selected_emitter,
+      gradient: self.gradient,
+    })
+    .width(Length::Fill)
+    .height(160);
+    let editor = column![
+      text("Particle emitters"),
+      ring,
+      row![
+        button("Add").on_press(Visual(Message::AddEmitter)),
+        button("Remove")
+          .on_press_maybe((!self.emitters.is_empty()).then_some(Visual(Message::RemoveEmitter))),
+      ]
+      .spacing(10),
+    ]
+    .spacing(10);
+    let Some(emitter) = self.emitters.get(self.selected_emitter) else {
+      return editor.into();
+    };
+    let index = self.selected_emitter;
+    let changed = move |emitter: Emitter| Visual(Message::EmitterChanged(index, emitter));
+
+    editor
+      .push(
+        row![
+          text("Band Hz").width(Length::Fill),
+          text_input("Low", &emitter.low_hz.to_string()).width(60).on_input(move |input| {
+            let low_hz = input.trim().parse().unwrap_or(emitter.low_hz);
+            changed(Emitter { low_hz, ..emitter.clone() })
+          }),
+          text_input("High", &emitter.high_hz.to_string()).width(60).on_input(move |input| {
+            let high_hz = input.trim().parse().unwrap_or(emitter.high_hz);
+            changed(Emitter { high_hz, ..emitter.clone() })
+          }),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center),
+      )
+      .push(
+        row![
+          text("Colour").width(Length::Fill),
+          text_input("#ffffff", &emitter.color)
+            .width(90)
+            .on_input(move |color| changed(Emitter { color, ..emitter.clone() })),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center),
+      )
+      .push(text(format!("Position {:.0} degrees", emitter.angle)))
+      .push(
+        slider(0.0..=359.0, emitter.angle, move |angle| {
+          changed(Emitter { angle, ..emitter.clone() })
+        })
+        .step(1.0),
+      )
+      .push(text(format!("Burst {} particles", emitter.burst)))
+      .push(slider(1..=MAX_BURST, emitter.burst, move |burst| {
+        changed(Emitter { burst, ..emitter.clone() })
+      }))
+      .push(text(format!("Fires at {:.0}%", emitter.threshold * 100.0)))
+      .push(
+        slider(0.05..=1.0, emitter.threshold, move |threshold| {
+          changed(Emitter { threshold, ..emitter.clone() })
+        })
+        .step(0.05),
+      )
+      .into()
+  }
+
+  /// Shows a new track's cover art as the backdrop, when album art is picked.
+  pub fn set_track_backdrop(&mut self, backdrop: Option<Backdrop>) {
+    self.track_backdrop = backdrop.map(|mut backdrop| {
+      backdrop.set_blur(self.backdrop_blur);
+      backdrop
+    });
+  }
+
+  /// The picture to draw behind the visuals, if any.
+  pub fn backdrop(&self) -> Option<&Backdrop> {
+    match self.backdrop_source {
+      BackdropSource::Off => None,
+      BackdropSource::AlbumArt => self.track_backdrop.as_ref(),
+      BackdropSource::Image => self.image_backdrop.as_ref(),
+    }
+  }
+
+  /// Follows a new track's cover art, or the theme when it has none.
+  pub fn set_track_palette(&mut self, palette: Option<Palette>) {
+    self.track_palette = palette;
+    self.apply_palette();
+  }
+
+  fn apply_palette(&mut self) {
+    self.gradient.palette = if self.lock_theme { None } else { self.track_palette };
+  }
+
+  /// The persisted part of the settings.
+  pub fn to_config(&self, analysis_settings: &AnalysisSettings) -> Config {
+    Config {
+      bar_count: self.bar_count,
+      envelopes: self.smoothing,
+      decibels: self.decibels,
+      auto_range: self.auto_range,
+      fft_size: analysis_settings.fft_size,
+      overlap: analysis_settings.overlap,
+      theme: self.gradient.theme,
+      custom_start: self.custom_start_input.clone(),
+      custom_end: self.custom_end_input.clone(),
+      lock_theme: self.lock_theme,
+      frame_rate: self.frame_rate,
+      background_binding: self.background_binding,
+      pulse_binding: self.pulse_binding,
+      tempo_lock: self.tempo_lock,
+      // A picked image isn't kept between runs, so neither is showing it
+      backdrop_source: match self.backdrop_source {
+        BackdropSource::Image => BackdropSource::Off,
+        source => source,
+      },
+      backdrop_blur: self.backdrop_blur,
+      backdrop_darken: self.backdrop_darken,
+      grade: self.gradient.grade,
+      particle_emitters: self.emitters.clone(),
+      ring: self.ring,
+      split: self.split,
+      effects: self.effects,
+      ..Config::default()
+    }
+  }
+
+  /// Ticks at `frame_rate` from now on. An uncapped rate keeps the last
+  /// interval until the first frame is timed.
+  pub fn set_frame_rate(&mut self, frame_rate: FrameRate) {
+    self.frame_rate = frame_rate;
+    if let Some(interval) = frame_rate.interval() {
+      self.update_interval = interval;
+    }
+  }
+
+  pub fn apply_config(&mut self, config: &Config, analysis_settings: &mut AnalysisSettings) {
+    self.bar_count = config.bar_count.clamp(MIN_BAR_COUNT, MAX_BAR_COUNT);
+    self.smoothing = config.envelopes;
+    self.decibels = config.decibels.repaired();
+    self.auto_range = config.auto_range;
+    if FFT_SIZES.contains(&config.fft_size) {
+      analysis_settings.fft_size = config.fft_size;
+    }
+    analysis_settings.overlap = config.overlap;
+    self.gradient.theme = config.theme;
+    self.update(Message::CustomStartChanged(config.custom_start.clone()));
+    self.update(Message::CustomEndChanged(config.custom_end.clone()));
+    self.update(Message::LockThemeToggled(config.lock_theme));
+    self.set_frame_rate(config.update_interval_ms.map_or(config.frame_rate, FrameRate::nearest));
+    self.background_binding = config.background_binding;
+    self.pulse_binding = config.pulse_binding;
+    self.tempo_lock = config.tempo_lock;
+    if config.backdrop_source != BackdropSource::Image {
+      self.backdrop_source = config.backdrop_source;
+    }
+    self.update(Message::BackdropBlurChanged(config.backdrop_blur.clamp(0.0, 1.0)));
+    self.backdrop_darken = config.backdrop_darken.clamp(0.0, 1.0);
+    self.gradient.grade = config.grade;
+    self.emitters = config.particle_emitters.clone();
+    self.selected_emitter = 0;
+    self.ring = config.ring;
+    self.split = SplitView {
+      ratio: config.split.ratio.clamp(*SPLIT_RATIO_RANGE.start(), *SPLIT_RATIO_RANGE.end()),
+      ..config.split
+    };
+    let effect = |value: f32| value.clamp(*EFFECT_RANGE.start(), *EFFECT_RANGE.end());
+    self.effects = Effects {
+      bloom: effect(config.effects.bloom),
+      trails: effect(config.effects.trails),
+      aberration: effect(config.effects.aberration),
+    };
+  }
+
+  /// The style `pane` shows and the colours it's drawn in. The second pane
+  /// has its own theme rather than the cover art's.
+  pub fn pane(&self, pane: Pane) -> (VisualStyle, Gradient) {
+    match pane {
+      Pane::Main => (self.style, self.gradient),
+      Pane::Split => {
+        (self.split.style, Gradient { theme: self.split.theme, palette: None, ..self.gradient })
+      }
+    }
+  }
+
+  /// Whether either pane shows `style`.
+  pub fn shows(&self, style: VisualStyle) -> bool {
+    self.style == style || (self.split.enabled && self.split.style == style)
+  }
+
+  /// The outline the current layout places bars along, if it needs one.
+  pub fn shape(&self) -> Option<Shape<'_>> {
+    match self.layout {
+      LayoutKind::CustomPath => self.custom_path.as_ref().map(Shape::Contours),
+      LayoutKind::Text => self.text_shape.as_ref().map(Shape::Contours),
+      LayoutKind::Mask => self.mask.as_ref().map(Shape::Mask),
+      _ => None,
+    }
+  }
+
+  /// Asks for an SVG and lays the bars along its path. Cancelling the
+  /// dialog isn't an error.
+  fn load_custom_path(&mut self) -> Result<(), String> {
+    let Some(path) = rfd::FileDialog::new().add_filter("SVG", &["svg"]).pick_file() else {
+      return Ok(());
+    };
+    let document = std::fs::read_to_string(&path).map_err(|e| {
+      eprintln!("Failed to read SVG file: {}", e);
+      format!("Couldn't read {}: {}", path.display(), e)
+    })?;
+    let contours = Contours::from_svg_document(&document).map_err(|e| {
+      eprintln!("Failed to read a path from {}: {}", path.display(), e);
+      format!("Couldn't find a path to lay the bars along in {}: {}", path.display(), e)
+    })?;
+    self.custom_path = Some(contours);
+    self.layout = LayoutKind::CustomPath;
+    Ok(())
+  }
+
+  /// Asks for an image and lays the bars along its edges.
+  fn load_mask(&mut self) -> Result<(), String> {
+    let Some(path) =
+      rfd::FileDialog::new().add_filter("Image", &["png", "jpg", "jpeg", "bmp", "gif"]).pick_file()
+    else {
+      return Ok(());
+    };
+    match MaskEdges::from_image(&path) {
+      Ok(mask) if !mask.is_empty() => {
+        self.mask = Some(mask);
+        self.layout = LayoutKind::Mask;
+        Ok(())
+      }
+      Ok(_) => {
+        eprintln!("No edges found in mask {}", path.display());
+        Err(format!("Couldn't find any edges in {} to lay the bars along", path.display()))
+      }
+      Err(e) => {
+        eprintln!("Failed to load mask image: {}", e);
+        Err(format!("Couldn't load {}: {}", path.display(), e))
+      }
+    }
+  }
+
+  /// Asks for an image to draw behind the visuals.
+  fn load_backdrop(&mut self) -> Result<(), String> {
+    let Some(path) = rfd::FileDialog::new()
+      .add_filter("Image", &["png", "jpg", "jpeg", "bmp", "gif", "webp"])
+      .pick_file()
+    else {
+      return Ok(());
+    };
+    let mut backdrop = Backdrop::open(&path).map_err(|e| {
+      eprintln!("Failed to load backdrop image: {}", e);
+      format!("Couldn't load {} as the backdrop: {}", path.display(), e)
+    })?;
+    backdrop.set_blur(self.backdrop_blur);
+    self.image_backdrop = Some(backdrop);
+    self.backdrop_source = BackdropSource::Image;
+    Ok(())
+  }
+
+  fn rebuild_text_shape(&mut self) {
+    if self.font_data.is_none() {
+      self.font_data = FALLBACK_FONTS.iter().find_map(|path| std::fs::read(path).ok());
+    }
+
+    let shape = match &self.font_data {
+      Some(font) => {
+        Contours::from_text(font, &self.layout_text).map_err(|e| format!("Bad font: {}", e))
+      }
+      None => Err("No font found, load one first".to_string()),
+    };
+    (self.text_shape, self.text_error) = match shape {
+      Ok(contours) => (Some(contours), None),
+      Err(e) => (None, Some(e)),
+    };
+  }
+
+  pub fn view(&self, analysis_settings: &AnalysisSettings) -> Element<'_, crate::app::Message> {
+    row![
+      text("Style"),
+      pick_list(VisualStyle::ALL, Some(self.style), |style| Visual(Message::StyleSelected(style))),
+      text("Layout"),
+      pick_list(LayoutKind::ALL, Some(self.layout), |layout| Visual(Message::LayoutSelected(
+        layout
+      ))),
+      button("Load SVG path").on_press(Visual(Message::LoadCustomPath)),
+      text_input("Layout text", &self.layout_text)
+        .on_input(|layout_text| Visual(Message::LayoutTextChanged(layout_text)))
+        .on_submit(Visual(Message::ApplyLayoutText))
+        .width(160),
+      button("Load font").on_press(Visual(Message::LoadFont)),
+      button("Load mask").on_press(Visual(Message::LoadMask)),
+      text("Channels"),
+      pick_list(ChannelMode::ALL, Some(analysis_settings.channel_mode), |mode| {
+        Analysis(analysis::Message::ChannelModeSelected(mode))
+      }),
+      text("Scale"),
+      pick_list(FrequencyScale::ALL, Some(self.frequency_scale), |scale| Visual(
+        Message::ScaleSelected(scale)
+      )),
+      text(format!("Waveform {:.0} ms", self.waveform_window_ms)),
+      slider(5.0..=500.0, self.waveform_window_ms, |window_ms| Visual(
+        Message::WaveformWindowChanged(window_ms)
+      ))
+      .width(120),
+      pick_list(Colormap::ALL, Some(self.colormap), |colormap| Visual(Message::ColormapSelected(
+        colormap
+      ))),
+      text(format!("History {}", self.spectrogram_length)),
+      slider(50..=1000, self.spectrogram_length, |length| Visual(
+        Message::SpectrogramLengthChanged(length)
+      ))
+      .width(120),
+      text("Jitter"),
+      slider(0.0..=1.0, self.noise_intensity, |intensity| Visual(Message::NoiseIntensityChanged(
+        intensity
+      )))
+      .step(0.01)
+      .width(100),
+      text_input("Seed", &self.noise_seed_input)
+        .on_input(|input| Visual(Message::NoiseSeedChanged(input)))
+        .width(80),
+    ]
+    .push_maybe(self.text_error.as_deref().map(text))
+    .push_maybe(
+      self
+        .shows(VisualStyle::Terrain)
+        .then(|| button("Reset camera").on_press(Display(display::Message::ResetCamera))),
+    )
+    .spacing(10)
+    .align_y(iced::Alignment::Center)
+    .into()
+  }
+
+  /// Side panel with the persisted settings.
+  pub fn panel<'a>(&'a self, inputs: PanelInputs<'a>) -> Element<'a, crate::app::Message> {
+    let PanelInputs {
+      analysis_settings,
+      encode,
+      graphics,
+      osc,
+      osc_host,
+      osc_port,
+      shm,
+      midi,
+      midi_ports,
+      rumble,
+      rumble_devices,
+      remote,
+      remote_port,
+      tray,
+      normalise,
+      equalizer,
+      session,
+      presets,
+      preset_name,
+      suggest,
+      suggested_preset,
+      keymap,
+      rebinding,
+    } = inputs;
+    let envelope = self.smoothing.get(self.smoothing_region);
+    let grade = self.gradient.grade;
+    let ring = self.ring;
+    let split = self.split;
+    let effects = self.effects;
+
+    let content = column![
+      text("Settings").size(20),
+      text("Quality"),
+      pick_list(
+        QualityProfile::ALL,
+        QualityProfile::matching(&analysis_settings, self),
+        |profile| Display(display::Message::QualityProfileSelected(profile))
+      )
+      .placeholder("Custom"),
+      text(format!("Bars {}", self.bar_count)),
+      slider(MIN_BAR_COUNT as u16..=MAX_BAR_COUNT as u16, self.bar_count as u16, |count| {
+        Visual(Message::BarCountChanged(count))
+      }),
+      text("FFT size"),
+      pick_list(FFT_SIZES, Some(analysis_settings.fft_size), |fft_size| {
+        Analysis(analysis::Message::FftSizeSelected(fft_size))
+      }),
+      text("Overlap"),
+      pick_list(Overlap::ALL, Some(analysis_settings.overlap), |overlap| {
+        Analysis(analysis::Message::OverlapSelected(overlap))
+      }),
+      text(format!("dB range {:.0} to {:.0}", self.decibels.min, self.decibels.max)),
+      slider(MIN_DECIBEL_RANGE, self.decibels.min, |min| Visual(Message::MinDecibelChanged(min))),
+      slider(MAX_DECIBEL_RANGE, self.decibels.max, |max| Visual(Message::MaxDecibelChanged(max))),
+      checkbox("Auto range (follow the signal)", self.auto_range)
+        .on_toggle(|auto_range| Visual(Message::AutoRangeToggled(auto_range))),
+      text("Frame rate"),
+      pick_list(FrameRate::ALL, Some(self.frame_rate), |frame_rate| Visual(
+        Message::FrameRateSelected(frame_rate)
+      )),
+      text("Theme"),
+      pick_list(ColorTheme::ALL, Some(self.gradient.theme), |theme| Visual(
+        Message::ThemeSelected(theme)
+      )),
+    ]
+    .push_maybe((self.gradient.theme == ColorTheme::Custom).then(|| {
+      row![
+        text_input("Start", &self.custom_start_input)
+          .on_input(|input| Visual(Message::CustomStartChanged(input))),
+        text_input("End", &self.custom_end_input)
+          .on_input(|input| Visual(Message::CustomEndChanged(input))),
+      ]
+      .spacing(10)
+    }))
+    .push(
+      checkbox("Lock theme (ignore cover art)", self.lock_theme)
+        .on_toggle(|lock| Visual(Message::LockThemeToggled(lock))),
+    )
+    .push(text("Background follows"))
+    .push(pick_list(EnergyBinding::ALL, Some(self.background_binding), |binding| {
+      Visual(Message::BackgroundBindingSelected(binding))
+    }))
+    .push(text("Pulse follows"))
+    .push(pick_list(EnergyBinding::ALL, Some(self.pulse_binding), |binding| {
+      Visual(Message::PulseBindingSelected(binding))
+    }))
+    .push(
+      checkbox("Lock spin and pulse to the tempo", self.tempo_lock)
+        .on_toggle(|tempo_lock| Visual(Message::TempoLockToggled(tempo_lock))),
+    )
+    .push(text("Backdrop"))
+    .push(pick_list(BackdropSource::ALL, Some(self.backdrop_source), |source| {
+      Visual(Message::BackdropSourceSelected(source))
+    }))
+    .push_maybe(
+      (self.backdrop_source == BackdropSource::Image)
+        .then(|| button("Load backdrop").on_press(Visual(Message::LoadBackdrop))),
+    )
+    .push(text(format!("Blur {:.0}%", self.backdrop_blur * 100.0)))
+    .push(
+      slider(0.0..=1.0, self.backdrop_blur, |blur| Visual(Message::BackdropBlurChanged(blur)))
+        .step(0.05),
+    )
+    .push(text(format!("Darken {:.0}%", self.backdrop_darken * 100.0)))
+    .push(
+      slider(0.0..=1.0, self.backdrop_darken, |darken| {
+        Visual(Message::BackdropDarkenChanged(darken))
+      })
+      .step(0.05),
+    )
+    .push(text("Ring"))
+    .push(pick_list(Mirror::ALL, Some(ring.mirror), move |mirror| {
+      Visual(Message::RingChanged(RingSettings { mirror, ..ring }))
+    }))
+    .push(text(format!("Start {:.0} degrees", ring.start_angle)))
+    .push(
+      slider(0.0..=359.0, ring.start_angle, move |start_angle| {
+        Visual(Message::RingChanged(RingSettings { start_angle, ..ring }))
+      })
+      .step(1.0),
+    )
+    .push(text(format!("Spin {:+.0} degrees/s", ring.spin)))
+    .push(
+      slider(-MAX_SPIN..=MAX_SPIN, ring.spin, move |spin| {
+        Visual(Message::RingChanged(RingSettings { spin, ..ring }))
+      })
+      .step(1.0),
+    )
+    .push(text(format!("Inner radius {:.0}%", ring.inner_radius * 100.0)))
+    .push(
+      slider(INNER_RADIUS_RANGE, ring.inner_radius, move |inner_radius| {
+        Visual(Message::RingChanged(RingSettings { inner_radius, ..ring }))
+      })
+      .step(0.01),
+    )
+    .push(
+      checkbox("Split view", split.enabled)
+        .on_toggle(move |enabled| Visual(Message::SplitChanged(SplitView { enabled, ..split }))),
+    )
+    .push_maybe(split.enabled.then(|| {
+      column![
+        pick_list(SplitDirection::ALL, Some(split.direction), move |direction| {
+          Visual(Message::SplitChanged(SplitView { direction, ..split }))
+        }),
+        text("Second style"),
+        pick_list(VisualStyle::ALL, Some(split.style), move |style| {
+          Visual(Message::SplitChanged(SplitView { style, ..split }))
+        }),
+        text("Second theme"),
+        pick_list(ColorTheme::ALL, Some(split.theme), move |theme| {
+          Visual(Message::SplitChanged(SplitView { theme, ..split }))
+        }),
+        text(format!("First pane {:.0}%", split.ratio * 100.0)),
+        slider(SPLIT_RATIO_RANGE, split.ratio, move |ratio| {
+          Visual(Message::SplitChanged(SplitView { ratio, ..split }))
+        })
+        .step(0.05),
+      ]
+      .spacing(10)
+    }))
+    .push(text("Effects over the bars"))
+    .push(text(format!("Bloom {:.0}%", effects.bloom * 100.0)))
+    .push(
+      slider(EFFECT_RANGE, effects.bloom, move |bloom| {
+        Visual(Message::EffectsChanged(Effects { bloom, ..effects }))
+      })
+      .step(0.05),
+    )
+    .push(text(format!("Trails {:.0}%", effects.trails * 100.0)))
+    .push(
+      slider(EFFECT_RANGE, effects.trails, move |trails| {
+        Visual(Message::EffectsChanged(Effects { trails, ..effects }))
+      })
+      .step(0.05),
+    )
+    .push(text(format!("Aberration {:.0}%", effects.aberration * 100.0)))
+    .push(
+      slider(EFFECT_RANGE, effects.aberration, move |aberration| {
+        Visual(Message::EffectsChanged(Effects { aberration, ..effects }))
+      })
+      .step(0.05),
+    )
+    .push(text("Colour grading"))
+    .push(text(format!("Brightness {:+.2}", grade.brightness)))
+    .push(
+      slider(BRIGHTNESS_RANGE, grade.brightness, move |brightness| {
+        Visual(Message::GradeChanged(ColorGrade { brightness, ..grade }))
+      })
+      .step(0.01),
+    )
+    .push(text(format!("Contrast {:.2}", grade.contrast)))
+    .push(
+      slider(CONTRAST_RANGE, grade.contrast, move |contrast| {
+        Visual(Message::GradeChanged(ColorGrade { contrast, ..grade }))
+      })
+      .step(0.01),
+    )
+    .push(text(format!("Saturation {:.2}", grade.saturation)))
+    .push(
+      slider(SATURATION_RANGE, grade.saturation, move |saturation| {
+        Visual(Message::GradeChanged(ColorGrade { saturation, ..grade }))
+      })
+      .step(0.01),
+    )
+    .push(text(format!("Gamma {:.2}", grade.gamma)))
+    .push(
+      slider(GAMMA_RANGE, grade.gamma, move |gamma| {
+        Visual(Message::GradeChanged(ColorGrade { gamma, ..grade }))
+      })
+      .step(0.01),
+    )
+    .push(
+      button("Reset grading").on_press_maybe(
+        (grade != ColorGrade::default())
+          .then_some(Visual(Message::GradeChanged(ColorGrade::default()))),
+      ),
+    )
+    .push(text("Smoothing"))
+    .push(pick_list(Region::ALL, Some(self.smoothing_region), |region| {
+      Visual(Message::SmoothingRegionSelected(region))
+    }))
+    .push(text(format!("Attack {:.0} ms", envelope.attack_ms)))
+    .push(
+      slider(0.0..=MAX_ATTACK_MS, envelope.attack_ms, |attack| {
+        Visual(Message::AttackChanged(attack))
+      })
+      .step(1.0),
+    )
+    .push(text(format!("Release {:.0} ms", envelope.release_ms)))
+    .push(
+      slider(0.0..=MAX_RELEASE_MS, envelope.release_ms, |release| {
+        Visual(Message::ReleaseChanged(release))
+      })
+      .step(5.0),
+    )
+    .push(self.emitter_editor())
+    .push(text("Export quality"))
+    .push(
+      row![
+        text("WAV").width(Length::Fill),
+        pick_list(WavDepth::ALL, Some(encode.wav_depth), move |wav_depth| {
+          Exports(exports::Message::EncodeSettingsChanged(EncodeSettings { wav_depth, ..encode }))
+        }),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(text(format!("FLAC level {}", encode.flac_level)))
+    .push(slider(FLAC_LEVELS, encode.flac_level, move |flac_level| {
+      Exports(exports::Message::EncodeSettingsChanged(EncodeSettings { flac_level, ..encode }))
+    }))
+    .push(
+      row![
+        text("MP3 kbit/s").width(Length::Fill),
+        pick_list(MP3_BITRATES, Some(encode.mp3_bitrate), move |mp3_bitrate| {
+          Exports(exports::Message::EncodeSettingsChanged(EncodeSettings { mp3_bitrate, ..encode }))
+        }),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(
+      row![
+        text("Opus kbit/s").width(Length::Fill),
+        pick_list(OPUS_BITRATES, Some(encode.opus_bitrate), move |opus_bitrate| {
+          Exports(exports::Message::EncodeSettingsChanged(EncodeSettings {
+            opus_bitrate,
+            ..encode
+          }))
+        }),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(text("Graphics (after a restart)"))
+    .push(
+      row![
+        text("Backend").width(Length::Fill),
+        pick_list(RendererBackend::ALL, Some(graphics.backend), move |backend| {
+          Preferences(preferences::Message::GraphicsSettingsChanged(GraphicsSettings {
+            backend,
+            ..graphics
+          }))
+        }),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(
+      row![
+        text("GPU").width(Length::Fill),
+        pick_list(PowerPreference::ALL, Some(graphics.power), move |power| {
+          Preferences(preferences::Message::GraphicsSettingsChanged(GraphicsSettings {
+            power,
+            ..graphics
+          }))
+        }),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(text("OSC output"))
+    .push(checkbox("Send bars and beats", osc.enabled).on_toggle(|enabled| {
+      Outputs(outputs::Message::OscSettingsChanged(OscSettings { enabled, ..osc.clone() }))
+    }))
+    .push(
+      row![
+        text_input("Host", osc_host)
+          .on_input(|host| Outputs(outputs::Message::OscHostEdited(host)))
+          .on_submit(Outputs(outputs::Message::ApplyOscTarget)),
+        text_input("Port", osc_port)
+          .width(80)
+          .on_input(|port| Outputs(outputs::Message::OscPortEdited(port)))
+          .on_submit(Outputs(outputs::Message::ApplyOscTarget)),
+        button("Apply").on_press_maybe(
+          (osc_host != osc.host || osc_port != osc.port.to_string())
+            .then_some(Outputs(outputs::Message::ApplyOscTarget))
+        ),
+      ]
+      .spacing(10),
+    )
+    .push(text_input("Bars address", &osc.bars_address).on_input(|bars_address| {
+      Outputs(outputs::Message::OscSettingsChanged(OscSettings { bars_address, ..osc.clone() }))
+    }))
+    .push(text_input("Beat address", &osc.beat_address).on_input(|beat_address| {
+      Outputs(outputs::Message::OscSettingsChanged(OscSettings { beat_address, ..osc.clone() }))
+    }))
+    .push(text("Shared memory"))
+    .push(checkbox("Share each frame with local apps", shm.enabled).on_toggle(|enabled| {
+      Outputs(outputs::Message::ShmSettingsChanged(ShmSettings { enabled, ..shm.clone() }))
+    }))
+    // Remapped on every change, so the path is only set in the config file
+    .push(text(format!("Mapped at {}", shm.path)))
+    .push(text("MIDI output"))
+    .push(checkbox("Send beats and energy", midi.enabled).on_toggle(|enabled| {
+      Outputs(outputs::Message::MidiSettingsChanged(MidiSettings { enabled, ..midi.clone() }))
+    }))
+    .push(
+      row![
+        pick_list(midi_ports, (!midi.port.is_empty()).then(|| midi.port.clone()), |port| {
+          Outputs(outputs::Message::MidiSettingsChanged(MidiSettings { port, ..midi.clone() }))
+        })
+        .placeholder("First output"),
+        pick_list(midi::CHANNELS.collect::<Vec<u8>>(), Some(midi.channel), |channel| {
+          Outputs(outputs::Message::MidiSettingsChanged(MidiSettings { channel, ..midi.clone() }))
+        }),
+      ]
+      .spacing(10),
+    )
+    .push(
+      row![
+        text("Beat note").width(Length::Fill),
+        text_input("Note", &midi.beat_note.to_string()).width(60).on_input(|input| {
+          let beat_note = midi::parse_data(&input).unwrap_or(midi.beat_note);
+          Outputs(outputs::Message::MidiSettingsChanged(MidiSettings { beat_note, ..midi.clone() }))
+        }),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(
+      row![
+        text("Bass, mid, treble CC").width(Length::Fill),
+        text_input("CC", &midi.bass_cc.to_string()).width(45).on_input(|input| {
+          let bass_cc = midi::parse_data(&input).unwrap_or(midi.bass_cc);
+          Outputs(outputs::Message::MidiSettingsChanged(MidiSettings { bass_cc, ..midi.clone() }))
+        }),
+        text_input("CC", &midi.mid_cc.to_string()).width(45).on_input(|input| {
+          let mid_cc = midi::parse_data(&input).unwrap_or(midi.mid_cc);
+          Outputs(outputs::Message::MidiSettingsChanged(MidiSettings { mid_cc, ..midi.clone() }))
+        }),
+        text_input("CC", &midi.treble_cc.to_string()).width(45).on_input(|input| {
+          let treble_cc = midi::parse_data(&input).unwrap_or(midi.treble_cc);
+          Outputs(outputs::Message::MidiSettingsChanged(MidiSettings { treble_cc, ..midi.clone() }))
+        }),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(text("Controller rumble"))
+    .push(checkbox("Rumble with the bass and beats", rumble.enabled).on_toggle(|enabled| {
+      Outputs(outputs::Message::RumbleSettingsChanged(RumbleSettings { enabled, ..rumble.clone() }))
+    }))
+    .push(
+      row![
+        pick_list(
+          rumble_devices,
+          (!rumble.device.is_empty()).then(|| rumble.device.clone()),
+          |device| Outputs(outputs::Message::RumbleSettingsChanged(RumbleSettings {
+            device,
+            ..rumble.clone()
+          }))
+        )
+        .placeholder("Every controller"),
+        pick_list(RumbleCurve::ALL, Some(rumble.curve), |curve| {
+          Outputs(outputs::Message::RumbleSettingsChanged(RumbleSettings {
+            curve,
+            ..rumble.clone()
+          }))
+        }),
+      ]
+      .spacing(10),
+    )
+    .push(text(format!("Intensity {:.0}%", rumble.intensity * 100.0)))
+    .push(
+      slider(0.0..=1.0, rumble.intensity, |intensity| {
+        Outputs(outputs::Message::RumbleSettingsChanged(RumbleSettings {
+          intensity,
+          ..rumble.clone()
+        }))
+      })
+      .step(0.05),
+    )
+    .push(text("Loudness"))
+    .push(
+      row![
+        checkbox("Normalise", normalise.enabled).on_toggle(move |enabled| {
+          Track(track::Message::NormaliseChanged(NormaliseSettings { enabled, ..normalise }))
+        }),
+        text(format!("Target {:.0} LUFS", normalise.target_lufs)),
+        slider(
+          normalise::MIN_TARGET_LUFS..=normalise::MAX_TARGET_LUFS,
+          normalise.target_lufs,
+          move |target_lufs| {
+            Track(track::Message::NormaliseChanged(NormaliseSettings { target_lufs, ..normalise }))
+          }
+        )
+        .step(1.0)
+        .width(100),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(text("Equalizer"))
+    .push(
+      row![
+        checkbox("Enabled", equalizer.enabled).on_toggle(move |enabled| {
+          Track(track::Message::EqualizerChanged(EqSettings { enabled, ..equalizer }))
+        }),
+        button("Flat").on_press(Track(track::Message::EqualizerChanged(EqSettings {
+          gains_db: EqSettings::default().gains_db,
+          ..equalizer
+        }))),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(EQ_BANDS.iter().enumerate().fold(row![].spacing(6), |bands, (band, &centre)| {
+      let label = if centre >= 1000.0 {
+        format!("{:.0}k", centre / 1000.0)
+      } else {
+        format!("{:.0}", centre)
+      };
+      bands.push(
+        column![
+          vertical_slider(
+            -MAX_EQ_GAIN_DB..=MAX_EQ_GAIN_DB,
+            equalizer.gains_db[band],
+            move |gain| {
+              let mut gains_db = equalizer.gains_db;
+              gains_db[band] = gain;
+              Track(track::Message::EqualizerChanged(EqSettings { gains_db, ..equalizer }))
+            }
+          )
+          .step(0.5)
+          .height(100),
+          text(label).size(11),
+        ]
+        .spacing(4)
+        .align_x(iced::Alignment::Center),
+      )
+    }))
+    .push(text("Phone remote"))
+    .push(
+      row![
+        checkbox("Serve on port", remote.enabled).on_toggle(|enabled| {
+          Outputs(outputs::Message::RemoteSettingsChanged(RemoteSettings {
+            enabled,
+            ..remote.clone()
+          }))
+        }),
+        text_input("Port", remote_port)
+          .width(80)
+          .on_input(|port| Outputs(outputs::Message::RemotePortEdited(port)))
+          .on_submit(Outputs(outputs::Message::ApplyRemotePort)),
+        button("Apply").on_press_maybe(
+          parse_port(remote_port)
+            .is_some_and(|port| port != remote.port)
+            .then_some(Outputs(outputs::Message::ApplyRemotePort)),
+        ),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push(text("Tray icon"))
+    .push(
+      row![
+        checkbox("Show in the tray", tray.enabled).on_toggle(move |enabled| {
+          Outputs(outputs::Message::TraySettingsChanged(TraySettings { enabled, ..tray }))
+        }),
+        checkbox("Close to the tray", tray.close_to_tray).on_toggle_maybe(tray.enabled.then_some(
+          move |close_to_tray| {
+            Outputs(outputs::Message::TraySettingsChanged(TraySettings { close_to_tray, ..tray }))
+          }
+        )),
+      ]
+      .spacing(10),
+    )
+    .push(text("Session"))
+    .push(
+      row![
+        checkbox("Reopen where I left off", session.restore).on_toggle(move |restore| {
+          Preferences(preferences::Message::SessionSettingsChanged(SessionSettings {
+            restore,
+            ..session
+          }))
+        }),
+        checkbox("Keep playing", session.resume_playing).on_toggle_maybe(
+          session.restore.then_some(move |resume_playing| {
+            Preferences(preferences::Message::SessionSettingsChanged(SessionSettings {
+              resume_playing,
+              ..session
+            }))
+          })
+        ),
+      ]
+      .spacing(10),
+    )
+    .push(text("Presets"))
+    .push(
+      row![
+        text_input("Preset name", preset_name)
+          .on_input(|name| Looks(looks::Message::PresetNameChanged(name)))
+          .on_submit(Looks(looks::Message::SavePreset)),
+        button(text("Save")).on_press_maybe(
+          (!preset_name.trim().is_empty()).then_some(Looks(looks::Message::SavePreset))
+        ),
+      ]
+      .spacing(10),
+    )
+    .push(
+      row![
+        pick_list(SuggestMode::ALL, Some(suggest.mode), move |mode| {
+          Looks(looks::Message::SuggestSettingsChanged(SuggestSettings { mode, ..suggest }))
+        }),
+        checkbox("Learn from my picks", suggest.learn).on_toggle(move |learn| {
+          Looks(looks::Message::SuggestSettingsChanged(SuggestSettings { learn, ..suggest }))
+        }),
+      ]
+      .spacing(10)
+      .align_y(iced::Alignment::Center),
+    )
+    .push_maybe(
+      suggested_preset
+        .filter(|_| suggest.mode == SuggestMode::Suggest)
+        .and_then(|index| Some((index, presets.get(index)?)))
+        .map(|(index, preset)| {
+          row![
+            text(format!("Suggested: {}", preset.name)).width(Length::Fill),
+            button(text("Load")).on_press(Looks(looks::Message::LoadPreset(index))),
+          ]
+          .spacing(10)
+          .align_y(iced::Alignment::Center)
+        }),
+    )
+    .push(presets.presets.iter().enumerate().fold(column![].spacing(4), |list, (index, preset)| {
+      // The first nine are on the number keys
+      let label = if index < HOTKEY_PRESETS {
+        format!("{}. {}", index + 1, preset.name)
+      } else {
+        preset.name.clone()
+      };
+      list.push(
+        row![
+          text(label).width(Length::Fill),
+          button(text("Load")).on_press(Looks(looks::Message::LoadPreset(index))),
+          button(text("Delete")).on_press(Looks(looks::Message::DeletePreset(index))),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center),
+      )
+    }))
+    .push(text("Shortcuts"))
+    .push(Action::ALL.into_iter().fold(column![].spacing(4), |shortcuts, action| {
+      let key = if rebinding == Some(action) {
+        "Press a key...".to_string()
+      } else {
+        keymap.key(action).to_string()
+      };
+      shortcuts.push(
+        row![
+          text(action.to_string()).width(Length::Fill),
+          button(text(key)).on_press(Preferences(preferences::Message::RebindKey(action))),
+        ]
+        .spacing(10)
+        .align_y(iced::Alignment::Center),
+      )
+    }))
+    .push(
+      row![
+        button("Save").on_press(Preferences(preferences::Message::SaveConfig)),
+        button("Reset").on_press(Preferences(preferences::Message::ResetConfig)),
+      ]
+      .spacing(10),
+    )
+    .spacing(8);
+
+    scrollable(content).width(260).into()
+  }
+}
+
+impl Default for VisualSettings {
+  fn default() -> Self {
+    Self {
+      style: VisualStyle::default(),
+      layout: LayoutKind::default(),
+      ring: RingSettings::default(),
+      custom_path: None,
+      layout_text: DEFAULT_LAYOUT_TEXT.to_string(),
+      font_data: None,
+      text_error: None,
+      text_shape: None,
+      mask: None,
+      frequency_scale: FrequencyScale::default(),
+      waveform_window_ms: DEFAULT_WAVEFORM_WINDOW_MS,
+      colormap: Colormap::default(),
+      spectrogram_length: DEFAULT_SPECTROGRAM_LENGTH,
+      noise: Perlin::new(DEFAULT_NOISE_SEED),
+      noise_intensity: 0.0,
+      noise_seed_input: DEFAULT_NOISE_SEED.to_string(),
+      gradient: Gradient::default(),
+      custom_start_input: DEFAULT_CUSTOM_START.to_string(),
+      custom_end_input: DEFAULT_CUSTOM_END.to_string(),
+      track_palette: None,
+      lock_theme: false,
+      smoothing: RegionSmoothing::default(),
+      smoothing_region: Region::default(),
+      bar_count: DEFAULT_NUM_BARS,
+      decibels: DecibelRange::default(),
+      auto_range: false,
+      frame_rate: FrameRate::default(),
+      update_interval: DEFAULT_UPDATE_INTERVAL,
+      background_binding: EnergyBinding::default(),
+      pulse_binding: EnergyBinding::default(),
+      tempo_lock: false,
+      backdrop_source: BackdropSource::default(),
+      image_backdrop: None,
+      track_backdrop: None,
+      backdrop_blur: DEFAULT_BACKDROP_BLUR,
+      backdrop_darken: DEFAULT_BACKDROP_DARKEN,
+      emitters: Vec::new(),
+      selected_emitter: 0,
+      split: SplitView::default(),
+      effects: Effects::default(),
+    }
+  }
+}
@@ -0,0 +1,141 @@
+//! Analysis for hosts that bring their own audio, like a game's mixer: push
+//! interleaved samples in and get [`AnalysisFrame`]s back, without rodio or
+//! the app's window.
+//!
+//! ```no_run
+//! use rust_audio_visualiser::watch::{AnalysisSettings, Watch};
+//!
+//! let settings = AnalysisSettings { channels: 2, sample_rate: 48000, ..Default::default() };
+//! let mut watch = Watch::new(settings);
+//! watch.on_frame(|frame| println!("beat: {:?}", frame.beat));
+//! # let mixed: Vec<f32> = Vec::new();
+//! watch.push(&mixed);
+//! ```
+
+use iced::{
+  Color, Element, Length, Point, Rectangle, Size, Theme, mouse,
+  widget::canvas::{self, Canvas, Geometry},
+};
+
+use crate::analysis::{Analyser, StreamBuffers};
+use crate::components::{binning::FrequencyScale, gradient::Gradient};
+
+pub use crate::analysis::{AnalysisFrame, AnalysisSettings, DecibelRange, FFT_SIZES};
+pub use crate::components::{
+  channels::ChannelMode, weighting::Weighting, window_fn::WindowFunction,
+};
+
+type Callback = Box<dyn FnMut(&AnalysisFrame) + Send>;
+
+/// The analyser fed by hand. Frames come out every quarter of an FFT window
+/// of audio pushed in, overlapping like the app's own.
+pub struct Watch {
+  analyser: Analyser,
+  streams: StreamBuffers,
+  callbacks: Vec<Callback>,
+  latest: Option<AnalysisFrame>,
+}
+
+impl Watch {
+  /// `settings.channels` and `settings.sample_rate` have to match the
+  /// samples that will be pushed.
+  pub fn new(settings: AnalysisSettings) -> Self {
+    Self {
+      analyser: Analyser::new(settings),
+      streams: StreamBuffers::new(settings),
+      callbacks: Vec::new(),
+      latest: None,
+    }
+  }
+
+  pub fn settings(&self) -> AnalysisSettings {
+    self.analyser.settings()
+  }
+
+  /// Switches to new settings, such as another FFT size or the host's
+  /// output changing sample rate, from the next frame on.
+  pub fn configure(&mut self, settings: AnalysisSettings) {
+    self.streams.configure(self.analyser.settings(), settings);
+    self.analyser.configure(settings);
+  }
+
+  /// Calls `callback` with every frame from now on, on whichever thread
+  /// pushes the samples.
+  pub fn on_frame(&mut self, callback: impl FnMut(&AnalysisFrame) + Send + 'static) {
+    self.callbacks.push(Box::new(callback));
+  }
+
+  /// Takes in interleaved samples, analysing every frame they complete.
+  /// Returns how many frames that was.
+  pub fn push(&mut self, samples: &[f32]) -> usize {
+    self.streams.push(samples, self.analyser.settings());
+    let mut count = 0;
+    while let Some((frame, _)) = self.streams.next_frame(&mut self.analyser) {
+      for callback in &mut self.callbacks {
+        callback(&frame);
+      }
+      self.latest = Some(frame);
+      count += 1;
+    }
+    count
+  }
+
+  /// The most recent frame, for hosts that poll instead of taking callbacks.
+  pub fn latest(&self) -> Option<&AnalysisFrame> {
+    self.latest.as_ref()
+  }
+
+  /// Bars of the latest frame, for embedding in a host's own iced UI.
+  pub fn widget<'a, M: 'a>(&'a self, bars: usize) -> Element<'a, M> {
+    Canvas::new(WatchBars {
+      frame: self.latest.as_ref(),
+      sample_rate: self.analyser.settings().sample_rate,
+      bars: bars.max(1),
+      decibels: DecibelRange::default(),
+      gradient: Gradient::default(),
+    })
+    .width(Length::Fill)
+    .height(Length::Fill)
+    .into()
+  }
+}
+
+/// Plain bars along the bottom, log-spaced and coloured by level.
+struct WatchBars<'a> {
+  frame: Option<&'a AnalysisFrame>,
+  sample_rate: u32,
+  bars: usize,
+  decibels: DecibelRange,
+  gradient: Gradient,
+}
+
+impl<'a, M> canvas::Program<M> for WatchBars<'a> {
+  type State = ();
+
+  fn draw(
+    &self,
+    _state: &Self::State,
+    renderer: &iced::Renderer,
+    _theme: &Theme,
+    bounds: Rectangle,
+    _cursor: mouse::Cursor,
+  ) -> Vec<Geometry> {
+    let mut frame = canvas::Frame::new(renderer, bounds.size());
+    let Some(analysed) = self.frame else {
+      return vec![frame.into_geometry()];
+    };
+
+    let levels = FrequencyScale::Logarithmic.bin(&analysed.mixed(), self.sample_rate, self.bars);
+    let slot = bounds.width / levels.len().max(1) as f32;
+    for (i, &magnitude) in levels.iter().enumerate() {
+      let level = self.decibels.normalise(magnitude);
+      let height = level * bounds.height;
+      frame.fill_rectangle(
+        Point::new(i as f32 * slot, bounds.height - height),
+        Size::new((slot - 1.0).max(1.0), height),
+        Color { a: 0.9, ..self.gradient.color(level) },
+      );
+    }
+    vec![frame.into_geometry()]
+  }
+}
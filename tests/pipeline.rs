@@ -0,0 +1,157 @@
+use std::time::Duration;
+
+use rust_audio_visualiser::testing::{AnalysisSettings, FakeSource, Pane, Pipeline, Signal, State};
+
+const SAMPLE_RATE: u32 = 44100;
+const BARS: usize = 64;
+
+fn pipeline(signal: Signal, seconds: f32) -> Pipeline {
+  let source = FakeSource::new(signal, 1, SAMPLE_RATE, Duration::from_secs_f32(seconds));
+  Pipeline::new(source, BARS)
+}
+
+/// Ticks for `seconds` of the fake clock, returning the strength of every
+/// beat that came out.
+fn run(pipeline: &mut Pipeline, seconds: f32) -> Vec<f32> {
+  let mut beats = Vec::new();
+  while pipeline.clock().elapsed() < Duration::from_secs_f32(seconds) {
+    if let Some(beat) = pipeline.tick().and_then(|frame| frame.beat) {
+      beats.push(beat);
+    }
+  }
+  beats
+}
+
+/// Index of the loudest bar in the first half; a mono spectrum mirrors onto
+/// the second.
+fn loudest_bar(levels: &[f32]) -> usize {
+  let half = &levels[..levels.len() / 2];
+  (0..half.len()).fold(0, |loudest, i| if half[i] > half[loudest] { i } else { loudest })
+}
+
+#[test]
+fn silence_stays_on_the_floor() {
+  let mut pipeline = pipeline(Signal::Silence, 2.0);
+  let beats = run(&mut pipeline, 1.0);
+  assert!(beats.is_empty());
+  assert!(pipeline.levels().iter().all(|&level| level < 1e-3));
+}
+
+#[test]
+fn sine_lights_the_bar_at_its_frequency() {
+  let mut low = pipeline(Signal::Sine { frequency: 200.0, amplitude: 0.5 }, 2.0);
+  let mut high = pipeline(Signal::Sine { frequency: 5000.0, amplitude: 0.5 }, 2.0);
+  run(&mut low, 1.0);
+  run(&mut high, 1.0);
+
+  let (low, high) = (low.levels(), high.levels());
+  assert!(low[loudest_bar(&low)] > 0.5);
+  assert!(high[loudest_bar(&high)] > 0.5);
+  assert!(loudest_bar(&low) < loudest_bar(&high));
+}
+
+#[test]
+fn mono_bars_repeat_on_both_halves() {
+  let mut pipeline = pipeline(Signal::Sine { frequency: 1000.0, amplitude: 0.5 }, 2.0);
+  run(&mut pipeline, 1.0);
+  let levels = pipeline.levels();
+  let (left, right) = levels.split_at(BARS / 2);
+  assert!(left.iter().eq(right.iter().rev()));
+}
+
+#[test]
+fn odd_bar_counts_mirror_too() {
+  // The default count, where the top band sits alone in the middle
+  let source = FakeSource::new(
+    Signal::Sine { frequency: 1000.0, amplitude: 0.5 },
+    1,
+    SAMPLE_RATE,
+    Duration::from_secs(2),
+  );
+  let mut pipeline = Pipeline::new(source, 75);
+  run(&mut pipeline, 1.0);
+  let levels = pipeline.levels();
+  assert_eq!(levels.len(), 75);
+  let (left, right) = levels.split_at(38);
+  assert!(left[..37].iter().eq(right.iter().rev()));
+}
+
+#[test]
+fn same_source_gives_same_bars() {
+  let signal = Signal::Clicks { every: Duration::from_millis(250), amplitude: 0.8 };
+  let mut first = pipeline(signal, 2.0);
+  let mut second = pipeline(signal, 2.0);
+  assert_eq!(run(&mut first, 1.5), run(&mut second, 1.5));
+  assert_eq!(first.levels(), second.levels());
+}
+
+#[test]
+fn clicks_are_heard_as_beats() {
+  let signal = Signal::Clicks { every: Duration::from_millis(500), amplitude: 0.8 };
+  let mut pipeline = pipeline(signal, 4.0);
+  let beats = run(&mut pipeline, 3.0);
+  // Six clicks in three seconds, give or take the one at the very start
+  assert!(beats.len().abs_diff(6) <= 1, "{} beats", beats.len());
+  assert!(beats.iter().all(|beat| (0.0..=1.0).contains(beat)));
+}
+
+#[test]
+fn bars_fall_back_once_the_source_runs_out() {
+  let mut pipeline = pipeline(Signal::Sine { frequency: 1000.0, amplitude: 0.5 }, 0.5);
+  run(&mut pipeline, 0.5);
+  assert!(pipeline.levels().iter().any(|&level| level > 0.0));
+
+  let mut ticks = 0;
+  while pipeline.state() != State::Idle {
+    assert!(ticks < 1000, "still {:?} after {} ticks", pipeline.state(), ticks);
+    pipeline.tick();
+    ticks += 1;
+  }
+  assert!(pipeline.levels().iter().all(|&level| level == 0.0));
+  assert!(pipeline.tick().is_none());
+}
+
+#[test]
+fn latency_holds_frames_back() {
+  let latency = Duration::from_millis(200);
+  let first_frame = |mut pipeline: Pipeline| {
+    while pipeline.tick().is_none() {
+      assert!(pipeline.clock().elapsed() < Duration::from_secs(1));
+    }
+    pipeline.clock().elapsed()
+  };
+
+  let signal = Signal::Sine { frequency: 1000.0, amplitude: 0.5 };
+  let prompt = first_frame(pipeline(signal, 2.0));
+  let delayed = first_frame(pipeline(signal, 2.0).with_latency(latency));
+  assert!(prompt < latency);
+  assert!(delayed >= latency);
+}
+
+#[test]
+fn larger_fft_still_finds_the_sine() {
+  let mut pipeline = pipeline(Signal::Sine { frequency: 200.0, amplitude: 0.5 }, 3.0);
+  run(&mut pipeline, 0.5);
+  let before = loudest_bar(&pipeline.levels());
+
+  pipeline.configure(AnalysisSettings { fft_size: 8192, ..Default::default() });
+  run(&mut pipeline, 2.0);
+  // Finer bins can tip the peak into the neighbouring band, but no further
+  assert!(loudest_bar(&pipeline.levels()).abs_diff(before) <= 1);
+}
+
+#[test]
+fn split_panes_keep_their_own_effects() {
+  let mut pipeline = pipeline(Signal::Sine { frequency: 1000.0, amplitude: 0.5 }, 2.0);
+  run(&mut pipeline, 0.5);
+  // Both are prepared before either renders, so sharing buffers would
+  // draw the second pane's bars in both
+  assert_eq!(pipeline.split_effects(), [Pane::Main, Pane::Split]);
+}
+
+#[test]
+fn split_panes_keep_their_own_terrain() {
+  let mut pipeline = pipeline(Signal::Sine { frequency: 1000.0, amplitude: 0.5 }, 2.0);
+  run(&mut pipeline, 0.5);
+  assert_eq!(pipeline.split_terrain(), [Pane::Main, Pane::Split]);
+}